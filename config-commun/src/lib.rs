@@ -0,0 +1,206 @@
+// src/lib.rs
+// Chargement de la configuration des services des TP : valeurs par défaut, fichier TOML, variables
+// d'environnement puis options de la ligne de commande, chaque source l'emportant sur la précédente
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use serde::de::DeserializeOwned;
+
+/// Configuration d'un service. Les clés absentes du fichier gardent leur valeur par défaut
+/// (`#[serde(default)]` sur le type)
+pub trait Settings: Default + DeserializeOwned {
+    /// Préfixe des variables d'environnement : avec `SCP_`, `SCP_CHAT_RATE` vaut `--chat-rate`
+    /// et `SCP_CONFIG` donne le fichier de configuration, comme `--config`
+    const ENV_PREFIX: &'static str;
+
+    /// Surcharger un réglage ; `key` est le nom de l'option sans `--`
+    fn set(&mut self, key: &str, value: &str) -> Result<(), String>;
+
+    /// Vérifier la cohérence des réglages une fois toutes les sources appliquées
+    fn validate(&mut self) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+/// Erreur de chargement, avec la source fautive
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigError {
+    /// Fichier illisible ou qui n'est pas une configuration valide
+    File { path: PathBuf, message: String },
+    /// Valeur refusée ; `origin` est la variable ou l'option qui l'a donnée (`SCP_CHAT_RATE`, `--chat-rate`)
+    Value { origin: String, message: String },
+    /// Ligne de commande mal formée
+    Argument(String),
+    /// Réglages incohérents entre eux
+    Invalid(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::File { path, message } => write!(f, "Configuration {} invalide: {}", path.display(), message),
+            ConfigError::Value { origin, message } => write!(f, "{} ({})", message, origin),
+            ConfigError::Argument(message) | ConfigError::Invalid(message) => f.write_str(message),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// Configuration du programme, d'après ses arguments et son environnement
+pub fn load<T: Settings>() -> Result<T, ConfigError> {
+    load_with(std::env::args().skip(1), std::env::vars())
+}
+
+/// Configuration d'après `args` (`--config <fichier>` et des paires `--<option> <valeur>`) et `env`
+pub fn load_with<T: Settings>(
+    args: impl IntoIterator<Item = String>,
+    env: impl IntoIterator<Item = (String, String)>,
+) -> Result<T, ConfigError> {
+    let mut file = None;
+    let mut options = Vec::new();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let key = arg.strip_prefix("--").ok_or_else(|| ConfigError::Argument(format!("Argument inattendu: {}", arg)))?;
+        // `--option=valeur` ou `--option valeur`
+        let (key, value) = match key.split_once('=') {
+            Some((key, value)) => (key.to_string(), value.to_string()),
+            None => {
+                let value = args.next().ok_or_else(|| ConfigError::Argument(format!("{} requiert une valeur", arg)))?;
+                (key.to_string(), value)
+            }
+        };
+        match key.as_str() {
+            "config" => file = Some(PathBuf::from(value)),
+            _ => options.push((key, value)),
+        }
+    }
+    merge(file.as_deref(), env, options)
+}
+
+/// Configuration finale : défauts, puis `file` (ou celui de la variable `<préfixe>CONFIG`), puis les variables
+/// de `env` qui commencent par le préfixe, puis `options` (nom sans `--`, valeur) ; validée
+pub fn merge<T: Settings>(
+    file: Option<&Path>,
+    env: impl IntoIterator<Item = (String, String)>,
+    options: impl IntoIterator<Item = (String, String)>,
+) -> Result<T, ConfigError> {
+    let config_var = format!("{}CONFIG", T::ENV_PREFIX);
+    let mut env: Vec<(String, String)> = env.into_iter().filter(|(name, _)| name.starts_with(T::ENV_PREFIX)).collect();
+    env.sort(); // Ordre stable, quel que soit celui du système
+    let env_file = env.iter().find(|(name, _)| *name == config_var).map(|(_, value)| PathBuf::from(value));
+
+    let mut config = match file.or(env_file.as_deref()) {
+        Some(path) => from_file(path)?,
+        None => T::default(),
+    };
+    for (name, value) in env.iter().filter(|(name, _)| *name != config_var) {
+        let key = name[T::ENV_PREFIX.len()..].to_lowercase().replace('_', "-");
+        config.set(&key, value).map_err(|message| ConfigError::Value { origin: name.clone(), message })?;
+    }
+    for (key, value) in options {
+        config.set(&key, &value).map_err(|message| ConfigError::Value { origin: format!("--{}", key), message })?;
+    }
+    config.validate().map_err(ConfigError::Invalid)?;
+    Ok(config)
+}
+
+/// Lire un fichier de configuration TOML, sans le valider
+pub fn from_file<T: DeserializeOwned>(path: &Path) -> Result<T, ConfigError> {
+    let file_error = |message: String| ConfigError::File { path: path.to_path_buf(), message };
+    let text = std::fs::read_to_string(path).map_err(|e| file_error(format!("lecture impossible: {}", e)))?;
+    toml::from_str(&text).map_err(|e| file_error(e.to_string()))
+}
+
+/// Valeur d'une option, pour les `Settings::set`
+pub fn parse<T: FromStr>(key: &str, value: &str) -> Result<T, String> {
+    value.trim().parse().map_err(|_| format!("Valeur invalide pour {}: {}", key, value))
+}
+
+/// Éléments non vides d'une liste séparée par des virgules
+pub fn list(value: &str) -> impl Iterator<Item = String> + '_ {
+    value.split(',').map(str::trim).filter(|item| !item.is_empty()).map(String::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    #[serde(default, deny_unknown_fields)]
+    struct Exemple {
+        port: u16,
+        nom: String,
+        salons: Vec<String>,
+    }
+
+    impl Default for Exemple {
+        fn default() -> Self {
+            Self { port: 80, nom: "serveur".to_string(), salons: Vec::new() }
+        }
+    }
+
+    impl Settings for Exemple {
+        const ENV_PREFIX: &'static str = "EX_";
+
+        fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+            match key {
+                "port" => self.port = parse(key, value)?,
+                "nom" => self.nom = value.to_string(),
+                "salon" => self.salons.extend(list(value)),
+                _ => return Err(format!("Option inconnue: {}", key)),
+            }
+            Ok(())
+        }
+
+        fn validate(&mut self) -> Result<(), String> {
+            if self.port == 0 {
+                return Err("port doit être positif".to_string());
+            }
+            Ok(())
+        }
+    }
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    fn vars(list: &[(&str, &str)]) -> Vec<(String, String)> {
+        list.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect()
+    }
+
+    #[test]
+    fn test_sources() {
+        let dir = std::env::temp_dir().join(format!("config-commun-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("exemple.toml");
+        std::fs::write(&path, "port = 8000\nsalons = [\"accueil\"]").unwrap();
+
+        // Fichier, puis environnement, puis ligne de commande
+        let config: Exemple = load_with(
+            args(&["--port=9000", "--salon", "jeux"]),
+            vars(&[("EX_CONFIG", path.to_str().unwrap()), ("EX_PORT", "8500"), ("EX_NOM", "principal"), ("HOME", "/root")]),
+        )
+        .unwrap();
+        assert_eq!(config, Exemple { port: 9000, nom: "principal".to_string(), salons: vec!["accueil".to_string(), "jeux".to_string()] });
+        assert_eq!(load_with::<Exemple>(Vec::new(), Vec::new()), Ok(Exemple::default()));
+
+        // Chaque erreur désigne sa source
+        let error = load_with::<Exemple>(Vec::new(), vars(&[("EX_PORT", "quatre")])).unwrap_err();
+        assert_eq!(error.to_string(), "Valeur invalide pour port: quatre (EX_PORT)");
+        let error = load_with::<Exemple>(args(&["--couleur", "bleu"]), Vec::new()).unwrap_err();
+        assert_eq!(error, ConfigError::Value { origin: "--couleur".to_string(), message: "Option inconnue: couleur".to_string() });
+        assert!(matches!(load_with::<Exemple>(args(&["--port", "0"]), Vec::new()), Err(ConfigError::Invalid(_))));
+        assert!(matches!(load_with::<Exemple>(args(&["port"]), Vec::new()), Err(ConfigError::Argument(_))));
+        assert!(matches!(load_with::<Exemple>(args(&["--port"]), Vec::new()), Err(ConfigError::Argument(_))));
+
+        std::fs::write(&path, "prot = 8000").unwrap();
+        let error = merge::<Exemple>(Some(&path), Vec::new(), Vec::new()).unwrap_err();
+        assert!(matches!(&error, ConfigError::File { message, .. } if message.contains("prot")), "{}", error);
+        assert!(merge::<Exemple>(Some(&dir.join("absent.toml")), Vec::new(), Vec::new()).is_err());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,256 @@
+// src/codec.rs
+// Codecs : extraire une trame complète du début d'un tampon de lecture, et écrire une trame à la suite d'un tampon d'écriture
+
+use std::fmt;
+use std::io;
+use std::marker::PhantomData;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// Taille maximale d'une trame par défaut
+pub const DEFAULT_MAX_LENGTH: usize = 1024 * 1024;
+
+/// Erreur de lecture ou d'écriture d'une trame
+#[derive(Debug)]
+pub enum FrameError {
+    /// Erreur de la connexion (y compris une fermeture au milieu d'une trame)
+    Io(io::Error),
+    /// Trame plus longue que le maximum accepté ; le flux n'est plus synchronisé
+    TooLarge { length: usize, max: usize },
+    /// Trame complète mais illisible (texte non UTF-8, JSON invalide) ; la trame suivante reste lisible
+    Invalid(String),
+}
+
+impl fmt::Display for FrameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FrameError::Io(e) => write!(f, "erreur de connexion: {}", e),
+            FrameError::TooLarge { length, max } => write!(f, "trame trop volumineuse: {} octets (max: {})", length, max),
+            FrameError::Invalid(reason) => write!(f, "trame invalide: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for FrameError {}
+
+impl From<io::Error> for FrameError {
+    fn from(e: io::Error) -> Self {
+        FrameError::Io(e)
+    }
+}
+
+/// Format des trames d'une connexion
+pub trait Codec {
+    type Item;
+
+    /// Retirer la première trame complète de `buffer` ; `None` s'il manque encore des octets
+    fn decode(&mut self, buffer: &mut Vec<u8>) -> Result<Option<Self::Item>, FrameError>;
+
+    /// Le pair a fermé la connexion en laissant `buffer` non vide : par défaut, une trame tronquée
+    fn decode_eof(&mut self, buffer: &mut Vec<u8>) -> Result<Option<Self::Item>, FrameError> {
+        match self.decode(buffer)? {
+            Some(item) => Ok(Some(item)),
+            None => Err(io::Error::from(io::ErrorKind::UnexpectedEof).into()),
+        }
+    }
+
+    /// Ajouter la trame de `item` à la fin de `buffer`
+    fn encode(&mut self, item: &Self::Item, buffer: &mut Vec<u8>) -> Result<(), FrameError>;
+}
+
+/// Lignes de texte terminées par `\n` (un `\r` final est retiré) ; la dernière ligne peut ne pas avoir de fin
+#[derive(Debug, Clone)]
+pub struct LineCodec {
+    max_length: usize,
+}
+
+impl LineCodec {
+    /// Lignes d'au plus `max_length` octets, fin de ligne non comprise
+    pub fn new(max_length: usize) -> Self {
+        Self { max_length }
+    }
+
+    fn take_line(&self, buffer: &mut Vec<u8>, end: usize) -> Result<String, FrameError> {
+        let mut line: Vec<u8> = buffer.drain(..end).collect();
+        if buffer.first() == Some(&b'\n') {
+            buffer.remove(0);
+        }
+        if line.last() == Some(&b'\r') {
+            line.pop();
+        }
+        String::from_utf8(line).map_err(|_| FrameError::Invalid("ligne non UTF-8".to_string()))
+    }
+}
+
+impl Default for LineCodec {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_LENGTH)
+    }
+}
+
+impl Codec for LineCodec {
+    type Item = String;
+
+    fn decode(&mut self, buffer: &mut Vec<u8>) -> Result<Option<String>, FrameError> {
+        match buffer.iter().position(|byte| *byte == b'\n') {
+            Some(end) if end > self.max_length + 1 => Err(FrameError::TooLarge { length: end, max: self.max_length }),
+            Some(end) => self.take_line(buffer, end).map(Some),
+            // Pas de fin de ligne en vue : inutile d'attendre au-delà du maximum
+            None if buffer.len() > self.max_length + 1 => Err(FrameError::TooLarge { length: buffer.len(), max: self.max_length }),
+            None => Ok(None),
+        }
+    }
+
+    fn decode_eof(&mut self, buffer: &mut Vec<u8>) -> Result<Option<String>, FrameError> {
+        if let Some(line) = self.decode(buffer)? {
+            return Ok(Some(line));
+        }
+        let end = buffer.len();
+        self.take_line(buffer, end).map(Some)
+    }
+
+    fn encode(&mut self, item: &String, buffer: &mut Vec<u8>) -> Result<(), FrameError> {
+        if item.contains('\n') {
+            return Err(FrameError::Invalid("fin de ligne au milieu de la ligne".to_string()));
+        }
+        if item.len() > self.max_length {
+            return Err(FrameError::TooLarge { length: item.len(), max: self.max_length });
+        }
+        buffer.extend_from_slice(item.as_bytes());
+        buffer.push(b'\n');
+        Ok(())
+    }
+}
+
+/// Blocs d'octets précédés de leur longueur sur 4 octets (big-endian)
+#[derive(Debug, Clone)]
+pub struct LengthPrefixedCodec {
+    max_length: usize,
+}
+
+impl LengthPrefixedCodec {
+    pub fn new(max_length: usize) -> Self {
+        Self { max_length }
+    }
+
+    pub fn max_length(&self) -> usize {
+        self.max_length
+    }
+}
+
+impl Default for LengthPrefixedCodec {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_LENGTH)
+    }
+}
+
+impl Codec for LengthPrefixedCodec {
+    type Item = Vec<u8>;
+
+    fn decode(&mut self, buffer: &mut Vec<u8>) -> Result<Option<Vec<u8>>, FrameError> {
+        let Some(prefix) = buffer.first_chunk::<4>() else {
+            return Ok(None);
+        };
+        let length = u32::from_be_bytes(*prefix) as usize;
+        // Refusée dès la lecture de la longueur, sans attendre le contenu
+        if length > self.max_length {
+            return Err(FrameError::TooLarge { length, max: self.max_length });
+        }
+        if buffer.len() < 4 + length {
+            return Ok(None);
+        }
+        let frame = buffer[4..4 + length].to_vec();
+        buffer.drain(..4 + length);
+        Ok(Some(frame))
+    }
+
+    fn encode(&mut self, item: &Vec<u8>, buffer: &mut Vec<u8>) -> Result<(), FrameError> {
+        if item.len() > self.max_length {
+            return Err(FrameError::TooLarge { length: item.len(), max: self.max_length });
+        }
+        buffer.extend_from_slice(&(item.len() as u32).to_be_bytes());
+        buffer.extend_from_slice(item);
+        Ok(())
+    }
+}
+
+/// Messages JSON de type `M`, un par bloc précédé de sa longueur
+#[derive(Debug, Clone)]
+pub struct JsonCodec<M> {
+    blocks: LengthPrefixedCodec,
+    message: PhantomData<fn() -> M>,
+}
+
+impl<M> JsonCodec<M> {
+    pub fn new(max_length: usize) -> Self {
+        Self { blocks: LengthPrefixedCodec::new(max_length), message: PhantomData }
+    }
+}
+
+impl<M> Default for JsonCodec<M> {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_LENGTH)
+    }
+}
+
+impl<M: Serialize + DeserializeOwned> Codec for JsonCodec<M> {
+    type Item = M;
+
+    fn decode(&mut self, buffer: &mut Vec<u8>) -> Result<Option<M>, FrameError> {
+        let Some(block) = self.blocks.decode(buffer)? else {
+            return Ok(None);
+        };
+        serde_json::from_slice(&block).map(Some).map_err(|e| FrameError::Invalid(e.to_string()))
+    }
+
+    fn encode(&mut self, item: &M, buffer: &mut Vec<u8>) -> Result<(), FrameError> {
+        let block = serde_json::to_vec(item).map_err(|e| FrameError::Invalid(e.to_string()))?;
+        self.blocks.encode(&block, buffer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_line_codec() {
+        let mut codec = LineCodec::new(8);
+        let mut buffer = b"un\r\ndeux\ntr".to_vec();
+        assert_eq!(codec.decode(&mut buffer).unwrap(), Some("un".to_string()));
+        assert_eq!(codec.decode(&mut buffer).unwrap(), Some("deux".to_string()));
+        assert_eq!(codec.decode(&mut buffer).unwrap(), None);
+        assert_eq!(codec.decode_eof(&mut buffer).unwrap(), Some("tr".to_string()));
+        assert!(buffer.is_empty());
+
+        let mut long = b"beaucoup trop long".to_vec();
+        assert!(matches!(codec.decode(&mut long), Err(FrameError::TooLarge { max: 8, .. })));
+        let mut invalid = vec![0xff, b'\n'];
+        assert!(matches!(codec.decode(&mut invalid), Err(FrameError::Invalid(_))));
+
+        let mut encoded = Vec::new();
+        codec.encode(&"salut".to_string(), &mut encoded).unwrap();
+        assert_eq!(encoded, b"salut\n");
+        assert!(codec.encode(&"a\nb".to_string(), &mut encoded).is_err());
+    }
+
+    #[test]
+    fn test_length_prefixed_codec() {
+        let mut codec = LengthPrefixedCodec::new(16);
+        let mut buffer = Vec::new();
+        codec.encode(&b"abc".to_vec(), &mut buffer).unwrap();
+        assert_eq!(buffer, [0, 0, 0, 3, b'a', b'b', b'c']);
+
+        // Trame incomplète : rien n'est consommé
+        let mut partial = buffer[..5].to_vec();
+        assert_eq!(codec.decode(&mut partial).unwrap(), None);
+        assert_eq!(partial.len(), 5);
+        assert!(codec.decode_eof(&mut partial).is_err());
+        assert_eq!(codec.decode(&mut buffer).unwrap(), Some(b"abc".to_vec()));
+
+        let mut oversized = 17u32.to_be_bytes().to_vec();
+        assert!(matches!(codec.decode(&mut oversized), Err(FrameError::TooLarge { length: 17, max: 16 })));
+        assert!(codec.encode(&vec![0; 17], &mut buffer).is_err());
+    }
+}
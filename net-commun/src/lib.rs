@@ -0,0 +1,9 @@
+// src/lib.rs
+// Découpage des flux réseau en trames, commun aux TP : lignes de texte, blocs précédés de leur
+// longueur, ou messages JSON. Un `FramedTransport` lit et écrit les trames d'un codec sur une connexion
+
+pub mod codec;
+pub mod transport;
+
+pub use codec::{Codec, FrameError, JsonCodec, LengthPrefixedCodec, LineCodec};
+pub use transport::{read_length_prefixed, write_length_prefixed, FramedTransport};
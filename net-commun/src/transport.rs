@@ -0,0 +1,182 @@
+// src/transport.rs
+// Connexion découpée en trames par un codec, et lecture/écriture directes de blocs précédés de leur longueur
+
+use std::io;
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::codec::{Codec, FrameError};
+
+/// Connexion qui lit et écrit des trames du codec `C`. Les octets reçus au-delà d'une trame restent dans
+/// le tampon : `read_frame` peut être abandonné (dans un `select!`) sans rien perdre
+pub struct FramedTransport<T, C> {
+    io: T,
+    codec: C,
+    read_buffer: Vec<u8>,
+    write_buffer: Vec<u8>,
+}
+
+impl<T, C: Codec> FramedTransport<T, C> {
+    pub fn new(io: T, codec: C) -> Self {
+        Self { io, codec, read_buffer: Vec::new(), write_buffer: Vec::new() }
+    }
+
+    pub fn get_ref(&self) -> &T {
+        &self.io
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.io
+    }
+
+    /// Rendre la connexion ; les octets déjà lus et pas encore décodés sont perdus
+    pub fn into_inner(self) -> T {
+        self.io
+    }
+
+    /// Continuer avec un autre codec (protocole négocié en début de connexion) ; les octets déjà lus
+    /// sont décodés par le nouveau codec
+    pub fn with_codec<D: Codec>(self, codec: D) -> FramedTransport<T, D> {
+        FramedTransport { io: self.io, codec, read_buffer: self.read_buffer, write_buffer: self.write_buffer }
+    }
+}
+
+impl<T: AsyncRead + Unpin, C: Codec> FramedTransport<T, C> {
+    /// Trame suivante ; `Ok(None)` si le pair a fermé proprement la connexion entre deux trames
+    pub async fn read_frame(&mut self) -> Result<Option<C::Item>, FrameError> {
+        loop {
+            if let Some(item) = self.codec.decode(&mut self.read_buffer)? {
+                return Ok(Some(item));
+            }
+            if self.io.read_buf(&mut self.read_buffer).await? == 0 {
+                if self.read_buffer.is_empty() {
+                    return Ok(None);
+                }
+                return self.codec.decode_eof(&mut self.read_buffer);
+            }
+        }
+    }
+}
+
+impl<T: AsyncWrite + Unpin, C: Codec> FramedTransport<T, C> {
+    /// Écrire une trame en une seule écriture, puis vider le tampon de la connexion
+    pub async fn write_frame(&mut self, item: &C::Item) -> Result<(), FrameError> {
+        self.write_buffer.clear();
+        self.codec.encode(item, &mut self.write_buffer)?;
+        self.io.write_all(&self.write_buffer).await?;
+        self.io.flush().await?;
+        Ok(())
+    }
+}
+
+/// Lire un bloc précédé de sa longueur (4 octets big-endian) directement sur `reader`, sans tampon propre ;
+/// `Ok(None)` si le pair a fermé la connexion entre deux blocs. Un bloc de plus de `max` octets est refusé
+/// dès la lecture de sa longueur
+pub async fn read_length_prefixed<R: AsyncRead + Unpin>(reader: &mut R, max: usize) -> Result<Option<Vec<u8>>, FrameError> {
+    let mut length_buf = [0u8; 4];
+    match reader.read_exact(&mut length_buf).await {
+        Ok(_) => {}
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e.into()),
+    }
+    let length = u32::from_be_bytes(length_buf) as usize;
+    if length > max {
+        return Err(FrameError::TooLarge { length, max });
+    }
+    let mut block = vec![0u8; length];
+    reader.read_exact(&mut block).await?;
+    Ok(Some(block))
+}
+
+/// Écrire un bloc précédé de sa longueur, en une seule écriture
+pub async fn write_length_prefixed<W: AsyncWrite + Unpin>(writer: &mut W, block: &[u8]) -> io::Result<()> {
+    let mut packet = Vec::with_capacity(4 + block.len());
+    packet.extend_from_slice(&(block.len() as u32).to_be_bytes());
+    packet.extend_from_slice(block);
+    writer.write_all(&packet).await?;
+    writer.flush().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec::{JsonCodec, LengthPrefixedCodec, LineCodec};
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Note {
+        auteur: String,
+        texte: String,
+    }
+
+    #[tokio::test]
+    async fn test_partial_reads() {
+        // Un tampon de 3 octets : chaque trame arrive en plusieurs morceaux
+        let (client, server) = tokio::io::duplex(3);
+        let mut server = FramedTransport::new(server, JsonCodec::<Note>::default());
+        let writer = tokio::spawn(async move {
+            let mut client = FramedTransport::new(client, JsonCodec::<Note>::default());
+            for texte in ["bonjour", "au revoir"] {
+                client.write_frame(&Note { auteur: "alice".to_string(), texte: texte.to_string() }).await.unwrap();
+            }
+        });
+        assert_eq!(server.read_frame().await.unwrap().unwrap().texte, "bonjour");
+        assert_eq!(server.read_frame().await.unwrap().unwrap().texte, "au revoir");
+        writer.await.unwrap();
+        assert!(server.read_frame().await.unwrap().is_none());
+
+        let (mut client, server) = tokio::io::duplex(2);
+        let mut server = FramedTransport::new(server, LineCodec::default());
+        tokio::spawn(async move { client.write_all(b"une ligne\r\nla derniere").await.unwrap() });
+        assert_eq!(server.read_frame().await.unwrap(), Some("une ligne".to_string()));
+        assert_eq!(server.read_frame().await.unwrap(), Some("la derniere".to_string()));
+        assert_eq!(server.read_frame().await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_oversized_and_truncated() {
+        let (mut client, server) = tokio::io::duplex(64);
+        let mut server = FramedTransport::new(server, LengthPrefixedCodec::new(8));
+        client.write_all(&9u32.to_be_bytes()).await.unwrap();
+        assert!(matches!(server.read_frame().await, Err(FrameError::TooLarge { length: 9, max: 8 })));
+
+        let (mut client, server) = tokio::io::duplex(64);
+        let mut server = FramedTransport::new(server, LineCodec::new(4));
+        client.write_all(b"bien\ntrop longue\n").await.unwrap();
+        assert_eq!(server.read_frame().await.unwrap(), Some("bien".to_string()));
+        assert!(matches!(server.read_frame().await, Err(FrameError::TooLarge { max: 4, .. })));
+
+        // Fermeture au milieu d'un bloc
+        let (mut client, server) = tokio::io::duplex(64);
+        let mut server = FramedTransport::new(server, LengthPrefixedCodec::new(8));
+        client.write_all(&[0, 0, 0, 4, b'a']).await.unwrap();
+        drop(client);
+        assert!(matches!(server.read_frame().await, Err(FrameError::Io(e)) if e.kind() == io::ErrorKind::UnexpectedEof));
+    }
+
+    #[tokio::test]
+    async fn test_codec_switch() {
+        // La ligne de négociation et le premier bloc arrivent ensemble
+        let (mut client, server) = tokio::io::duplex(64);
+        client.write_all(b"MODE blocs\n\0\0\0\x03abc").await.unwrap();
+        let mut server = FramedTransport::new(server, LineCodec::default());
+        assert_eq!(server.read_frame().await.unwrap(), Some("MODE blocs".to_string()));
+        let mut server = server.with_codec(LengthPrefixedCodec::new(8));
+        assert_eq!(server.read_frame().await.unwrap(), Some(b"abc".to_vec()));
+    }
+
+    #[tokio::test]
+    async fn test_direct_blocks() {
+        let (mut client, mut server) = tokio::io::duplex(4);
+        let writer = tokio::spawn(async move {
+            write_length_prefixed(&mut client, b"direct").await.unwrap();
+        });
+        assert_eq!(read_length_prefixed(&mut server, 16).await.unwrap(), Some(b"direct".to_vec()));
+        writer.await.unwrap();
+        assert_eq!(read_length_prefixed(&mut server, 16).await.unwrap(), None);
+
+        let (mut client, mut server) = tokio::io::duplex(64);
+        client.write_all(&100u32.to_be_bytes()).await.unwrap();
+        assert!(matches!(read_length_prefixed(&mut server, 16).await, Err(FrameError::TooLarge { length: 100, max: 16 })));
+    }
+}
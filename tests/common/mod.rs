@@ -0,0 +1,134 @@
+// tests/common/mod.rs
+// Outils communs aux tests de bout en bout : compiler le programme d'un TP, le lancer sur un port libre
+// dans un répertoire temporaire, attendre qu'il écoute, et l'arrêter à la fin du test
+
+#![allow(dead_code)] // Chaque fichier de tests n'utilise qu'une partie des outils
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::net::{SocketAddr, TcpListener, TcpStream, UdpSocket};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::{Mutex, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Attente maximale d'un serveur qui démarre, ou d'un résultat observable
+pub const DELAI: Duration = Duration::from_secs(20);
+
+/// Préfixes des variables qui changeraient la configuration des services lancés
+const VARIABLES_IGNOREES: [&str; 7] = ["TP3_", "TP7_", "TP9_", "SCP_", "LOG_SERVER", "LOG_FORMAT", "RUST_LOG"];
+
+/// Chemin du programme `nom` du TP `projet`, compilé au premier appel. Chaque TP a son répertoire de
+/// compilation : plusieurs s'appellent `serveur`, et celui de ces tests est verrouillé par cargo
+pub fn programme(projet: &str, nom: &str) -> PathBuf {
+    static COMPILES: OnceLock<Mutex<HashMap<(String, String), PathBuf>>> = OnceLock::new();
+    let mut compiles = COMPILES.get_or_init(Default::default).lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(chemin) = compiles.get(&(projet.to_string(), nom.to_string())) {
+        return chemin.clone();
+    }
+
+    let cible = Path::new(env!("CARGO_TARGET_TMPDIR")).join(format!("e2e-{}", projet));
+    let manifeste = Path::new(env!("CARGO_MANIFEST_DIR")).join(projet).join("Cargo.toml");
+    let cargo = std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_string());
+    let statut = Command::new(cargo)
+        .args(["build", "--quiet", "--bin", nom, "--manifest-path"])
+        .arg(&manifeste)
+        .arg("--target-dir")
+        .arg(&cible)
+        .status()
+        .expect("lancement de cargo");
+    assert!(statut.success(), "compilation de {} ({}) impossible", nom, projet);
+
+    let chemin = cible.join("debug").join(format!("{}{}", nom, std::env::consts::EXE_SUFFIX));
+    compiles.insert((projet.to_string(), nom.to_string()), chemin.clone());
+    chemin
+}
+
+/// Port libre en TCP et en UDP, sur la boucle locale
+pub fn port_libre() -> u16 {
+    loop {
+        let tcp = TcpListener::bind("127.0.0.1:0").expect("port TCP");
+        let port = tcp.local_addr().unwrap().port();
+        if UdpSocket::bind(("127.0.0.1", port)).is_ok() {
+            return port;
+        }
+    }
+}
+
+/// Répertoire vide propre au test
+pub fn repertoire(nom: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("projetrust-e2e-{}-{}", nom, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).expect("répertoire temporaire");
+    dir
+}
+
+/// Attendre que `condition` soit vraie, au plus `DELAI`
+pub fn attendre(description: &str, mut condition: impl FnMut() -> bool) {
+    let debut = Instant::now();
+    while !condition() {
+        assert!(debut.elapsed() < DELAI, "délai dépassé : {}", description);
+        thread::sleep(Duration::from_millis(50));
+    }
+}
+
+/// Programme d'un TP lancé pour un test, arrêté à la fin de celui-ci. Sa sortie est gardée dans son
+/// répertoire (`sortie.txt`) et affichée si le test échoue ; le répertoire est supprimé sinon
+pub struct Service {
+    nom: String,
+    enfant: Child,
+    pub dir: PathBuf,
+}
+
+impl Service {
+    /// Lancer `nom` du TP `projet` dans le répertoire `dir`, avec `args` et les variables `env`
+    pub fn lancer(projet: &str, nom: &str, dir: &Path, args: &[&str], env: &[(&str, &str)]) -> Self {
+        let programme = programme(projet, nom);
+        let sortie = File::create(dir.join("sortie.txt")).expect("fichier de sortie");
+        let mut commande = Command::new(programme);
+        commande
+            .args(args)
+            .current_dir(dir)
+            .stdin(Stdio::null())
+            .stdout(sortie.try_clone().unwrap())
+            .stderr(sortie);
+        for (variable, _) in std::env::vars() {
+            if VARIABLES_IGNOREES.iter().any(|prefixe| variable.starts_with(prefixe)) {
+                commande.env_remove(variable);
+            }
+        }
+        commande.envs(env.iter().copied());
+        let enfant = commande.spawn().expect("lancement du service");
+        Self { nom: format!("{}/{}", projet, nom), enfant, dir: dir.to_path_buf() }
+    }
+
+    /// Attendre que le service accepte les connexions TCP sur `addr`
+    pub fn attendre_ecoute(&mut self, addr: SocketAddr) {
+        let debut = Instant::now();
+        while TcpStream::connect(addr).is_err() {
+            if let Ok(Some(statut)) = self.enfant.try_wait() {
+                panic!("{} s'est arrêté ({}) :\n{}", self.nom, statut, self.sortie());
+            }
+            assert!(debut.elapsed() < DELAI, "{} n'écoute pas sur {} :\n{}", self.nom, addr, self.sortie());
+            thread::sleep(Duration::from_millis(50));
+        }
+    }
+
+    /// Ce que le service a écrit jusqu'ici
+    pub fn sortie(&self) -> String {
+        std::fs::read_to_string(self.dir.join("sortie.txt")).unwrap_or_default()
+    }
+}
+
+impl Drop for Service {
+    fn drop(&mut self) {
+        let _ = self.enfant.kill();
+        let _ = self.enfant.wait();
+        if thread::panicking() {
+            eprintln!("--- sortie de {} ({}) ---\n{}", self.nom, self.dir.display(), self.sortie());
+        } else {
+            let _ = std::fs::remove_dir_all(&self.dir);
+        }
+    }
+}
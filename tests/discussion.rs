@@ -0,0 +1,48 @@
+// tests/discussion.rs
+// Serveur de messagerie (tp8) : un message écrit dans un salon parvient aux autres membres
+
+mod common;
+
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use common::{port_libre, repertoire, Service, DELAI};
+use tp8::client::{BotHandler, ChatBot, ClientError, RoomMessage};
+
+const MOT_DE_PASSE: &str = "secret-de-test";
+
+/// Garde le premier message du salon reçu, puis se déconnecte
+#[derive(Default)]
+struct Attente(Option<RoomMessage>);
+
+impl BotHandler for Attente {
+    async fn on_room_message(&mut self, bot: &mut ChatBot, message: RoomMessage) -> Result<(), ClientError> {
+        self.0 = Some(message);
+        bot.quit().await
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_diffusion_dans_un_salon() {
+    let dir = repertoire("tp8-salon");
+    let addr: SocketAddr = format!("127.0.0.1:{}", port_libre()).parse().unwrap();
+    let mut tp8 = Service::lancer("tp8", "serveur", &dir, &["--bind", &addr.to_string(), "--shutdown-grace-secs", "0"], &[]);
+    tokio::task::block_in_place(|| tp8.attendre_ecoute(addr));
+
+    let mut alice = ChatBot::connect(&addr.to_string(), "alice", MOT_DE_PASSE).await.unwrap();
+    let mut bob = ChatBot::connect(&addr.to_string(), "bob", MOT_DE_PASSE).await.unwrap();
+    alice.join("general").await.unwrap();
+    let membres = bob.join("general").await.unwrap();
+    assert!(membres.contains(&"alice".to_string()), "{:?}", membres);
+
+    let ecoute = tokio::spawn(async move {
+        let mut attente = Attente::default();
+        bob.run(&mut attente).await.map(|()| attente.0)
+    });
+    alice.send("bonjour à tous").await.unwrap();
+
+    let recu = tokio::time::timeout(DELAI, ecoute).await.expect("message reçu à temps").unwrap().unwrap().expect("message du salon");
+    assert_eq!((recu.room_id.as_str(), recu.from.as_str(), recu.content.as_str()), ("general", "alice", "bonjour à tous"));
+    alice.quit().await.unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+}
@@ -0,0 +1,40 @@
+// tests/dns.rs
+// Serveur DNS (tp7) : réponses tirées d'un fichier hosts et d'une zone, en UDP, en TCP et dans le protocole texte
+
+mod common;
+
+use std::net::{Ipv4Addr, SocketAddr, UdpSocket};
+use std::time::Duration;
+
+use common::{port_libre, repertoire, Service};
+use tp7_dns::client::{ClientDns, ErreurClient};
+use tp7_dns::dns::{Donnees, TypeEnregistrement};
+
+#[test]
+fn test_reponses_dns() {
+    let dir = repertoire("tp7-dns");
+    std::fs::write(dir.join("hosts"), "10.1.2.3 e2e.test alias.test\n").unwrap();
+    std::fs::write(dir.join("zone"), "_scp._tcp.e2e.test 60 SRV 0 5 9999 e2e.test\n").unwrap();
+    let addr: SocketAddr = format!("127.0.0.1:{}", port_libre()).parse().unwrap();
+    let mut tp7 = Service::lancer("tp7", "serveur", &dir, &["--listen", &addr.to_string(), "--hosts", "hosts", "--zone", "zone"], &[]);
+    tp7.attendre_ecoute(addr);
+
+    for tcp in [false, true] {
+        let client = ClientDns::new(addr).tcp(tcp).delai(Duration::from_secs(2));
+        for nom in ["e2e.test", "alias.test"] {
+            let reponses = client.lookup(nom, TypeEnregistrement::A).unwrap();
+            assert_eq!(reponses.iter().map(|rr| &rr.donnees).collect::<Vec<_>>(), [&Donnees::A(Ipv4Addr::new(10, 1, 2, 3))], "{} (tcp: {})", nom, tcp);
+        }
+        // Sans résolveur amont, un nom absent de la zone n'existe pas
+        assert!(matches!(client.lookup("absent.test", TypeEnregistrement::A), Err(ErreurClient::NxDomain)));
+        // Découverte de service : SRV puis adresse de la cible
+        assert_eq!(client.decouvrir("_scp._tcp.e2e.test").unwrap(), "10.1.2.3:9999".parse().unwrap());
+    }
+
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    socket.set_read_timeout(Some(Duration::from_secs(2))).unwrap();
+    socket.send_to(b"e2e.test A", addr).unwrap();
+    let mut tampon = [0u8; 512];
+    let taille = socket.recv(&mut tampon).unwrap();
+    assert_eq!(String::from_utf8_lossy(&tampon[..taille]), "10.1.2.3");
+}
@@ -0,0 +1,299 @@
+// tests/journalisation.rs
+// Serveur de journalisation (tp3) : les lignes des clients arrivent dans son fichier, y compris les
+// événements qu'un autre service lui envoie (LOG_SERVER)
+
+mod common;
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpStream};
+
+use common::{attendre, port_libre, repertoire, Service};
+use tp3::batch::{BatchLogger, BatchOptions};
+use tp3::compression::{self, Mode};
+
+fn journal(service: &Service) -> String {
+    std::fs::read_to_string(service.dir.join("logs/serveur.log")).unwrap_or_default()
+}
+
+/// Lancer tp3 sur un port libre, son fichier dans `logs/` de son répertoire
+fn lancer_tp3(nom: &str) -> (Service, SocketAddr) {
+    lancer_tp3_avec(nom, &[])
+}
+
+fn lancer_tp3_avec(nom: &str, args: &[&str]) -> (Service, SocketAddr) {
+    let dir = repertoire(nom);
+    let addr: SocketAddr = format!("127.0.0.1:{}", port_libre()).parse().unwrap();
+    let addr_texte = addr.to_string();
+    let mut tp3 = Service::lancer("tp3", "serveur", &dir, args, &[("TP3_ADDR", &addr_texte), ("TP3_LOG_FILE", "logs/serveur.log")]);
+    tp3.attendre_ecoute(addr);
+    (tp3, addr)
+}
+
+#[test]
+fn test_lignes_des_clients() {
+    let (tp3, addr) = lancer_tp3("tp3-lignes");
+    let mut client = TcpStream::connect(addr).unwrap();
+    // La ligne arrive en deux morceaux, la suivante avec une fin de ligne Windows
+    client.write_all(b"bonj").unwrap();
+    client.flush().unwrap();
+    client.write_all(b"our\r\nau revoir\n\nquit\n").unwrap();
+
+    // Le client 1 est la connexion qui a vérifié que le serveur écoutait
+    attendre("déconnexion du client dans le journal", || journal(&tp3).contains("Client 2 déconnecté"));
+    let journal = journal(&tp3);
+    let lignes: Vec<&str> = journal.lines().map(|ligne| ligne.split_once("] ").map_or(ligne, |(_, texte)| texte)).collect();
+    assert_eq!(lignes.iter().filter(|ligne| ligne.starts_with("Client 2")).copied().collect::<Vec<_>>(), [
+        "Client 2 connecté",
+        "Client 2: bonjour",
+        "Client 2: au revoir",
+        "Client 2 déconnecté",
+    ]);
+}
+
+#[test]
+fn test_lots_compresses() {
+    let (tp3, addr) = lancer_tp3("tp3-gzip");
+    let mut client = TcpStream::connect(addr).unwrap();
+    writeln!(client, "{}", Mode::Gzip.demande()).unwrap();
+    let mut reponse = String::new();
+    BufReader::new(client.try_clone().unwrap()).read_line(&mut reponse).unwrap();
+    assert_eq!(reponse, "OK gzip\n");
+
+    // Deux lots, le second se termine par quit
+    for lignes in [vec!["bonjour", "ça compresse"], vec!["au revoir", "quit"]] {
+        let lot = compression::compresser(&lignes.iter().map(|ligne| ligne.to_string()).collect::<Vec<_>>()).unwrap();
+        client.write_all(&(lot.len() as u32).to_be_bytes()).unwrap();
+        client.write_all(&lot).unwrap();
+    }
+
+    attendre("déconnexion du client dans le journal", || journal(&tp3).contains("Client 2 déconnecté"));
+    let journal = journal(&tp3);
+    for ligne in ["Client 2: bonjour", "Client 2: ça compresse", "Client 2: au revoir"] {
+        assert!(journal.contains(ligne), "{}", journal);
+    }
+    assert!(!journal.contains("MODE"), "{}", journal);
+}
+
+#[test]
+fn test_requete_par_periode() {
+    let (tp3, addr) = lancer_tp3("tp3-requete");
+    let mut client = TcpStream::connect(addr).unwrap();
+    let mut reponses = BufReader::new(client.try_clone().unwrap()).lines();
+    client.write_all(b"premier\nsecond\nQUERY since 2000-01-01T00:00:00Z\n").unwrap();
+
+    // Les entrées telles qu'écrites dans le fichier, puis leur nombre
+    let mut lignes = Vec::new();
+    loop {
+        let ligne = reponses.next().unwrap().unwrap();
+        if let Some(nombre) = ligne.strip_prefix("END ") {
+            assert_eq!(nombre.parse::<usize>().unwrap(), lignes.len());
+            break;
+        }
+        lignes.push(ligne);
+    }
+    // Démarrage, arrivée et départ du client 1 (la vérification du port), puis le client 2
+    assert_eq!(lignes.len(), 6, "{:?}", lignes);
+    assert!(lignes[4].ends_with("] Client 2: premier") && lignes[5].ends_with("] Client 2: second"), "{:?}", lignes);
+    assert_eq!(lignes, journal(&tp3).lines().take(6).collect::<Vec<_>>());
+
+    client.write_all(b"QUERY since 2999-01-01T00:00:00Z\nQUERY since hier\n").unwrap();
+    assert_eq!(reponses.next().unwrap().unwrap(), "END 0");
+    assert!(reponses.next().unwrap().unwrap().starts_with("ERR "));
+    // Les requêtes ne sont pas journalisées
+    assert!(!journal(&tp3).contains("QUERY"));
+    assert!(tp3.dir.join("logs/serveur.log.idx").exists());
+}
+
+#[test]
+fn test_champs_des_entrees() {
+    let (_tp3, addr) = lancer_tp3("tp3-champs");
+    let mut client = TcpStream::connect(addr).unwrap();
+    let mut reponses = BufReader::new(client.try_clone().unwrap()).lines();
+
+    // Une clé non autorisée est refusée, le client peut continuer
+    client.write_all(b"couleur=rouge\n").unwrap();
+    assert!(reponses.next().unwrap().unwrap().starts_with("ERR champ non autorisé: couleur"));
+    client.write_all("level=error   service=api msg=\"délai dépassé\"\nlevel=info msg=ok\nlevel=error ou pas\n".as_bytes()).unwrap();
+    assert!(reponses.next().unwrap().unwrap().starts_with("ERR "));
+
+    // Les entrées écrites sous forme régulière, filtrées par champ
+    client.write_all(b"QUERY since 2000-01-01T00:00:00Z level=error\n").unwrap();
+    let ligne = reponses.next().unwrap().unwrap();
+    assert!(ligne.ends_with("] Client 2: level=error service=api msg=\"délai dépassé\""), "{}", ligne);
+    assert_eq!(reponses.next().unwrap().unwrap(), "END 1");
+}
+
+#[test]
+fn test_espaces_de_noms() {
+    // Quota de trois entrées par minute, rotation au-delà de 200 octets en gardant un ancien fichier
+    let (tp3, addr) = lancer_tp3_avec("tp3-espaces", &["--quota", "3", "--rotate-size", "200", "--retention", "1"]);
+    let espace = |nom: &str| std::fs::read_to_string(tp3.dir.join("logs").join(nom).join("serveur.log")).unwrap_or_default();
+
+    let mut client = TcpStream::connect(addr).unwrap();
+    let mut reponses = BufReader::new(client.try_clone().unwrap()).lines();
+    client.write_all(b"NS payments\n").unwrap();
+    assert_eq!(reponses.next().unwrap().unwrap(), "OK payments");
+    client.write_all(b"paiement 1\npaiement 2\npaiement 3\npaiement 4\nquit\n").unwrap();
+    attendre("départ du client", || journal(&tp3).contains("Client 2 déconnecté"));
+
+    // Les entrées dans l'espace, au plus trois ; le fichier principal ne garde que les connexions
+    let payments = espace("payments");
+    assert!(payments.contains("Client 2: paiement 3") && !payments.contains("paiement 4"), "{}", payments);
+    assert!(journal(&tp3).contains("Client 2 écrit dans l'espace payments"));
+    assert!(!journal(&tp3).contains("paiement"));
+
+    // Le fichier principal a dépassé 200 octets : il est passé en .1
+    let ancien = std::fs::read_to_string(tp3.dir.join("logs/serveur.log.1")).unwrap();
+    assert!(ancien.contains("Serveur de journalisation démarré"), "{}", ancien);
+
+    let mut refuse = TcpStream::connect(addr).unwrap();
+    refuse.write_all(b"NS ../etc\n").unwrap();
+    let mut reponse = String::new();
+    BufReader::new(refuse).read_line(&mut reponse).unwrap();
+    assert!(reponse.starts_with("ERR "), "{}", reponse);
+}
+
+#[test]
+fn test_journaliseur_par_lots() {
+    let (tp3, addr) = lancer_tp3("tp3-lots");
+    let espace = || std::fs::read_to_string(tp3.dir.join("logs/app/serveur.log")).unwrap_or_default();
+    let options = BatchOptions {
+        size: 10,
+        delay: std::time::Duration::from_millis(100),
+        mode: Mode::Gzip,
+        namespace: Some("app".to_string()),
+    };
+    let logger = BatchLogger::connect(addr, options).unwrap();
+
+    // Lot incomplet : il part après le délai
+    logger.log("requête 1");
+    logger.log("requête 2");
+    attendre("lot envoyé après le délai", || espace().contains("requête 2"));
+
+    // Le dernier message part à la fermeture du journaliseur
+    logger.log("dernière requête");
+    drop(logger);
+    attendre("départ du client", || journal(&tp3).contains("Client 2 déconnecté"));
+    assert!(espace().contains("dernière requête"), "{}", espace());
+}
+
+#[test]
+fn test_suivi_en_direct() {
+    let (_tp3, addr) = lancer_tp3("tp3-tail");
+    let suivi = TcpStream::connect(addr).unwrap();
+    suivi.set_read_timeout(Some(common::DELAI)).unwrap();
+    let mut lignes = BufReader::new(suivi.try_clone().unwrap()).lines();
+    writeln!(&suivi, "TAIL").unwrap();
+    assert_eq!(lignes.next().unwrap().unwrap(), "OK tail");
+
+    // Les entrées d'un autre client arrivent au fil de l'eau, horodatées comme dans le fichier
+    let mut client = TcpStream::connect(addr).unwrap();
+    writeln!(client, "en direct\nquit").unwrap();
+    let ligne = lignes.map(Result::unwrap).find(|ligne| ligne.ends_with("Client 3: en direct")).unwrap();
+    assert!(ligne.starts_with("[20"), "{}", ligne);
+}
+
+/// Requête PUT reçue par le faux stockage S3 : ligne de requête, en-têtes (noms en minuscules) et contenu
+fn recevoir_put(flux: &mut TcpStream) -> (String, Vec<(String, String)>, Vec<u8>) {
+    let mut lecteur = BufReader::new(flux.try_clone().unwrap());
+    let mut requete = String::new();
+    lecteur.read_line(&mut requete).unwrap();
+    let mut en_tetes = Vec::new();
+    loop {
+        let mut ligne = String::new();
+        lecteur.read_line(&mut ligne).unwrap();
+        match ligne.trim_end().split_once(": ") {
+            Some((nom, valeur)) => en_tetes.push((nom.to_ascii_lowercase(), valeur.to_string())),
+            None => break,
+        }
+    }
+    let taille: usize = en_tetes.iter().find(|(nom, _)| nom == "content-length").unwrap().1.parse().unwrap();
+    let mut contenu = vec![0; taille];
+    lecteur.read_exact(&mut contenu).unwrap();
+    (requete.trim_end().to_string(), en_tetes, contenu)
+}
+
+#[test]
+fn test_archives_vers_s3() {
+    let stockage = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let endpoint = format!("http://{}", stockage.local_addr().unwrap());
+    let args = ["--rotate-size", "200", "--retention", "0", "--archive-endpoint", &endpoint, "--archive-prefix", "e2e/"];
+    let (tp3, addr) = lancer_tp3_avec("tp3-archives", &[&args[..], &["--archive-access-key", "cle", "--archive-secret-key", "secret"]].concat());
+    let mut client = TcpStream::connect(addr).unwrap();
+    for numero in 0..8 {
+        writeln!(client, "entrée numéro {} avant la rotation", numero).unwrap();
+    }
+
+    // Faux stockage : le premier envoi est refusé, les suivants acceptés
+    let (recus, reception) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        for (numero, flux) in stockage.incoming().enumerate() {
+            let mut flux = flux.unwrap();
+            let put = recevoir_put(&mut flux);
+            let reponse: &[u8] = match numero {
+                0 => b"HTTP/1.1 503 Slow Down\r\ncontent-length: 0\r\n\r\n",
+                _ => b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n",
+            };
+            flux.write_all(reponse).unwrap();
+            let _ = recus.send(put);
+        }
+    });
+    let (premier, _, _) = reception.recv_timeout(common::DELAI).unwrap();
+    let (requete, en_tetes, contenu) = reception.recv_timeout(common::DELAI).unwrap();
+    assert_eq!(requete, premier);
+
+    assert!(requete.starts_with("PUT /logs/e2e/serveur.log.") && requete.ends_with(".gz HTTP/1.1"), "{}", requete);
+    let autorisation = &en_tetes.iter().find(|(nom, _)| nom == "authorization").unwrap().1;
+    assert!(autorisation.starts_with("AWS4-HMAC-SHA256 Credential=cle/"), "{}", autorisation);
+    let lignes = compression::decompresser(&contenu, 1 << 20).unwrap();
+    assert!(lignes.iter().any(|ligne| ligne.ends_with("Client 2 connecté")), "{:?}", lignes);
+
+    // Toutes envoyées, sans copie locale à garder (retention 0)
+    let archives_locales = || {
+        std::fs::read_dir(tp3.dir.join("logs")).unwrap().filter(|entree| {
+            let nom = entree.as_ref().unwrap().file_name().into_string().unwrap();
+            nom.starts_with("serveur.log.2")
+        }).count()
+    };
+    attendre("suppression de l'archive envoyée", || archives_locales() == 0);
+}
+
+/// Réponse complète du point de santé de tp3
+fn sante(addr: SocketAddr) -> String {
+    let mut flux = TcpStream::connect(addr).unwrap();
+    flux.write_all(b"GET /healthz HTTP/1.1\r\nHost: test\r\n\r\n").unwrap();
+    let mut reponse = String::new();
+    flux.read_to_string(&mut reponse).unwrap();
+    reponse
+}
+
+#[test]
+fn test_point_de_sante() {
+    let sonde: SocketAddr = format!("127.0.0.1:{}", port_libre()).parse().unwrap();
+    let (tp3, _) = lancer_tp3_avec("tp3-sante", &["--health-addr", &sonde.to_string()]);
+    let reponse = sante(sonde);
+    assert!(reponse.starts_with("HTTP/1.1 200") && reponse.ends_with("OK\n"), "{}", reponse);
+
+    // Dossier des logs supprimé : le serveur ne peut plus écrire, la sonde le signale
+    std::fs::remove_dir_all(tp3.dir.join("logs")).unwrap();
+    let reponse = sante(sonde);
+    assert!(reponse.starts_with("HTTP/1.1 503"), "{}", reponse);
+    assert!(reponse.contains("serveur.log"), "{}", reponse);
+}
+
+#[test]
+fn test_evenements_d_un_service() {
+    let (tp3, addr) = lancer_tp3("tp3-service");
+    let dir = repertoire("tp7-journal");
+    let dns: SocketAddr = format!("127.0.0.1:{}", port_libre()).parse().unwrap();
+    let serveur_journal = addr.to_string();
+    let mut tp7 = Service::lancer("tp7", "serveur", &dir, &["--listen", &dns.to_string()], &[("LOG_SERVER", &serveur_journal)]);
+    tp7.attendre_ecoute(dns);
+
+    // Chaque événement est une ligne JSON signée du service
+    attendre("démarrage de tp7 dans le journal de tp3", || journal(&tp3).contains("Serveur DNS démarré"));
+    let evenement = journal(&tp3).lines().find(|ligne| ligne.contains("Serveur DNS démarré")).unwrap().to_string();
+    let json: serde_json::Value = serde_json::from_str(evenement.split_once(": ").unwrap().1).unwrap();
+    assert_eq!(json["service"], "tp7-dns");
+    assert_eq!(json["level"], "INFO");
+}
@@ -0,0 +1,94 @@
+// tests/websocket.rs
+// Serveur WebSocket (tp9) : Ping renvoyé tel quel, messages diffusés dans un salon, état sur /health
+
+mod common;
+
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream};
+
+use futures_util::{SinkExt, StreamExt};
+use tokio::net::TcpStream as TokioTcpStream;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream};
+
+use common::{port_libre, repertoire, Service, DELAI};
+use tp9::protocol::WsMessage;
+
+type Connexion = WebSocketStream<MaybeTlsStream<TokioTcpStream>>;
+
+async fn envoyer(connexion: &mut Connexion, message: WsMessage) {
+    connexion.send(message.to_frame()).await.unwrap();
+}
+
+/// Lire jusqu'au premier message qui satisfait `attendu`
+async fn attendre(connexion: &mut Connexion, attendu: impl Fn(&WsMessage) -> bool) -> WsMessage {
+    let lecture = async {
+        loop {
+            match connexion.next().await {
+                Some(Ok(Message::Text(texte))) => {
+                    let message = WsMessage::parse(&texte).unwrap();
+                    if attendu(&message) {
+                        return message;
+                    }
+                }
+                Some(Ok(_)) => {}
+                autre => panic!("connexion interrompue : {:?}", autre),
+            }
+        }
+    };
+    tokio::time::timeout(DELAI, lecture).await.expect("message attendu à temps")
+}
+
+/// État renvoyé par /health
+fn sante(addr: SocketAddr) -> serde_json::Value {
+    let mut flux = TcpStream::connect(addr).unwrap();
+    flux.write_all(b"GET /health HTTP/1.1\r\nHost: test\r\nConnection: close\r\n\r\n").unwrap();
+    let mut reponse = String::new();
+    flux.read_to_string(&mut reponse).unwrap();
+    assert!(reponse.starts_with("HTTP/1.1 200"), "{}", reponse);
+    serde_json::from_str(reponse.split_once("\r\n\r\n").unwrap().1).unwrap()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_echo_et_salon() {
+    let dir = repertoire("tp9-salon");
+    let addr: SocketAddr = format!("127.0.0.1:{}", port_libre()).parse().unwrap();
+    let supervision = SocketAddr::new(addr.ip(), port_libre());
+    let mut tp9 = Service::lancer(
+        "tp9",
+        "serveur",
+        &dir,
+        &["--addr", &addr.to_string(), "--metrics-port", &supervision.port().to_string()],
+        &[("TP9_ROOMS", "general,jeux")],
+    );
+    tokio::task::block_in_place(|| {
+        tp9.attendre_ecoute(addr);
+        tp9.attendre_ecoute(supervision);
+    });
+
+    let url = format!("ws://{}/ws", addr);
+    let (mut alice, _) = connect_async(&url).await.unwrap();
+    let (mut bob, _) = connect_async(&url).await.unwrap();
+
+    envoyer(&mut alice, WsMessage::Ping { stamp: Some(42) }).await;
+    let echo = attendre(&mut alice, |m| matches!(m, WsMessage::Ping { .. })).await;
+    assert_eq!(echo, WsMessage::Ping { stamp: Some(42) });
+
+    envoyer(&mut alice, WsMessage::Join { name: "alice".to_string(), room: Some("jeux".to_string()) }).await;
+    attendre(&mut alice, |m| matches!(m, WsMessage::Join { name, .. } if name == "alice")).await;
+    envoyer(&mut bob, WsMessage::Join { name: "bob".to_string(), room: Some("jeux".to_string()) }).await;
+    attendre(&mut alice, |m| matches!(m, WsMessage::Join { name, .. } if name == "bob")).await;
+
+    envoyer(&mut alice, WsMessage::Chat { from: String::new(), text: "une partie ?".to_string() }).await;
+    let recu = attendre(&mut bob, |m| matches!(m, WsMessage::Chat { .. })).await;
+    assert_eq!(recu, WsMessage::Chat { from: "alice".to_string(), text: "une partie ?".to_string() });
+
+    // Salon absent de TP9_ROOMS : refusé
+    envoyer(&mut bob, WsMessage::Join { name: "bob".to_string(), room: Some("secret".to_string()) }).await;
+    attendre(&mut bob, |m| matches!(m, WsMessage::Error { .. })).await;
+
+    let etat = tokio::task::block_in_place(|| sante(supervision));
+    assert_eq!(etat["status"], "ok");
+    assert_eq!(etat["clients"], 2);
+    assert!(etat["messages_relayed"].as_u64().unwrap() >= 1, "{}", etat);
+}
@@ -0,0 +1,171 @@
+// Journal d'audit : chaque opération est ajoutée au journal local et, si une adresse est donnée
+// (--audit-serveur 127.0.0.1:8080), envoyée aussi au serveur de logs du TP3 sous forme d'entrée
+// structurée : "level=info service=banque user=amina msg=\"...\"". Le serveur n'est qu'une copie :
+// s'il est absent, l'opération est quand même faite et notée dans le fichier local
+
+use std::io::{self, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+use crate::journal::{self, Evenement};
+use crate::messages::Messages;
+
+const DELAI_SERVEUR: Duration = Duration::from_secs(2);
+
+pub struct Audit {
+    chemin: String,
+    serveur: Option<Serveur>,
+}
+
+struct Serveur {
+    adresse: String,
+    connexion: Option<TcpStream>,
+    en_panne: bool, // l'échec a déjà été signalé : on ne le répète pas à chaque opération
+}
+
+impl Serveur {
+    // Une connexion par session ; elle est rouverte après une erreur
+    fn envoyer(&mut self, ligne: &str) -> io::Result<()> {
+        if self.connexion.is_none() {
+            let adresse = self
+                .adresse
+                .to_socket_addrs()?
+                .next()
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "adresse introuvable"))?;
+            let connexion = TcpStream::connect_timeout(&adresse, DELAI_SERVEUR)?;
+            connexion.set_write_timeout(Some(DELAI_SERVEUR))?;
+            self.connexion = Some(connexion);
+        }
+        let resultat = match self.connexion.as_mut() {
+            Some(connexion) => writeln!(connexion, "{}", ligne),
+            None => Ok(()),
+        };
+        if resultat.is_err() {
+            self.connexion = None;
+        }
+        resultat
+    }
+}
+
+// Ce que l'utilisateur doit savoir après une opération notée
+#[derive(Debug)]
+pub enum Probleme {
+    JournalNonMisAJour(String, io::Error), // chemin du journal, erreur
+    ServeurInjoignable(String, io::Error), // adresse du serveur, erreur
+    ServeurJoignable(String),              // de nouveau, après une erreur
+}
+
+impl Probleme {
+    pub fn message(&self, messages: &Messages) -> String {
+        match self {
+            Probleme::JournalNonMisAJour(chemin, e) => messages.avec("audit.non_mis_a_jour", &[("chemin", chemin), ("erreur", e)]),
+            Probleme::ServeurInjoignable(adresse, e) => messages.avec("audit.injoignable", &[("adresse", adresse), ("erreur", e)]),
+            Probleme::ServeurJoignable(adresse) => messages.avec("audit.joignable", &[("adresse", adresse)]),
+        }
+    }
+}
+
+impl Audit {
+    pub fn new(chemin: &str, serveur: Option<String>) -> Audit {
+        Audit {
+            chemin: chemin.to_string(),
+            serveur: serveur.map(|adresse| Serveur { adresse, connexion: None, en_panne: false }),
+        }
+    }
+
+    pub fn chemin(&self) -> &str {
+        &self.chemin
+    }
+
+    // Noter l'événement ; renvoie les problèmes à signaler à l'utilisateur
+    pub fn noter(&mut self, evenement: &Evenement) -> Vec<Probleme> {
+        let mut problemes = Vec::new();
+        if let Err(e) = journal::ajouter(&self.chemin, evenement) {
+            problemes.push(Probleme::JournalNonMisAJour(self.chemin.clone(), e));
+        }
+        if let Some(serveur) = self.serveur.as_mut() {
+            match serveur.envoyer(&entree_structuree(evenement)) {
+                Ok(()) if serveur.en_panne => {
+                    serveur.en_panne = false;
+                    problemes.push(Probleme::ServeurJoignable(serveur.adresse.clone()));
+                }
+                Ok(()) => {}
+                Err(e) if !serveur.en_panne => {
+                    serveur.en_panne = true;
+                    problemes.push(Probleme::ServeurInjoignable(serveur.adresse.clone(), e));
+                }
+                Err(_) => {}
+            }
+        }
+        problemes
+    }
+}
+
+// L'événement tel que le serveur du TP3 l'attend : des champs qu'il accepte par défaut
+// (level, service, user, msg), le détail de l'opération dans msg
+pub fn entree_structuree(evenement: &Evenement) -> String {
+    let message = format!(
+        "{} titulaire={} compte={} nom={} montant={:.2} solde={:.2}->{:.2} date={}",
+        evenement.operation.nom(),
+        if evenement.titulaire.is_empty() { "-" } else { &evenement.titulaire },
+        evenement.compte + 1,
+        evenement.nom,
+        evenement.montant,
+        evenement.ancien_solde,
+        evenement.solde,
+        evenement.horodatage
+    );
+    format!(
+        "level=info service=banque user={} msg=\"{}\"",
+        valeur_simple(&evenement.auteur),
+        message.replace('\\', "\\\\").replace('"', "\\\"")
+    )
+}
+
+// Une valeur sans guillemets ne peut pas contenir d'espace ni de caractère spécial
+fn valeur_simple(valeur: &str) -> String {
+    let valeur: String = valeur
+        .chars()
+        .map(|c| if c.is_whitespace() || c == '"' || c == '\\' || c == '=' { '_' } else { c })
+        .collect();
+    if valeur.is_empty() { "inconnu".to_string() } else { valeur }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::journal::Operation;
+    use std::io::{BufRead, BufReader};
+    use std::net::TcpListener;
+
+    #[test]
+    fn test_envoi_au_serveur() {
+        let chemin = std::env::temp_dir().join(format!("tp1_audit_{}.journal", std::process::id()));
+        let _ = std::fs::remove_file(&chemin);
+        let ecoute = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut audit = Audit::new(chemin.to_str().unwrap(), Some(ecoute.local_addr().unwrap().to_string()));
+
+        let evenement = Evenement::maintenant("amina b", Operation::Depot, 0, "Kevin \"K\"", 50.0, 550.0).avec_titulaire("Kevin");
+        assert!(audit.noter(&evenement).is_empty());
+        let (connexion, _) = ecoute.accept().unwrap();
+        let mut ligne = String::new();
+        BufReader::new(connexion).read_line(&mut ligne).unwrap();
+        assert_eq!(
+            ligne.trim_end(),
+            format!(
+                "level=info service=banque user=amina_b msg=\"depot titulaire=Kevin compte=1 nom=Kevin \\\"K\\\" montant=50.00 solde=500.00->550.00 date={}\"",
+                evenement.horodatage
+            )
+        );
+        assert_eq!(journal::lire_tout(audit.chemin()).unwrap(), vec![evenement]);
+
+        // Serveur arrêté : signalé une seule fois, le fichier local reste à jour
+        drop(ecoute);
+        let mut audit = Audit::new(chemin.to_str().unwrap(), Some("127.0.0.1:1".to_string()));
+        let retrait = Evenement::maintenant("amina", Operation::Retrait, 0, "Kevin", 20.0, 530.0);
+        assert_eq!(audit.noter(&retrait).len(), 1);
+        assert!(audit.noter(&retrait).is_empty());
+        assert_eq!(journal::lire_tout(audit.chemin()).unwrap().len(), 3);
+        let _ = std::fs::remove_file(&chemin);
+    }
+}
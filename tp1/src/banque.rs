@@ -0,0 +1,448 @@
+// Comptes de la banque, partagés entre plusieurs tâches (plusieurs clients à terme) :
+// la liste est protégée par un RwLock, et chaque compte par le sien. Une opération sur un compte
+// ne bloque pas les autres, et un virement verrouille ses deux comptes dans l'ordre de leur position
+// pour que deux virements croisés ne s'attendent pas l'un l'autre
+
+use std::fmt;
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use crate::historique;
+use crate::messages::{euros, Messages};
+
+// Plafonds d'un compte ; None : pas de limite
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Plafonds {
+    pub retrait: Option<f64>,           // montant maximum d'un retrait ou d'un virement émis
+    pub retraits_par_jour: Option<f64>, // total des retraits et virements émis sur une journée (UTC)
+    pub solde: Option<f64>,             // solde maximum après un dépôt ou un virement reçu
+}
+
+// Ce qu'un titulaire peut faire sur un compte
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Droit {
+    Consultation, // voir le solde, l'historique et les plafonds
+    Complet,      // aussi déposer, retirer, virer et renommer
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Titulaire {
+    pub nom: String,
+    pub droit: Droit,
+}
+
+impl Titulaire {
+    pub fn new(nom: &str, droit: Droit) -> Titulaire {
+        Titulaire { nom: nom.to_string(), droit }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompteBancaire {
+    pub nom: String,
+    pub solde: f64,
+    pub taux: f64, // taux d'intérêt annuel d'un compte d'épargne (0.03 pour 3 %), 0 pour un compte courant
+    pub plafonds: Plafonds,
+    pub titulaires: Vec<Titulaire>, // plusieurs pour un compte joint
+    retraits_du_jour: (u64, f64),   // jour et total retiré ce jour-là
+}
+
+impl CompteBancaire {
+    // Un seul titulaire, qui a tous les droits : celui qui donne son nom au compte
+    pub fn new(nom: &str, solde: f64) -> CompteBancaire {
+        CompteBancaire::epargne(nom, solde, 0.0)
+    }
+
+    pub fn epargne(nom: &str, solde: f64, taux: f64) -> CompteBancaire {
+        CompteBancaire {
+            nom: nom.to_string(),
+            solde,
+            taux,
+            plafonds: Plafonds::default(),
+            titulaires: vec![Titulaire::new(nom, Droit::Complet)],
+            retraits_du_jour: (0, 0.0),
+        }
+    }
+
+    pub fn avec_titulaires(self, titulaires: Vec<Titulaire>) -> CompteBancaire {
+        CompteBancaire { titulaires, ..self }
+    }
+
+    // Droit du titulaire sur ce compte ; None s'il n'en est pas titulaire
+    pub fn droit_de(&self, titulaire: &str) -> Option<Droit> {
+        self.titulaires.iter().find(|t| t.nom == titulaire).map(|t| t.droit)
+    }
+
+    // Le titulaire a au moins ce droit sur le compte
+    pub fn autorise(&self, titulaire: &str, droit: Droit) -> bool {
+        self.droit_de(titulaire).is_some_and(|sien| sien >= droit)
+    }
+
+    pub fn avec_plafonds(self, plafonds: Plafonds) -> CompteBancaire {
+        CompteBancaire { plafonds, ..self }
+    }
+
+    pub fn est_epargne(&self) -> bool {
+        self.taux > 0.0
+    }
+
+    pub fn afficher_solde(&self, messages: &Messages) {
+        println!("{}", messages.avec("solde", &[("nom", &self.nom), ("solde", &euros(self.solde))]));
+    }
+
+    // Total retiré le jour donné
+    fn retire_le(&self, jour: u64) -> f64 {
+        match self.retraits_du_jour {
+            (jour_des_retraits, total) if jour_des_retraits == jour => total,
+            _ => 0.0,
+        }
+    }
+
+    // Ce qui peut encore être retiré le jour donné ; None sans plafond journalier
+    pub fn reste_le(&self, jour: u64) -> Option<f64> {
+        self.plafonds.retraits_par_jour.map(|plafond| (plafond - self.retire_le(jour)).max(0.0))
+    }
+
+    pub fn verifier_retrait(&self, montant: f64, jour: u64) -> Result<(), BanqueError> {
+        if montant.is_nan() || montant <= 0.0 {
+            return Err(BanqueError::MontantInvalide(montant));
+        }
+        if montant > self.solde {
+            return Err(BanqueError::SoldeInsuffisant { solde: self.solde, montant });
+        }
+        if let Some(plafond) = self.plafonds.retrait
+            && montant > plafond
+        {
+            return Err(BanqueError::PlafondRetrait { plafond, montant });
+        }
+        if let Some(reste) = self.reste_le(jour)
+            && montant > reste
+        {
+            return Err(BanqueError::PlafondJournalier { reste, montant });
+        }
+        Ok(())
+    }
+
+    pub fn verifier_depot(&self, montant: f64) -> Result<(), BanqueError> {
+        if !montant.is_finite() || montant <= 0.0 {
+            return Err(BanqueError::MontantInvalide(montant));
+        }
+        if let Some(plafond) = self.plafonds.solde
+            && self.solde + montant > plafond
+        {
+            return Err(BanqueError::PlafondSolde { plafond, solde: self.solde + montant });
+        }
+        Ok(())
+    }
+
+    // Renvoie le nouveau solde
+    pub fn retirer(&mut self, montant: f64) -> Result<f64, BanqueError> {
+        self.retirer_le(montant, historique::aujourd_hui())
+    }
+
+    // Retrait compté dans les retraits du jour donné ; renvoie le nouveau solde
+    pub fn retirer_le(&mut self, montant: f64, jour: u64) -> Result<f64, BanqueError> {
+        self.verifier_retrait(montant, jour)?;
+        self.retraits_du_jour = (jour, self.retire_le(jour) + montant);
+        self.solde -= montant;
+        Ok(self.solde)
+    }
+
+    // Renvoie le nouveau solde
+    pub fn deposer(&mut self, montant: f64) -> Result<f64, BanqueError> {
+        self.verifier_depot(montant)?;
+        self.solde += montant;
+        Ok(self.solde)
+    }
+
+    // Intérêts d'une journée (taux annuel / 365), arrondis au centime
+    pub fn interets_du_jour(&self) -> f64 {
+        match self.est_epargne() && self.solde > 0.0 {
+            true => (self.solde * self.taux / 365.0 * 100.0).round() / 100.0,
+            false => 0.0,
+        }
+    }
+
+    pub fn renommer(&self, nouveau_nom: &str) -> CompteBancaire {
+        CompteBancaire {
+            nom: nouveau_nom.to_string(),
+            ..self.clone()
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BanqueError {
+    CompteInconnu(usize),
+    MontantInvalide(f64),
+    SoldeInsuffisant { solde: f64, montant: f64 },
+    MemeCompte, // virement d'un compte vers lui-même
+    PlafondRetrait { plafond: f64, montant: f64 },
+    PlafondJournalier { reste: f64, montant: f64 }, // reste : ce qui pouvait encore être retiré ce jour-là
+    PlafondSolde { plafond: f64, solde: f64 },      // solde : celui qu'aurait eu le compte
+}
+
+impl fmt::Display for BanqueError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BanqueError::CompteInconnu(index) => write!(f, "Compte inconnu : {}", index + 1),
+            BanqueError::MontantInvalide(montant) => write!(f, "Montant invalide ({}) : il doit être positif.", montant),
+            BanqueError::SoldeInsuffisant { solde, montant } => {
+                write!(f, "Solde insuffisant : {:.2} € disponibles pour {:.2} €.", solde, montant)
+            }
+            BanqueError::MemeCompte => write!(f, "Le virement doit se faire vers un autre compte."),
+            BanqueError::PlafondRetrait { plafond, montant } => {
+                write!(f, "Retrait de {:.2} € refusé : {:.2} € au plus par opération.", montant, plafond)
+            }
+            BanqueError::PlafondJournalier { reste, montant } => {
+                write!(f, "Retrait de {:.2} € refusé : encore {:.2} € de retraits possibles aujourd'hui.", montant, reste)
+            }
+            BanqueError::PlafondSolde { plafond, solde } => {
+                write!(f, "Dépôt refusé : le solde ({:.2} €) dépasserait le plafond de {:.2} €.", solde, plafond)
+            }
+        }
+    }
+}
+
+impl BanqueError {
+    // L'erreur dans la langue de l'utilisateur (Display reste en français, pour les logs)
+    pub fn message(&self, messages: &Messages) -> String {
+        match self {
+            BanqueError::CompteInconnu(index) => messages.avec("erreur.compte_inconnu", &[("numero", &(index + 1))]),
+            BanqueError::MontantInvalide(montant) => messages.avec("erreur.montant_invalide", &[("montant", montant)]),
+            BanqueError::SoldeInsuffisant { solde, montant } => {
+                messages.avec("erreur.solde_insuffisant", &[("solde", &euros(*solde)), ("montant", &euros(*montant))])
+            }
+            BanqueError::MemeCompte => messages.texte("erreur.meme_compte"),
+            BanqueError::PlafondRetrait { plafond, montant } => {
+                messages.avec("erreur.plafond_retrait", &[("plafond", &euros(*plafond)), ("montant", &euros(*montant))])
+            }
+            BanqueError::PlafondJournalier { reste, montant } => {
+                messages.avec("erreur.plafond_journalier", &[("reste", &euros(*reste)), ("montant", &euros(*montant))])
+            }
+            BanqueError::PlafondSolde { plafond, solde } => {
+                messages.avec("erreur.plafond_solde", &[("plafond", &euros(*plafond)), ("solde", &euros(*solde))])
+            }
+        }
+    }
+}
+
+impl std::error::Error for BanqueError {}
+
+// Un compte partagé : chaque tâche qui le modifie prend son verrou
+type Compte = Arc<RwLock<CompteBancaire>>;
+
+#[derive(Debug, Default)]
+pub struct Banque {
+    comptes: RwLock<Vec<Compte>>,
+}
+
+// Un verrou « empoisonné » (une tâche a paniqué en le tenant) garde des données cohérentes ici :
+// chaque opération vérifie avant de modifier, on continue donc avec
+fn lire<T>(verrou: &RwLock<T>) -> RwLockReadGuard<'_, T> {
+    verrou.read().unwrap_or_else(|e| e.into_inner())
+}
+
+fn ecrire<T>(verrou: &RwLock<T>) -> RwLockWriteGuard<'_, T> {
+    verrou.write().unwrap_or_else(|e| e.into_inner())
+}
+
+impl Banque {
+    pub fn new(comptes: Vec<CompteBancaire>) -> Banque {
+        Banque { comptes: RwLock::new(comptes.into_iter().map(|compte| Arc::new(RwLock::new(compte))).collect()) }
+    }
+
+    // Ajoute un compte ; renvoie sa position
+    pub fn ouvrir(&self, compte: CompteBancaire) -> usize {
+        let mut comptes = ecrire(&self.comptes);
+        comptes.push(Arc::new(RwLock::new(compte)));
+        comptes.len() - 1
+    }
+
+    pub fn nombre(&self) -> usize {
+        lire(&self.comptes).len()
+    }
+
+    // Le verrou de la liste n'est gardé que le temps de trouver le compte
+    fn compte(&self, index: usize) -> Result<Compte, BanqueError> {
+        lire(&self.comptes).get(index).cloned().ok_or(BanqueError::CompteInconnu(index))
+    }
+
+    // Copie de l'état d'un compte
+    pub fn consulter(&self, index: usize) -> Result<CompteBancaire, BanqueError> {
+        let compte = self.compte(index)?;
+        let copie = lire(&compte).clone();
+        Ok(copie)
+    }
+
+    // Copie de tous les comptes, dans l'ordre
+    pub fn comptes(&self) -> Vec<CompteBancaire> {
+        let comptes: Vec<Compte> = lire(&self.comptes).clone();
+        comptes.iter().map(|compte| lire(compte).clone()).collect()
+    }
+
+    // Noms de tous les titulaires, chacun une fois, dans l'ordre des comptes
+    pub fn titulaires(&self) -> Vec<String> {
+        let mut noms: Vec<String> = Vec::new();
+        for compte in self.comptes() {
+            for titulaire in compte.titulaires {
+                if !noms.contains(&titulaire.nom) {
+                    noms.push(titulaire.nom);
+                }
+            }
+        }
+        noms
+    }
+
+    // Renvoie le nouveau solde
+    pub fn deposer(&self, index: usize, montant: f64) -> Result<f64, BanqueError> {
+        let compte = self.compte(index)?;
+        ecrire(&compte).deposer(montant)
+    }
+
+    // Renvoie le nouveau solde
+    pub fn retirer(&self, index: usize, montant: f64) -> Result<f64, BanqueError> {
+        let compte = self.compte(index)?;
+        ecrire(&compte).retirer(montant)
+    }
+
+    // Renvoie les nouveaux soldes des deux comptes ; rien ne change si le virement est refusé
+    pub fn virer(&self, de: usize, vers: usize, montant: f64) -> Result<(f64, f64), BanqueError> {
+        if de == vers {
+            return Err(BanqueError::MemeCompte);
+        }
+        let (source, destination) = (self.compte(de)?, self.compte(vers)?);
+        // Toujours le compte de plus petite position en premier
+        let (mut source, mut destination) = match de < vers {
+            true => {
+                let source = ecrire(&source);
+                (source, ecrire(&destination))
+            }
+            false => {
+                let destination = ecrire(&destination);
+                (ecrire(&source), destination)
+            }
+        };
+        // Les deux comptes sont vérifiés avant de toucher à l'un ou l'autre
+        let jour = historique::aujourd_hui();
+        source.verifier_retrait(montant, jour)?;
+        destination.verifier_depot(montant)?;
+        let nouveau_solde = source.retirer_le(montant, jour)?;
+        Ok((nouveau_solde, destination.deposer(montant)?))
+    }
+
+    // Verse les intérêts d'une journée sur un compte d'épargne ; renvoie le montant versé et le nouveau solde,
+    // None s'il n'y a rien à verser (compte courant, moins d'un centime)
+    pub fn verser_interets(&self, index: usize) -> Result<Option<(f64, f64)>, BanqueError> {
+        let compte = self.compte(index)?;
+        let mut compte = ecrire(&compte);
+        let interets = compte.interets_du_jour();
+        if interets < 0.01 {
+            return Ok(None);
+        }
+        // Comme sur un livret réglementé, les intérêts peuvent faire dépasser le plafond de solde
+        compte.solde += interets;
+        Ok(Some((interets, compte.solde)))
+    }
+
+    pub fn fixer_plafonds(&self, index: usize, plafonds: Plafonds) -> Result<(), BanqueError> {
+        let compte = self.compte(index)?;
+        ecrire(&compte).plafonds = plafonds;
+        Ok(())
+    }
+
+    pub fn renommer(&self, index: usize, nouveau_nom: &str) -> Result<(), BanqueError> {
+        let compte = self.compte(index)?;
+        let mut compte = ecrire(&compte);
+        *compte = compte.renommer(nouveau_nom);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn banque() -> Banque {
+        Banque::new(vec![CompteBancaire::new("Kevin", 500.0), CompteBancaire::new("Nourdine", 1000.0)])
+    }
+
+    #[test]
+    fn test_operations() {
+        let banque = banque();
+        assert_eq!(banque.deposer(0, 50.0), Ok(550.0));
+        assert_eq!(banque.retirer(1, 2000.0), Err(BanqueError::SoldeInsuffisant { solde: 1000.0, montant: 2000.0 }));
+        assert_eq!(banque.retirer(1, -5.0), Err(BanqueError::MontantInvalide(-5.0)));
+        assert_eq!(banque.virer(1, 0, 250.0), Ok((750.0, 800.0)));
+        assert_eq!(banque.virer(0, 0, 1.0), Err(BanqueError::MemeCompte));
+        assert_eq!(banque.virer(0, 7, 1.0), Err(BanqueError::CompteInconnu(7)));
+        banque.renommer(0, "Amina").unwrap();
+        assert_eq!(banque.consulter(0), Ok(CompteBancaire::new("Kevin", 800.0).renommer("Amina")));
+
+        let livret = banque.ouvrir(CompteBancaire::epargne("Fatou", 730.0, 0.03));
+        assert_eq!(banque.verser_interets(livret), Ok(Some((0.06, 730.06))));
+        assert_eq!(banque.verser_interets(0), Ok(None));
+    }
+
+    #[test]
+    fn test_titulaires() {
+        let joint = CompteBancaire::new("Compte joint", 100.0)
+            .avec_titulaires(vec![Titulaire::new("Amina", Droit::Complet), Titulaire::new("Kevin", Droit::Consultation)]);
+        assert!(joint.autorise("Amina", Droit::Complet));
+        assert!(joint.autorise("Kevin", Droit::Consultation));
+        assert!(!joint.autorise("Kevin", Droit::Complet));
+        assert_eq!(joint.droit_de("Fatou"), None);
+        assert_eq!(joint.renommer("Commun").titulaires, joint.titulaires);
+
+        let banque = Banque::new(vec![CompteBancaire::new("Kevin", 1.0), joint]);
+        assert_eq!(banque.titulaires(), vec!["Kevin".to_string(), "Amina".to_string()]);
+    }
+
+    #[test]
+    fn test_plafonds() {
+        let plafonds = Plafonds { retrait: Some(300.0), retraits_par_jour: Some(500.0), solde: Some(1000.0) };
+        let mut compte = CompteBancaire::new("Kevin", 900.0).avec_plafonds(plafonds);
+        assert_eq!(compte.retirer_le(400.0, 10), Err(BanqueError::PlafondRetrait { plafond: 300.0, montant: 400.0 }));
+        assert_eq!(compte.retirer_le(300.0, 10), Ok(600.0));
+        assert_eq!(compte.reste_le(10), Some(200.0));
+        assert_eq!(compte.retirer_le(250.0, 10), Err(BanqueError::PlafondJournalier { reste: 200.0, montant: 250.0 }));
+        // Le lendemain, le plafond journalier repart de zéro
+        assert_eq!(compte.reste_le(11), Some(500.0));
+        assert_eq!(compte.retirer_le(250.0, 11), Ok(350.0));
+        assert_eq!(compte.deposer(700.0), Err(BanqueError::PlafondSolde { plafond: 1000.0, solde: 1050.0 }));
+
+        // Un virement refusé par le compte destinataire ne débite pas la source
+        let banque = Banque::new(vec![CompteBancaire::new("Nourdine", 1000.0), compte]);
+        assert_eq!(banque.virer(0, 1, 700.0), Err(BanqueError::PlafondSolde { plafond: 1000.0, solde: 1050.0 }));
+        assert_eq!(banque.consulter(0).unwrap().solde, 1000.0);
+        assert_eq!(banque.virer(1, 0, 301.0), Err(BanqueError::PlafondRetrait { plafond: 300.0, montant: 301.0 }));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_taches_concurrentes() {
+        let banque = Arc::new(banque());
+        let troisieme = banque.ouvrir(CompteBancaire::new("Fatou", 750.0));
+
+        // Des virements croisés entre les trois comptes et des dépôts, depuis plusieurs tâches à la fois
+        let mut taches = Vec::new();
+        for tache in 0..8 {
+            let banque = Arc::clone(&banque);
+            taches.push(tokio::spawn(async move {
+                for i in 0..500 {
+                    let (de, vers) = ((tache + i) % 3, (tache + i + 1 + i % 2) % 3);
+                    let _ = banque.virer(de, vers, 10.0);
+                    if i % 100 == 0 {
+                        banque.deposer(troisieme, 1.0).unwrap();
+                    }
+                }
+            }));
+        }
+        for tache in taches {
+            tache.await.unwrap();
+        }
+
+        // Aucun virement n'a créé ni perdu d'argent : seuls les dépôts comptent
+        let total: f64 = banque.comptes().iter().map(|compte| compte.solde).sum();
+        assert_eq!(total, 500.0 + 1000.0 + 750.0 + 8.0 * 5.0);
+        assert!(banque.comptes().iter().all(|compte| compte.solde >= 0.0));
+    }
+}
@@ -0,0 +1,126 @@
+// Observateur : tableau de bord des comptes en lecture seule, mis à jour pendant que le menu
+// les modifie. Il suit le journal écrit par le menu (banque.journal, ou le chemin donné en argument) :
+// cargo run --bin observateur [chemin]   (textes dans la langue de la variable LANG)
+
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::thread;
+use std::time::Duration;
+
+use tp1::journal::{Evenement, Operation, FICHIER_JOURNAL};
+use tp1::messages::{euros, Langue, Messages};
+
+const OPERATIONS_AFFICHEES: usize = 10;
+
+// Ce que l'observateur sait de la banque, d'après le journal lu jusqu'ici
+#[derive(Default)]
+struct Tableau {
+    comptes: Vec<Option<(String, f64)>>, // nom et solde, par position dans la liste du menu
+    recentes: VecDeque<Evenement>,       // dernières opérations, la plus récente en tête
+}
+
+impl Tableau {
+    fn appliquer(&mut self, evenement: Evenement) {
+        if self.comptes.len() <= evenement.compte {
+            self.comptes.resize(evenement.compte + 1, None);
+        }
+        self.comptes[evenement.compte] = Some((evenement.nom.clone(), evenement.solde));
+        if evenement.operation != Operation::Ouverture {
+            self.recentes.push_front(evenement);
+            self.recentes.truncate(OPERATIONS_AFFICHEES);
+        }
+    }
+
+    fn afficher(&self, chemin: &str, messages: &Messages) {
+        // Effacer le terminal et revenir en haut
+        print!("\x1b[2J\x1b[H");
+        println!("{}", messages.avec("observateur.titre", &[("chemin", &chemin)]));
+        println!("\n{}", messages.texte("observateur.comptes"));
+        if self.comptes.is_empty() {
+            println!("  {}", messages.texte("observateur.aucun_compte"));
+        }
+        let mut total = 0.0;
+        for (i, compte) in self.comptes.iter().enumerate() {
+            if let Some((nom, solde)) = compte {
+                println!("  {}. {:<20} {:>12.2} €", i + 1, nom, solde);
+                total += solde;
+            }
+        }
+        println!("  {:<23} {:>12.2} €", messages.texte("observateur.total"), total);
+
+        println!("\n{}", messages.texte("observateur.recentes"));
+        for evenement in &self.recentes {
+            let ligne = messages.avec(
+                "observateur.operation",
+                &[
+                    ("heure", &evenement.heure()),
+                    ("operation", &evenement.description(messages)),
+                    ("nom", &evenement.nom),
+                    ("solde", &euros(evenement.solde)),
+                    ("auteur", &evenement.acteur(messages)),
+                ],
+            );
+            println!("  {}", ligne);
+        }
+    }
+}
+
+// Lire ce qui a été ajouté au journal depuis `position` ; seules les lignes complètes sont prises.
+// Renvoie les lignes et la nouvelle position
+fn lire_suite(chemin: &str, position: u64) -> io::Result<(Vec<String>, u64)> {
+    let mut fichier = File::open(chemin)?;
+    fichier.seek(SeekFrom::Start(position))?;
+    let mut texte = String::new();
+    fichier.read_to_string(&mut texte)?;
+    let complet = texte.rfind('\n').map(|fin| fin + 1).unwrap_or(0);
+    let lignes = texte[..complet].lines().map(String::from).collect();
+    Ok((lignes, position + complet as u64))
+}
+
+fn main() {
+    let chemin = std::env::args().nth(1).unwrap_or_else(|| FICHIER_JOURNAL.to_string());
+    let messages = Messages::charger(Langue::depuis_environnement());
+    let mut tableau = Tableau::default();
+    let mut position = 0;
+    let mut premier_affichage = true;
+
+    loop {
+        // Journal vidé ou remplacé : on repart du début
+        let taille = std::fs::metadata(&chemin).map(|m| m.len()).unwrap_or(0);
+        if taille < position {
+            tableau = Tableau::default();
+            position = 0;
+        }
+
+        match lire_suite(&chemin, position) {
+            Ok((lignes, nouvelle_position)) => {
+                let change = !lignes.is_empty();
+                for ligne in lignes {
+                    match Evenement::lire(&ligne) {
+                        Some(evenement) => tableau.appliquer(evenement),
+                        None => eprintln!("{}", messages.avec("observateur.ligne_ignoree", &[("ligne", &ligne)])),
+                    }
+                }
+                position = nouvelle_position;
+                if change || premier_affichage {
+                    tableau.afficher(&chemin, &messages);
+                    premier_affichage = false;
+                }
+            }
+            // Pas encore de journal : le menu n'a pas été lancé
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                if premier_affichage {
+                    tableau.afficher(&chemin, &messages);
+                    premier_affichage = false;
+                }
+            }
+            Err(e) => {
+                eprintln!("{}", messages.avec("observateur.erreur", &[("chemin", &chemin), ("erreur", &e)]));
+                return;
+            }
+        }
+
+        thread::sleep(Duration::from_millis(500));
+    }
+}
@@ -0,0 +1,159 @@
+// Historique des soldes : à la fin de chaque journée (UTC), le solde de chaque compte est ajouté à
+// banque.historique, une ligne par compte : "jour;compte;solde;nom" (le jour compté depuis le 1er janvier 1970).
+// Le menu en tire l'évolution d'un compte sur les 30 derniers jours, en graphique ASCII
+
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::ops::Range;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::journal;
+
+pub const FICHIER_HISTORIQUE: &str = "banque.historique";
+pub const JOURS_AFFICHES: u64 = 30;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Releve {
+    pub jour: u64,
+    pub compte: usize, // position du compte dans la liste, comme dans le journal
+    pub solde: f64,    // solde en fin de journée
+    pub nom: String,
+}
+
+impl Releve {
+    pub fn ligne(&self) -> String {
+        format!("{};{};{:.2};{}", self.jour, self.compte, self.solde, self.nom.replace('\n', " "))
+    }
+
+    // Relire une ligne de l'historique ; None si elle est illisible
+    pub fn lire(ligne: &str) -> Option<Releve> {
+        let mut champs = ligne.trim_end_matches(['\r', '\n']).splitn(4, ';');
+        Some(Releve {
+            jour: champs.next()?.parse().ok()?,
+            compte: champs.next()?.parse().ok()?,
+            solde: champs.next()?.parse().ok()?,
+            nom: champs.next()?.to_string(),
+        })
+    }
+}
+
+// Jour courant (UTC), compté depuis le 1er janvier 1970
+pub fn aujourd_hui() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() / 86400).unwrap_or(0)
+}
+
+pub fn ajouter(chemin: &str, releves: &[Releve]) -> io::Result<()> {
+    let mut fichier = OpenOptions::new().create(true).append(true).open(chemin)?;
+    for releve in releves {
+        writeln!(fichier, "{}", releve.ligne())?;
+    }
+    Ok(())
+}
+
+// Tous les relevés, dans l'ordre ; les lignes illisibles sont ignorées
+pub fn lire_tout(chemin: &str) -> io::Result<Vec<Releve>> {
+    let texte = std::fs::read_to_string(chemin)?;
+    Ok(texte.lines().filter_map(Releve::lire).collect())
+}
+
+// Repère les fins de journée : le menu lui demande régulièrement quels jours se sont terminés
+pub struct Planificateur {
+    jour: u64, // jour en cours au dernier appel
+}
+
+impl Planificateur {
+    pub fn new(aujourd_hui: u64) -> Planificateur {
+        Planificateur { jour: aujourd_hui }
+    }
+
+    // Jours terminés depuis le dernier appel ; plusieurs si la machine est restée en veille
+    pub fn jours_termines(&mut self, aujourd_hui: u64) -> Range<u64> {
+        let termines = self.jour..aujourd_hui.max(self.jour);
+        self.jour = termines.end;
+        termines
+    }
+}
+
+// Solde du compte pour chacun des `jours` jours qui finissent par `dernier_jour` (None : pas de relevé ce jour-là)
+pub fn evolution(releves: &[Releve], compte: usize, dernier_jour: u64, jours: u64) -> Vec<Option<f64>> {
+    let premier_jour = (dernier_jour + 1).saturating_sub(jours);
+    let mut soldes = vec![None; (dernier_jour + 1 - premier_jour) as usize];
+    for releve in releves {
+        if releve.compte == compte && (premier_jour..=dernier_jour).contains(&releve.jour) {
+            soldes[(releve.jour - premier_jour) as usize] = Some(releve.solde);
+        }
+    }
+    soldes
+}
+
+// Graphique ASCII des soldes, un point par jour à partir de `premier_jour`, sur `hauteur` lignes ;
+// rien s'il n'y a aucun solde
+pub fn graphique(soldes: &[Option<f64>], premier_jour: u64, hauteur: usize) -> Vec<String> {
+    let valeurs: Vec<f64> = soldes.iter().flatten().copied().collect();
+    let (Some(min), Some(max)) = (valeurs.iter().copied().reduce(f64::min), valeurs.iter().copied().reduce(f64::max)) else {
+        return Vec::new();
+    };
+    // Un solde qui n'a pas changé tient sur une seule ligne
+    let hauteur = if max > min { hauteur.max(2) } else { 1 };
+    let niveau = |solde: f64| match hauteur {
+        1 => 0,
+        _ => ((solde - min) / (max - min) * (hauteur - 1) as f64).round() as usize,
+    };
+
+    let mut lignes = Vec::new();
+    for rang in (0..hauteur).rev() {
+        let valeur = match hauteur {
+            1 => min,
+            _ => min + (max - min) * rang as f64 / (hauteur - 1) as f64,
+        };
+        let mut ligne = format!("{:>10.2} |", valeur);
+        for solde in soldes {
+            ligne.push_str(match solde {
+                Some(solde) if niveau(*solde) == rang => " *",
+                _ => "  ",
+            });
+        }
+        lignes.push(ligne.trim_end().to_string());
+    }
+    lignes.push(format!("{:>10} +{}", "", "--".repeat(soldes.len())));
+    let dernier_jour = premier_jour + soldes.len().saturating_sub(1) as u64;
+    let debut = journal::date_du_jour(premier_jour);
+    let fin = journal::date_du_jour(dernier_jour);
+    let largeur = (soldes.len() * 2).saturating_sub(debut.len()).max(fin.len() + 1);
+    lignes.push(format!("{:>10}  {}{:>largeur$}", "", debut, fin));
+    lignes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_releves_et_graphique() {
+        let releve = Releve { jour: 19783, compte: 2, solde: 750.06, nom: "Fatou; épargne".to_string() };
+        assert_eq!(Releve::lire(&releve.ligne()), Some(releve.clone()));
+
+        let mut planificateur = Planificateur::new(100);
+        assert_eq!(planificateur.jours_termines(100), 100..100);
+        assert_eq!(planificateur.jours_termines(103), 100..103);
+        assert_eq!(planificateur.jours_termines(103), 103..103);
+
+        let releves = vec![
+            Releve { jour: 19780, compte: 2, solde: 100.0, nom: "Fatou".to_string() },
+            Releve { jour: 19782, compte: 2, solde: 200.0, nom: "Fatou".to_string() },
+            Releve { jour: 19782, compte: 0, solde: 5.0, nom: "Kevin".to_string() },
+            Releve { jour: 19700, compte: 2, solde: 1.0, nom: "Fatou".to_string() },
+        ];
+        let soldes = evolution(&releves, 2, 19783, 4);
+        assert_eq!(soldes, vec![Some(100.0), None, Some(200.0), None]);
+
+        let lignes = graphique(&soldes, 19780, 3);
+        assert_eq!(lignes[0], "    200.00 |     *");
+        assert_eq!(lignes[1], "    150.00 |");
+        assert_eq!(lignes[2], "    100.00 | *");
+        assert_eq!(lignes[3], "           +--------");
+        assert!(lignes[4].contains("2024-02-27") && lignes[4].ends_with("2024-03-01"));
+        assert!(graphique(&[None, None], 19780, 3).is_empty());
+        assert_eq!(graphique(&[Some(5.0), Some(5.0)], 19780, 3)[0], "      5.00 | * *");
+    }
+}
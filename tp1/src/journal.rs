@@ -0,0 +1,198 @@
+// Journal des opérations (journal d'audit) : le menu ajoute une ligne par événement dans banque.journal,
+// avec qui l'a fait (le titulaire du compte qui agit, et l'utilisateur du menu) et le solde avant et après. L'observateur (cargo run --bin observateur) le relit
+// pour afficher les soldes en direct, et le menu pour afficher l'historique
+
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::messages::{euros, Messages};
+
+pub const FICHIER_JOURNAL: &str = "banque.journal";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operation {
+    Ouverture, // état d'un compte au lancement du menu
+    Depot,
+    Retrait,
+    Renommage,
+    VirementEmis, // le compte est la source du virement
+    VirementRecu, // le compte est la destination
+    Interets,     // intérêts d'un compte d'épargne, versés en fin de journée
+}
+
+impl Operation {
+    pub fn nom(&self) -> &'static str {
+        match self {
+            Operation::Ouverture => "ouverture",
+            Operation::Depot => "depot",
+            Operation::Retrait => "retrait",
+            Operation::Renommage => "renommage",
+            Operation::VirementEmis => "virement_emis",
+            Operation::VirementRecu => "virement_recu",
+            Operation::Interets => "interets",
+        }
+    }
+
+    // Effet de l'opération sur le solde du compte
+    pub fn variation(&self, montant: f64) -> f64 {
+        match self {
+            Operation::Depot | Operation::VirementRecu | Operation::Interets => montant,
+            Operation::Retrait | Operation::VirementEmis => -montant,
+            Operation::Ouverture | Operation::Renommage => 0.0,
+        }
+    }
+
+    fn depuis_nom(nom: &str) -> Option<Operation> {
+        match nom {
+            "ouverture" => Some(Operation::Ouverture),
+            "depot" => Some(Operation::Depot),
+            "retrait" => Some(Operation::Retrait),
+            "renommage" => Some(Operation::Renommage),
+            "virement_emis" => Some(Operation::VirementEmis),
+            "virement_recu" => Some(Operation::VirementRecu),
+            "interets" => Some(Operation::Interets),
+            _ => None,
+        }
+    }
+}
+
+// Une ligne du journal : "horodatage;auteur;titulaire;operation;compte;montant;ancien_solde;solde;nom"
+// (le nom en dernier, il peut contenir des ';')
+#[derive(Debug, Clone, PartialEq)]
+pub struct Evenement {
+    pub horodatage: u64, // secondes depuis 1970 (UTC)
+    pub auteur: String,    // qui a fait l'opération : l'utilisateur du menu, ou "banque" (intérêts)
+    pub titulaire: String, // titulaire du compte au nom duquel elle a été faite ; vide sans titulaire (ouverture, intérêts)
+    pub operation: Operation,
+    pub compte: usize, // position du compte dans la liste (le nom peut changer)
+    pub montant: f64,
+    pub ancien_solde: f64, // solde avant l'opération
+    pub solde: f64,        // solde après l'opération
+    pub nom: String,
+}
+
+impl Evenement {
+    // L'ancien solde est déduit du nouveau et de l'opération
+    pub fn maintenant(auteur: &str, operation: Operation, compte: usize, nom: &str, montant: f64, solde: f64) -> Evenement {
+        let horodatage = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        Evenement {
+            horodatage,
+            auteur: auteur.to_string(),
+            titulaire: String::new(),
+            operation,
+            compte,
+            montant,
+            ancien_solde: solde - operation.variation(montant),
+            solde,
+            nom: nom.to_string(),
+        }
+    }
+
+    pub fn avec_titulaire(self, titulaire: &str) -> Evenement {
+        Evenement { titulaire: titulaire.to_string(), ..self }
+    }
+
+    pub fn ligne(&self) -> String {
+        format!(
+            "{};{};{};{};{};{:.2};{:.2};{:.2};{}",
+            self.horodatage,
+            self.auteur.replace([';', '\n'], " "),
+            self.titulaire.replace([';', '\n'], " "),
+            self.operation.nom(),
+            self.compte,
+            self.montant,
+            self.ancien_solde,
+            self.solde,
+            self.nom.replace('\n', " ")
+        )
+    }
+
+    // Relire une ligne du journal ; None si elle est illisible
+    pub fn lire(ligne: &str) -> Option<Evenement> {
+        let mut champs = ligne.trim_end_matches(['\r', '\n']).splitn(9, ';');
+        Some(Evenement {
+            horodatage: champs.next()?.parse().ok()?,
+            auteur: champs.next()?.to_string(),
+            titulaire: champs.next()?.to_string(),
+            operation: Operation::depuis_nom(champs.next()?)?,
+            compte: champs.next()?.parse().ok()?,
+            montant: champs.next()?.parse().ok()?,
+            ancien_solde: champs.next()?.parse().ok()?,
+            solde: champs.next()?.parse().ok()?,
+            nom: champs.next()?.to_string(),
+        })
+    }
+
+    // Heure de l'événement (UTC), pour l'affichage
+    pub fn heure(&self) -> String {
+        let secondes = self.horodatage % 86400;
+        format!("{:02}:{:02}:{:02}", secondes / 3600, secondes % 3600 / 60, secondes % 60)
+    }
+
+    // Date et heure de l'événement (UTC) : "2024-03-01 12:00:05"
+    pub fn date(&self) -> String {
+        format!("{} {}", date_du_jour(self.horodatage / 86400), self.heure())
+    }
+
+    // Qui a fait l'opération : "Amina (amina)" si un titulaire a agi, sinon l'auteur seul
+    pub fn acteur(&self, messages: &Messages) -> String {
+        match self.titulaire.is_empty() {
+            true => self.auteur.clone(),
+            false => messages.avec("journal.acteur", &[("titulaire", &self.titulaire), ("auteur", &self.auteur)]),
+        }
+    }
+
+    // L'opération en quelques mots : "Dépôt de 50.00 €"
+    pub fn description(&self, messages: &Messages) -> String {
+        messages.avec(&format!("operation.{}", self.operation.nom()), &[("montant", &euros(self.montant))])
+    }
+}
+
+// Date (UTC) d'un jour compté depuis le 1er janvier 1970 : "2024-03-01"
+pub fn date_du_jour(jour: u64) -> String {
+    // Conversion en date du calendrier (algorithme de H. Hinnant)
+    let jours = jour as i64 + 719468;
+    let ere = jours / 146097;
+    let jour_ere = jours - ere * 146097;
+    let annee_ere = (jour_ere - jour_ere / 1460 + jour_ere / 36524 - jour_ere / 146096) / 365;
+    let jour_annee = jour_ere - (365 * annee_ere + annee_ere / 4 - annee_ere / 100);
+    let mois_decale = (5 * jour_annee + 2) / 153;
+    let jour = jour_annee - (153 * mois_decale + 2) / 5 + 1;
+    let mois = if mois_decale < 10 { mois_decale + 3 } else { mois_decale - 9 };
+    let annee = annee_ere + ere * 400 + if mois <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02}", annee, mois, jour)
+}
+
+// Tous les événements du journal, dans l'ordre ; les lignes illisibles sont ignorées
+pub fn lire_tout(chemin: &str) -> io::Result<Vec<Evenement>> {
+    let texte = std::fs::read_to_string(chemin)?;
+    Ok(texte.lines().filter_map(Evenement::lire).collect())
+}
+
+// Ajouter un événement à la fin du journal
+pub fn ajouter(chemin: &str, evenement: &Evenement) -> io::Result<()> {
+    let mut fichier = OpenOptions::new().create(true).append(true).open(chemin)?;
+    writeln!(fichier, "{}", evenement.ligne())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ligne_du_journal() {
+        let evenement = Evenement::maintenant("amina", Operation::Retrait, 1, "Nourdine; compte joint", 20.5, 979.5).avec_titulaire("Nourdine");
+        assert_eq!(evenement.ancien_solde, 1000.0);
+        let relu = Evenement::lire(&evenement.ligne()).unwrap();
+        assert_eq!(relu, evenement);
+        assert_eq!(Evenement::lire("12;amina;Kevin;virement;0;1.00;3.00;2.00;Kevin"), None);
+        assert_eq!(Evenement::lire("pas une ligne"), None);
+
+        let midi = Evenement { horodatage: 86400 * 3 + 12 * 3600 + 5, ..evenement.clone() };
+        assert_eq!(midi.heure(), "12:00:05");
+        assert_eq!(midi.date(), "1970-01-04 12:00:05");
+        let bissextile = Evenement { horodatage: 1709208000, ..evenement };
+        assert_eq!(bissextile.date(), "2024-02-29 12:00:00");
+    }
+}
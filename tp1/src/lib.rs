@@ -0,0 +1,8 @@
+// Code commun au menu de la banque et à l'observateur
+
+pub mod audit;
+pub mod banque;
+pub mod historique;
+pub mod journal;
+pub mod menu;
+pub mod messages;
@@ -1,148 +1,64 @@
-use std::io;
+// cargo run --bin tp1 -- [--auteur NOM] [--audit-serveur ADRESSE] [--lang fr|en]
+//   --auteur : nom noté dans le journal d'audit pour chaque opération (par défaut, l'utilisateur du système)
+//   --audit-serveur : serveur de logs du TP3 (par exemple 127.0.0.1:8080) qui reçoit aussi le journal
+//   --lang : langue des menus et des messages (par défaut, celle de la variable LANG, sinon le français)
 
-#[derive(Clone)]
-struct CompteBancaire {
-    nom: String,
-    solde: f64,
-}
-
-impl CompteBancaire {
-    fn afficher_solde(&self) {
-        println!("{} a un solde de {:.2} €", self.nom, self.solde);
-    }
-
-    fn retirer(&mut self, montant: f64) {
-        if montant > 0.0 && montant <= self.solde {
-            self.solde -= montant;
-            println!("{} a retiré {:.2} €. Nouveau solde : {:.2} €", self.nom, montant, self.solde);
-        } else {
-            println!("Montant invalide ou solde insuffisant.");
-        }
-    }
-
-    fn deposer(&mut self, montant: f64) {
-        if montant > 0.0 {
-            self.solde += montant;
-            println!("{} a déposé {:.2} €. Nouveau solde : {:.2} €", self.nom, montant, self.solde);
-        } else {
-            println!("Le dépôt doit être positif !");
-        }
-    }
-
-    fn renommer(&self, nouveau_nom: &str) -> CompteBancaire {
-        CompteBancaire {
-            nom: nouveau_nom.to_string(),
-            solde: self.solde,
-        }
-    }
-}
+use std::sync::{Arc, Mutex};
+use tp1::audit::Audit;
+use tp1::banque::{Banque, CompteBancaire, Droit, Plafonds, Titulaire};
+use tp1::journal::FICHIER_JOURNAL;
+use tp1::menu::{Menu, StdinInput};
+use tp1::messages::{Langue, Messages};
 
 fn main() {
-    let mut comptes = vec![
-        CompteBancaire { nom: "Kevin".to_string(), solde: 500.0 },
-        CompteBancaire { nom: "Nourdine".to_string(), solde: 1000.0 },
-        CompteBancaire { nom: "Fatou".to_string(), solde: 750.0 },
-    ];
-
-    loop {
-        println!("\n--- MENU ---");
-        println!("1 - Liste des comptes");
-        println!("2 - Afficher solde d’un compte");
-        println!("3 - Dépôt");
-        println!("4 - Retrait");
-        println!("5 - Renommer un compte");
-        println!("6 - Quitter");
-
-        println!("Entrez le numéro de votre choix :");
-
-        let mut choix = String::new();
-        io::stdin().read_line(&mut choix).expect("Erreur de lecture");
-        let choix: u32 = match choix.trim().parse() {
-            Ok(num) => num,
-            Err(_) => {
-                println!("Choix invalide.");
-                continue;
-            }
-        };
-
-        match choix {
-            1 => {
-                println!("Liste des comptes :");
-                for (i, compte) in comptes.iter().enumerate() {
-                    println!("{}. {}", i + 1, compte.nom);
-                }
-            },
-            2 => {
-                let index = choisir_compte(&comptes);
-                if let Some(i) = index {
-                    comptes[i].afficher_solde();
-                }
-            },
-            3 => {
-                let index = choisir_compte(&comptes);
-                if let Some(i) = index {
-                    println!("Montant à déposer : ");
-                    if let Some(montant) = lire_f64() {
-                        comptes[i].deposer(montant);
-                    }
-                }
-            },
-            4 => {
-                let index = choisir_compte(&comptes);
-                if let Some(i) = index {
-                    println!("Montant à retirer : ");
-                    if let Some(montant) = lire_f64() {
-                        comptes[i].retirer(montant);
-                    }
-                }
-            },
-            5 => {
-                let index = choisir_compte(&comptes);
-                if let Some(i) = index {
-                    println!("Nouveau nom pour le compte : ");
-                    let mut nouveau_nom = String::new();
-                    io::stdin().read_line(&mut nouveau_nom).expect("Erreur");
-                    let nouveau_nom = nouveau_nom.trim();
-                    comptes[i] = comptes[i].renommer(nouveau_nom);
-                    println!("Compte renommé avec succès.");
-                }
-            },
-            6 => {
-                println!("Au revoir !");
-                break;
+    let mut auteur = std::env::var("USER").or_else(|_| std::env::var("USERNAME")).unwrap_or_else(|_| "inconnu".to_string());
+    let mut serveur = None;
+    let mut langue = Langue::depuis_environnement();
+    let mut arguments = std::env::args().skip(1);
+    let mut usage = false;
+    while let Some(argument) = arguments.next() {
+        match (argument.as_str(), arguments.next()) {
+            ("--auteur", Some(nom)) => auteur = nom,
+            ("--audit-serveur", Some(adresse)) => serveur = Some(adresse),
+            ("--lang", Some(code)) => match Langue::depuis_code(&code) {
+                Some(choisie) => langue = choisie,
+                None => usage = true,
             },
-            _ => println!("Option invalide."),
+            _ => usage = true,
         }
     }
-}
-
-// Fonction utilitaire pour choisir un compte
-fn choisir_compte(comptes: &[CompteBancaire]) -> Option<usize> {
-    println!("Sélectionnez un compte :");
-    for (i, compte) in comptes.iter().enumerate() {
-        println!("{} - {}", i + 1, compte.nom);
+    if usage {
+        println!("{}", Messages::charger(langue).texte("usage"));
+        return;
     }
+    let audit = Arc::new(Mutex::new(Audit::new(FICHIER_JOURNAL, serveur)));
 
-    let mut choix = String::new();
-    io::stdin().read_line(&mut choix).expect("Erreur");
-    match choix.trim().parse::<usize>() {
-        Ok(num) if num >= 1 && num <= comptes.len() => Some(num - 1),
-        _ => {
-            println!("Choix invalide.");
-            None
-        }
-    }
-}
+    let banque = Arc::new(Banque::new(vec![
+        CompteBancaire::new("Kevin", 500.0).avec_plafonds(Plafonds {
+            retrait: Some(300.0),
+            retraits_par_jour: Some(500.0),
+            solde: None,
+        }),
+        // Compte joint : Nourdine et Amina peuvent tout faire
+        CompteBancaire::new("Nourdine", 1000.0)
+            .avec_plafonds(Plafonds {
+                retrait: None,
+                retraits_par_jour: Some(1000.0),
+                solde: None,
+            })
+            .avec_titulaires(vec![Titulaire::new("Nourdine", Droit::Complet), Titulaire::new("Amina", Droit::Complet)]),
+        // Livret d'épargne : solde plafonné, comme un livret A ; Nourdine peut le consulter
+        CompteBancaire::epargne("Fatou", 750.0, 0.03)
+            .avec_plafonds(Plafonds {
+                retrait: None,
+                retraits_par_jour: None,
+                solde: Some(22950.0),
+            })
+            .avec_titulaires(vec![Titulaire::new("Fatou", Droit::Complet), Titulaire::new("Nourdine", Droit::Consultation)]),
+    ]));
 
-// Lire un nombre flottant (montant)
-fn lire_f64() -> Option<f64> {
-    let mut entree = String::new();
-    io::stdin().read_line(&mut entree).expect("Erreur");
-    match entree.trim().parse::<f64>() {
-        Ok(val) => Some(val),
-        Err(_) => {
-            println!("Entrée invalide.");
-            None
-        }
-    }
+    let menu = Arc::new(Menu::new(banque, audit, &auteur, Messages::charger(langue)));
+    menu.ouvrir();
+    menu.planifier_fins_de_journee();
+    menu.executer(&mut StdinInput);
 }
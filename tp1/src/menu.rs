@@ -0,0 +1,494 @@
+// Menu de la banque. Toutes les saisies passent par le trait Input : StdinInput lit le terminal,
+// TestInput rejoue une liste de saisies, pour tester le menu de bout en bout sans terminal
+
+use std::collections::VecDeque;
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crate::audit::Audit;
+use crate::banque::{Banque, Droit};
+use crate::historique::{self, Planificateur, Releve, FICHIER_HISTORIQUE, JOURS_AFFICHES};
+use crate::journal::{self, Evenement, Operation};
+use crate::messages::{euros, Messages};
+
+pub trait Input {
+    // Ligne suivante, sans le retour à la ligne ; None quand il n'y a plus rien à lire
+    fn lire_ligne(&mut self) -> Option<String>;
+}
+
+pub struct StdinInput;
+
+impl Input for StdinInput {
+    fn lire_ligne(&mut self) -> Option<String> {
+        let mut ligne = String::new();
+        // Une erreur de lecture (terminal fermé) termine les saisies, comme la fin de l'entrée
+        match io::stdin().read_line(&mut ligne) {
+            Ok(0) | Err(_) => None,
+            Ok(_) => Some(ligne.trim_end_matches(['\r', '\n']).to_string()),
+        }
+    }
+}
+
+// Saisies écrites à l'avance, lues dans l'ordre
+#[derive(Default)]
+pub struct TestInput {
+    lignes: VecDeque<String>,
+}
+
+impl TestInput {
+    pub fn new(lignes: &[&str]) -> TestInput {
+        TestInput { lignes: lignes.iter().map(|ligne| ligne.to_string()).collect() }
+    }
+
+    pub fn restantes(&self) -> usize {
+        self.lignes.len()
+    }
+}
+
+impl Input for TestInput {
+    fn lire_ligne(&mut self) -> Option<String> {
+        self.lignes.pop_front()
+    }
+}
+
+pub struct Menu {
+    pub banque: Arc<Banque>,
+    pub audit: Arc<Mutex<Audit>>,
+    pub auteur: String,     // noté au journal pour chaque opération faite depuis le menu
+    pub historique: String, // chemin de l'historique des soldes
+    pub messages: Messages, // textes dans la langue de l'utilisateur
+}
+
+impl Menu {
+    pub fn new(banque: Arc<Banque>, audit: Arc<Mutex<Audit>>, auteur: &str, messages: Messages) -> Menu {
+        Menu { banque, audit, auteur: auteur.to_string(), historique: FICHIER_HISTORIQUE.to_string(), messages }
+    }
+
+    fn afficher(&self, cle: &str) {
+        println!("{}", self.messages.texte(cle));
+    }
+
+    // État de départ des comptes, pour l'observateur
+    pub fn ouvrir(&self) {
+        for i in 0..self.banque.nombre() {
+            self.noter_par(&self.auteur, "", Operation::Ouverture, i, 0.0);
+        }
+    }
+
+    // Le menu, jusqu'à « Quitter » ou la fin des saisies. Il commence par demander quel titulaire l'utilise :
+    // chaque opération est faite (et notée au journal) en son nom, sur les comptes où il en a le droit
+    pub fn executer(&self, entree: &mut dyn Input) {
+        let Some(mut titulaire) = self.choisir_titulaire(entree) else {
+            self.afficher("menu.au_revoir");
+            return;
+        };
+        loop {
+            println!();
+            self.afficher("menu.titre");
+            println!("{}", self.messages.avec("menu.titulaire_actif", &[("nom", &titulaire)]));
+            for cle in [
+                "menu.liste",
+                "menu.solde",
+                "menu.depot",
+                "menu.retrait",
+                "menu.virement",
+                "menu.renommer",
+                "menu.journal",
+                "menu.evolution",
+                "menu.plafonds",
+                "menu.titulaire",
+                "menu.quitter",
+            ] {
+                self.afficher(cle);
+            }
+
+            self.afficher("menu.choix");
+
+            let Some(choix) = entree.lire_ligne() else {
+                self.afficher("menu.au_revoir");
+                break;
+            };
+            let choix: u32 = match choix.trim().parse() {
+                Ok(num) => num,
+                Err(_) => {
+                    self.afficher("menu.choix_invalide");
+                    continue;
+                }
+            };
+
+            match choix {
+                1 => {
+                    self.afficher("liste.titre");
+                    for (i, compte) in self.banque.comptes().iter().enumerate() {
+                        let Some(droit) = compte.droit_de(&titulaire) else { continue };
+                        let cle = if compte.est_epargne() { "liste.epargne" } else { "liste.compte" };
+                        let taux = euros(compte.taux * 100.0);
+                        let mut ligne = self.messages.avec(cle, &[("numero", &(i + 1)), ("nom", &compte.nom), ("taux", &taux)]);
+                        if droit == Droit::Consultation {
+                            ligne = format!("{} {}", ligne, self.messages.texte("liste.consultation"));
+                        }
+                        println!("{}", ligne);
+                    }
+                },
+                2 => {
+                    let index = self.choisir_compte(entree, &titulaire, Some(Droit::Consultation));
+                    if let Some(i) = index
+                        && let Ok(compte) = self.banque.consulter(i)
+                    {
+                        compte.afficher_solde(&self.messages);
+                    }
+                },
+                3 => {
+                    let index = self.choisir_compte(entree, &titulaire, Some(Droit::Complet));
+                    if let Some(i) = index {
+                        self.afficher("depot.montant");
+                        if let Some(montant) = self.lire_f64(entree) {
+                            match self.banque.deposer(i, montant) {
+                                Ok(solde) => {
+                                    println!("{}", self.messages.avec("depot.effectue", &[("montant", &euros(montant)), ("solde", &euros(solde))]));
+                                    self.noter(&titulaire, Operation::Depot, i, montant);
+                                }
+                                Err(e) => println!("{}", e.message(&self.messages)),
+                            }
+                        }
+                    }
+                },
+                4 => {
+                    let index = self.choisir_compte(entree, &titulaire, Some(Droit::Complet));
+                    if let Some(i) = index {
+                        self.afficher("retrait.montant");
+                        if let Some(montant) = self.lire_f64(entree) {
+                            match self.banque.retirer(i, montant) {
+                                Ok(solde) => {
+                                    println!("{}", self.messages.avec("retrait.effectue", &[("montant", &euros(montant)), ("solde", &euros(solde))]));
+                                    self.noter(&titulaire, Operation::Retrait, i, montant);
+                                }
+                                Err(e) => println!("{}", e.message(&self.messages)),
+                            }
+                        }
+                    }
+                },
+                5 => {
+                    self.afficher("virement.debit");
+                    let Some(de) = self.choisir_compte(entree, &titulaire, Some(Droit::Complet)) else { continue };
+                    self.afficher("virement.credit");
+                    // Un virement peut aller vers n'importe quel compte de la banque
+                    let Some(vers) = self.choisir_compte(entree, &titulaire, None) else { continue };
+                    self.afficher("virement.montant");
+                    if let Some(montant) = self.lire_f64(entree) {
+                        match self.banque.virer(de, vers, montant) {
+                            Ok((solde_source, solde_destination)) => {
+                                println!("{}", self.messages.avec("virement.effectue", &[
+                                    ("montant", &euros(montant)),
+                                    ("source", &euros(solde_source)),
+                                    ("destination", &euros(solde_destination)),
+                                ]));
+                                self.noter(&titulaire, Operation::VirementEmis, de, montant);
+                                self.noter(&titulaire, Operation::VirementRecu, vers, montant);
+                            }
+                            Err(e) => println!("{}", e.message(&self.messages)),
+                        }
+                    }
+                },
+                6 => {
+                    let index = self.choisir_compte(entree, &titulaire, Some(Droit::Complet));
+                    if let Some(i) = index {
+                        self.afficher("renommage.nom");
+                        let Some(nouveau_nom) = entree.lire_ligne() else { continue };
+                        match self.banque.renommer(i, nouveau_nom.trim()) {
+                            Ok(()) => {
+                                self.afficher("renommage.effectue");
+                                self.noter(&titulaire, Operation::Renommage, i, 0.0);
+                            }
+                            Err(e) => println!("{}", e.message(&self.messages)),
+                        }
+                    }
+                },
+                7 => self.afficher_journal(&titulaire),
+                8 => {
+                    let index = self.choisir_compte(entree, &titulaire, Some(Droit::Consultation));
+                    if let Some(i) = index {
+                        self.afficher_evolution(i);
+                    }
+                },
+                9 => {
+                    let index = self.choisir_compte(entree, &titulaire, Some(Droit::Consultation));
+                    if let Some(i) = index {
+                        self.afficher_plafonds(i);
+                    }
+                },
+                10 => {
+                    let Some(nouveau) = self.choisir_titulaire(entree) else { continue };
+                    titulaire = nouveau;
+                },
+                11 => {
+                    self.afficher("menu.au_revoir");
+                    break;
+                },
+                _ => self.afficher("menu.option_invalide"),
+            }
+        }
+    }
+
+    // Ajouter l'opération faite par le titulaire au journal d'audit ; le menu continue même si l'écriture ou l'envoi échoue
+    fn noter(&self, titulaire: &str, operation: Operation, index: usize, montant: f64) {
+        self.noter_par(&self.auteur, titulaire, operation, index, montant);
+    }
+
+    fn noter_par(&self, auteur: &str, titulaire: &str, operation: Operation, index: usize, montant: f64) {
+        let Ok(compte) = self.banque.consulter(index) else { return };
+        let evenement = Evenement::maintenant(auteur, operation, index, &compte.nom, montant, compte.solde).avec_titulaire(titulaire);
+        let problemes = self.audit.lock().unwrap_or_else(|e| e.into_inner()).noter(&evenement);
+        for probleme in problemes {
+            println!("{}", probleme.message(&self.messages));
+        }
+    }
+
+    // Afficher les opérations notées sur les comptes que le titulaire peut consulter,
+    // de la plus ancienne à la plus récente
+    fn afficher_journal(&self, titulaire: &str) {
+        let comptes = self.banque.comptes();
+        let visible = |index: usize| comptes.get(index).is_some_and(|compte| compte.autorise(titulaire, Droit::Consultation));
+        let audit = self.audit.lock().unwrap_or_else(|e| e.into_inner());
+        let evenements = match journal::lire_tout(audit.chemin()) {
+            Ok(evenements) => evenements,
+            Err(e) => {
+                println!("{}", self.messages.avec("journal.illisible", &[("chemin", &audit.chemin()), ("erreur", &e)]));
+                return;
+            }
+        };
+        self.afficher("journal.titre");
+        for evenement in evenements.iter().filter(|evenement| evenement.operation != Operation::Ouverture && visible(evenement.compte)) {
+            println!("{}", self.messages.avec("journal.ligne", &[
+                ("date", &evenement.date()),
+                ("acteur", &evenement.acteur(&self.messages)),
+                ("nom", &evenement.nom),
+                ("operation", &evenement.description(&self.messages)),
+                ("ancien", &euros(evenement.ancien_solde)),
+                ("solde", &euros(evenement.solde)),
+            ]));
+        }
+    }
+
+    // Fins de journée, vérifiées toutes les 30 secondes pendant que le menu tourne
+    pub fn planifier_fins_de_journee(self: &Arc<Self>) {
+        let menu = Arc::clone(self);
+        let mut planificateur = Planificateur::new(historique::aujourd_hui());
+        thread::spawn(move || loop {
+            thread::sleep(Duration::from_secs(30));
+            for jour in planificateur.jours_termines(historique::aujourd_hui()) {
+                menu.fin_de_journee(jour);
+            }
+        });
+    }
+
+    // Intérêts du jour sur les comptes d'épargne, puis relevé des soldes de fin de journée
+    pub fn fin_de_journee(&self, jour: u64) {
+        for i in 0..self.banque.nombre() {
+            if let Ok(Some((interets, _))) = self.banque.verser_interets(i) {
+                self.noter_par("banque", "", Operation::Interets, i, interets);
+            }
+        }
+        let releves: Vec<Releve> = self
+            .banque
+            .comptes()
+            .into_iter()
+            .enumerate()
+            .map(|(i, compte)| Releve { jour, compte: i, solde: compte.solde, nom: compte.nom })
+            .collect();
+        if let Err(e) = historique::ajouter(&self.historique, &releves) {
+            println!("{}", self.messages.avec("historique.non_mis_a_jour", &[("chemin", &self.historique), ("erreur", &e)]));
+        }
+    }
+
+    // Graphique du solde du compte sur les 30 derniers jours ; aujourd'hui, le solde actuel
+    fn afficher_evolution(&self, index: usize) {
+        let releves = match historique::lire_tout(&self.historique) {
+            Ok(releves) => releves,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => {
+                println!("{}", self.messages.avec("historique.illisible", &[("chemin", &self.historique), ("erreur", &e)]));
+                return;
+            }
+        };
+        let Ok(compte) = self.banque.consulter(index) else { return };
+        let aujourd_hui = historique::aujourd_hui();
+        let mut soldes = historique::evolution(&releves, index, aujourd_hui, JOURS_AFFICHES);
+        if let Some(dernier) = soldes.last_mut() {
+            *dernier = Some(compte.solde);
+        }
+        println!("{}", self.messages.avec("evolution.titre", &[("nom", &compte.nom), ("jours", &JOURS_AFFICHES)]));
+        let lignes = historique::graphique(&soldes, aujourd_hui + 1 - soldes.len() as u64, 10);
+        if lignes.is_empty() {
+            self.afficher("evolution.vide");
+        }
+        for ligne in lignes {
+            println!("{}", ligne);
+        }
+    }
+
+    // Plafonds du compte et ce qui peut encore être retiré aujourd'hui
+    fn afficher_plafonds(&self, index: usize) {
+        let Ok(compte) = self.banque.consulter(index) else { return };
+        let plafond = |montant: Option<f64>| match montant {
+            Some(montant) => format!("{} €", euros(montant)),
+            None => self.messages.texte("plafonds.sans_limite"),
+        };
+        println!("{}", self.messages.avec("plafonds.titre", &[("nom", &compte.nom)]));
+        println!("{}", self.messages.avec("plafonds.retrait", &[("plafond", &plafond(compte.plafonds.retrait))]));
+        println!("{}", self.messages.avec("plafonds.retraits_par_jour", &[("plafond", &plafond(compte.plafonds.retraits_par_jour))]));
+        println!("{}", self.messages.avec("plafonds.solde", &[("plafond", &plafond(compte.plafonds.solde))]));
+        let reste = compte.reste_le(historique::aujourd_hui()).map(|reste| reste.min(compte.solde.max(0.0)));
+        println!("{}", self.messages.avec("plafonds.reste", &[("reste", &plafond(reste))]));
+    }
+
+    // Quel titulaire utilise le menu ; redemandé tant que le choix n'est pas valide
+    fn choisir_titulaire(&self, entree: &mut dyn Input) -> Option<String> {
+        let titulaires = self.banque.titulaires();
+        loop {
+            self.afficher("titulaire.choix");
+            for (i, nom) in titulaires.iter().enumerate() {
+                println!("{}", self.messages.avec("selection.compte", &[("numero", &(i + 1)), ("nom", nom)]));
+            }
+            let choix = entree.lire_ligne()?;
+            match choix.trim().parse::<usize>() {
+                Ok(num) if num >= 1 && num <= titulaires.len() => return Some(titulaires[num - 1].clone()),
+                _ => self.afficher("menu.choix_invalide"),
+            }
+        }
+    }
+
+    // Fonction utilitaire pour choisir un compte parmi ceux où le titulaire a le droit demandé
+    // (tous les comptes sans droit demandé) ; les comptes gardent leur numéro dans la liste complète
+    fn choisir_compte(&self, entree: &mut dyn Input, titulaire: &str, droit: Option<Droit>) -> Option<usize> {
+        let comptes: Vec<(usize, String)> = self
+            .banque
+            .comptes()
+            .into_iter()
+            .enumerate()
+            .filter(|(_, compte)| droit.is_none_or(|droit| compte.autorise(titulaire, droit)))
+            .map(|(i, compte)| (i, compte.nom))
+            .collect();
+        if comptes.is_empty() {
+            println!("{}", self.messages.avec("selection.aucun", &[("titulaire", &titulaire)]));
+            return None;
+        }
+        self.afficher("selection.titre");
+        for (i, nom) in &comptes {
+            println!("{}", self.messages.avec("selection.compte", &[("numero", &(i + 1)), ("nom", nom)]));
+        }
+
+        let choix = entree.lire_ligne()?;
+        match choix.trim().parse::<usize>() {
+            Ok(num) if comptes.iter().any(|(i, _)| i + 1 == num) => Some(num - 1),
+            _ => {
+                self.afficher("menu.choix_invalide");
+                None
+            }
+        }
+    }
+
+    // Lire un nombre flottant (montant)
+    fn lire_f64(&self, entree: &mut dyn Input) -> Option<f64> {
+        let saisie = entree.lire_ligne()?;
+        match saisie.trim().parse::<f64>() {
+            Ok(val) => Some(val),
+            Err(_) => {
+                self.afficher("entree_invalide");
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::banque::{CompteBancaire, Plafonds, Titulaire};
+    use crate::messages::Langue;
+
+    fn menu(nom: &str) -> Menu {
+        let chemin = std::env::temp_dir().join(format!("tp1_menu_{}_{}", nom, std::process::id()));
+        let _ = std::fs::remove_file(chemin.with_extension("journal"));
+        let _ = std::fs::remove_file(chemin.with_extension("historique"));
+        let plafonds = Plafonds { retrait: Some(300.0), retraits_par_jour: Some(500.0), solde: None };
+        // Kevin est aussi titulaire du compte joint de Nourdine, et peut consulter le livret de Fatou
+        let banque = Banque::new(vec![
+            CompteBancaire::new("Kevin", 500.0).avec_plafonds(plafonds),
+            CompteBancaire::new("Nourdine", 1000.0)
+                .avec_titulaires(vec![Titulaire::new("Nourdine", Droit::Complet), Titulaire::new("Kevin", Droit::Complet)]),
+            CompteBancaire::epargne("Fatou", 730.0, 0.03)
+                .avec_titulaires(vec![Titulaire::new("Fatou", Droit::Complet), Titulaire::new("Kevin", Droit::Consultation)]),
+        ]);
+        let audit = Audit::new(chemin.with_extension("journal").to_str().unwrap(), None);
+        let mut menu = Menu::new(Arc::new(banque), Arc::new(Mutex::new(audit)), "amina", Messages::charger(Langue::Francais));
+        menu.historique = chemin.with_extension("historique").to_string_lossy().into_owned();
+        menu
+    }
+
+    fn journal(menu: &Menu) -> Vec<Evenement> {
+        journal::lire_tout(menu.audit.lock().unwrap().chemin()).unwrap()
+    }
+
+    #[test]
+    fn test_parcours_du_menu() {
+        let menu = menu("parcours");
+        menu.ouvrir();
+        let mut entree = TestInput::new(&[
+            "1", // Kevin utilise le menu
+            "3", "1", "50", // dépôt sur Kevin
+            "4", "2", "5000", // retrait refusé sur le compte joint : solde insuffisant
+            "4", "2", "abc", // montant illisible
+            "5", "2", "3", "100", // virement de Nourdine vers Fatou
+            "6", "1", "Kevin L.", // renommage
+            "12", "x", // choix inconnus
+            "4", "1", "400", // retrait refusé : plafond par opération
+            "9", "1", // plafonds de Kevin
+            "3", "3", // dépôt sur le livret de Fatou : Kevin ne peut que le consulter
+            "10", "3", // Fatou prend le menu
+            "4", "1", // le compte de Kevin ne lui est pas proposé
+            "4", "3", "30", // retrait sur son livret
+            "7", "1", "11", "3", // « Quitter » : la dernière saisie n'est pas lue
+        ]);
+        menu.executer(&mut entree);
+        assert_eq!(entree.restantes(), 1);
+
+        let comptes = menu.banque.comptes();
+        assert_eq!((comptes[0].nom.as_str(), comptes[0].solde), ("Kevin L.", 550.0));
+        assert_eq!(comptes[1].solde, 900.0);
+        assert_eq!(comptes[2].solde, 800.0);
+
+        let operations: Vec<(Operation, String)> =
+            journal(&menu).into_iter().skip(3).map(|evenement| (evenement.operation, evenement.titulaire)).collect();
+        assert_eq!(
+            operations,
+            vec![
+                (Operation::Depot, "Kevin".to_string()),
+                (Operation::VirementEmis, "Kevin".to_string()),
+                (Operation::VirementRecu, "Kevin".to_string()),
+                (Operation::Renommage, "Kevin".to_string()),
+                (Operation::Retrait, "Fatou".to_string()),
+            ]
+        );
+        assert!(journal(&menu).iter().all(|evenement| evenement.auteur == "amina"));
+    }
+
+    #[test]
+    fn test_fin_des_saisies_et_fin_de_journee() {
+        let menu = menu("fin");
+        // Plus rien à lire au milieu d'une opération : le menu s'arrête sans rien changer
+        let mut entree = TestInput::new(&["1", "5", "1"]);
+        menu.executer(&mut entree);
+        assert_eq!(menu.banque.consulter(0).unwrap().solde, 500.0);
+
+        menu.fin_de_journee(19783);
+        assert_eq!(menu.banque.consulter(2).unwrap().solde, 730.06);
+        let evenements = journal(&menu);
+        assert_eq!((evenements[0].operation, evenements[0].auteur.as_str()), (Operation::Interets, "banque"));
+        let releves = historique::lire_tout(&menu.historique).unwrap();
+        assert_eq!(releves.len(), 3);
+        assert_eq!(releves[2], Releve { jour: 19783, compte: 2, solde: 730.06, nom: "Fatou".to_string() });
+    }
+}
@@ -0,0 +1,117 @@
+// Textes affichés à l'utilisateur, traduits : un catalogue par langue (lang/fr.txt, lang/en.txt),
+// intégré au programme et chargé au démarrage. La langue vient de --lang, sinon de la variable LANG
+// (fr_FR.UTF-8, en_US.UTF-8...) ; le français par défaut, et pour tout texte absent d'un catalogue
+
+use std::collections::HashMap;
+use std::fmt;
+
+const CATALOGUE_FR: &str = include_str!("../lang/fr.txt");
+const CATALOGUE_EN: &str = include_str!("../lang/en.txt");
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Langue {
+    Francais,
+    Anglais,
+}
+
+impl Langue {
+    // "fr", "en", ou une valeur de LANG comme "en_US.UTF-8"
+    pub fn depuis_code(code: &str) -> Option<Langue> {
+        let code = code.split(['_', '.', '-']).next().unwrap_or("").to_ascii_lowercase();
+        match code.as_str() {
+            "fr" => Some(Langue::Francais),
+            "en" => Some(Langue::Anglais),
+            _ => None,
+        }
+    }
+
+    // Langue de la variable LANG ; le français si elle est absente ou inconnue
+    pub fn depuis_environnement() -> Langue {
+        std::env::var("LANG").ok().and_then(|code| Langue::depuis_code(&code)).unwrap_or(Langue::Francais)
+    }
+
+    fn catalogue(&self) -> &'static str {
+        match self {
+            Langue::Francais => CATALOGUE_FR,
+            Langue::Anglais => CATALOGUE_EN,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Messages {
+    textes: HashMap<String, String>,
+}
+
+impl Messages {
+    pub fn charger(langue: Langue) -> Messages {
+        let mut textes = lire_catalogue(CATALOGUE_FR);
+        textes.extend(lire_catalogue(langue.catalogue()));
+        Messages { textes }
+    }
+
+    // Le texte d'une clé ; la clé elle-même si aucun catalogue ne la connaît
+    pub fn texte(&self, cle: &str) -> String {
+        self.textes.get(cle).cloned().unwrap_or_else(|| cle.to_string())
+    }
+
+    // Le texte d'une clé, ses {noms} remplacés par les valeurs données
+    pub fn avec(&self, cle: &str, valeurs: &[(&str, &dyn fmt::Display)]) -> String {
+        let mut texte = self.texte(cle);
+        for (nom, valeur) in valeurs {
+            texte = texte.replace(&format!("{{{}}}", nom), &valeur.to_string());
+        }
+        texte
+    }
+}
+
+// Un montant tel qu'il est affiché : deux décimales
+pub fn euros(montant: f64) -> String {
+    format!("{:.2}", montant)
+}
+
+// Lignes "cle = texte" ; les lignes vides et celles qui commencent par '#' sont ignorées
+fn lire_catalogue(catalogue: &str) -> HashMap<String, String> {
+    catalogue
+        .lines()
+        .map(str::trim)
+        .filter(|ligne| !ligne.is_empty() && !ligne.starts_with('#'))
+        .filter_map(|ligne| ligne.split_once('='))
+        .map(|(cle, texte)| (cle.trim().to_string(), texte.trim().to_string()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Les {noms} d'un texte, triés
+    fn noms(texte: &str) -> Vec<&str> {
+        let mut noms: Vec<&str> = texte.split('{').skip(1).filter_map(|suite| suite.split_once('}')).map(|(nom, _)| nom).collect();
+        noms.sort();
+        noms
+    }
+
+    #[test]
+    fn test_catalogues() {
+        let francais = lire_catalogue(CATALOGUE_FR);
+        let anglais = lire_catalogue(CATALOGUE_EN);
+        // Chaque texte est traduit, avec les mêmes valeurs à remplacer
+        for (cle, texte) in &francais {
+            let traduction = anglais.get(cle).unwrap_or_else(|| panic!("{} manque dans lang/en.txt", cle));
+            assert_eq!(noms(texte), noms(traduction), "{}", cle);
+        }
+        assert_eq!(francais.len(), anglais.len());
+
+        assert_eq!(Langue::depuis_code("en_US.UTF-8"), Some(Langue::Anglais));
+        assert_eq!(Langue::depuis_code("FR"), Some(Langue::Francais));
+        assert_eq!(Langue::depuis_code("C"), None);
+
+        let messages = Messages::charger(Langue::Anglais);
+        assert_eq!(
+            messages.avec("depot.effectue", &[("montant", &euros(50.0)), ("solde", &euros(550.0))]),
+            "Deposited 50.00 €. New balance: 550.00 €"
+        );
+        assert_eq!(messages.texte("cle.inconnue"), "cle.inconnue");
+    }
+}
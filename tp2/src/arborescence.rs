@@ -0,0 +1,171 @@
+// Arborescence d'un dossier : parcours récursif avec la taille de chaque fichier et la taille
+// cumulée de chaque dossier. Les motifs ignorés ("target/", ".git/", "*.log") écartent des entrées
+// du parcours ; un motif terminé par '/' ne s'applique qu'aux dossiers
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+pub const MOTIFS_PAR_DEFAUT: &str = "target/, .git/";
+
+pub struct Noeud {
+    pub nom: String,
+    pub taille: u64, // taille du fichier, ou taille cumulée du contenu d'un dossier
+    pub dossier: bool,
+    pub illisible: bool, // dossier dont le contenu n'a pas pu être lu
+    pub enfants: Vec<Noeud>,
+}
+
+// Parcourir un dossier (ou un fichier seul) ; les liens symboliques ne sont pas suivis
+pub fn parcourir(chemin: &Path, motifs: &[String]) -> io::Result<Noeud> {
+    let infos = fs::symlink_metadata(chemin)?;
+    let nom = chemin
+        .file_name()
+        .map(|nom| nom.to_string_lossy().to_string())
+        .unwrap_or_else(|| chemin.display().to_string());
+    let mut noeud = Noeud { nom, taille: infos.len(), dossier: infos.is_dir(), illisible: false, enfants: Vec::new() };
+    if !noeud.dossier {
+        return Ok(noeud);
+    }
+
+    noeud.taille = 0;
+    let entrees = match fs::read_dir(chemin) {
+        Ok(entrees) => entrees,
+        Err(_) => {
+            noeud.illisible = true;
+            return Ok(noeud);
+        }
+    };
+    for entree in entrees.flatten() {
+        let dossier = entree.file_type().map(|t| t.is_dir()).unwrap_or(false);
+        if est_ignore(&entree.file_name().to_string_lossy(), dossier, motifs) {
+            continue;
+        }
+        // Une entrée disparue pendant le parcours est simplement omise
+        if let Ok(enfant) = parcourir(&entree.path(), motifs) {
+            noeud.taille += enfant.taille;
+            noeud.enfants.push(enfant);
+        }
+    }
+    // Les dossiers d'abord, puis par nom
+    noeud.enfants.sort_by(|a, b| b.dossier.cmp(&a.dossier).then_with(|| a.nom.cmp(&b.nom)));
+    Ok(noeud)
+}
+
+// "target/, .git/, *.log" -> ["target/", ".git/", "*.log"]
+pub fn lire_motifs(texte: &str) -> Vec<String> {
+    texte.split(',').map(str::trim).filter(|motif| !motif.is_empty()).map(String::from).collect()
+}
+
+pub fn est_ignore(nom: &str, dossier: bool, motifs: &[String]) -> bool {
+    motifs.iter().any(|motif| match motif.strip_suffix('/') {
+        Some(motif) => dossier && correspond(nom, motif),
+        None => correspond(nom, motif),
+    })
+}
+
+// Le nom correspond-il au motif ? '*' remplace n'importe quelle suite de caractères
+fn correspond(nom: &str, motif: &str) -> bool {
+    let mut morceaux = motif.split('*');
+    let debut = morceaux.next().unwrap_or("");
+    let Some(mut reste) = nom.strip_prefix(debut) else {
+        return false;
+    };
+    let morceaux: Vec<&str> = morceaux.collect();
+    let Some((fin, milieu)) = morceaux.split_last() else {
+        return reste.is_empty(); // pas d'étoile : le nom exact
+    };
+    for morceau in milieu {
+        match reste.find(morceau) {
+            Some(position) => reste = &reste[position + morceau.len()..],
+            None => return false,
+        }
+    }
+    reste.ends_with(fin)
+}
+
+// Taille lisible : "532 o", "1.4 Ko", "12.0 Mo"
+pub fn taille_lisible(taille: u64) -> String {
+    let unites = ["o", "Ko", "Mo", "Go", "To"];
+    let mut valeur = taille as f64;
+    let mut unite = 0;
+    while valeur >= 1024.0 && unite < unites.len() - 1 {
+        valeur /= 1024.0;
+        unite += 1;
+    }
+    match unite {
+        0 => format!("{} {}", taille, unites[0]),
+        _ => format!("{:.1} {}", valeur, unites[unite]),
+    }
+}
+
+// L'arbre indenté, une ligne par entrée, jusqu'à la profondeur donnée (None : sans limite).
+// Les tailles des dossiers comptent tout leur contenu, même au-delà de la limite
+pub fn afficher(racine: &Noeud, profondeur: Option<usize>) -> Vec<String> {
+    let mut lignes = vec![format!("{}/ ({})", racine.nom.trim_end_matches('/'), taille_lisible(racine.taille))];
+    afficher_enfants(racine, "", 1, profondeur, &mut lignes);
+    lignes
+}
+
+fn afficher_enfants(noeud: &Noeud, prefixe: &str, niveau: usize, profondeur: Option<usize>, lignes: &mut Vec<String>) {
+    if noeud.illisible {
+        lignes.push(format!("{}└── (illisible)", prefixe));
+        return;
+    }
+    if profondeur.is_some_and(|maximum| niveau > maximum) {
+        if !noeud.enfants.is_empty() {
+            lignes.push(format!("{}└── … ({} entrées)", prefixe, noeud.enfants.len()));
+        }
+        return;
+    }
+    for (i, enfant) in noeud.enfants.iter().enumerate() {
+        let dernier = i + 1 == noeud.enfants.len();
+        let branche = if dernier { "└── " } else { "├── " };
+        let nom = if enfant.dossier { format!("{}/", enfant.nom) } else { enfant.nom.clone() };
+        lignes.push(format!("{}{}{} ({})", prefixe, branche, nom, taille_lisible(enfant.taille)));
+        if enfant.dossier {
+            let suite = format!("{}{}", prefixe, if dernier { "    " } else { "│   " });
+            afficher_enfants(enfant, &suite, niveau + 1, profondeur, lignes);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_arborescence() {
+        let racine = std::env::temp_dir().join(format!("tp2_arbre_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&racine);
+        fs::create_dir_all(racine.join("src/profond")).unwrap();
+        fs::create_dir_all(racine.join("target/debug")).unwrap();
+        fs::write(racine.join("Cargo.toml"), "0123456789").unwrap();
+        fs::write(racine.join("src/main.rs"), vec![b'x'; 2048]).unwrap();
+        fs::write(racine.join("src/profond/a.txt"), "abc").unwrap();
+        fs::write(racine.join("src/trace.log"), "ignoré").unwrap();
+        fs::write(racine.join("target/debug/tp2"), vec![0; 4096]).unwrap();
+
+        let arbre = parcourir(&racine, &lire_motifs("target/, *.log")).unwrap();
+        assert_eq!(arbre.taille, 10 + 2048 + 3);
+        let lignes = afficher(&arbre, None);
+        assert_eq!(
+            lignes[1..],
+            ["├── src/ (2.0 Ko)", "│   ├── profond/ (3 o)", "│   │   └── a.txt (3 o)", "│   └── main.rs (2.0 Ko)", "└── Cargo.toml (10 o)"]
+        );
+        // Limitée à un niveau, la taille du dossier compte tout son contenu
+        assert_eq!(afficher(&arbre, Some(1))[1..], ["├── src/ (2.0 Ko)", "│   └── … (2 entrées)", "└── Cargo.toml (10 o)"]);
+        let _ = fs::remove_dir_all(&racine);
+    }
+
+    #[test]
+    fn test_motifs() {
+        assert!(correspond("trace.log", "*.log"));
+        assert!(correspond("a-b-c", "a*b*c"));
+        assert!(!correspond("target2", "target"));
+        assert!(!correspond("ab", "a*b*c"));
+        assert!(est_ignore("target", true, &lire_motifs(MOTIFS_PAR_DEFAUT)));
+        assert!(!est_ignore("target", false, &lire_motifs(MOTIFS_PAR_DEFAUT)));
+        assert_eq!(taille_lisible(1536), "1.5 Ko");
+    }
+}
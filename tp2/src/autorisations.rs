@@ -0,0 +1,131 @@
+// Autorisations d'un fichier : sous Unix, les bits de mode (rwxr-xr-x), le propriétaire et le groupe,
+// et le changement de mode façon chmod (644, 0755...). Ailleurs (Windows), seul l'attribut
+// lecture seule existe : il est déduit du droit d'écriture du propriétaire dans le mode saisi
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+pub struct Autorisations {
+    pub lecture_seule: bool,
+    pub mode: Option<u32>,                   // bits de mode, sous Unix seulement
+    pub proprietaire: Option<(String, u32)>, // nom et numéro (uid)
+    pub groupe: Option<(String, u32)>,       // nom et numéro (gid)
+}
+
+impl Autorisations {
+    #[cfg(unix)]
+    pub fn lire(chemin: &Path) -> io::Result<Autorisations> {
+        use std::os::unix::fs::{MetadataExt, PermissionsExt};
+        let infos = fs::metadata(chemin)?;
+        Ok(Autorisations {
+            lecture_seule: infos.permissions().readonly(),
+            mode: Some(infos.permissions().mode() & 0o7777),
+            proprietaire: Some((nom_dans("/etc/passwd", infos.uid()), infos.uid())),
+            groupe: Some((nom_dans("/etc/group", infos.gid()), infos.gid())),
+        })
+    }
+
+    #[cfg(not(unix))]
+    pub fn lire(chemin: &Path) -> io::Result<Autorisations> {
+        let infos = fs::metadata(chemin)?;
+        Ok(Autorisations { lecture_seule: infos.permissions().readonly(), mode: None, proprietaire: None, groupe: None })
+    }
+
+    pub fn description(&self) -> String {
+        let mut description = match self.mode {
+            Some(mode) => format!("Mode : {} ({:o})", mode_lisible(mode), mode),
+            None => format!("Lecture seule : {}", if self.lecture_seule { "oui" } else { "non" }),
+        };
+        if let Some((nom, uid)) = &self.proprietaire {
+            description.push_str(&format!("\nPropriétaire : {} ({})", nom, uid));
+        }
+        if let Some((nom, gid)) = &self.groupe {
+            description.push_str(&format!("\nGroupe : {} ({})", nom, gid));
+        }
+        description
+    }
+}
+
+// Le nom associé à un numéro dans /etc/passwd ou /etc/group ("nom:x:numero:..."), le numéro sinon
+#[cfg(unix)]
+fn nom_dans(fichier: &str, numero: u32) -> String {
+    fs::read_to_string(fichier)
+        .ok()
+        .and_then(|texte| {
+            texte.lines().find_map(|ligne| {
+                let mut champs = ligne.split(':');
+                let nom = champs.next()?;
+                (champs.nth(1)?.parse() == Ok(numero)).then(|| nom.to_string())
+            })
+        })
+        .unwrap_or_else(|| numero.to_string())
+}
+
+// 0o754 -> "rwxr-xr--" ; les bits setuid, setgid et sticky s'affichent à la place des x
+pub fn mode_lisible(mode: u32) -> String {
+    let mut texte: Vec<char> = "rwxrwxrwx"
+        .chars()
+        .enumerate()
+        .map(|(i, lettre)| if mode & (0o400 >> i) != 0 { lettre } else { '-' })
+        .collect();
+    for (bit, position, lettre) in [(0o4000, 2, 's'), (0o2000, 5, 's'), (0o1000, 8, 't')] {
+        if mode & bit != 0 {
+            texte[position] = if texte[position] == 'x' { lettre } else { lettre.to_ascii_uppercase() };
+        }
+    }
+    texte.into_iter().collect()
+}
+
+// Un mode saisi comme pour chmod : 3 ou 4 chiffres octaux ("644", "0755", "1777")
+pub fn lire_mode(saisie: &str) -> Option<u32> {
+    let saisie = saisie.trim();
+    if !(3..=4).contains(&saisie.len()) || !saisie.chars().all(|c| ('0'..='7').contains(&c)) {
+        return None;
+    }
+    u32::from_str_radix(saisie, 8).ok()
+}
+
+// Appliquer le mode ; sans bits de mode, seul le droit d'écriture du propriétaire compte
+pub fn changer(chemin: &Path, mode: u32) -> io::Result<()> {
+    #[cfg(unix)]
+    let permissions = {
+        use std::os::unix::fs::PermissionsExt;
+        fs::Permissions::from_mode(mode)
+    };
+    #[cfg(not(unix))]
+    let permissions = {
+        let mut permissions = fs::metadata(chemin)?.permissions();
+        permissions.set_readonly(mode & 0o200 == 0);
+        permissions
+    };
+    fs::set_permissions(chemin, permissions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_autorisations() {
+        assert_eq!(mode_lisible(0o754), "rwxr-xr--");
+        assert_eq!(mode_lisible(0o1777), "rwxrwxrwt");
+        assert_eq!(mode_lisible(0o4644), "rwSr--r--");
+        assert_eq!(lire_mode("644"), Some(0o644));
+        assert_eq!(lire_mode("0755"), Some(0o755));
+        assert_eq!(lire_mode("8"), None);
+        assert_eq!(lire_mode("789"), None);
+        assert_eq!(lire_mode("+64"), None);
+
+        let chemin = std::env::temp_dir().join(format!("tp2_autorisations_{}", std::process::id()));
+        fs::write(&chemin, "texte").unwrap();
+        changer(&chemin, 0o444).unwrap();
+        let autorisations = Autorisations::lire(&chemin).unwrap();
+        assert!(autorisations.lecture_seule);
+        #[cfg(unix)]
+        assert_eq!(autorisations.mode, Some(0o444));
+        changer(&chemin, 0o640).unwrap();
+        assert!(!Autorisations::lire(&chemin).unwrap().lecture_seule);
+        let _ = fs::remove_file(&chemin);
+    }
+}
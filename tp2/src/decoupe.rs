@@ -0,0 +1,236 @@
+// Découpage d'un gros fichier (les logs du TP3, par exemple) en morceaux de N Mo, et recollage.
+// "serveur.log" donne "serveur.log.001", "serveur.log.002"... et un index "serveur.log.index" :
+//   fichier=serveur.log
+//   taille=2500000
+//   sha256=<empreinte du fichier entier>
+//   morceau=serveur.log.001;1048576;<empreinte du morceau>
+// Les morceaux sont cherchés à côté de l'index : on peut déplacer l'ensemble où l'on veut
+
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+
+use crate::doublons::hexa;
+
+pub const EXTENSION_INDEX: &str = "index";
+
+const TAILLE_TAMPON: usize = 64 * 1024;
+
+#[derive(Debug)]
+pub enum ErreurDecoupe {
+    Io(io::Error),
+    TailleInvalide,          // morceaux de 0 Mo, ou trop grands pour être comptés en octets
+    IndexInvalide(usize),    // numéro de la ligne illisible
+    MorceauAbsent(String),   // nom du morceau
+    MorceauCorrompu(String), // taille ou empreinte différente de celle de l'index
+    EmpreinteDifferente,     // le fichier recollé ne correspond pas à l'original
+}
+
+impl fmt::Display for ErreurDecoupe {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ErreurDecoupe::Io(e) => write!(f, "Erreur d'entrée/sortie : {}", e),
+            ErreurDecoupe::TailleInvalide => write!(f, "La taille des morceaux doit être comprise entre 1 et {} Mo.", u64::MAX / (1024 * 1024)),
+            ErreurDecoupe::IndexInvalide(ligne) => write!(f, "Index illisible (ligne {}).", ligne),
+            ErreurDecoupe::MorceauAbsent(nom) => write!(f, "Morceau introuvable : {}", nom),
+            ErreurDecoupe::MorceauCorrompu(nom) => write!(f, "Morceau abîmé (taille ou empreinte différente) : {}", nom),
+            ErreurDecoupe::EmpreinteDifferente => write!(f, "Le fichier recollé ne correspond pas à l'original."),
+        }
+    }
+}
+
+impl From<io::Error> for ErreurDecoupe {
+    fn from(e: io::Error) -> ErreurDecoupe {
+        ErreurDecoupe::Io(e)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Morceau {
+    pub nom: String,
+    pub taille: u64,
+    pub empreinte: String,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Index {
+    pub fichier: String, // nom du fichier d'origine, sans son dossier
+    pub taille: u64,
+    pub empreinte: String,
+    pub morceaux: Vec<Morceau>,
+}
+
+impl Index {
+    pub fn texte(&self) -> String {
+        let mut texte = format!("fichier={}\ntaille={}\nsha256={}\n", self.fichier, self.taille, self.empreinte);
+        for morceau in &self.morceaux {
+            texte.push_str(&format!("morceau={};{};{}\n", morceau.nom, morceau.taille, morceau.empreinte));
+        }
+        texte
+    }
+
+    pub fn lire(texte: &str) -> Result<Index, ErreurDecoupe> {
+        let mut index = Index { fichier: String::new(), taille: 0, empreinte: String::new(), morceaux: Vec::new() };
+        for (numero, ligne) in texte.lines().enumerate() {
+            let invalide = || ErreurDecoupe::IndexInvalide(numero + 1);
+            match ligne.split_once('=').ok_or_else(invalide)? {
+                // Des noms seuls : l'index ne peut désigner que des fichiers de son dossier
+                ("fichier", nom) | ("morceau", nom) if nom.contains(['/', '\\']) => return Err(invalide()),
+                ("fichier", nom) => index.fichier = nom.to_string(),
+                ("taille", taille) => index.taille = taille.parse().map_err(|_| invalide())?,
+                ("sha256", empreinte) => index.empreinte = empreinte.to_string(),
+                ("morceau", morceau) => {
+                    let mut champs = morceau.split(';');
+                    let (Some(nom), Some(taille), Some(empreinte), None) = (champs.next(), champs.next(), champs.next(), champs.next()) else {
+                        return Err(invalide());
+                    };
+                    let taille = taille.parse().map_err(|_| invalide())?;
+                    index.morceaux.push(Morceau { nom: nom.to_string(), taille, empreinte: empreinte.to_string() });
+                }
+                _ => return Err(invalide()),
+            }
+        }
+        if index.fichier.is_empty() || index.empreinte.is_empty() {
+            return Err(ErreurDecoupe::IndexInvalide(texte.lines().count()));
+        }
+        Ok(index)
+    }
+}
+
+// Découper le fichier en morceaux de taille_mo Mo, écrits à côté de lui ; renvoie le chemin de l'index
+pub fn decouper(chemin: &Path, taille_mo: u64) -> Result<PathBuf, ErreurDecoupe> {
+    if taille_mo == 0 {
+        return Err(ErreurDecoupe::TailleInvalide);
+    }
+    let taille_morceau = taille_mo.checked_mul(1024 * 1024).ok_or(ErreurDecoupe::TailleInvalide)?;
+    let mut source = File::open(chemin)?;
+    let nom = chemin.file_name().map(|nom| nom.to_string_lossy().to_string()).unwrap_or_default();
+    let mut index = Index { fichier: nom.clone(), taille: 0, empreinte: String::new(), morceaux: Vec::new() };
+
+    let mut total = Sha256::new();
+    let mut tampon = vec![0; TAILLE_TAMPON];
+    loop {
+        // take() arrête la lecture à la fin du morceau
+        let mut partie = (&mut source).take(taille_morceau);
+        let mut lus = partie.read(&mut tampon)?;
+        if lus == 0 {
+            break;
+        }
+        let nom_morceau = format!("{}.{:03}", nom, index.morceaux.len() + 1);
+        let mut sortie = BufWriter::new(File::create(chemin.with_file_name(&nom_morceau))?);
+        let mut hachage = Sha256::new();
+        let mut taille = 0;
+        while lus > 0 {
+            sortie.write_all(&tampon[..lus])?;
+            hachage.update(&tampon[..lus]);
+            total.update(&tampon[..lus]);
+            taille += lus as u64;
+            lus = partie.read(&mut tampon)?;
+        }
+        sortie.flush()?;
+        index.taille += taille;
+        index.morceaux.push(Morceau { nom: nom_morceau, taille, empreinte: hexa(&hachage.finalize()) });
+    }
+    index.empreinte = hexa(&total.finalize());
+
+    let chemin_index = chemin.with_file_name(format!("{}.{}", nom, EXTENSION_INDEX));
+    fs::write(&chemin_index, index.texte())?;
+    Ok(chemin_index)
+}
+
+// Le fichier d'origine tel que le recollage le recrée : à côté de l'index, sous son nom d'origine
+pub fn destination_par_defaut(chemin_index: &Path) -> Result<PathBuf, ErreurDecoupe> {
+    let index = Index::lire(&fs::read_to_string(chemin_index)?)?;
+    Ok(chemin_index.with_file_name(index.fichier))
+}
+
+// Recoller les morceaux décrits par l'index dans destination, en vérifiant chaque morceau puis
+// l'empreinte du tout. Le fichier est écrit sous un nom provisoire et n'apparaît qu'une fois vérifié
+pub fn recoller(chemin_index: &Path, destination: &Path) -> Result<(), ErreurDecoupe> {
+    let index = Index::lire(&fs::read_to_string(chemin_index)?)?;
+    let mut provisoire = destination.as_os_str().to_owned();
+    provisoire.push(".partiel");
+    let provisoire = PathBuf::from(provisoire);
+
+    let resultat = ecrire_morceaux(chemin_index, &index, &provisoire);
+    match resultat {
+        Ok(()) => fs::rename(&provisoire, destination)?,
+        Err(_) => {
+            let _ = fs::remove_file(&provisoire);
+        }
+    }
+    resultat
+}
+
+fn ecrire_morceaux(chemin_index: &Path, index: &Index, provisoire: &Path) -> Result<(), ErreurDecoupe> {
+    let mut sortie = BufWriter::new(File::create(provisoire)?);
+    let mut total = Sha256::new();
+    let mut tampon = vec![0; TAILLE_TAMPON];
+    for morceau in &index.morceaux {
+        let mut source = File::open(chemin_index.with_file_name(&morceau.nom)).map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => ErreurDecoupe::MorceauAbsent(morceau.nom.clone()),
+            _ => ErreurDecoupe::Io(e),
+        })?;
+        let mut hachage = Sha256::new();
+        let mut taille = 0;
+        loop {
+            let lus = source.read(&mut tampon)?;
+            if lus == 0 {
+                break;
+            }
+            sortie.write_all(&tampon[..lus])?;
+            hachage.update(&tampon[..lus]);
+            total.update(&tampon[..lus]);
+            taille += lus as u64;
+        }
+        if taille != morceau.taille || hexa(&hachage.finalize()) != morceau.empreinte {
+            return Err(ErreurDecoupe::MorceauCorrompu(morceau.nom.clone()));
+        }
+    }
+    sortie.flush()?;
+    if hexa(&total.finalize()) != index.empreinte {
+        return Err(ErreurDecoupe::EmpreinteDifferente);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decouper_recoller() {
+        let dossier = std::env::temp_dir().join(format!("tp2_decoupe_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dossier);
+        fs::create_dir_all(&dossier).unwrap();
+        let contenu: Vec<u8> = (0..2 * 1024 * 1024 + 100).map(|i| (i % 251) as u8).collect();
+        let original = dossier.join("serveur.log");
+        fs::write(&original, &contenu).unwrap();
+
+        let chemin_index = decouper(&original, 1).unwrap();
+        assert_eq!(chemin_index, dossier.join("serveur.log.index"));
+        let index = Index::lire(&fs::read_to_string(&chemin_index).unwrap()).unwrap();
+        let tailles: Vec<u64> = index.morceaux.iter().map(|m| m.taille).collect();
+        assert_eq!(tailles, [1024 * 1024, 1024 * 1024, 100]);
+        assert_eq!(index.morceaux[2].nom, "serveur.log.003");
+        assert_eq!(Index::lire(&index.texte()).unwrap(), index);
+        assert!(matches!(Index::lire("fichier=../secret"), Err(ErreurDecoupe::IndexInvalide(1))));
+
+        let copie = dossier.join("copie.log");
+        recoller(&chemin_index, &copie).unwrap();
+        assert_eq!(fs::read(&copie).unwrap(), contenu);
+        assert_eq!(destination_par_defaut(&chemin_index).unwrap(), original);
+
+        // Un morceau abîmé est signalé et rien n'est écrit
+        fs::write(dossier.join("serveur.log.002"), b"abime").unwrap();
+        let _ = fs::remove_file(&copie);
+        assert!(matches!(recoller(&chemin_index, &copie), Err(ErreurDecoupe::MorceauCorrompu(nom)) if nom == "serveur.log.002"));
+        assert!(!copie.exists());
+        assert!(matches!(decouper(&original, 0), Err(ErreurDecoupe::TailleInvalide)));
+        assert!(matches!(decouper(&original, u64::MAX / 1024), Err(ErreurDecoupe::TailleInvalide)));
+        let _ = fs::remove_dir_all(&dossier);
+    }
+}
@@ -0,0 +1,177 @@
+// Recherche de doublons : les fichiers sont d'abord regroupés par taille, puis seuls ceux qui
+// partagent une taille sont lus pour comparer leur empreinte SHA-256. Les copies peuvent ensuite
+// être supprimées ou remplacées par des liens physiques vers le premier fichier du groupe
+
+use std::collections::{BTreeMap, HashSet};
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+
+use crate::arborescence;
+
+pub struct Groupe {
+    pub taille: u64,
+    pub empreinte: String,
+    pub chemins: Vec<PathBuf>, // triés ; le premier est celui qu'on garde
+}
+
+impl Groupe {
+    // Octets occupés par les copies en trop
+    pub fn gaspille(&self) -> u64 {
+        self.taille * (self.chemins.len() as u64 - 1)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Action {
+    Supprimer,
+    Lier, // remplacer chaque copie par un lien physique vers l'original
+}
+
+// Les groupes de fichiers identiques sous un dossier, les plus coûteux d'abord.
+// Les fichiers vides et les liens symboliques sont laissés de côté, comme les fichiers
+// qui sont déjà des liens physiques les uns des autres
+pub fn chercher(dossier: &Path, motifs: &[String]) -> io::Result<Vec<Groupe>> {
+    let mut par_taille: BTreeMap<u64, Vec<PathBuf>> = BTreeMap::new();
+    let mut vus = HashSet::new();
+    fs::read_dir(dossier)?; // un dossier introuvable est une erreur, pas une liste vide
+    lister(dossier, motifs, &mut vus, &mut par_taille);
+
+    let mut groupes = Vec::new();
+    for (taille, chemins) in par_taille {
+        if chemins.len() < 2 {
+            continue;
+        }
+        let mut par_empreinte: BTreeMap<String, Vec<PathBuf>> = BTreeMap::new();
+        for chemin in chemins {
+            // Un fichier illisible ne peut pas être comparé : il est ignoré
+            if let Ok(empreinte) = empreinte(&chemin) {
+                par_empreinte.entry(empreinte).or_default().push(chemin);
+            }
+        }
+        for (empreinte, mut chemins) in par_empreinte {
+            if chemins.len() >= 2 {
+                chemins.sort();
+                groupes.push(Groupe { taille, empreinte, chemins });
+            }
+        }
+    }
+    groupes.sort_by(|a, b| b.gaspille().cmp(&a.gaspille()).then_with(|| a.chemins.cmp(&b.chemins)));
+    Ok(groupes)
+}
+
+fn lister(dossier: &Path, motifs: &[String], vus: &mut HashSet<(u64, u64)>, par_taille: &mut BTreeMap<u64, Vec<PathBuf>>) {
+    let Ok(entrees) = fs::read_dir(dossier) else {
+        return;
+    };
+    for entree in entrees.flatten() {
+        let Ok(infos) = entree.metadata() else {
+            continue;
+        };
+        if arborescence::est_ignore(&entree.file_name().to_string_lossy(), infos.is_dir(), motifs) {
+            continue;
+        }
+        if infos.is_dir() {
+            lister(&entree.path(), motifs, vus, par_taille);
+        } else if infos.is_file() && infos.len() > 0 && vus.insert(identifiant(&infos, vus.len())) {
+            par_taille.entry(infos.len()).or_default().push(entree.path());
+        }
+    }
+}
+
+// Ce qui identifie le contenu sur le disque : deux liens physiques partagent le même
+#[cfg(unix)]
+fn identifiant(infos: &fs::Metadata, _: usize) -> (u64, u64) {
+    use std::os::unix::fs::MetadataExt;
+    (infos.dev(), infos.ino())
+}
+
+// Sans numéro d'inode, chaque fichier est considéré comme distinct
+#[cfg(not(unix))]
+fn identifiant(_: &fs::Metadata, rang: usize) -> (u64, u64) {
+    (0, rang as u64)
+}
+
+// Empreinte SHA-256 du contenu, en hexadécimal ; le fichier est lu par morceaux
+pub fn empreinte(chemin: &Path) -> io::Result<String> {
+    let mut fichier = File::open(chemin)?;
+    let mut hachage = Sha256::new();
+    let mut tampon = vec![0; 64 * 1024];
+    loop {
+        let lus = fichier.read(&mut tampon)?;
+        if lus == 0 {
+            break;
+        }
+        hachage.update(&tampon[..lus]);
+    }
+    Ok(hexa(&hachage.finalize()))
+}
+
+pub fn hexa(octets: &[u8]) -> String {
+    octets.iter().map(|octet| format!("{:02x}", octet)).collect()
+}
+
+// Appliquer l'action aux copies du groupe (tous les chemins sauf le premier) ;
+// le résultat pour chaque copie
+pub fn traiter(groupe: &Groupe, action: Action) -> Vec<(PathBuf, io::Result<()>)> {
+    let original = &groupe.chemins[0];
+    groupe.chemins[1..]
+        .iter()
+        .map(|copie| {
+            let resultat = match action {
+                Action::Supprimer => fs::remove_file(copie),
+                Action::Lier => lier(original, copie),
+            };
+            (copie.clone(), resultat)
+        })
+        .collect()
+}
+
+// Le lien est créé à côté de la copie puis renommé par-dessus : si la création échoue,
+// la copie reste intacte
+fn lier(original: &Path, copie: &Path) -> io::Result<()> {
+    let mut temporaire = copie.as_os_str().to_owned();
+    temporaire.push(".lien-tp2");
+    let temporaire = PathBuf::from(temporaire);
+    fs::hard_link(original, &temporaire)?;
+    fs::rename(&temporaire, copie).inspect_err(|_| {
+        let _ = fs::remove_file(&temporaire);
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_doublons() {
+        let racine = std::env::temp_dir().join(format!("tp2_doublons_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&racine);
+        fs::create_dir_all(racine.join("copies")).unwrap();
+        fs::create_dir_all(racine.join(".git")).unwrap();
+        fs::write(racine.join("a.txt"), "même contenu").unwrap();
+        fs::write(racine.join("copies/b.txt"), "même contenu").unwrap();
+        fs::write(racine.join("copies/c.txt"), "même contenu").unwrap();
+        fs::write(racine.join("d.txt"), "autre contenu").unwrap(); // même taille, autre empreinte
+        fs::write(racine.join(".git/e.txt"), "même contenu").unwrap();
+        fs::write(racine.join("vide1"), "").unwrap();
+        fs::write(racine.join("vide2"), "").unwrap();
+
+        let motifs = arborescence::lire_motifs(arborescence::MOTIFS_PAR_DEFAUT);
+        let groupes = chercher(&racine, &motifs).unwrap();
+        assert_eq!(groupes.len(), 1);
+        let groupe = &groupes[0];
+        assert_eq!(groupe.chemins, [racine.join("a.txt"), racine.join("copies/b.txt"), racine.join("copies/c.txt")]);
+        assert_eq!(groupe.gaspille(), 2 * "même contenu".len() as u64);
+        assert_eq!(groupe.empreinte, empreinte(&racine.join("copies/c.txt")).unwrap());
+
+        assert!(traiter(groupe, Action::Lier).iter().all(|(_, resultat)| resultat.is_ok()));
+        assert_eq!(fs::read_to_string(racine.join("copies/b.txt")).unwrap(), "même contenu");
+        // Des liens physiques ne sont plus des doublons (là où le système le permet)
+        #[cfg(unix)]
+        assert!(chercher(&racine, &motifs).unwrap().is_empty());
+        let _ = fs::remove_dir_all(&racine);
+    }
+}
@@ -0,0 +1,239 @@
+// Historique des opérations : chaque création, écriture, modification, suppression ou déplacement
+// est noté dans .tp2/historique avec de quoi l'annuler. Avant une modification ou une suppression,
+// le fichier est copié dans .tp2/sauvegardes/ ; avant un ajout, on note sa taille.
+// Une ligne : "horodatage\toperation\tchemin\tdetail", où detail est la sauvegarde, l'ancienne
+// taille (vide si le fichier n'existait pas) ou la destination d'un déplacement
+
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use chrono::{Local, TimeZone, Utc};
+
+pub const DOSSIER_HISTORIQUE: &str = ".tp2";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operation {
+    Creation,
+    Ecriture, // ajout en fin de fichier
+    Modification,
+    Suppression,
+    Deplacement,
+}
+
+impl Operation {
+    pub fn nom(&self) -> &'static str {
+        match self {
+            Operation::Creation => "creation",
+            Operation::Ecriture => "ecriture",
+            Operation::Modification => "modification",
+            Operation::Suppression => "suppression",
+            Operation::Deplacement => "deplacement",
+        }
+    }
+
+    fn depuis_nom(nom: &str) -> Option<Operation> {
+        match nom {
+            "creation" => Some(Operation::Creation),
+            "ecriture" => Some(Operation::Ecriture),
+            "modification" => Some(Operation::Modification),
+            "suppression" => Some(Operation::Suppression),
+            "deplacement" => Some(Operation::Deplacement),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Action {
+    pub horodatage: i64, // secondes depuis 1970 (UTC)
+    pub operation: Operation,
+    pub chemin: String,
+    pub detail: String,
+}
+
+impl Action {
+    fn ligne(&self) -> String {
+        format!("{}\t{}\t{}\t{}", self.horodatage, self.operation.nom(), self.chemin, self.detail)
+    }
+
+    fn lire(ligne: &str) -> Option<Action> {
+        let mut champs = ligne.splitn(4, '\t');
+        Some(Action {
+            horodatage: champs.next()?.parse().ok()?,
+            operation: Operation::depuis_nom(champs.next()?)?,
+            chemin: champs.next()?.to_string(),
+            detail: champs.next()?.to_string(),
+        })
+    }
+
+    // "16/10/2026 14:05:12 - Modification de notes.txt"
+    pub fn description(&self) -> String {
+        let date = Local
+            .timestamp_opt(self.horodatage, 0)
+            .single()
+            .map(|date| date.format("%d/%m/%Y %H:%M:%S").to_string())
+            .unwrap_or_default();
+        let quoi = match self.operation {
+            Operation::Creation => format!("Création de {}", self.chemin),
+            Operation::Ecriture => format!("Écriture dans {}", self.chemin),
+            Operation::Modification => format!("Modification de {}", self.chemin),
+            Operation::Suppression => format!("Suppression de {}", self.chemin),
+            Operation::Deplacement => format!("Déplacement de {} vers {}", self.chemin, self.detail),
+        };
+        format!("{} - {}", date, quoi)
+    }
+
+    // Une action préparée mais pas faite : sa sauvegarde ne sert plus
+    fn oublier(&self) {
+        if matches!(self.operation, Operation::Modification | Operation::Suppression) {
+            let _ = fs::remove_file(&self.detail);
+        }
+    }
+}
+
+pub struct Historique {
+    dossier: PathBuf,
+}
+
+impl Historique {
+    pub fn new(dossier: &Path) -> Historique {
+        Historique { dossier: dossier.to_path_buf() }
+    }
+
+    fn journal(&self) -> PathBuf {
+        self.dossier.join("historique")
+    }
+
+    // Toutes les actions, de la plus ancienne à la plus récente ; les lignes illisibles sont ignorées
+    pub fn lire_tout(&self) -> io::Result<Vec<Action>> {
+        match fs::read_to_string(self.journal()) {
+            Ok(texte) => Ok(texte.lines().filter_map(Action::lire).collect()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+            Err(e) => Err(e),
+        }
+    }
+
+    // Faire l'opération et la noter ; ce qu'il faut pour l'annuler est préparé avant.
+    // detail : la destination d'un déplacement, ignoré sinon
+    pub fn executer(&self, operation: Operation, chemin: &str, detail: &str, faire: impl FnOnce() -> io::Result<()>) -> io::Result<()> {
+        let action = self.preparer(operation, chemin, detail)?;
+        if let Err(e) = faire() {
+            action.oublier();
+            return Err(e);
+        }
+        fs::create_dir_all(&self.dossier)?;
+        let mut journal = OpenOptions::new().create(true).append(true).open(self.journal())?;
+        writeln!(journal, "{}", action.ligne())
+    }
+
+    fn preparer(&self, operation: Operation, chemin: &str, detail: &str) -> io::Result<Action> {
+        let horodatage = Utc::now();
+        let detail = match operation {
+            Operation::Creation => String::new(),
+            Operation::Deplacement => detail.to_string(),
+            Operation::Ecriture => match fs::metadata(chemin) {
+                Ok(infos) => infos.len().to_string(),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+                Err(e) => return Err(e),
+            },
+            Operation::Modification | Operation::Suppression => {
+                let sauvegardes = self.dossier.join("sauvegardes");
+                fs::create_dir_all(&sauvegardes)?;
+                let nom = Path::new(chemin).file_name().map(|nom| nom.to_string_lossy().to_string()).unwrap_or_default();
+                let sauvegarde = sauvegardes.join(format!("{}-{}", horodatage.timestamp_nanos_opt().unwrap_or_default(), nom));
+                fs::copy(chemin, &sauvegarde)?;
+                sauvegarde.to_string_lossy().to_string()
+            }
+        };
+        Ok(Action { horodatage: horodatage.timestamp(), operation, chemin: chemin.to_string(), detail })
+    }
+
+    // Annuler la dernière action et la retirer de l'historique ; None s'il est vide
+    pub fn annuler_derniere(&self) -> io::Result<Option<Action>> {
+        let mut actions = self.lire_tout()?;
+        let Some(action) = actions.pop() else {
+            return Ok(None);
+        };
+        match action.operation {
+            Operation::Creation => fs::remove_file(&action.chemin)?,
+            Operation::Ecriture if action.detail.is_empty() => fs::remove_file(&action.chemin)?,
+            Operation::Ecriture => {
+                let taille = action.detail.parse().map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "taille illisible"))?;
+                OpenOptions::new().write(true).open(&action.chemin)?.set_len(taille)?;
+            }
+            Operation::Modification => restaurer(&action.detail, &action.chemin)?,
+            Operation::Suppression => {
+                refuser_si_existe(&action.chemin)?;
+                restaurer(&action.detail, &action.chemin)?;
+            }
+            Operation::Deplacement => {
+                refuser_si_existe(&action.chemin)?;
+                fs::rename(&action.detail, &action.chemin)?;
+            }
+        }
+
+        let texte: String = actions.iter().map(|action| action.ligne() + "\n").collect();
+        let provisoire = self.dossier.join("historique.partiel");
+        fs::write(&provisoire, texte)?;
+        fs::rename(provisoire, self.journal())?;
+        Ok(Some(action))
+    }
+}
+
+// Remettre la sauvegarde à la place du fichier ; elle n'est plus utile ensuite
+fn restaurer(sauvegarde: &str, chemin: &str) -> io::Result<()> {
+    fs::copy(sauvegarde, chemin)?;
+    let _ = fs::remove_file(sauvegarde);
+    Ok(())
+}
+
+// Un fichier recréé depuis ne doit pas être écrasé
+fn refuser_si_existe(chemin: &str) -> io::Result<()> {
+    match Path::new(chemin).exists() {
+        true => Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("{} existe déjà", chemin))),
+        false => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_annuler() {
+        let dossier = std::env::temp_dir().join(format!("tp2_historique_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dossier);
+        fs::create_dir_all(&dossier).unwrap();
+        let historique = Historique::new(&dossier.join(DOSSIER_HISTORIQUE));
+        let notes = dossier.join("notes.txt").to_string_lossy().to_string();
+        let ailleurs = dossier.join("ailleurs.txt").to_string_lossy().to_string();
+
+        historique.executer(Operation::Ecriture, &notes, "", || fs::write(&notes, "un\n")).unwrap();
+        historique.executer(Operation::Ecriture, &notes, "", || fs::write(&notes, "un\ndeux\n")).unwrap();
+        historique.executer(Operation::Modification, &notes, "", || fs::write(&notes, "remplacé")).unwrap();
+        historique.executer(Operation::Deplacement, &notes, &ailleurs, || fs::rename(&notes, &ailleurs)).unwrap();
+        historique.executer(Operation::Suppression, &ailleurs, "", || fs::remove_file(&ailleurs)).unwrap();
+        // Une opération qui échoue n'est pas notée
+        assert!(historique.executer(Operation::Modification, &notes, "", || Ok(())).is_err());
+        let actions = historique.lire_tout().unwrap();
+        assert_eq!(actions.len(), 5);
+        assert_eq!(Action::lire(&actions[3].ligne()).as_ref(), Some(&actions[3]));
+
+        let annulee = historique.annuler_derniere().unwrap().unwrap();
+        assert_eq!(annulee.operation, Operation::Suppression);
+        assert_eq!(fs::read_to_string(&ailleurs).unwrap(), "remplacé");
+        historique.annuler_derniere().unwrap();
+        assert_eq!(fs::read_to_string(&notes).unwrap(), "remplacé");
+        historique.annuler_derniere().unwrap();
+        assert_eq!(fs::read_to_string(&notes).unwrap(), "un\ndeux\n");
+        historique.annuler_derniere().unwrap();
+        assert_eq!(fs::read_to_string(&notes).unwrap(), "un\n");
+        historique.annuler_derniere().unwrap();
+        assert!(!Path::new(&notes).exists());
+        assert_eq!(historique.annuler_derniere().unwrap(), None);
+        // Les sauvegardes restaurées ont été retirées
+        assert_eq!(fs::read_dir(dossier.join(DOSSIER_HISTORIQUE).join("sauvegardes")).unwrap().count(), 0);
+        let _ = fs::remove_dir_all(&dossier);
+    }
+}
@@ -1,6 +1,20 @@
 use std::fs::{self, OpenOptions};
-use std::io::{self, Write, Read};
-use chrono::Utc;
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+use chrono::{Local, Utc};
+
+mod arborescence;
+mod autorisations;
+mod decoupe;
+mod doublons;
+mod historique;
+mod modeles;
+mod suivi;
+
+use historique::{Historique, Operation};
 
 struct Fichier {
     nom: String,
@@ -14,26 +28,326 @@ impl Fichier {
         }
     }
 
-    fn ecrire(&self, texte: &str) {
-        let mut fichier = OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open(&self.nom)
-            .expect("Erreur à l'ouverture du fichier");
+    // Les opérations qui changent le fichier passent par l'historique, pour pouvoir être annulées
+    fn ecrire(&self, texte: &str, historique: &Historique) {
+        let resultat = historique.executer(Operation::Ecriture, &self.nom, "", || {
+            let mut fichier = OpenOptions::new().create(true).append(true).open(&self.nom)?;
+            writeln!(fichier, "{} - {}", Utc::now().format("%d/%m/%Y %H:%M:%S"), texte)
+        });
+        match resultat {
+            Ok(()) => println!("Écriture réussie !"),
+            Err(e) => println!("Erreur à l'écriture : {}", e),
+        }
+    }
 
-        writeln!(fichier, "{} - {}", Utc::now().format("%d/%m/%Y %H:%M:%S"), texte)
-            .expect("Erreur à l'écriture");
-        println!("Écriture réussie !");
+    fn modifier(&self, nouveau_texte: &str, historique: &Historique) {
+        match historique.executer(Operation::Modification, &self.nom, "", || fs::write(&self.nom, nouveau_texte)) {
+            Ok(()) => println!("Fichier modifié."),
+            Err(e) => println!("Erreur à la modification : {}", e),
+        }
     }
 
-    fn modifier(&self, nouveau_texte: &str) {
-        fs::write(&self.nom, nouveau_texte).expect("Erreur à la modification");
-        println!("Fichier modifié.");
+    // Déplacer ou renommer le fichier ; vers un dossier existant, il y garde son nom
+    fn deplacer(&mut self, destination: &str, historique: &Historique) {
+        let mut destination = Path::new(destination).to_path_buf();
+        if destination.is_dir() {
+            destination.push(Path::new(&self.nom).file_name().unwrap_or_default());
+        }
+        if destination.exists() {
+            println!("Erreur : {} existe déjà.", destination.display());
+            return;
+        }
+        let destination = destination.to_string_lossy().to_string();
+        match historique.executer(Operation::Deplacement, &self.nom, &destination, || fs::rename(&self.nom, &destination)) {
+            Ok(()) => {
+                println!("Fichier déplacé vers {}.", destination);
+                self.nom = destination;
+            }
+            Err(e) => println!("Erreur au déplacement : {}", e),
+        }
     }
 
-    fn supprimer(&self) {
-        fs::remove_file(&self.nom).expect("Erreur à la suppression");
-        println!("Fichier supprimé.");
+    // Afficher les lignes ajoutées au fichier au fur et à mesure, jusqu'à ce que l'utilisateur appuie sur Entrée
+    fn suivre(&self, motif: Option<&str>) {
+        let (mut suivi, dernieres) = match suivi::Suivi::depuis_la_fin(Path::new(&self.nom), 10) {
+            Ok(debut) => debut,
+            Err(_) => {
+                println!("Erreur : fichier introuvable ou illisible.");
+                return;
+            }
+        };
+        println!("Suivi de {} (Entrée pour arrêter) :", self.nom);
+        for ligne in dernieres.iter().filter_map(|ligne| suivi::filtrer(ligne, motif)) {
+            println!("{}", ligne);
+        }
+
+        let (arret, attente) = mpsc::channel();
+        thread::spawn(move || {
+            let mut ligne = String::new();
+            let _ = io::stdin().read_line(&mut ligne);
+            let _ = arret.send(());
+        });
+        while attente.recv_timeout(Duration::from_millis(500)).is_err() {
+            match suivi.nouvelles_lignes() {
+                Ok(lignes) => {
+                    for ligne in lignes.iter().filter_map(|ligne| suivi::filtrer(ligne, motif)) {
+                        println!("{}", ligne);
+                    }
+                }
+                Err(e) => {
+                    // Le fil qui lit le clavier attend toujours Entrée : on l'attend aussi
+                    println!("Erreur de lecture : {} (Entrée pour revenir au menu)", e);
+                    let _ = attente.recv();
+                    break;
+                }
+            }
+        }
+        println!("Fin du suivi.");
+    }
+
+    // Afficher le mode et le propriétaire, puis proposer un nouveau mode (comme chmod)
+    fn autorisations(&self) {
+        let chemin = Path::new(&self.nom);
+        match autorisations::Autorisations::lire(chemin) {
+            Ok(actuelles) => println!("{}", actuelles.description()),
+            Err(_) => {
+                println!("Erreur : fichier introuvable ou illisible.");
+                return;
+            }
+        }
+        if !cfg!(unix) {
+            println!("Sur ce système, seul l'attribut lecture seule est modifiable : il suit le droit d'écriture du propriétaire (6xx : écriture, 4xx : lecture seule).");
+        }
+        let saisie = demander("Nouveau mode en octal, par exemple 644 (Entrée : ne rien changer) :", "");
+        if saisie.is_empty() {
+            return;
+        }
+        let Some(mode) = autorisations::lire_mode(&saisie) else {
+            println!("Mode invalide : 3 ou 4 chiffres de 0 à 7.");
+            return;
+        };
+        match autorisations::changer(chemin, mode).and_then(|()| autorisations::Autorisations::lire(chemin)) {
+            Ok(nouvelles) => println!("Autorisations modifiées.\n{}", nouvelles.description()),
+            Err(e) => println!("Erreur : {}", e),
+        }
+    }
+
+    fn supprimer(&self, historique: &Historique) {
+        match historique.executer(Operation::Suppression, &self.nom, "", || fs::remove_file(&self.nom)) {
+            Ok(()) => println!("Fichier supprimé (une copie est gardée pour pouvoir l'annuler)."),
+            Err(e) => println!("Erreur à la suppression : {}", e),
+        }
+    }
+}
+
+// Demander une ligne, la valeur par défaut si elle est vide
+fn demander(question: &str, defaut: &str) -> String {
+    println!("{}", question);
+    let mut reponse = String::new();
+    io::stdin().read_line(&mut reponse).expect("Erreur de lecture");
+    let reponse = reponse.trim();
+    if reponse.is_empty() { defaut.to_string() } else { reponse.to_string() }
+}
+
+fn afficher_arborescence() {
+    let dossier = demander("Dossier à parcourir (Entrée : dossier courant) :", ".");
+    let profondeur = demander("Profondeur maximale (Entrée : sans limite) :", "");
+    let profondeur = match profondeur.parse::<usize>() {
+        Ok(profondeur) => Some(profondeur),
+        Err(_) if profondeur.is_empty() => None,
+        Err(_) => {
+            println!("Profondeur invalide.");
+            return;
+        }
+    };
+    let motifs = demander(
+        &format!("Motifs à ignorer, séparés par des virgules (Entrée : {}) :", arborescence::MOTIFS_PAR_DEFAUT),
+        arborescence::MOTIFS_PAR_DEFAUT,
+    );
+
+    match arborescence::parcourir(Path::new(&dossier), &arborescence::lire_motifs(&motifs)) {
+        Ok(arbre) => {
+            for ligne in arborescence::afficher(&arbre, profondeur) {
+                println!("{}", ligne);
+            }
+        }
+        Err(_) => println!("Erreur : dossier introuvable ou illisible."),
+    }
+}
+
+fn chercher_doublons() {
+    let dossier = demander("Dossier à analyser (Entrée : dossier courant) :", ".");
+    let motifs = demander(
+        &format!("Motifs à ignorer, séparés par des virgules (Entrée : {}) :", arborescence::MOTIFS_PAR_DEFAUT),
+        arborescence::MOTIFS_PAR_DEFAUT,
+    );
+    let groupes = match doublons::chercher(Path::new(&dossier), &arborescence::lire_motifs(&motifs)) {
+        Ok(groupes) => groupes,
+        Err(_) => {
+            println!("Erreur : dossier introuvable ou illisible.");
+            return;
+        }
+    };
+    if groupes.is_empty() {
+        println!("Aucun doublon trouvé.");
+        return;
+    }
+
+    let gaspille: u64 = groupes.iter().map(doublons::Groupe::gaspille).sum();
+    println!(
+        "{} groupe(s) de doublons, {} gaspillé(s) :",
+        groupes.len(),
+        arborescence::taille_lisible(gaspille)
+    );
+    for (i, groupe) in groupes.iter().enumerate() {
+        println!(
+            "\n[{}] {} fichiers de {} ({} gaspillé(s)), SHA-256 {}",
+            i + 1,
+            groupe.chemins.len(),
+            arborescence::taille_lisible(groupe.taille),
+            arborescence::taille_lisible(groupe.gaspille()),
+            &groupe.empreinte[..16]
+        );
+        for (j, chemin) in groupe.chemins.iter().enumerate() {
+            println!("    {} {}", if j == 0 { "gardé :" } else { "copie :" }, chemin.display());
+        }
+    }
+
+    let action = match demander("\nTraiter les copies ? (s : supprimer, l : liens physiques, Entrée : ne rien faire)", "").as_str() {
+        "s" => doublons::Action::Supprimer,
+        "l" => doublons::Action::Lier,
+        _ => return,
+    };
+    let verbe = if action == doublons::Action::Supprimer { "supprimer" } else { "remplacer par des liens" };
+    let confirmation = demander(
+        &format!("Confirmer : {} {} copie(s) ? (o/n)", verbe, groupes.iter().map(|g| g.chemins.len() - 1).sum::<usize>()),
+        "n",
+    );
+    if confirmation != "o" {
+        println!("Aucun fichier modifié.");
+        return;
+    }
+    for groupe in &groupes {
+        for (copie, resultat) in doublons::traiter(groupe, action) {
+            match resultat {
+                Ok(()) => println!("{} : fait.", copie.display()),
+                Err(e) => println!("{} : erreur ({}).", copie.display(), e),
+            }
+        }
+    }
+}
+
+fn decouper() {
+    let fichier = demander("Fichier à découper :", "");
+    let taille = demander("Taille des morceaux en Mo (Entrée : 10) :", "10");
+    let Ok(taille) = taille.parse::<u64>() else {
+        println!("Taille invalide.");
+        return;
+    };
+    match decoupe::decouper(Path::new(&fichier), taille) {
+        Ok(index) => {
+            let morceaux = fs::read_to_string(&index).map(|texte| texte.lines().filter(|l| l.starts_with("morceau=")).count());
+            println!("Fichier découpé en {} morceau(x), index : {}", morceaux.unwrap_or(0), index.display());
+        }
+        Err(e) => println!("{}", e),
+    }
+}
+
+fn recoller() {
+    let index = demander(&format!("Index des morceaux (fichier .{}) :", decoupe::EXTENSION_INDEX), "");
+    let index = Path::new(&index);
+    let defaut = match decoupe::destination_par_defaut(index) {
+        Ok(defaut) => defaut,
+        Err(e) => {
+            println!("{}", e);
+            return;
+        }
+    };
+    let destination = demander(&format!("Fichier à recréer (Entrée : {}) :", defaut.display()), &defaut.to_string_lossy());
+    let destination = Path::new(&destination);
+    if destination.exists() && demander(&format!("{} existe déjà : le remplacer ? (o/n)", destination.display()), "n") != "o" {
+        println!("Aucun fichier modifié.");
+        return;
+    }
+    match decoupe::recoller(index, destination) {
+        Ok(()) => println!("Fichier recollé et vérifié : {}", destination.display()),
+        Err(e) => println!("{}", e),
+    }
+}
+
+fn nouveau_depuis_modele(historique: &Historique) {
+    let dossier = Path::new(modeles::DOSSIER_MODELES);
+    let noms = match modeles::lister(dossier) {
+        Ok(noms) if !noms.is_empty() => noms,
+        _ => {
+            println!("Aucun modèle : placez des fichiers dans le dossier {}/.", modeles::DOSSIER_MODELES);
+            return;
+        }
+    };
+    println!("Modèles disponibles :");
+    for (i, nom) in noms.iter().enumerate() {
+        println!("{}. {}", i + 1, nom);
+    }
+    let choix = demander("Modèle (numéro ou nom) :", "");
+    let Some(nom) = choix.parse::<usize>().ok().and_then(|i| noms.get(i.wrapping_sub(1))).or_else(|| noms.iter().find(|nom| **nom == choix)) else {
+        println!("Modèle inconnu.");
+        return;
+    };
+    let Ok(Some(modele)) = modeles::chemin(dossier, nom) else {
+        println!("Erreur : modèle introuvable ou illisible.");
+        return;
+    };
+
+    let titre = demander("Titre :", nom);
+    let date = Local::now();
+    let extension = modele.extension().map(|e| format!(".{}", e.to_string_lossy())).unwrap_or_default();
+    let defaut = format!("{}-{}{}", nom, date.format("%Y-%m-%d"), extension);
+    let destination = demander(&format!("Nom du nouveau fichier (Entrée : {}) :", defaut), &defaut);
+
+    let utilisateur = std::env::var("USER").or_else(|_| std::env::var("USERNAME")).unwrap_or_else(|_| "inconnu".to_string());
+    let date = date.format("%d/%m/%Y").to_string();
+    let valeurs = [("date", date.as_str()), ("user", utilisateur.as_str()), ("titre", titre.as_str())];
+    let mut inconnus = Vec::new();
+    let creation = historique.executer(Operation::Creation, &destination, "", || {
+        inconnus = modeles::creer(&modele, Path::new(&destination), &valeurs)?;
+        Ok(())
+    });
+    match creation {
+        Ok(()) => {
+            println!("Fichier créé : {}", destination);
+            if !inconnus.is_empty() {
+                println!("Repères inconnus laissés tels quels : {}", inconnus.join(", "));
+            }
+        }
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => println!("Erreur : {} existe déjà.", destination),
+        Err(e) => println!("Erreur : {}", e),
+    }
+}
+
+// Annuler la dernière opération ; si elle avait déplacé le fichier courant, il reprend son ancien nom
+fn annuler(fichier: &mut Fichier, historique: &Historique) {
+    match historique.annuler_derniere() {
+        Ok(Some(action)) => {
+            println!("Annulé : {}", action.description());
+            if action.operation == Operation::Deplacement && action.detail == fichier.nom {
+                fichier.nom = action.chemin;
+            }
+        }
+        Ok(None) => println!("Aucune opération à annuler."),
+        Err(e) => println!("Erreur : annulation impossible ({}).", e),
+    }
+}
+
+fn afficher_historique(historique: &Historique) {
+    match historique.lire_tout() {
+        Ok(actions) if actions.is_empty() => println!("Aucune opération dans l'historique."),
+        Ok(actions) => {
+            println!("Dernières opérations (la plus récente en premier) :");
+            for action in actions.iter().rev().take(20) {
+                println!("  {}", action.description());
+            }
+        }
+        Err(e) => println!("Erreur : historique illisible ({}).", e),
     }
 }
 
@@ -45,12 +359,13 @@ fn main() {
     io::stdin().read_line(&mut nom_fichier).expect("Erreur de lecture");
     let mut nom_fichier = nom_fichier.trim().to_string();
 
-    // ✅ Ajoute l'extension automatiquement si manquante
-    if !nom_fichier.ends_with(".txt") {
+    // ✅ Ajoute l'extension automatiquement si manquante (server.log garde la sienne)
+    if Path::new(&nom_fichier).extension().is_none() {
         nom_fichier.push_str(".txt");
     }
 
-    let mon_fichier = Fichier { nom: nom_fichier };
+    let mut mon_fichier = Fichier { nom: nom_fichier };
+    let historique = Historique::new(Path::new(historique::DOSSIER_HISTORIQUE));
 
     loop {
         println!("\n--- MENU ---");
@@ -58,7 +373,17 @@ fn main() {
         println!("2. Écrire dans le fichier");
         println!("3. Modifier le fichier");
         println!("4. Supprimer le fichier");
-        println!("5. Quitter");
+        println!("5. Arborescence d'un dossier");
+        println!("6. Rechercher les doublons");
+        println!("7. Découper un fichier en morceaux");
+        println!("8. Recoller des morceaux");
+        println!("9. Suivre le fichier (comme tail -f)");
+        println!("10. Autorisations du fichier");
+        println!("11. Nouveau fichier depuis un modèle");
+        println!("12. Déplacer ou renommer le fichier");
+        println!("13. Annuler la dernière opération");
+        println!("14. Historique des opérations");
+        println!("15. Quitter");
 
         let mut choix = String::new();
         io::stdin().read_line(&mut choix).expect("Erreur de lecture");
@@ -70,19 +395,34 @@ fn main() {
                 println!("Texte à écrire :");
                 let mut texte = String::new();
                 io::stdin().read_line(&mut texte).expect("Erreur");
-                mon_fichier.ecrire(texte.trim());
+                mon_fichier.ecrire(texte.trim(), &historique);
             },
             "3" => {
                 println!("Nouveau contenu :");
                 let mut texte = String::new();
                 io::stdin().read_line(&mut texte).expect("Erreur");
-                mon_fichier.modifier(texte.trim());
+                mon_fichier.modifier(texte.trim(), &historique);
+            },
+            "4" => mon_fichier.supprimer(&historique),
+            "5" => afficher_arborescence(),
+            "6" => chercher_doublons(),
+            "7" => decouper(),
+            "8" => recoller(),
+            "9" => {
+                let motif = demander("Motif à surligner, seules les lignes qui le contiennent s'affichent (Entrée : toutes les lignes) :", "");
+                mon_fichier.suivre(Some(motif.as_str()).filter(|motif| !motif.is_empty()));
             },
-            "4" => {
-                mon_fichier.supprimer();
-                break;
+            "10" => mon_fichier.autorisations(),
+            "11" => nouveau_depuis_modele(&historique),
+            "12" => {
+                let destination = demander("Nouveau chemin (fichier ou dossier existant) :", "");
+                if !destination.is_empty() {
+                    mon_fichier.deplacer(&destination, &historique);
+                }
             },
-            "5" => break,
+            "13" => annuler(&mut mon_fichier, &historique),
+            "14" => afficher_historique(&historique),
+            "15" => break,
             _ => println!("Choix invalide."),
         }
     }
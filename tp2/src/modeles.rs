@@ -0,0 +1,92 @@
+// Modèles de fichiers : chaque fichier du dossier templates/ est un modèle, désigné par son nom sans
+// extension ("note-du-jour" pour templates/note-du-jour.txt). À la création, {{date}}, {{user}} et
+// {{titre}} sont remplacés ; un repère inconnu est laissé tel quel et signalé
+
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+pub const DOSSIER_MODELES: &str = "templates";
+
+// Les noms des modèles du dossier, triés
+pub fn lister(dossier: &Path) -> io::Result<Vec<String>> {
+    let mut noms: Vec<String> = fs::read_dir(dossier)?
+        .flatten()
+        .filter(|entree| entree.file_type().map(|t| t.is_file()).unwrap_or(false))
+        .filter_map(|entree| entree.path().file_stem().map(|nom| nom.to_string_lossy().to_string()))
+        .collect();
+    noms.sort();
+    noms.dedup();
+    Ok(noms)
+}
+
+// Le fichier du modèle : le premier (par ordre alphabétique) dont le nom sans extension correspond
+pub fn chemin(dossier: &Path, nom: &str) -> io::Result<Option<PathBuf>> {
+    let mut chemins: Vec<PathBuf> = fs::read_dir(dossier)?
+        .flatten()
+        .map(|entree| entree.path())
+        .filter(|chemin| chemin.is_file() && chemin.file_stem().is_some_and(|stem| stem == nom))
+        .collect();
+    chemins.sort();
+    Ok(chemins.into_iter().next())
+}
+
+// Remplacer les repères {{nom}} par leur valeur ; renvoie aussi les repères inconnus
+pub fn remplir(modele: &str, valeurs: &[(&str, &str)]) -> (String, Vec<String>) {
+    let mut texte = String::new();
+    let mut inconnus = Vec::new();
+    let mut reste = modele;
+    while let Some(debut) = reste.find("{{") {
+        let Some(longueur) = reste[debut + 2..].find("}}") else {
+            break;
+        };
+        let repere = &reste[debut + 2..debut + 2 + longueur];
+        texte.push_str(&reste[..debut]);
+        match valeurs.iter().find(|(nom, _)| *nom == repere.trim()) {
+            Some((_, valeur)) => texte.push_str(valeur),
+            None => {
+                texte.push_str(&reste[debut..debut + 4 + longueur]);
+                inconnus.push(repere.trim().to_string());
+            }
+        }
+        reste = &reste[debut + 4 + longueur..];
+    }
+    texte.push_str(reste);
+    (texte, inconnus)
+}
+
+// Créer destination à partir du modèle ; un fichier existant n'est jamais écrasé.
+// Renvoie les repères inconnus du modèle
+pub fn creer(modele: &Path, destination: &Path, valeurs: &[(&str, &str)]) -> io::Result<Vec<String>> {
+    let (texte, inconnus) = remplir(&fs::read_to_string(modele)?, valeurs);
+    let mut fichier = OpenOptions::new().write(true).create_new(true).open(destination)?;
+    fichier.write_all(texte.as_bytes())?;
+    Ok(inconnus)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_modeles() {
+        let valeurs = [("date", "16/10/2026"), ("user", "amina"), ("titre", "Bilan")];
+        let (texte, inconnus) = remplir("{{titre}} par {{ user }}, le {{date}} ({{projet}}) {{", &valeurs);
+        assert_eq!(texte, "Bilan par amina, le 16/10/2026 ({{projet}}) {{");
+        assert_eq!(inconnus, ["projet"]);
+
+        // Les modèles livrés avec le programme
+        let dossier = Path::new(env!("CARGO_MANIFEST_DIR")).join(DOSSIER_MODELES);
+        let noms = lister(&dossier).unwrap();
+        assert!(noms.contains(&"note-du-jour".to_string()) && noms.contains(&"rapport".to_string()));
+        let modele = chemin(&dossier, "rapport").unwrap().unwrap();
+        assert!(remplir(&fs::read_to_string(&modele).unwrap(), &valeurs).1.is_empty());
+
+        let destination = std::env::temp_dir().join(format!("tp2_modele_{}.txt", std::process::id()));
+        let _ = fs::remove_file(&destination);
+        assert!(creer(&modele, &destination, &valeurs).unwrap().is_empty());
+        assert!(fs::read_to_string(&destination).unwrap().starts_with("RAPPORT : Bilan\nRédigé par amina le 16/10/2026"));
+        assert_eq!(creer(&modele, &destination, &valeurs).unwrap_err().kind(), io::ErrorKind::AlreadyExists);
+        let _ = fs::remove_file(&destination);
+    }
+}
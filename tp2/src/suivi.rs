@@ -0,0 +1,109 @@
+// Suivi d'un fichier comme "tail -f" : les dernières lignes, puis celles qui sont ajoutées.
+// Le fichier est relu régulièrement depuis la dernière position lue ; s'il raccourcit (log vidé
+// ou remplacé par une rotation), la lecture reprend au début
+
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+// Lu à la fin du fichier pour retrouver les dernières lignes
+const FIN_LUE: u64 = 64 * 1024;
+
+const SURLIGNE: &str = "\x1b[1;31m";
+const NORMAL: &str = "\x1b[0m";
+
+pub struct Suivi {
+    chemin: PathBuf,
+    position: u64,
+    reste: Vec<u8>, // début d'une ligne pas encore terminée
+}
+
+impl Suivi {
+    // Commencer à la fin du fichier ; renvoie aussi ses dernières lignes
+    pub fn depuis_la_fin(chemin: &Path, dernieres: usize) -> io::Result<(Suivi, Vec<String>)> {
+        let mut fichier = File::open(chemin)?;
+        let taille = fichier.metadata()?.len();
+        let debut = taille.saturating_sub(FIN_LUE);
+        fichier.seek(SeekFrom::Start(debut))?;
+        let mut fin = Vec::new();
+        fichier.read_to_end(&mut fin)?;
+
+        let mut suivi = Suivi { chemin: chemin.to_path_buf(), position: debut + fin.len() as u64, reste: Vec::new() };
+        let mut lignes = suivi.decouper(&fin);
+        if debut > 0 && !lignes.is_empty() {
+            lignes.remove(0); // coupée par le début de la lecture
+        }
+        let lignes = lignes.split_off(lignes.len().saturating_sub(dernieres));
+        Ok((suivi, lignes))
+    }
+
+    // Les lignes terminées ajoutées depuis le dernier appel
+    pub fn nouvelles_lignes(&mut self) -> io::Result<Vec<String>> {
+        let mut fichier = match File::open(&self.chemin) {
+            Ok(fichier) => fichier,
+            // Pendant une rotation, le fichier peut manquer un instant
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        if fichier.metadata()?.len() < self.position {
+            self.position = 0;
+            self.reste.clear();
+        }
+        fichier.seek(SeekFrom::Start(self.position))?;
+        let mut ajout = Vec::new();
+        fichier.read_to_end(&mut ajout)?;
+        self.position += ajout.len() as u64;
+        Ok(self.decouper(&ajout))
+    }
+
+    fn decouper(&mut self, octets: &[u8]) -> Vec<String> {
+        self.reste.extend_from_slice(octets);
+        let Some(fin) = self.reste.iter().rposition(|&octet| octet == b'\n') else {
+            return Vec::new();
+        };
+        let suite = self.reste.split_off(fin + 1);
+        let termine = std::mem::replace(&mut self.reste, suite);
+        String::from_utf8_lossy(&termine).lines().map(String::from).collect()
+    }
+}
+
+// La ligne à afficher : None si elle ne contient pas le motif, sinon avec le motif surligné
+pub fn filtrer(ligne: &str, motif: Option<&str>) -> Option<String> {
+    match motif {
+        None => Some(ligne.to_string()),
+        Some(motif) if ligne.contains(motif) => Some(ligne.replace(motif, &format!("{}{}{}", SURLIGNE, motif, NORMAL))),
+        Some(_) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::{self, OpenOptions};
+    use std::io::Write;
+
+    #[test]
+    fn test_suivi() {
+        let chemin = std::env::temp_dir().join(format!("tp2_suivi_{}.log", std::process::id()));
+        fs::write(&chemin, "un\ndeux\ntrois\n").unwrap();
+        let (mut suivi, dernieres) = Suivi::depuis_la_fin(&chemin, 2).unwrap();
+        assert_eq!(dernieres, ["deux", "trois"]);
+        assert!(suivi.nouvelles_lignes().unwrap().is_empty());
+
+        // Une ligne n'est rendue qu'une fois terminée
+        let mut fichier = OpenOptions::new().append(true).open(&chemin).unwrap();
+        write!(fichier, "quatre\ncin").unwrap();
+        assert_eq!(suivi.nouvelles_lignes().unwrap(), ["quatre"]);
+        writeln!(fichier, "q").unwrap();
+        assert_eq!(suivi.nouvelles_lignes().unwrap(), ["cinq"]);
+
+        // Fichier vidé puis réécrit : la lecture reprend au début
+        fs::write(&chemin, "six\n").unwrap();
+        assert_eq!(suivi.nouvelles_lignes().unwrap(), ["six"]);
+        let _ = fs::remove_file(&chemin);
+
+        assert_eq!(filtrer("level=error msg=x", Some("error")), Some("level=\x1b[1;31merror\x1b[0m msg=x".to_string()));
+        assert_eq!(filtrer("level=info", Some("error")), None);
+        assert_eq!(filtrer("level=info", None), Some("level=info".to_string()));
+    }
+}
@@ -0,0 +1,229 @@
+//journalisation par lots côté client : les messages s'accumulent en mémoire et partent vers le serveur quand
+//le lot atteint `size` messages, quand le plus ancien attend depuis `delay`, ou sur flush(). Un fil dédié
+//tient la connexion ; à la fermeture (close() ou Drop), ce qui reste est envoyé avant de quitter
+
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::compression::{self, Mode};
+use crate::espaces;
+
+//réglages des lots
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatchOptions {
+    pub size: usize,             //messages par lot
+    pub delay: Duration,         //attente maximale d'un message avant l'envoi de son lot
+    pub mode: Mode,              //texte : une ligne par message ; gzip : un bloc compressé par lot
+    pub namespace: Option<String>, //espace où écrire ("NS ...")
+}
+
+impl Default for BatchOptions {
+    fn default() -> Self {
+        BatchOptions { size: 100, delay: Duration::from_millis(500), mode: Mode::Texte, namespace: None }
+    }
+}
+
+enum Commande {
+    Message(String),
+    Flush(Sender<io::Result<()>>),
+}
+
+//client du serveur de journalisation qui envoie ses messages par lots
+pub struct BatchLogger {
+    commandes: Option<Sender<Commande>>,
+    fil: Option<JoinHandle<io::Result<()>>>,
+}
+
+impl BatchLogger {
+    //se connecte tout de suite, pour qu'une adresse fausse ou un espace refusé se voient dès le départ
+    pub fn connect(addr: SocketAddr, options: BatchOptions) -> io::Result<Self> {
+        let connexion = Connexion::ouvrir(addr, &options)?;
+        let (commandes, reception) = mpsc::channel();
+        let fil = thread::Builder::new()
+            .name("tp3-batch".to_string())
+            .spawn(move || Envoi { addr, options, connexion: Some(connexion), lot: Vec::new(), debut: None }.boucle(reception))?;
+        Ok(BatchLogger { commandes: Some(commandes), fil: Some(fil) })
+    }
+
+    //ajoute un message au lot, sans attendre
+    pub fn log(&self, message: impl Into<String>) {
+        if let Some(commandes) = &self.commandes {
+            let _ = commandes.send(Commande::Message(message.into()));
+        }
+    }
+
+    //envoie le lot en cours et attend que le serveur l'ait reçu ; en cas d'erreur, le lot est gardé pour la suite
+    pub fn flush(&self) -> io::Result<()> {
+        let (reponse, attente) = mpsc::channel();
+        let envoye = self.commandes.as_ref().is_some_and(|commandes| commandes.send(Commande::Flush(reponse)).is_ok());
+        if !envoye {
+            return Err(io::Error::new(io::ErrorKind::BrokenPipe, "journaliseur arrêté"));
+        }
+        attente.recv().unwrap_or_else(|_| Err(io::Error::new(io::ErrorKind::BrokenPipe, "journaliseur arrêté")))
+    }
+
+    //envoie ce qui reste, termine la connexion ("quit") et attend la fin du fil
+    pub fn close(mut self) -> io::Result<()> {
+        self.arreter()
+    }
+
+    fn arreter(&mut self) -> io::Result<()> {
+        self.commandes = None; //le fil voit la fin du canal
+        match self.fil.take().map(JoinHandle::join) {
+            Some(Ok(resultat)) => resultat,
+            Some(Err(_)) => Err(io::Error::other("fil d'envoi interrompu")),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for BatchLogger {
+    fn drop(&mut self) {
+        let _ = self.arreter();
+    }
+}
+
+//connexion au serveur, négociée (espace, mode)
+struct Connexion {
+    flux: TcpStream,
+    mode: Mode,
+}
+
+impl Connexion {
+    fn ouvrir(addr: SocketAddr, options: &BatchOptions) -> io::Result<Self> {
+        let flux = TcpStream::connect(addr)?;
+        let mut reponses = BufReader::new(flux.try_clone()?);
+        let mut negocier = |demande: String| -> io::Result<()> {
+            writeln!(&flux, "{}", demande)?;
+            let mut reponse = String::new();
+            reponses.read_line(&mut reponse)?;
+            match reponse.starts_with("OK") {
+                true => Ok(()),
+                false => Err(io::Error::other(format!("{} refusé: {}", demande, reponse.trim()))),
+            }
+        };
+        if let Some(espace) = &options.namespace {
+            negocier(espaces::demande(espace))?;
+        }
+        if options.mode != Mode::Texte {
+            negocier(options.mode.demande())?;
+        }
+        Ok(Connexion { flux, mode: options.mode })
+    }
+
+    fn envoyer(&mut self, lot: &[String]) -> io::Result<()> {
+        let mut paquet = Vec::new();
+        match self.mode {
+            Mode::Texte => {
+                for message in lot {
+                    paquet.extend_from_slice(message.as_bytes());
+                    paquet.push(b'\n');
+                }
+            }
+            Mode::Gzip => {
+                let compresse = compression::compresser(lot)?;
+                paquet.extend_from_slice(&(compresse.len() as u32).to_be_bytes());
+                paquet.extend_from_slice(&compresse);
+            }
+        }
+        self.flux.write_all(&paquet)?;
+        self.flux.flush()
+    }
+}
+
+//état du fil d'envoi
+struct Envoi {
+    addr: SocketAddr,
+    options: BatchOptions,
+    connexion: Option<Connexion>, //None après une erreur : reconnexion au prochain envoi
+    lot: Vec<String>,
+    debut: Option<Instant>,       //arrivée du plus ancien message du lot
+}
+
+impl Envoi {
+    fn boucle(mut self, commandes: Receiver<Commande>) -> io::Result<()> {
+        loop {
+            let commande = match self.debut {
+                Some(debut) => commandes.recv_timeout(self.options.delay.saturating_sub(debut.elapsed())),
+                None => commandes.recv().map_err(|_| RecvTimeoutError::Disconnected),
+            };
+            match commande {
+                Ok(Commande::Message(message)) => {
+                    self.debut.get_or_insert_with(Instant::now);
+                    self.lot.push(message);
+                    if self.lot.len() >= self.options.size {
+                        let _ = self.vider();
+                    }
+                }
+                Ok(Commande::Flush(reponse)) => {
+                    let _ = reponse.send(self.vider());
+                }
+                Err(RecvTimeoutError::Timeout) => {
+                    //serveur injoignable : on réessaiera au prochain délai
+                    if self.vider().is_err() {
+                        self.debut = Some(Instant::now());
+                    }
+                }
+                //plus de journaliseur : dernier lot, puis fin de la connexion
+                Err(RecvTimeoutError::Disconnected) => {
+                    self.lot.push("quit".to_string());
+                    return self.vider();
+                }
+            }
+        }
+    }
+
+    //envoie le lot ; en cas d'échec il reste en mémoire
+    fn vider(&mut self) -> io::Result<()> {
+        if self.lot.is_empty() {
+            return Ok(());
+        }
+        let connexion = match &mut self.connexion {
+            Some(connexion) => connexion,
+            None => self.connexion.insert(Connexion::ouvrir(self.addr, &self.options)?),
+        };
+        if let Err(e) = connexion.envoyer(&self.lot) {
+            self.connexion = None;
+            return Err(e);
+        }
+        self.lot.clear();
+        self.debut = None;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+    use std::net::TcpListener;
+
+    #[test]
+    fn test_lots() {
+        let serveur = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = serveur.local_addr().unwrap();
+        let options = BatchOptions { size: 2, delay: Duration::from_secs(60), ..BatchOptions::default() };
+        let logger = BatchLogger::connect(addr, options).unwrap();
+        let (mut flux, _) = serveur.accept().unwrap();
+        flux.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+
+        // Deux messages : le lot est plein et part seul
+        logger.log("un");
+        logger.log("deux");
+        let mut tampon = [0u8; 8];
+        flux.read_exact(&mut tampon).unwrap();
+        assert_eq!(&tampon, b"un\ndeux\n");
+
+        // Un message puis flush() : envoyé sans attendre le délai ; le reste part à la fermeture
+        logger.log("trois");
+        logger.flush().unwrap();
+        logger.log("quatre");
+        drop(logger);
+        let mut reste = String::new();
+        flux.read_to_string(&mut reste).unwrap();
+        assert_eq!(reste, "trois\nquatre\nquit\n");
+    }
+}
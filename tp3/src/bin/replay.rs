@@ -0,0 +1,147 @@
+//outil de rejeu : relit un fichier de logs et renvoie ses entrées à un autre serveur de journalisation,
+//au rythme d'origine ou accéléré. Chaque message garde son horodatage d'origine en tête ("[replay <date>] ...")
+
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use config_commun::{parse, Settings}; //options : --file, --addr, --speed, --mode, --batch
+use net_commun::{FramedTransport, LengthPrefixedCodec, LineCodec};
+use serde::Deserialize;
+use tokio::io::{AsyncBufReadExt, BufReader}; //lecture du fichier ligne par ligne
+use tokio::net::TcpStream;
+use tp3::compression::{self, Mode};
+use tp3::journal::Entree;
+
+//réglages du rejeu ; variables REPLAY_* (REPLAY_SPEED pour --speed)
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct Options {
+    file: PathBuf,    //fichier de logs à relire
+    addr: SocketAddr, //serveur qui reçoit les entrées
+    speed: f64,       //1 : rythme d'origine, 10 : dix fois plus vite, 0 : sans attendre
+    mode: Mode,       //texte, ou lots compressés (gzip)
+    batch: usize,     //mode gzip : entrées au plus par lot
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            file: PathBuf::from("logs/server.log"),
+            addr: "127.0.0.1:8080".parse().expect("adresse par défaut valide"),
+            speed: 1.0,
+            mode: Mode::Texte,
+            batch: 100,
+        }
+    }
+}
+
+impl Settings for Options {
+    const ENV_PREFIX: &'static str = "REPLAY_";
+
+    fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "file" => self.file = PathBuf::from(value),
+            "addr" => self.addr = parse(key, value)?,
+            "speed" => self.speed = parse(key, value)?,
+            "mode" => self.mode = parse(key, value)?,
+            "batch" => self.batch = parse(key, value)?,
+            _ => return Err(format!("Option inconnue: {}", key)),
+        }
+        Ok(())
+    }
+
+    fn validate(&mut self) -> Result<(), String> {
+        if !(self.speed >= 0.0 && self.speed.is_finite()) {
+            return Err("speed doit être positif (0 : sans attendre)".to_string());
+        }
+        if self.batch == 0 {
+            return Err("batch doit être positif".to_string());
+        }
+        Ok(())
+    }
+}
+
+//connexion au serveur cible, dans le mode choisi
+enum Cible {
+    Texte(FramedTransport<TcpStream, LineCodec>),
+    Gzip { lots: FramedTransport<TcpStream, LengthPrefixedCodec>, lot: Vec<String>, taille: usize },
+}
+
+impl Cible {
+    async fn connecter(options: &Options) -> Result<Self, Box<dyn std::error::Error>> {
+        let stream = TcpStream::connect(options.addr).await?;
+        let mut lignes = FramedTransport::new(stream, LineCodec::default());
+        if options.mode == Mode::Texte {
+            return Ok(Cible::Texte(lignes));
+        }
+        lignes.write_frame(&options.mode.demande()).await?;
+        match lignes.read_frame().await? {
+            Some(reponse) if reponse.starts_with("OK") => {}
+            reponse => return Err(format!("mode {} refusé: {}", options.mode, reponse.unwrap_or_default()).into()),
+        }
+        Ok(Cible::Gzip { lots: lignes.with_codec(LengthPrefixedCodec::default()), lot: Vec::new(), taille: options.batch })
+    }
+
+    //envoie une ligne ; en mode gzip, elle attend dans le lot jusqu'à ce qu'il soit plein
+    async fn envoyer(&mut self, ligne: String) -> Result<(), Box<dyn std::error::Error>> {
+        match self {
+            Cible::Texte(lignes) => lignes.write_frame(&ligne).await?,
+            Cible::Gzip { lot, taille, .. } => {
+                lot.push(ligne);
+                if lot.len() >= *taille {
+                    self.vider().await?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    //envoie le lot en attente (avant une pause, et à la fin)
+    async fn vider(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        if let Cible::Gzip { lots, lot, .. } = self {
+            if !lot.is_empty() {
+                lots.write_frame(&compression::compresser(lot)?).await?;
+                lot.clear();
+            }
+        }
+        Ok(())
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let options: Options = config_commun::load().map_err(|e| e.to_string())?;
+    println!("=== REJEU DE LOGS ===");
+    println!(" {} -> {} (vitesse {}, mode {})", options.file.display(), options.addr, options.speed, options.mode);
+
+    let fichier = tokio::fs::File::open(&options.file).await.map_err(|e| format!("{}: {}", options.file.display(), e))?;
+    let mut lignes = BufReader::new(fichier).lines();
+    let mut cible = Cible::connecter(&options).await?;
+
+    let (mut envoyees, mut ignorees) = (0u64, 0u64);
+    let mut precedente = None;
+    while let Some(ligne) = lignes.next_line().await? {
+        let Some(entree) = Entree::analyser(&ligne) else {
+            ignorees += 1;
+            continue;
+        };
+
+        //attendre l'écart d'origine entre deux entrées, divisé par la vitesse
+        if let Some(precedente) = precedente.replace(entree.horodatage) {
+            let ecart = (entree.horodatage - precedente).to_std().unwrap_or_default();
+            if options.speed > 0.0 && !ecart.is_zero() {
+                cible.vider().await?;
+                tokio::time::sleep(Duration::from_secs_f64(ecart.as_secs_f64() / options.speed)).await;
+            }
+        }
+
+        cible.envoyer(entree.rejeu()).await?;
+        envoyees += 1;
+    }
+    cible.envoyer("quit".to_string()).await?;
+    cible.vider().await?;
+
+    println!(" {} entrée(s) renvoyée(s), {} ligne(s) illisible(s) ignorée(s)", envoyees, ignorees);
+    Ok(())
+}
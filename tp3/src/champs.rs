@@ -0,0 +1,170 @@
+//métadonnées des messages : une ligne qui commence par "cle=valeur" est une entrée structurée
+//("level=error service=api msg=\"délai dépassé\""). Le serveur vérifie les clés et leur nombre, puis l'écrit
+//sous une forme régulière, que les requêtes par période peuvent filtrer ("QUERY since ... level=error")
+
+use std::fmt;
+
+//champs d'une entrée, dans l'ordre du client
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Champs(Vec<(String, String)>);
+
+impl Champs {
+    //None pour un message ordinaire (le premier mot n'est pas "cle=valeur") ; une erreur pour une ligne
+    //structurée mal formée (guillemet non fermé, mot sans "=", clé en double)
+    pub fn analyser(ligne: &str) -> Option<Result<Self, String>> {
+        let premier = ligne.split_whitespace().next()?;
+        let (cle, _) = premier.split_once('=')?;
+        if !cle_valide(cle) {
+            return None;
+        }
+        Some(analyser_champs(ligne))
+    }
+
+    //champs d'un message du fichier de logs ("Client 3: level=error ..."), s'il en a
+    pub fn du_message(message: &str) -> Option<Self> {
+        let (_, texte) = message.split_once(": ")?;
+        Self::analyser(texte)?.ok()
+    }
+
+    pub fn get(&self, cle: &str) -> Option<&str> {
+        self.0.iter().find(|(nom, _)| nom == cle).map(|(_, valeur)| valeur.as_str())
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    //tous les champs de `filtre` sont présents avec la même valeur
+    pub fn contient(&self, filtre: &Champs) -> bool {
+        filtre.0.iter().all(|(cle, valeur)| self.get(cle) == Some(valeur))
+    }
+}
+
+//forme écrite dans le fichier : cle=valeur séparés par un espace, valeur entre guillemets si besoin
+impl fmt::Display for Champs {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, (cle, valeur)) in self.0.iter().enumerate() {
+            if i > 0 {
+                f.write_str(" ")?;
+            }
+            let simple = !valeur.is_empty() && !valeur.contains(|c: char| c.is_whitespace() || c == '"' || c == '\\' || c == '=');
+            match simple {
+                true => write!(f, "{}={}", cle, valeur)?,
+                false => write!(f, "{}=\"{}\"", cle, valeur.replace('\\', "\\\\").replace('"', "\\\""))?,
+            }
+        }
+        Ok(())
+    }
+}
+
+//règles du serveur pour les entrées structurées
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Regles {
+    pub autorisees: Vec<String>, //clés acceptées (vide : toutes)
+    pub max: usize,              //champs au plus par entrée
+}
+
+impl Regles {
+    pub fn valider(&self, champs: &Champs) -> Result<(), String> {
+        if champs.len() > self.max {
+            return Err(format!("{} champs, {} au plus", champs.len(), self.max));
+        }
+        if self.autorisees.is_empty() {
+            return Ok(());
+        }
+        match champs.0.iter().find(|(cle, _)| !self.autorisees.contains(cle)) {
+            Some((cle, _)) => Err(format!("champ non autorisé: {} (autorisés: {})", cle, self.autorisees.join(", "))),
+            None => Ok(()),
+        }
+    }
+}
+
+//clé : minuscules, chiffres, '_', '.' et '-', en commençant par une lettre
+pub fn cle_valide(cle: &str) -> bool {
+    cle.starts_with(|c: char| c.is_ascii_lowercase())
+        && cle.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, '_' | '.' | '-'))
+}
+
+fn analyser_champs(ligne: &str) -> Result<Champs, String> {
+    let mut champs: Vec<(String, String)> = Vec::new();
+    let mut reste = ligne.trim();
+    while !reste.is_empty() {
+        let (cle, suite) = reste.split_once('=').ok_or_else(|| format!("champ sans valeur: {}", reste))?;
+        if !cle_valide(cle) {
+            return Err(format!("clé invalide: {}", cle));
+        }
+        if champs.iter().any(|(nom, _)| nom == cle) {
+            return Err(format!("champ en double: {}", cle));
+        }
+        let (valeur, suite) = match suite.strip_prefix('"') {
+            Some(entre_guillemets) => valeur_entre_guillemets(entre_guillemets).ok_or_else(|| format!("guillemet non fermé: {}", cle))?,
+            None => {
+                let (valeur, suite) = suite.split_once(char::is_whitespace).unwrap_or((suite, ""));
+                (valeur.to_string(), suite)
+            }
+        };
+        champs.push((cle.to_string(), valeur));
+        reste = suite.trim_start();
+    }
+    Ok(Champs(champs))
+}
+
+//valeur jusqu'au guillemet fermant (\" et \\ échappés) et ce qui suit
+fn valeur_entre_guillemets(texte: &str) -> Option<(String, &str)> {
+    let mut valeur = String::new();
+    let mut caracteres = texte.char_indices();
+    while let Some((i, c)) = caracteres.next() {
+        match c {
+            '"' => {
+                let suite = &texte[i + 1..];
+                //la valeur se termine au guillemet : la suite commence par un espace, ou rien
+                return match suite.is_empty() || suite.starts_with(char::is_whitespace) {
+                    true => Some((valeur, suite)),
+                    false => None,
+                };
+            }
+            '\\' => valeur.push(caracteres.next()?.1),
+            c => valeur.push(c),
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_analyse() {
+        assert_eq!(Champs::analyser("bonjour à tous"), None);
+        assert_eq!(Champs::analyser("2+2=4"), None);
+        let champs = Champs::analyser(r#"level=error service=api msg="délai \"dépassé\"""#).unwrap().unwrap();
+        assert_eq!(champs.get("level"), Some("error"));
+        assert_eq!(champs.get("msg"), Some("délai \"dépassé\""));
+        assert_eq!(champs.to_string(), r#"level=error service=api msg="délai \"dépassé\"""#);
+        assert_eq!(Champs::analyser(&champs.to_string()), Some(Ok(champs.clone())));
+
+        assert!(Champs::analyser("level=error et puis").unwrap().is_err());
+        assert!(Champs::analyser("level=error level=info").unwrap().is_err());
+        assert!(Champs::analyser("msg=\"pas fermé").unwrap().is_err());
+
+        let filtre = Champs::analyser("level=error").unwrap().unwrap();
+        assert!(champs.contient(&filtre));
+        assert!(Champs::du_message("Client 3: level=error service=api").is_some_and(|champs| champs.contient(&filtre)));
+        assert_eq!(Champs::du_message("Client 3: bonjour"), None);
+    }
+
+    #[test]
+    fn test_regles() {
+        let regles = Regles { autorisees: vec!["level".to_string(), "msg".to_string()], max: 2 };
+        assert!(regles.valider(&Champs::analyser("level=info msg=ok").unwrap().unwrap()).is_ok());
+        assert!(regles.valider(&Champs::analyser("level=info user=bob").unwrap().unwrap()).is_err());
+        assert!(regles.valider(&Champs::analyser("level=info msg=ok level2=x").unwrap().unwrap()).is_err());
+        let toutes = Regles { autorisees: Vec::new(), max: 8 };
+        assert!(toutes.valider(&Champs::analyser("user=bob").unwrap().unwrap()).is_ok());
+    }
+}
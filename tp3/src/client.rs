@@ -1,42 +1,53 @@
 //test client
 
 
-use tokio::net::TcpStream;
-use tokio::io::AsyncWriteExt;
+use tp3::batch::BatchLogger;
+use tp3::configuration::Config;
 use std::io::{self, Write};
 
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
+//lit une ligne tapée ; None en fin d'entrée (Ctrl+D)
+fn lire_ligne() -> io::Result<Option<String>> {
+    print!("> ");
+    io::stdout().flush()?;
+
+    let mut input = String::new();
+    if io::stdin().read_line(&mut input)? == 0 {
+        return Ok(None);
+    }
+    Ok(Some(input.trim().to_string()))
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("=== CLIENT DE TEST ===");
     println!(" Connexion au serveur de logs...");
-    
-    let mut stream = TcpStream::connect("127.0.0.1:8080").await?;
+
+    //même configuration que le serveur : --config, TP3_ADDR ou --addr ; --mode gzip pour compresser,
+    //--namespace pour un espace, --batch-size et --flush-ms pour les lots
+    let config: Config = config_commun::load().map_err(|e| e.to_string())?;
+    let logger = BatchLogger::connect(config.addr, config.batch_options())?;
     println!("Connecté au serveur !");
-    
-    println!("Tapez vos messages (tapez 'quit' pour quitter) :");
-    
-    loop {
-        print!("> ");
-        io::stdout().flush()?;
-        
-        let mut input = String::new();
-        io::stdin().read_line(&mut input)?;
-        let message = input.trim();
-        
+    if let Some(espace) = &config.namespace {
+        println!("Espace: {}", espace);
+    }
+
+    //les messages partent par lots de --batch-size, après --flush-ms, ou tout de suite sur une ligne vide
+    println!("Tapez vos messages, une ligne vide pour envoyer tout de suite (tapez 'quit' pour quitter) :");
+    while let Some(message) = lire_ligne()? {
         if message.is_empty() {
+            logger.flush()?;
+            println!("Messages envoyés");
             continue;
         }
-        
-        // Envoyer le message au serveur
-        stream.write_all(format!("{}\n", message).as_bytes()).await?;
-        
+
         if message.eq_ignore_ascii_case("quit") {
             break;
         }
-        
-        println!("Message envoyé: {}", message);
+
+        logger.log(message);
     }
-    
+
+    //ce qui reste part avant la déconnexion
     println!("Déconnexion...");
+    logger.close()?;
     Ok(())
-}
\ No newline at end of file
+}
@@ -0,0 +1,111 @@
+//compression des messages : négociée par la première ligne du client ("MODE gzip"), à laquelle le serveur
+//répond "OK gzip" (ou "ERR ..." avant de fermer la connexion). Ensuite le client n'envoie plus de lignes
+//mais des lots : un bloc précédé de sa longueur (4 octets big-endian) qui contient les lignes compressées
+
+use std::fmt;
+use std::io::{Read, Write};
+use std::str::FromStr;
+
+use flate2::read::GzDecoder; //décompression d'un lot
+use flate2::write::GzEncoder; //compression d'un lot
+use flate2::Compression;
+use net_commun::FrameError;
+use serde::Deserialize;
+
+//façon dont le client envoie ses messages
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Mode {
+    #[default]
+    Texte, //une ligne par message, le protocole d'origine
+    Gzip,  //lots de lignes compressés
+}
+
+impl Mode {
+    pub fn nom(&self) -> &'static str {
+        match self {
+            Mode::Texte => "texte",
+            Mode::Gzip => "gzip",
+        }
+    }
+
+    //ligne de négociation envoyée par le client
+    pub fn demande(&self) -> String {
+        format!("MODE {}", self.nom())
+    }
+}
+
+impl fmt::Display for Mode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.nom())
+    }
+}
+
+impl FromStr for Mode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "texte" => Ok(Mode::Texte),
+            "gzip" => Ok(Mode::Gzip),
+            autre => Err(format!("mode inconnu: {} (texte ou gzip)", autre)),
+        }
+    }
+}
+
+//mode demandé par une ligne "MODE ..." ; None pour une ligne de log ordinaire
+pub fn mode_demande(ligne: &str) -> Option<&str> {
+    ligne.trim().strip_prefix("MODE ")
+}
+
+//compresse des lignes en un lot
+pub fn compresser(lignes: &[String]) -> std::io::Result<Vec<u8>> {
+    let mut encodeur = GzEncoder::new(Vec::new(), Compression::default());
+    for ligne in lignes {
+        encodeur.write_all(ligne.as_bytes())?;
+        encodeur.write_all(b"\n")?;
+    }
+    encodeur.finish()
+}
+
+//lignes d'un lot ; refusé (Invalid, comme une ligne illisible) s'il dépasse `max` octets une fois décompressé,
+//sans tout décompresser
+pub fn decompresser(lot: &[u8], max: usize) -> Result<Vec<String>, FrameError> {
+    let mut contenu = Vec::new();
+    GzDecoder::new(lot)
+        .take((max as u64).saturating_add(1))
+        .read_to_end(&mut contenu)
+        .map_err(|e| FrameError::Invalid(format!("lot compressé illisible: {}", e)))?;
+    if contenu.len() > max {
+        return Err(FrameError::Invalid(format!("lot de plus de {} octets une fois décompressé", max)));
+    }
+    let texte = String::from_utf8(contenu).map_err(|_| FrameError::Invalid("lot qui n'est pas en UTF-8".to_string()))?;
+    Ok(texte.lines().map(|ligne| ligne.trim_end_matches('\r').to_string()).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lots() {
+        let lignes = vec!["première ligne".to_string(), "seconde".to_string()];
+        let lot = compresser(&lignes).unwrap();
+        assert_eq!(decompresser(&lot, 1024).unwrap(), lignes);
+
+        // Des lignes répétées : le lot est bien plus petit que le texte, mais dépasse la limite une fois décompressé
+        let bavard = vec!["GET /index.html 200".to_string(); 1000];
+        let lot = compresser(&bavard).unwrap();
+        assert!(lot.len() < 1000);
+        assert!(matches!(decompresser(&lot, 1000), Err(FrameError::Invalid(_))));
+        assert!(matches!(decompresser(b"pas du gzip", 1000), Err(FrameError::Invalid(_))));
+    }
+
+    #[test]
+    fn test_negociation() {
+        assert_eq!(mode_demande(&Mode::Gzip.demande()), Some("gzip"));
+        assert_eq!(mode_demande("Client prêt"), None);
+        assert_eq!("GZIP".parse::<Mode>(), Ok(Mode::Gzip));
+        assert!("zstd".parse::<Mode>().is_err());
+    }
+}
@@ -0,0 +1,240 @@
+//configuration : valeurs par défaut, puis fichier TOML (--config ou TP3_CONFIG), puis variables TP3_*, puis options
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use config_commun::{parse, Settings}; //chargement commun aux TP
+use serde::Deserialize;
+
+use crate::archive::{ArchiveOptions, PointDAcces};
+use crate::batch::BatchOptions;
+use crate::champs::{self, Regles};
+use crate::compression::Mode;
+use crate::espaces::{self, Politique};
+
+//réglages du serveur ; le client n'utilise que l'adresse et ses propres réglages (mode, espace, lots)
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub addr: SocketAddr,            //adresse d'écoute du serveur (et de connexion du client)
+    pub log_file: PathBuf,           //fichier où les messages sont ajoutés
+    pub max_line_length: usize,      //taille maximale d'une ligne envoyée par un client (au-delà, la connexion est fermée)
+    pub index_interval: usize,       //entrées entre deux points de l'index (server.log.idx)
+    pub max_batch_size: usize,       //taille maximale d'un lot compressé, une fois décompressé
+    pub rotate_size: u64,            //taille au-delà de laquelle un fichier est renommé en .1 (0 : jamais)
+    pub retention: usize,            //anciens fichiers gardés après rotation (.1 à .N)
+    pub quota: u32,                  //entrées de clients acceptées par minute et par espace (0 : sans limite)
+    pub namespaces: HashMap<String, NamespaceConfig>, //réglages propres à certains espaces ([namespaces.payments])
+    pub write_buffer: usize,         //entrées gardées en mémoire quand le fichier ne peut pas être écrit (disque plein)
+    pub allowed_fields: Vec<String>, //clés acceptées dans les entrées structurées ("level=error msg=..."), vide : toutes
+    pub max_fields: usize,           //champs au plus par entrée structurée
+    pub archive_endpoint: Option<String>, //stockage compatible S3 où envoyer les fichiers après rotation (http://minio:9000)
+    pub archive_bucket: String,      //bucket des archives
+    pub archive_prefix: String,      //début des clés des archives ("prod/tp3/")
+    pub archive_region: String,      //région de la signature (us-east-1 pour MinIO)
+    pub archive_access_key: String,  //identifiants du stockage (TP3_ARCHIVE_ACCESS_KEY, TP3_ARCHIVE_SECRET_KEY)
+    pub archive_secret_key: String,
+    pub health_addr: Option<SocketAddr>, //serveur : point de santé HTTP (GET /healthz), désactivé par défaut
+    pub daemon: bool,                //mode démon : détaché du terminal (ou suivi par systemd), avec un fichier pid
+    pub pid_file: Option<PathBuf>,   //fichier pid du mode démon (par défaut serveur.pid, à côté des logs)
+    pub mode: Mode,                  //client : lignes en texte ou lots compressés (gzip)
+    pub namespace: Option<String>,   //client : espace où écrire ("NS ..." en début de connexion)
+    pub batch_size: usize,           //client : messages par lot
+    pub flush_ms: u64,               //client : attente maximale d'un message avant l'envoi de son lot
+}
+
+//réglages d'un espace ; ceux qui manquent sont pris au niveau principal
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NamespaceConfig {
+    pub rotate_size: Option<u64>,
+    pub retention: Option<usize>,
+    pub quota: Option<u32>,
+}
+
+impl Config {
+    //fichier pid du mode démon
+    pub fn pid_file(&self) -> PathBuf {
+        self.pid_file.clone().unwrap_or_else(|| self.dossier_des_logs().join("serveur.pid"))
+    }
+
+    //sorties du serveur détaché (ses propres messages, pas ceux des clients)
+    pub fn daemon_output(&self) -> PathBuf {
+        self.dossier_des_logs().join("serveur.out")
+    }
+
+    //réglages des lots du client
+    pub fn batch_options(&self) -> BatchOptions {
+        BatchOptions {
+            size: self.batch_size,
+            delay: Duration::from_millis(self.flush_ms),
+            mode: self.mode,
+            namespace: self.namespace.clone(),
+        }
+    }
+
+    fn dossier_des_logs(&self) -> &Path {
+        self.log_file.parent().unwrap_or(Path::new(""))
+    }
+
+    //envoi des archives après rotation, si un stockage est configuré
+    pub fn archive_options(&self) -> Result<Option<ArchiveOptions>, String> {
+        let Some(endpoint) = &self.archive_endpoint else {
+            return Ok(None);
+        };
+        Ok(Some(ArchiveOptions {
+            endpoint: PointDAcces::analyser(endpoint)?,
+            bucket: self.archive_bucket.clone(),
+            prefix: self.archive_prefix.clone(),
+            region: self.archive_region.clone(),
+            access_key: self.archive_access_key.clone(),
+            secret_key: self.archive_secret_key.clone(),
+            base: self.dossier_des_logs().to_path_buf(),
+        }))
+    }
+
+    //règles des entrées structurées
+    pub fn regles_des_champs(&self) -> Regles {
+        Regles { autorisees: self.allowed_fields.clone(), max: self.max_fields }
+    }
+
+    //règles d'un espace (None : le fichier principal)
+    pub fn politique(&self, espace: Option<&str>) -> Politique {
+        let propre = espace.and_then(|espace| self.namespaces.get(espace)).cloned().unwrap_or_default();
+        Politique {
+            rotate_size: propre.rotate_size.unwrap_or(self.rotate_size),
+            retention: propre.retention.unwrap_or(self.retention),
+            quota: propre.quota.unwrap_or(self.quota),
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            addr: "127.0.0.1:8080".parse().expect("adresse par défaut valide"),
+            log_file: PathBuf::from("logs/server.log"),
+            max_line_length: 8 * 1024,
+            index_interval: 100,
+            max_batch_size: 1024 * 1024,
+            rotate_size: 0,
+            retention: 5,
+            quota: 0,
+            namespaces: HashMap::new(),
+            write_buffer: 10_000,
+            allowed_fields: ["level", "service", "msg", "host", "request_id", "user"].map(String::from).to_vec(),
+            max_fields: 16,
+            archive_endpoint: None,
+            archive_bucket: "logs".to_string(),
+            archive_prefix: String::new(),
+            archive_region: "us-east-1".to_string(),
+            archive_access_key: String::new(),
+            archive_secret_key: String::new(),
+            health_addr: None,
+            daemon: false,
+            pid_file: None,
+            mode: Mode::Texte,
+            namespace: None,
+            batch_size: 100,
+            flush_ms: 500,
+        }
+    }
+}
+
+impl Settings for Config {
+    const ENV_PREFIX: &'static str = "TP3_";
+
+    //options de la ligne de commande (sans --) et variables d'environnement (TP3_LOG_FILE pour --log-file)
+    fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "addr" => self.addr = parse(key, value)?,
+            "log-file" => self.log_file = PathBuf::from(value),
+            "max-line-length" => self.max_line_length = parse(key, value)?,
+            "index-interval" => self.index_interval = parse(key, value)?,
+            "max-batch-size" => self.max_batch_size = parse(key, value)?,
+            "rotate-size" => self.rotate_size = parse(key, value)?,
+            "retention" => self.retention = parse(key, value)?,
+            "quota" => self.quota = parse(key, value)?,
+            "mode" => self.mode = parse(key, value)?,
+            "namespace" => self.namespace = Some(value.trim().to_string()),
+            "batch-size" => self.batch_size = parse(key, value)?,
+            "flush-ms" => self.flush_ms = parse(key, value)?,
+            "write-buffer" => self.write_buffer = parse(key, value)?,
+            "allowed-fields" => self.allowed_fields = config_commun::list(value).collect(),
+            "max-fields" => self.max_fields = parse(key, value)?,
+            "archive-endpoint" => self.archive_endpoint = Some(value.trim().to_string()),
+            "archive-bucket" => self.archive_bucket = value.trim().to_string(),
+            "archive-prefix" => self.archive_prefix = value.trim().to_string(),
+            "archive-region" => self.archive_region = value.trim().to_string(),
+            "archive-access-key" => self.archive_access_key = value.trim().to_string(),
+            "archive-secret-key" => self.archive_secret_key = value.trim().to_string(),
+            "health-addr" => self.health_addr = Some(parse(key, value)?),
+            "daemon" => self.daemon = parse(key, value)?,
+            "pid-file" => self.pid_file = Some(PathBuf::from(value)),
+            _ => return Err(format!("Option inconnue: {}", key)),
+        }
+        Ok(())
+    }
+
+    fn validate(&mut self) -> Result<(), String> {
+        if self.max_line_length == 0 {
+            return Err("max_line_length doit être positif".to_string());
+        }
+        if self.batch_size == 0 {
+            return Err("batch_size doit être positif".to_string());
+        }
+        if self.index_interval == 0 {
+            return Err("index_interval doit être positif".to_string());
+        }
+        if self.max_batch_size < self.max_line_length {
+            return Err("max_batch_size doit contenir au moins une ligne (max_line_length)".to_string());
+        }
+        if self.log_file.as_os_str().is_empty() {
+            return Err("log_file ne peut pas être vide".to_string());
+        }
+        if self.archive_endpoint.is_some() {
+            self.archive_options()?;
+            if self.archive_bucket.is_empty() || self.archive_access_key.is_empty() || self.archive_secret_key.is_empty() {
+                return Err("archive_endpoint demande archive_bucket, archive_access_key et archive_secret_key".to_string());
+            }
+        }
+        if self.max_fields == 0 {
+            return Err("max_fields doit être positif".to_string());
+        }
+        if let Some(cle) = self.allowed_fields.iter().find(|cle| !champs::cle_valide(cle)) {
+            return Err(format!("allowed_fields: clé invalide: {}", cle));
+        }
+        for espace in self.namespaces.keys().chain(&self.namespace) {
+            espaces::valider_nom(espace)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_politique_des_espaces() {
+        let options = [("quota", "100"), ("rotate-size", "4096")].map(|(key, value)| (key.to_string(), value.to_string()));
+        let dir = std::env::temp_dir().join(format!("tp3-config-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let fichier = dir.join("tp3.toml");
+        std::fs::write(&fichier, "retention = 2\n[namespaces.payments]\nquota = 10\nretention = 30\n").unwrap();
+        let config: Config = config_commun::merge(Some(&fichier), Vec::new(), options).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(config.politique(None), Politique { rotate_size: 4096, retention: 2, quota: 100 });
+        assert_eq!(config.politique(Some("payments")), Politique { rotate_size: 4096, retention: 30, quota: 10 });
+        assert_eq!(config.politique(Some("audit")), config.politique(None));
+
+        let mauvais = [("namespace".to_string(), "../etc".to_string())];
+        assert!(config_commun::merge::<Config>(None, Vec::new(), mauvais).is_err());
+
+        assert_eq!(config.pid_file(), Path::new("logs/serveur.pid"));
+        assert_eq!(config.daemon_output(), Path::new("logs/serveur.out"));
+    }
+}
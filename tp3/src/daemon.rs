@@ -0,0 +1,154 @@
+//mode démon (--daemon) : le serveur se détache du terminal, écrit ses propres messages dans le dossier des logs
+//et son numéro de processus dans un fichier. Sous systemd (NOTIFY_SOCKET), il reste au premier plan et signale
+//son état (READY=1, RELOADING=1, STOPPING=1). Sous Windows, passer par un gestionnaire de services (sc.exe, NSSM)
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+//vrai si systemd attend des notifications (service de Type=notify)
+pub fn sous_systemd() -> bool {
+    std::env::var_os("NOTIFY_SOCKET").is_some()
+}
+
+//détache le processus : double fork, nouvelle session, entrée standard vide et sorties ajoutées à `sortie`.
+//À appeler avant de lancer le runtime tokio (un seul thread) ; le dossier courant ne change pas,
+//les chemins relatifs de la configuration restent valables
+#[cfg(unix)]
+pub fn detacher(sortie: &Path) -> io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    let fichier = fs::OpenOptions::new().create(true).append(true).open(sortie)?;
+    let vide = fs::File::open("/dev/null")?;
+    //SAFETY: le processus n'a qu'un thread ; le parent quitte sans rien exécuter d'autre
+    unsafe {
+        match libc::fork() {
+            -1 => return Err(io::Error::last_os_error()),
+            0 => {}
+            _ => libc::_exit(0),
+        }
+        if libc::setsid() == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        //second fork : le processus n'est plus chef de session et ne peut pas reprendre de terminal
+        match libc::fork() {
+            -1 => return Err(io::Error::last_os_error()),
+            0 => {}
+            _ => libc::_exit(0),
+        }
+        for (source, cible) in [(vide.as_raw_fd(), 0), (fichier.as_raw_fd(), 1), (fichier.as_raw_fd(), 2)] {
+            if libc::dup2(source, cible) == -1 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn detacher(_sortie: &Path) -> io::Result<()> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "--daemon n'existe que sous Unix ; sous Windows, utiliser un gestionnaire de services (sc.exe, NSSM)"))
+}
+
+//fichier qui contient le numéro du processus, supprimé à l'arrêt du serveur
+#[derive(Debug)]
+pub struct FichierPid {
+    chemin: PathBuf,
+}
+
+impl FichierPid {
+    //refusé si un autre serveur vivant y a déjà écrit son numéro
+    pub fn ecrire(chemin: &Path) -> io::Result<Self> {
+        if let Some(pid) = fs::read_to_string(chemin).ok().and_then(|texte| texte.trim().parse::<u32>().ok()) {
+            if pid != std::process::id() && processus_vivant(pid) {
+                return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("{}: serveur déjà lancé (processus {})", chemin.display(), pid)));
+            }
+        }
+        if let Some(dossier) = chemin.parent().filter(|dossier| !dossier.as_os_str().is_empty()) {
+            fs::create_dir_all(dossier)?;
+        }
+        fs::write(chemin, format!("{}\n", std::process::id()))?;
+        Ok(FichierPid { chemin: chemin.to_path_buf() })
+    }
+}
+
+impl Drop for FichierPid {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.chemin);
+    }
+}
+
+#[cfg(unix)]
+fn processus_vivant(pid: u32) -> bool {
+    //SAFETY: le signal 0 ne fait que vérifier l'existence du processus
+    unsafe { libc::kill(pid as libc::pid_t, 0) == 0 }
+}
+
+#[cfg(not(unix))]
+fn processus_vivant(_pid: u32) -> bool {
+    false
+}
+
+//notification à systemd (READY=1...) ; rien hors de systemd
+pub fn notifier(etat: &str) {
+    if let Some(socket) = std::env::var_os("NOTIFY_SOCKET") {
+        if let Err(e) = notifier_vers(Path::new(&socket), etat) {
+            tracing::warn!("Notification systemd impossible: {}", e);
+        }
+    }
+}
+
+#[cfg(unix)]
+fn notifier_vers(socket: &Path, etat: &str) -> io::Result<()> {
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::net::UnixDatagram;
+
+    let client = UnixDatagram::unbound()?;
+    //un nom qui commence par @ désigne une socket abstraite (Linux)
+    match socket.as_os_str().as_bytes().strip_prefix(b"@") {
+        #[cfg(target_os = "linux")]
+        Some(nom) => {
+            use std::os::linux::net::SocketAddrExt;
+            let adresse = std::os::unix::net::SocketAddr::from_abstract_name(nom)?;
+            client.send_to_addr(etat.as_bytes(), &adresse)?;
+        }
+        _ => {
+            client.send_to(etat.as_bytes(), socket)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn notifier_vers(_socket: &Path, _etat: &str) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::net::UnixDatagram;
+
+    #[test]
+    fn test_fichier_pid_et_notification() {
+        let dir = std::env::temp_dir().join(format!("tp3-daemon-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let chemin = dir.join("run/serveur.pid");
+        let pid = FichierPid::ecrire(&chemin).unwrap();
+        assert_eq!(fs::read_to_string(&chemin).unwrap(), format!("{}\n", std::process::id()));
+        drop(pid);
+        assert!(!chemin.exists());
+        // Numéro d'un processus disparu : le fichier est repris
+        fs::write(&chemin, "999999999\n").unwrap();
+        assert!(FichierPid::ecrire(&chemin).is_ok());
+
+        let socket = dir.join("notify.sock");
+        let systemd = UnixDatagram::bind(&socket).unwrap();
+        notifier_vers(&socket, "READY=1").unwrap();
+        let mut tampon = [0u8; 64];
+        let taille = systemd.recv(&mut tampon).unwrap();
+        assert_eq!(&tampon[..taille], b"READY=1");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
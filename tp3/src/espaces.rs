@@ -0,0 +1,112 @@
+//espaces de noms : un client qui commence par "NS payments" écrit dans logs/payments/server.log, avec sa propre
+//rotation, sa propre durée de conservation et son propre quota. Sans "NS", tout va dans le fichier principal
+
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+//longueur maximale d'un nom d'espace
+pub const LONGUEUR_MAX: usize = 32;
+
+//nom demandé par une ligne "NS ..." ; None pour une ligne de log ordinaire
+pub fn espace_demande(ligne: &str) -> Option<&str> {
+    ligne.trim().strip_prefix("NS ").map(str::trim)
+}
+
+//ligne envoyée par le client pour écrire dans l'espace `nom`
+pub fn demande(nom: &str) -> String {
+    format!("NS {}", nom)
+}
+
+//un nom d'espace devient un dossier : minuscules, chiffres, '-' et '_' seulement
+pub fn valider_nom(nom: &str) -> Result<(), String> {
+    if nom.is_empty() || nom.len() > LONGUEUR_MAX {
+        return Err(format!("nom d'espace de 1 à {} caractères attendu", LONGUEUR_MAX));
+    }
+    if !nom.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_') {
+        return Err(format!("nom d'espace invalide: {} (minuscules, chiffres, - et _)", nom));
+    }
+    Ok(())
+}
+
+//fichier de logs d'un espace : même nom que le fichier principal, dans un sous-dossier à côté de lui
+pub fn chemin(log_file: &Path, espace: &str) -> PathBuf {
+    let dossier = log_file.parent().unwrap_or(Path::new(""));
+    let nom = log_file.file_name().unwrap_or("server.log".as_ref());
+    dossier.join(espace).join(nom)
+}
+
+//règles appliquées au fichier d'un espace
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Politique {
+    pub rotate_size: u64, //taille au-delà de laquelle le fichier est renommé en .1 (0 : jamais)
+    pub retention: usize, //anciens fichiers gardés (.1 à .N)
+    pub quota: u32,       //entrées de clients acceptées par minute (0 : sans limite)
+}
+
+//décompte des entrées de la minute en cours, pour le quota
+#[derive(Debug)]
+pub struct Quota {
+    max: u32,
+    debut: Instant,
+    acceptees: u32,
+    refusees: u32,
+}
+
+impl Quota {
+    const FENETRE: Duration = Duration::from_secs(60);
+
+    pub fn new(max: u32) -> Self {
+        Quota { max, debut: Instant::now(), acceptees: 0, refusees: 0 }
+    }
+
+    //nouvelle limite (rechargement de la configuration), à partir de la minute en cours
+    pub fn limiter(&mut self, max: u32) {
+        self.max = max;
+    }
+
+    //compte une entrée arrivée à `maintenant` ; false si la minute est pleine. Au changement de minute,
+    //renvoie aussi le nombre d'entrées refusées pendant la précédente
+    pub fn accepter(&mut self, maintenant: Instant) -> (bool, u32) {
+        let mut refusees = 0;
+        if maintenant.duration_since(self.debut) >= Self::FENETRE {
+            refusees = std::mem::take(&mut self.refusees);
+            (self.debut, self.acceptees) = (maintenant, 0);
+        }
+        if self.max > 0 && self.acceptees >= self.max {
+            self.refusees += 1;
+            return (false, refusees);
+        }
+        self.acceptees += 1;
+        (true, refusees)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_noms() {
+        assert_eq!(espace_demande(&demande("payments")), Some("payments"));
+        assert_eq!(espace_demande("Client prêt"), None);
+        assert!(valider_nom("payments").is_ok() && valider_nom("audit_2").is_ok());
+        assert!(valider_nom("../etc").is_err() && valider_nom("Paiements").is_err() && valider_nom("").is_err());
+        assert_eq!(chemin(Path::new("logs/server.log"), "payments"), Path::new("logs/payments/server.log"));
+        assert_eq!(chemin(Path::new("server.log"), "audit"), Path::new("audit/server.log"));
+    }
+
+    #[test]
+    fn test_quota() {
+        let debut = Instant::now();
+        let mut quota = Quota::new(2);
+        assert_eq!(quota.accepter(debut), (true, 0));
+        assert_eq!(quota.accepter(debut), (true, 0));
+        assert_eq!(quota.accepter(debut), (false, 0));
+        assert_eq!(quota.accepter(debut + Duration::from_secs(30)), (false, 0));
+        // Minute suivante : de nouveau accepté, et les deux refus sont signalés
+        assert_eq!(quota.accepter(debut + Duration::from_secs(61)), (true, 2));
+
+        let mut illimite = Quota::new(0);
+        assert!((0..1000).all(|_| illimite.accepter(debut).0));
+    }
+}
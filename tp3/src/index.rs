@@ -0,0 +1,244 @@
+//index du fichier de logs : à côté de server.log, server.log.idx garde l'horodatage et la position (en octets)
+//d'une entrée sur `intervalle`. Une requête par période ("QUERY since <date>") commence sa lecture au dernier
+//point de l'index avant la date, au lieu de parcourir tout le fichier
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+
+use crate::champs::Champs;
+use crate::journal::{analyser_date, Entree, FORMAT_DATE};
+
+//chemin de l'index d'un fichier de logs (logs/server.log.idx)
+pub fn chemin_index(journal: &Path) -> PathBuf {
+    let mut nom = journal.as_os_str().to_owned();
+    nom.push(".idx");
+    PathBuf::from(nom)
+}
+
+//points de l'index, tenus à jour par celui qui écrit le fichier de logs
+#[derive(Debug)]
+pub struct Index {
+    points: Vec<(DateTime<Utc>, u64)>, //horodatage et position d'une entrée, dans l'ordre du fichier
+    intervalle: usize,                 //entrées entre deux points
+    depuis_dernier: usize,             //entrées écrites depuis le dernier point (lui compris)
+    fichier: File,                     //index ouvert en ajout
+}
+
+impl Index {
+    //ouvre l'index de `journal` et le complète avec les entrées écrites depuis son dernier point ; il est
+    //reconstruit s'il manque ou ne correspond plus au fichier (fichier vidé ou remplacé)
+    pub fn ouvrir(journal: &Path, intervalle: usize) -> io::Result<Self> {
+        let chemin = chemin_index(journal);
+        let taille = std::fs::metadata(journal).map(|m| m.len()).unwrap_or(0);
+        let mut points = lire_points(&chemin)?;
+        let valide = points.last().is_none_or(|(_, position)| *position < taille);
+        if !valide {
+            points.clear();
+        }
+        let fichier = OpenOptions::new().create(true).append(true).truncate(false).open(&chemin)?;
+        if !valide {
+            fichier.set_len(0)?;
+        }
+        let mut index = Index { points, intervalle: intervalle.max(1), depuis_dernier: 0, fichier };
+
+        //rattrapage : entrées écrites après le dernier point (toutes, pour un index neuf)
+        let debut = index.points.last().map_or(0, |(_, position)| *position);
+        if taille > debut {
+            let mut lecteur = BufReader::new(File::open(journal)?);
+            lecteur.seek(SeekFrom::Start(debut))?;
+            let mut position = debut;
+            let mut ligne = String::new();
+            loop {
+                ligne.clear();
+                let lu = lecteur.read_line(&mut ligne)?;
+                if lu == 0 {
+                    break;
+                }
+                if let Some(entree) = Entree::analyser(&ligne) {
+                    if index.points.last().is_some_and(|(_, dernier)| *dernier == position) {
+                        index.depuis_dernier = 1;
+                    } else {
+                        index.ajouter(entree.horodatage, position)?;
+                    }
+                }
+                position += lu as u64;
+            }
+        }
+        Ok(index)
+    }
+
+    //à appeler pour chaque entrée écrite dans le fichier de logs, à la position `position`
+    pub fn ajouter(&mut self, horodatage: DateTime<Utc>, position: u64) -> io::Result<()> {
+        if self.depuis_dernier == 0 || self.depuis_dernier >= self.intervalle {
+            writeln!(self.fichier, "{} {}", horodatage.format(FORMAT_DATE), position)?;
+            self.points.push((horodatage, position));
+            self.depuis_dernier = 0;
+        }
+        self.depuis_dernier += 1;
+        Ok(())
+    }
+
+    //position où commencer la lecture pour trouver toutes les entrées à partir de `depuis` : celle du dernier
+    //point strictement avant (les entrées de la même seconde peuvent le précéder)
+    pub fn debut(&self, depuis: DateTime<Utc>) -> u64 {
+        let suivants = self.points.partition_point(|(horodatage, _)| *horodatage < depuis);
+        match suivants {
+            0 => 0,
+            n => self.points[n - 1].1,
+        }
+    }
+
+    pub fn nombre_de_points(&self) -> usize {
+        self.points.len()
+    }
+}
+
+//points d'un fichier d'index ; aucun s'il n'existe pas. Une ligne illisible (écriture interrompue) l'arrête
+fn lire_points(chemin: &Path) -> io::Result<Vec<(DateTime<Utc>, u64)>> {
+    let mut texte = String::new();
+    match File::open(chemin) {
+        Ok(mut fichier) => fichier.read_to_string(&mut texte)?,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    let points = texte
+        .lines()
+        .map_while(|ligne| {
+            let (date, position) = ligne.split_once(' ')?;
+            Some((analyser_date(date)?, position.trim().parse().ok()?))
+        })
+        .collect();
+    Ok(points)
+}
+
+//requête d'un client : "QUERY since <date> [until <date>] [cle=valeur ...]", bornes comprises ; les champs
+//ne retiennent que les entrées structurées qui les ont tous
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Requete {
+    pub depuis: DateTime<Utc>,
+    pub jusqu_a: Option<DateTime<Utc>>,
+    pub champs: Champs,
+}
+
+impl Requete {
+    //None pour une ligne de log ordinaire ; une erreur pour une requête mal formée
+    pub fn analyser(ligne: &str) -> Option<Result<Self, String>> {
+        let suite = ligne.trim().strip_prefix("QUERY ")?;
+        let mots: Vec<&str> = suite.split_whitespace().collect();
+        let (bornes, filtre) = mots.split_at(mots.iter().position(|mot| mot.contains('=')).unwrap_or(mots.len()));
+        let champs = match Champs::analyser(&filtre.join(" ")) {
+            None if filtre.is_empty() => Champs::default(),
+            Some(Ok(champs)) => champs,
+            Some(Err(e)) => return Some(Err(e)),
+            None => return Some(Err(format!("filtre invalide: {}", filtre.join(" ")))),
+        };
+        let date = |texte: &str| analyser_date(texte).ok_or_else(|| format!("date invalide: {} (format 2024-05-01T12:00:00Z)", texte));
+        Some(match bornes {
+            ["since", depuis] => date(depuis).map(|depuis| Requete { depuis, jusqu_a: None, champs }),
+            ["since", depuis, "until", jusqu_a] => {
+                date(depuis).and_then(|depuis| Ok(Requete { depuis, jusqu_a: Some(date(jusqu_a)?), champs }))
+            }
+            _ => Err("requête attendue: QUERY since <date> [until <date>] [cle=valeur ...]".to_string()),
+        })
+    }
+
+    pub fn contient(&self, horodatage: DateTime<Utc>) -> bool {
+        horodatage >= self.depuis && self.jusqu_a.is_none_or(|fin| horodatage <= fin)
+    }
+
+    //l'entrée est dans la période et a les champs demandés
+    pub fn retient(&self, entree: &Entree) -> bool {
+        self.contient(entree.horodatage)
+            && (self.champs.is_empty() || Champs::du_message(&entree.message).is_some_and(|champs| champs.contient(&self.champs)))
+    }
+}
+
+//entrées de `journal` qui répondent à `requete`, lues de `debut` (donné par l'index) à `fin` (la taille du
+//fichier au moment de la requête : une entrée en cours d'écriture n'est pas lue)
+pub fn lire_periode(journal: &Path, requete: &Requete, debut: u64, fin: u64) -> io::Result<Vec<Entree>> {
+    let mut fichier = File::open(journal)?;
+    fichier.seek(SeekFrom::Start(debut))?;
+    let mut entrees = Vec::new();
+    for ligne in BufReader::new(fichier.take(fin.saturating_sub(debut))).lines() {
+        let Some(entree) = Entree::analyser(&ligne?) else {
+            continue;
+        };
+        //le fichier est dans l'ordre : au-delà de la fin de la période, plus rien ne répond
+        if requete.jusqu_a.is_some_and(|jusqu_a| entree.horodatage > jusqu_a) {
+            break;
+        }
+        if requete.retient(&entree) {
+            entrees.push(entree);
+        }
+    }
+    Ok(entrees)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(texte: &str) -> DateTime<Utc> {
+        analyser_date(texte).unwrap()
+    }
+
+    #[test]
+    fn test_index() {
+        let dir = std::env::temp_dir().join(format!("tp3-index-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let journal = dir.join("server.log");
+        let _ = std::fs::remove_file(chemin_index(&journal));
+
+        // Dix entrées, une par minute, un point toutes les trois
+        let mut texte = String::new();
+        for minute in 0..10 {
+            texte.push_str(&format!("[2024-05-01T12:{:02}:00Z] Client 1: message {}\n", minute, minute));
+        }
+        std::fs::write(&journal, &texte).unwrap();
+        let index = Index::ouvrir(&journal, 3).unwrap();
+        assert_eq!(index.nombre_de_points(), 4); // entrées 0, 3, 6 et 9
+
+        let requete = Requete::analyser("QUERY since 2024-05-01T12:04:00Z until 2024-05-01T12:06:00Z").unwrap().unwrap();
+        let debut = index.debut(requete.depuis);
+        assert!(debut > 0);
+        let entrees = lire_periode(&journal, &requete, debut, texte.len() as u64).unwrap();
+        let messages: Vec<&str> = entrees.iter().map(|entree| entree.message.as_str()).collect();
+        assert_eq!(messages, ["Client 1: message 4", "Client 1: message 5", "Client 1: message 6"]);
+
+        // Réouverture après deux entrées de plus : l'index est complété, pas reconstruit
+        drop(index);
+        let mut fichier = OpenOptions::new().append(true).open(&journal).unwrap();
+        writeln!(fichier, "[2024-05-01T12:10:00Z] Client 1: message 10\n[2024-05-01T12:11:00Z] Client 1: message 11").unwrap();
+        let mut index = Index::ouvrir(&journal, 3).unwrap();
+        assert_eq!(index.nombre_de_points(), 4);
+        index.ajouter(date("2024-05-01T12:12:00Z"), 10_000).unwrap();
+        assert_eq!(index.nombre_de_points(), 5);
+        assert_eq!(index.debut(date("2024-05-01T12:13:00Z")), 10_000);
+
+        // Fichier de logs remplacé par un plus court : reconstruit
+        std::fs::write(&journal, "[2024-06-01T00:00:00Z] neuf\n").unwrap();
+        let index = Index::ouvrir(&journal, 3).unwrap();
+        assert_eq!(index.nombre_de_points(), 1);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_requetes() {
+        assert_eq!(Requete::analyser("Client 1: bonjour"), None);
+        let requete = Requete::analyser("QUERY since 2024-05-01T12:00:00Z").unwrap().unwrap();
+        assert!(requete.contient(date("2030-01-01T00:00:00Z")));
+        assert!(!requete.contient(date("2024-05-01T11:59:59Z")));
+        assert!(Requete::analyser("QUERY since hier").unwrap().is_err());
+        assert!(Requete::analyser("QUERY until 2024-05-01T12:00:00Z").unwrap().is_err());
+
+        // Filtre sur les champs des entrées structurées
+        let requete = Requete::analyser("QUERY since 2024-05-01T12:00:00Z level=error").unwrap().unwrap();
+        assert!(requete.retient(&Entree::analyser("[2024-05-01T12:00:00Z] Client 1: level=error msg=panne").unwrap()));
+        assert!(!requete.retient(&Entree::analyser("[2024-05-01T12:00:00Z] Client 1: level=info msg=ok").unwrap()));
+        assert!(!requete.retient(&Entree::analyser("[2024-05-01T12:00:00Z] Client 1: level=error, vraiment").unwrap()));
+        assert!(Requete::analyser("QUERY since 2024-05-01T12:00:00Z level=error et").unwrap().is_err());
+    }
+}
@@ -0,0 +1,109 @@
+//entrées du fichier de logs : "[horodatage] message", une par ligne
+
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, NaiveDateTime, Utc}; //dates des entrées
+
+//format de l'horodatage (UTC, à la seconde)
+pub const FORMAT_DATE: &str = "%Y-%m-%dT%H:%M:%SZ";
+
+//une ligne du fichier de logs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entree {
+    pub horodatage: DateTime<Utc>,
+    pub message: String,
+}
+
+impl Entree {
+    //entrée datée de maintenant
+    pub fn maintenant(message: &str) -> Self {
+        Entree { horodatage: Utc::now(), message: message.to_string() }
+    }
+
+    //relit une ligne écrite par le serveur ; None si elle n'a pas le format attendu
+    pub fn analyser(ligne: &str) -> Option<Self> {
+        let (date, message) = ligne.strip_prefix('[')?.split_once("] ")?;
+        Some(Entree { horodatage: analyser_date(date)?, message: message.trim_end().to_string() })
+    }
+
+    //message renvoyé par l'outil de rejeu : l'horodatage d'origine en tête ("[replay <date>] message")
+    pub fn rejeu(&self) -> String {
+        format!("[replay {}] {}", self.horodatage.format(FORMAT_DATE), self.message)
+    }
+}
+
+impl fmt::Display for Entree {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}] {}", self.horodatage.format(FORMAT_DATE), self.message)
+    }
+}
+
+//date au format du fichier (2024-05-01T12:00:00Z)
+pub fn analyser_date(date: &str) -> Option<DateTime<Utc>> {
+    NaiveDateTime::parse_from_str(date.trim(), FORMAT_DATE).ok().map(|date| date.and_utc())
+}
+
+//fichier gardé après rotation : server.log.1, server.log.2...
+pub fn chemin_ancien(chemin: &Path, numero: usize) -> PathBuf {
+    let mut nom = chemin.as_os_str().to_owned();
+    nom.push(format!(".{}", numero));
+    PathBuf::from(nom)
+}
+
+//rotation : server.log devient server.log.1, .1 devient .2... ; seuls les `retention` plus récents restent
+pub fn faire_tourner(chemin: &Path, retention: usize) -> io::Result<()> {
+    let supprimer = |chemin: &Path| match std::fs::remove_file(chemin) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    };
+    if retention == 0 {
+        return supprimer(chemin);
+    }
+    supprimer(&chemin_ancien(chemin, retention))?;
+    for numero in (1..retention).rev() {
+        let ancien = chemin_ancien(chemin, numero);
+        if ancien.exists() {
+            std::fs::rename(&ancien, chemin_ancien(chemin, numero + 1))?;
+        }
+    }
+    std::fs::rename(chemin, chemin_ancien(chemin, 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entrees() {
+        let entree = Entree::analyser("[2024-05-01T12:00:00Z] Client 1: bonjour").unwrap();
+        assert_eq!(entree.horodatage, analyser_date("2024-05-01T12:00:00Z").unwrap());
+        assert_eq!(entree.message, "Client 1: bonjour");
+        assert_eq!(entree.to_string(), "[2024-05-01T12:00:00Z] Client 1: bonjour");
+        assert_eq!(entree.rejeu(), "[replay 2024-05-01T12:00:00Z] Client 1: bonjour");
+
+        assert_eq!(Entree::analyser("Client 1: bonjour"), None);
+        assert_eq!(Entree::analyser("[hier] Client 1: bonjour"), None);
+    }
+
+    #[test]
+    fn test_rotation() {
+        let dir = std::env::temp_dir().join(format!("tp3-rotation-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let chemin = dir.join("server.log");
+        for numero in 1..=4 {
+            std::fs::write(&chemin, format!("fichier {}", numero)).unwrap();
+            faire_tourner(&chemin, 2).unwrap();
+        }
+        assert!(!chemin.exists());
+        assert_eq!(std::fs::read_to_string(chemin_ancien(&chemin, 1)).unwrap(), "fichier 4");
+        assert_eq!(std::fs::read_to_string(chemin_ancien(&chemin, 2)).unwrap(), "fichier 3");
+        assert!(!chemin_ancien(&chemin, 3).exists());
+
+        std::fs::write(&chemin, "sans conservation").unwrap();
+        faire_tourner(&chemin, 0).unwrap();
+        assert!(!chemin.exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
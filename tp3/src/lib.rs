@@ -0,0 +1,13 @@
+//code commun au serveur de journalisation, à son client de test et à l'outil de rejeu
+
+pub mod archive;
+pub mod batch;
+pub mod champs;
+pub mod compression;
+pub mod configuration;
+pub mod daemon;
+pub mod espaces;
+pub mod index;
+pub mod journal;
+pub mod sante;
+pub mod tampon;
@@ -1,124 +1,719 @@
 //serveur de journalisation
 
 use tokio::net::{TcpListener, TcpStream}; //gérer les connexions réseau asynchrones (serveur/client TCP)
-use tokio::io::{AsyncBufReadExt, BufReader}; //lire les messages du client de façon asynchrone, ligne par ligne
-use std::sync::Arc; //partager les données entre plusieurs tâches (threads)
-use tokio::sync::Mutex; //protéger les accès concurrents au fichier de log
+use net_commun::{FrameError, FramedTransport, LengthPrefixedCodec, LineCodec}; //lire les messages du client de façon asynchrone, ligne par ligne ou par lot
+use std::sync::{Arc, Weak}; //partager les données entre plusieurs tâches (threads)
+use std::collections::HashMap; //espaces déjà ouverts
+use std::time::{Duration, Instant}; //quota par minute, délais du point de santé
+use tokio::sync::{broadcast, Mutex}; //protéger les accès concurrents au fichier de log, diffuser aux clients TAIL
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader}; //requêtes HTTP du point de santé
 use std::fs::OpenOptions; //ouvrir/créer un fichier avec des options (ici, en mode ajout)
 use std::io::Write; //écrire manuellement dans le fichier
-use chrono::Utc; //obtenir la date et l'heure actuelles
+use tracing::{error, info, warn}; //messages du serveur sur le terminal, filtrables avec RUST_LOG
+use trace_commun::LogOptions;
+use tp3::archive::{self, Archiveur}; //envoi des fichiers vers un stockage S3 après rotation
+use tp3::champs::{Champs, Regles}; //entrées structurées (level=error msg=...)
+use tp3::compression::{self, Mode}; //lots compressés (MODE gzip)
+use tp3::configuration::Config; //adresse, fichier de logs et taille des lignes
+use tp3::daemon::{self, FichierPid}; //mode démon (--daemon)
+use tp3::espaces::{self, Politique, Quota}; //espaces de noms (NS payments)
+use tp3::journal::{self, Entree}; //format des lignes du fichier, rotation
+use tp3::index::{self, Index, Requete}; //requêtes par période (QUERY since)
+use tp3::sante; //point de santé (GET /healthz)
+use tp3::tampon::{self, Tampon}; //entrées en attente quand le fichier ne peut pas être écrit
+use std::path::{Path, PathBuf};
 
 
-//Structure pour gérer le fichier de logs partagé
-struct LogManager {
-    log_file: Arc<Mutex<std::fs::File>>, 
+//fichier de logs ouvert, avec sa taille et son index
+struct FichierLogs {
+    file: std::fs::File,
+    position: u64, //taille du fichier : position de la prochaine entrée
+    index: Index,  //positions de quelques entrées, pour les requêtes par période
+    politique: Politique, //rotation, conservation et quota
+    quota: Quota,  //entrées des clients acceptées dans la minute
+    erreur: Option<String>, //écriture impossible en ce moment (disque plein...), None quand tout va bien
+    tampon: Tampon, //entrées en attente pendant que le fichier ne peut pas être écrit
 }
-//initialisation du gestionnaire de logs
-impl LogManager {
-    fn new() -> Result<Self, std::io::Error> {
-        //Créer le dossier logs s'il n'existe pas
-        std::fs::create_dir_all("logs")?;
-        
+
+impl FichierLogs {
+    fn ouvrir(path: &Path, index_interval: usize, politique: Politique, write_buffer: usize) -> Result<Self, std::io::Error> {
+        let (file, position, index) = Self::ouvrir_fichier(path, index_interval)?;
+        Ok(FichierLogs { file, position, index, politique, quota: Quota::new(politique.quota), erreur: None, tampon: Tampon::new(write_buffer) })
+    }
+    //nouveau fichier après une rotation : quota et entrées en attente restent
+    fn rouvrir(&mut self, path: &Path, index_interval: usize) -> Result<(), std::io::Error> {
+        (self.file, self.position, self.index) = Self::ouvrir_fichier(path, index_interval)?;
+        Ok(())
+    }
+    fn ouvrir_fichier(path: &Path, index_interval: usize) -> Result<(std::fs::File, u64, Index), std::io::Error> {
         let file = OpenOptions::new()
             .create(true)
             .append(true)
-            .open("logs/server.log")?;    //ouvrir le fichier de logs en mode append
+            .open(path)?;    //ouvrir le fichier de logs en mode append
+        let position = file.metadata()?.len();
+        let index = Index::ouvrir(path, index_interval)?; //complété si le serveur s'est arrêté avant
+        Ok((file, position, index))
+    }
+}
 
+//Structure pour gérer le fichier de logs partagé (celui du serveur, ou celui d'un espace)
+struct LogManager {
+    path: PathBuf,
+    index_interval: usize,
+    log_file: Arc<Mutex<FichierLogs>>, 
+    abonnes: broadcast::Sender<String>, //clients TAIL : entrées écrites et avertissements
+    archiveur: Option<Archiveur>, //avec un stockage S3 : les fichiers y partent après rotation
+}
+//initialisation du gestionnaire de logs
+impl LogManager {
+    fn new(path: &Path, index_interval: usize, politique: Politique, write_buffer: usize, archiveur: Option<Archiveur>) -> Result<Arc<Self>, std::io::Error> {
+        //Créer le dossier du fichier (logs/ par défaut) s'il n'existe pas
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)?;
+        }
+        
+        let fichier = FichierLogs::ouvrir(path, index_interval, politique, write_buffer)?;
+        //archives pas encore envoyées lors du dernier arrêt
+        if let Some(archiveur) = &archiveur {
+            archiveur.reprendre(path, politique.retention);
+        }
             
-        Ok(LogManager {
-            log_file: Arc::new(Mutex::new(file)),
-        })
+        let log_manager = Arc::new(LogManager {
+            path: path.to_path_buf(),
+            index_interval,
+            log_file: Arc::new(Mutex::new(fichier)),
+            abonnes: broadcast::channel(1024).0,
+            archiveur,
+        });
+        //nouveaux essais d'écriture des entrées en attente, tant que le gestionnaire existe
+        tokio::spawn(Self::reessayer(Arc::downgrade(&log_manager)));
+        Ok(log_manager)
     }
     //ecrire le message dans le fichier log
-    async fn write_log(&self, message: &str) -> Result<(), std::io::Error> {
-        let timestamp = Utc::now().format("%Y-%m-%dT%H:%M:%SZ"); //ajout du timestamp
-        let log_entry = format!("[{}] {}\n", timestamp, message); //formate le log
-        
-        let mut file = self.log_file.lock().await; //attend le verou
-        file.write_all(log_entry.as_bytes())?;
-        file.flush()?;
+    async fn write_log(&self, message: &str) {
+        let mut journal = self.log_file.lock().await; //attend le verou
+        self.ecrire(&mut journal, message);
+    }
+    //message d'un client : compté dans le quota de l'espace, et ignoré si la minute est pleine
+    async fn write_client_log(&self, message: &str) {
+        let mut journal = self.log_file.lock().await;
+        let (accepte, refusees) = journal.quota.accepter(Instant::now());
+        if refusees > 0 {
+            warn!("{}: {} entrée(s) refusée(s) par le quota", self.path.display(), refusees);
+            self.ecrire(&mut journal, &format!("Quota dépassé: {} entrée(s) refusée(s) pendant la minute précédente", refusees));
+        }
+        if accepte {
+            self.ecrire(&mut journal, message);
+        }
+    }
+    //écrit une entrée ; si le fichier ne peut pas être écrit (disque plein...), elle attend dans la mémoire
+    //tampon, derrière celles qui attendent déjà
+    fn ecrire(&self, journal: &mut FichierLogs, message: &str) {
+        let log_entry = Entree::maintenant(message); //ajout du timestamp
+        let essayer = journal.tampon.est_vide() || (journal.tampon.pret(Instant::now()) && self.vider(journal));
+        if essayer {
+            match self.ajouter_entree(journal, &log_entry) {
+                Ok(()) => return self.retablir(journal),
+                Err(e) => self.echec(journal, &e),
+            }
+        }
+        if !journal.tampon.garder(log_entry) && journal.tampon.perdues() == 1 {
+            let avertissement = format!("mémoire tampon pleine ({} entrées): les nouvelles entrées sont perdues", journal.tampon.en_attente());
+            self.avertir(&avertissement);
+        }
+    }
+    //écrit les entrées en attente, dans l'ordre ; true si toutes l'ont été
+    fn vider(&self, journal: &mut FichierLogs) -> bool {
+        while let Some(log_entry) = journal.tampon.premiere().cloned() {
+            if let Err(e) = self.ajouter_entree(journal, &log_entry) {
+                self.echec(journal, &e);
+                return false;
+            }
+            journal.tampon.ecrite();
+        }
+        self.retablir(journal);
+        true
+    }
+    fn echec(&self, journal: &mut FichierLogs, e: &std::io::Error) {
+        journal.tampon.echec(Instant::now());
+        let premier = journal.erreur.is_none();
+        journal.erreur = Some(tampon::cause(e));
+        if premier {
+            self.avertir(&format!("écriture impossible ({}): entrées gardées en mémoire", tampon::cause(e)));
+        }
+    }
+    //écriture réussie après une panne : les pertes éventuelles sont notées dans le fichier
+    fn retablir(&self, journal: &mut FichierLogs) {
+        if journal.erreur.take().is_none() {
+            return;
+        }
+        let perdues = journal.tampon.succes();
+        info!("{}: écriture rétablie", self.path.display());
+        if perdues > 0 {
+            let message = format!("Écriture impossible: {} entrée(s) perdue(s), mémoire tampon pleine", perdues);
+            if let Err(e) = self.ajouter_entree(journal, &Entree::maintenant(&message)) {
+                self.echec(journal, &e);
+            }
+        }
+    }
+    //avertissement sur le terminal du serveur et aux clients TAIL
+    fn avertir(&self, texte: &str) {
+        warn!("{}: {}", self.path.display(), texte);
+        let _ = self.abonnes.send(format!("WARN {}", texte));
+    }
+    async fn reessayer(log_manager: Weak<LogManager>) {
+        loop {
+            let prochain_essai = {
+                let Some(log_manager) = log_manager.upgrade() else {
+                    return;
+                };
+                let mut journal = log_manager.log_file.lock().await;
+                if !journal.tampon.est_vide() && journal.tampon.pret(Instant::now()) {
+                    log_manager.vider(&mut journal);
+                }
+                match journal.tampon.est_vide() {
+                    true => Instant::now() + Duration::from_secs(1),
+                    false => journal.tampon.prochain_essai(),
+                }
+            };
+            tokio::time::sleep_until(prochain_essai.into()).await;
+        }
+    }
+    //arrêt du serveur : dernier essai pour les entrées en attente
+    async fn fermer(&self) {
+        let mut journal = self.log_file.lock().await;
+        if !journal.tampon.est_vide() && !self.vider(&mut journal) {
+            error!("{}: {} entrée(s) non écrite(s) à l'arrêt", self.path.display(), journal.tampon.en_attente());
+        }
+    }
+    fn ajouter_entree(&self, journal: &mut FichierLogs, log_entry: &Entree) -> Result<(), std::io::Error> {
+        let ligne = format!("{}\n", log_entry); //formate le log
+
+        //fichier plein : il part en .1 (ou vers le stockage S3) et un nouveau commence, avec un nouvel index
+        let politique = journal.politique;
+        if politique.rotate_size > 0 && journal.position > 0 && journal.position + ligne.len() as u64 > politique.rotate_size {
+            match &self.archiveur {
+                Some(archiveur) => archiveur.archiver(&self.path, archive::mettre_de_cote(&self.path)?, politique.retention),
+                None => journal::faire_tourner(&self.path, politique.retention)?,
+            }
+            let _ = std::fs::remove_file(index::chemin_index(&self.path));
+            journal.rouvrir(&self.path, self.index_interval)?;
+            info!("Rotation de {}", self.path.display());
+        }
+
+        //écriture interrompue (disque plein) : le début de ligne déjà écrit est retiré
+        if let Err(e) = journal.file.write_all(ligne.as_bytes()).and_then(|()| journal.file.flush()) {
+            let _ = journal.file.set_len(journal.position);
+            return Err(e);
+        }
+        let position = journal.position;
+        journal.position += ligne.len() as u64;
+        //un index incomplet ralentit les requêtes sans fausser leurs réponses : on continue
+        if let Err(e) = journal.index.ajouter(log_entry.horodatage, position) {
+            warn!("Erreur lors de l'écriture de l'index: {}", e);
+        }
         
-        println!("Log écrit: [{}] {}", timestamp, message); //affichage terminal
+        info!("Log écrit: {}", log_entry); //affichage terminal
+        let _ = self.abonnes.send(log_entry.to_string());
+        Ok(())
+    }
+    //nouvelles règles, après un rechargement de la configuration
+    async fn appliquer(&self, politique: Politique, write_buffer: usize) {
+        let mut journal = self.log_file.lock().await;
+        journal.politique = politique;
+        journal.quota.limiter(politique.quota);
+        journal.tampon.limiter(write_buffer);
+    }
+    //état pour le point de santé : l'écriture n'est pas bloquée (verrou obtenu à temps), la dernière a réussi,
+    //et le dossier accepte encore des écritures
+    async fn sante(&self) -> Result<(), String> {
+        let journal = tokio::time::timeout(Duration::from_secs(1), self.log_file.lock())
+            .await
+            .map_err(|_| format!("{}: écriture bloquée", self.path.display()))?;
+        if let Some(e) = &journal.erreur {
+            return Err(format!("{}: {} ({} entrée(s) en attente)", self.path.display(), e, journal.tampon.en_attente()));
+        }
+        drop(journal);
+        let path = self.path.clone();
+        match tokio::task::spawn_blocking(move || sante::dossier_inscriptible(&path)).await {
+            Ok(Ok(())) => Ok(()),
+            Ok(Err(e)) => Err(format!("{}: {}", self.path.display(), e)),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+    //entrées d'une période : lecture à partir du point de l'index qui la précède
+    async fn query(&self, requete: &Requete) -> Result<Vec<Entree>, std::io::Error> {
+        let (debut, fin) = {
+            let journal = self.log_file.lock().await;
+            (journal.index.debut(requete.depuis), journal.position)
+        };
+        let (path, requete) = (self.path.clone(), requete.clone());
+        tokio::task::spawn_blocking(move || index::lire_periode(&path, &requete, debut, fin))
+            .await
+            .map_err(std::io::Error::other)?
+    }
+}
+
+//fichier principal et fichiers des espaces, ouverts à la première connexion qui les demande
+struct Espaces {
+    config: std::sync::RwLock<Arc<Config>>, //remplacée au rechargement (SIGHUP)
+    principal: Arc<LogManager>,
+    ouverts: Mutex<HashMap<String, Arc<LogManager>>>,
+    archiveur: Option<Archiveur>,
+}
+
+impl Espaces {
+    fn new(config: Arc<Config>) -> Result<Self, Box<dyn std::error::Error>> {
+        let archiveur = config.archive_options()?.map(Archiveur::lancer).transpose()?;
+        let principal = LogManager::new(&config.log_file, config.index_interval, config.politique(None), config.write_buffer, archiveur.clone())?;
+        Ok(Espaces { config: std::sync::RwLock::new(config), principal, ouverts: Mutex::new(HashMap::new()), archiveur })
+    }
+    //configuration actuelle ; une connexion garde celle de son arrivée
+    fn config(&self) -> Arc<Config> {
+        Arc::clone(&self.config.read().unwrap_or_else(|e| e.into_inner()))
+    }
+    //rechargement : quotas, rotation et tailles s'appliquent tout de suite ; l'adresse, le fichier principal
+    //et le stockage des archives ne changent qu'au redémarrage
+    async fn recharger(&self, mut config: Config) {
+        let actuelle = self.config();
+        if config.addr != actuelle.addr || config.log_file != actuelle.log_file {
+            warn!("addr et log_file ne changent qu'au redémarrage du serveur");
+            (config.addr, config.log_file) = (actuelle.addr, actuelle.log_file.clone());
+        }
+        if config.archive_options() != actuelle.archive_options() {
+            warn!("Le stockage des archives ne change qu'au redémarrage du serveur");
+        }
+        self.principal.appliquer(config.politique(None), config.write_buffer).await;
+        for (nom, espace) in self.ouverts.lock().await.iter() {
+            espace.appliquer(config.politique(Some(nom)), config.write_buffer).await;
+        }
+        *self.config.write().unwrap_or_else(|e| e.into_inner()) = Arc::new(config);
+    }
+    //santé du fichier principal et de ceux des espaces ouverts
+    async fn sante(&self) -> Result<(), String> {
+        self.principal.sante().await?;
+        let ouverts: Vec<Arc<LogManager>> = self.ouverts.lock().await.values().cloned().collect();
+        for espace in ouverts {
+            espace.sante().await?;
+        }
         Ok(())
     }
+    //arrêt : entrées en attente du fichier principal et des espaces
+    async fn fermer(&self) {
+        self.principal.fermer().await;
+        for espace in self.ouverts.lock().await.values() {
+            espace.fermer().await;
+        }
+    }
+    async fn ouvrir(&self, nom: &str) -> Result<Arc<LogManager>, String> {
+        espaces::valider_nom(nom)?;
+        let mut ouverts = self.ouverts.lock().await;
+        if let Some(espace) = ouverts.get(nom) {
+            return Ok(Arc::clone(espace));
+        }
+        let config = self.config();
+        let chemin = espaces::chemin(&config.log_file, nom);
+        let espace = LogManager::new(&chemin, config.index_interval, config.politique(Some(nom)), config.write_buffer, self.archiveur.clone())
+            .map_err(|e| format!("{}: {}", chemin.display(), e))?;
+        info!("Espace {} ouvert ({})", nom, chemin.display());
+        ouverts.insert(nom.to_string(), Arc::clone(&espace));
+        Ok(espace)
+    }
+}
+
+//répond à une requête par période : les entrées telles qu'écrites dans le fichier, puis "END <nombre>"
+async fn repondre(lines: &mut FramedTransport<TcpStream, LineCodec>, log_manager: &LogManager, requete: Result<Requete, String>) -> Result<(), FrameError> {
+    let requete = match requete {
+        Ok(requete) => requete,
+        Err(e) => return lines.write_frame(&format!("ERR {}", e)).await,
+    };
+    match log_manager.query(&requete).await {
+        Ok(entrees) => {
+            for entree in &entrees {
+                lines.write_frame(&entree.to_string()).await?;
+            }
+            lines.write_frame(&format!("END {}", entrees.len())).await
+        }
+        Err(e) => {
+            error!("Erreur lors de la lecture des logs: {}", e);
+            lines.write_frame(&"ERR lecture des logs impossible".to_string()).await
+        }
+    }
+}
+
+//"TAIL" : chaque entrée écrite dans le fichier est renvoyée au client, avec les avertissements du serveur
+//("WARN ..."), jusqu'à ce qu'il envoie "quit" ou se déconnecte
+async fn suivre(lines: &mut FramedTransport<TcpStream, LineCodec>, log_manager: &LogManager) -> Result<(), FrameError> {
+    let mut abonnement = log_manager.abonnes.subscribe();
+    lines.write_frame(&"OK tail".to_string()).await?;
+    loop {
+        tokio::select! {
+            recu = abonnement.recv() => match recu {
+                Ok(ligne) => lines.write_frame(&ligne).await?,
+                Err(broadcast::error::RecvError::Lagged(sautees)) => {
+                    lines.write_frame(&format!("WARN {} entrée(s) sautée(s): client trop lent", sautees)).await?
+                }
+                Err(broadcast::error::RecvError::Closed) => return Ok(()),
+            },
+            lue = lines.read_frame() => match lue? {
+                Some(line) if line.trim().eq_ignore_ascii_case("quit") => return Ok(()),
+                Some(_) => {}
+                None => return Ok(()),
+            },
+        }
+    }
+}
+
+//écrit une ligne reçue dans le fichier de logs ; Ok(false) si le client a fini (quit), une erreur si c'est
+//une entrée structurée refusée (clé non autorisée, trop de champs, ligne mal formée)
+async fn journaliser(log_manager: &LogManager, regles: &Regles, client_id: u32, line: &str) -> Result<bool, String> {
+    if line.trim().is_empty() {
+        return Ok(true);
+    }
+
+    // Si le client envoie "quit", on ferme la connexion
+    if line.trim().eq_ignore_ascii_case("quit") {
+        return Ok(false);
+    }
+
+    //entrée structurée : écrite sous la forme régulière de ses champs, pour les requêtes par champ
+    let texte = match Champs::analyser(line.trim()) {
+        Some(champs) => {
+            let champs = champs?;
+            regles.valider(&champs)?;
+            champs.to_string()
+        }
+        None => line.trim().to_string(),
+    };
+
+    // Écrire le message dans le fichier de logs
+    //un fichier qui ne peut pas être écrit ne coupe pas le client : l'entrée attend en mémoire
+    let log_message = format!("Client {}: {}", client_id, texte);
+    log_manager.write_client_log(&log_message).await;
+    Ok(true)
 }
 
 //fonction pour gérer chaque client connecté
-async fn handle_client(mut socket: TcpStream, log_manager: Arc<LogManager>, client_id: u32) {
-    println!("Client {} connecté", client_id);
+async fn handle_client(socket: TcpStream, espaces: Arc<Espaces>, client_id: u32) {
+    info!("Client {} connecté", client_id);
     
-    let reader = BufReader::new(&mut socket); 
-    let mut lines = reader.lines();
+    let config = espaces.config();
+    let regles = config.regles_des_champs();
+    let principal = &espaces.principal; //connexions et départs, quel que soit l'espace
+    let mut log_manager = Arc::clone(principal); //entrées du client : fichier principal, ou celui de son espace
+    let mut lines = FramedTransport::new(socket, LineCodec::new(config.max_line_length));
     
     //écrire un log de connexion
-    if let Err(e) = log_manager.write_log(&format!("Client {} connecté", client_id)).await {
-        eprintln!("Erreur lors de l'écriture du log de connexion: {}", e);
-    }
+    principal.write_log(&format!("Client {} connecté", client_id)).await;
     
-    // Lire les messages du client ligne par ligne
-    while let Ok(Some(line)) = lines.next_line().await {
-        if line.trim().is_empty() {
+    // Lire les messages du client ligne par ligne, jusqu'à ce qu'il demande les lots compressés
+    let mut preambule = true; //"NS ..." puis "MODE ...", avant la première entrée
+    let lots = loop {
+        let line = match lines.read_frame().await {
+            Ok(Some(line)) => line,
+            Ok(None) => break None,
+            //ligne illisible (pas en UTF-8) : on l'ignore et on passe à la suivante
+            Err(FrameError::Invalid(e)) => {
+                warn!("Client {}: ligne ignorée ({})", client_id, e);
+                continue;
+            }
+            //ligne trop longue ou connexion coupée : on arrête
+            Err(e) => {
+                warn!("Client {}: {}", client_id, e);
+                break None;
+            }
+        };
+
+        //choix de l'espace ("NS payments"), puis du mode ("MODE gzip"), seulement en début de connexion
+        if preambule {
+            if let Some(nom) = espaces::espace_demande(&line) {
+                let reponse = match espaces.ouvrir(nom).await {
+                    Ok(espace) => {
+                        log_manager = espace;
+                        principal.write_log(&format!("Client {} écrit dans l'espace {}", client_id, nom)).await;
+                        Ok(format!("OK {}", nom))
+                    }
+                    Err(e) => Err(format!("ERR {}", e)),
+                };
+                //un client refusé n'écrit pas ailleurs que là où il l'a demandé : la connexion est fermée
+                let refuse = reponse.is_err();
+                if let Err(e) = lines.write_frame(&reponse.unwrap_or_else(|e| e)).await {
+                    warn!("Client {}: {}", client_id, e);
+                    break None;
+                }
+                if refuse {
+                    warn!("Client {}: espace {} refusé", client_id, nom);
+                    break None;
+                }
+                continue;
+            }
+            preambule = false;
+            if let Some(demande) = compression::mode_demande(&line) {
+                let mode = demande.parse::<Mode>();
+                let reponse = match &mode {
+                    Ok(mode) => format!("OK {}", mode),
+                    Err(e) => format!("ERR {}", e),
+                };
+                if let Err(e) = lines.write_frame(&reponse).await {
+                    warn!("Client {}: {}", client_id, e);
+                    break None;
+                }
+                match mode {
+                    Ok(Mode::Texte) => continue,
+                    Ok(Mode::Gzip) => {
+                        info!("Client {}: lots compressés (gzip)", client_id);
+                        break Some(lines.with_codec(LengthPrefixedCodec::new(config.max_batch_size)));
+                    }
+                    Err(e) => {
+                        warn!("Client {}: {}", client_id, e);
+                        break None;
+                    }
+                }
+            }
+        }
+
+        //suivi en direct : la connexion ne sert plus qu'à recevoir les entrées de son espace
+        if line.trim().eq_ignore_ascii_case("TAIL") {
+            if let Err(e) = suivre(&mut lines, &log_manager).await {
+                warn!("Client {}: {}", client_id, e);
+            }
+            break None;
+        }
+
+        //requête par période : la réponse n'est pas journalisée
+        if let Some(requete) = Requete::analyser(&line) {
+            if let Err(e) = repondre(&mut lines, &log_manager, requete).await {
+                warn!("Client {}: {}", client_id, e);
+                break None;
+            }
             continue;
         }
-        
-        // Si le client envoie "quit", on ferme la connexion
-        if line.trim().eq_ignore_ascii_case("quit") {
-            break;
+
+        match journaliser(&log_manager, &regles, client_id, &line).await {
+            Ok(true) => {}
+            Ok(false) => break None,
+            //entrée refusée : le client le sait, la connexion continue
+            Err(e) => {
+                warn!("Client {}: entrée refusée ({})", client_id, e);
+                if let Err(e) = lines.write_frame(&format!("ERR {}", e)).await {
+                    warn!("Client {}: {}", client_id, e);
+                    break None;
+                }
+            }
         }
-        
-        // Écrire le message dans le fichier de logs
-        let log_message = format!("Client {}: {}", client_id, line.trim());
-        if let Err(e) = log_manager.write_log(&log_message).await {
-            eprintln!("Erreur lors de l'écriture du log: {}", e);
-            break;
+    };
+
+    // Mode gzip : chaque lot est décompressé, puis ses lignes écrites comme en mode texte
+    if let Some(mut lots) = lots {
+        'lots: loop {
+            let lot = match lots.read_frame().await {
+                Ok(Some(lot)) => lot,
+                Ok(None) => break,
+                Err(e) => {
+                    warn!("Client {}: {}", client_id, e);
+                    break;
+                }
+            };
+            //lot illisible ou trop gros une fois décompressé : on l'ignore, le suivant est intact
+            let lignes = match compression::decompresser(&lot, config.max_batch_size) {
+                Ok(lignes) => lignes,
+                Err(e) => {
+                    warn!("Client {}: lot ignoré ({})", client_id, e);
+                    continue;
+                }
+            };
+            for line in lignes {
+                match journaliser(&log_manager, &regles, client_id, &line).await {
+                    Ok(true) => {}
+                    Ok(false) => break 'lots,
+                    Err(e) => warn!("Client {}: entrée refusée ({})", client_id, e),
+                }
+            }
         }
     }
     
     // Log de déconnexion
-    if let Err(e) = log_manager.write_log(&format!("Client {} déconnecté", client_id)).await {
-        eprintln!("Erreur lors de l'écriture du log de déconnexion: {}", e);
-    }
+    principal.write_log(&format!("Client {} déconnecté", client_id)).await;
     
-    println!("Client {} déconnecté", client_id);
+    info!("Client {} déconnecté", client_id);
+}
+
+//point de santé : une requête HTTP par connexion, réponse 200 ou 503 selon l'état des fichiers de logs
+async fn servir_sante(listener: TcpListener, espaces: Arc<Espaces>) {
+    loop {
+        let (mut socket, _) = match listener.accept().await {
+            Ok(connexion) => connexion,
+            Err(e) => {
+                warn!("Point de santé: {}", e);
+                continue;
+            }
+        };
+        let espaces = Arc::clone(&espaces);
+        tokio::spawn(async move {
+            let (lecture, mut ecriture) = socket.split();
+            let mut ligne = String::new();
+            let lue = tokio::time::timeout(Duration::from_secs(5), BufReader::new(lecture).read_line(&mut ligne)).await;
+            if !matches!(lue, Ok(Ok(_))) {
+                return;
+            }
+            let chemin = sante::chemin_requete(&ligne);
+            let etat = match chemin {
+                Some("/healthz") => espaces.sante().await,
+                _ => Ok(()),
+            };
+            if let Err(raison) = &etat {
+                warn!("Point de santé: {}", raison);
+            }
+            let _ = ecriture.write_all(sante::reponse(chemin, etat).as_bytes()).await;
+        });
+    }
+}
+
+//signaux qui comptent pour le serveur
+enum Evenement {
+    Recharger, //SIGHUP
+    Arreter,   //SIGTERM ou Ctrl+C
+}
+
+struct Signaux {
+    #[cfg(unix)]
+    hup: tokio::signal::unix::Signal,
+    #[cfg(unix)]
+    term: tokio::signal::unix::Signal,
+}
+
+impl Signaux {
+    fn new() -> Result<Self, std::io::Error> {
+        #[cfg(unix)]
+        {
+            use tokio::signal::unix::{signal, SignalKind};
+            Ok(Signaux { hup: signal(SignalKind::hangup())?, term: signal(SignalKind::terminate())? })
+        }
+        #[cfg(not(unix))]
+        Ok(Signaux {})
+    }
+
+    async fn suivant(&mut self) -> Evenement {
+        #[cfg(unix)]
+        {
+            tokio::select! {
+                _ = self.hup.recv() => Evenement::Recharger,
+                _ = self.term.recv() => Evenement::Arreter,
+                _ = tokio::signal::ctrl_c() => Evenement::Arreter,
+            }
+        }
+        #[cfg(not(unix))]
+        {
+            let _ = tokio::signal::ctrl_c().await;
+            Evenement::Arreter
+        }
+    }
+}
+
+//"--daemon" seul vaut "--daemon true" (les autres options ont toujours une valeur)
+fn arguments() -> Vec<String> {
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    for i in 0..args.len() {
+        let valeur_suivante = args.get(i + 1).is_some_and(|suivant| !suivant.starts_with("--"));
+        if args[i] == "--daemon" && !valeur_suivante {
+            args[i] = "--daemon=true".to_string();
+        }
+    }
+    args
 }
 
 //main
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    //Configuration : fichier (--config), variables TP3_*, options (--addr, --log-file, --daemon...)
+    let args = arguments();
+    let config: Config = config_commun::load_with(args.clone(), std::env::vars()).map_err(|e| e.to_string())?;
+
+    //mode démon : se détacher avant de lancer le runtime (sous systemd, c'est lui qui détache)
+    if config.daemon && !daemon::sous_systemd() {
+        let sortie = config.daemon_output();
+        if let Some(dir) = sortie.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)?;
+        }
+        daemon::detacher(&sortie)?;
+    }
+    let _pid = match config.daemon {
+        true => Some(FichierPid::ecrire(&config.pid_file())?),
+        false => None,
+    };
+
+    tokio::runtime::Runtime::new()?.block_on(serveur(config, args))
+}
+
+async fn serveur(config: Config, args: Vec<String>) -> Result<(), Box<dyn std::error::Error>> {
     let _debut = std::time::Instant::now();
-    println!("=== SERVEUR DE JOURNALISATION ===");
-    println!("Démarrage du serveur de journalisation asynchrone...");
+    //journalisation commune aux TP, sans LOG_SERVER : le serveur ne s'envoie pas ses propres messages
+    let mut options = LogOptions::from_env("tp3-serveur")?;
+    options.forward = None;
+    let _journal = trace_commun::init(&options)?;
+    info!("=== SERVEUR DE JOURNALISATION ===");
+    info!("Démarrage du serveur de journalisation asynchrone...");
+    let config = Arc::new(config);
     
-    //Initialiser le gestionnaire de logs
-    let log_manager = Arc::new(LogManager::new()?);
+    //Initialiser le gestionnaire de logs (les espaces s'ouvrent à la demande des clients)
+    let espaces = Arc::new(Espaces::new(Arc::clone(&config))?);
     
-    // Créer le listener TCP sur le port 8080
-    let listener = TcpListener::bind("127.0.0.1:8080").await?;
-    println!(" Serveur en écoute sur 127.0.0.1:8080");
+    // Créer le listener TCP (127.0.0.1:8080 par défaut)
+    let listener = TcpListener::bind(config.addr).await?;
+    info!(" Serveur en écoute sur {} (logs dans {})", config.addr, config.log_file.display());
+    
+    //point de santé pour les orchestrateurs (--health-addr 127.0.0.1:8081)
+    if let Some(health_addr) = config.health_addr {
+        let listener = TcpListener::bind(health_addr).await?;
+        info!(" Point de santé sur http://{}/healthz", health_addr);
+        tokio::spawn(servir_sante(listener, Arc::clone(&espaces)));
+    }
     
     // Log du démarrage du serveur
-    log_manager.write_log("Serveur de journalisation démarré").await?;
+    espaces.principal.write_log("Serveur de journalisation démarré").await;
     
     let mut client_counter = 0u32;
     let mut tasks = Vec::new();
     
-    println!(" En attente de connexions clients... (Ctrl+C pour arrêter)");
-    println!(" Pour tester: ouvrez un autre terminal et tapez 'cargo run --bin client'");
+    info!(" En attente de connexions clients... (Ctrl+C pour arrêter)");
+    info!(" Pour tester: ouvrez un autre terminal et tapez 'cargo run --bin client'");
+    
+    daemon::notifier("READY=1");
+    let mut signaux = Signaux::new()?;
     
-    // Boucle principale pour accepter les connexions
+    // Boucle principale pour accepter les connexions, jusqu'à SIGTERM ou Ctrl+C
     loop {
-        match listener.accept().await {
+        let accepte = tokio::select! {
+            accepte = listener.accept() => accepte,
+            evenement = signaux.suivant() => match evenement {
+                //SIGHUP : relire le fichier de configuration, en gardant l'ancienne si la nouvelle est invalide
+                Evenement::Recharger => {
+                    daemon::notifier("RELOADING=1");
+                    match config_commun::load_with(args.clone(), std::env::vars()) {
+                        Ok(nouvelle) => {
+                            espaces.recharger(nouvelle).await;
+                            info!("Configuration rechargée");
+                            espaces.principal.write_log("Configuration rechargée").await;
+                        }
+                        Err(e) => warn!("Configuration gardée: {}", e),
+                    }
+                    daemon::notifier("READY=1");
+                    continue;
+                }
+                Evenement::Arreter => break,
+            },
+        };
+        match accepte {
             Ok((socket, addr)) => {
                 client_counter += 1;
-                println!(" Nouvelle connexion de {} - Client ID: {}", addr, client_counter);
+                info!(" Nouvelle connexion de {} - Client ID: {}", addr, client_counter);
                 
                 // Cloner les références pour la tâche
-                let log_manager_clone = Arc::clone(&log_manager);
+                let espaces_clone = Arc::clone(&espaces);
                 let current_client_id = client_counter;
                 
                 // Lancer une tâche asynchrone pour chaque client
                 let task = tokio::spawn(async move {
-                    handle_client(socket, log_manager_clone, current_client_id).await;
+                    handle_client(socket, espaces_clone, current_client_id).await;
                 });
                 
                 tasks.push(task);
@@ -128,8 +723,14 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 
             }
             Err(e) => {
-                eprintln!(" Erreur lors de l'acceptation de connexion: {}", e);
+                error!(" Erreur lors de l'acceptation de connexion: {}", e);
             }
         }
     }
-}
\ No newline at end of file
+
+    daemon::notifier("STOPPING=1");
+    info!("Arrêt du serveur");
+    espaces.principal.write_log("Serveur de journalisation arrêté").await;
+    espaces.fermer().await;
+    Ok(())
+}
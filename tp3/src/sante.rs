@@ -0,0 +1,62 @@
+//point de santé (--health-addr) : "GET /healthz" répond 200 OK si les fichiers de logs sont accessibles en
+//écriture et que leur écriture n'est pas bloquée, 503 avec la raison sinon. Pour les orchestrateurs
+//(sonde liveness de Kubernetes, healthcheck de Docker) qui redémarrent un serveur au disque plein
+
+use std::io;
+use std::path::Path;
+
+//chemin demandé par la première ligne d'une requête HTTP ("GET /healthz HTTP/1.1")
+pub fn chemin_requete(ligne: &str) -> Option<&str> {
+    let mut morceaux = ligne.split_whitespace();
+    match (morceaux.next(), morceaux.next()) {
+        (Some("GET" | "HEAD"), Some(chemin)) => Some(chemin),
+        _ => None,
+    }
+}
+
+//réponse HTTP complète à une requête sur `chemin`, d'après l'état du serveur
+pub fn reponse(chemin: Option<&str>, etat: Result<(), String>) -> String {
+    let (statut, corps) = match (chemin, etat) {
+        (Some("/healthz"), Ok(())) => ("200 OK", "OK\n".to_string()),
+        (Some("/healthz"), Err(raison)) => ("503 Service Unavailable", format!("ERR {}\n", raison)),
+        (Some(_), _) => ("404 Not Found", "introuvable\n".to_string()),
+        (None, _) => ("400 Bad Request", "requête invalide\n".to_string()),
+    };
+    format!(
+        "HTTP/1.1 {}\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        statut,
+        corps.len(),
+        corps
+    )
+}
+
+//vérifie qu'on peut encore écrire dans le dossier d'un fichier de logs (disque plein, droits retirés) :
+//un octet dans un fichier témoin, supprimé aussitôt
+pub fn dossier_inscriptible(fichier_logs: &Path) -> io::Result<()> {
+    let temoin = fichier_logs.with_extension("healthz");
+    let resultat = std::fs::write(&temoin, b"x");
+    let _ = std::fs::remove_file(&temoin);
+    resultat
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reponses() {
+        assert_eq!(chemin_requete("GET /healthz HTTP/1.1"), Some("/healthz"));
+        assert_eq!(chemin_requete("POST /healthz HTTP/1.1"), None);
+        assert!(reponse(Some("/healthz"), Ok(())).starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(reponse(Some("/healthz"), Ok(())).ends_with("\r\n\r\nOK\n"));
+        let panne = reponse(Some("/healthz"), Err("disque plein".to_string()));
+        assert!(panne.starts_with("HTTP/1.1 503") && panne.ends_with("ERR disque plein\n"));
+        assert!(reponse(Some("/"), Ok(())).starts_with("HTTP/1.1 404"));
+
+        let dir = std::env::temp_dir().join(format!("tp3-sante-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        assert!(dossier_inscriptible(&dir.join("server.log")).is_ok());
+        assert!(dossier_inscriptible(&dir.join("absent/server.log")).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
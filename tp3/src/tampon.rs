@@ -0,0 +1,140 @@
+//mémoire tampon des entrées qui n'ont pas pu être écrites (disque plein, erreur d'entrée/sortie) : elles
+//attendent dans l'ordre, le serveur réessaie avec un délai qui double à chaque échec, et ne les perd qu'une fois
+//la mémoire tampon pleine, en les comptant
+
+use std::collections::VecDeque;
+use std::io;
+use std::time::{Duration, Instant};
+
+use crate::journal::Entree;
+
+const DELAI_INITIAL: Duration = Duration::from_millis(100);
+const DELAI_MAX: Duration = Duration::from_secs(30);
+
+#[derive(Debug)]
+pub struct Tampon {
+    entrees: VecDeque<Entree>,
+    max: usize,              //entrées gardées au plus (0 : aucune, perdues tout de suite)
+    perdues: u64,            //entrées perdues depuis la dernière écriture réussie
+    delai: Duration,         //attente avant le prochain essai
+    prochain_essai: Instant,
+}
+
+impl Tampon {
+    pub fn new(max: usize) -> Self {
+        Tampon { entrees: VecDeque::new(), max, perdues: 0, delai: DELAI_INITIAL, prochain_essai: Instant::now() }
+    }
+
+    //nouvelle taille maximale, après un rechargement de la configuration ; les entrées en trop sont perdues
+    pub fn limiter(&mut self, max: usize) {
+        self.max = max;
+        while self.entrees.len() > max {
+            self.entrees.pop_back();
+            self.perdues += 1;
+        }
+    }
+
+    //garde une entrée pour plus tard ; false (et une perte comptée) si la mémoire tampon est pleine
+    pub fn garder(&mut self, entree: Entree) -> bool {
+        if self.entrees.len() >= self.max {
+            self.perdues += 1;
+            return false;
+        }
+        self.entrees.push_back(entree);
+        true
+    }
+
+    pub fn est_vide(&self) -> bool {
+        self.entrees.is_empty()
+    }
+
+    pub fn en_attente(&self) -> usize {
+        self.entrees.len()
+    }
+
+    pub fn perdues(&self) -> u64 {
+        self.perdues
+    }
+
+    //plus ancienne entrée en attente, à écrire avant toutes les autres
+    pub fn premiere(&self) -> Option<&Entree> {
+        self.entrees.front()
+    }
+
+    //la plus ancienne entrée a été écrite
+    pub fn ecrite(&mut self) {
+        self.entrees.pop_front();
+    }
+
+    //l'écriture a réussi : le délai repart du début ; renvoie les pertes à signaler
+    pub fn succes(&mut self) -> u64 {
+        self.delai = DELAI_INITIAL;
+        std::mem::take(&mut self.perdues)
+    }
+
+    //l'écriture a échoué : prochain essai plus tard, le délai double jusqu'à DELAI_MAX
+    pub fn echec(&mut self, maintenant: Instant) {
+        self.prochain_essai = maintenant + self.delai;
+        self.delai = (self.delai * 2).min(DELAI_MAX);
+    }
+
+    //un nouvel essai peut avoir lieu
+    pub fn pret(&self, maintenant: Instant) -> bool {
+        maintenant >= self.prochain_essai
+    }
+
+    pub fn prochain_essai(&self) -> Instant {
+        self.prochain_essai
+    }
+}
+
+//description d'une erreur d'écriture pour les avertissements
+pub fn cause(erreur: &io::Error) -> String {
+    match erreur.kind() {
+        io::ErrorKind::StorageFull => format!("disque plein ({})", erreur),
+        _ => erreur.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tampon() {
+        let mut tampon = Tampon::new(2);
+        let debut = Instant::now();
+        assert!(tampon.pret(debut));
+
+        // Deux échecs : le délai double
+        tampon.echec(debut);
+        assert!(!tampon.pret(debut) && tampon.pret(debut + Duration::from_millis(100)));
+        tampon.echec(debut);
+        assert_eq!(tampon.prochain_essai(), debut + Duration::from_millis(200));
+
+        // Deux entrées gardées, la troisième est perdue et comptée
+        assert!(tampon.garder(Entree::maintenant("un")));
+        assert!(tampon.garder(Entree::maintenant("deux")));
+        assert!(!tampon.garder(Entree::maintenant("trois")));
+        assert_eq!(tampon.premiere().map(|entree| entree.message.as_str()), Some("un"));
+        tampon.ecrite();
+        assert_eq!(tampon.en_attente(), 1);
+
+        // Retour à la normale : les pertes sont rendues une fois, le délai repart de 100 ms
+        assert_eq!(tampon.succes(), 1);
+        assert_eq!(tampon.perdues(), 0);
+        tampon.echec(debut);
+        assert_eq!(tampon.prochain_essai(), debut + Duration::from_millis(100));
+
+        tampon.limiter(0);
+        assert!(tampon.est_vide());
+        assert_eq!(tampon.perdues(), 1);
+    }
+
+    #[test]
+    fn test_cause() {
+        let plein = io::Error::new(io::ErrorKind::StorageFull, "No space left on device");
+        assert!(cause(&plein).starts_with("disque plein"));
+        assert_eq!(cause(&io::Error::other("panne")), "panne");
+    }
+}
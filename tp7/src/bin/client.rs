@@ -0,0 +1,47 @@
+use std::io::{self, Write};
+
+use tp7_dns::client::{ClientDns, SERVEUR_PAR_DEFAUT};
+use tp7_dns::texte;
+
+fn main() -> std::io::Result<()> {
+    let serveur = SERVEUR_PAR_DEFAUT.parse().expect("adresse par défaut valide");
+    let client = ClientDns::new(serveur);
+
+    loop {
+        print!(" Entrez un nom de domaine [type] (ou 'quit') : ");
+        io::stdout().flush()?;
+
+        let mut input = String::new();
+        if io::stdin().read_line(&mut input)? == 0 {
+            break;
+        }
+        let input = input.trim();
+
+        if input.eq_ignore_ascii_case("quit") {
+            break;
+        }
+        if input.is_empty() {
+            continue;
+        }
+
+        let (nom, type_rr) = match texte::analyser_requete(input) {
+            Ok(requete) => requete,
+            Err(e) => {
+                println!(" Requête invalide : {}", e);
+                continue;
+            }
+        };
+
+        match client.lookup(&nom, type_rr) {
+            Ok(enregistrements) if enregistrements.is_empty() => println!(" Réponse du serveur : NODATA"),
+            Ok(enregistrements) => {
+                for rr in enregistrements {
+                    println!(" Réponse du serveur : {} {} {} {}", rr.nom, rr.ttl, rr.type_rr, rr.donnees);
+                }
+            }
+            Err(e) => println!(" Réponse du serveur : {} ({})", e.statut(), e),
+        }
+    }
+
+    Ok(())
+}
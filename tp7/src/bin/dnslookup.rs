@@ -0,0 +1,150 @@
+// dnslookup : résolution en ligne de commande, sortie texte ou JSON pour les scripts
+
+use std::process::ExitCode;
+use std::time::Duration;
+
+use tp7_dns::client::{ClientDns, ErreurClient, SERVEUR_PAR_DEFAUT};
+use tp7_dns::dns::{Enregistrement, TypeEnregistrement, TAILLE_EDNS_ANNONCEE};
+
+const USAGE: &str =
+    "Usage: dnslookup [--type TYPE] [--server ip:port] [--json] [--timeout-ms N] [--retries N]
+                 [--bufsize N | --no-edns] [--tcp] <nom>...";
+
+struct Options {
+    type_rr: TypeEnregistrement,
+    serveur: String,
+    json: bool,
+    delai: Duration,
+    tentatives: u32,
+    taille_edns: Option<u16>,
+    tcp: bool,
+    noms: Vec<String>,
+}
+
+fn lire_options() -> Result<Options, String> {
+    let mut options = Options {
+        type_rr: TypeEnregistrement::A,
+        serveur: SERVEUR_PAR_DEFAUT.to_string(),
+        json: false,
+        delai: Duration::from_secs(2),
+        tentatives: 3,
+        taille_edns: Some(TAILLE_EDNS_ANNONCEE),
+        tcp: false,
+        noms: Vec::new(),
+    };
+
+    let mut arguments = std::env::args().skip(1);
+    while let Some(argument) = arguments.next() {
+        let mut valeur = || arguments.next().ok_or(format!("valeur manquante pour {}", argument));
+        match argument.as_str() {
+            "--type" | "-t" => options.type_rr = valeur()?.parse()?,
+            "--server" | "-s" => options.serveur = valeur()?,
+            "--json" => options.json = true,
+            "--timeout-ms" => {
+                let ms = valeur()?.parse().map_err(|_| "délai invalide".to_string())?;
+                options.delai = Duration::from_millis(ms);
+            }
+            "--retries" => {
+                options.tentatives = valeur()?.parse().map_err(|_| "nombre de tentatives invalide".to_string())?;
+            }
+            "--bufsize" => {
+                let taille = valeur()?.parse().map_err(|_| "taille de tampon invalide".to_string())?;
+                options.taille_edns = Some(taille);
+            }
+            "--no-edns" => options.taille_edns = None,
+            "--tcp" => options.tcp = true,
+            "--help" | "-h" => return Err(String::new()),
+            option if option.starts_with('-') => return Err(format!("option inconnue: {}", option)),
+            nom => options.noms.push(nom.to_string()),
+        }
+    }
+
+    if options.noms.is_empty() {
+        return Err("aucun nom à résoudre".to_string());
+    }
+    Ok(options)
+}
+
+fn main() -> ExitCode {
+    let options = match lire_options() {
+        Ok(options) => options,
+        Err(e) => {
+            if !e.is_empty() {
+                eprintln!("dnslookup: {}", e);
+            }
+            eprintln!("{}", USAGE);
+            return ExitCode::from(2);
+        }
+    };
+
+    let serveur = match options.serveur.parse() {
+        Ok(adresse) => adresse,
+        Err(_) => {
+            eprintln!("dnslookup: adresse de serveur invalide: {}", options.serveur);
+            return ExitCode::from(2);
+        }
+    };
+    let client = ClientDns::new(serveur)
+        .delai(options.delai)
+        .tentatives(options.tentatives)
+        .taille_edns(options.taille_edns)
+        .tcp(options.tcp);
+
+    let mut succes = true;
+    let mut documents = Vec::new();
+
+    for nom in &options.noms {
+        let resultat = client.lookup(nom, options.type_rr);
+        succes &= resultat.is_ok();
+
+        if options.json {
+            documents.push(document_json(nom, options.type_rr, &resultat));
+        } else {
+            afficher_texte(nom, options.type_rr, &resultat);
+        }
+    }
+
+    if options.json {
+        let sortie = if documents.len() == 1 {
+            documents.remove(0)
+        } else {
+            serde_json::Value::Array(documents)
+        };
+        println!("{}", serde_json::to_string_pretty(&sortie).unwrap_or_default());
+    }
+
+    if succes {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+fn afficher_texte(nom: &str, type_rr: TypeEnregistrement, resultat: &Result<Vec<Enregistrement>, ErreurClient>) {
+    match resultat {
+        Ok(enregistrements) if enregistrements.is_empty() => println!("{} {}: NODATA", nom, type_rr),
+        Ok(enregistrements) => {
+            for rr in enregistrements {
+                println!("{}\t{}\tIN\t{}\t{}", rr.nom, rr.ttl, rr.type_rr, rr.donnees);
+            }
+        }
+        Err(e) => println!("{} {}: {} ({})", nom, type_rr, e.statut(), e),
+    }
+}
+
+fn document_json(
+    nom: &str,
+    type_rr: TypeEnregistrement,
+    resultat: &Result<Vec<Enregistrement>, ErreurClient>,
+) -> serde_json::Value {
+    let (statut, reponses, erreur) = match resultat {
+        Ok(enregistrements) => ("NOERROR", enregistrements.iter().map(Enregistrement::en_json).collect(), None),
+        Err(e) => (e.statut(), Vec::new(), Some(e.to_string())),
+    };
+    serde_json::json!({
+        "query": { "name": nom, "type": type_rr.to_string() },
+        "status": statut,
+        "answers": reponses,
+        "error": erreur,
+    })
+}
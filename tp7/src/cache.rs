@@ -0,0 +1,188 @@
+// Cache des réponses obtenues auprès du résolveur amont (positives et négatives), borné en nombre
+// d'entrées : une fois plein, l'entrée utilisée le moins récemment (LRU) laisse sa place
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use crate::dns::TypeEnregistrement;
+use crate::dnssec::Securite;
+use crate::resolveur::Resultat;
+
+/// Nombre d'entrées par défaut
+pub const CAPACITE_PAR_DEFAUT: usize = 10_000;
+
+type Cle = (String, TypeEnregistrement);
+
+/// Entrée du cache avec son statut DNSSEC, son instant d'expiration et son dernier usage
+struct Entree {
+    resultat: Resultat,
+    securite: Securite,
+    expire_a: Instant,
+    ttl: u32,
+    usage: u64,
+}
+
+/// Compteurs du cache depuis le démarrage
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct StatistiquesCache {
+    pub entrees: usize,
+    pub capacite: usize,
+    pub succes: u64,
+    pub echecs: u64,
+    /// Entrées évincées pour faire de la place
+    pub evictions: u64,
+    /// Entrées retirées parce que leur TTL était écoulé
+    pub expirations: u64,
+}
+
+impl fmt::Display for StatistiquesCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "cache: {}/{} entrées, {} succès, {} échecs, {} évictions, {} expirations",
+            self.entrees, self.capacite, self.succes, self.echecs, self.evictions, self.expirations
+        )
+    }
+}
+
+/// Cache indexé par (nom, type) ; `ordre` range les clés du plus ancien au plus récent usage
+pub struct Cache {
+    entrees: HashMap<Cle, Entree>,
+    ordre: BTreeMap<u64, Cle>,
+    horloge: u64,
+    statistiques: StatistiquesCache,
+}
+
+impl Default for Cache {
+    fn default() -> Self {
+        Self::new(CAPACITE_PAR_DEFAUT)
+    }
+}
+
+impl Cache {
+    /// Cache d'au plus `capacite` entrées (0 : rien n'est mis en cache)
+    pub fn new(capacite: usize) -> Self {
+        Self {
+            entrees: HashMap::new(),
+            ordre: BTreeMap::new(),
+            horloge: 0,
+            statistiques: StatistiquesCache { capacite, ..StatistiquesCache::default() },
+        }
+    }
+
+    /// Chercher une réponse encore valide ; les TTL sont décrémentés du temps écoulé
+    pub fn obtenir(&mut self, nom: &str, type_rr: TypeEnregistrement) -> Option<(Resultat, Securite)> {
+        let cle = (nom.to_string(), type_rr);
+        let maintenant = Instant::now();
+
+        let Some(entree) = self.entrees.get_mut(&cle) else {
+            self.statistiques.echecs += 1;
+            return None;
+        };
+        if entree.expire_a <= maintenant {
+            self.ordre.remove(&entree.usage);
+            self.entrees.remove(&cle);
+            self.statistiques.echecs += 1;
+            self.statistiques.expirations += 1;
+            return None;
+        }
+
+        // Entrée utilisée : elle passe en fin d'ordre
+        self.horloge += 1;
+        self.ordre.remove(&entree.usage);
+        entree.usage = self.horloge;
+        self.ordre.insert(self.horloge, cle);
+        self.statistiques.succes += 1;
+
+        let restant = (entree.expire_a - maintenant).as_secs() as u32;
+        let mut resultat = entree.resultat.clone();
+        if let Resultat::Reponses(enregistrements) = &mut resultat {
+            for rr in enregistrements.iter_mut() {
+                rr.ttl = rr.ttl.min(restant);
+            }
+        }
+        Some((resultat, entree.securite))
+    }
+
+    /// Mémoriser une réponse pour `ttl` secondes (un TTL nul n'est pas mis en cache) ;
+    /// si le cache est plein, l'entrée la moins récemment utilisée est évincée
+    pub fn inserer(&mut self, nom: &str, type_rr: TypeEnregistrement, resultat: Resultat, securite: Securite, ttl: u32) {
+        if ttl == 0 || self.statistiques.capacite == 0 {
+            return;
+        }
+        let cle = (nom.to_string(), type_rr);
+        if let Some(ancienne) = self.entrees.remove(&cle) {
+            self.ordre.remove(&ancienne.usage);
+        }
+        let maintenant = Instant::now();
+        while self.entrees.len() >= self.statistiques.capacite {
+            let Some((_, evincee)) = self.ordre.pop_first() else {
+                break;
+            };
+            // Une entrée déjà expirée qui part n'est pas une éviction
+            match self.entrees.remove(&evincee) {
+                Some(entree) if entree.expire_a <= maintenant => self.statistiques.expirations += 1,
+                _ => self.statistiques.evictions += 1,
+            }
+        }
+
+        self.horloge += 1;
+        let expire_a = maintenant + Duration::from_secs(ttl as u64);
+        let entree = Entree { resultat, securite, expire_a, ttl, usage: self.horloge };
+        self.ordre.insert(self.horloge, cle.clone());
+        self.entrees.insert(cle, entree);
+    }
+
+    /// Durée de vie restante d'une entrée et son TTL d'origine, sans compter d'usage
+    pub fn restant(&self, nom: &str, type_rr: TypeEnregistrement) -> Option<(Duration, u32)> {
+        let entree = self.entrees.get(&(nom.to_string(), type_rr))?;
+        let restant = entree.expire_a.checked_duration_since(Instant::now())?;
+        Some((restant, entree.ttl))
+    }
+
+    pub fn statistiques(&self) -> StatistiquesCache {
+        StatistiquesCache { entrees: self.entrees.len(), ..self.statistiques }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entrees.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entrees.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn inserer(cache: &mut Cache, nom: &str) {
+        cache.inserer(nom, TypeEnregistrement::A, Resultat::NxDomain, Securite::NonSecurisee, 60);
+    }
+
+    #[test]
+    fn test_eviction_lru() {
+        let mut cache = Cache::new(2);
+        inserer(&mut cache, "a.fr");
+        inserer(&mut cache, "b.fr");
+        // a.fr vient de servir : c'est b.fr qui laisse sa place
+        assert!(cache.obtenir("a.fr", TypeEnregistrement::A).is_some());
+        inserer(&mut cache, "c.fr");
+        assert_eq!(cache.len(), 2);
+        assert!(cache.obtenir("b.fr", TypeEnregistrement::A).is_none());
+        assert!(cache.obtenir("a.fr", TypeEnregistrement::A).is_some());
+        assert!(cache.obtenir("c.fr", TypeEnregistrement::A).is_some());
+
+        // Remplacer une entrée existante n'évince rien
+        inserer(&mut cache, "c.fr");
+        let statistiques = cache.statistiques();
+        assert_eq!((statistiques.entrees, statistiques.evictions), (2, 1));
+        assert_eq!((statistiques.succes, statistiques.echecs), (3, 1));
+
+        let mut desactive = Cache::new(0);
+        inserer(&mut desactive, "a.fr");
+        assert!(desactive.is_empty());
+    }
+}
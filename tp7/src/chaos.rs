@@ -0,0 +1,120 @@
+// Mode chaos, pour observer les nouvelles tentatives et délais d'attente des clients : une part
+// des réponses est retardée, abandonnée ou tronquée (réponse illisible mais de même identifiant)
+
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Latence injectée par défaut
+pub const LATENCE_PAR_DEFAUT: Duration = Duration::from_millis(1500);
+
+/// Perturbation possible d'une réponse
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Effet {
+    Latence,
+    Perte,
+    Corruption,
+}
+
+impl FromStr for Effet {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "latency" => Ok(Effet::Latence),
+            "drop" => Ok(Effet::Perte),
+            "malformed" => Ok(Effet::Corruption),
+            autre => Err(format!("effet de chaos inconnu: {} (attendu: latency, drop, malformed)", autre)),
+        }
+    }
+}
+
+/// Sort réservé à une réponse
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Perturbation {
+    Aucune,
+    /// Envoyer la réponse après ce délai
+    Retard(Duration),
+    /// Ne pas répondre
+    Perte,
+    /// Envoyer une réponse tronquée (voir `corrompre`)
+    Corruption,
+}
+
+/// Tirage des perturbations : `pourcentage` % des réponses subissent l'un des effets, au hasard
+pub struct Chaos {
+    pourcentage: u8,
+    latence: Duration,
+    effets: Vec<Effet>,
+    etat: u64,
+}
+
+impl Chaos {
+    pub fn new(pourcentage: u8, latence: Duration, effets: Vec<Effet>) -> Self {
+        // Générateur xorshift sans dépendance externe ; l'état ne doit jamais être nul
+        let graine = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_nanos() as u64).unwrap_or(0);
+        Self { pourcentage: pourcentage.min(100), latence, effets, etat: graine | 1 }
+    }
+
+    pub fn actif(&self) -> bool {
+        self.pourcentage > 0 && !self.effets.is_empty()
+    }
+
+    fn aleatoire(&mut self) -> u64 {
+        self.etat ^= self.etat << 13;
+        self.etat ^= self.etat >> 7;
+        self.etat ^= self.etat << 17;
+        self.etat
+    }
+
+    /// Décider du sort de la prochaine réponse
+    pub fn tirer(&mut self) -> Perturbation {
+        if !self.actif() || self.aleatoire() % 100 >= self.pourcentage as u64 {
+            return Perturbation::Aucune;
+        }
+        let choix = (self.aleatoire() % self.effets.len() as u64) as usize;
+        match self.effets[choix] {
+            Effet::Latence => Perturbation::Retard(self.latence),
+            Effet::Perte => Perturbation::Perte,
+            Effet::Corruption => Perturbation::Corruption,
+        }
+    }
+
+    /// Tronquer la réponse au hasard en gardant ses deux premiers octets : un client DNS y retrouve
+    /// l'identifiant de sa requête mais ne peut pas décoder la suite
+    pub fn corrompre(&mut self, octets: &mut Vec<u8>) {
+        if octets.len() > 2 {
+            let longueur = 2 + (self.aleatoire() % (octets.len() as u64 - 2)) as usize;
+            octets.truncate(longueur);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dns::{PaquetDns, TypeEnregistrement};
+
+    #[test]
+    fn test_chaos() {
+        let mut inactif = Chaos::new(0, LATENCE_PAR_DEFAUT, vec![Effet::Perte]);
+        assert!((0..100).all(|_| inactif.tirer() == Perturbation::Aucune));
+        let mut perte = Chaos::new(100, LATENCE_PAR_DEFAUT, vec![Effet::Perte]);
+        assert!((0..100).all(|_| perte.tirer() == Perturbation::Perte));
+
+        let mut moitie = Chaos::new(50, Duration::from_millis(200), vec![Effet::Latence, Effet::Corruption]);
+        let tirages: Vec<Perturbation> = (0..1000).map(|_| moitie.tirer()).collect();
+        let retards = tirages.iter().filter(|p| **p == Perturbation::Retard(Duration::from_millis(200))).count();
+        let corruptions = tirages.iter().filter(|p| **p == Perturbation::Corruption).count();
+        assert!((150..350).contains(&retards) && (150..350).contains(&corruptions), "{} / {}", retards, corruptions);
+
+        // Réponse tronquée : même identifiant, paquet illisible
+        let mut octets = PaquetDns::requete(0x1234, "esgi.fr", TypeEnregistrement::A).encoder();
+        let taille = octets.len();
+        moitie.corrompre(&mut octets);
+        assert!(octets.len() < taille && octets.starts_with(&[0x12, 0x34]));
+        assert!(PaquetDns::decoder(&octets).is_err());
+
+        assert_eq!("Malformed".parse(), Ok(Effet::Corruption));
+        assert!("lent".parse::<Effet>().is_err());
+    }
+}
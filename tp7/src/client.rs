@@ -1,30 +1,225 @@
-use std::io::{self, Write};
-use std::net::UdpSocket;
+// Client résolveur réutilisable : requêtes binaires avec délai d'attente et nouvelles tentatives
 
-fn main() -> std::io::Result<()> {
-    let socket = UdpSocket::bind("127.0.0.1:0")?; // Port aléatoire local
-    socket.connect("127.0.0.1:8053")?;
+use std::fmt;
+use std::io;
+use std::net::{IpAddr, SocketAddr, UdpSocket};
+use std::time::{Duration, Instant};
 
-    loop {
-        print!(" Entrez un nom de domaine (ou 'quit') : ");
-        io::stdout().flush()?;
+use crate::dns::{
+    interroger_tcp, normaliser_nom, CodeReponse, Donnees, Enregistrement, ErreurDns, PaquetDns, TypeEnregistrement, TAILLE_EDNS_ANNONCEE,
+};
 
-        let mut input = String::new();
-        io::stdin().read_line(&mut input)?;
-        let input = input.trim();
+/// Adresse par défaut du serveur tp7
+pub const SERVEUR_PAR_DEFAUT: &str = "127.0.0.1:8053";
 
-        if input.eq_ignore_ascii_case("quit") {
-            break;
+/// Erreur renvoyée par une résolution
+#[derive(Debug)]
+pub enum ErreurClient {
+    /// Le nom n'existe pas
+    NxDomain,
+    /// Le serveur n'a pas pu résoudre le nom
+    ServFail,
+    /// Autre code de réponse (REFUSED, FORMERR...)
+    Refuse(CodeReponse),
+    /// Le nom existe mais sans donnée utile (service sans SRV, cible sans adresse)
+    SansDonnees,
+    /// Aucune réponse après toutes les tentatives
+    DelaiDepasse,
+    /// Réponse illisible
+    Format(ErreurDns),
+    Io(io::Error),
+}
+
+impl ErreurClient {
+    /// Statut court, identique aux réponses du protocole texte
+    pub fn statut(&self) -> &'static str {
+        match self {
+            ErreurClient::NxDomain => "NXDOMAIN",
+            ErreurClient::ServFail => "SERVFAIL",
+            ErreurClient::Refuse(_) => "REFUSED",
+            ErreurClient::SansDonnees => "NODATA",
+            ErreurClient::DelaiDepasse => "TIMEOUT",
+            ErreurClient::Format(_) => "FORMERR",
+            ErreurClient::Io(_) => "IOERROR",
+        }
+    }
+}
+
+impl fmt::Display for ErreurClient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ErreurClient::NxDomain => write!(f, "domaine inexistant"),
+            ErreurClient::ServFail => write!(f, "échec du serveur"),
+            ErreurClient::Refuse(code) => write!(f, "requête refusée ({:?})", code),
+            ErreurClient::SansDonnees => write!(f, "aucune donnée pour ce nom"),
+            ErreurClient::DelaiDepasse => write!(f, "aucune réponse du serveur"),
+            ErreurClient::Format(e) => write!(f, "réponse invalide: {}", e),
+            ErreurClient::Io(e) => write!(f, "erreur réseau: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ErreurClient {}
+
+impl From<io::Error> for ErreurClient {
+    fn from(e: io::Error) -> Self {
+        ErreurClient::Io(e)
+    }
+}
+
+/// Client DNS vers un serveur unique
+#[derive(Debug, Clone)]
+pub struct ClientDns {
+    serveur: SocketAddr,
+    delai: Duration,
+    tentatives: u32,
+    taille_edns: Option<u16>,
+    tcp: bool,
+}
+
+impl ClientDns {
+    pub fn new(serveur: SocketAddr) -> Self {
+        Self {
+            serveur,
+            delai: Duration::from_secs(2),
+            tentatives: 3,
+            taille_edns: Some(TAILLE_EDNS_ANNONCEE),
+            tcp: false,
         }
+    }
+
+    /// Taille de tampon UDP annoncée en EDNS0 (`None` : requêtes DNS classiques, 512 octets)
+    pub fn taille_edns(mut self, taille: Option<u16>) -> Self {
+        self.taille_edns = taille;
+        self
+    }
+
+    /// Interroger directement en TCP plutôt qu'en UDP
+    pub fn tcp(mut self, tcp: bool) -> Self {
+        self.tcp = tcp;
+        self
+    }
 
-        socket.send(input.as_bytes())?;
+    /// Délai d'attente de chaque tentative
+    pub fn delai(mut self, delai: Duration) -> Self {
+        self.delai = delai;
+        self
+    }
 
-        let mut buffer = [0u8; 1024];
-        let taille = socket.recv(&mut buffer)?;
-        let reponse = String::from_utf8_lossy(&buffer[..taille]);
+    /// Nombre total d'envois avant d'abandonner (au moins un)
+    pub fn tentatives(mut self, tentatives: u32) -> Self {
+        self.tentatives = tentatives.max(1);
+        self
+    }
 
-        println!(" Réponse du serveur : {}", reponse);
+    pub fn serveur(&self) -> SocketAddr {
+        self.serveur
     }
 
-    Ok(())
+    /// Résoudre un nom ; une liste vide signifie NOERROR sans donnée
+    pub fn lookup(&self, nom: &str, type_rr: TypeEnregistrement) -> Result<Vec<Enregistrement>, ErreurClient> {
+        let reponse = self.interroger(nom, type_rr)?;
+        match reponse.en_tete.rcode {
+            CodeReponse::NoError => Ok(reponse.reponses),
+            CodeReponse::NxDomain => Err(ErreurClient::NxDomain),
+            CodeReponse::ServFail => Err(ErreurClient::ServFail),
+            autre => Err(ErreurClient::Refuse(autre)),
+        }
+    }
+
+    /// Adresse d'un service annoncé par un enregistrement SRV ("_scp._tcp.local") : la cible de
+    /// plus petite priorité, puis de plus grand poids, avec son adresse A ou AAAA (ou littérale)
+    pub fn decouvrir(&self, service: &str) -> Result<SocketAddr, ErreurClient> {
+        let (cible, port) = self
+            .lookup(service, TypeEnregistrement::SRV)?
+            .into_iter()
+            .filter_map(|rr| match rr.donnees {
+                Donnees::SRV { priorite, poids, port, cible } => Some((priorite, poids, cible, port)),
+                _ => None,
+            })
+            .min_by_key(|(priorite, poids, _, _)| (*priorite, std::cmp::Reverse(*poids)))
+            .map(|(_, _, cible, port)| (normaliser_nom(&cible), port))
+            .ok_or(ErreurClient::SansDonnees)?;
+
+        if let Ok(ip) = cible.parse::<IpAddr>() {
+            return Ok(SocketAddr::new(ip, port));
+        }
+        for type_rr in [TypeEnregistrement::A, TypeEnregistrement::AAAA] {
+            let adresse = self.lookup(&cible, type_rr)?.into_iter().find_map(|rr| match rr.donnees {
+                Donnees::A(ip) => Some(IpAddr::V4(ip)),
+                Donnees::AAAA(ip) => Some(IpAddr::V6(ip)),
+                _ => None,
+            });
+            if let Some(ip) = adresse {
+                return Ok(SocketAddr::new(ip, port));
+            }
+        }
+        Err(ErreurClient::SansDonnees)
+    }
+
+    /// Envoyer la requête et renvoyer le paquet de réponse brut ; une réponse UDP
+    /// tronquée est redemandée sur l'écoute TCP du serveur
+    pub fn interroger(&self, nom: &str, type_rr: TypeEnregistrement) -> Result<PaquetDns, ErreurClient> {
+        let mut requete = PaquetDns::requete(identifiant_requete(), nom, type_rr);
+        if let Some(taille) = self.taille_edns {
+            requete.ajouter_edns(taille);
+        }
+
+        if self.tcp {
+            return self.interroger_tcp(&requete);
+        }
+
+        let locale = if self.serveur.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" };
+        let socket = UdpSocket::bind(locale)?;
+        socket.connect(self.serveur)?;
+
+        let id = requete.en_tete.id;
+        let octets = requete.encoder();
+        let mut tampon = vec![0u8; requete.taille_udp_max()];
+
+        for _ in 0..self.tentatives {
+            socket.send(&octets)?;
+            let echeance = Instant::now() + self.delai;
+
+            loop {
+                let restant = echeance.saturating_duration_since(Instant::now());
+                if restant.is_zero() {
+                    break;
+                }
+                socket.set_read_timeout(Some(restant))?;
+
+                let taille = match socket.recv(&mut tampon) {
+                    Ok(taille) => taille,
+                    Err(e) if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => break,
+                    // Port fermé côté serveur (ICMP) : on retente comme pour un délai dépassé
+                    Err(e) if e.kind() == io::ErrorKind::ConnectionRefused => break,
+                    Err(e) => return Err(e.into()),
+                };
+
+                let reponse = PaquetDns::decoder(&tampon[..taille]).map_err(ErreurClient::Format)?;
+                if reponse.en_tete.qr && reponse.en_tete.id == id {
+                    if reponse.en_tete.tc {
+                        return self.interroger_tcp(&requete);
+                    }
+                    return Ok(reponse);
+                }
+            }
+        }
+
+        Err(ErreurClient::DelaiDepasse)
+    }
+
+    fn interroger_tcp(&self, requete: &PaquetDns) -> Result<PaquetDns, ErreurClient> {
+        let echeance = Instant::now() + self.delai * self.tentatives;
+        interroger_tcp(self.serveur, requete, echeance).map_err(|e| match e.kind() {
+            io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock => ErreurClient::DelaiDepasse,
+            _ => ErreurClient::Io(e),
+        })
+    }
+}
+
+fn identifiant_requete() -> u16 {
+    use std::time::{SystemTime, UNIX_EPOCH};
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.subsec_nanos()).unwrap_or(0);
+    (nanos ^ (nanos >> 16)) as u16
 }
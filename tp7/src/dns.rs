@@ -0,0 +1,995 @@
+// Format binaire DNS (RFC 1035) : sous-ensemble suffisant pour le serveur et le relais amont
+
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, TcpStream};
+use std::time::Instant;
+
+/// Taille maximale d'un paquet UDP DNS classique (sans EDNS0)
+pub const TAILLE_UDP_STANDARD: usize = 512;
+
+/// Taille de tampon UDP annoncée dans nos propres enregistrements OPT
+pub const TAILLE_EDNS_ANNONCEE: u16 = 4096;
+
+/// Bit DO (« DNSSEC OK ») dans le TTL d'un OPT, qui porte les drapeaux étendus
+const BIT_DO: u32 = 0x8000;
+
+/// Nombre maximal de pointeurs de compression suivis lors du décodage d'un nom
+const MAX_SAUTS_COMPRESSION: usize = 16;
+
+/// Erreur de décodage d'un paquet DNS
+#[derive(Debug, Clone, PartialEq)]
+pub enum ErreurDns {
+    /// Le paquet se termine avant la fin d'un champ
+    Tronque,
+    /// Un nom est mal formé (label trop long, boucle de compression...)
+    NomInvalide,
+    /// Une donnée ne respecte pas le format attendu
+    FormatInvalide(String),
+}
+
+impl fmt::Display for ErreurDns {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ErreurDns::Tronque => write!(f, "paquet tronqué"),
+            ErreurDns::NomInvalide => write!(f, "nom de domaine invalide"),
+            ErreurDns::FormatInvalide(detail) => write!(f, "format invalide: {}", detail),
+        }
+    }
+}
+
+impl std::error::Error for ErreurDns {}
+
+/// Types d'enregistrements pris en charge
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TypeEnregistrement {
+    A,
+    NS,
+    CNAME,
+    SOA,
+    TXT,
+    AAAA,
+    SRV,
+    /// Pseudo-enregistrement EDNS0 (section additionnelle uniquement)
+    OPT,
+    /// Empreinte de la clé d'une zone fille, publiée par le parent (DNSSEC)
+    DS,
+    /// Signature d'un ensemble d'enregistrements (DNSSEC)
+    RRSIG,
+    /// Clé publique d'une zone (DNSSEC)
+    DNSKEY,
+    /// Transfert incrémental (question uniquement)
+    IXFR,
+    /// Transfert complet de zone (question uniquement)
+    AXFR,
+    /// Type non interprété, conservé par son code numérique
+    Autre(u16),
+}
+
+impl TypeEnregistrement {
+    pub fn code(self) -> u16 {
+        match self {
+            TypeEnregistrement::A => 1,
+            TypeEnregistrement::NS => 2,
+            TypeEnregistrement::CNAME => 5,
+            TypeEnregistrement::SOA => 6,
+            TypeEnregistrement::TXT => 16,
+            TypeEnregistrement::AAAA => 28,
+            TypeEnregistrement::SRV => 33,
+            TypeEnregistrement::OPT => 41,
+            TypeEnregistrement::DS => 43,
+            TypeEnregistrement::RRSIG => 46,
+            TypeEnregistrement::DNSKEY => 48,
+            TypeEnregistrement::IXFR => 251,
+            TypeEnregistrement::AXFR => 252,
+            TypeEnregistrement::Autre(code) => code,
+        }
+    }
+
+    pub fn depuis_code(code: u16) -> Self {
+        match code {
+            1 => TypeEnregistrement::A,
+            2 => TypeEnregistrement::NS,
+            5 => TypeEnregistrement::CNAME,
+            6 => TypeEnregistrement::SOA,
+            16 => TypeEnregistrement::TXT,
+            28 => TypeEnregistrement::AAAA,
+            33 => TypeEnregistrement::SRV,
+            41 => TypeEnregistrement::OPT,
+            43 => TypeEnregistrement::DS,
+            46 => TypeEnregistrement::RRSIG,
+            48 => TypeEnregistrement::DNSKEY,
+            251 => TypeEnregistrement::IXFR,
+            252 => TypeEnregistrement::AXFR,
+            autre => TypeEnregistrement::Autre(autre),
+        }
+    }
+}
+
+impl fmt::Display for TypeEnregistrement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TypeEnregistrement::Autre(code) => write!(f, "TYPE{}", code),
+            autre => write!(f, "{:?}", autre),
+        }
+    }
+}
+
+impl std::str::FromStr for TypeEnregistrement {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_uppercase().as_str() {
+            "A" => Ok(TypeEnregistrement::A),
+            "NS" => Ok(TypeEnregistrement::NS),
+            "CNAME" => Ok(TypeEnregistrement::CNAME),
+            "SOA" => Ok(TypeEnregistrement::SOA),
+            "TXT" => Ok(TypeEnregistrement::TXT),
+            "AAAA" => Ok(TypeEnregistrement::AAAA),
+            "SRV" => Ok(TypeEnregistrement::SRV),
+            "DS" => Ok(TypeEnregistrement::DS),
+            "RRSIG" => Ok(TypeEnregistrement::RRSIG),
+            "DNSKEY" => Ok(TypeEnregistrement::DNSKEY),
+            "IXFR" => Ok(TypeEnregistrement::IXFR),
+            "AXFR" => Ok(TypeEnregistrement::AXFR),
+            autre => autre
+                .strip_prefix("TYPE")
+                .and_then(|code| code.parse().ok())
+                .map(TypeEnregistrement::depuis_code)
+                .ok_or_else(|| format!("type d'enregistrement inconnu: {}", s)),
+        }
+    }
+}
+
+/// Code de réponse (RCODE) d'un paquet DNS
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CodeReponse {
+    NoError,
+    FormErr,
+    ServFail,
+    NxDomain,
+    NotImp,
+    Refused,
+    Autre(u8),
+}
+
+impl CodeReponse {
+    pub fn code(self) -> u8 {
+        match self {
+            CodeReponse::NoError => 0,
+            CodeReponse::FormErr => 1,
+            CodeReponse::ServFail => 2,
+            CodeReponse::NxDomain => 3,
+            CodeReponse::NotImp => 4,
+            CodeReponse::Refused => 5,
+            CodeReponse::Autre(code) => code,
+        }
+    }
+
+    pub fn depuis_code(code: u8) -> Self {
+        match code {
+            0 => CodeReponse::NoError,
+            1 => CodeReponse::FormErr,
+            2 => CodeReponse::ServFail,
+            3 => CodeReponse::NxDomain,
+            4 => CodeReponse::NotImp,
+            5 => CodeReponse::Refused,
+            autre => CodeReponse::Autre(autre),
+        }
+    }
+}
+
+/// Données (RDATA) d'un enregistrement
+#[derive(Debug, Clone, PartialEq)]
+pub enum Donnees {
+    A(Ipv4Addr),
+    AAAA(Ipv6Addr),
+    NS(String),
+    CNAME(String),
+    TXT(String),
+    SRV { priorite: u16, poids: u16, port: u16, cible: String },
+    SOA {
+        mname: String,
+        rname: String,
+        serial: u32,
+        refresh: u32,
+        retry: u32,
+        expire: u32,
+        minimum: u32,
+    },
+    /// Clé publique d'une zone (RFC 4034 §2) ; le drapeau 257 désigne une clé de signature de clés
+    DNSKEY { drapeaux: u16, protocole: u8, algorithme: u8, cle: Vec<u8> },
+    /// Signature du RRset `type_couvert` du même nom, par la clé `identifiant_cle` de `signataire` (RFC 4034 §3)
+    RRSIG {
+        type_couvert: TypeEnregistrement,
+        algorithme: u8,
+        labels: u8,
+        ttl_original: u32,
+        expiration: u32,
+        inception: u32,
+        identifiant_cle: u16,
+        signataire: String,
+        signature: Vec<u8>,
+    },
+    /// Empreinte d'une clé de la zone fille, publiée dans la zone parente (RFC 4034 §5)
+    DS { identifiant_cle: u16, algorithme: u8, type_empreinte: u8, empreinte: Vec<u8> },
+    /// Données brutes pour les types non interprétés
+    Brut(Vec<u8>),
+}
+
+impl fmt::Display for Donnees {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Donnees::A(ip) => write!(f, "{}", ip),
+            Donnees::AAAA(ip) => write!(f, "{}", ip),
+            Donnees::NS(nom) | Donnees::CNAME(nom) => write!(f, "{}", nom),
+            Donnees::TXT(texte) => write!(f, "\"{}\"", texte),
+            Donnees::SRV { priorite, poids, port, cible } => {
+                write!(f, "{} {} {} {}", priorite, poids, port, cible)
+            }
+            Donnees::SOA { mname, rname, serial, refresh, retry, expire, minimum } => write!(
+                f,
+                "{} {} {} {} {} {} {}",
+                mname, rname, serial, refresh, retry, expire, minimum
+            ),
+            Donnees::DNSKEY { drapeaux, protocole, algorithme, cle } => {
+                write!(f, "{} {} {} {}", drapeaux, protocole, algorithme, base64(cle))
+            }
+            Donnees::RRSIG {
+                type_couvert,
+                algorithme,
+                labels,
+                ttl_original,
+                expiration,
+                inception,
+                identifiant_cle,
+                signataire,
+                signature,
+            } => write!(
+                f,
+                "{} {} {} {} {} {} {} {} {}",
+                type_couvert,
+                algorithme,
+                labels,
+                ttl_original,
+                expiration,
+                inception,
+                identifiant_cle,
+                signataire,
+                base64(signature)
+            ),
+            Donnees::DS { identifiant_cle, algorithme, type_empreinte, empreinte } => {
+                let hexa: String = empreinte.iter().map(|o| format!("{:02X}", o)).collect();
+                write!(f, "{} {} {} {}", identifiant_cle, algorithme, type_empreinte, hexa)
+            }
+            Donnees::Brut(octets) => write!(f, "\\# {}", octets.len()),
+        }
+    }
+}
+
+/// Un enregistrement de ressource (RR)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Enregistrement {
+    pub nom: String,
+    pub type_rr: TypeEnregistrement,
+    pub classe: u16,
+    pub ttl: u32,
+    pub donnees: Donnees,
+}
+
+impl Enregistrement {
+    /// Créer un enregistrement de classe IN
+    pub fn new(nom: &str, ttl: u32, donnees: Donnees) -> Self {
+        let type_rr = match &donnees {
+            Donnees::A(_) => TypeEnregistrement::A,
+            Donnees::AAAA(_) => TypeEnregistrement::AAAA,
+            Donnees::NS(_) => TypeEnregistrement::NS,
+            Donnees::CNAME(_) => TypeEnregistrement::CNAME,
+            Donnees::TXT(_) => TypeEnregistrement::TXT,
+            Donnees::SRV { .. } => TypeEnregistrement::SRV,
+            Donnees::SOA { .. } => TypeEnregistrement::SOA,
+            Donnees::DNSKEY { .. } => TypeEnregistrement::DNSKEY,
+            Donnees::RRSIG { .. } => TypeEnregistrement::RRSIG,
+            Donnees::DS { .. } => TypeEnregistrement::DS,
+            Donnees::Brut(_) => TypeEnregistrement::Autre(0),
+        };
+        Self { nom: normaliser_nom(nom), type_rr, classe: CLASSE_IN, ttl, donnees }
+    }
+
+    /// Pseudo-enregistrement OPT : la classe porte la taille de tampon UDP, le TTL les drapeaux étendus
+    pub fn opt(taille_udp: u16) -> Self {
+        Self {
+            nom: String::new(),
+            type_rr: TypeEnregistrement::OPT,
+            classe: taille_udp,
+            ttl: 0,
+            donnees: Donnees::Brut(Vec::new()),
+        }
+    }
+
+    /// RDATA au format binaire ; les noms ne sont jamais compressés et déjà en minuscules,
+    /// c'est donc aussi la forme canonique utilisée par les signatures DNSSEC
+    pub fn rdata(&self) -> Vec<u8> {
+        let mut rdata = Vec::new();
+        encoder_rdata(&mut rdata, &self.donnees);
+        rdata
+    }
+
+    /// Représentation JSON destinée aux scripts
+    pub fn en_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "name": self.nom,
+            "type": self.type_rr.to_string(),
+            "ttl": self.ttl,
+            "data": self.donnees.to_string(),
+        })
+    }
+}
+
+/// Classe Internet
+pub const CLASSE_IN: u16 = 1;
+
+/// Question d'une requête DNS
+#[derive(Debug, Clone, PartialEq)]
+pub struct Question {
+    pub nom: String,
+    pub type_rr: TypeEnregistrement,
+    pub classe: u16,
+}
+
+/// En-tête d'un paquet DNS (les compteurs sont déduits des sections)
+#[derive(Debug, Clone, PartialEq)]
+pub struct EnTete {
+    pub id: u16,
+    /// Vrai pour une réponse, faux pour une requête
+    pub qr: bool,
+    pub opcode: u8,
+    /// Réponse faisant autorité
+    pub aa: bool,
+    /// Réponse tronquée
+    pub tc: bool,
+    /// Récursion demandée
+    pub rd: bool,
+    /// Récursion disponible
+    pub ra: bool,
+    pub rcode: CodeReponse,
+}
+
+/// Paquet DNS complet
+#[derive(Debug, Clone, PartialEq)]
+pub struct PaquetDns {
+    pub en_tete: EnTete,
+    pub questions: Vec<Question>,
+    pub reponses: Vec<Enregistrement>,
+    pub autorite: Vec<Enregistrement>,
+    pub additionnels: Vec<Enregistrement>,
+}
+
+impl PaquetDns {
+    /// Construire une requête récursive pour un nom et un type
+    pub fn requete(id: u16, nom: &str, type_rr: TypeEnregistrement) -> Self {
+        Self {
+            en_tete: EnTete {
+                id,
+                qr: false,
+                opcode: 0,
+                aa: false,
+                tc: false,
+                rd: true,
+                ra: false,
+                rcode: CodeReponse::NoError,
+            },
+            questions: vec![Question { nom: normaliser_nom(nom), type_rr, classe: CLASSE_IN }],
+            reponses: Vec::new(),
+            autorite: Vec::new(),
+            additionnels: Vec::new(),
+        }
+    }
+
+    /// Construire une réponse vide à une requête (mêmes id, questions et bit RD)
+    pub fn reponse_a(requete: &PaquetDns, rcode: CodeReponse) -> Self {
+        Self {
+            en_tete: EnTete {
+                id: requete.en_tete.id,
+                qr: true,
+                opcode: requete.en_tete.opcode,
+                aa: false,
+                tc: false,
+                rd: requete.en_tete.rd,
+                ra: false,
+                rcode,
+            },
+            questions: requete.questions.clone(),
+            reponses: Vec::new(),
+            autorite: Vec::new(),
+            additionnels: Vec::new(),
+        }
+    }
+
+    /// Encoder le paquet au format binaire (sans compression des noms)
+    pub fn encoder(&self) -> Vec<u8> {
+        let mut sortie = Vec::with_capacity(TAILLE_UDP_STANDARD);
+        let h = &self.en_tete;
+
+        sortie.extend_from_slice(&h.id.to_be_bytes());
+        let mut drapeaux: u16 = 0;
+        if h.qr {
+            drapeaux |= 0x8000;
+        }
+        drapeaux |= ((h.opcode as u16) & 0x0F) << 11;
+        if h.aa {
+            drapeaux |= 0x0400;
+        }
+        if h.tc {
+            drapeaux |= 0x0200;
+        }
+        if h.rd {
+            drapeaux |= 0x0100;
+        }
+        if h.ra {
+            drapeaux |= 0x0080;
+        }
+        drapeaux |= (h.rcode.code() as u16) & 0x0F;
+        sortie.extend_from_slice(&drapeaux.to_be_bytes());
+
+        for compte in [
+            self.questions.len(),
+            self.reponses.len(),
+            self.autorite.len(),
+            self.additionnels.len(),
+        ] {
+            sortie.extend_from_slice(&(compte as u16).to_be_bytes());
+        }
+
+        for question in &self.questions {
+            encoder_nom(&mut sortie, &question.nom);
+            sortie.extend_from_slice(&question.type_rr.code().to_be_bytes());
+            sortie.extend_from_slice(&question.classe.to_be_bytes());
+        }
+
+        for rr in self.reponses.iter().chain(&self.autorite).chain(&self.additionnels) {
+            encoder_enregistrement(&mut sortie, rr);
+        }
+
+        sortie
+    }
+
+    /// Décoder un paquet depuis son format binaire
+    pub fn decoder(octets: &[u8]) -> Result<Self, ErreurDns> {
+        let mut lecteur = Lecteur { octets, position: 0 };
+
+        let id = lecteur.u16()?;
+        let drapeaux = lecteur.u16()?;
+        let qdcount = lecteur.u16()?;
+        let ancount = lecteur.u16()?;
+        let nscount = lecteur.u16()?;
+        let arcount = lecteur.u16()?;
+
+        let en_tete = EnTete {
+            id,
+            qr: drapeaux & 0x8000 != 0,
+            opcode: ((drapeaux >> 11) & 0x0F) as u8,
+            aa: drapeaux & 0x0400 != 0,
+            tc: drapeaux & 0x0200 != 0,
+            rd: drapeaux & 0x0100 != 0,
+            ra: drapeaux & 0x0080 != 0,
+            rcode: CodeReponse::depuis_code((drapeaux & 0x0F) as u8),
+        };
+
+        let mut questions = Vec::with_capacity(qdcount as usize);
+        for _ in 0..qdcount {
+            let nom = lecteur.nom()?;
+            let type_rr = TypeEnregistrement::depuis_code(lecteur.u16()?);
+            let classe = lecteur.u16()?;
+            questions.push(Question { nom, type_rr, classe });
+        }
+
+        let mut lire_section = |compte: u16| -> Result<Vec<Enregistrement>, ErreurDns> {
+            (0..compte).map(|_| lecteur.enregistrement()).collect()
+        };
+        let reponses = lire_section(ancount)?;
+        let autorite = lire_section(nscount)?;
+        let additionnels = lire_section(arcount)?;
+
+        Ok(Self { en_tete, questions, reponses, autorite, additionnels })
+    }
+
+    /// Indique si le paquet ressemble à une requête standard à une seule question.
+    /// Une requête texte ne peut pas passer ce test : le quatrième octet d'un texte
+    /// imprimable donne toujours un opcode non nul ou un bit QR levé.
+    pub fn est_requete_standard(&self) -> bool {
+        !self.en_tete.qr && self.en_tete.opcode == 0 && self.questions.len() == 1
+    }
+
+    /// Taille de tampon UDP annoncée par l'OPT EDNS0 (RFC 6891), si présent
+    pub fn taille_edns(&self) -> Option<u16> {
+        self.additionnels
+            .iter()
+            .find(|rr| rr.type_rr == TypeEnregistrement::OPT)
+            .map(|opt| opt.classe)
+    }
+
+    /// Taille maximale d'une réponse UDP à cette requête (jamais moins de 512 octets)
+    pub fn taille_udp_max(&self) -> usize {
+        self.taille_edns()
+            .map(|taille| (taille as usize).max(TAILLE_UDP_STANDARD))
+            .unwrap_or(TAILLE_UDP_STANDARD)
+    }
+
+    /// Ajouter un OPT annonçant notre taille de tampon
+    pub fn ajouter_edns(&mut self, taille: u16) {
+        self.additionnels.retain(|rr| rr.type_rr != TypeEnregistrement::OPT);
+        self.additionnels.push(Enregistrement::opt(taille));
+    }
+
+    /// Lever le bit DO de l'OPT (RFC 3225) : les signatures DNSSEC sont demandées avec la réponse
+    pub fn demander_dnssec(&mut self) {
+        if self.taille_edns().is_none() {
+            self.ajouter_edns(TAILLE_EDNS_ANNONCEE);
+        }
+        for opt in self.additionnels.iter_mut().filter(|rr| rr.type_rr == TypeEnregistrement::OPT) {
+            opt.ttl |= BIT_DO;
+        }
+    }
+
+    /// Vrai si l'OPT porte le bit DO
+    pub fn dnssec_demande(&self) -> bool {
+        self.additionnels.iter().any(|rr| rr.type_rr == TypeEnregistrement::OPT && rr.ttl & BIT_DO != 0)
+    }
+
+    /// Retirer des enregistrements jusqu'à tenir dans `taille_max` octets et lever le bit TC
+    /// si quelque chose a été retiré ; l'OPT est conservé pour que le client sache réessayer
+    pub fn tronquer(&mut self, taille_max: usize) -> bool {
+        if self.encoder().len() <= taille_max {
+            return false;
+        }
+
+        self.en_tete.tc = true;
+        self.additionnels.retain(|rr| rr.type_rr == TypeEnregistrement::OPT);
+        while self.encoder().len() > taille_max {
+            if self.autorite.pop().is_none() && self.reponses.pop().is_none() {
+                break;
+            }
+        }
+        true
+    }
+}
+
+/// Lire un message DNS préfixé par sa longueur sur deux octets (RFC 1035 §4.2.2) ;
+/// `None` si le pair a fermé proprement la connexion
+pub fn lire_message_tcp(flux: &mut impl Read) -> io::Result<Option<Vec<u8>>> {
+    let mut longueur = [0u8; 2];
+    match flux.read_exact(&mut longueur) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+    let mut message = vec![0u8; u16::from_be_bytes(longueur) as usize];
+    flux.read_exact(&mut message)?;
+    Ok(Some(message))
+}
+
+/// Écrire un message DNS préfixé par sa longueur
+pub fn ecrire_message_tcp(flux: &mut impl Write, octets: &[u8]) -> io::Result<()> {
+    let longueur = u16::try_from(octets.len())
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "message DNS trop long pour TCP"))?;
+    flux.write_all(&longueur.to_be_bytes())?;
+    flux.write_all(octets)?;
+    flux.flush()
+}
+
+/// Poser une question en TCP (après une réponse UDP tronquée) avant l'échéance donnée
+pub fn interroger_tcp(serveur: SocketAddr, requete: &PaquetDns, echeance: Instant) -> io::Result<PaquetDns> {
+    let delai = |echeance: Instant| {
+        let restant = echeance.saturating_duration_since(Instant::now());
+        if restant.is_zero() {
+            Err(io::Error::new(io::ErrorKind::TimedOut, "délai dépassé"))
+        } else {
+            Ok(restant)
+        }
+    };
+
+    let mut flux = TcpStream::connect_timeout(&serveur, delai(echeance)?)?;
+    ecrire_message_tcp(&mut flux, &requete.encoder())?;
+    loop {
+        flux.set_read_timeout(Some(delai(echeance)?))?;
+        let octets = lire_message_tcp(&mut flux)?
+            .ok_or_else(|| io::Error::new(io::ErrorKind::UnexpectedEof, "connexion fermée sans réponse"))?;
+        let reponse = PaquetDns::decoder(&octets).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        if reponse.en_tete.qr && reponse.en_tete.id == requete.en_tete.id {
+            return Ok(reponse);
+        }
+    }
+}
+
+/// Mettre un nom en minuscules et retirer le point final
+pub fn normaliser_nom(nom: &str) -> String {
+    nom.trim().trim_end_matches('.').to_ascii_lowercase()
+}
+
+/// Nom au format binaire, en minuscules et sans compression (forme canonique DNSSEC)
+pub fn nom_canonique(nom: &str) -> Vec<u8> {
+    let mut sortie = Vec::new();
+    encoder_nom(&mut sortie, &normaliser_nom(nom));
+    sortie
+}
+
+/// Base64 (RFC 4648), pour afficher clés et signatures comme dans un fichier de zone
+fn base64(octets: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut texte = String::with_capacity(octets.len().div_ceil(3) * 4);
+    for groupe in octets.chunks(3) {
+        let valeur = groupe.iter().enumerate().fold(0u32, |v, (i, &o)| v | (o as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= groupe.len() {
+                texte.push(ALPHABET[(valeur >> (18 - 6 * i) & 0x3F) as usize] as char);
+            } else {
+                texte.push('=');
+            }
+        }
+    }
+    texte
+}
+
+fn encoder_nom(sortie: &mut Vec<u8>, nom: &str) {
+    for label in nom.split('.').filter(|l| !l.is_empty()) {
+        let label = &label.as_bytes()[..label.len().min(63)];
+        sortie.push(label.len() as u8);
+        sortie.extend_from_slice(label);
+    }
+    sortie.push(0);
+}
+
+fn encoder_enregistrement(sortie: &mut Vec<u8>, rr: &Enregistrement) {
+    encoder_nom(sortie, &rr.nom);
+    sortie.extend_from_slice(&rr.type_rr.code().to_be_bytes());
+    sortie.extend_from_slice(&rr.classe.to_be_bytes());
+    sortie.extend_from_slice(&rr.ttl.to_be_bytes());
+
+    let rdata = rr.rdata();
+    sortie.extend_from_slice(&(rdata.len() as u16).to_be_bytes());
+    sortie.extend_from_slice(&rdata);
+}
+
+fn encoder_rdata(rdata: &mut Vec<u8>, donnees: &Donnees) {
+    match donnees {
+        Donnees::A(ip) => rdata.extend_from_slice(&ip.octets()),
+        Donnees::AAAA(ip) => rdata.extend_from_slice(&ip.octets()),
+        Donnees::NS(nom) | Donnees::CNAME(nom) => encoder_nom(rdata, nom),
+        Donnees::TXT(texte) => {
+            // Un TXT est une suite de chaînes de 255 octets au plus
+            for morceau in texte.as_bytes().chunks(255) {
+                rdata.push(morceau.len() as u8);
+                rdata.extend_from_slice(morceau);
+            }
+            if texte.is_empty() {
+                rdata.push(0);
+            }
+        }
+        Donnees::SRV { priorite, poids, port, cible } => {
+            rdata.extend_from_slice(&priorite.to_be_bytes());
+            rdata.extend_from_slice(&poids.to_be_bytes());
+            rdata.extend_from_slice(&port.to_be_bytes());
+            encoder_nom(rdata, cible);
+        }
+        Donnees::SOA { mname, rname, serial, refresh, retry, expire, minimum } => {
+            encoder_nom(rdata, mname);
+            encoder_nom(rdata, rname);
+            for valeur in [serial, refresh, retry, expire, minimum] {
+                rdata.extend_from_slice(&valeur.to_be_bytes());
+            }
+        }
+        Donnees::DNSKEY { drapeaux, protocole, algorithme, cle } => {
+            rdata.extend_from_slice(&drapeaux.to_be_bytes());
+            rdata.push(*protocole);
+            rdata.push(*algorithme);
+            rdata.extend_from_slice(cle);
+        }
+        Donnees::RRSIG { signature, .. } => {
+            encoder_rrsig_sans_signature(rdata, donnees);
+            rdata.extend_from_slice(signature);
+        }
+        Donnees::DS { identifiant_cle, algorithme, type_empreinte, empreinte } => {
+            rdata.extend_from_slice(&identifiant_cle.to_be_bytes());
+            rdata.push(*algorithme);
+            rdata.push(*type_empreinte);
+            rdata.extend_from_slice(empreinte);
+        }
+        Donnees::Brut(octets) => rdata.extend_from_slice(octets),
+    }
+}
+
+/// Champs d'un RRSIG qui précèdent la signature : c'est le début des données signées (RFC 4034 §3.1.8.1)
+pub fn encoder_rrsig_sans_signature(rdata: &mut Vec<u8>, donnees: &Donnees) {
+    if let Donnees::RRSIG {
+        type_couvert,
+        algorithme,
+        labels,
+        ttl_original,
+        expiration,
+        inception,
+        identifiant_cle,
+        signataire,
+        ..
+    } = donnees
+    {
+        rdata.extend_from_slice(&type_couvert.code().to_be_bytes());
+        rdata.push(*algorithme);
+        rdata.push(*labels);
+        for valeur in [ttl_original, expiration, inception] {
+            rdata.extend_from_slice(&valeur.to_be_bytes());
+        }
+        rdata.extend_from_slice(&identifiant_cle.to_be_bytes());
+        encoder_nom(rdata, signataire);
+    }
+}
+
+/// Curseur de lecture sur un paquet (les pointeurs de compression sont relatifs au début)
+struct Lecteur<'a> {
+    octets: &'a [u8],
+    position: usize,
+}
+
+impl Lecteur<'_> {
+    fn octets(&mut self, n: usize) -> Result<&[u8], ErreurDns> {
+        let fin = self.position.checked_add(n).ok_or(ErreurDns::Tronque)?;
+        let tranche = self.octets.get(self.position..fin).ok_or(ErreurDns::Tronque)?;
+        self.position = fin;
+        Ok(tranche)
+    }
+
+    fn u8(&mut self) -> Result<u8, ErreurDns> {
+        Ok(self.octets(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, ErreurDns> {
+        let o = self.octets(2)?;
+        Ok(u16::from_be_bytes([o[0], o[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, ErreurDns> {
+        let o = self.octets(4)?;
+        Ok(u32::from_be_bytes([o[0], o[1], o[2], o[3]]))
+    }
+
+    /// Lire un nom à la position courante en suivant les pointeurs de compression
+    fn nom(&mut self) -> Result<String, ErreurDns> {
+        let mut labels: Vec<String> = Vec::new();
+        let mut position = self.position;
+        let mut fin_lecture = None;
+        let mut sauts = 0;
+
+        loop {
+            let longueur = *self.octets.get(position).ok_or(ErreurDns::Tronque)? as usize;
+            match longueur & 0xC0 {
+                0x00 => {
+                    position += 1;
+                    if longueur == 0 {
+                        break;
+                    }
+                    let label = self
+                        .octets
+                        .get(position..position + longueur)
+                        .ok_or(ErreurDns::Tronque)?;
+                    labels.push(String::from_utf8_lossy(label).to_ascii_lowercase());
+                    position += longueur;
+                }
+                0xC0 => {
+                    let second = *self.octets.get(position + 1).ok_or(ErreurDns::Tronque)? as usize;
+                    if fin_lecture.is_none() {
+                        fin_lecture = Some(position + 2);
+                    }
+                    sauts += 1;
+                    if sauts > MAX_SAUTS_COMPRESSION {
+                        return Err(ErreurDns::NomInvalide);
+                    }
+                    position = ((longueur & 0x3F) << 8) | second;
+                }
+                _ => return Err(ErreurDns::NomInvalide),
+            }
+        }
+
+        self.position = fin_lecture.unwrap_or(position);
+        Ok(labels.join("."))
+    }
+
+    fn enregistrement(&mut self) -> Result<Enregistrement, ErreurDns> {
+        let nom = self.nom()?;
+        let type_rr = TypeEnregistrement::depuis_code(self.u16()?);
+        let classe = self.u16()?;
+        let ttl = self.u32()?;
+        let longueur = self.u16()? as usize;
+        let debut = self.position;
+        let fin = debut + longueur;
+        if fin > self.octets.len() {
+            return Err(ErreurDns::Tronque);
+        }
+
+        let donnees = match type_rr {
+            TypeEnregistrement::A if longueur == 4 => {
+                let o = self.octets(4)?;
+                Donnees::A(Ipv4Addr::new(o[0], o[1], o[2], o[3]))
+            }
+            TypeEnregistrement::AAAA if longueur == 16 => {
+                let mut o = [0u8; 16];
+                o.copy_from_slice(self.octets(16)?);
+                Donnees::AAAA(Ipv6Addr::from(o))
+            }
+            TypeEnregistrement::NS => Donnees::NS(self.nom()?),
+            TypeEnregistrement::CNAME => Donnees::CNAME(self.nom()?),
+            TypeEnregistrement::TXT => {
+                let mut texte = String::new();
+                while self.position < fin {
+                    let taille = self.u8()? as usize;
+                    texte.push_str(&String::from_utf8_lossy(self.octets(taille)?));
+                }
+                Donnees::TXT(texte)
+            }
+            TypeEnregistrement::SRV => Donnees::SRV {
+                priorite: self.u16()?,
+                poids: self.u16()?,
+                port: self.u16()?,
+                cible: self.nom()?,
+            },
+            TypeEnregistrement::SOA => Donnees::SOA {
+                mname: self.nom()?,
+                rname: self.nom()?,
+                serial: self.u32()?,
+                refresh: self.u32()?,
+                retry: self.u32()?,
+                expire: self.u32()?,
+                minimum: self.u32()?,
+            },
+            TypeEnregistrement::DNSKEY if longueur >= 4 => Donnees::DNSKEY {
+                drapeaux: self.u16()?,
+                protocole: self.u8()?,
+                algorithme: self.u8()?,
+                cle: self.octets(fin - debut - 4)?.to_vec(),
+            },
+            TypeEnregistrement::RRSIG => {
+                let type_couvert = TypeEnregistrement::depuis_code(self.u16()?);
+                let algorithme = self.u8()?;
+                let labels = self.u8()?;
+                let ttl_original = self.u32()?;
+                let expiration = self.u32()?;
+                let inception = self.u32()?;
+                let identifiant_cle = self.u16()?;
+                let signataire = self.nom()?;
+                let reste = fin.checked_sub(self.position).ok_or(ErreurDns::Tronque)?;
+                Donnees::RRSIG {
+                    type_couvert,
+                    algorithme,
+                    labels,
+                    ttl_original,
+                    expiration,
+                    inception,
+                    identifiant_cle,
+                    signataire,
+                    signature: self.octets(reste)?.to_vec(),
+                }
+            }
+            TypeEnregistrement::DS if longueur >= 4 => Donnees::DS {
+                identifiant_cle: self.u16()?,
+                algorithme: self.u8()?,
+                type_empreinte: self.u8()?,
+                empreinte: self.octets(fin - debut - 4)?.to_vec(),
+            },
+            _ => Donnees::Brut(self.octets(longueur)?.to_vec()),
+        };
+
+        if self.position != fin {
+            return Err(ErreurDns::FormatInvalide(format!(
+                "longueur RDATA incohérente pour {} {}",
+                nom, type_rr
+            )));
+        }
+
+        Ok(Enregistrement { nom, type_rr, classe, ttl, donnees })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_aller_retour_paquet() {
+        let requete = PaquetDns::requete(42, "Esgi.FR.", TypeEnregistrement::A);
+        let mut reponse = PaquetDns::reponse_a(&requete, CodeReponse::NoError);
+        reponse.reponses.push(Enregistrement::new("esgi.fr", 300, Donnees::A(Ipv4Addr::new(192, 168, 1, 42))));
+        reponse.reponses.push(Enregistrement::new(
+            "_scp._tcp.local",
+            60,
+            Donnees::SRV { priorite: 0, poids: 5, port: 9999, cible: "localhost".to_string() },
+        ));
+
+        let decode = PaquetDns::decoder(&reponse.encoder()).unwrap();
+        assert_eq!(decode, reponse);
+        assert_eq!(decode.questions[0].nom, "esgi.fr");
+    }
+
+    #[test]
+    fn test_decodage_compression_et_boucle() {
+        // En-tête + question "a.b" + réponse dont le nom est un pointeur vers la question
+        let mut paquet = vec![0, 1, 0x81, 0x80, 0, 1, 0, 1, 0, 0, 0, 0];
+        paquet.extend_from_slice(&[1, b'a', 1, b'b', 0, 0, 1, 0, 1]);
+        paquet.extend_from_slice(&[0xC0, 12, 0, 1, 0, 1, 0, 0, 0, 60, 0, 4, 10, 0, 0, 1]);
+        let decode = PaquetDns::decoder(&paquet).unwrap();
+        assert_eq!(decode.reponses[0].nom, "a.b");
+        assert_eq!(decode.reponses[0].donnees, Donnees::A(Ipv4Addr::new(10, 0, 0, 1)));
+
+        // Un pointeur qui se désigne lui-même ne doit pas boucler indéfiniment
+        let mut boucle = vec![0, 1, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0];
+        boucle.extend_from_slice(&[0xC0, 12, 0, 1, 0, 1]);
+        assert_eq!(PaquetDns::decoder(&boucle), Err(ErreurDns::NomInvalide));
+    }
+
+    #[test]
+    fn test_enregistrements_dnssec() {
+        let mut requete = PaquetDns::requete(9, "esgi.fr", TypeEnregistrement::DNSKEY);
+        assert!(!requete.dnssec_demande());
+        requete.demander_dnssec();
+        let requete = PaquetDns::decoder(&requete.encoder()).unwrap();
+        assert!(requete.dnssec_demande());
+        assert_eq!(requete.taille_edns(), Some(TAILLE_EDNS_ANNONCEE));
+
+        let mut reponse = PaquetDns::reponse_a(&requete, CodeReponse::NoError);
+        let cle = Donnees::DNSKEY { drapeaux: 257, protocole: 3, algorithme: 15, cle: vec![1, 2, 3, 4, 5] };
+        reponse.reponses.push(Enregistrement::new("esgi.fr", 3600, cle));
+        reponse.reponses.push(Enregistrement::new(
+            "esgi.fr",
+            3600,
+            Donnees::RRSIG {
+                type_couvert: TypeEnregistrement::DNSKEY,
+                algorithme: 15,
+                labels: 2,
+                ttl_original: 3600,
+                expiration: 1_800_000_000,
+                inception: 1_700_000_000,
+                identifiant_cle: 4242,
+                signataire: "esgi.fr".to_string(),
+                signature: vec![9; 64],
+            },
+        ));
+        reponse.autorite.push(Enregistrement::new(
+            "esgi.fr",
+            86400,
+            Donnees::DS { identifiant_cle: 4242, algorithme: 15, type_empreinte: 2, empreinte: vec![0xAB; 32] },
+        ));
+        assert_eq!(PaquetDns::decoder(&reponse.encoder()).unwrap(), reponse);
+        assert_eq!(reponse.reponses[0].donnees.to_string(), "257 3 15 AQIDBAU=");
+        assert!(reponse.autorite[0].donnees.to_string().starts_with("4242 15 2 ABABAB"));
+    }
+
+    #[test]
+    fn test_edns_et_troncature() {
+        let mut requete = PaquetDns::requete(7, "gros.local", TypeEnregistrement::TXT);
+        assert_eq!(requete.taille_udp_max(), TAILLE_UDP_STANDARD);
+        requete.ajouter_edns(1232);
+        let requete = PaquetDns::decoder(&requete.encoder()).unwrap();
+        assert_eq!(requete.taille_edns(), Some(1232));
+
+        let mut reponse = PaquetDns::reponse_a(&requete, CodeReponse::NoError);
+        for i in 0..20 {
+            reponse.reponses.push(Enregistrement::new("gros.local", 60, Donnees::TXT(format!("{:0>100}", i))));
+        }
+        reponse.ajouter_edns(TAILLE_EDNS_ANNONCEE);
+
+        assert!(!reponse.clone().tronquer(4096));
+        assert!(reponse.tronquer(requete.taille_udp_max()));
+        assert!(reponse.en_tete.tc);
+        assert!(reponse.encoder().len() <= 1232);
+        assert!(reponse.reponses.len() < 20);
+        assert_eq!(reponse.taille_edns(), Some(TAILLE_EDNS_ANNONCEE));
+    }
+
+    #[test]
+    fn test_texte_ne_ressemble_pas_a_une_requete() {
+        for texte in ["google.com", "yahoo.com AAAA", "un.nom.tres.long.exemple.org"] {
+            let standard = PaquetDns::decoder(texte.as_bytes())
+                .map(|p| p.est_requete_standard())
+                .unwrap_or(false);
+            assert!(!standard, "{} ne doit pas être vu comme un paquet DNS", texte);
+        }
+    }
+}
@@ -0,0 +1,458 @@
+// Validation DNSSEC allégée des réponses relayées : chaque RRset de la réponse doit être signé (RRSIG)
+// par une clé (DNSKEY) de sa zone, elle-même rattachée à une ancre de confiance par la chaîne des DS
+// publiés dans les zones parentes. Les réponses négatives (NSEC/NSEC3) ne sont pas prouvées : elles
+// restent « insecure », comme toute réponse qu'on ne sait pas rattacher à une ancre
+
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use ring::digest;
+use ring::signature::{self, RsaPublicKeyComponents, UnparsedPublicKey};
+
+use crate::dns::{encoder_rrsig_sans_signature, nom_canonique, normaliser_nom, Donnees, Enregistrement, TypeEnregistrement};
+
+/// Ancres de la racine (KSK-2017 et KSK-2024), utilisées si aucune n'est configurée
+pub const ANCRES_RACINE: [&str; 2] = [
+    ". 20326 8 2 E06D44B80B8F1D39A95C0B0D7C65D08458E880409BBC683457104237C7F8EC8D",
+    ". 38696 8 2 683D2D0ACB8C9B712A1948B27F741219298D0A450D612C483AF444A4C0FB2B16",
+];
+
+/// Longueur maximale de la chaîne de confiance parcourue (une zone par niveau)
+const PROFONDEUR_MAX: usize = 8;
+
+/// Durée maximale de conservation des clés validées, quel que soit leur TTL
+const DUREE_MAX_CLES: Duration = Duration::from_secs(3600);
+
+/// Drapeau « clé de zone » d'un DNSKEY : seules ces clés signent des enregistrements
+const DRAPEAU_CLE_DE_ZONE: u16 = 0x0100;
+
+/// Statut DNSSEC d'une réponse
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Securite {
+    /// Chaque RRset est signé par une chaîne de clés remontant à une ancre de confiance
+    Validee,
+    /// Non vérifiée : DNSSEC désactivé, zone non signée, hors des ancres ou signature invalide
+    #[default]
+    NonSecurisee,
+}
+
+impl fmt::Display for Securite {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Securite::Validee => write!(f, "validated"),
+            Securite::NonSecurisee => write!(f, "insecure"),
+        }
+    }
+}
+
+/// Ancre de confiance : l'empreinte d'une clé de signature de clés d'une zone, au format DS
+/// d'un fichier de zone sans classe ni type : "zone identifiant algorithme type_empreinte empreinte"
+#[derive(Debug, Clone, PartialEq)]
+pub struct AncreConfiance {
+    pub zone: String,
+    pub identifiant_cle: u16,
+    pub algorithme: u8,
+    pub type_empreinte: u8,
+    pub empreinte: Vec<u8>,
+}
+
+impl FromStr for AncreConfiance {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalide = || format!("ancre de confiance invalide (attendu: zone identifiant algorithme type empreinte): {}", s);
+        let champs: Vec<&str> = s.split_whitespace().collect();
+        let [zone, identifiant_cle, algorithme, type_empreinte, empreinte @ ..] = champs.as_slice() else {
+            return Err(invalide());
+        };
+        let hexa = empreinte.concat();
+        if hexa.is_empty() || hexa.len() % 2 != 0 {
+            return Err(invalide());
+        }
+        let empreinte = (0..hexa.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hexa[i..i + 2], 16))
+            .collect::<Result<Vec<u8>, _>>()
+            .map_err(|_| invalide())?;
+        Ok(Self {
+            zone: normaliser_nom(zone),
+            identifiant_cle: identifiant_cle.parse().map_err(|_| invalide())?,
+            algorithme: algorithme.parse().map_err(|_| invalide())?,
+            type_empreinte: type_empreinte.parse().map_err(|_| invalide())?,
+            empreinte,
+        })
+    }
+}
+
+/// Question posée à l'amont pendant la validation : les enregistrements de la réponse, signatures comprises
+pub type Interroger<'a> = dyn FnMut(&str, TypeEnregistrement) -> Option<Vec<Enregistrement>> + 'a;
+
+/// Validateur : ancres de confiance et clés déjà validées, par zone
+#[derive(Default, Clone)]
+pub struct Validateur {
+    ancres: Vec<AncreConfiance>,
+    cles: HashMap<String, (Vec<Enregistrement>, Instant)>,
+}
+
+impl Validateur {
+    pub fn new(ancres: Vec<AncreConfiance>) -> Self {
+        Self { ancres, cles: HashMap::new() }
+    }
+
+    /// Vrai si une ancre couvre ce nom (sa zone ou une zone parente)
+    pub fn couvre(&self, nom: &str) -> bool {
+        self.ancres.iter().any(|ancre| dans_zone(nom, &ancre.zone))
+    }
+
+    /// Valider les enregistrements d'une réponse obtenue avec le bit DO ; `maintenant` en secondes
+    /// depuis 1970. L'erreur explique pourquoi la réponse reste non sécurisée
+    pub fn valider(&mut self, enregistrements: &[Enregistrement], maintenant: u32, interroger: &mut Interroger) -> Result<(), String> {
+        let mut rrsets: Vec<(&str, TypeEnregistrement)> = Vec::new();
+        for rr in enregistrements.iter().filter(|rr| rr.type_rr != TypeEnregistrement::RRSIG) {
+            if !rrsets.contains(&(rr.nom.as_str(), rr.type_rr)) {
+                rrsets.push((&rr.nom, rr.type_rr));
+            }
+        }
+        if rrsets.is_empty() {
+            return Err("aucun enregistrement à valider".to_string());
+        }
+
+        for (nom, type_rr) in rrsets {
+            if !self.couvre(nom) {
+                return Err(format!("{} n'est couvert par aucune ancre de confiance", nom));
+            }
+            let (rrset, signatures) = rrset_et_signatures(enregistrements, nom, type_rr);
+            self.valider_rrset(&rrset, &signatures, maintenant, interroger, 0)?;
+        }
+        Ok(())
+    }
+
+    fn valider_rrset(
+        &mut self,
+        rrset: &[&Enregistrement],
+        signatures: &[&Enregistrement],
+        maintenant: u32,
+        interroger: &mut Interroger,
+        profondeur: usize,
+    ) -> Result<(), String> {
+        let rr = rrset[0];
+        let mut erreur = format!("{} {} n'est pas signé", rr.nom, rr.type_rr);
+        for signature in signatures {
+            let Donnees::RRSIG { signataire, .. } = &signature.donnees else {
+                continue;
+            };
+            if !dans_zone(&rr.nom, signataire) {
+                erreur = format!("{} {} signé par {}, hors de sa zone", rr.nom, rr.type_rr, signataire);
+                continue;
+            }
+            match self.cles_de(signataire, maintenant, interroger, profondeur + 1) {
+                Ok(cles) if cles.iter().any(|cle| verifier(signature, cle, rrset, maintenant)) => return Ok(()),
+                Ok(_) => erreur = format!("signature invalide ou expirée pour {} {}", rr.nom, rr.type_rr),
+                Err(e) => erreur = e,
+            }
+        }
+        Err(erreur)
+    }
+
+    /// Clés d'une zone, une fois leur RRset vérifié par une clé rattachée à une ancre :
+    /// directement pour la zone d'une ancre, par le DS publié (et validé) dans le parent sinon
+    fn cles_de(&mut self, zone: &str, maintenant: u32, interroger: &mut Interroger, profondeur: usize) -> Result<Vec<Enregistrement>, String> {
+        if profondeur > PROFONDEUR_MAX {
+            return Err(format!("chaîne de confiance trop longue pour {}", zone));
+        }
+        if let Some((cles, expire_a)) = self.cles.get(zone) {
+            if *expire_a > Instant::now() {
+                return Ok(cles.clone());
+            }
+        }
+
+        let reponse = interroger(zone, TypeEnregistrement::DNSKEY).ok_or_else(|| format!("DNSKEY de {} introuvables", zone))?;
+        let (cles, signatures) = rrset_et_signatures(&reponse, zone, TypeEnregistrement::DNSKEY);
+        if cles.is_empty() {
+            return Err(format!("{} ne publie aucune DNSKEY", zone));
+        }
+
+        let empreintes: Vec<AncreConfiance> = match self.ancres.iter().filter(|ancre| ancre.zone == zone).cloned().collect::<Vec<_>>() {
+            ancres if !ancres.is_empty() => ancres,
+            _ => {
+                let reponse = interroger(zone, TypeEnregistrement::DS).ok_or_else(|| format!("DS de {} introuvables", zone))?;
+                let (ds, signatures_ds) = rrset_et_signatures(&reponse, zone, TypeEnregistrement::DS);
+                if ds.is_empty() {
+                    return Err(format!("{} n'a pas de DS : zone non signée", zone));
+                }
+                self.valider_rrset(&ds, &signatures_ds, maintenant, interroger, profondeur)?;
+                ds.iter().filter_map(|rr| ancre_depuis_ds(rr)).collect()
+            }
+        };
+
+        let de_confiance: Vec<&Enregistrement> =
+            cles.iter().copied().filter(|cle| empreintes.iter().any(|empreinte| correspond(cle, empreinte))).collect();
+        let verifie = signatures
+            .iter()
+            .any(|signature| de_confiance.iter().any(|cle| verifier(signature, cle, &cles, maintenant)));
+        if !verifie {
+            return Err(format!("DNSKEY de {} : aucune signature par une clé de confiance", zone));
+        }
+
+        let cles: Vec<Enregistrement> = cles.into_iter().cloned().collect();
+        let ttl = cles.iter().map(|cle| cle.ttl).min().unwrap_or(0);
+        let duree = Duration::from_secs(ttl as u64).min(DUREE_MAX_CLES);
+        self.cles.insert(zone.to_string(), (cles.clone(), Instant::now() + duree));
+        Ok(cles)
+    }
+}
+
+/// Vrai si `nom` est `zone` ou l'un de ses sous-domaines (la racine est "")
+fn dans_zone(nom: &str, zone: &str) -> bool {
+    zone.is_empty() || nom == zone || nom.ends_with(&format!(".{}", zone))
+}
+
+/// Le RRset (nom, type) d'une liste d'enregistrements, et les RRSIG qui le couvrent
+fn rrset_et_signatures<'a>(
+    enregistrements: &'a [Enregistrement],
+    nom: &str,
+    type_rr: TypeEnregistrement,
+) -> (Vec<&'a Enregistrement>, Vec<&'a Enregistrement>) {
+    let rrset = enregistrements.iter().filter(|rr| rr.nom == nom && rr.type_rr == type_rr).collect();
+    let signatures = enregistrements
+        .iter()
+        .filter(|rr| rr.nom == nom && matches!(rr.donnees, Donnees::RRSIG { type_couvert, .. } if type_couvert == type_rr))
+        .collect();
+    (rrset, signatures)
+}
+
+fn ancre_depuis_ds(rr: &Enregistrement) -> Option<AncreConfiance> {
+    match &rr.donnees {
+        Donnees::DS { identifiant_cle, algorithme, type_empreinte, empreinte } => Some(AncreConfiance {
+            zone: rr.nom.clone(),
+            identifiant_cle: *identifiant_cle,
+            algorithme: *algorithme,
+            type_empreinte: *type_empreinte,
+            empreinte: empreinte.clone(),
+        }),
+        _ => None,
+    }
+}
+
+/// Identifiant (key tag) d'une clé, calculé sur son RDATA (RFC 4034 annexe B)
+pub fn identifiant_cle(rdata: &[u8]) -> u16 {
+    let mut somme: u32 = 0;
+    for (i, octet) in rdata.iter().enumerate() {
+        somme += if i % 2 == 0 { (*octet as u32) << 8 } else { *octet as u32 };
+    }
+    somme += (somme >> 16) & 0xFFFF;
+    (somme & 0xFFFF) as u16
+}
+
+/// Empreinte DS d'une clé : SHA-256 (type 2) ou SHA-384 (type 4) du nom puis du RDATA
+pub fn empreinte_ds(cle: &Enregistrement, type_empreinte: u8) -> Option<Vec<u8>> {
+    let algorithme = match type_empreinte {
+        2 => &digest::SHA256,
+        4 => &digest::SHA384,
+        _ => return None,
+    };
+    let mut donnees = nom_canonique(&cle.nom);
+    donnees.extend_from_slice(&cle.rdata());
+    Some(digest::digest(algorithme, &donnees).as_ref().to_vec())
+}
+
+/// Vrai si la clé est celle que désigne l'empreinte (ancre ou DS)
+fn correspond(cle: &Enregistrement, empreinte: &AncreConfiance) -> bool {
+    let Donnees::DNSKEY { algorithme, .. } = cle.donnees else {
+        return false;
+    };
+    cle.nom == empreinte.zone
+        && algorithme == empreinte.algorithme
+        && identifiant_cle(&cle.rdata()) == empreinte.identifiant_cle
+        && empreinte_ds(cle, empreinte.type_empreinte).as_deref() == Some(empreinte.empreinte.as_slice())
+}
+
+/// Données couvertes par une signature : le RRSIG sans sa signature, puis les enregistrements
+/// sous forme canonique, triés, avec le TTL d'origine (RFC 4034 §3.1.8.1)
+fn donnees_signees(signature: &Enregistrement, rrset: &[&Enregistrement]) -> Vec<u8> {
+    let mut donnees = Vec::new();
+    encoder_rrsig_sans_signature(&mut donnees, &signature.donnees);
+    let Donnees::RRSIG { labels, ttl_original, .. } = signature.donnees else {
+        return donnees;
+    };
+
+    let mut rdatas: Vec<Vec<u8>> = rrset.iter().map(|rr| rr.rdata()).collect();
+    rdatas.sort();
+    rdatas.dedup();
+    // Réponse issue d'un joker : le nom signé est "*." suivi des `labels` derniers labels
+    let nom = &rrset[0].nom;
+    let etiquettes: Vec<&str> = nom.split('.').filter(|l| !l.is_empty()).collect();
+    let proprietaire = if (labels as usize) < etiquettes.len() {
+        format!("*.{}", etiquettes[etiquettes.len() - labels as usize..].join("."))
+    } else {
+        nom.clone()
+    };
+    for rdata in rdatas {
+        donnees.extend_from_slice(&nom_canonique(&proprietaire));
+        donnees.extend_from_slice(&rrset[0].type_rr.code().to_be_bytes());
+        donnees.extend_from_slice(&rrset[0].classe.to_be_bytes());
+        donnees.extend_from_slice(&ttl_original.to_be_bytes());
+        donnees.extend_from_slice(&(rdata.len() as u16).to_be_bytes());
+        donnees.extend_from_slice(&rdata);
+    }
+    donnees
+}
+
+/// Vérifier qu'une signature couvre le RRset avec cette clé, et qu'elle est en cours de validité
+fn verifier(signature: &Enregistrement, cle: &Enregistrement, rrset: &[&Enregistrement], maintenant: u32) -> bool {
+    let (
+        Donnees::RRSIG { algorithme, labels, expiration, inception, identifiant_cle: identifiant, signataire, signature: octets, .. },
+        Donnees::DNSKEY { drapeaux, protocole, algorithme: algorithme_cle, cle: publique },
+    ) = (&signature.donnees, &cle.donnees)
+    else {
+        return false;
+    };
+    let etiquettes = rrset[0].nom.split('.').filter(|l| !l.is_empty()).count();
+    // Dates en arithmétique de numéros de série (RFC 1982) : elles bouclent en 2106
+    let commencee = maintenant.wrapping_sub(*inception) as i32 >= 0;
+    let pas_expiree = expiration.wrapping_sub(maintenant) as i32 >= 0;
+
+    drapeaux & DRAPEAU_CLE_DE_ZONE != 0
+        && *protocole == 3
+        && algorithme == algorithme_cle
+        && *signataire == cle.nom
+        && *identifiant == identifiant_cle(&cle.rdata())
+        && *labels as usize <= etiquettes
+        && commencee
+        && pas_expiree
+        && verifier_signature(*algorithme, publique, &donnees_signees(signature, rrset), octets)
+}
+
+/// Vérification cryptographique selon l'algorithme DNSSEC (RFC 8624) ; les autres sont refusés
+fn verifier_signature(algorithme: u8, cle: &[u8], donnees: &[u8], signature: &[u8]) -> bool {
+    match algorithme {
+        // RSA/SHA-256 et RSA/SHA-512 : longueur de l'exposant (1 octet, ou 0 puis 2 octets), exposant, module
+        8 | 10 => {
+            let (longueur, debut) = match cle {
+                [0, fort, faible, ..] => (u16::from_be_bytes([*fort, *faible]) as usize, 3),
+                [longueur, ..] => (*longueur as usize, 1),
+                [] => return false,
+            };
+            let Some((e, n)) = cle.get(debut..).filter(|reste| reste.len() > longueur).map(|reste| reste.split_at(longueur)) else {
+                return false;
+            };
+            let parametres = if algorithme == 8 {
+                &signature::RSA_PKCS1_1024_8192_SHA256_FOR_LEGACY_USE_ONLY
+            } else {
+                &signature::RSA_PKCS1_1024_8192_SHA512_FOR_LEGACY_USE_ONLY
+            };
+            RsaPublicKeyComponents { n, e }.verify(parametres, donnees, signature).is_ok()
+        }
+        // ECDSA : la clé est le point (x, y) sans le préfixe 0x04 du format non compressé
+        13 | 14 => {
+            let parametres = if algorithme == 13 { &signature::ECDSA_P256_SHA256_FIXED } else { &signature::ECDSA_P384_SHA384_FIXED };
+            let mut point = vec![0x04];
+            point.extend_from_slice(cle);
+            UnparsedPublicKey::new(parametres, point).verify(donnees, signature).is_ok()
+        }
+        15 => UnparsedPublicKey::new(&signature::ED25519, cle).verify(donnees, signature).is_ok(),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ring::signature::{Ed25519KeyPair, KeyPair};
+    use std::net::Ipv4Addr;
+
+    const MAINTENANT: u32 = 1_750_000_000;
+
+    /// Clé Ed25519 déterministe et son DNSKEY
+    fn cle(zone: &str, graine: u8, drapeaux: u16) -> (Ed25519KeyPair, Enregistrement) {
+        let paire = Ed25519KeyPair::from_seed_unchecked(&[graine; 32]).unwrap();
+        let publique = paire.public_key().as_ref().to_vec();
+        let rr = Enregistrement::new(zone, 3600, Donnees::DNSKEY { drapeaux, protocole: 3, algorithme: 15, cle: publique });
+        (paire, rr)
+    }
+
+    fn signer(paire: &Ed25519KeyPair, cle: &Enregistrement, rrset: &[Enregistrement]) -> Enregistrement {
+        let mut rrsig = Enregistrement::new(
+            &rrset[0].nom,
+            rrset[0].ttl,
+            Donnees::RRSIG {
+                type_couvert: rrset[0].type_rr,
+                algorithme: 15,
+                labels: rrset[0].nom.split('.').count() as u8,
+                ttl_original: rrset[0].ttl,
+                expiration: MAINTENANT + 86400,
+                inception: MAINTENANT - 86400,
+                identifiant_cle: identifiant_cle(&cle.rdata()),
+                signataire: cle.nom.clone(),
+                signature: Vec::new(),
+            },
+        );
+        let donnees = donnees_signees(&rrsig, &rrset.iter().collect::<Vec<_>>());
+        if let Donnees::RRSIG { signature, .. } = &mut rrsig.donnees {
+            *signature = paire.sign(&donnees).as_ref().to_vec();
+        }
+        rrsig
+    }
+
+    fn ds(cle: &Enregistrement) -> Enregistrement {
+        let empreinte = empreinte_ds(cle, 2).unwrap();
+        Enregistrement::new(
+            &cle.nom,
+            3600,
+            Donnees::DS { identifiant_cle: identifiant_cle(&cle.rdata()), algorithme: 15, type_empreinte: 2, empreinte },
+        )
+    }
+
+    #[test]
+    fn test_chaine_de_confiance() {
+        // "test" : ancre de confiance ; "exemple.test" : zone fille déléguée par un DS signé
+        let (ksk_test, dnskey_ksk_test) = cle("test", 1, 257);
+        let (zsk_test, dnskey_zsk_test) = cle("test", 2, 256);
+        let (ksk_fille, dnskey_fille) = cle("exemple.test", 3, 257);
+        let ancre = ancre_depuis_ds(&ds(&dnskey_ksk_test)).unwrap();
+
+        let cles_test = vec![dnskey_ksk_test.clone(), dnskey_zsk_test.clone()];
+        let ds_fille = vec![ds(&dnskey_fille)];
+        let cles_fille = vec![dnskey_fille.clone()];
+        let mut zones: HashMap<(String, TypeEnregistrement), Vec<Enregistrement>> = HashMap::new();
+        let mut publier = |rrset: Vec<Enregistrement>, signature: Enregistrement| {
+            let cle = (rrset[0].nom.clone(), rrset[0].type_rr);
+            zones.insert(cle, rrset.into_iter().chain([signature]).collect());
+        };
+        publier(cles_test.clone(), signer(&ksk_test, &dnskey_ksk_test, &cles_test));
+        publier(ds_fille.clone(), signer(&zsk_test, &dnskey_zsk_test, &ds_fille));
+        publier(cles_fille.clone(), signer(&ksk_fille, &dnskey_fille, &cles_fille));
+        let mut interroger = |nom: &str, type_rr| zones.get(&(nom.to_string(), type_rr)).cloned();
+
+        let a = vec![Enregistrement::new("www.exemple.test", 300, Donnees::A(Ipv4Addr::new(10, 0, 0, 1)))];
+        let reponse: Vec<Enregistrement> = a.iter().cloned().chain([signer(&ksk_fille, &dnskey_fille, &a)]).collect();
+
+        let mut validateur = Validateur::new(vec![ancre]);
+        assert_eq!(validateur.valider(&reponse, MAINTENANT, &mut interroger), Ok(()));
+        // Signature expirée, donnée modifiée, signature absente
+        assert!(validateur.valider(&reponse, MAINTENANT + 2 * 86400, &mut interroger).is_err());
+        let mut modifiee = reponse.clone();
+        modifiee[0].donnees = Donnees::A(Ipv4Addr::new(10, 6, 6, 6));
+        assert!(validateur.valider(&modifiee, MAINTENANT, &mut interroger).is_err());
+        assert!(validateur.valider(&a, MAINTENANT, &mut interroger).is_err());
+
+        // Sans ancre pour "test", rien n'est validé
+        let mut autre = Validateur::new(vec![ANCRES_RACINE[0].parse().unwrap()]);
+        assert!(autre.valider(&reponse, MAINTENANT, &mut interroger).is_err());
+        let mut aucune = Validateur::new(Vec::new());
+        assert!(aucune.valider(&reponse, MAINTENANT, &mut interroger).unwrap_err().contains("aucune ancre"));
+    }
+
+    #[test]
+    fn test_ancre_de_confiance() {
+        let ancre: AncreConfiance = ANCRES_RACINE[0].parse().unwrap();
+        assert_eq!(ancre.zone, "");
+        assert_eq!((ancre.identifiant_cle, ancre.algorithme, ancre.type_empreinte), (20326, 8, 2));
+        assert_eq!(ancre.empreinte.len(), 32);
+        assert!("esgi.fr 1 15 2 ABC".parse::<AncreConfiance>().is_err());
+        assert!("esgi.fr 1 15".parse::<AncreConfiance>().is_err());
+        assert_eq!(Securite::default().to_string(), "insecure");
+    }
+}
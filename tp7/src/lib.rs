@@ -0,0 +1,10 @@
+// src/lib.rs
+pub mod cache;
+pub mod chaos;
+pub mod client;
+pub mod dns;
+pub mod dnssec;
+pub mod resolveur;
+pub mod texte;
+pub mod transfert;
+pub mod zone;
@@ -1,33 +1,403 @@
-use std::collections::HashMap;
-use std::net::UdpSocket;
+use std::net::{Ipv4Addr, SocketAddr, TcpListener, UdpSocket};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use config_commun::{list, parse, Settings};
+use serde::Deserialize;
+use tracing::{info, warn};
+
+use tp7_dns::chaos::{self, Chaos, Perturbation};
+use tp7_dns::dns::{normaliser_nom, Donnees, Enregistrement, PaquetDns};
+use tp7_dns::dnssec::{AncreConfiance, ANCRES_RACINE};
+use tp7_dns::resolveur::{ConfigResolveur, Resolveur, INTERVALLE_PREFETCH};
+use tp7_dns::texte;
+use tp7_dns::transfert::{self, ResolveurPartage};
+use tp7_dns::zone::{self, Zone};
+
+/// TTL des enregistrements de la base locale
+const TTL_LOCAL: u32 = 3600;
+
+/// Intervalle de vérification des fichiers de zone et hosts
+const INTERVALLE_RECHARGEMENT: Duration = Duration::from_secs(2);
+
+const USAGE: &str = "Usage: serveur [--config fichier.toml] [--listen ip:port] [--upstream ip:port] [--forward suffixe=ip:port,...] [--timeout-ms N] [--negative-ttl N] [--cache-size N] [--prefetch K]
+               [--dnssec true|false] [--trust-anchor \"zone tag alg type empreinte\",...]
+               [--chaos POURCENT [--chaos-latency-ms N] [--chaos-effects latency,drop,malformed]]
+               [--zone fichier] [--hosts fichier] | [--secondary-of ip:port [--refresh-secs N]]";
+
+/// Configuration du serveur : fichier TOML (`--config` ou TP7_CONFIG), variables TP7_* puis options ;
+/// les clés du fichier portent le nom des options (`listen`, `timeout_ms`...)
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct Options {
+    #[serde(rename = "listen")]
+    ecoute: SocketAddr,
+    #[serde(rename = "upstream")]
+    amont: Option<SocketAddr>,
+    /// Délégations "suffixe=ip:port" : les noms sous ce suffixe vont à cet amont
+    #[serde(rename = "forward")]
+    delegations: Vec<String>,
+    #[serde(rename = "timeout_ms")]
+    delai_amont_ms: u64,
+    #[serde(rename = "negative_ttl")]
+    ttl_negatif: u32,
+    #[serde(rename = "cache_size")]
+    capacite_cache: usize,
+    /// Nombre de noms populaires rafraîchis avant expiration (0 : désactivé)
+    prefetch: usize,
+    #[serde(rename = "zone")]
+    fichier_zone: Option<PathBuf>,
+    #[serde(rename = "hosts")]
+    fichier_hosts: Option<PathBuf>,
+    #[serde(rename = "secondary_of")]
+    primaire: Option<SocketAddr>,
+    #[serde(rename = "refresh_secs")]
+    intervalle_secondaire_secs: u64,
+    /// Validation DNSSEC des réponses amont ; sans ancre configurée, celles de la racine
+    dnssec: bool,
+    #[serde(rename = "trust_anchors")]
+    ancres: Vec<String>,
+    /// Mode chaos : part des réponses UDP perturbées (0 : désactivé), latence et effets utilisés
+    #[serde(rename = "chaos")]
+    chaos_pourcentage: u8,
+    #[serde(rename = "chaos_latency_ms")]
+    chaos_latence_ms: u64,
+    #[serde(rename = "chaos_effects")]
+    chaos_effets: Vec<String>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        let resolveur = ConfigResolveur::default();
+        Self {
+            ecoute: "127.0.0.1:8053".parse().expect("adresse par défaut valide"),
+            amont: resolveur.amont,
+            delegations: Vec::new(),
+            delai_amont_ms: resolveur.delai_amont.as_millis() as u64,
+            ttl_negatif: resolveur.ttl_negatif,
+            capacite_cache: resolveur.capacite_cache,
+            prefetch: resolveur.prefetch,
+            fichier_zone: None,
+            fichier_hosts: None,
+            primaire: None,
+            intervalle_secondaire_secs: 10,
+            dnssec: resolveur.dnssec,
+            ancres: Vec::new(),
+            chaos_pourcentage: 0,
+            chaos_latence_ms: chaos::LATENCE_PAR_DEFAUT.as_millis() as u64,
+            chaos_effets: ["latency", "drop", "malformed"].map(String::from).to_vec(),
+        }
+    }
+}
+
+impl Options {
+    fn config_resolveur(&self) -> ConfigResolveur {
+        ConfigResolveur {
+            amont: self.amont,
+            delegations: self.delegations.iter().filter_map(|delegation| lire_delegation(delegation).ok()).collect(),
+            delai_amont: Duration::from_millis(self.delai_amont_ms),
+            ttl_negatif: self.ttl_negatif,
+            capacite_cache: self.capacite_cache,
+            prefetch: self.prefetch,
+            dnssec: self.dnssec,
+            ancres: self.ancres.iter().filter_map(|ancre| ancre.parse().ok()).collect(),
+        }
+    }
+
+    fn chaos(&self) -> Chaos {
+        let effets = self.chaos_effets.iter().filter_map(|effet| effet.parse().ok()).collect();
+        Chaos::new(self.chaos_pourcentage, Duration::from_millis(self.chaos_latence_ms), effets)
+    }
+
+    fn intervalle_secondaire(&self) -> Duration {
+        Duration::from_secs(self.intervalle_secondaire_secs.max(1))
+    }
+}
+
+impl Settings for Options {
+    const ENV_PREFIX: &'static str = "TP7_";
+
+    fn set(&mut self, cle: &str, valeur: &str) -> Result<(), String> {
+        match cle {
+            "listen" => self.ecoute = parse(cle, valeur)?,
+            "upstream" => self.amont = Some(parse(cle, valeur)?),
+            "forward" => self.delegations.extend(list(valeur)),
+            "timeout-ms" => self.delai_amont_ms = parse(cle, valeur)?,
+            "negative-ttl" => self.ttl_negatif = parse(cle, valeur)?,
+            "cache-size" => self.capacite_cache = parse(cle, valeur)?,
+            "prefetch" => self.prefetch = parse(cle, valeur)?,
+            "zone" => self.fichier_zone = Some(PathBuf::from(valeur)),
+            "hosts" => self.fichier_hosts = Some(PathBuf::from(valeur)),
+            "secondary-of" => self.primaire = Some(parse(cle, valeur)?),
+            "refresh-secs" => self.intervalle_secondaire_secs = parse(cle, valeur)?,
+            "dnssec" => self.dnssec = parse(cle, valeur)?,
+            "trust-anchor" => self.ancres.extend(list(valeur)),
+            "chaos" => self.chaos_pourcentage = parse(cle, valeur)?,
+            "chaos-latency-ms" => self.chaos_latence_ms = parse(cle, valeur)?,
+            "chaos-effects" => self.chaos_effets = list(valeur).collect(),
+            autre => return Err(format!("option inconnue: {}", autre)),
+        }
+        Ok(())
+    }
+
+    fn validate(&mut self) -> Result<(), String> {
+        if (self.fichier_zone.is_some() || self.fichier_hosts.is_some()) && self.primaire.is_some() {
+            return Err("--zone/--hosts et --secondary-of sont incompatibles : un secondaire reçoit sa zone du primaire".to_string());
+        }
+        for delegation in &self.delegations {
+            lire_delegation(delegation)?;
+        }
+        for ancre in &self.ancres {
+            ancre.parse::<AncreConfiance>()?;
+        }
+        if self.chaos_pourcentage > 100 {
+            return Err(format!("--chaos attend un pourcentage entre 0 et 100: {}", self.chaos_pourcentage));
+        }
+        for effet in &self.chaos_effets {
+            effet.parse::<chaos::Effet>()?;
+        }
+        if self.dnssec && self.ancres.is_empty() {
+            self.ancres = ANCRES_RACINE.iter().map(|ancre| ancre.to_string()).collect();
+        }
+        Ok(())
+    }
+}
+
+/// "corp.local=10.0.0.1:53" -> ("corp.local", 10.0.0.1:53) ; "." délègue tous les noms
+fn lire_delegation(texte: &str) -> Result<(String, SocketAddr), String> {
+    let (suffixe, amont) = texte
+        .split_once('=')
+        .ok_or_else(|| format!("délégation invalide (attendu: suffixe=ip:port): {}", texte))?;
+    Ok((normaliser_nom(suffixe.trim()), parse("forward", amont)?))
+}
 
 fn main() -> std::io::Result<()> {
-    // Associer un socket UDP à une adresse locale
-    let socket = UdpSocket::bind("127.0.0.1:8053")?;
-    println!("Serveur DNS démarré sur 127.0.0.1:8053");
+    let _journal = trace_commun::init_from_env("tp7-dns");
+    let options: Options = match config_commun::load() {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}", e);
+            eprintln!("{}", USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    // Associer un socket UDP à une adresse locale, et un listener TCP sur la même adresse
+    let socket = UdpSocket::bind(options.ecoute)?;
+    let listener = TcpListener::bind(options.ecoute)?;
+    info!("Serveur DNS démarré sur {} (UDP et TCP)", options.ecoute);
+    match options.amont {
+        Some(amont) => info!("Résolveur amont: {}", amont),
+        None => info!("Aucun résolveur amont : les noms inconnus répondent NXDOMAIN"),
+    }
+    for (suffixe, amont) in options.config_resolveur().delegations {
+        info!("Délégation: {} -> {}", suffixe, amont);
+    }
+    if options.dnssec {
+        info!("Validation DNSSEC des réponses amont ({} ancre(s) de confiance)", options.ancres.len());
+    }
+
+    // Base de données DNS : fichier de zone et/ou fichier hosts, ou base simulée par défaut
+    let sources = Sources { zone: options.fichier_zone.clone(), hosts: options.fichier_hosts.clone() };
+    let mut zone = Zone::new();
+    if !sources.est_vide() {
+        zone.remplacer(sources.charger().map_err(std::io::Error::other)?);
+    } else if options.primaire.is_none() {
+        for (nom, ip) in [
+            ("esgi.fr", Ipv4Addr::new(192, 168, 1, 42)),
+            ("yahoo.com", Ipv4Addr::new(93, 184, 216, 34)),
+            ("google.com", Ipv4Addr::new(8, 8, 8, 8)),
+        ] {
+            zone.ajouter(Enregistrement::new(nom, TTL_LOCAL, Donnees::A(ip)));
+        }
+        // Services du dépôt, découverts par les clients tp8 et tp9 (--discover)
+        zone.ajouter(Enregistrement::new("localhost", TTL_LOCAL, Donnees::A(Ipv4Addr::LOCALHOST)));
+        for (service, port) in [("_scp._tcp.local", 9999), ("_ws._tcp.local", 9001)] {
+            let srv = Donnees::SRV { priorite: 0, poids: 0, port, cible: "localhost".to_string() };
+            zone.ajouter(Enregistrement::new(service, TTL_LOCAL, srv));
+        }
+    }
+    info!("Zone locale: {} enregistrement(s), serial {}", zone.len(), zone.serial());
+
+    let resolveur: ResolveurPartage = Arc::new(Mutex::new(Resolveur::new(zone, options.config_resolveur())));
+
+    let partage = Arc::clone(&resolveur);
+    thread::spawn(move || transfert::servir_tcp(listener, partage));
 
-    // Base de données DNS simulée
-    let dns_records: HashMap<&str, &str> = HashMap::from([
-        ("esgi.fr", "192.168.1.42"),
-        ("yahoo.com", "93.184.216.34"),
-        ("google.com", "8.8.8.8"),
-    ]);
+    if let Some(primaire) = options.primaire {
+        info!("Mode secondaire : réplication depuis {}", primaire);
+        let partage = Arc::clone(&resolveur);
+        let intervalle = options.intervalle_secondaire();
+        thread::spawn(move || transfert::synchroniser(primaire, partage, intervalle));
+    }
+
+    if options.prefetch > 0 && resolveur.lock().unwrap().recursion_disponible() {
+        let partage = Arc::clone(&resolveur);
+        thread::spawn(move || prefetcher(partage));
+    }
+
+    if !sources.est_vide() {
+        let partage = Arc::clone(&resolveur);
+        thread::spawn(move || surveiller_sources(sources, partage));
+    }
+
+    let mut chaos = options.chaos();
+    if chaos.actif() {
+        warn!(
+            "Mode chaos : {}% des réponses UDP perturbées ({}, latence {} ms)",
+            options.chaos_pourcentage,
+            options.chaos_effets.join(", "),
+            options.chaos_latence_ms
+        );
+    }
 
     let mut buffer = [0u8; 1024];
 
     loop {
         // Réception de la requête
         let (taille, src) = socket.recv_from(&mut buffer)?;
-        let requete = String::from_utf8_lossy(&buffer[..taille]).to_string();
-        println!("Requête de {}: {}", src, requete);
 
-        // Traitement : résolution DNS
-        let reponse = match dns_records.get(requete.trim()) {
-            Some(ip) => ip.to_string(),
-            None => "Domaine inconnu".to_string(),
+        // Paquet DNS binaire ou requête texte historique
+        let reponse = match PaquetDns::decoder(&buffer[..taille]) {
+            Ok(requete) if requete.est_requete_standard() => {
+                let (mut reponse, securite) = resolveur.lock().unwrap().repondre_avec_securite(&requete);
+                let question = &requete.questions[0];
+                info!("Requête DNS de {}: {} {} ({})", src, question.nom, question.type_rr, securite);
+
+                // Trop gros pour le tampon annoncé : réponse tronquée, le client réessaiera en TCP
+                if reponse.tronquer(requete.taille_udp_max()) {
+                    info!("Réponse tronquée pour {} (limite {} octets)", src, requete.taille_udp_max());
+                }
+                reponse.encoder()
+            }
+            _ => repondre_texte(&resolveur, &buffer[..taille], src),
         };
 
-        // Envoi de la réponse
-        socket.send_to(reponse.as_bytes(), &src)?;
+        // Envoi de la réponse, sauf si le mode chaos en décide autrement
+        match chaos.tirer() {
+            Perturbation::Aucune => {
+                socket.send_to(&reponse, src)?;
+            }
+            Perturbation::Perte => info!("Chaos : réponse à {} abandonnée", src),
+            Perturbation::Corruption => {
+                let mut reponse = reponse;
+                chaos.corrompre(&mut reponse);
+                info!("Chaos : réponse à {} tronquée à {} octets", src, reponse.len());
+                socket.send_to(&reponse, src)?;
+            }
+            // Le retard ne doit pas bloquer les autres clients : l'envoi se fait depuis un thread
+            Perturbation::Retard(delai) => {
+                info!("Chaos : réponse à {} retardée de {} ms", src, delai.as_millis());
+                let socket = socket.try_clone()?;
+                thread::spawn(move || {
+                    thread::sleep(delai);
+                    if let Err(e) = socket.send_to(&reponse, src) {
+                        warn!("Réponse retardée à {} non envoyée: {}", src, e);
+                    }
+                });
+            }
+        }
+    }
+}
+
+fn repondre_texte(resolveur: &ResolveurPartage, octets: &[u8], src: SocketAddr) -> Vec<u8> {
+    let requete = String::from_utf8_lossy(octets).to_string();
+
+    // Compteurs du résolveur, demandés en texte
+    if requete.trim().eq_ignore_ascii_case("STATS") {
+        info!("Requête de {}: STATS", src);
+        return resolveur.lock().unwrap().statistiques().to_string().into_bytes();
+    }
+
+    // Traitement : résolution DNS
+    let reponse = match texte::analyser(&requete) {
+        Ok(question) => {
+            let resolution = resolveur.lock().unwrap().resolution(&question.nom, question.type_rr);
+            info!("Requête de {}: {} ({}, {})", src, requete, resolution.source, resolution.securite);
+            match question.json {
+                true => texte::formater_json(&question.nom, question.type_rr, &resolution),
+                false => texte::formater_resultat(&resolution.resultat),
+            }
+        }
+        Err(e) => {
+            info!("Requête de {}: {}", src, requete);
+            format!("FORMERR: {}", e)
+        }
+    };
+    reponse.into_bytes()
+}
+
+/// Fichiers qui alimentent la zone locale
+struct Sources {
+    zone: Option<PathBuf>,
+    hosts: Option<PathBuf>,
+}
+
+impl Sources {
+    fn est_vide(&self) -> bool {
+        self.zone.is_none() && self.hosts.is_none()
+    }
+
+    /// Lire le fichier de zone puis le fichier hosts, en une seule liste d'enregistrements
+    fn charger(&self) -> Result<Vec<Enregistrement>, String> {
+        let mut enregistrements = Vec::new();
+        if let Some(chemin) = &self.zone {
+            enregistrements.extend(zone::lire_fichier_zone(chemin)?);
+        }
+        if let Some(chemin) = &self.hosts {
+            for rr in zone::lire_fichier_hosts(chemin)? {
+                if !enregistrements.contains(&rr) {
+                    enregistrements.push(rr);
+                }
+            }
+        }
+        Ok(enregistrements)
+    }
+
+    fn dates_modification(&self) -> Vec<Option<SystemTime>> {
+        [&self.zone, &self.hosts]
+            .into_iter()
+            .flatten()
+            .map(|chemin| std::fs::metadata(chemin).and_then(|m| m.modified()).ok())
+            .collect()
+    }
+}
+
+/// Recharger la zone à chaque modification d'un des fichiers ; le serial avance si le contenu change
+fn surveiller_sources(sources: Sources, resolveur: ResolveurPartage) {
+    let mut dernieres = sources.dates_modification();
+
+    loop {
+        thread::sleep(INTERVALLE_RECHARGEMENT);
+        let actuelles = sources.dates_modification();
+        if actuelles == dernieres {
+            continue;
+        }
+        dernieres = actuelles;
+
+        match sources.charger() {
+            Ok(enregistrements) => {
+                let mut resolveur = resolveur.lock().unwrap();
+                if resolveur.zone_mut().remplacer(enregistrements) {
+                    info!("Zone rechargée (serial {})", resolveur.zone().serial());
+                }
+            }
+            Err(e) => warn!("Rechargement ignoré: {}", e),
+        }
+    }
+}
+
+/// Rafraîchir régulièrement les réponses populaires avant qu'elles n'expirent du cache
+fn prefetcher(resolveur: ResolveurPartage) {
+    loop {
+        thread::sleep(INTERVALLE_PREFETCH);
+        // Les questions à l'amont se posent sans le verrou : les clients restent servis pendant ce temps
+        let Some(mut prefetch) = resolveur.lock().unwrap().preparer_prefetch() else {
+            continue;
+        };
+        prefetch.interroger();
+        resolveur.lock().unwrap().terminer_prefetch(prefetch);
     }
 }
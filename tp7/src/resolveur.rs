@@ -0,0 +1,713 @@
+// Chemin de résolution : zone locale, puis cache, puis résolveur amont (dont les réponses peuvent
+// être validées par DNSSEC)
+
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::net::{SocketAddr, UdpSocket};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use tracing::{info, warn};
+
+use crate::cache::{Cache, StatistiquesCache, CAPACITE_PAR_DEFAUT};
+use crate::dns::{
+    interroger_tcp, normaliser_nom, CodeReponse, Donnees, Enregistrement, PaquetDns, TypeEnregistrement,
+    TAILLE_EDNS_ANNONCEE,
+};
+use crate::dnssec::{AncreConfiance, Securite, Validateur};
+use crate::zone::Zone;
+
+/// Durée maximale (en secondes) pendant laquelle une réponse négative reste en cache
+pub const TTL_NEGATIF_PAR_DEFAUT: u32 = 60;
+
+/// Délai d'attente par défaut du résolveur amont
+pub const DELAI_AMONT_PAR_DEFAUT: Duration = Duration::from_secs(2);
+
+/// Nombre de noms les plus demandés rafraîchis avant expiration
+pub const PREFETCH_PAR_DEFAUT: usize = 10;
+
+/// Intervalle entre deux passes de prefetch ; la marge avant expiration doit le dépasser
+pub const INTERVALLE_PREFETCH: Duration = Duration::from_secs(1);
+
+/// Intervalle au bout duquel les compteurs de popularité sont divisés par deux
+const INTERVALLE_OUBLI: Duration = Duration::from_secs(60);
+
+/// Issue d'une résolution
+#[derive(Debug, Clone, PartialEq)]
+pub enum Resultat {
+    /// NOERROR avec au moins un enregistrement
+    Reponses(Vec<Enregistrement>),
+    /// NOERROR sans donnée : le nom existe mais pas pour ce type
+    SansDonnees,
+    /// Le nom n'existe pas
+    NxDomain,
+    /// Le résolveur amont n'a pas répondu à temps ou a échoué
+    ServFail,
+}
+
+/// Provenance d'une réponse
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Source {
+    /// Zone locale, ou NXDOMAIN faute de résolveur amont
+    Locale,
+    Cache,
+    Amont,
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Source::Locale => write!(f, "local"),
+            Source::Cache => write!(f, "cache"),
+            Source::Amont => write!(f, "upstream"),
+        }
+    }
+}
+
+/// Résultat d'une résolution, sa provenance et son statut DNSSEC
+#[derive(Debug, Clone, PartialEq)]
+pub struct Resolution {
+    pub resultat: Resultat,
+    pub source: Source,
+    pub securite: Securite,
+}
+
+/// Compteurs du résolveur depuis le démarrage
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Statistiques {
+    pub cache: StatistiquesCache,
+    /// Réponses amont écartées : source, identifiant ou question différents de la requête en cours
+    pub reponses_rejetees: u64,
+    /// Enregistrements de réponse amont sans rapport avec la question, non mis en cache
+    pub enregistrements_ecartes: u64,
+    /// Réponses populaires redemandées à l'amont avant leur expiration
+    pub prefetchs: u64,
+}
+
+impl fmt::Display for Statistiques {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}\namont: {} réponses rejetées, {} enregistrements écartés\nprefetch: {} rafraîchissements",
+            self.cache, self.reponses_rejetees, self.enregistrements_ecartes, self.prefetchs
+        )
+    }
+}
+
+impl Resultat {
+    /// Code de réponse DNS correspondant
+    pub fn code_reponse(&self) -> CodeReponse {
+        match self {
+            Resultat::Reponses(_) | Resultat::SansDonnees => CodeReponse::NoError,
+            Resultat::NxDomain => CodeReponse::NxDomain,
+            Resultat::ServFail => CodeReponse::ServFail,
+        }
+    }
+}
+
+/// Paramètres du résolveur
+#[derive(Debug, Clone)]
+pub struct ConfigResolveur {
+    /// Résolveur amont pour les noms absents de la zone (aucun : NXDOMAIN)
+    pub amont: Option<SocketAddr>,
+    /// Résolveurs amont propres à certains domaines : (suffixe, amont), le suffixe le plus long l'emporte
+    pub delegations: Vec<(String, SocketAddr)>,
+    /// Au-delà de ce délai sans réponse, la requête échoue en SERVFAIL
+    pub delai_amont: Duration,
+    /// Plafond du TTL des réponses négatives mises en cache
+    pub ttl_negatif: u32,
+    /// Nombre maximal d'entrées du cache
+    pub capacite_cache: usize,
+    /// Nombre de noms populaires rafraîchis avant expiration (0 : pas de prefetch)
+    pub prefetch: usize,
+    /// Demander les signatures à l'amont (bit DO) et valider ses réponses
+    pub dnssec: bool,
+    /// Ancres de confiance de la validation DNSSEC
+    pub ancres: Vec<AncreConfiance>,
+}
+
+impl Default for ConfigResolveur {
+    fn default() -> Self {
+        Self {
+            amont: None,
+            delegations: Vec::new(),
+            delai_amont: DELAI_AMONT_PAR_DEFAUT,
+            ttl_negatif: TTL_NEGATIF_PAR_DEFAUT,
+            capacite_cache: CAPACITE_PAR_DEFAUT,
+            prefetch: PREFETCH_PAR_DEFAUT,
+            dnssec: false,
+            ancres: Vec::new(),
+        }
+    }
+}
+
+pub struct Resolveur {
+    zone: Zone,
+    cache: Cache,
+    amont: ClientAmont,
+    /// Demandes par (nom, type) hors zone locale, divisées par deux à chaque INTERVALLE_OUBLI ;
+    /// tenu seulement avec le prefetch, et borné par la capacité du cache
+    popularite: HashMap<(String, TypeEnregistrement), u64>,
+    dernier_oubli: Instant,
+    prefetchs: u64,
+}
+
+/// Questions au résolveur amont, avec la validation DNSSEC et les compteurs qui s'y rapportent
+#[derive(Clone)]
+struct ClientAmont {
+    config: ConfigResolveur,
+    validateur: Validateur,
+    prochain_id: u16,
+    reponses_rejetees: u64,
+    enregistrements_ecartes: u64,
+}
+
+/// Passe de prefetch : les noms sont choisis sous le verrou du résolveur, l'amont est interrogé
+/// sans lui (avec une copie du client amont), puis les réponses sont remises en cache sous le verrou
+pub struct Prefetch {
+    amont: ClientAmont,
+    noms: Vec<(String, TypeEnregistrement)>,
+    reponses: Vec<(String, TypeEnregistrement, Resultat, Securite, u32)>,
+}
+
+impl Prefetch {
+    /// Interroger l'amont pour chaque nom choisi ; ne tient pas le verrou du résolveur
+    pub fn interroger(&mut self) {
+        for (nom, type_rr) in std::mem::take(&mut self.noms) {
+            info!("Prefetch de {} {}", nom, type_rr);
+            if let Some((resultat, securite, ttl)) = self.amont.interroger(&nom, type_rr) {
+                self.reponses.push((nom, type_rr, resultat, securite, ttl));
+            }
+        }
+    }
+}
+
+impl Resolveur {
+    pub fn new(zone: Zone, config: ConfigResolveur) -> Self {
+        // Identifiants de requête amont peu prévisibles sans dépendance externe
+        let graine = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.subsec_nanos())
+            .unwrap_or(0);
+        let validateur = Validateur::new(config.ancres.clone());
+        Self {
+            zone,
+            cache: Cache::new(config.capacite_cache),
+            amont: ClientAmont {
+                config,
+                validateur,
+                prochain_id: graine as u16,
+                reponses_rejetees: 0,
+                enregistrements_ecartes: 0,
+            },
+            popularite: HashMap::new(),
+            dernier_oubli: Instant::now(),
+            prefetchs: 0,
+        }
+    }
+
+    /// Vrai si les noms inconnus sont relayés vers un résolveur amont
+    pub fn recursion_disponible(&self) -> bool {
+        self.amont.config.amont.is_some() || !self.amont.config.delegations.is_empty()
+    }
+
+    /// Résolveur amont d'un nom : celui de la délégation au suffixe le plus long, l'amont par défaut sinon
+    pub fn amont_pour(&self, nom: &str) -> Option<SocketAddr> {
+        self.amont.amont_pour(nom)
+    }
+
+    pub fn statistiques(&self) -> Statistiques {
+        Statistiques {
+            cache: self.cache.statistiques(),
+            reponses_rejetees: self.amont.reponses_rejetees,
+            enregistrements_ecartes: self.amont.enregistrements_ecartes,
+            prefetchs: self.prefetchs,
+        }
+    }
+
+    pub fn zone(&self) -> &Zone {
+        &self.zone
+    }
+
+    pub fn zone_mut(&mut self) -> &mut Zone {
+        &mut self.zone
+    }
+
+    /// Construire la réponse complète à une requête standard
+    pub fn repondre(&mut self, requete: &PaquetDns) -> PaquetDns {
+        self.repondre_avec_securite(requete).0
+    }
+
+    /// Comme `repondre`, avec le statut DNSSEC de la réponse pour le journal
+    pub fn repondre_avec_securite(&mut self, requete: &PaquetDns) -> (PaquetDns, Securite) {
+        let question = &requete.questions[0];
+        let Resolution { resultat, securite, .. } = self.resolution(&question.nom, question.type_rr);
+
+        let mut reponse = PaquetDns::reponse_a(requete, resultat.code_reponse());
+        reponse.en_tete.ra = self.recursion_disponible();
+        if let Resultat::Reponses(enregistrements) = resultat {
+            reponse.reponses = enregistrements;
+        }
+        // On ne répond en EDNS0 qu'aux clients qui l'ont eux-mêmes utilisé
+        if requete.taille_edns().is_some() {
+            reponse.ajouter_edns(TAILLE_EDNS_ANNONCEE);
+        }
+        (reponse, securite)
+    }
+
+    pub fn resoudre(&mut self, nom: &str, type_rr: TypeEnregistrement) -> Resultat {
+        self.resolution(nom, type_rr).resultat
+    }
+
+    /// Résoudre un nom ; seules les réponses amont validées sont marquées « validated »
+    pub fn resolution(&mut self, nom: &str, type_rr: TypeEnregistrement) -> Resolution {
+        let nom = normaliser_nom(nom);
+
+        if let Some(resultat) = self.zone.chercher(&nom, type_rr) {
+            return Resolution { resultat, source: Source::Locale, securite: Securite::NonSecurisee };
+        }
+
+        self.compter_demande(&nom, type_rr);
+        if let Some((resultat, securite)) = self.cache.obtenir(&nom, type_rr) {
+            return Resolution { resultat, source: Source::Cache, securite };
+        }
+
+        let Some((resultat, securite, ttl)) = self.amont.interroger(&nom, type_rr) else {
+            return Resolution { resultat: Resultat::NxDomain, source: Source::Locale, securite: Securite::NonSecurisee };
+        };
+        self.mettre_en_cache(&nom, type_rr, &resultat, securite, ttl);
+        Resolution { resultat, source: Source::Amont, securite }
+    }
+
+    /// Compter une demande pour le prefetch. Sans prefetch rien n'est compté ; une fois la table
+    /// aussi grande que le cache, les nouveaux noms attendent que l'oubli fasse de la place
+    fn compter_demande(&mut self, nom: &str, type_rr: TypeEnregistrement) {
+        if self.amont.config.prefetch == 0 || !self.recursion_disponible() {
+            return;
+        }
+        let cle = (nom.to_string(), type_rr);
+        if let Some(demandes) = self.popularite.get_mut(&cle) {
+            *demandes += 1;
+        } else if self.popularite.len() < self.amont.config.capacite_cache {
+            self.popularite.insert(cle, 1);
+        }
+    }
+
+    /// Choisir, parmi les `prefetch` noms les plus demandés, ceux dont la réponse va expirer
+    /// (moins de 10 % de leur TTL, ou de deux passes, restant) ; None s'il n'y a rien à rafraîchir
+    pub fn preparer_prefetch(&mut self) -> Option<Prefetch> {
+        if self.dernier_oubli.elapsed() >= INTERVALLE_OUBLI {
+            self.dernier_oubli = Instant::now();
+            // Les noms sortis du cache n'ont plus rien à rafraîchir
+            let cache = &self.cache;
+            self.popularite.retain(|(nom, type_rr), demandes| {
+                *demandes /= 2;
+                *demandes > 0 && cache.restant(nom, *type_rr).is_some()
+            });
+        }
+
+        // Classement des `prefetch` premiers seulement : plus demandé d'abord, puis par nom
+        let k = self.amont.config.prefetch;
+        let mut populaires: Vec<(&(String, TypeEnregistrement), u64)> = Vec::with_capacity(k + 1);
+        for (cle, &demandes) in &self.popularite {
+            let rang = populaires.partition_point(|(autre, n)| *n > demandes || (*n == demandes && autre.0 < cle.0));
+            if rang < k {
+                populaires.insert(rang, (cle, demandes));
+                populaires.truncate(k);
+            }
+        }
+        let noms: Vec<(String, TypeEnregistrement)> = populaires
+            .into_iter()
+            .map(|(cle, _)| cle.clone())
+            .filter(|(nom, type_rr)| match self.cache.restant(nom, *type_rr) {
+                Some((restant, ttl)) => restant < Duration::from_secs(ttl as u64 / 10).max(INTERVALLE_PREFETCH * 2),
+                None => false,
+            })
+            .collect();
+
+        if noms.is_empty() {
+            return None;
+        }
+        Some(Prefetch { amont: self.amont.copie(), noms, reponses: Vec::new() })
+    }
+
+    /// Mettre en cache les réponses d'une passe de prefetch ; renvoie le nombre de noms rafraîchis
+    pub fn terminer_prefetch(&mut self, prefetch: Prefetch) -> usize {
+        self.amont.reponses_rejetees += prefetch.amont.reponses_rejetees;
+        self.amont.enregistrements_ecartes += prefetch.amont.enregistrements_ecartes;
+        for (nom, type_rr, resultat, securite, ttl) in &prefetch.reponses {
+            self.mettre_en_cache(nom, *type_rr, resultat, *securite, *ttl);
+        }
+        self.prefetchs += prefetch.reponses.len() as u64;
+        prefetch.reponses.len()
+    }
+
+    fn mettre_en_cache(&mut self, nom: &str, type_rr: TypeEnregistrement, resultat: &Resultat, securite: Securite, ttl: u32) {
+        // Les SERVFAIL ne sont pas mis en cache : l'amont peut revenir à tout moment
+        if *resultat != Resultat::ServFail {
+            self.cache.inserer(nom, type_rr, resultat.clone(), securite, ttl);
+        }
+    }
+}
+
+impl ClientAmont {
+    /// Voir `Resolveur::amont_pour`
+    fn amont_pour(&self, nom: &str) -> Option<SocketAddr> {
+        self.config
+            .delegations
+            .iter()
+            .filter(|(suffixe, _)| suffixe.is_empty() || nom == suffixe || nom.ends_with(&format!(".{}", suffixe)))
+            .max_by_key(|(suffixe, _)| suffixe.len())
+            .map(|(_, amont)| *amont)
+            .or(self.config.amont)
+    }
+
+    /// Copie aux compteurs nuls et aux identifiants de requête distincts, pour interroger l'amont
+    /// sans tenir le résolveur ; ses compteurs sont ensuite ajoutés à ceux de l'original
+    fn copie(&mut self) -> Self {
+        let prochain_id = self.nouvel_id().rotate_left(8);
+        Self { prochain_id, reponses_rejetees: 0, enregistrements_ecartes: 0, ..self.clone() }
+    }
+
+    /// Poser la question à l'amont (nom déjà normalisé) : résultat, statut DNSSEC et durée de mise
+    /// en cache ; None si aucun amont ne couvre ce nom
+    fn interroger(&mut self, nom: &str, type_rr: TypeEnregistrement) -> Option<(Resultat, Securite, u32)> {
+        let amont = self.amont_pour(nom)?;
+        Some(match self.interroger_amont(amont, nom, type_rr) {
+            Ok(reponse) => {
+                let securite = self.valider(nom, type_rr, &reponse);
+                let (resultat, ttl) = self.interpreter_reponse(nom, type_rr, &reponse);
+                (resultat, securite, ttl)
+            }
+            Err(e) => {
+                warn!("Résolveur amont {} injoignable pour {}: {}", amont, nom, e);
+                (Resultat::ServFail, Securite::NonSecurisee, 0)
+            }
+        })
+    }
+
+    /// Statut DNSSEC d'une réponse amont ; les clés et DS nécessaires sont demandés à l'amont de leur zone.
+    /// Un échec n'empêche pas de répondre : la réponse est seulement marquée « insecure »
+    fn valider(&mut self, nom: &str, type_rr: TypeEnregistrement, reponse: &PaquetDns) -> Securite {
+        if !self.config.dnssec || reponse.en_tete.rcode != CodeReponse::NoError || reponse.reponses.is_empty() {
+            return Securite::NonSecurisee;
+        }
+        let maintenant = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as u32).unwrap_or(0);
+
+        // Le validateur est sorti du client le temps que ses questions passent par `interroger_amont`
+        let mut validateur = std::mem::take(&mut self.validateur);
+        let verdict = validateur.valider(&reponse.reponses, maintenant, &mut |zone, type_rr| {
+            let amont = self.amont_pour(zone)?;
+            let reponse = self.interroger_amont(amont, zone, type_rr).ok()?;
+            (reponse.en_tete.rcode == CodeReponse::NoError).then_some(reponse.reponses)
+        });
+        self.validateur = validateur;
+
+        match verdict {
+            Ok(()) => Securite::Validee,
+            Err(raison) => {
+                info!("DNSSEC: {} {} non validé: {}", nom, type_rr, raison);
+                Securite::NonSecurisee
+            }
+        }
+    }
+
+    fn nouvel_id(&mut self) -> u16 {
+        self.prochain_id = self.prochain_id.wrapping_mul(25173).wrapping_add(13849);
+        self.prochain_id
+    }
+
+    /// Envoyer la question à l'amont et attendre la réponse de cet amont portant le même identifiant
+    /// et la même question ; les autres sont ignorées (tentatives d'empoisonnement du cache).
+    /// Une réponse tronquée (TC) est redemandée en TCP
+    fn interroger_amont(
+        &mut self,
+        amont: SocketAddr,
+        nom: &str,
+        type_rr: TypeEnregistrement,
+    ) -> io::Result<PaquetDns> {
+        let id = self.nouvel_id();
+        let mut requete = PaquetDns::requete(id, nom, type_rr);
+        requete.ajouter_edns(TAILLE_EDNS_ANNONCEE);
+        if self.config.dnssec {
+            requete.demander_dnssec();
+        }
+
+        let locale = if amont.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" };
+        let socket = UdpSocket::bind(locale)?;
+        socket.send_to(&requete.encoder(), amont)?;
+
+        let echeance = Instant::now() + self.config.delai_amont;
+        let mut tampon = vec![0u8; TAILLE_EDNS_ANNONCEE as usize];
+        loop {
+            let restant = echeance.saturating_duration_since(Instant::now());
+            if restant.is_zero() {
+                return Err(io::Error::new(io::ErrorKind::TimedOut, "délai amont dépassé"));
+            }
+            socket.set_read_timeout(Some(restant))?;
+
+            let (taille, source) = socket.recv_from(&mut tampon)?;
+            if source != amont {
+                self.rejeter(&format!("réponse de {} au lieu de {}", source, amont));
+                continue;
+            }
+            match PaquetDns::decoder(&tampon[..taille]) {
+                Ok(reponse) if reponse.en_tete.qr && reponse.en_tete.id == id && meme_question(&requete, &reponse) => {
+                    if reponse.en_tete.tc {
+                        let reponse = interroger_tcp(amont, &requete, echeance)?;
+                        if !meme_question(&requete, &reponse) {
+                            self.rejeter(&format!("réponse TCP de {} pour une autre question", amont));
+                            return Err(io::Error::new(io::ErrorKind::InvalidData, "réponse TCP pour une autre question"));
+                        }
+                        return Ok(reponse);
+                    }
+                    return Ok(reponse);
+                }
+                Ok(reponse) if reponse.en_tete.qr => {
+                    self.rejeter(&format!("réponse de {} (id {}) ne correspondant pas à la requête {} {}", amont, reponse.en_tete.id, nom, type_rr));
+                }
+                // Illisible : on continue d'attendre la bonne
+                _ => continue,
+            }
+        }
+    }
+
+    fn rejeter(&mut self, raison: &str) {
+        self.reponses_rejetees += 1;
+        warn!("Réponse amont rejetée: {}", raison);
+    }
+
+    /// Traduire la réponse amont en résultat et en durée de mise en cache. Seuls les enregistrements
+    /// du nom demandé et des cibles de ses CNAME sont gardés ; les signatures, utiles seulement
+    /// à la validation, ne le sont que si elles ont été demandées
+    fn interpreter_reponse(&mut self, nom: &str, type_rr: TypeEnregistrement, reponse: &PaquetDns) -> (Resultat, u32) {
+        let mut noms = vec![nom.to_string()];
+        let mut reponses = Vec::new();
+        for rr in &reponse.reponses {
+            if !noms.contains(&normaliser_nom(&rr.nom)) {
+                self.enregistrements_ecartes += 1;
+                warn!("Enregistrement hors sujet écarté pour {}: {} {}", nom, rr.nom, rr.type_rr);
+                continue;
+            }
+            if let Donnees::CNAME(cible) = &rr.donnees {
+                noms.push(normaliser_nom(cible));
+            }
+            if rr.type_rr != TypeEnregistrement::RRSIG || type_rr == TypeEnregistrement::RRSIG {
+                reponses.push(rr.clone());
+            }
+        }
+        match reponse.en_tete.rcode {
+            CodeReponse::NoError if !reponses.is_empty() => {
+                let ttl = reponses.iter().map(|rr| rr.ttl).min().unwrap_or(0);
+                (Resultat::Reponses(reponses), ttl)
+            }
+            CodeReponse::NoError => (Resultat::SansDonnees, self.ttl_negatif(reponse)),
+            CodeReponse::NxDomain => (Resultat::NxDomain, self.ttl_negatif(reponse)),
+            _ => (Resultat::ServFail, 0),
+        }
+    }
+
+    /// TTL négatif (RFC 2308) : minimum du SOA d'autorité, plafonné par la configuration
+    fn ttl_negatif(&self, reponse: &PaquetDns) -> u32 {
+        reponse
+            .autorite
+            .iter()
+            .find_map(|rr| match rr.donnees {
+                Donnees::SOA { minimum, .. } => Some(rr.ttl.min(minimum)),
+                _ => None,
+            })
+            .unwrap_or(self.config.ttl_negatif)
+            .min(self.config.ttl_negatif)
+    }
+}
+
+/// Vrai si la réponse porte sur la question de la requête (nom sans distinction de casse, type)
+fn meme_question(requete: &PaquetDns, reponse: &PaquetDns) -> bool {
+    match (requete.questions.first(), reponse.questions.as_slice()) {
+        (Some(question), [retour]) => {
+            normaliser_nom(&question.nom) == normaliser_nom(&retour.nom) && question.type_rr == retour.type_rr
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    fn zone_de_test() -> Zone {
+        let mut zone = Zone::new();
+        zone.ajouter(Enregistrement::new("esgi.fr", 3600, Donnees::A(Ipv4Addr::new(192, 168, 1, 42))));
+        zone
+    }
+
+    #[test]
+    fn test_nxdomain_et_sans_donnees() {
+        let mut resolveur = Resolveur::new(zone_de_test(), ConfigResolveur::default());
+
+        assert!(matches!(resolveur.resoudre("ESGI.fr.", TypeEnregistrement::A), Resultat::Reponses(_)));
+        assert_eq!(resolveur.resoudre("esgi.fr", TypeEnregistrement::AAAA), Resultat::SansDonnees);
+        assert_eq!(resolveur.resoudre("inconnu.fr", TypeEnregistrement::A), Resultat::NxDomain);
+    }
+
+    #[test]
+    fn test_servfail_sur_amont_muet() {
+        // Un socket qui ne répond jamais simule un amont en panne
+        let muet = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let config = ConfigResolveur {
+            amont: Some(muet.local_addr().unwrap()),
+            delai_amont: Duration::from_millis(100),
+            ..ConfigResolveur::default()
+        };
+        let mut resolveur = Resolveur::new(zone_de_test(), config);
+
+        assert_eq!(resolveur.resoudre("ailleurs.org", TypeEnregistrement::A), Resultat::ServFail);
+        assert!(resolveur.cache.is_empty(), "un SERVFAIL ne doit pas être mis en cache");
+    }
+
+    #[test]
+    fn test_reponse_negative_mise_en_cache() {
+        let amont = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let adresse_amont = amont.local_addr().unwrap();
+        let serveur = std::thread::spawn(move || {
+            let mut tampon = [0u8; 512];
+            let (taille, client) = amont.recv_from(&mut tampon).unwrap();
+            let requete = PaquetDns::decoder(&tampon[..taille]).unwrap();
+            // Réponses usurpées d'abord : mauvais identifiant, puis mauvaise question
+            let mut fausse = PaquetDns::reponse_a(&requete, CodeReponse::NoError);
+            fausse.en_tete.id = requete.en_tete.id.wrapping_add(1);
+            amont.send_to(&fausse.encoder(), client).unwrap();
+            let mut fausse = PaquetDns::requete(requete.en_tete.id, "autre.org", TypeEnregistrement::A);
+            fausse.en_tete.qr = true;
+            amont.send_to(&fausse.encoder(), client).unwrap();
+            let reponse = PaquetDns::reponse_a(&requete, CodeReponse::NxDomain);
+            amont.send_to(&reponse.encoder(), client).unwrap();
+        });
+
+        let config = ConfigResolveur { amont: Some(adresse_amont), ..ConfigResolveur::default() };
+        let mut resolveur = Resolveur::new(zone_de_test(), config);
+        assert_eq!(resolveur.resoudre("absent.org", TypeEnregistrement::A), Resultat::NxDomain);
+        serveur.join().unwrap();
+
+        // Deuxième question servie par le cache négatif, l'amont ne répond plus
+        assert_eq!(resolveur.resoudre("absent.org", TypeEnregistrement::A), Resultat::NxDomain);
+        assert_eq!(resolveur.cache.len(), 1);
+        assert_eq!(resolveur.statistiques().reponses_rejetees, 2);
+    }
+
+    #[test]
+    fn test_delegations() {
+        let config = ConfigResolveur {
+            amont: Some("192.0.2.1:53".parse().unwrap()),
+            delegations: vec![
+                ("corp.local".to_string(), "10.0.0.1:53".parse().unwrap()),
+                ("lab.corp.local".to_string(), "10.0.0.2:53".parse().unwrap()),
+            ],
+            ..ConfigResolveur::default()
+        };
+        let resolveur = Resolveur::new(zone_de_test(), config);
+
+        assert_eq!(resolveur.amont_pour("corp.local"), Some("10.0.0.1:53".parse().unwrap()));
+        assert_eq!(resolveur.amont_pour("intranet.corp.local"), Some("10.0.0.1:53".parse().unwrap()));
+        assert_eq!(resolveur.amont_pour("a.lab.corp.local"), Some("10.0.0.2:53".parse().unwrap()));
+        assert_eq!(resolveur.amont_pour("moncorp.local"), Some("192.0.2.1:53".parse().unwrap()));
+        assert_eq!(resolveur.amont_pour("esgi.fr"), Some("192.0.2.1:53".parse().unwrap()));
+
+        // Sans amont par défaut, seuls les domaines délégués sont relayés
+        let config = ConfigResolveur {
+            delegations: vec![("corp.local".to_string(), "10.0.0.1:53".parse().unwrap())],
+            ..ConfigResolveur::default()
+        };
+        let mut resolveur = Resolveur::new(zone_de_test(), config);
+        assert!(resolveur.recursion_disponible());
+        assert_eq!(resolveur.amont_pour("esgi.org"), None);
+        assert_eq!(resolveur.resoudre("inconnu.fr", TypeEnregistrement::A), Resultat::NxDomain);
+    }
+
+    #[test]
+    fn test_dnssec_sans_ancre() {
+        // L'amont reçoit le bit DO et répond sans signature : la réponse reste « insecure »
+        let amont = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let adresse_amont = amont.local_addr().unwrap();
+        let serveur = std::thread::spawn(move || {
+            let mut tampon = [0u8; 512];
+            let (taille, client) = amont.recv_from(&mut tampon).unwrap();
+            let requete = PaquetDns::decoder(&tampon[..taille]).unwrap();
+            let mut reponse = PaquetDns::reponse_a(&requete, CodeReponse::NoError);
+            reponse.reponses.push(Enregistrement::new("signe.org", 300, Donnees::A(Ipv4Addr::new(10, 0, 0, 1))));
+            amont.send_to(&reponse.encoder(), client).unwrap();
+            requete.dnssec_demande()
+        });
+
+        let config = ConfigResolveur { amont: Some(adresse_amont), dnssec: true, ..ConfigResolveur::default() };
+        let mut resolveur = Resolveur::new(zone_de_test(), config);
+        let resolution = resolveur.resolution("signe.org", TypeEnregistrement::A);
+        assert!(serveur.join().unwrap(), "le bit DO doit être demandé à l'amont");
+        assert!(matches!(resolution.resultat, Resultat::Reponses(_)));
+        assert_eq!(resolution.securite, Securite::NonSecurisee);
+        // Le statut est conservé en cache avec la réponse
+        let depuis_le_cache = resolveur.resolution("signe.org", TypeEnregistrement::A);
+        assert_eq!((resolution.source, depuis_le_cache.source), (Source::Amont, Source::Cache));
+        assert_eq!(depuis_le_cache.securite, Securite::NonSecurisee);
+        assert_eq!(resolveur.cache.len(), 1);
+    }
+
+    #[test]
+    fn test_prefetch() {
+        // L'amont répond avec un TTL de 2 s : la réponse est à rafraîchir dès la première passe
+        let amont = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let adresse_amont = amont.local_addr().unwrap();
+        let serveur = std::thread::spawn(move || {
+            let mut tampon = [0u8; 512];
+            for _ in 0..3 {
+                let (taille, client) = amont.recv_from(&mut tampon).unwrap();
+                let requete = PaquetDns::decoder(&tampon[..taille]).unwrap();
+                let mut reponse = PaquetDns::reponse_a(&requete, CodeReponse::NoError);
+                let nom = requete.questions[0].nom.clone();
+                reponse.reponses.push(Enregistrement::new(&nom, 2, Donnees::A(Ipv4Addr::new(10, 0, 0, 1))));
+                amont.send_to(&reponse.encoder(), client).unwrap();
+            }
+        });
+
+        let config = ConfigResolveur { amont: Some(adresse_amont), prefetch: 1, ..ConfigResolveur::default() };
+        let mut resolveur = Resolveur::new(zone_de_test(), config);
+        resolveur.resoudre("populaire.org", TypeEnregistrement::A);
+        resolveur.resoudre("populaire.org", TypeEnregistrement::A);
+        resolveur.resoudre("rare.org", TypeEnregistrement::A);
+        // Les noms de la zone locale ne comptent pas
+        for _ in 0..5 {
+            resolveur.resoudre("esgi.fr", TypeEnregistrement::A);
+        }
+
+        // Seul le nom le plus demandé est rafraîchi (top 1), l'amont étant interrogé hors du résolveur
+        let mut prefetch = resolveur.preparer_prefetch().expect("un nom à rafraîchir");
+        prefetch.interroger();
+        assert_eq!(resolveur.terminer_prefetch(prefetch), 1);
+        serveur.join().unwrap();
+        assert_eq!(resolveur.statistiques().prefetchs, 1);
+        assert_eq!(resolveur.statistiques().cache.succes, 1);
+
+        // Sans prefetch, les demandes ne sont pas comptées
+        let config = ConfigResolveur { amont: Some(adresse_amont), prefetch: 0, ..ConfigResolveur::default() };
+        let mut resolveur = Resolveur::new(zone_de_test(), config);
+        resolveur.cache.inserer("populaire.org", TypeEnregistrement::A, Resultat::NxDomain, Securite::NonSecurisee, 60);
+        assert_eq!(resolveur.resoudre("populaire.org", TypeEnregistrement::A), Resultat::NxDomain);
+        assert!(resolveur.popularite.is_empty());
+    }
+
+    #[test]
+    fn test_enregistrements_hors_sujet() {
+        let mut resolveur = Resolveur::new(zone_de_test(), ConfigResolveur::default());
+        let mut reponse = PaquetDns::reponse_a(&PaquetDns::requete(1, "www.esgi.org", TypeEnregistrement::A), CodeReponse::NoError);
+        reponse.reponses = vec![
+            Enregistrement::new("www.esgi.org", 300, Donnees::CNAME("web.esgi.org".to_string())),
+            Enregistrement::new("web.esgi.org", 300, Donnees::A(Ipv4Addr::new(10, 0, 0, 1))),
+            Enregistrement::new("banque.fr", 86400, Donnees::A(Ipv4Addr::new(10, 6, 6, 6))),
+        ];
+        let (resultat, ttl) = resolveur.amont.interpreter_reponse("www.esgi.org", TypeEnregistrement::A, &reponse);
+        assert!(matches!(resultat, Resultat::Reponses(rrs) if rrs.len() == 2));
+        assert_eq!(ttl, 300);
+        assert_eq!(resolveur.statistiques().enregistrements_ecartes, 1);
+    }
+}
@@ -0,0 +1,98 @@
+// Protocole texte historique : "nom [TYPE]" en requête, valeurs ou statut en réponse.
+// "JSON nom [TYPE]" demande la même réponse sous forme d'un document JSON pour les scripts
+
+use crate::dns::{Enregistrement, TypeEnregistrement};
+use crate::resolveur::{Resolution, Resultat};
+
+/// Requête texte analysée
+#[derive(Debug, Clone, PartialEq)]
+pub struct RequeteTexte {
+    pub nom: String,
+    pub type_rr: TypeEnregistrement,
+    /// Réponse attendue en JSON
+    pub json: bool,
+}
+
+/// Analyser une requête texte, précédée ou non du mot-clé JSON
+pub fn analyser(ligne: &str) -> Result<RequeteTexte, String> {
+    let ligne = ligne.trim();
+    let (json, reste) = match ligne.split_once(char::is_whitespace) {
+        Some((mot, reste)) if mot.eq_ignore_ascii_case("JSON") => (true, reste),
+        _ => (false, ligne),
+    };
+    let (nom, type_rr) = analyser_requete(reste)?;
+    Ok(RequeteTexte { nom, type_rr, json })
+}
+
+/// Analyser une requête texte ; le type vaut A par défaut
+pub fn analyser_requete(ligne: &str) -> Result<(String, TypeEnregistrement), String> {
+    let mut morceaux = ligne.split_whitespace();
+    let nom = morceaux.next().ok_or("requête vide")?;
+    let type_rr = match morceaux.next() {
+        Some(t) => t.parse()?,
+        None => TypeEnregistrement::A,
+    };
+    if morceaux.next().is_some() {
+        return Err("trop d'arguments (attendu: nom [TYPE])".to_string());
+    }
+    Ok((nom.to_string(), type_rr))
+}
+
+/// Réponse texte : une valeur par ligne, ou le statut DNS en clair
+pub fn formater_resultat(resultat: &Resultat) -> String {
+    match resultat {
+        Resultat::Reponses(enregistrements) => enregistrements
+            .iter()
+            .map(|rr| rr.donnees.to_string())
+            .collect::<Vec<_>>()
+            .join("\n"),
+        Resultat::SansDonnees => "NODATA".to_string(),
+        Resultat::NxDomain => "NXDOMAIN".to_string(),
+        Resultat::ServFail => "SERVFAIL".to_string(),
+    }
+}
+
+/// Réponse JSON : la question, le statut DNS, la provenance, le statut DNSSEC et les enregistrements
+pub fn formater_json(nom: &str, type_rr: TypeEnregistrement, resolution: &Resolution) -> String {
+    let (statut, reponses) = match &resolution.resultat {
+        Resultat::Reponses(enregistrements) => ("NOERROR".to_string(), enregistrements.iter().map(Enregistrement::en_json).collect()),
+        autre => (formater_resultat(autre), Vec::new()),
+    };
+    serde_json::json!({
+        "query": { "name": nom, "type": type_rr.to_string() },
+        "status": statut,
+        "source": resolution.source.to_string(),
+        "dnssec": resolution.securite.to_string(),
+        "answers": reponses,
+    })
+    .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dns::Donnees;
+    use crate::dnssec::Securite;
+    use crate::resolveur::Source;
+    use std::net::Ipv4Addr;
+
+    #[test]
+    fn test_requete_json() {
+        let requete = analyser("json esgi.fr AAAA").unwrap();
+        assert_eq!(requete, RequeteTexte { nom: "esgi.fr".to_string(), type_rr: TypeEnregistrement::AAAA, json: true });
+        assert!(!analyser("esgi.fr").unwrap().json);
+        assert_eq!(analyser("json").unwrap().nom, "json");
+        assert!(analyser("JSON esgi.fr A B").is_err());
+
+        let resolution = Resolution {
+            resultat: Resultat::Reponses(vec![Enregistrement::new("esgi.fr", 42, Donnees::A(Ipv4Addr::new(192, 168, 1, 42)))]),
+            source: Source::Cache,
+            securite: Securite::NonSecurisee,
+        };
+        let document: serde_json::Value = serde_json::from_str(&formater_json("esgi.fr", TypeEnregistrement::A, &resolution)).unwrap();
+        assert_eq!(document["status"], "NOERROR");
+        assert_eq!(document["source"], "cache");
+        assert_eq!(document["answers"][0]["ttl"], 42);
+        assert_eq!(document["answers"][0]["data"], "192.168.1.42");
+    }
+}
@@ -0,0 +1,329 @@
+// Écoute TCP et réplication de zone entre un primaire et un secondaire (façon AXFR/IXFR)
+
+use std::io;
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use tracing::{error, info, warn};
+
+use crate::dns::{
+    ecrire_message_tcp, lire_message_tcp, CodeReponse, Donnees, Enregistrement, PaquetDns, TypeEnregistrement,
+};
+use crate::resolveur::Resolveur;
+use crate::zone::{Modification, Zone};
+
+/// Résolveur partagé entre les écoutes UDP, TCP et la tâche de réplication
+pub type ResolveurPartage = Arc<Mutex<Resolveur>>;
+
+/// Nombre d'enregistrements par message lors d'un transfert
+const ENREGISTREMENTS_PAR_MESSAGE: usize = 100;
+
+/// Délai de lecture sur une connexion de transfert
+const DELAI_TCP: Duration = Duration::from_secs(5);
+
+/// Accepter les connexions TCP (requêtes ordinaires et transferts de zone)
+pub fn servir_tcp(listener: TcpListener, resolveur: ResolveurPartage) {
+    for connexion in listener.incoming() {
+        match connexion {
+            Ok(flux) => {
+                let resolveur = Arc::clone(&resolveur);
+                thread::spawn(move || {
+                    let pair = flux.peer_addr().ok();
+                    if let Err(e) = traiter_connexion(flux, &resolveur) {
+                        warn!("Connexion TCP {:?} interrompue: {}", pair, e);
+                    }
+                });
+            }
+            Err(e) => error!("Erreur d'acceptation TCP: {}", e),
+        }
+    }
+}
+
+fn traiter_connexion(mut flux: TcpStream, resolveur: &ResolveurPartage) -> io::Result<()> {
+    flux.set_read_timeout(Some(DELAI_TCP * 6))?;
+    let pair = flux.peer_addr()?;
+
+    while let Some(octets) = lire_message_tcp(&mut flux)? {
+        let requete = match PaquetDns::decoder(&octets) {
+            Ok(requete) if requete.est_requete_standard() => requete,
+            _ => {
+                warn!("Message TCP invalide de {}", pair);
+                return Ok(());
+            }
+        };
+
+        let question = &requete.questions[0];
+        let reponses = match question.type_rr {
+            TypeEnregistrement::AXFR => {
+                info!("Requête TCP de {}: {} {}", pair, question.nom, question.type_rr);
+                let resolveur = resolveur.lock().unwrap();
+                let zone = resolveur.zone();
+                flux_complet(zone.soa(), zone.tous())
+            }
+            TypeEnregistrement::IXFR => {
+                info!("Requête TCP de {}: {} {}", pair, question.nom, question.type_rr);
+                let serial_client = requete.autorite.iter().find_map(serial_soa);
+                let resolveur = resolveur.lock().unwrap();
+                let zone = resolveur.zone();
+                match serial_client.and_then(|serial| zone.modifications_depuis(serial)) {
+                    Some(modifications) => flux_incremental(zone.soa(), &modifications),
+                    // Journal trop court : on se rabat sur un transfert complet
+                    None => flux_complet(zone.soa(), zone.tous()),
+                }
+            }
+            _ => {
+                let (reponse, securite) = resolveur.lock().unwrap().repondre_avec_securite(&requete);
+                info!("Requête TCP de {}: {} {} ({})", pair, question.nom, question.type_rr, securite);
+                ecrire_message_tcp(&mut flux, &reponse.encoder())?;
+                continue;
+            }
+        };
+
+        for morceau in decouper(&requete, reponses) {
+            ecrire_message_tcp(&mut flux, &morceau.encoder())?;
+        }
+    }
+    Ok(())
+}
+
+fn serial_soa(rr: &Enregistrement) -> Option<u32> {
+    match rr.donnees {
+        Donnees::SOA { serial, .. } => Some(serial),
+        _ => None,
+    }
+}
+
+/// SOA, enregistrements, SOA
+fn flux_complet(soa: Enregistrement, enregistrements: Vec<Enregistrement>) -> Vec<Enregistrement> {
+    let mut flux = Vec::with_capacity(enregistrements.len() + 2);
+    flux.push(soa.clone());
+    flux.extend(enregistrements);
+    flux.push(soa);
+    flux
+}
+
+/// SOA(N), puis pour chaque étape SOA(avant), retirés, SOA(après), ajoutés, et enfin SOA(N) (RFC 1995)
+fn flux_incremental(soa: Enregistrement, modifications: &[Modification]) -> Vec<Enregistrement> {
+    let mut flux = vec![soa.clone()];
+    if modifications.is_empty() {
+        return flux;
+    }
+    for modification in modifications {
+        flux.push(Zone::soa_avec_serial(modification.serial_avant));
+        flux.extend(modification.retires.iter().cloned());
+        flux.push(Zone::soa_avec_serial(modification.serial_apres));
+        flux.extend(modification.ajoutes.iter().cloned());
+    }
+    flux.push(soa);
+    flux
+}
+
+fn decouper(requete: &PaquetDns, flux: Vec<Enregistrement>) -> Vec<PaquetDns> {
+    flux.chunks(ENREGISTREMENTS_PAR_MESSAGE)
+        .map(|morceau| {
+            let mut reponse = PaquetDns::reponse_a(requete, CodeReponse::NoError);
+            reponse.en_tete.aa = true;
+            reponse.reponses = morceau.to_vec();
+            reponse
+        })
+        .collect()
+}
+
+/// Contenu reçu lors d'un transfert
+#[derive(Debug, Clone, PartialEq)]
+pub enum Transfert {
+    /// Zone complète au numéro de série donné
+    Complet { serial: u32, enregistrements: Vec<Enregistrement> },
+    /// Suite de modifications à appliquer dans l'ordre
+    Incremental(Vec<Modification>),
+    /// Le secondaire est déjà à jour
+    AJour,
+}
+
+/// Interpréter le flux reçu jusqu'ici ; `Ok(None)` tant qu'il est incomplet
+pub fn analyser_flux(flux: &[Enregistrement], serial_local: Option<u32>) -> Result<Option<Transfert>, String> {
+    let Some(premier) = flux.first() else {
+        return Ok(None);
+    };
+    let serial = serial_soa(premier).ok_or("le transfert doit commencer par un SOA")?;
+
+    if flux.len() == 1 {
+        return Ok((serial_local == Some(serial)).then_some(Transfert::AJour));
+    }
+
+    match serial_soa(&flux[1]) {
+        // Deuxième SOA différent : transfert incrémental
+        Some(avant) if avant != serial => analyser_incremental(&flux[1..], serial),
+        _ => {
+            let dernier = &flux[flux.len() - 1];
+            if serial_soa(dernier) != Some(serial) {
+                return Ok(None);
+            }
+            Ok(Some(Transfert::Complet { serial, enregistrements: flux[1..flux.len() - 1].to_vec() }))
+        }
+    }
+}
+
+fn analyser_incremental(flux: &[Enregistrement], serial_final: u32) -> Result<Option<Transfert>, String> {
+    let mut modifications = Vec::new();
+    let mut i = 0;
+
+    loop {
+        let Some(rr) = flux.get(i) else { return Ok(None) };
+        let serial_avant = serial_soa(rr).ok_or("SOA attendu en début d'étape")?;
+        if serial_avant == serial_final {
+            return Ok(Some(Transfert::Incremental(modifications)));
+        }
+        i += 1;
+
+        let mut retires = Vec::new();
+        while let Some(rr) = flux.get(i).filter(|rr| serial_soa(rr).is_none()) {
+            retires.push(rr.clone());
+            i += 1;
+        }
+        let Some(serial_apres) = flux.get(i).and_then(serial_soa) else { return Ok(None) };
+        i += 1;
+
+        let mut ajoutes = Vec::new();
+        while let Some(rr) = flux.get(i).filter(|rr| serial_soa(rr).is_none()) {
+            ajoutes.push(rr.clone());
+            i += 1;
+        }
+
+        modifications.push(Modification { serial_avant, serial_apres, retires, ajoutes });
+    }
+}
+
+/// Demander un transfert au primaire ; IXFR si un numéro de série local est fourni
+pub fn demander_transfert(primaire: SocketAddr, serial_local: Option<u32>) -> Result<Transfert, String> {
+    let mut flux = TcpStream::connect_timeout(&primaire, DELAI_TCP).map_err(|e| e.to_string())?;
+    flux.set_read_timeout(Some(DELAI_TCP)).map_err(|e| e.to_string())?;
+
+    let type_rr = if serial_local.is_some() { TypeEnregistrement::IXFR } else { TypeEnregistrement::AXFR };
+    let mut requete = PaquetDns::requete(1, "", type_rr);
+    if let Some(serial) = serial_local {
+        requete.autorite.push(Zone::soa_avec_serial(serial));
+    }
+    ecrire_message_tcp(&mut flux, &requete.encoder()).map_err(|e| e.to_string())?;
+
+    let mut recus = Vec::new();
+    loop {
+        let octets = lire_message_tcp(&mut flux)
+            .map_err(|e| e.to_string())?
+            .ok_or("le primaire a fermé la connexion au milieu du transfert")?;
+        let reponse = PaquetDns::decoder(&octets).map_err(|e| e.to_string())?;
+        if reponse.en_tete.rcode != CodeReponse::NoError {
+            return Err(format!("transfert refusé: {:?}", reponse.en_tete.rcode));
+        }
+        recus.extend(reponse.reponses);
+
+        if let Some(transfert) = analyser_flux(&recus, serial_local)? {
+            return Ok(transfert);
+        }
+    }
+}
+
+/// Numéro de série courant du primaire (question SOA sur la racine)
+pub fn serial_primaire(primaire: SocketAddr) -> Result<u32, String> {
+    let mut flux = TcpStream::connect_timeout(&primaire, DELAI_TCP).map_err(|e| e.to_string())?;
+    flux.set_read_timeout(Some(DELAI_TCP)).map_err(|e| e.to_string())?;
+
+    let requete = PaquetDns::requete(1, "", TypeEnregistrement::SOA);
+    ecrire_message_tcp(&mut flux, &requete.encoder()).map_err(|e| e.to_string())?;
+    let octets = lire_message_tcp(&mut flux)
+        .map_err(|e| e.to_string())?
+        .ok_or("le primaire a fermé la connexion")?;
+    let reponse = PaquetDns::decoder(&octets).map_err(|e| e.to_string())?;
+    reponse.reponses.iter().find_map(serial_soa).ok_or_else(|| "réponse SOA sans numéro de série".to_string())
+}
+
+/// Mettre la zone locale au niveau du primaire ; renvoie vrai si elle a changé
+pub fn mettre_a_jour(primaire: SocketAddr, resolveur: &ResolveurPartage, initialisee: bool) -> Result<bool, String> {
+    let serial_local = resolveur.lock().unwrap().zone().serial();
+    if initialisee && serial_primaire(primaire)? == serial_local {
+        return Ok(false);
+    }
+
+    let transfert = demander_transfert(primaire, initialisee.then_some(serial_local))?;
+    let mut resolveur = resolveur.lock().unwrap();
+    let zone = resolveur.zone_mut();
+    match transfert {
+        Transfert::AJour => return Ok(false),
+        Transfert::Complet { serial, enregistrements } => {
+            info!("Transfert complet reçu: {} enregistrements (serial {})", enregistrements.len(), serial);
+            zone.remplacer_avec_serial(enregistrements, serial);
+        }
+        Transfert::Incremental(modifications) => {
+            info!("Transfert incrémental reçu: {} modification(s)", modifications.len());
+            for modification in modifications {
+                zone.appliquer(modification);
+            }
+        }
+    }
+    Ok(true)
+}
+
+/// Boucle du secondaire : transfert complet au démarrage puis interrogation périodique du serial
+pub fn synchroniser(primaire: SocketAddr, resolveur: ResolveurPartage, intervalle: Duration) {
+    let mut initialisee = false;
+    loop {
+        match mettre_a_jour(primaire, &resolveur, initialisee) {
+            Ok(change) => {
+                initialisee = true;
+                if change {
+                    let serial = resolveur.lock().unwrap().zone().serial();
+                    info!("Zone synchronisée avec {} (serial {})", primaire, serial);
+                }
+            }
+            Err(e) => warn!("Synchronisation avec {} impossible: {}", primaire, e),
+        }
+        thread::sleep(intervalle);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resolveur::ConfigResolveur;
+    use std::net::Ipv4Addr;
+
+    fn a(nom: &str, dernier_octet: u8) -> Enregistrement {
+        Enregistrement::new(nom, 60, Donnees::A(Ipv4Addr::new(10, 0, 0, dernier_octet)))
+    }
+
+    #[test]
+    fn test_replication_complete_puis_incrementale() {
+        let mut zone = Zone::new();
+        zone.remplacer(vec![a("un.local", 1), a("deux.local", 2)]);
+        let primaire: ResolveurPartage = Arc::new(Mutex::new(Resolveur::new(zone, ConfigResolveur::default())));
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let adresse = listener.local_addr().unwrap();
+        let partage = Arc::clone(&primaire);
+        thread::spawn(move || servir_tcp(listener, partage));
+
+        let secondaire: ResolveurPartage = Arc::new(Mutex::new(Resolveur::new(Zone::new(), ConfigResolveur::default())));
+        assert!(mettre_a_jour(adresse, &secondaire, false).unwrap());
+        assert_eq!(secondaire.lock().unwrap().zone().tous(), primaire.lock().unwrap().zone().tous());
+        assert!(!mettre_a_jour(adresse, &secondaire, true).unwrap());
+
+        // Une modification côté primaire arrive en incrémental
+        primaire.lock().unwrap().zone_mut().remplacer(vec![a("un.local", 1), a("trois.local", 3)]);
+        assert!(mettre_a_jour(adresse, &secondaire, true).unwrap());
+
+        let secondaire = secondaire.lock().unwrap();
+        let primaire = primaire.lock().unwrap();
+        assert_eq!(secondaire.zone().serial(), primaire.zone().serial());
+        assert_eq!(secondaire.zone().tous(), primaire.zone().tous());
+    }
+
+    #[test]
+    fn test_flux_incomplet() {
+        let zone = Zone::new();
+        let flux = flux_complet(zone.soa(), vec![a("un.local", 1)]);
+        assert_eq!(analyser_flux(&flux[..2], None), Ok(None));
+        assert!(matches!(analyser_flux(&flux, None), Ok(Some(Transfert::Complet { .. }))));
+    }
+}
@@ -0,0 +1,330 @@
+// Zone locale : enregistrements servis avec autorité, numéro de série et journal des modifications
+
+use std::collections::{HashMap, VecDeque};
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::path::Path;
+
+use crate::dns::{normaliser_nom, Donnees, Enregistrement, TypeEnregistrement};
+use crate::resolveur::Resultat;
+
+/// Nombre de modifications conservées pour les transferts incrémentaux
+const TAILLE_JOURNAL: usize = 32;
+
+/// TTL appliqué aux lignes de fichier de zone qui n'en précisent pas
+pub const TTL_PAR_DEFAUT: u32 = 3600;
+
+/// Différence entre deux versions successives de la zone
+#[derive(Debug, Clone, PartialEq)]
+pub struct Modification {
+    pub serial_avant: u32,
+    pub serial_apres: u32,
+    pub retires: Vec<Enregistrement>,
+    pub ajoutes: Vec<Enregistrement>,
+}
+
+/// Table des enregistrements servis localement
+pub struct Zone {
+    enregistrements: HashMap<String, Vec<Enregistrement>>,
+    serial: u32,
+    journal: VecDeque<Modification>,
+}
+
+impl Default for Zone {
+    fn default() -> Self {
+        Self { enregistrements: HashMap::new(), serial: 1, journal: VecDeque::new() }
+    }
+}
+
+impl Zone {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn ajouter(&mut self, enregistrement: Enregistrement) {
+        self.enregistrements
+            .entry(enregistrement.nom.clone())
+            .or_default()
+            .push(enregistrement);
+    }
+
+    fn retirer(&mut self, enregistrement: &Enregistrement) {
+        if let Some(liste) = self.enregistrements.get_mut(&enregistrement.nom) {
+            liste.retain(|rr| rr != enregistrement);
+            if liste.is_empty() {
+                self.enregistrements.remove(&enregistrement.nom);
+            }
+        }
+    }
+
+    /// Chercher un nom dans la zone ; `None` si le nom n'y figure pas du tout
+    pub fn chercher(&self, nom: &str, type_rr: TypeEnregistrement) -> Option<Resultat> {
+        // Le SOA de la racine porte le numéro de série consulté par les secondaires
+        if nom.is_empty() && type_rr == TypeEnregistrement::SOA {
+            return Some(Resultat::Reponses(vec![self.soa()]));
+        }
+
+        let enregistrements = self.enregistrements.get(nom)?;
+
+        let mut trouves: Vec<Enregistrement> = enregistrements
+            .iter()
+            .filter(|rr| rr.type_rr == type_rr)
+            .cloned()
+            .collect();
+        if trouves.is_empty() {
+            // Un alias répond à tous les types
+            trouves = enregistrements
+                .iter()
+                .filter(|rr| rr.type_rr == TypeEnregistrement::CNAME)
+                .cloned()
+                .collect();
+        }
+
+        if trouves.is_empty() {
+            Some(Resultat::SansDonnees)
+        } else {
+            Some(Resultat::Reponses(trouves))
+        }
+    }
+
+    /// Tous les enregistrements, triés par nom pour un ordre de transfert stable
+    pub fn tous(&self) -> Vec<Enregistrement> {
+        let mut noms: Vec<&String> = self.enregistrements.keys().collect();
+        noms.sort();
+        noms.into_iter()
+            .flat_map(|nom| self.enregistrements[nom].iter().cloned())
+            .collect()
+    }
+
+    pub fn serial(&self) -> u32 {
+        self.serial
+    }
+
+    /// Enregistrement SOA synthétique de la zone (propriétaire : racine)
+    pub fn soa(&self) -> Enregistrement {
+        Self::soa_avec_serial(self.serial)
+    }
+
+    /// SOA portant un numéro de série donné (bornes des étapes d'un transfert incrémental)
+    pub fn soa_avec_serial(serial: u32) -> Enregistrement {
+        Enregistrement::new(
+            "",
+            0,
+            Donnees::SOA {
+                mname: "tp7.local".to_string(),
+                rname: "admin.tp7.local".to_string(),
+                serial,
+                refresh: 10,
+                retry: 5,
+                expire: 3600,
+                minimum: 60,
+            },
+        )
+    }
+
+    /// Remplacer le contenu de la zone ; le numéro de série n'avance que s'il y a un changement
+    pub fn remplacer(&mut self, nouveaux: Vec<Enregistrement>) -> bool {
+        let modification = self.difference(&nouveaux, self.serial.wrapping_add(1));
+        if modification.retires.is_empty() && modification.ajoutes.is_empty() {
+            return false;
+        }
+        self.appliquer(modification);
+        true
+    }
+
+    /// Remplacer le contenu en imposant le numéro de série (zone secondaire après un transfert complet)
+    pub fn remplacer_avec_serial(&mut self, nouveaux: Vec<Enregistrement>, serial: u32) {
+        let modification = self.difference(&nouveaux, serial);
+        self.appliquer(modification);
+    }
+
+    fn difference(&self, nouveaux: &[Enregistrement], serial_apres: u32) -> Modification {
+        let anciens = self.tous();
+        Modification {
+            serial_avant: self.serial,
+            serial_apres,
+            retires: anciens.iter().filter(|rr| !nouveaux.contains(rr)).cloned().collect(),
+            ajoutes: nouveaux.iter().filter(|rr| !anciens.contains(rr)).cloned().collect(),
+        }
+    }
+
+    /// Appliquer une modification et l'inscrire au journal
+    pub fn appliquer(&mut self, modification: Modification) {
+        for rr in &modification.retires {
+            self.retirer(rr);
+        }
+        for rr in &modification.ajoutes {
+            self.ajouter(rr.clone());
+        }
+        self.serial = modification.serial_apres;
+
+        self.journal.push_back(modification);
+        while self.journal.len() > TAILLE_JOURNAL {
+            self.journal.pop_front();
+        }
+    }
+
+    /// Modifications permettant de passer de `serial` à la version courante,
+    /// ou `None` si le journal ne remonte pas jusque-là
+    pub fn modifications_depuis(&self, serial: u32) -> Option<Vec<Modification>> {
+        if serial == self.serial {
+            return Some(Vec::new());
+        }
+        let debut = self.journal.iter().position(|m| m.serial_avant == serial)?;
+        Some(self.journal.iter().skip(debut).cloned().collect())
+    }
+
+    pub fn len(&self) -> usize {
+        self.enregistrements.values().map(Vec::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.enregistrements.is_empty()
+    }
+}
+
+/// Lire un fichier de zone : une ligne "nom [ttl] TYPE valeur", commentaires avec '#' ou ';'
+pub fn lire_fichier_zone(chemin: &Path) -> Result<Vec<Enregistrement>, String> {
+    let contenu = std::fs::read_to_string(chemin)
+        .map_err(|e| format!("lecture de {} impossible: {}", chemin.display(), e))?;
+
+    let mut enregistrements = Vec::new();
+    for (numero, ligne) in contenu.lines().enumerate() {
+        let ligne = ligne.split(['#', ';']).next().unwrap_or("").trim();
+        if ligne.is_empty() {
+            continue;
+        }
+        let rr = analyser_ligne_zone(ligne)
+            .map_err(|e| format!("{}:{}: {}", chemin.display(), numero + 1, e))?;
+        enregistrements.push(rr);
+    }
+    Ok(enregistrements)
+}
+
+/// Lire un fichier au format /etc/hosts : "adresse nom [alias...]", une entrée A ou AAAA par nom
+pub fn lire_fichier_hosts(chemin: &Path) -> Result<Vec<Enregistrement>, String> {
+    let contenu = std::fs::read_to_string(chemin)
+        .map_err(|e| format!("lecture de {} impossible: {}", chemin.display(), e))?;
+
+    let mut enregistrements: Vec<Enregistrement> = Vec::new();
+    for (numero, ligne) in contenu.lines().enumerate() {
+        let ligne = ligne.split('#').next().unwrap_or("").trim();
+        if ligne.is_empty() {
+            continue;
+        }
+        for rr in analyser_ligne_hosts(ligne).map_err(|e| format!("{}:{}: {}", chemin.display(), numero + 1, e))? {
+            // Un même couple nom/adresse peut apparaître sur plusieurs lignes
+            if !enregistrements.contains(&rr) {
+                enregistrements.push(rr);
+            }
+        }
+    }
+    Ok(enregistrements)
+}
+
+fn analyser_ligne_hosts(ligne: &str) -> Result<Vec<Enregistrement>, String> {
+    let mut champs = ligne.split_whitespace();
+    let adresse = champs.next().ok_or("adresse manquante")?;
+    // Les adresses de lien local portent parfois un identifiant d'interface (fe80::1%lo0)
+    let adresse = adresse.split('%').next().unwrap_or(adresse);
+
+    let donnees = |adresse: &str| -> Result<Donnees, String> {
+        if let Ok(ip) = adresse.parse::<Ipv4Addr>() {
+            return Ok(Donnees::A(ip));
+        }
+        adresse.parse::<Ipv6Addr>().map(Donnees::AAAA).map_err(|_| format!("adresse invalide: {}", adresse))
+    };
+    let donnees = donnees(adresse)?;
+
+    let noms: Vec<&str> = champs.collect();
+    if noms.is_empty() {
+        return Err(format!("aucun nom pour {}", adresse));
+    }
+    Ok(noms.into_iter().map(|nom| Enregistrement::new(nom, TTL_PAR_DEFAUT, donnees.clone())).collect())
+}
+
+fn analyser_ligne_zone(ligne: &str) -> Result<Enregistrement, String> {
+    let mut champs = ligne.split_whitespace();
+    let nom = champs.next().ok_or("nom manquant")?;
+
+    let mut suivant = champs.next().ok_or("type manquant")?;
+    let ttl = match suivant.parse::<u32>() {
+        Ok(ttl) => {
+            suivant = champs.next().ok_or("type manquant")?;
+            ttl
+        }
+        Err(_) => TTL_PAR_DEFAUT,
+    };
+    let type_rr: TypeEnregistrement = suivant.parse()?;
+    let valeurs: Vec<&str> = champs.collect();
+    let valeur = |i: usize| valeurs.get(i).copied().ok_or(format!("valeur manquante pour {}", type_rr));
+
+    let donnees = match type_rr {
+        TypeEnregistrement::A => Donnees::A(valeur(0)?.parse::<Ipv4Addr>().map_err(|e| e.to_string())?),
+        TypeEnregistrement::AAAA => Donnees::AAAA(valeur(0)?.parse::<Ipv6Addr>().map_err(|e| e.to_string())?),
+        TypeEnregistrement::CNAME => Donnees::CNAME(normaliser_nom(valeur(0)?)),
+        TypeEnregistrement::NS => Donnees::NS(normaliser_nom(valeur(0)?)),
+        TypeEnregistrement::TXT => Donnees::TXT(valeurs.join(" ").trim_matches('"').to_string()),
+        TypeEnregistrement::SRV => {
+            let nombre = |i: usize| -> Result<u16, String> {
+                valeur(i)?.parse().map_err(|_| format!("champ SRV invalide: {}", valeurs[i]))
+            };
+            Donnees::SRV {
+                priorite: nombre(0)?,
+                poids: nombre(1)?,
+                port: nombre(2)?,
+                cible: normaliser_nom(valeur(3)?),
+            }
+        }
+        autre => return Err(format!("type {} non pris en charge dans un fichier de zone", autre)),
+    };
+
+    Ok(Enregistrement::new(nom, ttl, donnees))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_journal_et_modifications() {
+        let mut zone = Zone::new();
+        let a = Enregistrement::new("a.local", 60, Donnees::A(Ipv4Addr::new(10, 0, 0, 1)));
+        let b = Enregistrement::new("b.local", 60, Donnees::A(Ipv4Addr::new(10, 0, 0, 2)));
+
+        assert!(zone.remplacer(vec![a.clone()]));
+        assert!(!zone.remplacer(vec![a.clone()]), "contenu identique : pas de nouvelle version");
+        assert!(zone.remplacer(vec![b.clone()]));
+        assert_eq!(zone.serial(), 3);
+
+        let depuis_2 = zone.modifications_depuis(2).unwrap();
+        assert_eq!(depuis_2.len(), 1);
+        assert_eq!(depuis_2[0].retires, vec![a]);
+        assert_eq!(depuis_2[0].ajoutes, vec![b]);
+        assert!(zone.modifications_depuis(99).is_none());
+    }
+
+    #[test]
+    fn test_ligne_de_zone() {
+        let rr = analyser_ligne_zone("_scp._tcp.local 60 SRV 0 5 9999 localhost").unwrap();
+        assert_eq!(rr.ttl, 60);
+        assert!(matches!(rr.donnees, Donnees::SRV { port: 9999, .. }));
+
+        let rr = analyser_ligne_zone("esgi.fr A 192.168.1.42").unwrap();
+        assert_eq!(rr.ttl, TTL_PAR_DEFAUT);
+        assert!(analyser_ligne_zone("esgi.fr A pas-une-ip").is_err());
+    }
+
+    #[test]
+    fn test_ligne_hosts() {
+        let rrs = analyser_ligne_hosts("127.0.0.1 localhost localhost.localdomain").unwrap();
+        assert_eq!(rrs.len(), 2);
+        assert_eq!(rrs[1].nom, "localhost.localdomain");
+        assert_eq!(rrs[0].donnees, Donnees::A(Ipv4Addr::LOCALHOST));
+
+        let rrs = analyser_ligne_hosts("fe80::1%lo0 localhost").unwrap();
+        assert_eq!(rrs[0].type_rr, TypeEnregistrement::AAAA);
+
+        assert!(analyser_ligne_hosts("127.0.0.1").is_err());
+        assert!(analyser_ligne_hosts("pas-une-ip nom").is_err());
+    }
+}
@@ -0,0 +1,80 @@
+// src/admin.rs
+// Console d'administration du serveur : commandes de l'opérateur, tapées sur l'entrée standard ou envoyées sur la socket d'administration
+
+/// Aide affichée par la commande `help`
+pub const ADMIN_HELP: &str = "\
+commandes :
+  list-clients            connexions en cours (utilisateur, salon, statut, file d'envoi)
+  list-rooms              salons et nombre de membres
+  kick <utilisateur> [raison]  déconnecter un utilisateur
+  broadcast <texte>       annonce envoyée à tous les clients
+  stats                   chiffres du serveur
+  help                    cette aide";
+
+/// Commande de l'opérateur, une par ligne
+#[derive(Debug, Clone, PartialEq)]
+pub enum AdminCommand {
+    ListClients,
+    ListRooms,
+    Kick { username: String, reason: Option<String> },
+    Broadcast { text: String },
+    Stats,
+    Help,
+}
+
+impl AdminCommand {
+    /// Lire une ligne de commande ; `None` pour une ligne vide
+    pub fn parse(line: &str) -> Option<Result<Self, String>> {
+        let line = line.trim();
+        if line.is_empty() {
+            return None;
+        }
+        let (name, rest) = match line.split_once(char::is_whitespace) {
+            Some((name, rest)) => (name, rest.trim()),
+            None => (line, ""),
+        };
+        let command = match (name, rest) {
+            ("list-clients", "") => Ok(Self::ListClients),
+            ("list-rooms", "") => Ok(Self::ListRooms),
+            ("stats", "") => Ok(Self::Stats),
+            ("help", "") => Ok(Self::Help),
+            ("list-clients" | "list-rooms" | "stats" | "help", _) => Err(format!("{} ne prend pas d'argument", name)),
+            ("kick", "") => Err("usage : kick <utilisateur> [raison]".to_string()),
+            ("kick", rest) => {
+                let (username, reason) = match rest.split_once(char::is_whitespace) {
+                    Some((username, reason)) => (username, Some(reason.trim().to_string())),
+                    None => (rest, None),
+                };
+                Ok(Self::Kick { username: username.to_string(), reason })
+            }
+            ("broadcast", "") => Err("usage : broadcast <texte>".to_string()),
+            ("broadcast", text) => Ok(Self::Broadcast { text: text.to_string() }),
+            _ => Err(format!("Commande inconnue : {} (help pour la liste)", name)),
+        };
+        Some(command)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(AdminCommand::parse("   "), None);
+        assert_eq!(AdminCommand::parse(" list-clients "), Some(Ok(AdminCommand::ListClients)));
+        assert_eq!(
+            AdminCommand::parse("kick bob  trop de spam"),
+            Some(Ok(AdminCommand::Kick { username: "bob".to_string(), reason: Some("trop de spam".to_string()) })),
+        );
+        assert_eq!(AdminCommand::parse("kick bob"), Some(Ok(AdminCommand::Kick { username: "bob".to_string(), reason: None })));
+        assert_eq!(
+            AdminCommand::parse("broadcast Redémarrage à  midi"),
+            Some(Ok(AdminCommand::Broadcast { text: "Redémarrage à  midi".to_string() })),
+        );
+
+        for line in ["kick", "broadcast  ", "stats now", "shutdown"] {
+            assert!(matches!(AdminCommand::parse(line), Some(Err(_))), "{}", line);
+        }
+    }
+}
@@ -0,0 +1,85 @@
+// src/bin/bot.rs
+// Example bots built on tp8::client: an echo bot, or a bot that logs a room's conversation
+
+use tp8::client::{BotHandler, ChatBot, ClientError, PrivateMessage, RoomMessage};
+use tp8::protocole::{Message, PROTOCOL_VERSION};
+use tracing::{error, info};
+
+/// Chat server to join by default
+const DEFAULT_SERVER_ADDR: &str = "127.0.0.1:9999";
+
+/// Repeats what it hears: room messages in the room, private messages to their sender
+struct EchoBot;
+
+impl BotHandler for EchoBot {
+    async fn on_room_message(&mut self, bot: &mut ChatBot, message: RoomMessage) -> Result<(), ClientError> {
+        bot.send(format!("{} a dit : {}", message.from, message.content)).await
+    }
+
+    async fn on_private_message(&mut self, bot: &mut ChatBot, message: PrivateMessage) -> Result<(), ClientError> {
+        bot.private_message(&message.from, message.content).await
+    }
+}
+
+/// Prints the conversation, one line per message; "!quit" in private from anybody stops it
+struct LogBot;
+
+impl BotHandler for LogBot {
+    async fn on_room_message(&mut self, _bot: &mut ChatBot, message: RoomMessage) -> Result<(), ClientError> {
+        info!("{} [{}] {}: {}", message.timestamp.format("%H:%M:%S"), message.room_id, message.from, message.content);
+        Ok(())
+    }
+
+    async fn on_private_message(&mut self, bot: &mut ChatBot, message: PrivateMessage) -> Result<(), ClientError> {
+        if message.content.trim() == "!quit" {
+            info!("👋 Asked to leave by {}", message.from);
+            return bot.quit().await;
+        }
+        info!("{} (private) {}: {}", message.timestamp.format("%H:%M:%S"), message.from, message.content);
+        Ok(())
+    }
+
+    async fn on_event(&mut self, _bot: &mut ChatBot, message: Message) -> Result<(), ClientError> {
+        match message {
+            Message::UserJoined { username, room_id, .. } => info!("🚪 {} joined {}", username, room_id),
+            Message::UserLeft { username, room_id } => info!("🚪 {} left {}", username, room_id),
+            Message::Error { message, .. } => error!("❌ {}", message),
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let _journal = trace_commun::init_from_env("tp8-bot");
+    info!("🤖 === SCP BOT (SCP v{}) ===", PROTOCOL_VERSION);
+
+    let usage = "usage: bot (echo | log) [--server <addr>] [--name <username>] [--password <password>] [--room <id>]";
+    let mut args = std::env::args().skip(1);
+    let mode = args.next().filter(|mode| mode == "echo" || mode == "log").ok_or(usage)?;
+    let mut server = DEFAULT_SERVER_ADDR.to_string();
+    let mut username = format!("{}-bot", mode);
+    let mut password = "bot-secret".to_string();
+    let mut room = "general".to_string();
+    while let Some(arg) = args.next() {
+        let mut value = |option: &str| args.next().ok_or(format!("{} requires a value", option));
+        match arg.as_str() {
+            "--server" => server = value("--server")?,
+            "--name" => username = value("--name")?,
+            "--password" => password = value("--password")?,
+            "--room" => room = value("--room")?,
+            other => return Err(format!("Unknown option: {} ({})", other, usage).into()),
+        }
+    }
+
+    let mut bot = ChatBot::connect(&server, &username, &password).await?;
+    let users = bot.join(&room).await?;
+    info!("✅ {} connected to {}, in {} with {}", username, server, room, users.join(", "));
+    match mode.as_str() {
+        "echo" => bot.run(&mut EchoBot).await?,
+        _ => bot.run(&mut LogBot).await?,
+    }
+    info!("🔌 Connection closed");
+    Ok(())
+}
@@ -0,0 +1,311 @@
+// src/bin/charge.rs
+// Load generator: many clients at once against a running server, to measure how it holds up
+
+use std::time::{Duration, Instant};
+
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::TcpStream;
+use tokio::task::JoinSet;
+
+use tp8::protocole::{ErrorCode, Message, MessageKind, ProtocolFrame, PROTOCOL_VERSION};
+use tp8::trame::{read_frame, write_frame};
+
+/// Chat server to load by default
+const DEFAULT_SERVER_ADDR: &str = "127.0.0.1:9999";
+
+/// A client that hears nothing for this long is given up
+const READ_TIMEOUT: Duration = Duration::from_secs(30);
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+#[derive(Debug, Clone)]
+struct Settings {
+    server: String,
+    clients: usize,
+    rooms: usize,
+    messages: usize,
+    logins: usize, // Extra clients logging in while the others chat
+    password: String,
+}
+
+/// One simulated client, once authenticated
+struct Connection {
+    index: usize,
+    username: String,
+    room_id: String,
+    reader: OwnedReadHalf,
+    writer: OwnedWriteHalf,
+}
+
+/// What a client measured while chatting
+#[derive(Debug, Default)]
+struct ChatReport {
+    sent: usize,
+    latencies: Vec<Duration>, // From sending a message to seeing it come back from the room
+    received: usize,          // Room messages received, its own included
+    rate_limited: usize,
+}
+
+async fn send(writer: &mut OwnedWriteHalf, message: Message) -> Result<(), BoxError> {
+    Ok(write_frame(writer, &ProtocolFrame::new(message, None, 0)).await?)
+}
+
+/// Next message from the server, answering its heartbeat on the way
+async fn receive(reader: &mut OwnedReadHalf, writer: &mut OwnedWriteHalf) -> Result<Message, BoxError> {
+    loop {
+        let frame = tokio::time::timeout(READ_TIMEOUT, read_frame(reader)).await??
+            .ok_or("connection closed by the server")?;
+        match frame.message {
+            Message::Ping => send(writer, Message::Pong).await?,
+            message => return Ok(message),
+        }
+    }
+}
+
+/// Read messages until one satisfies `wanted`; an Error from the server ends the wait
+async fn wait_for(
+    reader: &mut OwnedReadHalf,
+    writer: &mut OwnedWriteHalf,
+    wanted: impl Fn(&Message) -> bool,
+) -> Result<Message, BoxError> {
+    loop {
+        match receive(reader, writer).await? {
+            message if wanted(&message) => return Ok(message),
+            Message::Error { message, .. } => return Err(message.into()),
+            _ => {}
+        }
+    }
+}
+
+/// Connect and authenticate; accounts are registered on the first run and reused afterwards
+async fn connect(settings: &Settings, index: usize) -> Result<(Connection, Duration), BoxError> {
+    let stream = TcpStream::connect(&settings.server).await?;
+    stream.set_nodelay(true)?;
+    let (mut reader, mut writer) = stream.into_split();
+    send(&mut writer, Message::Hello { min_version: PROTOCOL_VERSION, max_version: PROTOCOL_VERSION }).await?;
+    wait_for(&mut reader, &mut writer, |message| matches!(message, Message::HelloAck { .. })).await?;
+
+    let username = format!("charge{}", index);
+    let password = settings.password.clone();
+    let answered = |message: &Message| matches!(message, Message::ConnectAck { .. } | Message::ConnectError { .. });
+    let started = Instant::now();
+    send(&mut writer, Message::Register { username: username.clone(), password: password.clone() }).await?;
+    match wait_for(&mut reader, &mut writer, answered).await? {
+        Message::ConnectError { code: ErrorCode::UsernameAlreadyTaken, .. } => {
+            // The refusal is followed by a generic processing error
+            wait_for(&mut reader, &mut writer, |message| matches!(message, Message::Error { .. })).await?;
+            send(&mut writer, Message::Login { username: username.clone(), password }).await?;
+            if let Message::ConnectError { reason, .. } = wait_for(&mut reader, &mut writer, answered).await? {
+                return Err(reason.into());
+            }
+        }
+        Message::ConnectError { reason, .. } => return Err(reason.into()),
+        _ => {}
+    }
+    let elapsed = started.elapsed();
+
+    let room_id = format!("charge-{}", index % settings.rooms);
+    Ok((Connection { index, username, room_id, reader, writer }, elapsed))
+}
+
+/// Authenticate the clients numbered in `indices` at the same time; returns them and how long each took
+async fn connect_all(settings: &Settings, indices: std::ops::Range<usize>) -> (Vec<Connection>, Vec<Duration>) {
+    let mut tasks = JoinSet::new();
+    for index in indices {
+        let settings = settings.clone();
+        tasks.spawn(async move { connect(&settings, index).await });
+    }
+    let (mut connections, mut logins) = (Vec::new(), Vec::new());
+    while let Some(result) = tasks.join_next().await {
+        match result {
+            Ok(Ok((connection, elapsed))) => {
+                connections.push(connection);
+                logins.push(elapsed);
+            }
+            Ok(Err(e)) => eprintln!("❌ Client could not log in: {}", e),
+            Err(e) => eprintln!("❌ Login task failed: {}", e),
+        }
+    }
+    (connections, logins)
+}
+
+/// Create the room if it does not exist yet (rooms from a previous run are reused)
+async fn create_room(connection: &mut Connection) -> Result<(), BoxError> {
+    let message = Message::CreateRoom { room_id: connection.room_id.clone(), name: String::new(), password: None, invite_only: false, max_users: None };
+    send(&mut connection.writer, message).await?;
+    let created = |m: &Message| matches!(m, Message::CreateRoomAck { .. } | Message::CreateRoomError { .. });
+    match wait_for(&mut connection.reader, &mut connection.writer, created).await? {
+        Message::CreateRoomError { reason } => {
+            wait_for(&mut connection.reader, &mut connection.writer, |m| matches!(m, Message::Error { .. })).await?;
+            if reason.contains("existe déjà") { Ok(()) } else { Err(reason.into()) }
+        }
+        _ => Ok(()),
+    }
+}
+
+async fn join_room(mut connection: Connection) -> Result<(Connection, Duration), BoxError> {
+    let started = Instant::now();
+    send(&mut connection.writer, Message::JoinRoom { room_id: connection.room_id.clone(), password: None, wait: false, spectator: false }).await?;
+    let joined = |m: &Message| matches!(m, Message::JoinRoomAck { .. } | Message::JoinRoomError { .. });
+    match wait_for(&mut connection.reader, &mut connection.writer, joined).await? {
+        Message::JoinRoomError { reason } => Err(reason.into()),
+        _ => Ok((connection, started.elapsed())),
+    }
+}
+
+/// Send `messages` messages, each one once the previous came back from the room, counting the
+/// messages of the other members on the way. At most one message per client is in flight, so the
+/// room channels never overflow and the latency measured is the server's, not a queue's
+async fn chat(connection: Connection, messages: usize) -> Result<ChatReport, BoxError> {
+    let Connection { username, mut reader, mut writer, .. } = connection;
+    let mut report = ChatReport::default();
+    for n in 0..messages {
+        let sent_at = Instant::now();
+        send(&mut writer, Message::SendMessage { content: format!("message {}", n), kind: MessageKind::Text, parent_message_id: None }).await?;
+        report.sent += 1;
+        loop {
+            match receive(&mut reader, &mut writer).await? {
+                Message::RoomMessage { from, .. } => {
+                    report.received += 1;
+                    if from == username {
+                        report.latencies.push(sent_at.elapsed());
+                        break;
+                    }
+                }
+                Message::Error { code: ErrorCode::RateLimitExceeded, .. } => {
+                    report.rate_limited += 1;
+                    break;
+                }
+                _ => {}
+            }
+        }
+    }
+    let _ = send(&mut writer, Message::Disconnect).await;
+    Ok(report)
+}
+
+/// Value below which `fraction` of the sorted samples fall
+fn percentile(sorted: &[Duration], fraction: f64) -> Duration {
+    if sorted.is_empty() {
+        return Duration::ZERO;
+    }
+    sorted[((sorted.len() - 1) as f64 * fraction).round() as usize]
+}
+
+fn summary(mut samples: Vec<Duration>) -> String {
+    samples.sort();
+    format!(
+        "p50 {:?}, p99 {:?}, max {:?}",
+        percentile(&samples, 0.5),
+        percentile(&samples, 0.99),
+        samples.last().copied().unwrap_or_default(),
+    )
+}
+
+#[tokio::main]
+async fn main() -> Result<(), BoxError> {
+    println!("🏋️ === SCP LOAD GENERATOR (SCP v{}) ===", PROTOCOL_VERSION);
+
+    // The server must let the load through, e.g. `serveur --chat-burst 100000 --chat-rate 100000`
+    let usage = "usage: charge [--server <addr>] [--clients <n>] [--rooms <n>] [--messages <n per client>] [--logins <n>] [--password <password>]";
+    let mut settings = Settings {
+        server: DEFAULT_SERVER_ADDR.to_string(),
+        clients: 100,
+        rooms: 10,
+        messages: 100,
+        logins: 0,
+        password: "charge-secret".to_string(),
+    };
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut number = |option: &str| -> Result<usize, String> {
+            args.next()
+                .and_then(|value| value.parse().ok())
+                .filter(|value| *value > 0)
+                .ok_or(format!("{} requires a positive number", option))
+        };
+        match arg.as_str() {
+            "--clients" => settings.clients = number("--clients")?,
+            "--rooms" => settings.rooms = number("--rooms")?,
+            "--messages" => settings.messages = number("--messages")?,
+            "--logins" => settings.logins = number("--logins")?,
+            "--server" => settings.server = args.next().ok_or("--server requires an address")?,
+            "--password" => settings.password = args.next().ok_or("--password requires a value")?,
+            other => return Err(format!("Unknown option: {} ({})", other, usage).into()),
+        }
+    }
+    settings.rooms = settings.rooms.min(settings.clients);
+    println!(
+        "🎯 {} client(s) in {} room(s), {} message(s) each, against {}",
+        settings.clients, settings.rooms, settings.messages, settings.server,
+    );
+
+    // 1. Every client authenticates at the same time
+    let started = Instant::now();
+    let (mut connections, logins) = connect_all(&settings, 0..settings.clients).await;
+    println!("🔐 {} login(s) in {:?} ({})", logins.len(), started.elapsed(), summary(logins));
+
+    // 2. One client per room creates it, then everybody joins at once
+    for connection in connections.iter_mut().filter(|connection| connection.index < settings.rooms) {
+        create_room(connection).await?;
+    }
+    let started = Instant::now();
+    let mut tasks = JoinSet::new();
+    for connection in connections {
+        tasks.spawn(join_room(connection));
+    }
+    let mut connections = Vec::new();
+    let mut joins = Vec::new();
+    while let Some(result) = tasks.join_next().await {
+        match result? {
+            Ok((connection, elapsed)) => {
+                connections.push(connection);
+                joins.push(elapsed);
+            }
+            Err(e) => eprintln!("❌ Client could not join its room: {}", e),
+        }
+    }
+    println!("🚪 {} join(s) in {:?} ({})", joins.len(), started.elapsed(), summary(joins));
+
+    // 3. Everybody talks at once, each room fanning the messages out to its members,
+    // while newcomers log in: their password hashing should not slow the conversations down
+    let started = Instant::now();
+    let newcomers = {
+        let settings = settings.clone();
+        tokio::spawn(async move { connect_all(&settings, settings.clients..settings.clients + settings.logins).await })
+    };
+    let mut tasks = JoinSet::new();
+    for connection in connections {
+        tasks.spawn(chat(connection, settings.messages));
+    }
+    let (mut latencies, mut sent, mut received, mut rate_limited) = (Vec::new(), 0, 0, 0);
+    while let Some(result) = tasks.join_next().await {
+        match result? {
+            Ok(report) => {
+                latencies.extend(report.latencies);
+                sent += report.sent;
+                received += report.received;
+                rate_limited += report.rate_limited;
+            }
+            Err(e) => eprintln!("❌ Client failed while chatting: {}", e),
+        }
+    }
+    let elapsed = started.elapsed().as_secs_f64();
+    println!(
+        "💬 {} message(s) sent, {} delivered in {:.2}s: {:.0} sent/s, {:.0} delivered/s",
+        sent,
+        received,
+        elapsed,
+        sent as f64 / elapsed,
+        received as f64 / elapsed,
+    );
+    println!("⏱️ Latency: {}", summary(latencies));
+    if settings.logins > 0 {
+        let (_, logins) = newcomers.await?;
+        println!("🔐 {} login(s) during the chat ({})", logins.len(), summary(logins));
+    }
+    if rate_limited > 0 {
+        println!("⚠️ {} message(s) rate-limited: raise the server's --chat-burst and --chat-rate", rate_limited);
+    }
+    Ok(())
+}
@@ -0,0 +1,514 @@
+// src/bin/client/commands.rs
+// Slash commands: each one is declared once with its arguments, help text and handler
+
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use tp8::fichiers::{sanitize_filename, IncomingFile};
+use tp8::protocole::{FileTarget, MessageKind, PresenceStatus};
+use uuid::Uuid;
+
+use crate::{ClientCommand, ClientLocalState};
+
+/// Where received files are written
+const DOWNLOAD_DIR: &str = "downloads";
+
+/// Messages shown by /history when no count is given
+const DEFAULT_HISTORY: usize = 20;
+
+/// A positional argument, named as it appears in the usage
+pub enum Arg {
+    /// A word that must be given
+    Word(&'static str),
+    /// A word that may be left out
+    OptionalWord(&'static str),
+    /// The rest of the line, spaces included; must not be empty
+    Text(&'static str),
+    /// The rest of the line, possibly empty
+    OptionalText(&'static str),
+}
+
+/// An option that may appear anywhere among the arguments
+pub enum Opt {
+    Flag(&'static str),
+    /// An option followed by its value, named as in the usage
+    Value(&'static str, &'static str),
+}
+
+/// Turns validated arguments into a command; the error is shown to the user
+type Handler = fn(&Arguments, &mut ClientLocalState) -> Result<ClientCommand, String>;
+
+pub struct Command {
+    pub name: &'static str,
+    pub args: &'static [Arg],
+    pub options: &'static [Opt],
+    pub help: &'static str,
+    handler: Handler,
+}
+
+impl Command {
+    /// `/name <required> [optional] [--option <value>]`, as shown by /help
+    pub fn usage(&self) -> String {
+        let mut usage = self.name.to_string();
+        for arg in self.args {
+            match arg {
+                Arg::Word(name) | Arg::Text(name) => usage.push_str(&format!(" <{}>", name)),
+                Arg::OptionalWord(name) | Arg::OptionalText(name) => usage.push_str(&format!(" [{}]", name)),
+            }
+        }
+        for option in self.options {
+            match option {
+                Opt::Flag(flag) => usage.push_str(&format!(" [{}]", flag)),
+                Opt::Value(flag, value) => usage.push_str(&format!(" [{} <{}>]", flag, value)),
+            }
+        }
+        usage
+    }
+
+    /// Checks `input` (the line after the command name) against the declared arguments
+    fn arguments(&self, input: &str) -> Result<Arguments, String> {
+        let mut arguments = Arguments { command: self.name, values: Vec::new(), flags: Vec::new() };
+        let usage = || format!("Usage: {}", self.usage());
+
+        // Options first: what remains holds the positional arguments
+        let remaining = if self.options.is_empty() {
+            input.trim().to_string()
+        } else {
+            let mut words = input.split_whitespace();
+            let mut kept = Vec::new();
+            while let Some(word) = words.next() {
+                match self.options.iter().find(|option| matches!(option, Opt::Flag(flag) | Opt::Value(flag, _) if *flag == word)) {
+                    Some(Opt::Flag(flag)) => arguments.flags.push(flag),
+                    Some(Opt::Value(flag, value)) => match words.next() {
+                        Some(given) => arguments.values.push((flag, given.to_string())),
+                        None => return Err(format!("{} requires <{}>. {}", flag, value, usage())),
+                    },
+                    None => kept.push(word),
+                }
+            }
+            kept.join(" ")
+        };
+
+        let mut rest = remaining.as_str();
+        for arg in self.args {
+            match arg {
+                Arg::Word(name) | Arg::OptionalWord(name) => {
+                    let (word, after) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+                    if word.is_empty() {
+                        if let Arg::Word(_) = arg {
+                            return Err(format!("Missing <{}>. {}", name, usage()));
+                        }
+                    } else {
+                        arguments.values.push((name, word.to_string()));
+                    }
+                    rest = after.trim_start();
+                }
+                Arg::Text(name) | Arg::OptionalText(name) => {
+                    if rest.is_empty() {
+                        if let Arg::Text(_) = arg {
+                            return Err(format!("Missing <{}>. {}", name, usage()));
+                        }
+                    } else {
+                        arguments.values.push((name, rest.to_string()));
+                    }
+                    rest = "";
+                }
+            }
+        }
+        if !rest.is_empty() {
+            return Err(format!("Too many arguments. {}", usage()));
+        }
+        Ok(arguments)
+    }
+}
+
+/// Arguments of a command, checked against its declaration
+pub struct Arguments {
+    /// Name of the command, for handlers shared by several commands
+    pub command: &'static str,
+    values: Vec<(&'static str, String)>,
+    flags: Vec<&'static str>,
+}
+
+impl Arguments {
+    /// Value of an argument or option; always present for a required argument
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.values.iter().find(|(arg, _)| *arg == name).map(|(_, value)| value.as_str())
+    }
+
+    /// Value of a required argument
+    pub fn value(&self, name: &str) -> &str {
+        self.get(name).unwrap_or_default()
+    }
+
+    pub fn flag(&self, flag: &str) -> bool {
+        self.flags.contains(&flag)
+    }
+
+    /// Value of an argument converted to `T`, if it was given
+    pub fn parse<T: FromStr>(&self, name: &str) -> Result<Option<T>, String> {
+        self.get(name)
+            .map(|value| value.parse().map_err(|_| format!("Invalid {}: {}", name, value)))
+            .transpose()
+    }
+
+    /// A message number as shown next to messages, with or without its `#`
+    fn sequence(&self, name: &str) -> Result<u64, String> {
+        let value = self.value(name);
+        value.trim_start_matches('#').parse().map_err(|_| format!("Invalid message number: {}", value))
+    }
+}
+
+/// Every command the client understands, in the order /help lists them
+pub const COMMANDS: &[Command] = &[
+    Command { name: "/register", args: &[Arg::Word("username"), Arg::Word("password")], options: &[], help: "Create an account and log in", handler: authenticate },
+    Command { name: "/login", args: &[Arg::Word("username"), Arg::Word("password")], options: &[], help: "Log in", handler: authenticate },
+    Command {
+        name: "/join",
+        args: &[Arg::Word("room_id"), Arg::OptionalWord("password")],
+        options: &[Opt::Flag("--wait"), Opt::Flag("--spectate")],
+        help: "Enter a room, leaving the current one; --wait queues for a full room, --spectate only reads it",
+        handler: |args, _| {
+            Ok(ClientCommand::JoinRoom {
+                room_id: args.value("room_id").to_string(),
+                password: args.get("password").map(String::from),
+                wait: args.flag("--wait"),
+                spectator: args.flag("--spectate"),
+            })
+        },
+    },
+    Command { name: "/leave", args: &[], options: &[], help: "Leave the current room", handler: |_, _| Ok(ClientCommand::LeaveRoom) },
+    Command {
+        name: "/msg",
+        args: &[Arg::Text("message")],
+        options: &[],
+        help: "Send a message to the current room, like plain text",
+        handler: |args, _| Ok(ClientCommand::SendMessage(args.value("message").to_string(), MessageKind::Text)),
+    },
+    Command {
+        name: "/me",
+        args: &[Arg::Text("action")],
+        options: &[],
+        help: "Describe what you are doing",
+        handler: |args, _| Ok(ClientCommand::SendMessage(args.value("action").to_string(), MessageKind::Action)),
+    },
+    Command { name: "/code", args: &[Arg::Word("language"), Arg::Text("code")], options: &[], help: "Send code; - for no language, \\n starts a new line", handler: code },
+    Command {
+        name: "/announce",
+        args: &[Arg::Text("text")],
+        options: &[],
+        help: "Make an announcement (room administrators)",
+        handler: |args, _| Ok(ClientCommand::SendMessage(args.value("text").to_string(), MessageKind::Announcement)),
+    },
+    Command {
+        name: "/priv",
+        args: &[Arg::Word("username"), Arg::Text("message")],
+        options: &[],
+        help: "Send a private message, kept until the user comes back if they are offline",
+        handler: |args, _| Ok(ClientCommand::PrivateMessage(args.value("username").to_string(), args.value("message").to_string())),
+    },
+    Command { name: "/rooms", args: &[], options: &[], help: "List the rooms", handler: |_, _| Ok(ClientCommand::ListRooms) },
+    Command { name: "/users", args: &[], options: &[], help: "List the users online", handler: |_, _| Ok(ClientCommand::ListUsers) },
+    Command {
+        name: "/history",
+        args: &[Arg::OptionalWord("count")],
+        options: &[],
+        help: "Show the last messages of the current room",
+        handler: |args, _| Ok(ClientCommand::GetHistory(args.parse("count")?.unwrap_or(DEFAULT_HISTORY))),
+    },
+    Command { name: "/typing", args: &[], options: &[], help: "Tell the room you are typing", handler: |_, _| Ok(ClientCommand::Typing) },
+    Command {
+        name: "/status",
+        args: &[Arg::Word("online|away|busy")],
+        options: &[],
+        help: "Set your presence",
+        handler: |args, _| Ok(ClientCommand::SetPresence(args.value("online|away|busy").parse::<PresenceStatus>()?)),
+    },
+    Command {
+        name: "/create",
+        args: &[Arg::Word("room_id"), Arg::OptionalText("name")],
+        options: &[Opt::Value("--password", "password"), Opt::Flag("--invite-only"), Opt::Value("--max-users", "n")],
+        help: "Create a room, protected by a password or open on invitation only, with a user limit",
+        handler: |args, _| {
+            Ok(ClientCommand::CreateRoom {
+                room_id: args.value("room_id").to_string(),
+                name: args.get("name").unwrap_or_default().to_string(),
+                password: args.get("--password").map(String::from),
+                invite_only: args.flag("--invite-only"),
+                max_users: args.parse("--max-users")?,
+            })
+        },
+    },
+    Command {
+        name: "/delete",
+        args: &[Arg::Word("room_id")],
+        options: &[],
+        help: "Delete a room you created",
+        handler: |args, _| Ok(ClientCommand::DeleteRoom(args.value("room_id").to_string())),
+    },
+    Command {
+        name: "/invite",
+        args: &[Arg::Word("room_id"), Arg::Word("username")],
+        options: &[],
+        help: "Invite a user into an invitation-only room",
+        handler: |args, _| Ok(ClientCommand::InviteUser(args.value("room_id").to_string(), args.value("username").to_string())),
+    },
+    Command { name: "/kick", args: &[Arg::Word("room_id"), Arg::Word("username")], options: &[], help: "Send a user out of a room", handler: moderate },
+    Command {
+        name: "/ban",
+        args: &[Arg::Word("room_id"), Arg::Word("username"), Arg::OptionalWord("seconds")],
+        options: &[],
+        help: "Ban a user from a room, for good without a duration",
+        handler: moderate,
+    },
+    Command {
+        name: "/mute",
+        args: &[Arg::Word("room_id"), Arg::Word("username"), Arg::OptionalWord("seconds")],
+        options: &[],
+        help: "Stop a user from writing in a room, for good without a duration",
+        handler: moderate,
+    },
+    Command { name: "/promote", args: &[Arg::Word("room_id"), Arg::Word("username")], options: &[], help: "Let a spectator write in a room", handler: moderate },
+    Command {
+        name: "/topic",
+        args: &[Arg::Word("room_id"), Arg::OptionalText("topic")],
+        options: &[],
+        help: "Set the topic of a room; no topic clears it",
+        handler: |args, _| Ok(ClientCommand::SetTopic(args.value("room_id").to_string(), args.get("topic").unwrap_or_default().to_string())),
+    },
+    Command { name: "/pin", args: &[Arg::Word("room_id"), Arg::Word("message_number")], options: &[], help: "Pin a message of a room", handler: pin },
+    Command { name: "/unpin", args: &[Arg::Word("room_id"), Arg::Word("message_number")], options: &[], help: "Unpin a message", handler: pin },
+    Command {
+        name: "/edit",
+        args: &[Arg::Word("message_number"), Arg::Text("new text")],
+        options: &[],
+        help: "Change one of your messages in the current room",
+        handler: |args, _| Ok(ClientCommand::EditMessage(args.sequence("message_number")?, args.value("new text").to_string())),
+    },
+    Command {
+        name: "/reply",
+        args: &[Arg::Word("message_number"), Arg::Text("message")],
+        options: &[],
+        help: "Answer a message of the current room, in its thread",
+        handler: |args, _| Ok(ClientCommand::Reply(args.sequence("message_number")?, args.value("message").to_string())),
+    },
+    Command {
+        name: "/thread",
+        args: &[Arg::Word("message_number")],
+        options: &[],
+        help: "Show the thread a message of the current room belongs to",
+        handler: |args, _| Ok(ClientCommand::GetThread(args.sequence("message_number")?)),
+    },
+    Command {
+        name: "/erase",
+        args: &[Arg::Word("message_number")],
+        options: &[],
+        help: "Delete one of your messages in the current room",
+        handler: |args, _| Ok(ClientCommand::EraseMessage(args.sequence("message_number")?)),
+    },
+    Command {
+        name: "/pins",
+        args: &[Arg::OptionalWord("room_id")],
+        options: &[],
+        help: "Show the pinned messages of a room, the current one by default",
+        handler: |args, _| Ok(ClientCommand::GetPins(args.get("room_id").map(String::from))),
+    },
+    Command {
+        name: "/search",
+        args: &[Arg::Text("text | /regex/")],
+        options: &[],
+        help: "Search the history of the current room",
+        handler: |args, _| Ok(ClientCommand::Search(args.value("text | /regex/").to_string())),
+    },
+    Command {
+        name: "/profile",
+        args: &[Arg::OptionalWord("username")],
+        options: &[],
+        help: "Show a user's profile, your own by default",
+        handler: |args, _| Ok(ClientCommand::GetProfile(args.get("username").map(String::from))),
+    },
+    Command {
+        name: "/whois",
+        args: &[Arg::Word("username")],
+        options: &[],
+        help: "Show who a user is: their profile",
+        handler: |args, _| Ok(ClientCommand::GetProfile(Some(args.value("username").to_string()))),
+    },
+    Command {
+        name: "/setprofile",
+        args: &[Arg::Word("name|avatar|room|mentions|bell"), Arg::OptionalText("value")],
+        options: &[],
+        help: "Change your profile; no value clears a field, on/off for mentions and bell",
+        handler: set_profile,
+    },
+    Command {
+        name: "/block",
+        args: &[Arg::Word("username")],
+        options: &[],
+        help: "Stop receiving a user's private messages and mentions",
+        handler: |args, _| Ok(ClientCommand::Block(args.value("username").to_string(), true)),
+    },
+    Command {
+        name: "/unblock",
+        args: &[Arg::Word("username")],
+        options: &[],
+        help: "Receive a blocked user's private messages and mentions again",
+        handler: |args, _| Ok(ClientCommand::Block(args.value("username").to_string(), false)),
+    },
+    Command { name: "/stats", args: &[], options: &[], help: "Show the server's activity (server admins only)", handler: |_, _| Ok(ClientCommand::GetStats) },
+    Command { name: "/sendfile", args: &[Arg::Word("username|#room_id"), Arg::Text("path")], options: &[], help: "Offer a file to a user or a room", handler: send_file },
+    Command { name: "/accept", args: &[Arg::Word("transfer_id")], options: &[], help: "Receive a file offered to you", handler: accept_file },
+    Command {
+        name: "/help",
+        args: &[Arg::OptionalWord("command")],
+        options: &[],
+        help: "List the commands, or describe one",
+        handler: |args, _| Ok(ClientCommand::Help(args.get("command").map(String::from))),
+    },
+    Command { name: "/quit", args: &[], options: &[], help: "Disconnect and quit", handler: |_, _| Ok(ClientCommand::Disconnect) },
+    Command { name: "/ping", args: &[], options: &[], help: "Check the connection to the server", handler: |_, _| Ok(ClientCommand::Ping) },
+];
+
+/// Turns a line starting with `/` into a command; the error is shown to the user
+pub fn parse(line: &str, client_state: &mut ClientLocalState) -> Result<ClientCommand, String> {
+    let (name, input) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+    let command = find(name).ok_or_else(|| format!("Unknown command: {} (/help lists the commands)", name))?;
+    let arguments = command.arguments(input)?;
+    (command.handler)(&arguments, client_state)
+}
+
+fn find(name: &str) -> Option<&'static Command> {
+    COMMANDS.iter().find(|command| command.name == name)
+}
+
+/// Lines shown by /help: every command, or the description of one
+pub fn help(topic: Option<&str>) -> Vec<String> {
+    let Some(topic) = topic else {
+        let mut lines = vec!["Enter your commands (plain text is sent to the current room):".to_string()];
+        lines.extend(COMMANDS.iter().map(|command| format!("  {}", command.usage())));
+        lines.push("/help <command> describes a command".to_string());
+        lines.push("------------------------------------".to_string());
+        return lines;
+    };
+    let name = format!("/{}", topic.trim_start_matches('/'));
+    match find(&name) {
+        Some(command) => vec![command.usage(), format!("  {}", command.help)],
+        None => vec![format!("Unknown command: {} (/help lists the commands)", name)],
+    }
+}
+
+fn authenticate(args: &Arguments, _: &mut ClientLocalState) -> Result<ClientCommand, String> {
+    Ok(ClientCommand::Authenticate {
+        username: args.value("username").to_string(),
+        password: args.value("password").to_string(),
+        register: args.command == "/register",
+    })
+}
+
+fn code(args: &Arguments, _: &mut ClientLocalState) -> Result<ClientCommand, String> {
+    let language = Some(args.value("language")).filter(|language| *language != "-").map(String::from);
+    Ok(ClientCommand::SendMessage(args.value("code").replace("\\n", "\n"), MessageKind::Code { language }))
+}
+
+fn moderate(args: &Arguments, _: &mut ClientLocalState) -> Result<ClientCommand, String> {
+    Ok(ClientCommand::Moderate {
+        action: args.command.to_string(),
+        room_id: args.value("room_id").to_string(),
+        username: args.value("username").to_string(),
+        duration: args.parse("seconds")?,
+    })
+}
+
+fn pin(args: &Arguments, _: &mut ClientLocalState) -> Result<ClientCommand, String> {
+    Ok(ClientCommand::Pin {
+        room_id: args.value("room_id").to_string(),
+        sequence: args.sequence("message_number")?,
+        pin: args.command == "/pin",
+    })
+}
+
+fn set_profile(args: &Arguments, client_state: &mut ClientLocalState) -> Result<ClientCommand, String> {
+    // Updated here rather than on the server's answer, so that several changes in a row add up
+    let Some(profile) = client_state.profile.as_mut() else {
+        return Err("Log in first".to_string());
+    };
+    let mut changed = profile.clone();
+    changed.set(args.value("name|avatar|room|mentions|bell"), args.get("value").unwrap_or_default())?;
+    changed.validate()?;
+    *profile = changed.clone();
+    Ok(ClientCommand::UpdateProfile(changed))
+}
+
+fn send_file(args: &Arguments, client_state: &mut ClientLocalState) -> Result<ClientCommand, String> {
+    let target = args.value("username|#room_id");
+    let target = match target.strip_prefix('#') {
+        Some(room_id) => FileTarget::Room(room_id.to_string()),
+        None => FileTarget::User(target.to_string()),
+    };
+    let path = PathBuf::from(args.value("path"));
+    offer_file(client_state, target, &path).map_err(|e| format!("Cannot send {}: {}", path.display(), e))
+}
+
+/// Prepares a file offer; the file is remembered until a recipient accepts it
+fn offer_file(client_state: &mut ClientLocalState, target: FileTarget, path: &Path) -> Result<ClientCommand, String> {
+    let metadata = std::fs::metadata(path).map_err(|e| e.to_string())?;
+    if !metadata.is_file() {
+        return Err("not a regular file".to_string());
+    }
+    let filename = path.file_name().and_then(|name| name.to_str()).and_then(sanitize_filename)
+        .ok_or("invalid file name")?;
+
+    let transfer_id = Uuid::new_v4().to_string();
+    client_state.outgoing.insert(transfer_id.clone(), path.to_path_buf());
+    Ok(ClientCommand::FileOffer { transfer_id, target, filename, size: metadata.len() })
+}
+
+fn accept_file(args: &Arguments, client_state: &mut ClientLocalState) -> Result<ClientCommand, String> {
+    let transfer_id = args.value("transfer_id");
+    let Some((filename, size)) = client_state.offers.remove(transfer_id) else {
+        return Err(format!("No pending file offer with id {}", transfer_id));
+    };
+    let incoming = IncomingFile::create(DOWNLOAD_DIR, &filename, size).map_err(|e| format!("Cannot receive {}: {}", filename, e))?;
+    client_state.incoming.insert(transfer_id.to_string(), incoming);
+    Ok(ClientCommand::FileAccept(transfer_id.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn command(name: &str) -> &'static Command {
+        find(name).unwrap()
+    }
+
+    #[test]
+    fn test_arguments() {
+        let args = command("/priv").arguments("bob  bonjour  à toi").unwrap();
+        assert_eq!(args.get("username"), Some("bob"));
+        assert_eq!(args.get("message"), Some("bonjour  à toi"));
+
+        let args = command("/join").arguments("secret --wait pass").unwrap();
+        assert_eq!((args.get("room_id"), args.get("password"), args.flag("--wait")), (Some("secret"), Some("pass"), true));
+
+        let args = command("/create").arguments("jeux --max-users 5 Salon des jeux --invite-only").unwrap();
+        assert_eq!(args.get("name"), Some("Salon des jeux"));
+        assert_eq!(args.parse::<usize>("--max-users"), Ok(Some(5)));
+        assert!(args.flag("--invite-only") && args.get("--password").is_none());
+    }
+
+    #[test]
+    fn test_invalid_arguments() {
+        assert_eq!(command("/invite").arguments("salon").err().unwrap(), "Missing <username>. Usage: /invite <room_id> <username>");
+        assert_eq!(command("/leave").arguments("maintenant").err().unwrap(), "Too many arguments. Usage: /leave");
+        assert!(command("/create").arguments("jeux --password").err().unwrap().starts_with("--password requires <password>"));
+        let args = command("/ban").arguments("salon bob jamais").unwrap();
+        assert_eq!(args.parse::<u64>("seconds"), Err("Invalid seconds: jamais".to_string()));
+    }
+
+    #[test]
+    fn test_help() {
+        assert!(COMMANDS.iter().all(|command| command.name.starts_with('/') && !command.help.is_empty()));
+        assert_eq!(help(Some("whois")), vec!["/whois <username>".to_string(), "  Show who a user is: their profile".to_string()]);
+        assert!(help(None).contains(&"  /create <room_id> [name] [--password <password>] [--invite-only] [--max-users <n>]".to_string()));
+    }
+}
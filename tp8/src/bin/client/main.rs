@@ -0,0 +1,869 @@
+// src/bin/client/main.rs
+// Client de messagerie utilisant le protocole SCP
+
+mod commands;
+mod ui;
+
+use tokio::net::TcpStream;
+use tokio::io::AsyncReadExt;
+use tokio::sync::mpsc;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use sha2::{Digest, Sha256};
+
+// Import elements from the `protocole` module
+use tp8::protocole::{
+    PROTOCOL_VERSION, MIN_PROTOCOL_VERSION, Message, ProtocolFrame,
+    ClientId, RoomId, MessageId, MessageKind, SessionState, PresenceStatus, FileTarget, extract_mentions
+};
+use tp8::trame::{read_frame, write_frame, FrameError};
+use tp8::chiffrement::{self, Transport};
+use tp8::fichiers::{decode_chunk, encode_chunk, sha256_hex, IncomingFile, FILE_CHUNK_SIZE};
+use tp8::profils::UserProfile;
+use tp7_dns::client::{ClientDns, SERVEUR_PAR_DEFAUT};
+use ui::Ui;
+
+/// Number of results asked for by /search
+const SEARCH_LIMIT: usize = 20;
+
+/// Server address used without --discover
+const DEFAULT_SERVER_ADDR: &str = "127.0.0.1:9999";
+
+/// SRV name under which tp7 announces the SCP server
+const SCP_SERVICE: &str = "_scp._tcp.local";
+
+/// Client local state
+struct ClientLocalState {
+    id: Option<ClientId>,
+    username: Option<String>,
+    current_room: Option<RoomId>,
+    session_state: SessionState,
+    outgoing: HashMap<String, PathBuf>, // transfer_id -> file offered, streamed on the first acceptance
+    offers: HashMap<String, (String, u64)>, // transfer_id -> (filename, size) offered to us, not accepted yet
+    incoming: HashMap<String, IncomingFile>, // transfer_id -> file being received
+    message_ids: HashMap<u64, MessageId>, // sequence -> id of the current room's messages seen, for /edit, /erase and /reply
+    profile: Option<UserProfile>, // Our own profile, sent by the server at login
+    labels: HashMap<String, String>, // username -> how to show them, for users whose profile we have seen
+}
+
+impl ClientLocalState {
+    fn new() -> Self {
+        Self {
+            id: None,
+            username: None,
+            current_room: None,
+            session_state: SessionState::Connected,
+            outgoing: HashMap::new(),
+            offers: HashMap::new(),
+            incoming: HashMap::new(),
+            message_ids: HashMap::new(),
+            profile: None,
+            labels: HashMap::new(),
+        }
+    }
+
+    fn update_state(&mut self, new_state: SessionState) {
+        self.session_state = new_state;
+    }
+
+    /// How to show a user: with their display name and avatar if we know them
+    fn label(&self, username: &str) -> String {
+        self.labels.get(username).cloned().unwrap_or_else(|| username.to_string())
+    }
+
+    /// Whether the user asked for the terminal bell on private messages and mentions
+    fn bell(&self) -> bool {
+        self.profile.as_ref().is_some_and(|profile| profile.notifications.bell)
+    }
+}
+
+/// Server address announced by tp7's SRV record; the DNS client blocks, so it runs off the async workers
+async fn discover_server(dns: SocketAddr) -> Result<SocketAddr, Box<dyn std::error::Error>> {
+    let client = ClientDns::new(dns);
+    let addr = tokio::task::spawn_blocking(move || client.decouvrir(SCP_SERVICE))
+        .await?
+        .map_err(|e| format!("discovery of {} through {} failed: {}", SCP_SERVICE, dns, e))?;
+    Ok(addr)
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    println!("👋 === CLIENT DE MESSAGERIE (SCP v{}) ===", PROTOCOL_VERSION);
+
+    // Options: `client [--tls] [--ca <cert.pem> | --insecure] [--server-name <name>] [--plain] [--discover [--dns <ip:port>]]`
+    let usage = "usage: client [--tls] [--ca <cert.pem> | --insecure] [--server-name <name>] [--plain] [--discover [--dns <ip:port>]]";
+    let mut plain = false;
+    let mut tls = false;
+    let mut ca: Option<PathBuf> = None;
+    let mut insecure = false;
+    let mut server_name = "localhost".to_string();
+    let mut discover = false;
+    let mut dns: SocketAddr = SERVEUR_PAR_DEFAUT.parse()?;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--tls" => tls = true,
+            "--ca" => ca = Some(args.next().ok_or("--ca requires a path")?.into()),
+            "--insecure" => insecure = true, // Accept self-signed certificates without checking them
+            "--server-name" => server_name = args.next().ok_or("--server-name requires a name")?,
+            "--plain" => plain = true, // Line-by-line output instead of the terminal UI
+            "--discover" => discover = true, // Ask tp7 for the server address instead of using the default one
+            "--dns" => dns = args.next().ok_or("--dns requires an address")?.parse()?,
+            other => return Err(format!("Unknown option: {} ({})", other, usage).into()),
+        }
+    }
+    tls |= ca.is_some() || insecure;
+
+    let addr: SocketAddr = if discover {
+        let addr = discover_server(dns).await?;
+        println!("Serveur découvert via {} ({}) : {}", dns, SCP_SERVICE, addr);
+        addr
+    } else {
+        DEFAULT_SERVER_ADDR.parse()?
+    };
+    println!("Tentative de connexion au serveur sur {}", addr);
+
+    let tcp = TcpStream::connect(addr).await?;
+    let stream: Box<dyn Transport> = if tls {
+        let connector = chiffrement::connector(ca.as_deref(), insecure)?;
+        Box::new(connector.connect(chiffrement::server_name(&server_name)?, tcp).await?)
+    } else {
+        Box::new(tcp)
+    };
+    println!("✅ Connecté au serveur sur {}{}", addr, if tls { " (TLS)" } else { "" });
+
+    // Display: terminal UI on a terminal, plain output otherwise; typed lines arrive on `rx_lines`
+    let (tx_lines, mut rx_lines) = mpsc::unbounded_channel::<String>();
+    let (ui, display) = ui::start(ui::use_tui(plain), tx_lines);
+
+    // Split stream into read and write halves for concurrent operations
+    let (mut reader, mut writer) = tokio::io::split(stream);
+
+    // Versions this client speaks; the server answers with HelloAck or VersionMismatch
+    let hello = Message::Hello { min_version: MIN_PROTOCOL_VERSION, max_version: PROTOCOL_VERSION };
+    write_frame(&mut writer, &ProtocolFrame::new(hello, None, 0)).await?;
+
+    // Channel for internal client messages (e.g., from command input to sender task)
+    let (tx_commands, mut rx_commands) = mpsc::unbounded_channel::<ClientCommand>();
+
+    // Shared state for the client (Arc<RwLock<...>>)
+    let client_state = Arc::new(RwLock::new(ClientLocalState::new()));
+    let client_state_for_sender = Arc::clone(&client_state);
+    let ui_for_sender = ui.clone();
+
+    // --- Sender Task ---
+    // Reads commands from `rx_commands` and sends them over the network
+    let send_task = tokio::spawn(async move {
+        while let Some(command) = rx_commands.recv().await {
+            let current_client_state = client_state_for_sender.read().await;
+
+            let frame = match process_client_command(command, &current_client_state) {
+                Ok(f) => f,
+                Err(e) => {
+                    ui_for_sender.line(format!("Client command error: {}", e));
+                    continue;
+                }
+            };
+
+            if let Err(e) = write_frame(&mut writer, &frame).await {
+                ui_for_sender.line(format!("❌ Error writing message to server: {}. Connection lost.", e));
+                break;
+            }
+        }
+        ui_for_sender.line("⚙️ Send task finished.");
+    });
+
+    // --- Reader Task ---
+    // Reads incoming messages from the network and prints them
+    let client_state_for_reader = Arc::clone(&client_state);
+    let tx_replies = tx_commands.clone(); // Automatic replies: heartbeats and delivery acknowledgements
+    let ui_for_reader = ui.clone();
+    let receive_task = tokio::spawn(async move {
+        loop {
+            match read_frame(&mut reader).await {
+                // Server heartbeat: answer silently
+                Ok(Some(frame)) if frame.message == Message::Ping => {
+                    let _ = tx_replies.send(ClientCommand::Pong);
+                }
+                Ok(Some(frame)) => {
+                    let ack = delivery_ack(&frame.message);
+                    handle_server_message(frame, &client_state_for_reader, &tx_replies, &ui_for_reader).await;
+                    if let Some(ack) = ack {
+                        let _ = tx_replies.send(ack);
+                    }
+                }
+                Ok(None) => {
+                    ui_for_reader.line("🔌 Server closed the connection.");
+                    break;
+                }
+                // A malformed frame is skipped, the next one is still readable
+                Err(FrameError::Invalid(e)) => {
+                    ui_for_reader.line(format!("❌ Deserialization error from server: {}", e));
+                }
+                Err(e) => {
+                    ui_for_reader.line(format!("❌ Error reading from server: {}", e));
+                    break;
+                }
+            }
+        }
+        ui_for_reader.line("⚙️ Receive task finished.");
+    });
+
+    // --- Input Loop ---
+    // Lines typed by the user (terminal UI or stdin) become commands for `tx_commands`
+    for line in commands::help(None) {
+        ui.line(line);
+    }
+    ui.prompt();
+
+    loop {
+        let Some(line) = rx_lines.recv().await else { // EOF, stdin closed
+            ui.line("EOF received from stdin. Quitting...");
+            break;
+        };
+        let line = line.trim();
+        if line.is_empty() {
+            ui.prompt();
+            continue;
+        }
+        let cmd = match parse_input(line, &client_state).await {
+            Ok(cmd) => cmd,
+            Err(e) => {
+                ui.line(e);
+                ui.prompt();
+                continue;
+            }
+        };
+        match cmd {
+            ClientCommand::Disconnect => {
+                ui.line("Quitting...");
+                tx_commands.send(ClientCommand::Disconnect)?; // Send disconnect message to server
+                break; // Exit input loop
+            }
+            ClientCommand::Help(topic) => {
+                for line in commands::help(topic.as_deref()) {
+                    ui.line(line);
+                }
+                ui.prompt();
+                continue;
+            }
+            // The server does not confirm leaving a room
+            ClientCommand::LeaveRoom => ui.room(None, None, Vec::new()),
+            _ => {}
+        }
+
+        if tx_commands.send(cmd).is_err() {
+            ui.line("Error sending command to sender task. Server connection might be closed.");
+            break;
+        }
+        ui.prompt();
+    }
+
+    // Give the terminal back first, then wait for the tasks to complete cleanup
+    ui.quit();
+    let _ = display.await;
+    let _ = send_task.await;
+    let _ = receive_task.await;
+
+    println!("Client disconnected. Goodbye!");
+    Ok(())
+}
+
+/// Internal commands for the client
+enum ClientCommand {
+    Authenticate { username: String, password: String, register: bool },
+    JoinRoom { room_id: String, password: Option<String>, wait: bool, spectator: bool }, // wait if full
+    LeaveRoom,
+    SendMessage(String, MessageKind),
+    PrivateMessage(String, String),
+    ListRooms,
+    ListUsers,
+    GetHistory(usize),
+    Typing,
+    SetPresence(PresenceStatus),
+    CreateRoom { room_id: String, name: String, password: Option<String>, invite_only: bool, max_users: Option<usize> },
+    DeleteRoom(String),
+    InviteUser(String, String),
+    Moderate { action: String, room_id: String, username: String, duration: Option<u64> },
+    SetTopic(String, String),
+    Pin { room_id: String, sequence: u64, pin: bool },
+    EditMessage(u64, String), // in the current room
+    EraseMessage(u64),
+    Reply(u64, String),      // in the current room
+    GetThread(u64),
+    GetPins(Option<String>), // None = current room
+    Search(String),          // in the current room
+    GetProfile(Option<String>), // None = our own
+    UpdateProfile(UserProfile),
+    Block(String, bool), // username, false to unblock
+    GetStats,
+    Help(Option<String>), // shown locally, never sent
+    Disconnect,
+    Ping,
+    Pong,
+    Ack(RoomId, u64),
+    MarkRead(RoomId, u64), // shown in the current room
+    FileOffer { transfer_id: String, target: FileTarget, filename: String, size: u64 },
+    FileAccept(String),
+    FileChunk { transfer_id: String, seq: u64, data: String },
+    FileComplete { transfer_id: String, sha256: String },
+}
+
+/// Streams an accepted file as FileChunk commands, then FileComplete with its SHA-256
+async fn stream_file(path: PathBuf, transfer_id: String, replies: mpsc::UnboundedSender<ClientCommand>, ui: Ui) {
+    let mut file = match tokio::fs::File::open(&path).await {
+        Ok(file) => file,
+        Err(e) => {
+            ui.line(format!("[FILE] Cannot open {}: {}", path.display(), e));
+            return;
+        }
+    };
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; FILE_CHUNK_SIZE];
+    let mut seq = 0;
+    loop {
+        let read = match file.read(&mut buffer).await {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(e) => {
+                ui.line(format!("[FILE] Error reading {}: {}", path.display(), e));
+                return;
+            }
+        };
+        hasher.update(&buffer[..read]);
+        let chunk = ClientCommand::FileChunk { transfer_id: transfer_id.clone(), seq, data: encode_chunk(&buffer[..read]) };
+        if replies.send(chunk).is_err() {
+            return;
+        }
+        seq += 1;
+    }
+    let _ = replies.send(ClientCommand::FileComplete { transfer_id, sha256: sha256_hex(hasher) });
+    ui.line(format!("[FILE] {} sent ({} chunk(s)).", path.display(), seq));
+}
+
+/// Acknowledgement to send back for room messages, up to the highest sequence received
+fn delivery_ack(message: &Message) -> Option<ClientCommand> {
+    let (room_id, sequence) = match message {
+        Message::RoomMessage { room_id, sequence, .. } => (room_id, *sequence),
+        Message::History { room_id, messages } => (room_id, messages.iter().map(|e| e.sequence).max()?),
+        _ => return None,
+    };
+    // Sequence 0 means the server does not number its messages
+    (sequence > 0).then(|| ClientCommand::Ack(room_id.clone(), sequence))
+}
+
+/// Turns a line typed by the user into a command; the error is shown to the user
+async fn parse_input(line: &str, client_state: &Arc<RwLock<ClientLocalState>>) -> Result<ClientCommand, String> {
+    // Plain text goes to the current room
+    if !line.starts_with('/') {
+        return Ok(ClientCommand::SendMessage(line.to_string(), MessageKind::Text));
+    }
+    commands::parse(line, &mut *client_state.write().await)
+}
+
+/// Id of a message of the current room, from the number shown next to it
+fn message_id_of(client_state: &ClientLocalState, sequence: u64) -> Result<MessageId, String> {
+    if client_state.current_room.is_none() {
+        return Err("You are not in a room".to_string());
+    }
+    client_state.message_ids.get(&sequence).cloned()
+        .ok_or_else(|| format!("Message #{} was not received in this room since you joined it", sequence))
+}
+
+/// Processes a client command and converts it into a ProtocolFrame
+fn process_client_command(
+    command: ClientCommand,
+    client_state: &ClientLocalState,
+) -> Result<ProtocolFrame, String> {
+    let message = match command {
+        ClientCommand::Authenticate { username, password, register: true } => Message::Register { username, password },
+        ClientCommand::Authenticate { username, password, register: false } => Message::Login { username, password },
+        ClientCommand::JoinRoom { room_id, password, wait, spectator } => Message::JoinRoom { room_id, password, wait, spectator },
+        ClientCommand::LeaveRoom => Message::LeaveRoom,
+        ClientCommand::SendMessage(content, kind) => Message::SendMessage { content, kind, parent_message_id: None },
+        ClientCommand::Reply(sequence, content) => Message::SendMessage {
+            content,
+            kind: MessageKind::Text,
+            parent_message_id: Some(message_id_of(client_state, sequence)?),
+        },
+        ClientCommand::GetThread(sequence) => Message::GetThread { message_id: message_id_of(client_state, sequence)? },
+        ClientCommand::PrivateMessage(target_user, content) => Message::PrivateMessage { target_user, content },
+        ClientCommand::ListRooms => Message::ListRooms,
+        ClientCommand::ListUsers => Message::ListUsers,
+        ClientCommand::GetHistory(count) => Message::GetHistory { count },
+        ClientCommand::Typing => match &client_state.current_room {
+            Some(room_id) => Message::Typing { room_id: room_id.clone() },
+            None => return Err("You are not in a room".to_string()),
+        },
+        ClientCommand::SetPresence(status) => Message::PresenceUpdate { status },
+        ClientCommand::CreateRoom { room_id, name, password, invite_only, max_users } => {
+            Message::CreateRoom { room_id, name, password, invite_only, max_users }
+        }
+        ClientCommand::DeleteRoom(room_id) => Message::DeleteRoom { room_id },
+        ClientCommand::InviteUser(room_id, username) => Message::InviteUser { room_id, username },
+        ClientCommand::Moderate { action, room_id, username, duration } => match action.as_str() {
+            "/kick" => Message::KickUser { room_id, username },
+            "/ban" => Message::BanUser { room_id, username, duration },
+            "/mute" => Message::MuteUser { room_id, username, duration },
+            "/promote" => Message::PromoteUser { room_id, username },
+            other => return Err(format!("Unknown moderation command: {}", other)),
+        },
+        ClientCommand::SetTopic(room_id, topic) => Message::SetTopic { room_id, topic },
+        ClientCommand::Pin { room_id, sequence, pin: true } => Message::PinMessage { room_id, sequence },
+        ClientCommand::Pin { room_id, sequence, pin: false } => Message::UnpinMessage { room_id, sequence },
+        ClientCommand::EditMessage(sequence, new_content) => Message::EditMessage {
+            message_id: message_id_of(client_state, sequence)?,
+            new_content,
+        },
+        ClientCommand::EraseMessage(sequence) => Message::DeleteMessage { message_id: message_id_of(client_state, sequence)? },
+        ClientCommand::GetPins(room_id) => match room_id.or_else(|| client_state.current_room.clone()) {
+            Some(room_id) => Message::GetPins { room_id },
+            None => return Err("You are not in a room".to_string()),
+        },
+        ClientCommand::Search(query) => match &client_state.current_room {
+            Some(room_id) => Message::SearchHistory { room_id: room_id.clone(), query, limit: SEARCH_LIMIT },
+            None => return Err("You are not in a room".to_string()),
+        },
+        ClientCommand::GetProfile(username) => Message::GetProfile { username },
+        ClientCommand::UpdateProfile(profile) => Message::UpdateProfile { profile },
+        ClientCommand::Block(username, true) => Message::BlockUser { username },
+        ClientCommand::Block(username, false) => Message::UnblockUser { username },
+        ClientCommand::GetStats => Message::GetStats,
+        ClientCommand::Help(_) => return Err("/help is not sent to the server".to_string()),
+        ClientCommand::Disconnect => Message::Disconnect,
+        ClientCommand::Ping => Message::Ping,
+        ClientCommand::Pong => Message::Pong,
+        ClientCommand::Ack(room_id, sequence) => Message::MessageAck { room_id, sequence },
+        ClientCommand::MarkRead(room_id, sequence) => Message::MarkRead { room_id, sequence },
+        ClientCommand::FileOffer { transfer_id, target, filename, size } => {
+            Message::FileOffer { transfer_id, target, filename, size, from: None }
+        }
+        ClientCommand::FileAccept(transfer_id) => Message::FileAccept { transfer_id, from: None },
+        ClientCommand::FileChunk { transfer_id, seq, data } => Message::FileChunk { transfer_id, seq, data },
+        ClientCommand::FileComplete { transfer_id, sha256 } => Message::FileComplete { transfer_id, sha256 },
+    };
+
+    let session_id = client_state.id.clone();
+    let sequence = 0; // Client doesn't track sequence numbers for outgoing requests in this simple example
+
+    Ok(ProtocolFrame::new(message, session_id, sequence))
+}
+
+/// Handles incoming messages from the server
+async fn handle_server_message(
+    frame: ProtocolFrame,
+    client_state: &Arc<RwLock<ClientLocalState>>,
+    replies: &mpsc::UnboundedSender<ClientCommand>,
+    ui: &Ui,
+) {
+    let mut state = client_state.write().await;
+
+    match frame.message {
+        Message::HelloAck { version } => {
+            ui.line(format!("[SERVER] Protocol SCP v{}", version));
+        }
+        Message::VersionMismatch { min_version, max_version, message } => {
+            ui.line(format!("[SERVER ERROR] Incompatible protocol (server speaks v{} to v{}): {}", min_version, max_version, message));
+        }
+        Message::ConnectAck { client_id, message } => {
+            state.id = Some(client_id.clone());
+            state.username = Some(message.split("Bienvenue, ").last().unwrap_or("unknown").trim_end_matches('!').trim().to_string());
+            let username = state.username.clone().unwrap_or_default();
+            state.update_state(SessionState::Authenticated(username));
+            ui.line(format!("[SERVER] {}", message));
+            ui.line(format!("Your Client ID: {}", client_id));
+            ui.line(format!("You are now authenticated as: {}", state.username.as_ref().unwrap_or(&"N/A".to_string())));
+        }
+        Message::DisconnectAck => {
+            ui.line("[SERVER] Disconnected.");
+        }
+        Message::ConnectError { code, reason } => {
+            // The connection stays open: the user can retry /login or /register
+            ui.line(format!("[SERVER ERROR] Authentication failed ({:?}): {}", code, reason));
+        }
+        Message::JoinRoomAck { room_id, users, topic } => {
+            state.current_room = Some(room_id.clone());
+            state.message_ids.clear();
+            if let Some(username) = state.username.clone() {
+                state.update_state(SessionState::InRoom(username, room_id.clone()));
+            }
+            ui.line(format!("[SERVER] Joined room: #{}", room_id));
+            if let Some(topic) = &topic {
+                ui.line(format!("Topic: {}", topic));
+            }
+            ui.line(format!("Users in #{}: {}", room_id, users.join(", ")));
+            ui.room(Some(&room_id), topic, users);
+        }
+        Message::JoinRoomError { reason } => {
+            ui.line(format!("[SERVER ERROR] Failed to join room: {}", reason));
+        }
+        Message::RoomQueued { room_id, position } => {
+            ui.line(format!("[SERVER] ⏳ #{} is full: you are number {} in line, you will enter when a place frees up.", room_id, position));
+        }
+        Message::CreateRoomAck { room_id, name } => {
+            ui.line(format!("[SERVER] Room #{} ({}) created. You are its admin.", room_id, name));
+        }
+        Message::CreateRoomError { reason } => {
+            ui.line(format!("[SERVER ERROR] Failed to create room: {}", reason));
+        }
+        Message::DeleteRoomAck { room_id } => {
+            ui.line(format!("[SERVER] Room #{} deleted.", room_id));
+        }
+        Message::DeleteRoomError { reason } => {
+            ui.line(format!("[SERVER ERROR] Failed to delete room: {}", reason));
+        }
+        Message::InviteUserAck { room_id, username } => {
+            ui.line(format!("[SERVER] {} can now join #{}.", username, room_id));
+        }
+        Message::RoomInvitation { room_id, from } => {
+            ui.line(format!("[SERVER] {} invited you to #{} (/join {}).", from, room_id, room_id));
+        }
+        Message::UserKicked { room_id, username, by } => {
+            leave_room_if_target(&mut state, &room_id, &username, ui);
+            ui.line(format!("[ROOM #{}] {} was kicked by {}.", room_id, username, by));
+        }
+        Message::UserBanned { room_id, username, by, until } => {
+            leave_room_if_target(&mut state, &room_id, &username, ui);
+            match until {
+                Some(until) => ui.line(format!("[ROOM #{}] {} was banned by {} until {}.", room_id, username, by, until.format("%d/%m %H:%M:%S"))),
+                None => ui.line(format!("[ROOM #{}] {} was banned by {}.", room_id, username, by)),
+            }
+        }
+        Message::UserMuted { room_id, username, by, until } => {
+            match until {
+                Some(until) => ui.line(format!("[ROOM #{}] {} was muted by {} until {}.", room_id, username, by, until.format("%d/%m %H:%M:%S"))),
+                None => ui.line(format!("[ROOM #{}] {} was muted by {}.", room_id, username, by)),
+            }
+        }
+        Message::UserPromoted { room_id, username, by } => {
+            if state.username.as_deref() == Some(username.as_str()) {
+                ui.line(format!("[ROOM #{}] {} let you write in the room.", room_id, by));
+            } else {
+                ui.line(format!("[ROOM #{}] {} was promoted by {}.", room_id, username, by));
+            }
+        }
+        Message::RoomDeleted { room_id } => {
+            if state.current_room.as_deref() == Some(room_id.as_str()) {
+                state.current_room = None;
+                if let Some(username) = state.username.clone() {
+                    state.update_state(SessionState::Authenticated(username));
+                }
+                ui.room(None, None, Vec::new());
+            }
+            ui.line(format!("[SERVER] Room #{} has been deleted.", room_id));
+        }
+        Message::TopicChanged { room_id, topic, by } => {
+            match &topic {
+                Some(topic) => ui.line(format!("[ROOM #{}] {} set the topic: {}", room_id, by, topic)),
+                None => ui.line(format!("[ROOM #{}] {} cleared the topic.", room_id, by)),
+            }
+            if state.current_room.as_deref() == Some(room_id.as_str()) {
+                ui.topic(topic);
+            }
+        }
+        Message::MessagePinned { room_id, entry, by } => {
+            ui.line(format!("[ROOM #{}] {} pinned #{} from {}: {}", room_id, by, entry.sequence, entry.from, entry.content));
+        }
+        Message::MessageUnpinned { room_id, sequence, by } => {
+            ui.line(format!("[ROOM #{}] {} unpinned #{}.", room_id, by, sequence));
+        }
+        Message::Pins { room_id, messages } => {
+            ui.line(format!("[SERVER] {} pinned message(s) in #{}:", messages.len(), room_id));
+            for entry in messages {
+                ui.line(format!("  #{} <{}> {}: {}", entry.sequence, entry.timestamp.format("%d/%m %H:%M:%S"), entry.from, entry.content));
+            }
+        }
+        Message::SearchResults { room_id, query, messages } => {
+            ui.line(format!("[SERVER] {} message(s) matching \"{}\" in #{}:", messages.len(), query, room_id));
+            for entry in messages {
+                ui.line(format!("  #{} <{}> {}: {}", entry.sequence, entry.timestamp.format("%d/%m %H:%M:%S"), entry.from, entry.content));
+            }
+        }
+        Message::UserJoined { username, room_id, display_name, avatar } => {
+            let label = UserProfile { display_name, avatar, ..UserProfile::default() }.label(&username);
+            state.labels.insert(username.clone(), label.clone());
+            ui.line(format!("[ROOM #{}] {} has joined.", room_id, label));
+            if state.current_room.as_deref() == Some(room_id.as_str()) {
+                ui.user_joined(&username);
+            }
+        }
+        Message::UserLeft { username, room_id } => {
+            ui.line(format!("[ROOM #{}] {} has left.", room_id, username));
+            if state.current_room.as_deref() == Some(room_id.as_str()) {
+                ui.user_left(&username);
+            }
+        }
+        Message::RoomMessage { from, content, timestamp, room_id, sequence, message_id, kind, parent_message_id, .. } => {
+            // A reply shows the number of the message it answers, when we have seen it
+            let reply_to = parent_message_id.map(|parent| {
+                state.message_ids.iter()
+                    .find(|(_, id)| **id == parent)
+                    .map_or(" ↪".to_string(), |(parent_sequence, _)| format!(" ↪ #{}", parent_sequence))
+            });
+            if state.current_room.as_deref() == Some(room_id.as_str()) {
+                if !message_id.is_empty() {
+                    state.message_ids.insert(sequence, message_id);
+                }
+                if sequence > 0 {
+                    let _ = replies.send(ClientCommand::MarkRead(room_id.clone(), sequence));
+                }
+            }
+            // The number is what /pin, /edit, /erase, /reply and /thread expect
+            let prefix = format!("[#{} #{}{}]", room_id, sequence, reply_to.unwrap_or_default());
+            let mentioned = mentions_me(&state, &content);
+            if mentioned && state.bell() {
+                ui.bell();
+            }
+            let highlight = kind == MessageKind::Announcement || mentioned;
+            for line in render_message(&prefix, &timestamp.format("%H:%M:%S").to_string(), &state.label(&from), &content, &kind) {
+                if highlight {
+                    ui.highlight(line);
+                } else {
+                    ui.line(line);
+                }
+            }
+        }
+        Message::Mention { room_id, from, content, timestamp, .. } => {
+            // In the room itself the RoomMessage is already highlighted
+            if state.current_room.as_deref() != Some(room_id.as_str()) {
+                if state.bell() {
+                    ui.bell();
+                }
+                ui.highlight(format!(
+                    "[MENTION in #{}] <{}> {}: {}",
+                    room_id, timestamp.format("%d/%m %H:%M:%S"), from, content
+                ));
+            }
+        }
+        Message::PrivateMessageReceived { from, content, timestamp } => {
+            if state.bell() {
+                ui.bell();
+            }
+            ui.line(format!("[PRIVATE from {}] <{}>: {}", state.label(&from), timestamp.format("%H:%M:%S"), content));
+        }
+        Message::Profile { username, profile } => {
+            state.labels.insert(username.clone(), profile.label(&username));
+            if state.username.as_deref() != Some(username.as_str()) {
+                ui.line(format!("[SERVER] Profile of {}", profile.label(&username)));
+                return;
+            }
+            // The first one arrives right after login: go to the default room, if any
+            let login = state.profile.replace(profile.clone()).is_none();
+            if login {
+                if let Some(room_id) = profile.default_room.filter(|_| state.current_room.is_none()) {
+                    ui.line(format!("[CLIENT] Joining your default room #{}...", room_id));
+                    let _ = replies.send(ClientCommand::JoinRoom { room_id, password: None, wait: false, spectator: false });
+                }
+                return;
+            }
+            let on_off = |on: bool| if on { "on" } else { "off" };
+            ui.line(format!("[SERVER] Your profile: {}", profile.label(&username)));
+            ui.line(format!("  default room: {}", profile.default_room.as_deref().map_or("none".to_string(), |room_id| format!("#{}", room_id))));
+            ui.line(format!("  mentions: {}, bell: {}", on_off(profile.notifications.mentions), on_off(profile.notifications.bell)));
+            if !profile.blocked.is_empty() {
+                ui.line(format!("  blocked: {}", profile.blocked.iter().cloned().collect::<Vec<_>>().join(", ")));
+            }
+        }
+        Message::FileOffer { transfer_id, target, filename, size, from } => {
+            let to = match target {
+                FileTarget::User(_) => "you".to_string(),
+                FileTarget::Room(room_id) => format!("#{}", room_id),
+            };
+            ui.line(format!(
+                "[FILE] {} offers {} ({} bytes) to {}. Type /accept {} to receive it.",
+                from.as_deref().unwrap_or("someone"), filename, size, to, transfer_id
+            ));
+            state.offers.insert(transfer_id, (filename, size));
+        }
+        Message::FileAccept { transfer_id, from } => {
+            let from = from.unwrap_or_else(|| "someone".to_string());
+            // Streaming starts on the first acceptance; later ones are refused by the server
+            if let Some(path) = state.outgoing.remove(&transfer_id) {
+                ui.line(format!("[FILE] {} accepted {}, sending...", from, path.display()));
+                tokio::spawn(stream_file(path, transfer_id, replies.clone(), ui.clone()));
+            }
+        }
+        Message::FileChunk { transfer_id, data, .. } => {
+            let Some(incoming) = state.incoming.get_mut(&transfer_id) else {
+                return;
+            };
+            if let Err(e) = decode_chunk(&data).and_then(|bytes| incoming.write_chunk(&bytes)) {
+                ui.line(format!("[FILE] Transfer {} failed: {}", transfer_id, e));
+                if let Some(incoming) = state.incoming.remove(&transfer_id) {
+                    incoming.abort();
+                }
+            }
+        }
+        Message::FileComplete { transfer_id, sha256 } => {
+            if let Some(incoming) = state.incoming.remove(&transfer_id) {
+                match incoming.finish(&sha256) {
+                    Ok(path) => ui.line(format!("[FILE] Received {} (SHA-256 verified).", path.display())),
+                    Err(e) => ui.line(format!("[FILE] Transfer {} failed: {}", transfer_id, e)),
+                }
+            }
+        }
+        Message::FileAborted { transfer_id, reason } => {
+            state.offers.remove(&transfer_id);
+            state.outgoing.remove(&transfer_id);
+            if let Some(incoming) = state.incoming.remove(&transfer_id) {
+                incoming.abort();
+            }
+            ui.line(format!("[FILE] Transfer {} aborted: {}", transfer_id, reason));
+        }
+        Message::ServerShutdown { reason, grace_seconds } => {
+            ui.line(format!("[SERVER] Shutting down in {}s: {}", grace_seconds, reason));
+        }
+        Message::ServerAnnouncement { message } => {
+            ui.line(format!("[SERVER] 📢 {}", message));
+        }
+        Message::ClockSkew { offset_secs } => {
+            let direction = if offset_secs > 0 { "ahead" } else { "behind" };
+            ui.line(format!("[SERVER] ⏰ Your clock is {}s {} of the server's: your messages get the server time.", offset_secs.abs(), direction));
+        }
+        Message::PrivateMessageQueued { target_user } => {
+            ui.line(format!("[SERVER] {} is offline; your message will be delivered at their next login.", target_user));
+        }
+        Message::PendingMessages { count, senders } => {
+            ui.line(format!("[SERVER] {} private message(s) or mention(s) received while you were away:", count));
+            for (from, n) in senders {
+                ui.line(format!("  - {} from {}", n, from));
+            }
+        }
+        Message::RoomList { rooms, unread } => {
+            ui.line("[SERVER] Available Rooms:");
+            if rooms.is_empty() {
+                ui.line("  No rooms available.");
+            } else {
+                for (room_id, user_count) in rooms {
+                    match unread.get(&room_id).filter(|count| **count > 0) {
+                        Some(count) => ui.line(format!("  - #{} ({} users, {} unread)", room_id, user_count, count)),
+                        None => ui.line(format!("  - #{} ({} users)", room_id, user_count)),
+                    }
+                }
+            }
+        }
+        Message::UserList { users, room_id, statuses, spectators } => {
+            if state.current_room.as_deref() == Some(room_id.as_str()) {
+                ui.users(users.clone());
+            }
+            ui.line(format!("[SERVER] Users in #{}:", room_id));
+            if users.is_empty() {
+                ui.line("  No users in this room.");
+            } else {
+                for user in users {
+                    let status = statuses.get(&user).copied().unwrap_or_default();
+                    let spectator = if spectators.contains(&user) { ", spectator" } else { "" };
+                    ui.line(format!("  - {} ({}{})", user, status, spectator));
+                }
+            }
+        }
+        Message::UserTyping { room_id, username } => {
+            ui.line(format!("[ROOM #{}] {} is typing…", room_id, username));
+        }
+        Message::UserPresence { username, status } => {
+            ui.line(format!("[SERVER] {} is now {}.", username, status));
+        }
+        Message::History { room_id, messages } => {
+            ui.line(format!("[SERVER] Last {} message(s) in #{}:", messages.len(), room_id));
+            let current = state.current_room.as_deref() == Some(room_id.as_str());
+            if let Some(sequence) = messages.iter().map(|e| e.sequence).max().filter(|sequence| current && *sequence > 0) {
+                let _ = replies.send(ClientCommand::MarkRead(room_id.clone(), sequence));
+            }
+            for entry in messages {
+                let prefix = format!("  #{}{}", entry.sequence, if entry.edited_at.is_some() { " (edited)" } else { "" });
+                let time = entry.timestamp.format("%d/%m %H:%M:%S").to_string();
+                for line in render_message(&prefix, &time, &state.label(&entry.from), &entry.content, &entry.kind) {
+                    ui.line(line);
+                }
+                if current && !entry.id.is_empty() {
+                    state.message_ids.insert(entry.sequence, entry.id);
+                }
+            }
+        }
+        Message::Thread { room_id, messages, .. } => {
+            ui.line(format!("[SERVER] Thread of {} message(s) in #{}:", messages.len(), room_id));
+            for entry in messages {
+                let indent = if entry.parent_id.is_some() { "    ↪ " } else { "  " };
+                let prefix = format!("{}#{}{}", indent, entry.sequence, if entry.edited_at.is_some() { " (edited)" } else { "" });
+                let time = entry.timestamp.format("%d/%m %H:%M:%S").to_string();
+                for line in render_message(&prefix, &time, &state.label(&entry.from), &entry.content, &entry.kind) {
+                    ui.line(line);
+                }
+            }
+        }
+        Message::Stats { stats } => {
+            ui.line(format!(
+                "[SERVER] Up {}s, {} connection(s), {} active user(s) in the last 5 minutes",
+                stats.uptime_secs, stats.connected, stats.active_users,
+            ));
+            let mut rooms: Vec<_> = stats.rooms.iter().collect();
+            rooms.sort_by(|a, b| a.0.cmp(b.0));
+            for (room_id, room) in rooms {
+                ui.line(format!(
+                    "  #{}: {} member(s), {} active, {} message(s) ({}/min), {:.0} bytes on average",
+                    room_id, room.members, room.active_users, room.messages, room.messages_per_minute, room.average_size,
+                ));
+            }
+            let mut errors: Vec<String> = stats.errors.iter().map(|(code, count)| format!("{:?} ×{}", code, count)).collect();
+            if !errors.is_empty() {
+                errors.sort();
+                ui.line(format!("  Errors sent: {}", errors.join(", ")));
+            }
+        }
+        Message::MessageEdited { room_id, sequence, new_content, by, .. } => {
+            ui.line(format!("[#{} #{}] ✏️ {} edited the message: {}", room_id, sequence, by, new_content));
+        }
+        Message::MessageDeleted { room_id, sequence, by, .. } => {
+            if state.current_room.as_deref() == Some(room_id.as_str()) {
+                state.message_ids.remove(&sequence);
+            }
+            ui.line(format!("[#{} #{}] 🗑️ {} deleted the message.", room_id, sequence, by));
+        }
+        Message::Error { code, message } => {
+            ui.line(format!("[SERVER ERROR] Code: {:?}, Message: {}", code, message));
+        }
+        Message::Pong => {
+            ui.line("[SERVER] Pong!");
+        }
+        // Client should not receive these message types directly as responses
+        _ => {
+            ui.line(format!("[SERVER] Received unexpected message type: {:?}", frame.message));
+        }
+    }
+}
+
+/// Whether a room message mentions the local user (@username)
+/// Lines showing a room message according to its kind; `prefix` tells where it comes from
+fn render_message(prefix: &str, time: &str, from: &str, content: &str, kind: &MessageKind) -> Vec<String> {
+    match kind {
+        MessageKind::Text => vec![format!("{} <{}> {}: {}", prefix, time, from, content)],
+        MessageKind::Action => vec![format!("{} <{}> * {} {}", prefix, time, from, content)],
+        MessageKind::Announcement => vec![format!("{} <{}> 📣 {} announces: {}", prefix, time, from, content)],
+        MessageKind::Code { language } => {
+            // Fenced like Markdown: the language is a hint for terminals and tools that highlight code
+            let mut lines = vec![format!("{} <{}> {}:", prefix, time, from)];
+            lines.push(format!("```{}", language.as_deref().unwrap_or("")));
+            lines.extend(content.lines().map(str::to_string));
+            lines.push("```".to_string());
+            lines
+        }
+    }
+}
+
+fn mentions_me(state: &ClientLocalState, content: &str) -> bool {
+    state.username.as_ref().is_some_and(|username| extract_mentions(content).contains(username))
+}
+
+/// Kicks and bans remove their target from the room: keep the local state in sync
+fn leave_room_if_target(state: &mut ClientLocalState, room_id: &str, target: &str, ui: &Ui) {
+    if state.username.as_deref() == Some(target) && state.current_room.as_deref() == Some(room_id) {
+        state.current_room = None;
+        state.update_state(SessionState::Authenticated(target.to_string()));
+        ui.room(None, None, Vec::new());
+    } else if state.current_room.as_deref() == Some(room_id) {
+        ui.user_left(target);
+    }
+}
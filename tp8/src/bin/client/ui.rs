@@ -0,0 +1,396 @@
+// src/bin/client/ui.rs
+// Client display: prompt-style output, or a terminal UI with separate panes
+
+use std::collections::{BTreeSet, VecDeque};
+use std::io::{self, BufRead, IsTerminal, Write};
+
+use crossterm::event::{Event, EventStream, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use futures_util::StreamExt;
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style, Stylize};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, List, Paragraph};
+use ratatui::Frame;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use unicode_width::UnicodeWidthChar;
+
+/// Terminal colors used to highlight messages mentioning us in plain mode
+const HIGHLIGHT: &str = "\x1b[1;33m";
+const RESET: &str = "\x1b[0m";
+
+/// Messages kept in the terminal UI
+const MAX_LINES: usize = 1000;
+
+/// Width of the user list pane
+const USERS_WIDTH: u16 = 24;
+
+/// Lines scrolled by PageUp / PageDown
+const SCROLL_STEP: usize = 10;
+
+/// What the rest of the client asks the display to show
+enum UiEvent {
+    Line { text: String, highlight: bool },
+    Room { room_id: Option<String>, topic: Option<String>, users: Vec<String> },
+    Users(Vec<String>),
+    Topic(Option<String>),
+    UserJoined(String),
+    UserLeft(String),
+    Prompt,
+    Entered, // The user pressed Enter: the prompt line is used up
+    Quit,
+}
+
+/// Handle shared by every task that displays something
+#[derive(Clone)]
+pub struct Ui {
+    events: mpsc::UnboundedSender<UiEvent>,
+}
+
+impl Ui {
+    pub fn line(&self, text: impl Into<String>) {
+        let _ = self.events.send(UiEvent::Line { text: text.into(), highlight: false });
+    }
+
+    /// A line that concerns the user directly (mentions)
+    pub fn highlight(&self, text: impl Into<String>) {
+        let _ = self.events.send(UiEvent::Line { text: text.into(), highlight: true });
+    }
+
+    /// Ring the terminal bell; it is not drawn, so it goes straight to the terminal in both displays
+    pub fn bell(&self) {
+        let mut stdout = io::stdout();
+        let _ = stdout.write_all(b"\x07");
+        let _ = stdout.flush();
+    }
+
+    /// Entered or left a room (`None`): the room, its topic and its members
+    pub fn room(&self, room_id: Option<&str>, topic: Option<String>, users: Vec<String>) {
+        let _ = self.events.send(UiEvent::Room { room_id: room_id.map(str::to_string), topic, users });
+    }
+
+    /// Members of the current room
+    pub fn users(&self, users: Vec<String>) {
+        let _ = self.events.send(UiEvent::Users(users));
+    }
+
+    pub fn topic(&self, topic: Option<String>) {
+        let _ = self.events.send(UiEvent::Topic(topic));
+    }
+
+    pub fn user_joined(&self, username: &str) {
+        let _ = self.events.send(UiEvent::UserJoined(username.to_string()));
+    }
+
+    pub fn user_left(&self, username: &str) {
+        let _ = self.events.send(UiEvent::UserLeft(username.to_string()));
+    }
+
+    /// The user's line has been handled: plain mode shows the prompt again
+    pub fn prompt(&self) {
+        let _ = self.events.send(UiEvent::Prompt);
+    }
+
+    /// Close the display (the terminal UI restores the terminal)
+    pub fn quit(&self) {
+        let _ = self.events.send(UiEvent::Quit);
+    }
+}
+
+/// The terminal UI needs a terminal on both ends; piped input keeps the plain display
+pub fn use_tui(plain: bool) -> bool {
+    !plain && io::stdin().is_terminal() && io::stdout().is_terminal()
+}
+
+/// Start the display; every line typed by the user is sent to `lines`
+pub fn start(tui: bool, lines: mpsc::UnboundedSender<String>) -> (Ui, JoinHandle<()>) {
+    let (tx, rx) = mpsc::unbounded_channel();
+    let display = if tui {
+        tokio::spawn(async move {
+            if let Err(e) = run_tui(rx, lines).await {
+                eprintln!("Terminal UI error: {}", e);
+            }
+        })
+    } else {
+        // Blocking reads on a thread of their own: a pending read never delays exiting
+        let entered = tx.clone();
+        std::thread::spawn(move || {
+            for line in io::stdin().lock().lines() {
+                match line {
+                    Ok(line) => {
+                        let _ = entered.send(UiEvent::Entered);
+                        if lines.send(line).is_err() {
+                            break;
+                        }
+                    }
+                    Err(e) => {
+                        eprintln!("Error reading input: {}", e);
+                        break;
+                    }
+                }
+            }
+        });
+        tokio::spawn(run_plain(rx))
+    };
+    (Ui { events: tx }, display)
+}
+
+/// Plain display: lines are printed as they come, then the prompt is shown again
+async fn run_plain(mut events: mpsc::UnboundedReceiver<UiEvent>) {
+    let mut prompt_shown = false;
+    while let Some(event) = events.recv().await {
+        match event {
+            UiEvent::Line { text, highlight } => {
+                // Incoming lines start below a prompt that is already displayed
+                if prompt_shown {
+                    println!();
+                    prompt_shown = false;
+                }
+                if highlight {
+                    println!("{}{}{}", HIGHLIGHT, text, RESET);
+                } else {
+                    println!("{}", text);
+                }
+                if events.is_empty() {
+                    print!("> ");
+                    prompt_shown = true;
+                }
+            }
+            UiEvent::Prompt if !prompt_shown => {
+                print!("> ");
+                prompt_shown = true;
+            }
+            UiEvent::Entered => prompt_shown = false,
+            UiEvent::Quit => break,
+            _ => {}
+        }
+        let _ = io::stdout().flush();
+    }
+}
+
+/// Terminal UI: header, message stream, user list and input line
+async fn run_tui(mut events: mpsc::UnboundedReceiver<UiEvent>, lines: mpsc::UnboundedSender<String>) -> io::Result<()> {
+    let mut terminal = ratatui::init();
+    let mut screen = Screen::default();
+    let mut keys = EventStream::new();
+
+    let result = loop {
+        if let Err(e) = terminal.draw(|frame| screen.draw(frame)) {
+            break Err(e);
+        }
+        tokio::select! {
+            event = events.recv() => {
+                let Some(event) = event else { break Ok(()) };
+                if !screen.apply(event) {
+                    break Ok(());
+                }
+                // Apply whatever else is queued before drawing again
+                while let Ok(event) = events.try_recv() {
+                    if !screen.apply(event) {
+                        break;
+                    }
+                }
+                if screen.closed {
+                    break Ok(());
+                }
+            }
+            key = keys.next() => match key {
+                Some(Ok(Event::Key(key))) if key.kind == KeyEventKind::Press => {
+                    if let Some(line) = screen.on_key(key) {
+                        if lines.send(line).is_err() {
+                            break Ok(());
+                        }
+                    }
+                }
+                Some(Ok(_)) => {} // Resize and others: redrawn on the next turn
+                Some(Err(e)) => break Err(e),
+                None => break Ok(()),
+            },
+        }
+    };
+    ratatui::restore();
+    result
+}
+
+/// Everything the terminal UI displays
+#[derive(Default)]
+struct Screen {
+    lines: VecDeque<(String, bool)>, // (text, highlighted)
+    room_id: Option<String>,
+    topic: Option<String>,
+    users: BTreeSet<String>,
+    input: Vec<char>,
+    cursor: usize,              // position in `input`
+    scroll: usize,              // screen lines scrolled up from the latest message
+    history: Vec<String>,       // lines already entered, for Up / Down
+    history_pos: Option<usize>,
+    closed: bool,
+}
+
+impl Screen {
+    /// Returns false once the display must close
+    fn apply(&mut self, event: UiEvent) -> bool {
+        match event {
+            UiEvent::Line { text, highlight } => {
+                for line in text.lines() {
+                    if self.lines.len() == MAX_LINES {
+                        self.lines.pop_front();
+                    }
+                    self.lines.push_back((line.to_string(), highlight));
+                }
+            }
+            UiEvent::Room { room_id, topic, users } => {
+                self.room_id = room_id;
+                self.topic = topic;
+                self.users = users.into_iter().collect();
+            }
+            UiEvent::Users(users) => self.users = users.into_iter().collect(),
+            UiEvent::Topic(topic) => self.topic = topic,
+            UiEvent::UserJoined(username) => {
+                self.users.insert(username);
+            }
+            UiEvent::UserLeft(username) => {
+                self.users.remove(&username);
+            }
+            UiEvent::Prompt | UiEvent::Entered => {}
+            UiEvent::Quit => self.closed = true,
+        }
+        !self.closed
+    }
+
+    /// Edit the input line; returns the line once Enter is pressed
+    fn on_key(&mut self, key: KeyEvent) -> Option<String> {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            // Raw mode swallows Ctrl+C: treat it as /quit
+            KeyCode::Char('c') | KeyCode::Char('d') if ctrl => return Some("/quit".to_string()),
+            KeyCode::Char('u') if ctrl => {
+                self.input.drain(..self.cursor);
+                self.cursor = 0;
+            }
+            KeyCode::Char(c) => {
+                self.input.insert(self.cursor, c);
+                self.cursor += 1;
+            }
+            KeyCode::Backspace if self.cursor > 0 => {
+                self.cursor -= 1;
+                self.input.remove(self.cursor);
+            }
+            KeyCode::Delete if self.cursor < self.input.len() => {
+                self.input.remove(self.cursor);
+            }
+            KeyCode::Left => self.cursor = self.cursor.saturating_sub(1),
+            KeyCode::Right => self.cursor = (self.cursor + 1).min(self.input.len()),
+            KeyCode::Home => self.cursor = 0,
+            KeyCode::End => self.cursor = self.input.len(),
+            KeyCode::Esc => {
+                self.input.clear();
+                self.cursor = 0;
+            }
+            KeyCode::Up => self.recall(true),
+            KeyCode::Down => self.recall(false),
+            KeyCode::PageUp => self.scroll += SCROLL_STEP,
+            KeyCode::PageDown => self.scroll = self.scroll.saturating_sub(SCROLL_STEP),
+            KeyCode::Enter => {
+                let line: String = self.input.drain(..).collect();
+                self.cursor = 0;
+                self.scroll = 0;
+                self.history_pos = None;
+                if line.trim().is_empty() {
+                    return None;
+                }
+                self.history.push(line.clone());
+                return Some(line);
+            }
+            _ => {}
+        }
+        None
+    }
+
+    /// Browse the lines already entered
+    fn recall(&mut self, older: bool) {
+        let position = match (self.history_pos, older) {
+            (None, true) => self.history.len().checked_sub(1),
+            (None, false) => None,
+            (Some(i), true) => Some(i.saturating_sub(1)),
+            (Some(i), false) => Some(i + 1).filter(|&i| i < self.history.len()),
+        };
+        self.history_pos = position;
+        self.input = position.map(|i| self.history[i].chars().collect()).unwrap_or_default();
+        self.cursor = self.input.len();
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [header, body, input] = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Min(3),
+            Constraint::Length(3),
+        ]).areas(frame.area());
+        let [messages, users] = Layout::horizontal([Constraint::Min(20), Constraint::Length(USERS_WIDTH)]).areas(body);
+
+        let title = match (&self.room_id, &self.topic) {
+            (Some(room_id), Some(topic)) => format!(" #{} — {}", room_id, topic),
+            (Some(room_id), None) => format!(" #{}", room_id),
+            (None, _) => " Not in a room (/join <room_id>, /help)".to_string(),
+        };
+        frame.render_widget(Paragraph::new(title).style(Style::new().add_modifier(Modifier::REVERSED)), header);
+
+        self.draw_messages(frame, messages);
+
+        let user_list = List::new(self.users.iter().map(String::as_str))
+            .block(Block::bordered().title(format!(" Users ({}) ", self.users.len())));
+        frame.render_widget(user_list, users);
+
+        // The input scrolls horizontally so that the cursor stays visible
+        let width = usize::from(input.width.saturating_sub(2)).max(1);
+        let mut start = 0;
+        while columns(&self.input[start..self.cursor]) >= width {
+            start += 1;
+        }
+        let visible: String = self.input[start..].iter().collect();
+        frame.render_widget(Paragraph::new(visible).block(Block::bordered().title(" > ")), input);
+        let x = input.x + 1 + columns(&self.input[start..self.cursor]) as u16;
+        frame.set_cursor_position((x, input.y + 1));
+    }
+
+    /// Latest messages at the bottom, wrapped to the pane width
+    fn draw_messages(&mut self, frame: &mut Frame, area: Rect) {
+        let width = usize::from(area.width.saturating_sub(2)).max(1);
+        let height = usize::from(area.height.saturating_sub(2));
+
+        let mut rows = Vec::new();
+        for (text, highlight) in &self.lines {
+            let style = if *highlight { Style::new().fg(Color::Yellow).bold() } else { Style::new() };
+            rows.extend(wrap(text, width).into_iter().map(|row| Line::styled(row, style)));
+        }
+        self.scroll = self.scroll.min(rows.len().saturating_sub(height));
+        let end = rows.len() - self.scroll;
+        let visible: Vec<Line> = rows.drain(end.saturating_sub(height)..end).collect();
+
+        let title = if self.scroll > 0 { format!(" Messages (↑{}) ", self.scroll) } else { " Messages ".to_string() };
+        frame.render_widget(Paragraph::new(visible).block(Block::bordered().title(title)), area);
+    }
+}
+
+/// Display width of some characters
+fn columns(chars: &[char]) -> usize {
+    chars.iter().map(|c| c.width().unwrap_or(0)).sum()
+}
+
+/// Cut a line into rows of at most `width` columns
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut rows = Vec::new();
+    let mut row = String::new();
+    let mut used = 0;
+    for c in text.chars() {
+        let w = c.width().unwrap_or(0);
+        if used + w > width && !row.is_empty() {
+            rows.push(std::mem::take(&mut row));
+            used = 0;
+        }
+        row.push(c);
+        used += w;
+    }
+    rows.push(row);
+    rows
+}
@@ -0,0 +1,189 @@
+// src/bin/gateway.rs
+// WebSocket gateway: lets browsers talk SCP to the chat server
+
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
+use futures_util::sink::SinkExt;
+use futures_util::stream::StreamExt;
+use tokio::net::{TcpListener, TcpStream};
+use tokio_tungstenite::accept_async;
+use tokio_tungstenite::tungstenite::Message as WsMessage;
+use tracing::{error, info, warn};
+
+use tp8::chiffrement::{self, Transport};
+use tp8::protocole::{ErrorCode, Message, ProtocolFrame, PROTOCOL_VERSION};
+use tp8::trame::{read_frame, write_frame, FrameError};
+
+/// Address browsers connect to by default
+const DEFAULT_LISTEN_ADDR: &str = "127.0.0.1:9002";
+
+/// Chat server the gateway forwards to by default
+const DEFAULT_SERVER_ADDR: &str = "127.0.0.1:9999";
+
+/// How to reach the chat server
+#[derive(Debug, Clone)]
+struct Upstream {
+    addr: String,
+    tls: bool,
+    ca: Option<PathBuf>,
+    insecure: bool,
+    server_name: String,
+}
+
+impl Upstream {
+    /// One chat server connection per browser, so the server sees a regular client
+    async fn connect(&self) -> Result<Box<dyn Transport>, Box<dyn std::error::Error + Send + Sync>> {
+        let tcp = TcpStream::connect(&self.addr).await?;
+        if !self.tls {
+            return Ok(Box::new(tcp));
+        }
+        let connector = chiffrement::connector(self.ca.as_deref(), self.insecure)?;
+        Ok(Box::new(connector.connect(chiffrement::server_name(&self.server_name)?, tcp).await?))
+    }
+}
+
+/// Browsers may send a full ProtocolFrame or just a Message, which is then wrapped in a frame
+fn parse_browser_frame(text: &str) -> Result<ProtocolFrame, serde_json::Error> {
+    serde_json::from_str::<ProtocolFrame>(text)
+        .or_else(|_| serde_json::from_str::<Message>(text).map(|message| ProtocolFrame::new(message, None, 0)))
+}
+
+/// Error reported to the browser without involving the chat server
+fn error_frame(message: String) -> Option<WsMessage> {
+    let frame = ProtocolFrame::new(Message::Error { code: ErrorCode::InvalidFormat, message }, None, 0);
+    let json = serde_json::to_string(&frame).ok()?;
+    Some(WsMessage::Text(json))
+}
+
+async fn handle_browser(stream: TcpStream, addr: SocketAddr, upstream: Upstream) {
+    let ws_stream = match accept_async(stream).await {
+        Ok(ws_stream) => ws_stream,
+        Err(e) => {
+            warn!("❌ WebSocket handshake failed with {}: {}", addr, e);
+            return;
+        }
+    };
+    let server = match upstream.connect().await {
+        Ok(server) => server,
+        Err(e) => {
+            error!("❌ Cannot reach chat server {} for {}: {}", upstream.addr, addr, e);
+            return;
+        }
+    };
+    info!("🌐 Browser {} bridged to {}", addr, upstream.addr);
+
+    let (mut ws_write, mut ws_read) = ws_stream.split();
+    let (mut server_read, mut server_write) = tokio::io::split(server);
+    let (tx_ws, mut rx_ws) = tokio::sync::mpsc::unbounded_channel::<WsMessage>();
+
+    // Single writer towards the browser: server frames and gateway errors
+    let ws_task = tokio::spawn(async move {
+        while let Some(message) = rx_ws.recv().await {
+            if ws_write.send(message).await.is_err() {
+                break;
+            }
+        }
+        let _ = ws_write.close().await;
+    });
+
+    // Chat server -> browser
+    let tx_server_frames = tx_ws.clone();
+    let mut server_task = tokio::spawn(async move {
+        loop {
+            match read_frame(&mut server_read).await {
+                Ok(Some(frame)) => match serde_json::to_string(&frame) {
+                    Ok(json) => {
+                        if tx_server_frames.send(WsMessage::Text(json)).is_err() {
+                            break;
+                        }
+                    }
+                    Err(e) => error!("❌ Serialization error for {}: {}", addr, e),
+                },
+                Ok(None) => break,
+                Err(FrameError::Invalid(e)) => warn!("❌ Invalid frame from chat server for {}: {}", addr, e),
+                Err(e) => {
+                    warn!("❌ Error reading from chat server for {}: {}", addr, e);
+                    break;
+                }
+            }
+        }
+    });
+
+    // Browser -> chat server
+    let mut browser_task = tokio::spawn(async move {
+        while let Some(message) = ws_read.next().await {
+            let text = match message {
+                Ok(WsMessage::Text(text)) => text,
+                Ok(WsMessage::Binary(bytes)) => String::from_utf8_lossy(&bytes).into_owned(),
+                Ok(WsMessage::Close(_)) | Err(_) => break,
+                Ok(_) => continue, // Ping/Pong are answered by tungstenite
+            };
+            match parse_browser_frame(&text) {
+                Ok(frame) => {
+                    if let Err(e) = write_frame(&mut server_write, &frame).await {
+                        warn!("❌ Error writing to chat server for {}: {}", addr, e);
+                        break;
+                    }
+                }
+                Err(e) => {
+                    if let Some(error) = error_frame(format!("Trame invalide: {}", e)) {
+                        let _ = tx_ws.send(error);
+                    }
+                }
+            }
+        }
+    });
+
+    // Whichever side closes first ends the bridge
+    tokio::select! {
+        _ = &mut server_task => browser_task.abort(),
+        _ = &mut browser_task => server_task.abort(),
+    }
+    let _ = ws_task.await;
+    info!("🔌 Browser {} disconnected", addr);
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let _journal = trace_commun::init_from_env("tp8-gateway");
+    info!("🌐 === SCP WEBSOCKET GATEWAY (SCP v{}) ===", PROTOCOL_VERSION);
+
+    // Options: `gateway [--listen <addr>] [--server <addr>] [--tls] [--ca <cert.pem> | --insecure] [--server-name <name>]`
+    let usage = "usage: gateway [--listen <addr>] [--server <addr>] [--tls] [--ca <cert.pem> | --insecure] [--server-name <name>]";
+    let mut listen_addr = DEFAULT_LISTEN_ADDR.to_string();
+    let mut upstream = Upstream {
+        addr: DEFAULT_SERVER_ADDR.to_string(),
+        tls: false,
+        ca: None,
+        insecure: false,
+        server_name: "localhost".to_string(),
+    };
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--listen" => listen_addr = args.next().ok_or("--listen requires an address")?,
+            "--server" => upstream.addr = args.next().ok_or("--server requires an address")?,
+            "--tls" => upstream.tls = true,
+            "--ca" => upstream.ca = Some(args.next().ok_or("--ca requires a path")?.into()),
+            "--insecure" => upstream.insecure = true,
+            "--server-name" => upstream.server_name = args.next().ok_or("--server-name requires a name")?,
+            other => return Err(format!("Unknown option: {} ({})", other, usage).into()),
+        }
+    }
+    upstream.tls |= upstream.ca.is_some() || upstream.insecure;
+
+    let listener = TcpListener::bind(&listen_addr).await?;
+    info!("📡 Gateway listening on ws://{}", listen_addr);
+    info!("➡️ Forwarding to chat server {}{}", upstream.addr, if upstream.tls { " (TLS)" } else { "" });
+
+    loop {
+        match listener.accept().await {
+            Ok((stream, addr)) => {
+                tokio::spawn(handle_browser(stream, addr, upstream.clone()));
+            }
+            // Too many open files, connection aborted before accept...: the next one may succeed
+            Err(e) => warn!("⚠️ Error accepting a connection: {}", e),
+        }
+    }
+}
@@ -0,0 +1,156 @@
+// src/chiffrement.rs
+// Transport chiffré : configuration TLS (rustls) du serveur et du client
+
+use std::path::Path;
+use std::sync::Arc;
+
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio_rustls::rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use tokio_rustls::rustls::crypto::{self, CryptoProvider};
+use tokio_rustls::rustls::pki_types::pem::PemObject;
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime};
+use tokio_rustls::rustls::{self, ClientConfig, DigitallySignedStruct, RootCertStore, ServerConfig, SignatureScheme};
+use tokio_rustls::{TlsAcceptor, TlsConnector};
+
+/// Flux sur lequel circulent les trames : TCP en clair ou TLS
+pub trait Transport: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> Transport for T {}
+
+fn provider() -> Arc<CryptoProvider> {
+    Arc::new(crypto::ring::default_provider())
+}
+
+fn read_certificates(path: &Path) -> Result<Vec<CertificateDer<'static>>, String> {
+    let certificates = CertificateDer::pem_file_iter(path)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| format!("Lecture de {} impossible: {}", path.display(), e))?;
+    if certificates.is_empty() {
+        return Err(format!("Aucun certificat dans {}", path.display()));
+    }
+    Ok(certificates)
+}
+
+/// Configuration TLS du serveur à partir d'un certificat (chaîne PEM) et de sa clé privée (PEM)
+pub fn acceptor(cert_path: &Path, key_path: &Path) -> Result<TlsAcceptor, String> {
+    let certificates = read_certificates(cert_path)?;
+    let key = PrivateKeyDer::from_pem_file(key_path)
+        .map_err(|e| format!("Lecture de la clé {} impossible: {}", key_path.display(), e))?;
+
+    let config = ServerConfig::builder_with_provider(provider())
+        .with_safe_default_protocol_versions()
+        .map_err(|e| e.to_string())?
+        .with_no_client_auth()
+        .with_single_cert(certificates, key)
+        .map_err(|e| format!("Certificat ou clé invalide: {}", e))?;
+    Ok(TlsAcceptor::from(Arc::new(config)))
+}
+
+/// Configuration TLS du client : certificats de confiance lus dans `ca_path`,
+/// ou aucune vérification du certificat serveur si `insecure` (certificats auto-signés)
+pub fn connector(ca_path: Option<&Path>, insecure: bool) -> Result<TlsConnector, String> {
+    let builder = ClientConfig::builder_with_provider(provider())
+        .with_safe_default_protocol_versions()
+        .map_err(|e| e.to_string())?;
+
+    let config = if insecure {
+        builder
+            .dangerous()
+            .with_custom_certificate_verifier(Arc::new(AcceptAnyCertificate(provider())))
+            .with_no_client_auth()
+    } else {
+        let ca_path = ca_path.ok_or("Un certificat d'autorité (--ca) ou --insecure est nécessaire")?;
+        let mut roots = RootCertStore::empty();
+        for certificate in read_certificates(ca_path)? {
+            roots.add(certificate).map_err(|e| format!("Certificat d'autorité invalide: {}", e))?;
+        }
+        builder.with_root_certificates(roots).with_no_client_auth()
+    };
+    Ok(TlsConnector::from(Arc::new(config)))
+}
+
+/// Nom attendu dans le certificat du serveur
+pub fn server_name(name: &str) -> Result<ServerName<'static>, String> {
+    ServerName::try_from(name.to_string()).map_err(|_| format!("Nom de serveur invalide: {}", name))
+}
+
+/// Accepte n'importe quel certificat serveur ; les signatures de la poignée de main restent vérifiées
+#[derive(Debug)]
+struct AcceptAnyCertificate(Arc<CryptoProvider>);
+
+impl ServerCertVerifier for AcceptAnyCertificate {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        crypto::verify_tls12_signature(message, cert, dss, &self.0.signature_verification_algorithms)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        crypto::verify_tls13_signature(message, cert, dss, &self.0.signature_verification_algorithms)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.0.signature_verification_algorithms.supported_schemes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocole::{Message, ProtocolFrame};
+    use crate::trame::{read_frame, write_frame};
+
+    /// Poignée de main puis échange d'une trame ; `None` si la connexion TLS échoue
+    async fn exchange(acceptor: TlsAcceptor, connector: TlsConnector) -> Option<ProtocolFrame> {
+        let (client, server) = tokio::io::duplex(16 * 1024);
+        let serveur = tokio::spawn(async move {
+            let mut stream = acceptor.accept(server).await.ok()?;
+            read_frame(&mut stream).await.ok()?
+        });
+        let mut stream = connector.connect(server_name("localhost").unwrap(), client).await.ok()?;
+        write_frame(&mut stream, &ProtocolFrame::new(Message::Ping, None, 1)).await.ok()?;
+        serveur.await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_handshake_with_ca_or_insecure() {
+        let dir = std::env::temp_dir().join(format!("tp8-chiffrement-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let generated = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        let (cert_path, key_path) = (dir.join("cert.pem"), dir.join("key.pem"));
+        std::fs::write(&cert_path, generated.cert.pem()).unwrap();
+        std::fs::write(&key_path, generated.key_pair.serialize_pem()).unwrap();
+
+        let acceptor = acceptor(&cert_path, &key_path).unwrap();
+        let frame = exchange(acceptor.clone(), connector(Some(&cert_path), false).unwrap()).await;
+        assert_eq!(frame.map(|f| f.message), Some(Message::Ping));
+        assert!(exchange(acceptor.clone(), connector(None, true).unwrap()).await.is_some());
+
+        // Un autre certificat auto-signé n'est pas une autorité de confiance
+        let other = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        let other_path = dir.join("other.pem");
+        std::fs::write(&other_path, other.cert.pem()).unwrap();
+        assert!(exchange(acceptor, connector(Some(&other_path), false).unwrap()).await.is_none());
+        assert!(connector(None, false).is_err());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
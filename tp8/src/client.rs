@@ -0,0 +1,267 @@
+// src/client.rs
+// Interface des bots : connexion et authentification SCP, puis boucle qui appelle le code du bot à chaque message reçu
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::future::Future;
+use std::io;
+
+use chrono::{DateTime, Utc};
+use tokio::io::{ReadHalf, WriteHalf};
+use tokio::net::TcpStream;
+
+use crate::chiffrement::Transport;
+use crate::protocole::{ErrorCode, Message, MessageKind, ProtocolFrame, RoomId, MIN_PROTOCOL_VERSION, PROTOCOL_VERSION};
+use crate::trame::{read_frame, write_frame, FrameError};
+
+/// Erreur d'un bot
+#[derive(Debug)]
+pub enum ClientError {
+    Io(io::Error),
+    Frame(FrameError),
+    /// Demande refusée par le serveur (connexion, entrée dans un salon...), avec sa raison
+    Refused(String),
+    /// Connexion fermée par le serveur avant la réponse attendue
+    Closed,
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClientError::Io(e) => write!(f, "erreur de connexion: {}", e),
+            ClientError::Frame(e) => write!(f, "{}", e),
+            ClientError::Refused(reason) => write!(f, "refusé par le serveur: {}", reason),
+            ClientError::Closed => write!(f, "connexion fermée par le serveur"),
+        }
+    }
+}
+
+impl std::error::Error for ClientError {}
+
+impl From<io::Error> for ClientError {
+    fn from(e: io::Error) -> Self {
+        ClientError::Io(e)
+    }
+}
+
+impl From<FrameError> for ClientError {
+    fn from(e: FrameError) -> Self {
+        ClientError::Frame(e)
+    }
+}
+
+/// Message reçu dans un salon
+#[derive(Debug, Clone, PartialEq)]
+pub struct RoomMessage {
+    pub room_id: RoomId,
+    /// Auteur ; « nom@serveur » pour un message venu d'un serveur fédéré
+    pub from: String,
+    pub content: String,
+    /// Heure d'envoi, selon l'horloge de l'auteur
+    pub timestamp: DateTime<Utc>,
+    pub kind: MessageKind,
+    /// Heure de réception par le serveur, qui ordonne les messages du salon
+    pub received_at: Option<DateTime<Utc>>,
+}
+
+/// Message privé reçu (ou laissé pendant que le bot était hors ligne)
+#[derive(Debug, Clone, PartialEq)]
+pub struct PrivateMessage {
+    pub from: String,
+    pub content: String,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// Code d'un bot : chaque méthode est appelée par `ChatBot::run` pour le message correspondant, avec le bot
+/// pour répondre ; une erreur arrête la boucle. Par défaut, les messages sont ignorés
+pub trait BotHandler: Send {
+    fn on_room_message(&mut self, bot: &mut ChatBot, message: RoomMessage) -> impl Future<Output = Result<(), ClientError>> + Send {
+        let _ = (bot, message);
+        async { Ok(()) }
+    }
+
+    fn on_private_message(&mut self, bot: &mut ChatBot, message: PrivateMessage) -> impl Future<Output = Result<(), ClientError>> + Send {
+        let _ = (bot, message);
+        async { Ok(()) }
+    }
+
+    /// Tous les autres messages du serveur : arrivées et départs, erreurs, notifications...
+    fn on_event(&mut self, bot: &mut ChatBot, message: Message) -> impl Future<Output = Result<(), ClientError>> + Send {
+        let _ = (bot, message);
+        async { Ok(()) }
+    }
+}
+
+/// Connexion authentifiée d'un bot au serveur de discussion
+pub struct ChatBot {
+    reader: ReadHalf<Box<dyn Transport>>,
+    writer: WriteHalf<Box<dyn Transport>>,
+    username: String,
+    room: Option<RoomId>,
+    /// Messages lus en attendant une réponse, à traiter par `run`
+    pending: VecDeque<Message>,
+}
+
+impl ChatBot {
+    /// Se connecter en TCP à `addr` sous le compte `username`, créé au premier lancement
+    pub async fn connect(addr: &str, username: &str, password: &str) -> Result<Self, ClientError> {
+        let stream = TcpStream::connect(addr).await?;
+        stream.set_nodelay(true)?;
+        Self::connect_with(Box::new(stream), username, password).await
+    }
+
+    /// Comme `connect`, sur une connexion déjà établie (TLS par exemple)
+    pub async fn connect_with(stream: Box<dyn Transport>, username: &str, password: &str) -> Result<Self, ClientError> {
+        let (reader, writer) = tokio::io::split(stream);
+        let mut bot = Self { reader, writer, username: username.to_string(), room: None, pending: VecDeque::new() };
+        bot.write(Message::Hello { min_version: MIN_PROTOCOL_VERSION, max_version: PROTOCOL_VERSION }).await?;
+        if let Message::VersionMismatch { message, .. } = bot.wait_for(|m| matches!(m, Message::HelloAck { .. } | Message::VersionMismatch { .. })).await? {
+            return Err(ClientError::Refused(message));
+        }
+
+        // Inscription, ou connexion si le compte existe déjà
+        let answered = |m: &Message| matches!(m, Message::ConnectAck { .. } | Message::ConnectError { .. });
+        bot.write(Message::Register { username: username.to_string(), password: password.to_string() }).await?;
+        match bot.wait_for(answered).await? {
+            Message::ConnectError { code: ErrorCode::UsernameAlreadyTaken, .. } => {
+                bot.skip_processing_error().await?;
+                bot.write(Message::Login { username: username.to_string(), password: password.to_string() }).await?;
+                if let Message::ConnectError { reason, .. } = bot.wait_for(answered).await? {
+                    return Err(ClientError::Refused(reason));
+                }
+            }
+            Message::ConnectError { reason, .. } => return Err(ClientError::Refused(reason)),
+            _ => {}
+        }
+        Ok(bot)
+    }
+
+    pub fn username(&self) -> &str {
+        &self.username
+    }
+
+    /// Salon où le bot se trouve
+    pub fn room(&self) -> Option<&str> {
+        self.room.as_deref()
+    }
+
+    /// Entrer dans un salon (en quittant le précédent) ; renvoie ses membres
+    pub async fn join(&mut self, room_id: &str) -> Result<Vec<String>, ClientError> {
+        self.join_with_password(room_id, None).await
+    }
+
+    /// Comme `join`, pour un salon protégé par un mot de passe
+    pub async fn join_with_password(&mut self, room_id: &str, password: Option<&str>) -> Result<Vec<String>, ClientError> {
+        self.write(Message::JoinRoom { room_id: room_id.to_string(), password: password.map(String::from), wait: false, spectator: false }).await?;
+        let answered = |m: &Message| matches!(m, Message::JoinRoomAck { .. } | Message::JoinRoomError { .. } | Message::Error { .. });
+        match self.wait_for(answered).await? {
+            Message::JoinRoomAck { room_id, users, .. } => {
+                self.room = Some(room_id);
+                Ok(users)
+            }
+            Message::JoinRoomError { reason } | Message::Error { message: reason, .. } => {
+                self.skip_processing_error().await?;
+                Err(ClientError::Refused(reason))
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    /// Quitter le salon actuel
+    pub async fn leave(&mut self) -> Result<(), ClientError> {
+        self.room = None;
+        self.write(Message::LeaveRoom).await
+    }
+
+    /// Écrire dans le salon actuel
+    pub async fn send(&mut self, content: impl Into<String>) -> Result<(), ClientError> {
+        self.write(Message::SendMessage { content: content.into(), kind: MessageKind::Text, parent_message_id: None }).await
+    }
+
+    /// Message privé ; gardé par le serveur si le destinataire est hors ligne
+    pub async fn private_message(&mut self, to: &str, content: impl Into<String>) -> Result<(), ClientError> {
+        self.write(Message::PrivateMessage { target_user: to.to_string(), content: content.into() }).await
+    }
+
+    /// Demander la déconnexion ; `run` s'arrête quand le serveur a fermé la connexion
+    pub async fn quit(&mut self) -> Result<(), ClientError> {
+        self.write(Message::Disconnect).await
+    }
+
+    /// Passer chaque message reçu à `handler`, jusqu'à la fermeture de la connexion. Les messages
+    /// du bot lui-même, renvoyés par le salon, ne lui sont pas transmis
+    pub async fn run(&mut self, handler: &mut impl BotHandler) -> Result<(), ClientError> {
+        while let Some(message) = self.next_message().await? {
+            match message {
+                Message::RoomMessage { from, .. } if from == self.username => {}
+                Message::RoomMessage { room_id, from, content, timestamp, kind, received_at, .. } => {
+                    let message = RoomMessage { room_id, from, content, timestamp, kind, received_at };
+                    handler.on_room_message(self, message).await?;
+                }
+                Message::PrivateMessageReceived { from, content, timestamp } => {
+                    handler.on_private_message(self, PrivateMessage { from, content, timestamp }).await?;
+                }
+                message => {
+                    // Exclu ou salon supprimé : le bot n'y est plus
+                    match &message {
+                        Message::RoomDeleted { room_id } if self.room.as_ref() == Some(room_id) => self.room = None,
+                        Message::UserKicked { room_id, username, .. } | Message::UserBanned { room_id, username, .. }
+                            if *username == self.username && self.room.as_ref() == Some(room_id) => self.room = None,
+                        _ => {}
+                    }
+                    handler.on_event(self, message).await?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    async fn write(&mut self, message: Message) -> Result<(), ClientError> {
+        Ok(write_frame(&mut self.writer, &ProtocolFrame::new(message, None, 0)).await?)
+    }
+
+    /// Prochain message à traiter ; les Ping sont répondus et les messages de salon acquittés au passage
+    async fn next_message(&mut self) -> Result<Option<Message>, ClientError> {
+        if let Some(message) = self.pending.pop_front() {
+            return Ok(Some(message));
+        }
+        self.receive().await
+    }
+
+    async fn receive(&mut self) -> Result<Option<Message>, ClientError> {
+        loop {
+            let Some(frame) = read_frame(&mut self.reader).await? else {
+                return Ok(None);
+            };
+            match frame.message {
+                Message::Ping => self.write(Message::Pong).await?,
+                message => {
+                    if let Message::RoomMessage { room_id, sequence, .. } = &message {
+                        // Numéro 0 : le serveur ne numérote pas ses messages
+                        if *sequence > 0 {
+                            self.write(Message::MessageAck { room_id: room_id.clone(), sequence: *sequence }).await?;
+                        }
+                    }
+                    return Ok(Some(message));
+                }
+            }
+        }
+    }
+
+    /// Lire jusqu'au premier message qui satisfait `wanted` ; les autres sont gardés pour `run`
+    async fn wait_for(&mut self, wanted: impl Fn(&Message) -> bool) -> Result<Message, ClientError> {
+        loop {
+            match self.receive().await? {
+                Some(message) if wanted(&message) => return Ok(message),
+                Some(message) => self.pending.push_back(message),
+                None => return Err(ClientError::Closed),
+            }
+        }
+    }
+
+    /// Une demande refusée est suivie d'une erreur générique du serveur, sans intérêt pour le bot
+    async fn skip_processing_error(&mut self) -> Result<(), ClientError> {
+        self.wait_for(|m| matches!(m, Message::Error { code: ErrorCode::InternalError, .. })).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,427 @@
+// src/configuration.rs
+// Configuration du serveur : valeurs par défaut, fichier TOML, variables d'environnement SCP_* puis options de la ligne de commande
+
+use std::collections::BTreeSet;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use config_commun::{list, parse, Settings};
+use serde::Deserialize;
+
+use crate::fichiers::DEFAULT_MAX_FILE_SIZE;
+use crate::filtres::FilterConfig;
+use crate::historique::{HISTORY_CAPACITY, HISTORY_REPLAY_ON_JOIN};
+use crate::protocole::{validate_room_id, RoomId, MAX_MESSAGE_SIZE};
+
+/// Adresse d'écoute par défaut
+pub const DEFAULT_BIND_ADDR: &str = "127.0.0.1:9999";
+
+/// Préfixe des variables d'environnement qui surchargent la configuration (`SCP_CHAT_RATE` pour `--chat-rate`)
+pub const ENV_PREFIX: &str = "SCP_";
+
+/// Variable d'environnement donnant le fichier de configuration, comme `--config`
+pub const CONFIG_ENV_VAR: &str = "SCP_CONFIG";
+
+/// Plus petite taille de trame acceptable : en dessous, les messages ordinaires ne passent plus
+pub const MIN_MESSAGE_SIZE: usize = 1024;
+
+/// Nombre maximal de messages gardés en mémoire par salon
+pub const MAX_HISTORY_CAPACITY: usize = 100_000;
+
+/// Salon créé au démarrage, sans administrateur
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DefaultRoom {
+    pub id: String,
+    pub name: String,
+    /// Membres à la fois, sans limite si absent
+    #[serde(default)]
+    pub max_users: Option<usize>,
+}
+
+impl DefaultRoom {
+    fn new(id: &str, name: &str) -> Self {
+        Self { id: id.to_string(), name: name.to_string(), max_users: None }
+    }
+}
+
+/// Limites de débit de chaque client : rafale puis débit soutenu (messages par seconde)
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RateLimits {
+    /// Messages de discussion (salon et privés)
+    pub chat_burst: u32,
+    pub chat_per_sec: f64,
+    /// Autres requêtes (entrées dans un salon, listes, modération...)
+    pub control_burst: u32,
+    pub control_per_sec: f64,
+    /// Dépassements après lesquels le client est déconnecté
+    pub max_violations: u32,
+}
+
+impl Default for RateLimits {
+    fn default() -> Self {
+        Self {
+            chat_burst: 5,
+            chat_per_sec: 1.0,
+            control_burst: 20,
+            control_per_sec: 5.0,
+            max_violations: 10,
+        }
+    }
+}
+
+/// Configuration complète du serveur ; les clés absentes du fichier gardent leur valeur par défaut
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub bind: SocketAddr,
+    /// Salons intégrés, recréés à chaque démarrage
+    pub rooms: Vec<DefaultRoom>,
+    /// Taille maximale d'une trame reçue, au plus MAX_MESSAGE_SIZE
+    pub max_message_size: usize,
+    /// Messages gardés en mémoire par salon
+    pub history_capacity: usize,
+    /// Messages renvoyés à un client qui rejoint un salon
+    pub history_replay: usize,
+    /// Historique persisté sur disque si renseigné
+    pub history_dir: Option<PathBuf>,
+    pub users_file: PathBuf,
+    pub mailbox_file: PathBuf,
+    pub rooms_file: PathBuf,
+    pub profiles_file: PathBuf,
+    /// Utilisateurs qui reçoivent le rôle d'administrateur à la connexion
+    pub admins: BTreeSet<String>,
+    pub max_file_size: u64,
+    pub heartbeat_secs: u64,
+    pub max_missed_pongs: u32,
+    /// Délai avant la suppression d'un salon créé par un client et resté vide
+    pub room_grace_secs: u64,
+    /// Délai laissé aux clients pour partir à l'arrêt du serveur
+    pub shutdown_grace_secs: u64,
+    /// Messages en attente d'envoi par client ; au-delà, les plus anciens sont abandonnés
+    pub send_queue_capacity: usize,
+    /// Un client dont la file d'envoi reste pleine aussi longtemps est déconnecté
+    pub slow_client_secs: u64,
+    /// Écart toléré entre l'horloge d'un client et celle du serveur ; au-delà, l'heure des trames
+    /// du client est remplacée par celle du serveur (0 : jamais)
+    pub max_clock_skew_secs: u64,
+    /// Intervalle entre deux résumés de l'activité dans le journal (0 : jamais)
+    pub metrics_digest_secs: u64,
+    pub tls_cert: Option<PathBuf>,
+    pub tls_key: Option<PathBuf>,
+    pub rate_limits: RateLimits,
+    /// Filtres appliqués aux messages des salons
+    pub filters: FilterConfig,
+    /// Socket de la console d'administration, en boucle locale seulement (l'entrée standard reste disponible)
+    pub admin_bind: Option<SocketAddr>,
+    /// Nom de ce serveur auprès des serveurs fédérés : l'origine des messages qu'il leur relaie
+    pub server_name: String,
+    /// Écoute des serveurs fédérés qui se connectent à celui-ci
+    pub federation_bind: Option<SocketAddr>,
+    /// Serveurs fédérés auxquels se connecter (l'adresse de leur federation_bind)
+    pub federation_peers: Vec<String>,
+    /// Salons dont les messages sont partagés avec les serveurs fédérés
+    pub federated_rooms: BTreeSet<RoomId>,
+    /// Secret que présentent les serveurs fédérés, le même partout
+    pub federation_secret: Option<String>,
+    /// Serveur de journalisation (tp3) qui reçoit les événements en JSON, une ligne chacun ; remplace LOG_SERVER
+    pub log_server: Option<String>,
+    /// Fichier où ajouter les événements en JSON, une ligne chacun
+    pub log_file: Option<PathBuf>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            bind: DEFAULT_BIND_ADDR.parse().expect("adresse par défaut valide"),
+            rooms: vec![
+                DefaultRoom::new("general", "Salon Général"),
+                DefaultRoom::new("tech", "Discussions Tech"),
+                DefaultRoom::new("random", "Discussions Libres"),
+            ],
+            max_message_size: MAX_MESSAGE_SIZE,
+            history_capacity: HISTORY_CAPACITY,
+            history_replay: HISTORY_REPLAY_ON_JOIN,
+            history_dir: None,
+            users_file: PathBuf::from("users.json"),
+            mailbox_file: PathBuf::from("mailboxes.json"),
+            rooms_file: PathBuf::from("rooms.json"),
+            profiles_file: PathBuf::from("profiles.json"),
+            admins: BTreeSet::new(),
+            max_file_size: DEFAULT_MAX_FILE_SIZE,
+            heartbeat_secs: 30,
+            max_missed_pongs: 3,
+            room_grace_secs: 300,
+            shutdown_grace_secs: 5,
+            send_queue_capacity: 1024,
+            slow_client_secs: 10,
+            max_clock_skew_secs: 300,
+            metrics_digest_secs: 300,
+            tls_cert: None,
+            tls_key: None,
+            rate_limits: RateLimits::default(),
+            filters: FilterConfig::default(),
+            admin_bind: None,
+            server_name: "scp".to_string(),
+            federation_bind: None,
+            federation_peers: Vec::new(),
+            federated_rooms: BTreeSet::new(),
+            federation_secret: None,
+            log_server: None,
+            log_file: None,
+        }
+    }
+}
+
+impl ServerConfig {
+    /// Configuration finale : défauts, puis fichier (`--config` ou SCP_CONFIG), puis environnement, puis arguments ; validée
+    pub fn load(
+        args: impl IntoIterator<Item = String>,
+        env: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<Self, String> {
+        config_commun::load_with(args, env).map_err(|e| e.to_string())
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self, String> {
+        config_commun::from_file(path).map_err(|e| e.to_string())
+    }
+
+    pub fn heartbeat_interval(&self) -> Duration {
+        Duration::from_secs(self.heartbeat_secs)
+    }
+
+    pub fn room_grace(&self) -> Duration {
+        Duration::from_secs(self.room_grace_secs)
+    }
+
+    pub fn shutdown_grace(&self) -> Duration {
+        Duration::from_secs(self.shutdown_grace_secs)
+    }
+
+    pub fn slow_client_limit(&self) -> Duration {
+        Duration::from_secs(self.slow_client_secs)
+    }
+
+    /// Écart d'horloge toléré ; `None` si l'heure des clients n'est jamais remise en cause
+    pub fn max_clock_skew(&self) -> Option<Duration> {
+        (self.max_clock_skew_secs > 0).then(|| Duration::from_secs(self.max_clock_skew_secs))
+    }
+
+    /// Intervalle des résumés d'activité ; `None` s'ils sont désactivés
+    pub fn metrics_digest_interval(&self) -> Option<Duration> {
+        (self.metrics_digest_secs > 0).then(|| Duration::from_secs(self.metrics_digest_secs))
+    }
+}
+
+impl Settings for ServerConfig {
+    const ENV_PREFIX: &'static str = ENV_PREFIX;
+
+    /// Surcharger un réglage ; `key` est le nom de l'option sans `--`. Les listes sont séparées par des virgules :
+    /// `admin`, `federation-peer` et `federated-rooms` complètent la leur, `banned-words` remplace la liste commune
+    fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "bind" => self.bind = parse(key, value)?,
+            "max-message-size" => self.max_message_size = parse(key, value)?,
+            "history-capacity" => self.history_capacity = parse(key, value)?,
+            "history-replay" => self.history_replay = parse(key, value)?,
+            "history-dir" => self.history_dir = Some(PathBuf::from(value)),
+            "users-file" => self.users_file = PathBuf::from(value),
+            "mailbox-file" => self.mailbox_file = PathBuf::from(value),
+            "rooms-file" => self.rooms_file = PathBuf::from(value),
+            "profiles-file" => self.profiles_file = PathBuf::from(value),
+            "admin" => self.admins.extend(list(value)),
+            "max-file-size" => self.max_file_size = parse(key, value)?,
+            "heartbeat-secs" => self.heartbeat_secs = parse(key, value)?,
+            "max-missed-pongs" => self.max_missed_pongs = parse(key, value)?,
+            "room-grace-secs" => self.room_grace_secs = parse(key, value)?,
+            "shutdown-grace-secs" => self.shutdown_grace_secs = parse(key, value)?,
+            "send-queue-capacity" => self.send_queue_capacity = parse(key, value)?,
+            "slow-client-secs" => self.slow_client_secs = parse(key, value)?,
+            "max-clock-skew-secs" => self.max_clock_skew_secs = parse(key, value)?,
+            "metrics-digest-secs" => self.metrics_digest_secs = parse(key, value)?,
+            "tls-cert" => self.tls_cert = Some(PathBuf::from(value)),
+            "tls-key" => self.tls_key = Some(PathBuf::from(value)),
+            "chat-burst" => self.rate_limits.chat_burst = parse(key, value)?,
+            "chat-rate" => self.rate_limits.chat_per_sec = parse(key, value)?,
+            "control-burst" => self.rate_limits.control_burst = parse(key, value)?,
+            "control-rate" => self.rate_limits.control_per_sec = parse(key, value)?,
+            "max-rate-violations" => self.rate_limits.max_violations = parse(key, value)?,
+            "banned-words" => self.filters.banned_words = list(value).collect(),
+            "strip-links" => self.filters.strip_links = parse(key, value)?,
+            "duplicate-limit" => self.filters.duplicate_limit = parse(key, value)?,
+            "duplicate-window-secs" => self.filters.duplicate_window_secs = parse(key, value)?,
+            "admin-bind" => self.admin_bind = Some(parse(key, value)?),
+            "server-name" => self.server_name = value.trim().to_string(),
+            "federation-bind" => self.federation_bind = Some(parse(key, value)?),
+            "federation-peer" => self.federation_peers.extend(list(value)),
+            "federated-rooms" => self.federated_rooms.extend(list(value)),
+            "federation-secret" => self.federation_secret = Some(value.to_string()),
+            "log-server" => self.log_server = Some(value.trim().to_string()),
+            "log-file" => self.log_file = Some(PathBuf::from(value)),
+            _ => return Err(format!("Option inconnue: {}", key)),
+        }
+        Ok(())
+    }
+
+    /// Vérifier la cohérence des réglages avant de démarrer
+    fn validate(&mut self) -> Result<(), String> {
+        if !(MIN_MESSAGE_SIZE..=MAX_MESSAGE_SIZE).contains(&self.max_message_size) {
+            return Err(format!("max_message_size doit être entre {} et {} octets", MIN_MESSAGE_SIZE, MAX_MESSAGE_SIZE));
+        }
+        if !(1..=MAX_HISTORY_CAPACITY).contains(&self.history_capacity) {
+            return Err(format!("history_capacity doit être entre 1 et {}", MAX_HISTORY_CAPACITY));
+        }
+        if self.history_replay > self.history_capacity {
+            return Err("history_replay ne peut pas dépasser history_capacity".to_string());
+        }
+        if self.heartbeat_secs == 0 || self.max_missed_pongs == 0 {
+            return Err("heartbeat_secs et max_missed_pongs doivent être positifs".to_string());
+        }
+        if self.max_file_size == 0 {
+            return Err("max_file_size doit être positif".to_string());
+        }
+        if self.send_queue_capacity == 0 || self.slow_client_secs == 0 {
+            return Err("send_queue_capacity et slow_client_secs doivent être positifs".to_string());
+        }
+
+        let limits = &self.rate_limits;
+        if limits.chat_burst == 0 || limits.control_burst == 0 || limits.max_violations == 0 {
+            return Err("Les rafales et max_violations doivent être positives".to_string());
+        }
+        for rate in [limits.chat_per_sec, limits.control_per_sec] {
+            if !rate.is_finite() || rate <= 0.0 {
+                return Err(format!("Débit invalide: {} messages par seconde", rate));
+            }
+        }
+
+        self.filters.validate()?;
+
+        if self.tls_cert.is_some() != self.tls_key.is_some() {
+            return Err("tls_cert et tls_key vont ensemble".to_string());
+        }
+        // La console n'authentifie personne : elle ne doit pas être joignable depuis le réseau
+        if let Some(addr) = self.admin_bind.filter(|addr| !addr.ip().is_loopback()) {
+            return Err(format!("admin_bind doit être une adresse locale (127.0.0.1 ou ::1), pas {}", addr));
+        }
+
+        if self.server_name.is_empty() || !self.server_name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+            return Err("server_name ne peut contenir que des lettres, chiffres, '-' et '_'".to_string());
+        }
+        if self.federation_bind.is_some() || !self.federation_peers.is_empty() {
+            if self.federation_secret.as_deref().is_none_or(str::is_empty) {
+                return Err("La fédération requiert federation_secret".to_string());
+            }
+            if self.federated_rooms.is_empty() {
+                return Err("La fédération requiert au moins un salon dans federated_rooms".to_string());
+            }
+        }
+        for room_id in &self.federated_rooms {
+            validate_room_id(room_id)?;
+        }
+
+        let mut ids = BTreeSet::new();
+        for room in &self.rooms {
+            validate_room_id(&room.id)?;
+            if room.name.trim().is_empty() {
+                return Err(format!("Le salon {} doit avoir un nom", room.id));
+            }
+            if !ids.insert(room.id.as_str()) {
+                return Err(format!("Salon {} déclaré deux fois", room.id));
+            }
+            if room.max_users == Some(0) {
+                return Err(format!("max_users du salon {} doit être positif", room.id));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    fn vars(list: &[(&str, &str)]) -> Vec<(String, String)> {
+        list.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect()
+    }
+
+    #[test]
+    fn test_file_then_env_then_args() {
+        let dir = std::env::temp_dir().join(format!("tp8-configuration-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("serveur.toml");
+        std::fs::write(&path, r#"
+            bind = "0.0.0.0:7000"
+            history_capacity = 500
+            admins = ["alice"]
+            rooms = [{ id = "lobby", name = "Accueil", max_users = 20 }]
+
+            [rate_limits]
+            chat_burst = 8
+            chat_per_sec = 2.0
+
+            [filters]
+            banned_words = ["zut"]
+
+            [filters.rooms.lobby]
+            strip_links = true
+        "#).unwrap();
+
+        let config = ServerConfig::load(
+            args(&["--config", path.to_str().unwrap(), "--chat-rate", "4", "--admin", "carol"]),
+            vars(&[("SCP_CHAT_RATE", "3"), ("SCP_ADMIN", "bob"), ("SCP_HISTORY_REPLAY", "40"), ("HOME", "/root")]),
+        ).unwrap();
+        assert_eq!(config.bind, "0.0.0.0:7000".parse().unwrap());
+        assert_eq!(config.rooms, vec![DefaultRoom { max_users: Some(20), ..DefaultRoom::new("lobby", "Accueil") }]);
+        assert_eq!((config.history_capacity, config.history_replay), (500, 40));
+        assert_eq!(config.rate_limits.chat_burst, 8);
+        assert_eq!(config.rate_limits.chat_per_sec, 4.0); // La ligne de commande l'emporte sur l'environnement
+        assert_eq!(config.rate_limits.control_burst, RateLimits::default().control_burst);
+        assert_eq!(config.admins.iter().collect::<Vec<_>>(), ["alice", "bob", "carol"]);
+        assert_eq!(config.filters.defaults().banned_words, ["zut"]);
+        let lobby = &config.filters.rooms["lobby"];
+        assert!(lobby.strip_links && lobby.banned_words.is_empty()); // Un salon réglé à part ne garde rien des réglages communs
+
+        std::fs::write(&path, "bnid = \"0.0.0.0:7000\"").unwrap();
+        assert!(ServerConfig::from_file(&path).is_err()); // Clé inconnue
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_validation() {
+        assert_eq!(ServerConfig::load(Vec::new(), Vec::new()), Ok(ServerConfig::default()));
+
+        for (key, value) in [
+            ("max-message-size", "100"),
+            ("max-message-size", "1000000"),
+            ("history-capacity", "0"),
+            ("history-replay", "1000"),
+            ("heartbeat-secs", "0"),
+            ("chat-rate", "0"),
+            ("chat-rate", "NaN"),
+            ("tls-cert", "cert.pem"),
+            ("admin-bind", "0.0.0.0:9000"),
+            ("send-queue-capacity", "0"),
+            ("banned-words", "gros mot"),
+            ("strip-links", "oui"),
+            ("server-name", "mon serveur"),
+            ("federation-peer", "10.0.0.2:7777"), // Sans secret ni salon partagé
+        ] {
+            let option = format!("--{}", key);
+            assert!(ServerConfig::load(args(&[&option, value]), Vec::new()).is_err(), "{} {}", key, value);
+        }
+        assert!(ServerConfig::load(args(&["--bind", "nowhere"]), Vec::new()).is_err());
+        assert!(ServerConfig::load(args(&["--unknown", "1"]), Vec::new()).is_err());
+        assert!(ServerConfig::load(args(&["--heartbeat-secs"]), Vec::new()).is_err());
+        assert!(ServerConfig::load(Vec::new(), vars(&[("SCP_MAX_FILE_SIZE", "big")])).is_err());
+
+        let mut config = ServerConfig::default();
+        config.rooms.push(DefaultRoom::new("general", "Encore"));
+        assert!(config.validate().is_err());
+    }
+}
@@ -0,0 +1,130 @@
+// src/courrier.rs
+// Boîtes aux lettres : messages privés et mentions adressés à des utilisateurs hors ligne, persistés dans un fichier JSON
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::stockage::write_json_atomically;
+
+/// Nombre maximal de messages en attente par destinataire
+pub const MAILBOX_CAPACITY: usize = 100;
+
+/// Message privé ou mention en attente de livraison
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PendingMessage {
+    pub from: String,
+    pub content: String,
+    pub timestamp: DateTime<Utc>,
+    /// Salon où l'utilisateur a été mentionné (None : message privé)
+    #[serde(default)]
+    pub room_id: Option<String>,
+    /// Numéro du message dans ce salon
+    #[serde(default)]
+    pub sequence: u64,
+}
+
+/// Boîtes aux lettres de tous les utilisateurs ; sans chemin, elles ne vivent qu'en mémoire
+#[derive(Debug, Default)]
+pub struct MailboxStore {
+    path: Option<PathBuf>,
+    boxes: HashMap<String, Vec<PendingMessage>>,
+}
+
+impl MailboxStore {
+    /// Charger les boîtes depuis un fichier JSON (absent : boîtes vides)
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let boxes = match fs::read_to_string(&path) {
+            Ok(contenu) => serde_json::from_str(&contenu).map_err(io::Error::other)?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => return Err(e),
+        };
+        Ok(Self { path: Some(path), boxes })
+    }
+
+    /// Déposer un message dans la boîte d'un utilisateur et l'enregistrer sur disque
+    pub fn deposit(&mut self, username: &str, message: PendingMessage) -> Result<(), String> {
+        let mailbox = self.boxes.entry(username.to_string()).or_default();
+        if mailbox.len() >= MAILBOX_CAPACITY {
+            return Err(format!("La boîte aux lettres de {} est pleine", username));
+        }
+        mailbox.push(message);
+
+        if let Err(e) = self.save() {
+            if let Some(mailbox) = self.boxes.get_mut(username) {
+                mailbox.pop();
+            }
+            return Err(format!("Impossible d'enregistrer le message: {}", e));
+        }
+        Ok(())
+    }
+
+    /// Vider la boîte d'un utilisateur ; les messages sont rendus du plus ancien au plus récent
+    pub fn take(&mut self, username: &str) -> io::Result<Vec<PendingMessage>> {
+        let Some(messages) = self.boxes.remove(username) else {
+            return Ok(Vec::new());
+        };
+        if let Err(e) = self.save() {
+            self.boxes.insert(username.to_string(), messages);
+            return Err(e);
+        }
+        Ok(messages)
+    }
+
+    /// Nombre de messages en attente pour un utilisateur
+    pub fn pending(&self, username: &str) -> usize {
+        self.boxes.get(username).map_or(0, Vec::len)
+    }
+
+    fn save(&self) -> io::Result<()> {
+        match &self.path {
+            Some(path) => write_json_atomically(path, &self.boxes),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(content: &str) -> PendingMessage {
+        PendingMessage {
+            from: "alice".to_string(),
+            content: content.to_string(),
+            timestamp: Utc::now(),
+            room_id: None,
+            sequence: 0,
+        }
+    }
+
+    #[test]
+    fn test_deposit_persisted_then_take() {
+        let path = std::env::temp_dir().join(format!("tp8-courrier-{}.json", std::process::id()));
+        let mut store = MailboxStore::open(&path).unwrap();
+        store.deposit("bob", message("bonjour")).unwrap();
+        store.deposit("bob", message("tu es là ?")).unwrap();
+
+        let mut store = MailboxStore::open(&path).unwrap();
+        assert_eq!(store.pending("bob"), 2);
+        let contents: Vec<String> = store.take("bob").unwrap().into_iter().map(|m| m.content).collect();
+        assert_eq!(contents, vec!["bonjour", "tu es là ?"]);
+        assert!(MailboxStore::open(&path).unwrap().take("bob").unwrap().is_empty());
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_capacity() {
+        let mut store = MailboxStore::default();
+        for i in 0..MAILBOX_CAPACITY {
+            store.deposit("bob", message(&i.to_string())).unwrap();
+        }
+        assert!(store.deposit("bob", message("de trop")).is_err());
+        assert_eq!(store.pending("bob"), MAILBOX_CAPACITY);
+    }
+}
@@ -0,0 +1,65 @@
+// src/debit.rs
+// Limitation de débit : seau à jetons (token bucket)
+
+use std::time::Instant;
+
+/// Seau à jetons : `capacity` messages d'affilée au plus, puis `refill_per_sec` messages par seconde
+#[derive(Debug, Clone)]
+pub struct TokenBucket {
+    capacity: f64,
+    refill_per_sec: f64,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    /// Seau plein au départ
+    pub fn new(capacity: u32, refill_per_sec: f64) -> Self {
+        Self {
+            capacity: f64::from(capacity.max(1)),
+            refill_per_sec: refill_per_sec.max(0.0),
+            tokens: f64::from(capacity.max(1)),
+            last_refill: Instant::now(),
+        }
+    }
+
+    /// Consommer un jeton s'il y en a un
+    pub fn try_take(&mut self) -> bool {
+        self.try_take_at(Instant::now())
+    }
+
+    /// Comme `try_take`, à un instant donné
+    pub fn try_take_at(&mut self, now: Instant) -> bool {
+        let elapsed = now.saturating_duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.refill_per_sec).min(self.capacity);
+        self.last_refill = now;
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_burst_then_refill() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(3, 2.0);
+        assert!((0..3).all(|_| bucket.try_take_at(start)));
+        assert!(!bucket.try_take_at(start));
+
+        // 2 jetons par seconde : un jeton après 500 ms, jamais plus que la capacité
+        assert!(bucket.try_take_at(start + Duration::from_millis(500)));
+        assert!(!bucket.try_take_at(start + Duration::from_millis(600)));
+        let later = start + Duration::from_secs(60);
+        assert!((0..3).all(|_| bucket.try_take_at(later)));
+        assert!(!bucket.try_take_at(later));
+    }
+}
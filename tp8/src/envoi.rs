@@ -0,0 +1,179 @@
+// src/envoi.rs
+// File d'envoi bornée d'une connexion : quand le client ne suit plus, les plus anciens messages sont abandonnés
+
+use std::collections::VecDeque;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+
+use tokio::sync::Notify;
+
+/// Ce qu'il est advenu d'un élément confié à la file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Push {
+    Queued,
+    /// File pleine : le plus ancien élément abandonnable a été retiré pour faire de la place
+    DroppedOldest,
+    /// Comme DroppedOldest, mais la file est pleine depuis plus longtemps que la limite ; signalé une seule fois
+    Stalled,
+    /// File fermée : l'élément est perdu
+    Closed,
+}
+
+#[derive(Debug)]
+struct State<T> {
+    items: VecDeque<(T, bool)>, // (élément, abandonnable)
+    droppable: usize, // Éléments abandonnables en file
+    full_since: Option<Instant>,
+    stalled: bool,
+    dropped: u64,
+    closed: bool,
+}
+
+/// File à un consommateur, bornée à `capacity` éléments abandonnables ; les éléments indispensables
+/// (changements d'abonnement...) ne sont jamais retirés et passent même si la file est pleine
+#[derive(Debug)]
+pub struct SendQueue<T> {
+    state: Mutex<State<T>>,
+    ready: Notify,
+    capacity: usize,
+    stall_limit: Duration,
+}
+
+impl<T> SendQueue<T> {
+    /// File de `capacity` éléments (au moins 1) ; pleine plus de `stall_limit` sans être redescendue
+    /// à moitié entre-temps, elle se déclare bloquée
+    pub fn new(capacity: usize, stall_limit: Duration) -> Self {
+        Self {
+            state: Mutex::new(State { items: VecDeque::new(), droppable: 0, full_since: None, stalled: false, dropped: 0, closed: false }),
+            ready: Notify::new(),
+            capacity: capacity.max(1),
+            stall_limit,
+        }
+    }
+
+    /// Ajouter un élément ; s'il est `droppable`, il pourra être abandonné à son tour si le client ne suit pas
+    pub fn push(&self, item: T, droppable: bool) -> Push {
+        let mut state = self.lock();
+        if state.closed {
+            return Push::Closed;
+        }
+        let mut outcome = Push::Queued;
+        if droppable && state.droppable >= self.capacity {
+            if let Some(oldest) = state.items.iter().position(|(_, droppable)| *droppable) {
+                state.items.remove(oldest);
+                state.droppable -= 1;
+                state.dropped += 1;
+            }
+            let full_since = *state.full_since.get_or_insert_with(Instant::now);
+            outcome = if !state.stalled && full_since.elapsed() >= self.stall_limit {
+                state.stalled = true;
+                Push::Stalled
+            } else {
+                Push::DroppedOldest
+            };
+        }
+        state.items.push_back((item, droppable));
+        state.droppable += usize::from(droppable);
+        drop(state);
+        self.ready.notify_one();
+        outcome
+    }
+
+    /// Prochain élément, dans l'ordre d'arrivée ; `None` une fois la file fermée et vidée
+    pub async fn recv(&self) -> Option<T> {
+        loop {
+            {
+                let mut state = self.lock();
+                if let Some((item, droppable)) = state.items.pop_front() {
+                    state.droppable -= usize::from(droppable);
+                    // Un client qui lit au compte-gouttes garde sa file pleine : il doit la vider de moitié pour repartir de zéro
+                    if state.droppable <= self.capacity / 2 {
+                        state.full_since = None;
+                        state.stalled = false;
+                    }
+                    return Some(item);
+                }
+                if state.closed {
+                    return None;
+                }
+            }
+            // Un seul consommateur : un push survenu entre-temps laisse un jeton, aucun réveil n'est perdu
+            self.ready.notified().await;
+        }
+    }
+
+    /// Refuser les nouveaux éléments ; ceux déjà en file seront encore rendus par `recv`
+    pub fn close(&self) {
+        self.lock().closed = true;
+        self.ready.notify_one();
+    }
+
+    /// Éléments en attente
+    pub fn len(&self) -> usize {
+        self.lock().items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lock().items.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Éléments abandonnés depuis la création de la file
+    pub fn dropped(&self) -> u64 {
+        self.lock().dropped
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State<T>> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_drop_oldest() {
+        let queue = SendQueue::new(2, Duration::from_secs(60));
+        assert_eq!(queue.push("abonnement", false), Push::Queued);
+        assert_eq!(queue.push("a", true), Push::Queued);
+        assert_eq!(queue.push("b", true), Push::Queued);
+        assert_eq!(queue.push("c", true), Push::DroppedOldest);
+        assert_eq!((queue.len(), queue.dropped()), (3, 1));
+
+        // L'élément indispensable reste, le plus ancien des autres est parti
+        queue.close();
+        assert_eq!(queue.push("d", true), Push::Closed);
+        let mut received = Vec::new();
+        while let Some(item) = queue.recv().await {
+            received.push(item);
+        }
+        assert_eq!(received, vec!["abonnement", "b", "c"]);
+    }
+
+    #[tokio::test]
+    async fn test_stalled_once() {
+        let queue = SendQueue::new(1, Duration::ZERO);
+        assert_eq!(queue.push(1, true), Push::Queued);
+        assert_eq!(queue.push(2, true), Push::Stalled);
+        assert_eq!(queue.push(3, true), Push::DroppedOldest);
+
+        // Vidée, la file repart de zéro
+        assert_eq!(queue.recv().await, Some(3));
+        assert_eq!(queue.push(4, true), Push::Queued);
+        assert_eq!(queue.push(5, true), Push::Stalled);
+
+        // Lire un élément de temps en temps ne suffit pas
+        let queue = SendQueue::new(4, Duration::from_millis(50));
+        for n in 0..5 {
+            queue.push(n, true);
+        }
+        std::thread::sleep(Duration::from_millis(60));
+        assert_eq!(queue.recv().await, Some(1));
+        assert_eq!(queue.push(5, true), Push::Queued);
+        assert_eq!(queue.push(6, true), Push::Stalled);
+    }
+}
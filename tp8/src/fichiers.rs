@@ -0,0 +1,137 @@
+// src/fichiers.rs
+// Transfert de fichiers : découpage en morceaux base64, empreinte SHA-256, écriture côté destinataire
+
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use sha2::{Digest, Sha256};
+
+/// Taille des morceaux envoyés (une fois encodés en base64, ils tiennent dans une trame)
+pub const FILE_CHUNK_SIZE: usize = 32 * 1024;
+
+/// Taille maximale d'un fichier acceptée par défaut par le serveur
+pub const DEFAULT_MAX_FILE_SIZE: u64 = 10 * 1024 * 1024;
+
+/// Encoder un morceau de fichier pour le champ `data` de FileChunk
+pub fn encode_chunk(bytes: &[u8]) -> String {
+    STANDARD.encode(bytes)
+}
+
+/// Décoder le champ `data` d'un FileChunk
+pub fn decode_chunk(data: &str) -> Result<Vec<u8>, String> {
+    STANDARD.decode(data).map_err(|e| format!("Morceau de fichier mal encodé: {}", e))
+}
+
+/// Empreinte SHA-256 en hexadécimal, telle qu'envoyée dans FileComplete
+pub fn sha256_hex(hasher: Sha256) -> String {
+    format!("{:x}", hasher.finalize())
+}
+
+/// Nom de fichier sans chemin, pour ne jamais écrire en dehors du répertoire de réception
+pub fn sanitize_filename(filename: &str) -> Option<String> {
+    let name = Path::new(filename).file_name()?.to_str()?;
+    let name: String = name.chars().filter(|c| !c.is_control()).collect();
+    if name.is_empty() || name.starts_with('.') {
+        return None;
+    }
+    Some(name)
+}
+
+/// Fichier en cours de réception
+#[derive(Debug)]
+pub struct IncomingFile {
+    path: PathBuf,
+    file: File,
+    hasher: Sha256,
+    expected_size: u64,
+    received: u64,
+}
+
+impl IncomingFile {
+    /// Créer le fichier dans `dir` ; un suffixe numérique évite d'écraser un fichier existant
+    pub fn create(dir: impl AsRef<Path>, filename: &str, expected_size: u64) -> io::Result<Self> {
+        let name = sanitize_filename(filename)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "nom de fichier invalide"))?;
+        fs::create_dir_all(&dir)?;
+
+        let mut path = dir.as_ref().join(&name);
+        let mut suffix = 1;
+        while path.exists() {
+            path = dir.as_ref().join(format!("{}.{}", name, suffix));
+            suffix += 1;
+        }
+        let file = File::create(&path)?;
+        Ok(Self { path, file, hasher: Sha256::new(), expected_size, received: 0 })
+    }
+
+    /// Ajouter un morceau reçu
+    pub fn write_chunk(&mut self, bytes: &[u8]) -> Result<(), String> {
+        if self.received + bytes.len() as u64 > self.expected_size {
+            return Err(format!("Fichier plus gros que les {} octets annoncés", self.expected_size));
+        }
+        self.file.write_all(bytes).map_err(|e| format!("Écriture impossible: {}", e))?;
+        self.hasher.update(bytes);
+        self.received += bytes.len() as u64;
+        Ok(())
+    }
+
+    /// Terminer la réception : le fichier est gardé seulement si taille et empreinte correspondent
+    pub fn finish(self, sha256: &str) -> Result<PathBuf, String> {
+        let complete = self.received == self.expected_size;
+        let digest = sha256_hex(self.hasher);
+        if !complete || !digest.eq_ignore_ascii_case(sha256) {
+            let _ = fs::remove_file(&self.path);
+            return Err(if complete {
+                "Empreinte SHA-256 différente, fichier supprimé".to_string()
+            } else {
+                format!("Fichier incomplet ({} / {} octets), fichier supprimé", self.received, self.expected_size)
+            });
+        }
+        Ok(self.path)
+    }
+
+    /// Abandonner la réception et supprimer le fichier partiel
+    pub fn abort(self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sanitize_filename() {
+        assert_eq!(sanitize_filename("../../etc/passwd").as_deref(), Some("passwd"));
+        assert_eq!(sanitize_filename("rapport.pdf").as_deref(), Some("rapport.pdf"));
+        assert_eq!(sanitize_filename(".."), None);
+        assert_eq!(sanitize_filename(".bashrc"), None);
+    }
+
+    #[test]
+    fn test_incoming_file_checks_digest() {
+        let dir = std::env::temp_dir().join(format!("tp8-fichiers-{}", std::process::id()));
+        let contenu = b"bonjour le monde";
+        let mut hasher = Sha256::new();
+        hasher.update(contenu);
+        let sha256 = sha256_hex(hasher);
+
+        let mut incoming = IncomingFile::create(&dir, "note.txt", contenu.len() as u64).unwrap();
+        let data = encode_chunk(&contenu[..7]);
+        incoming.write_chunk(&decode_chunk(&data).unwrap()).unwrap();
+        incoming.write_chunk(&contenu[7..]).unwrap();
+        assert!(incoming.write_chunk(b"!").is_err());
+        let path = incoming.finish(&sha256).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), contenu);
+
+        // Même nom : pas d'écrasement, et une mauvaise empreinte supprime le fichier
+        let mut incoming = IncomingFile::create(&dir, "note.txt", 1).unwrap();
+        incoming.write_chunk(b"x").unwrap();
+        assert!(incoming.finish(&sha256).is_err());
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
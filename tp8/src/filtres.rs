@@ -0,0 +1,373 @@
+// src/filtres.rs
+// Filtrage du contenu des messages de salon : chaîne de filtres (mots interdits, liens, répétitions), réglable par salon
+
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+
+use crate::protocole::{validate_room_id, ErrorCode, Message, RoomId};
+
+/// Texte qui remplace un lien retiré
+pub const LINK_PLACEHOLDER: &str = "[lien retiré]";
+
+/// Message en cours de filtrage : où et par qui il est envoyé
+#[derive(Debug, Clone, Copy)]
+pub struct FilterContext<'a> {
+    pub room_id: &'a str,
+    pub username: &'a str,
+    pub now: Instant,
+}
+
+/// Verdict d'un filtre ; un filtre qui modifie le message l'accepte
+#[derive(Debug, Clone, PartialEq)]
+pub enum FilterDecision {
+    Accept,
+    /// Message refusé : l'erreur est renvoyée à l'expéditeur et les filtres suivants ne sont pas consultés
+    Reject(ErrorCode, String),
+}
+
+/// Filtre de la chaîne ; il ne voit que les SendMessage et peut en réécrire le contenu
+pub trait MessageFilter: Send + Sync {
+    /// Nom court, pour les journaux
+    fn name(&self) -> &'static str;
+
+    fn filter(&self, ctx: &FilterContext, message: &mut Message) -> FilterDecision;
+}
+
+fn content_mut(message: &mut Message) -> Option<&mut String> {
+    match message {
+        Message::SendMessage { content, .. } => Some(content),
+        _ => None,
+    }
+}
+
+/// Masque les mots interdits par des astérisques, sans tenir compte de la casse
+#[derive(Debug, Clone)]
+pub struct BannedWords {
+    words: HashSet<String>,
+}
+
+impl BannedWords {
+    pub fn new<S: AsRef<str>>(words: impl IntoIterator<Item = S>) -> Self {
+        Self { words: words.into_iter().map(|word| word.as_ref().trim().to_lowercase()).collect() }
+    }
+
+    /// `text` avec chaque mot interdit remplacé par autant d'astérisques qu'il a de caractères
+    pub fn mask(&self, text: &str) -> String {
+        let mut masked = String::with_capacity(text.len());
+        let mut word = String::new();
+        let flush = |word: &mut String, masked: &mut String| {
+            if self.words.contains(&word.to_lowercase()) {
+                masked.extend(word.chars().map(|_| '*'));
+            } else {
+                masked.push_str(word);
+            }
+            word.clear();
+        };
+        for c in text.chars() {
+            if c.is_alphanumeric() {
+                word.push(c);
+            } else {
+                flush(&mut word, &mut masked);
+                masked.push(c);
+            }
+        }
+        flush(&mut word, &mut masked);
+        masked
+    }
+}
+
+impl MessageFilter for BannedWords {
+    fn name(&self) -> &'static str {
+        "banned-words"
+    }
+
+    fn filter(&self, _ctx: &FilterContext, message: &mut Message) -> FilterDecision {
+        if let Some(content) = content_mut(message) {
+            *content = self.mask(content);
+        }
+        FilterDecision::Accept
+    }
+}
+
+/// Remplace les liens (http://, https://, www.) par LINK_PLACEHOLDER
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LinkStripper;
+
+impl LinkStripper {
+    fn is_link(word: &str) -> bool {
+        let word = word.to_lowercase();
+        word.contains("http://") || word.contains("https://") || word.starts_with("www.")
+    }
+
+    /// `text` sans ses liens ; les espaces et retours à la ligne sont conservés
+    pub fn strip(text: &str) -> String {
+        text.split_inclusive(char::is_whitespace)
+            .map(|piece| {
+                let word = piece.trim_end_matches(char::is_whitespace);
+                if Self::is_link(word) {
+                    format!("{}{}", LINK_PLACEHOLDER, &piece[word.len()..])
+                } else {
+                    piece.to_string()
+                }
+            })
+            .collect()
+    }
+}
+
+impl MessageFilter for LinkStripper {
+    fn name(&self) -> &'static str {
+        "links"
+    }
+
+    fn filter(&self, _ctx: &FilterContext, message: &mut Message) -> FilterDecision {
+        if let Some(content) = content_mut(message) {
+            if content.split_whitespace().any(Self::is_link) {
+                *content = Self::strip(content);
+            }
+        }
+        FilterDecision::Accept
+    }
+}
+
+/// Messages récents de chaque (salon, utilisateur), avec leur date d'envoi
+type RecentMessages = HashMap<(RoomId, String), VecDeque<(Instant, String)>>;
+
+/// Refuse un message déjà envoyé `limit` fois dans le même salon par le même utilisateur pendant `window`.
+/// La comparaison ignore la casse et les espaces répétés
+#[derive(Debug)]
+pub struct DuplicateSpam {
+    limit: usize,
+    window: Duration,
+    recent: Mutex<RecentMessages>,
+}
+
+impl DuplicateSpam {
+    pub fn new(limit: usize, window: Duration) -> Self {
+        Self { limit: limit.max(1), window, recent: Mutex::new(HashMap::new()) }
+    }
+
+    fn normalize(content: &str) -> String {
+        content.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase()
+    }
+}
+
+impl MessageFilter for DuplicateSpam {
+    fn name(&self) -> &'static str {
+        "duplicates"
+    }
+
+    fn filter(&self, ctx: &FilterContext, message: &mut Message) -> FilterDecision {
+        let Some(content) = content_mut(message) else {
+            return FilterDecision::Accept;
+        };
+        let content = Self::normalize(content);
+        let expired = |at: &Instant| ctx.now.saturating_duration_since(*at) >= self.window;
+
+        let mut recent = self.recent.lock().unwrap_or_else(PoisonError::into_inner);
+        let key = (ctx.room_id.to_string(), ctx.username.to_string());
+        if !recent.contains_key(&key) {
+            // Nouvel expéditeur : on en profite pour oublier ceux qui se sont tus
+            recent.retain(|_, sent| sent.back().is_some_and(|(at, _)| !expired(at)));
+        }
+        let sent = recent.entry(key).or_default();
+        while sent.front().is_some_and(|(at, _)| expired(at)) {
+            sent.pop_front();
+        }
+        if sent.iter().filter(|(_, previous)| *previous == content).count() >= self.limit {
+            return FilterDecision::Reject(
+                ErrorCode::RateLimitExceeded,
+                format!("Message identique envoyé {} fois en moins de {}s", self.limit, self.window.as_secs()),
+            );
+        }
+        sent.push_back((ctx.now, content));
+        FilterDecision::Accept
+    }
+}
+
+/// Filtres appliqués dans l'ordre ; le premier refus l'emporte
+#[derive(Default)]
+pub struct FilterChain {
+    filters: Vec<Box<dyn MessageFilter>>,
+}
+
+impl FilterChain {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Ajouter un filtre en fin de chaîne
+    pub fn with(mut self, filter: impl MessageFilter + 'static) -> Self {
+        self.filters.push(Box::new(filter));
+        self
+    }
+
+    /// Chaîne décrite par des réglages : répétitions d'abord (sur le texte d'origine), puis mots interdits, puis liens
+    pub fn from_rules(rules: &FilterRules) -> Self {
+        let mut chain = Self::new();
+        if rules.duplicate_limit > 0 {
+            chain = chain.with(DuplicateSpam::new(rules.duplicate_limit as usize, Duration::from_secs(rules.duplicate_window_secs)));
+        }
+        if !rules.banned_words.is_empty() {
+            chain = chain.with(BannedWords::new(&rules.banned_words));
+        }
+        if rules.strip_links {
+            chain = chain.with(LinkStripper);
+        }
+        chain
+    }
+
+    pub fn apply(&self, ctx: &FilterContext, message: &mut Message) -> FilterDecision {
+        for filter in &self.filters {
+            if let decision @ FilterDecision::Reject(..) = filter.filter(ctx, message) {
+                return decision;
+            }
+        }
+        FilterDecision::Accept
+    }
+
+    pub fn names(&self) -> Vec<&'static str> {
+        self.filters.iter().map(|filter| filter.name()).collect()
+    }
+}
+
+/// Réglages des filtres d'un salon
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FilterRules {
+    /// Mots masqués par des astérisques
+    pub banned_words: Vec<String>,
+    /// Remplacer les liens par LINK_PLACEHOLDER
+    pub strip_links: bool,
+    /// Nombre d'envois identiques tolérés pendant `duplicate_window_secs` (0 : pas de limite)
+    pub duplicate_limit: u32,
+    pub duplicate_window_secs: u64,
+}
+
+impl Default for FilterRules {
+    fn default() -> Self {
+        Self { banned_words: Vec::new(), strip_links: false, duplicate_limit: 3, duplicate_window_secs: 30 }
+    }
+}
+
+impl FilterRules {
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(word) = self.banned_words.iter().find(|word| word.is_empty() || !word.chars().all(char::is_alphanumeric)) {
+            return Err(format!("Mot interdit invalide: {:?} (lettres et chiffres seulement)", word));
+        }
+        if self.duplicate_limit > 0 && self.duplicate_window_secs == 0 {
+            return Err("duplicate_window_secs doit être positif si duplicate_limit l'est".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Filtres de tous les salons : les réglages communs, remplacés en entier pour les salons listés dans `rooms`
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FilterConfig {
+    pub banned_words: Vec<String>,
+    pub strip_links: bool,
+    pub duplicate_limit: u32,
+    pub duplicate_window_secs: u64,
+    pub rooms: BTreeMap<RoomId, FilterRules>,
+}
+
+impl Default for FilterConfig {
+    fn default() -> Self {
+        let FilterRules { banned_words, strip_links, duplicate_limit, duplicate_window_secs } = FilterRules::default();
+        Self { banned_words, strip_links, duplicate_limit, duplicate_window_secs, rooms: BTreeMap::new() }
+    }
+}
+
+impl FilterConfig {
+    /// Réglages communs, pour les salons sans réglage à part
+    pub fn defaults(&self) -> FilterRules {
+        FilterRules {
+            banned_words: self.banned_words.clone(),
+            strip_links: self.strip_links,
+            duplicate_limit: self.duplicate_limit,
+            duplicate_window_secs: self.duplicate_window_secs,
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        self.defaults().validate()?;
+        for (room_id, rules) in &self.rooms {
+            validate_room_id(room_id)?;
+            rules.validate().map_err(|e| format!("{} (salon {})", e, room_id))?;
+        }
+        Ok(())
+    }
+}
+
+/// Une chaîne par salon réglé à part, la chaîne commune pour les autres
+#[derive(Default)]
+pub struct RoomFilters {
+    defaults: FilterChain,
+    rooms: HashMap<RoomId, FilterChain>,
+}
+
+impl RoomFilters {
+    pub fn new(config: &FilterConfig) -> Self {
+        Self {
+            defaults: FilterChain::from_rules(&config.defaults()),
+            rooms: config.rooms.iter().map(|(room_id, rules)| (room_id.clone(), FilterChain::from_rules(rules))).collect(),
+        }
+    }
+
+    pub fn chain(&self, room_id: &str) -> &FilterChain {
+        self.rooms.get(room_id).unwrap_or(&self.defaults)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocole::MessageKind;
+
+    fn send(content: &str) -> Message {
+        Message::SendMessage { content: content.to_string(), kind: MessageKind::Text, parent_message_id: None }
+    }
+
+    fn content(message: &Message) -> &str {
+        match message {
+            Message::SendMessage { content, .. } => content,
+            other => panic!("message inattendu: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_masks_and_strips() {
+        let rules = FilterRules { banned_words: vec!["Zut".to_string()], strip_links: true, ..FilterRules::default() };
+        let chain = FilterChain::from_rules(&rules);
+        assert_eq!(chain.names(), ["duplicates", "banned-words", "links"]);
+
+        let ctx = FilterContext { room_id: "general", username: "alice", now: Instant::now() };
+        let mut message = send("ZUT alors, zutique!\nvoir https://exemple.fr et www.a.b");
+        assert_eq!(chain.apply(&ctx, &mut message), FilterDecision::Accept);
+        assert_eq!(content(&message), "*** alors, zutique!\nvoir [lien retiré] et [lien retiré]");
+
+        // Les autres messages ne sont pas touchés
+        let mut message = Message::PrivateMessage { target_user: "bob".to_string(), content: "zut".to_string() };
+        assert_eq!(chain.apply(&ctx, &mut message), FilterDecision::Accept);
+    }
+
+    #[test]
+    fn test_duplicates() {
+        let filter = DuplicateSpam::new(2, Duration::from_secs(30));
+        let start = Instant::now();
+        let at = |room_id, username, secs| FilterContext { room_id, username, now: start + Duration::from_secs(secs) };
+
+        assert_eq!(filter.filter(&at("general", "alice", 0), &mut send("Salut")), FilterDecision::Accept);
+        assert_eq!(filter.filter(&at("general", "alice", 1), &mut send("salut  ")), FilterDecision::Accept);
+        assert!(matches!(filter.filter(&at("general", "alice", 2), &mut send("SALUT")), FilterDecision::Reject(ErrorCode::RateLimitExceeded, _)));
+
+        // Autre salon, autre utilisateur ou fenêtre écoulée : accepté
+        assert_eq!(filter.filter(&at("tech", "alice", 2), &mut send("salut")), FilterDecision::Accept);
+        assert_eq!(filter.filter(&at("general", "bob", 2), &mut send("salut")), FilterDecision::Accept);
+        assert_eq!(filter.filter(&at("general", "alice", 31), &mut send("salut")), FilterDecision::Accept);
+    }
+}
@@ -0,0 +1,496 @@
+// src/historique.rs
+// Historique des salons : tampon circulaire en mémoire, persistance optionnelle sur disque, recherche
+
+use std::collections::{HashSet, VecDeque};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+
+use crate::protocole::HistoryEntry;
+
+/// Nombre de messages conservés en mémoire par salon, par défaut
+pub const HISTORY_CAPACITY: usize = 100;
+
+/// Nombre de messages renvoyés à un client qui rejoint un salon, par défaut
+pub const HISTORY_REPLAY_ON_JOIN: usize = 20;
+
+/// Nombre maximal de résultats renvoyés par une recherche
+pub const MAX_SEARCH_RESULTS: usize = 50;
+
+/// Longueur maximale d'un motif de recherche
+pub const MAX_SEARCH_QUERY_LENGTH: usize = 200;
+
+/// Recherche dans le contenu des messages : `/motif/` est une expression régulière,
+/// tout autre texte est cherché tel quel, sans tenir compte de la casse
+#[derive(Debug, Clone)]
+pub enum SearchQuery {
+    Substring(String), // en minuscules
+    Regex(Regex),
+}
+
+impl SearchQuery {
+    pub fn parse(query: &str) -> Result<Self, String> {
+        let query = query.trim();
+        if query.is_empty() || query.chars().count() > MAX_SEARCH_QUERY_LENGTH {
+            return Err(format!("Le motif de recherche doit faire entre 1 et {} caractères", MAX_SEARCH_QUERY_LENGTH));
+        }
+        match query.strip_prefix('/').and_then(|q| q.strip_suffix('/')) {
+            Some(pattern) if !pattern.is_empty() => RegexBuilder::new(pattern)
+                .size_limit(1 << 20)
+                .build()
+                .map(SearchQuery::Regex)
+                .map_err(|e| format!("Expression régulière invalide: {}", e)),
+            _ => Ok(SearchQuery::Substring(query.to_lowercase())),
+        }
+    }
+
+    pub fn matches(&self, entry: &HistoryEntry) -> bool {
+        match self {
+            SearchQuery::Substring(text) => entry.content.to_lowercase().contains(text),
+            SearchQuery::Regex(regex) => regex.is_match(&entry.content),
+        }
+    }
+}
+
+/// Garder les `limit` derniers messages qui correspondent, du plus ancien au plus récent
+fn last_matches(entries: impl Iterator<Item = HistoryEntry>, query: &SearchQuery, limit: usize) -> Vec<HistoryEntry> {
+    if limit == 0 {
+        return Vec::new();
+    }
+    let mut found = VecDeque::with_capacity(limit.min(MAX_SEARCH_RESULTS));
+    for entry in entries.filter(|e| query.matches(e)) {
+        if found.len() == limit {
+            found.pop_front();
+        }
+        found.push_back(entry);
+    }
+    found.into()
+}
+
+/// Derniers messages d'un salon (les plus anciens sont oubliés au-delà de la capacité)
+#[derive(Debug, Clone, PartialEq)]
+pub struct RoomHistory {
+    entries: VecDeque<HistoryEntry>,
+    capacity: usize,
+    last_sequence: u64, // Numéro du dernier message du salon, y compris ceux déjà oubliés
+}
+
+impl Default for RoomHistory {
+    fn default() -> Self {
+        Self::with_capacity(HISTORY_CAPACITY)
+    }
+}
+
+impl RoomHistory {
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            entries: VecDeque::with_capacity(capacity),
+            capacity: capacity.max(1),
+            last_sequence: 0,
+        }
+    }
+
+    /// Numéro de séquence à attribuer au prochain message du salon
+    pub fn next_sequence(&self) -> u64 {
+        self.last_sequence + 1
+    }
+
+    pub fn last_sequence(&self) -> u64 {
+        self.last_sequence
+    }
+
+    pub fn push(&mut self, entry: HistoryEntry) {
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.last_sequence = self.last_sequence.max(entry.sequence);
+        self.entries.push_back(entry);
+    }
+
+    /// Heure de réception du dernier message en mémoire, si elle est connue
+    pub fn last_received_at(&self) -> Option<DateTime<Utc>> {
+        self.entries.back().and_then(|e| e.received_at)
+    }
+
+    /// Les `count` derniers messages, du plus ancien au plus récent selon l'heure du serveur
+    pub fn last(&self, count: usize) -> Vec<HistoryEntry> {
+        let skip = self.entries.len().saturating_sub(count);
+        chronological(self.entries.iter().skip(skip).cloned().collect())
+    }
+
+    /// Les messages postérieurs au numéro `sequence` encore en mémoire, du plus ancien au plus récent selon l'heure du serveur
+    pub fn since(&self, sequence: u64) -> Vec<HistoryEntry> {
+        chronological(self.entries.iter().filter(|e| e.sequence > sequence).cloned().collect())
+    }
+
+    /// Nombre de messages en mémoire postérieurs au numéro `sequence` et écrits par un autre que `reader`
+    pub fn unread(&self, sequence: u64, reader: &str) -> usize {
+        self.entries.iter().filter(|e| e.sequence > sequence && e.from != reader).count()
+    }
+
+    /// Le message numéro `sequence`, s'il est encore en mémoire
+    pub fn get(&self, sequence: u64) -> Option<&HistoryEntry> {
+        self.entries.iter().find(|e| e.sequence == sequence)
+    }
+
+    /// Le message `id`, s'il est encore en mémoire
+    pub fn find(&self, id: &str) -> Option<&HistoryEntry> {
+        self.entries.iter().find(|e| !id.is_empty() && e.id == id)
+    }
+
+    /// Le fil de discussion du message `id` : le message qui l'a lancé puis toutes les réponses encore en
+    /// mémoire, directes ou non, du plus ancien au plus récent ; vide si `id` n'est plus en mémoire
+    pub fn thread(&self, id: &str) -> Vec<HistoryEntry> {
+        let Some(mut root) = self.find(id) else {
+            return Vec::new();
+        };
+        // Remonter jusqu'au premier message encore en mémoire (borné, au cas où un serveur fédéré bouclerait)
+        for _ in 0..self.entries.len() {
+            match root.parent_id.as_deref().and_then(|parent| self.find(parent)) {
+                Some(parent) => root = parent,
+                None => break,
+            }
+        }
+
+        // Une réponse arrive toujours après son parent : un seul passage suffit
+        let mut ids: HashSet<&str> = HashSet::from([root.id.as_str()]);
+        let mut thread = Vec::new();
+        for entry in &self.entries {
+            if entry.id == root.id || entry.parent_id.as_deref().is_some_and(|parent| ids.contains(parent)) {
+                ids.insert(&entry.id);
+                thread.push(entry.clone());
+            }
+        }
+        chronological(thread)
+    }
+
+    /// Remplacer le contenu du message `id` s'il est encore en mémoire ; renvoie le message modifié
+    pub fn edit(&mut self, id: &str, content: &str, at: DateTime<Utc>) -> Option<HistoryEntry> {
+        let entry = self.entries.iter_mut().find(|e| !id.is_empty() && e.id == id)?;
+        entry.content = content.to_string();
+        entry.edited_at = Some(at);
+        Some(entry.clone())
+    }
+
+    /// Retirer le message `id` s'il est encore en mémoire (son numéro n'est pas réattribué)
+    pub fn remove(&mut self, id: &str) -> Option<HistoryEntry> {
+        let position = self.entries.iter().position(|e| !id.is_empty() && e.id == id)?;
+        self.entries.remove(position)
+    }
+
+    /// Les `limit` derniers messages en mémoire qui correspondent à la recherche
+    pub fn search(&self, query: &SearchQuery, limit: usize) -> Vec<HistoryEntry> {
+        last_matches(self.entries.iter().cloned(), query, limit)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+/// Trier des messages selon l'heure du serveur, puis leur numéro. Les nouveaux messages sont déjà dans cet
+/// ordre ; les anciens messages relayés par la fédération n'ont que l'heure d'envoi de leur serveur d'origine
+fn chronological(mut entries: Vec<HistoryEntry>) -> Vec<HistoryEntry> {
+    entries.sort_by_key(|e| (e.server_time(), e.sequence));
+    entries
+}
+
+/// Ligne du fichier d'un salon : un message, ou la trace d'un message supprimé
+enum Record {
+    Entry(HistoryEntry),
+    Deleted(u64),
+}
+
+/// Trace laissée par un message supprimé : son numéro ne doit pas être réattribué au redémarrage
+#[derive(Serialize, Deserialize)]
+struct Tombstone {
+    deleted: u64,
+}
+
+/// Persistance de l'historique : un fichier JSON Lines par salon dans un répertoire
+#[derive(Debug, Clone)]
+pub struct HistoryStore {
+    dir: PathBuf,
+}
+
+impl HistoryStore {
+    /// Ouvrir (et créer si besoin) le répertoire de stockage
+    pub fn open(dir: impl AsRef<Path>) -> io::Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+        Ok(Self { dir })
+    }
+
+    fn path(&self, room_id: &str) -> PathBuf {
+        // Les identifiants de salon ne doivent pas sortir du répertoire
+        let name: String = room_id
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+            .collect();
+        self.dir.join(format!("{}.jsonl", name))
+    }
+
+    /// Ajouter un message à la fin du fichier du salon
+    pub fn append(&self, room_id: &str, entry: &HistoryEntry) -> io::Result<()> {
+        let mut file = OpenOptions::new().create(true).append(true).open(self.path(room_id))?;
+        let line = serde_json::to_string(entry).map_err(io::Error::other)?;
+        writeln!(file, "{}", line)
+    }
+
+    /// Réécrire le fichier d'un salon en remplaçant le message `id` par `replacement`, ou par une trace de
+    /// sa suppression. Les autres lignes sont recopiées telles quelles ; le fichier n'est jamais laissé à moitié écrit
+    pub fn rewrite(&self, room_id: &str, id: &str, replacement: Option<&HistoryEntry>) -> io::Result<()> {
+        let path = self.path(room_id);
+        let lines = match File::open(&path) {
+            Ok(file) => BufReader::new(file).lines().collect::<io::Result<Vec<String>>>()?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e),
+        };
+
+        let mut contents = String::new();
+        for line in lines {
+            let matching = serde_json::from_str::<HistoryEntry>(&line).ok().filter(|entry| !id.is_empty() && entry.id == id);
+            let line = match (matching, replacement) {
+                (None, _) => line,
+                (Some(_), Some(entry)) => serde_json::to_string(entry).map_err(io::Error::other)?,
+                (Some(entry), None) => serde_json::to_string(&Tombstone { deleted: entry.sequence }).map_err(io::Error::other)?,
+            };
+            contents.push_str(&line);
+            contents.push('\n');
+        }
+        let temporary = path.with_extension("tmp");
+        fs::write(&temporary, contents)?;
+        fs::rename(temporary, path)
+    }
+
+    /// Oublier l'historique d'un salon supprimé
+    pub fn remove(&self, room_id: &str) -> io::Result<()> {
+        match fs::remove_file(self.path(room_id)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    /// Recharger l'historique d'un salon ; les lignes illisibles sont ignorées
+    pub fn load(&self, room_id: &str, capacity: usize) -> io::Result<RoomHistory> {
+        let mut history = RoomHistory::with_capacity(capacity);
+        for record in self.records(room_id)? {
+            match record? {
+                Record::Entry(entry) => history.push(entry),
+                Record::Deleted(sequence) => history.last_sequence = history.last_sequence.max(sequence),
+            }
+        }
+        Ok(history)
+    }
+
+    /// Chercher dans tout l'historique enregistré d'un salon, y compris les messages oubliés en mémoire
+    pub fn search(&self, room_id: &str, query: &SearchQuery, limit: usize) -> io::Result<Vec<HistoryEntry>> {
+        let mut error = None;
+        let entries = self.entries(room_id)?.map_while(|entry| entry.map_err(|e| error = Some(e)).ok());
+        let found = last_matches(entries, query, limit);
+        match error {
+            Some(e) => Err(e),
+            None => Ok(found),
+        }
+    }
+
+    /// Messages du fichier d'un salon, dans l'ordre ; les lignes illisibles sont ignorées
+    fn entries(&self, room_id: &str) -> io::Result<impl Iterator<Item = io::Result<HistoryEntry>>> {
+        Ok(self.records(room_id)?.filter_map(|record| match record {
+            Ok(Record::Entry(entry)) => Some(Ok(entry)),
+            Ok(Record::Deleted(_)) => None,
+            Err(e) => Some(Err(e)),
+        }))
+    }
+
+    /// Lignes du fichier d'un salon, dans l'ordre ; les lignes illisibles sont ignorées
+    fn records(&self, room_id: &str) -> io::Result<impl Iterator<Item = io::Result<Record>>> {
+        let lines = match File::open(self.path(room_id)) {
+            Ok(file) => Some(BufReader::new(file).lines()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+            Err(e) => return Err(e),
+        };
+        let mut last_sequence = 0;
+        Ok(lines.into_iter().flatten().filter_map(move |line| {
+            let line = match line {
+                Ok(line) => line,
+                Err(e) => return Some(Err(e)),
+            };
+            let Ok(mut entry) = serde_json::from_str::<HistoryEntry>(&line) else {
+                let tombstone = serde_json::from_str::<Tombstone>(&line).ok()?;
+                last_sequence = last_sequence.max(tombstone.deleted);
+                return Some(Ok(Record::Deleted(tombstone.deleted)));
+            };
+            // Les fichiers antérieurs à la numérotation sont renumérotés dans l'ordre
+            if entry.sequence == 0 {
+                entry.sequence = last_sequence + 1;
+            }
+            last_sequence = last_sequence.max(entry.sequence);
+            Some(Ok(Record::Entry(entry)))
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocole::MessageKind;
+    use chrono::Utc;
+
+    fn entry(content: &str) -> HistoryEntry {
+        HistoryEntry {
+            from: "alice".to_string(),
+            content: content.to_string(),
+            timestamp: Utc::now(),
+            sequence: 0,
+            id: format!("id-{}", content),
+            edited_at: None,
+            kind: MessageKind::Text,
+            received_at: None,
+            parent_id: None,
+        }
+    }
+
+    #[test]
+    fn test_ring_buffer_keeps_latest() {
+        let mut history = RoomHistory::with_capacity(3);
+        for i in 0..5 {
+            history.push(entry(&i.to_string()));
+        }
+        assert_eq!(history.len(), 3);
+        let contents: Vec<String> = history.last(2).into_iter().map(|e| e.content).collect();
+        assert_eq!(contents, vec!["3", "4"]);
+        assert_eq!(history.last(10).len(), 3);
+    }
+
+    #[test]
+    fn test_sequence_numbers() {
+        let mut history = RoomHistory::with_capacity(3);
+        for i in 0..5 {
+            let mut e = entry(&i.to_string());
+            e.sequence = history.next_sequence();
+            history.push(e);
+        }
+        assert_eq!(history.last_sequence(), 5);
+        let sequences: Vec<u64> = history.since(3).into_iter().map(|e| e.sequence).collect();
+        assert_eq!(sequences, vec![4, 5]);
+        // Les messages oubliés ne peuvent plus être renvoyés
+        assert_eq!(history.since(0).len(), 3);
+        assert_eq!(history.unread(3, "bob"), 2);
+        assert_eq!(history.unread(3, "alice"), 0); // Ses propres messages ne sont pas à lire
+    }
+
+    #[test]
+    fn test_replay_by_server_time() {
+        // Un message relayé d'un serveur en avance, puis un message local : l'heure du serveur décide
+        let now = Utc::now();
+        let mut history = RoomHistory::with_capacity(10);
+        let mut relayed = entry("relayé");
+        (relayed.sequence, relayed.timestamp) = (1, now + chrono::TimeDelta::minutes(10));
+        let mut local = entry("local");
+        (local.sequence, local.timestamp, local.received_at) = (2, now - chrono::TimeDelta::minutes(1), Some(now));
+        let mut old = entry("ancien");
+        (old.sequence, old.timestamp) = (3, now - chrono::TimeDelta::minutes(5));
+        for e in [relayed, local, old] {
+            history.push(e);
+        }
+        let contents: Vec<String> = history.last(10).into_iter().map(|e| e.content).collect();
+        assert_eq!(contents, vec!["ancien", "local", "relayé"]);
+        assert_eq!(history.since(1).len(), 2);
+        assert_eq!(history.last_received_at(), None);
+    }
+
+    #[test]
+    fn test_thread() {
+        let mut history = RoomHistory::with_capacity(10);
+        let reply = |content: &str, parent: &str| HistoryEntry { parent_id: Some(format!("id-{}", parent)), ..entry(content) };
+        for (sequence, e) in [
+            entry("question"),
+            entry("autre sujet"),
+            reply("réponse", "question"),
+            reply("hors fil", "autre sujet"),
+            reply("réponse à la réponse", "réponse"),
+        ].into_iter().enumerate() {
+            history.push(HistoryEntry { sequence: sequence as u64 + 1, ..e });
+        }
+
+        // Le même fil, quel que soit le message demandé
+        for id in ["id-question", "id-réponse à la réponse"] {
+            let contents: Vec<String> = history.thread(id).into_iter().map(|e| e.content).collect();
+            assert_eq!(contents, vec!["question", "réponse", "réponse à la réponse"]);
+        }
+        assert_eq!(history.thread("id-autre sujet").len(), 2);
+        assert!(history.thread("id-inconnu").is_empty());
+    }
+
+    #[test]
+    fn test_search() {
+        let dir = std::env::temp_dir().join(format!("tp8-recherche-{}", std::process::id()));
+        let store = HistoryStore::open(&dir).unwrap();
+        for content in ["Rust 1.80 est sorti", "vive rust", "réunion à 14h", "Rust 2024"] {
+            store.append("general", &entry(content)).unwrap();
+        }
+
+        let query = SearchQuery::parse("RUST").unwrap();
+        let found: Vec<String> = store.search("general", &query, 2).unwrap().into_iter().map(|e| e.content).collect();
+        assert_eq!(found, vec!["vive rust", "Rust 2024"]);
+        let query = SearchQuery::parse(r"/\d+h/").unwrap();
+        assert_eq!(store.search("general", &query, 10).unwrap()[0].sequence, 3);
+        // La recherche porte aussi sur les messages sortis du tampon en mémoire
+        assert_eq!(store.load("general", 1).unwrap().search(&query, 10).len(), 0);
+
+        assert!(SearchQuery::parse("/(/").is_err());
+        assert!(SearchQuery::parse("  ").is_err());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_store_round_trip() {
+        let dir = std::env::temp_dir().join(format!("tp8-historique-{}", std::process::id()));
+        let store = HistoryStore::open(&dir).unwrap();
+        store.append("general", &entry("bonjour")).unwrap();
+        store.append("general", &entry("salut")).unwrap();
+
+        let history = store.load("general", 10).unwrap();
+        assert_eq!(history.last(1)[0].content, "salut");
+        assert_eq!(history.last_sequence(), 2);
+        assert!(store.load("inconnu", 10).unwrap().is_empty());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_store_rewrite() {
+        let dir = std::env::temp_dir().join(format!("tp8-reecriture-{}", std::process::id()));
+        let store = HistoryStore::open(&dir).unwrap();
+        for (sequence, content) in (1..).zip(["un", "deux", "trois"]) {
+            store.append("general", &HistoryEntry { sequence, ..entry(content) }).unwrap();
+        }
+        let mut history = store.load("general", 10).unwrap();
+
+        let edited = history.edit("id-un", "premier", Utc::now()).unwrap();
+        store.rewrite("general", "id-un", Some(&edited)).unwrap();
+        for id in ["id-deux", "id-trois"] {
+            let removed = history.remove(id).unwrap();
+            store.rewrite("general", &removed.id, None).unwrap();
+        }
+        assert!(history.remove("id-deux").is_none());
+
+        // Le fichier relu donne le même historique ; le numéro du dernier message, supprimé, reste pris
+        let reloaded = store.load("general", 10).unwrap();
+        assert_eq!(reloaded, history);
+        assert_eq!(reloaded.last(10).len(), 1);
+        assert!(reloaded.last(1)[0].edited_at.is_some());
+        assert_eq!(reloaded.next_sequence(), 4);
+        let query = SearchQuery::parse("e").unwrap();
+        assert_eq!(store.search("general", &query, 10).unwrap().len(), 1);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
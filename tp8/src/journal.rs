@@ -0,0 +1,214 @@
+// src/journal.rs
+// Journal des événements du serveur : console (et par elle le serveur de journalisation tp3) et fichier JSON, une ligne par événement
+
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::{Mutex, PoisonError};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+/// Gravité d'un événement
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Level {
+    Info,
+    Warn,
+    Error,
+}
+
+/// Événement du serveur de discussion
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum ChatEvent {
+    Connected { client_id: String, addr: String },
+    /// Fin d'une connexion, authentifiée ou non
+    Disconnected { client_id: String, username: Option<String> },
+    Authenticated { client_id: String, username: String, registered: bool },
+    Joined { username: String, room_id: String },
+    Left { username: String, room_id: String },
+    /// Message de salon ; `origin` est le serveur fédéré d'où il vient
+    RoomMessage {
+        room_id: String,
+        from: String,
+        content: String,
+        message_id: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        origin: Option<String>,
+    },
+    PrivateMessage { from: String, to: String, content: String, offline: bool },
+    Error {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        client_id: Option<String>,
+        message: String,
+    },
+    /// Tout le reste : démarrage, modération, transferts, fédération...
+    Notice {
+        #[serde(skip)] // Déjà dans l'entrée du journal
+        level: Level,
+        message: String,
+    },
+}
+
+impl ChatEvent {
+    pub fn info(message: impl Into<String>) -> Self {
+        ChatEvent::Notice { level: Level::Info, message: message.into() }
+    }
+
+    pub fn warn(message: impl Into<String>) -> Self {
+        ChatEvent::Notice { level: Level::Warn, message: message.into() }
+    }
+
+    pub fn error(client_id: Option<&str>, message: impl Into<String>) -> Self {
+        ChatEvent::Error { client_id: client_id.map(String::from), message: message.into() }
+    }
+
+    pub fn level(&self) -> Level {
+        match self {
+            ChatEvent::Notice { level, .. } => *level,
+            ChatEvent::Error { .. } => Level::Error,
+            _ => Level::Info,
+        }
+    }
+}
+
+/// Forme lisible, pour la console
+impl fmt::Display for ChatEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChatEvent::Connected { client_id, addr } => write!(f, "🔗 New connection: {} ({})", addr, client_id),
+            ChatEvent::Disconnected { client_id, username: Some(username) } => write!(f, "🔌 {} disconnected ({})", username, client_id),
+            ChatEvent::Disconnected { client_id, username: None } => write!(f, "🔌 Client {} disconnected", client_id),
+            ChatEvent::Authenticated { client_id, username, registered: true } => write!(f, "🆕 Compte {} créé, utilisateur authentifié ({})", username, client_id),
+            ChatEvent::Authenticated { client_id, username, registered: false } => write!(f, "✅ Utilisateur {} authentifié ({})", username, client_id),
+            ChatEvent::Joined { username, room_id } => write!(f, "🚪 {} a rejoint le salon {}", username, room_id),
+            ChatEvent::Left { username, room_id } => write!(f, "🚪 {} a quitté le salon {}", username, room_id),
+            ChatEvent::RoomMessage { room_id, from, content, origin: None, .. } => write!(f, "💬 [{}] {}: {}", room_id, from, content),
+            ChatEvent::RoomMessage { room_id, from, content, origin: Some(_), .. } => write!(f, "🌐 [{}] {}: {}", room_id, from, content),
+            ChatEvent::PrivateMessage { from, to, content, offline: false } => write!(f, "📩 {} -> {} (privé): {}", from, to, content),
+            ChatEvent::PrivateMessage { from, to, content, offline: true } => write!(f, "📬 {} -> {} (privé, hors ligne): {}", from, to, content),
+            ChatEvent::Error { client_id: Some(client_id), message } => write!(f, "❌ Client {}: {}", client_id, message),
+            ChatEvent::Error { client_id: None, message } => write!(f, "❌ {}", message),
+            ChatEvent::Notice { message, .. } => f.write_str(message),
+        }
+    }
+}
+
+/// Entrée du journal : l'événement, daté et signé du nom du serveur
+#[derive(Debug, Serialize)]
+pub struct LogRecord<'a> {
+    pub timestamp: DateTime<Utc>,
+    pub server: &'a str,
+    pub level: Level,
+    #[serde(flatten)]
+    pub event: &'a ChatEvent,
+}
+
+impl LogRecord<'_> {
+    /// Une ligne JSON, sans retour à la ligne final
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_else(|e| format!("{{\"event\":\"error\",\"message\":\"journal: {}\"}}", e))
+    }
+}
+
+/// Destination des événements ; `log` ne doit pas bloquer le serveur
+pub trait EventLogger: Send + Sync {
+    fn log(&self, record: &LogRecord);
+}
+
+/// Transmission à `tracing`, qui affiche selon les réglages du programme (niveaux, texte ou JSON) et
+/// envoie au serveur de journalisation s'il y en a un (voir `trace_commun::LogOptions::forward`)
+#[derive(Debug, Default)]
+pub struct ConsoleLogger;
+
+impl EventLogger for ConsoleLogger {
+    fn log(&self, record: &LogRecord) {
+        match record.level {
+            Level::Info => tracing::info!("{}", record.event),
+            Level::Warn => tracing::warn!("{}", record.event),
+            Level::Error => tracing::error!("{}", record.event),
+        }
+    }
+}
+
+/// Ajout des événements à un fichier, une ligne JSON chacun
+#[derive(Debug)]
+pub struct JsonFileLogger {
+    file: Mutex<File>,
+}
+
+impl JsonFileLogger {
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self { file: Mutex::new(file) })
+    }
+}
+
+impl EventLogger for JsonFileLogger {
+    fn log(&self, record: &LogRecord) {
+        let mut file = self.file.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Err(e) = writeln!(file, "{}", record.to_json()) {
+            tracing::warn!("⚠️ Écriture du journal impossible: {}", e);
+        }
+    }
+}
+
+/// Journal du serveur : chaque événement part vers toutes les destinations
+#[derive(Default)]
+pub struct Journal {
+    server: String,
+    loggers: Vec<Box<dyn EventLogger>>,
+}
+
+impl Journal {
+    /// Journal sans destination, signé `server`
+    pub fn new(server: impl Into<String>) -> Self {
+        Self { server: server.into(), loggers: Vec::new() }
+    }
+
+    pub fn with(mut self, logger: impl EventLogger + 'static) -> Self {
+        self.loggers.push(Box::new(logger));
+        self
+    }
+
+    pub fn log(&self, event: ChatEvent) {
+        let record = LogRecord { timestamp: Utc::now(), server: &self.server, level: event.level(), event: &event };
+        for logger in &self.loggers {
+            logger.log(&record);
+        }
+    }
+
+    pub fn info(&self, message: impl Into<String>) {
+        self.log(ChatEvent::info(message));
+    }
+
+    pub fn warn(&self, message: impl Into<String>) {
+        self.log(ChatEvent::warn(message));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_record() {
+        let event = ChatEvent::RoomMessage {
+            room_id: "general".to_string(),
+            from: "alice".to_string(),
+            content: "Bonjour\nà tous".to_string(),
+            message_id: "m1".to_string(),
+            origin: None,
+        };
+        let record = LogRecord { timestamp: Utc::now(), server: "scp", level: event.level(), event: &event };
+        let json: serde_json::Value = serde_json::from_str(&record.to_json()).unwrap();
+        assert_eq!(json["event"], "room_message");
+        assert_eq!((json["server"].as_str(), json["level"].as_str()), (Some("scp"), Some("info")));
+        assert_eq!(json["content"], "Bonjour\nà tous");
+        assert!(json.get("origin").is_none());
+        assert!(!record.to_json().contains('\n')); // Une ligne par événement
+        assert_eq!(event.to_string(), "💬 [general] alice: Bonjour\nà tous");
+    }
+}
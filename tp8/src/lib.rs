@@ -1,2 +1,21 @@
 // src/lib.rs
-pub mod protocole;
\ No newline at end of file
+pub mod admin;
+pub mod chiffrement;
+pub mod client;
+pub mod configuration;
+pub mod courrier;
+pub mod debit;
+pub mod envoi;
+pub mod fichiers;
+pub mod filtres;
+pub mod historique;
+pub mod journal;
+pub mod metriques;
+pub mod motdepasse;
+pub mod profils;
+pub mod protocole;
+pub mod salons;
+pub mod serveur;
+pub mod stockage;
+pub mod trame;
+pub mod utilisateurs;
@@ -0,0 +1,182 @@
+// src/metriques.rs
+// Mesures de l'activité du serveur, pour dimensionner les salons et régler les limites de débit :
+// messages par salon et par minute, utilisateurs actifs, taille moyenne des messages, erreurs renvoyées
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::protocole::{ErrorCode, RoomId};
+
+/// Fenêtre sur laquelle le débit des messages est mesuré
+pub const RATE_WINDOW: Duration = Duration::from_secs(60);
+
+/// Un utilisateur qui a écrit depuis moins longtemps est compté comme actif
+pub const ACTIVE_WINDOW: Duration = Duration::from_secs(300);
+
+/// Chiffres d'un salon
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct RoomStats {
+    /// Messages depuis le démarrage du serveur
+    pub messages: u64,
+    /// Messages de la dernière minute
+    pub messages_per_minute: usize,
+    /// Auteurs distincts des cinq dernières minutes
+    pub active_users: usize,
+    /// Membres présents dans le salon
+    pub members: usize,
+    /// Taille moyenne d'un message, en octets
+    pub average_size: f64,
+}
+
+/// Chiffres du serveur, renvoyés aux administrateurs par GetStats
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ServerStats {
+    pub uptime_secs: u64,
+    /// Connexions en cours
+    pub connected: usize,
+    /// Utilisateurs qui ont écrit dans un salon ces cinq dernières minutes
+    pub active_users: usize,
+    pub rooms: HashMap<RoomId, RoomStats>,
+    /// Erreurs renvoyées aux clients depuis le démarrage, par code
+    pub errors: HashMap<ErrorCode, u64>,
+}
+
+impl ServerStats {
+    /// Résumé sur une ligne pour le journal : l'activité globale, puis les salons les plus actifs
+    pub fn digest(&self) -> String {
+        let per_minute: usize = self.rooms.values().map(|room| room.messages_per_minute).sum();
+        let mut digest = format!(
+            "📊 {} connection(s), {} active user(s), {} message(s)/min",
+            self.connected, self.active_users, per_minute,
+        );
+
+        let mut busiest: Vec<(&RoomId, &RoomStats)> = self.rooms.iter().filter(|(_, room)| room.messages > 0).collect();
+        busiest.sort_by(|a, b| b.1.messages_per_minute.cmp(&a.1.messages_per_minute).then(b.1.messages.cmp(&a.1.messages)).then(a.0.cmp(b.0)));
+        for (room_id, room) in busiest.iter().take(DIGEST_ROOMS) {
+            digest.push_str(&format!(
+                "; #{} {}/min, {} active of {} member(s), {:.0} B avg",
+                room_id, room.messages_per_minute, room.active_users, room.members, room.average_size,
+            ));
+        }
+
+        if !self.errors.is_empty() {
+            let mut errors: Vec<String> = self.errors.iter().map(|(code, count)| format!("{:?} ×{}", code, count)).collect();
+            errors.sort();
+            digest.push_str(&format!("; errors: {}", errors.join(", ")));
+        }
+        digest
+    }
+}
+
+/// Salons détaillés dans le résumé du journal
+const DIGEST_ROOMS: usize = 5;
+
+#[derive(Debug, Default)]
+struct RoomCounters {
+    messages: u64,
+    bytes: u64,
+    recent: VecDeque<(Instant, String)>, // Messages de la fenêtre ACTIVE_WINDOW : heure et auteur
+}
+
+impl RoomCounters {
+    fn forget_before(&mut self, now: Instant) {
+        while self.recent.front().is_some_and(|(at, _)| now.saturating_duration_since(*at) > ACTIVE_WINDOW) {
+            self.recent.pop_front();
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    rooms: HashMap<RoomId, RoomCounters>,
+    errors: HashMap<ErrorCode, u64>,
+}
+
+/// Compteurs partagés par toutes les connexions (verrouillés en interne)
+#[derive(Debug, Default)]
+pub struct Metrics {
+    counters: Mutex<Counters>,
+}
+
+impl Metrics {
+    /// Compter un message de `size` octets écrit par `author` dans un salon
+    pub fn message(&self, room_id: &str, author: &str, size: usize, now: Instant) {
+        let mut counters = self.counters.lock().unwrap_or_else(PoisonError::into_inner);
+        let room = counters.rooms.entry(room_id.to_string()).or_default();
+        room.messages += 1;
+        room.bytes += size as u64;
+        room.forget_before(now);
+        room.recent.push_back((now, author.to_string()));
+    }
+
+    /// Compter une erreur renvoyée à un client
+    pub fn error(&self, code: &ErrorCode) {
+        let mut counters = self.counters.lock().unwrap_or_else(PoisonError::into_inner);
+        *counters.errors.entry(code.clone()).or_insert(0) += 1;
+    }
+
+    /// Oublier un salon supprimé
+    pub fn forget_room(&self, room_id: &str) {
+        self.counters.lock().unwrap_or_else(PoisonError::into_inner).rooms.remove(room_id);
+    }
+
+    /// Chiffres à l'instant `now` ; ceux que seul le serveur connaît (durée, connexions, membres) restent à zéro
+    pub fn snapshot(&self, now: Instant) -> ServerStats {
+        let mut counters = self.counters.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut active = HashSet::new();
+        let mut rooms = HashMap::new();
+        for (room_id, room) in counters.rooms.iter_mut() {
+            room.forget_before(now);
+            let authors: HashSet<&str> = room.recent.iter().map(|(_, author)| author.as_str()).collect();
+            active.extend(authors.iter().map(|author| author.to_string()));
+            rooms.insert(room_id.clone(), RoomStats {
+                messages: room.messages,
+                messages_per_minute: room.recent.iter().filter(|(at, _)| now.saturating_duration_since(*at) <= RATE_WINDOW).count(),
+                active_users: authors.len(),
+                members: 0,
+                average_size: if room.messages > 0 { room.bytes as f64 / room.messages as f64 } else { 0.0 },
+            });
+        }
+        ServerStats {
+            active_users: active.len(),
+            rooms,
+            errors: counters.errors.clone(),
+            ..ServerStats::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot() {
+        let metrics = Metrics::default();
+        let start = Instant::now();
+        metrics.message("general", "alice", 10, start);
+        metrics.message("general", "bob", 30, start + Duration::from_secs(90));
+        metrics.message("general", "alice", 20, start + Duration::from_secs(100));
+        metrics.message("tech", "carol", 5, start + Duration::from_secs(100));
+        metrics.error(&ErrorCode::RateLimitExceeded);
+        metrics.error(&ErrorCode::RateLimitExceeded);
+
+        let stats = metrics.snapshot(start + Duration::from_secs(120));
+        let general = &stats.rooms["general"];
+        assert_eq!((general.messages, general.messages_per_minute, general.active_users), (3, 2, 2));
+        assert_eq!(general.average_size, 20.0);
+        assert_eq!(stats.active_users, 3);
+        assert_eq!(stats.errors[&ErrorCode::RateLimitExceeded], 2);
+        assert!(stats.digest().contains("#general 2/min"), "{}", stats.digest());
+
+        // Plus personne d'actif, mais le total reste
+        let stats = metrics.snapshot(start + Duration::from_secs(1000));
+        assert_eq!((stats.rooms["general"].messages, stats.rooms["general"].messages_per_minute, stats.active_users), (3, 0, 0));
+
+        metrics.forget_room("tech");
+        assert!(!metrics.snapshot(start).rooms.contains_key("tech"));
+    }
+}
@@ -0,0 +1,36 @@
+// src/motdepasse.rs
+// Hachage des mots de passe (Argon2) : seul le hash est conservé côté serveur
+
+use argon2::password_hash::rand_core::OsRng;
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+
+/// Hacher un mot de passe avec un sel aléatoire (format PHC, ex. "$argon2id$...")
+pub fn hash_password(password: &str) -> Result<String, String> {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|e| format!("Erreur de hachage du mot de passe: {}", e))
+}
+
+/// Vérifier un mot de passe contre un hash produit par `hash_password`
+pub fn verify_password(password: &str, hash: &str) -> bool {
+    PasswordHash::new(hash)
+        .map(|parsed| Argon2::default().verify_password(password.as_bytes(), &parsed).is_ok())
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hash_and_verify() {
+        let hash = hash_password("secret").unwrap();
+        assert!(hash.starts_with("$argon2"));
+        assert!(verify_password("secret", &hash));
+        assert!(!verify_password("Secret", &hash));
+        assert!(!verify_password("secret", "pas un hash"));
+    }
+}
@@ -0,0 +1,241 @@
+// src/profils.rs
+// Profils des utilisateurs (nom affiché, avatar, salon par défaut, notifications), persistés dans un fichier JSON
+
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{PoisonError, RwLock};
+
+use serde::{Deserialize, Serialize};
+
+use crate::protocole::{validate_room_id, ErrorCode, RoomId};
+use crate::stockage::write_json_atomically;
+
+/// Longueur maximale d'un nom affiché
+pub const MAX_DISPLAY_NAME_LENGTH: usize = 32;
+
+/// Longueur maximale d'un avatar, en caractères (un emoji peut en compter plusieurs)
+pub const MAX_AVATAR_LENGTH: usize = 8;
+
+/// Notifications souhaitées par l'utilisateur
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct NotificationSettings {
+    /// Recevoir les mentions (@nom) des autres salons, et les trouver à la connexion si on était absent
+    pub mentions: bool,
+    /// Sonnerie du terminal à la réception d'un message privé ou d'une mention (affichage du client)
+    pub bell: bool,
+}
+
+impl Default for NotificationSettings {
+    fn default() -> Self {
+        Self { mentions: true, bell: false }
+    }
+}
+
+/// Profil d'un utilisateur ; les champs absents gardent leur valeur par défaut
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct UserProfile {
+    pub display_name: Option<String>,
+    pub avatar: Option<String>,
+    /// Salon rejoint automatiquement par le client après la connexion
+    pub default_room: Option<RoomId>,
+    pub notifications: NotificationSettings,
+    /// Utilisateurs dont on ne reçoit ni les messages privés ni les mentions ; changé par BlockUser/UnblockUser
+    #[serde(skip_serializing_if = "BTreeSet::is_empty")]
+    pub blocked: BTreeSet<String>,
+}
+
+impl UserProfile {
+    /// Vérifier chaque champ avant d'enregistrer le profil
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(name) = &self.display_name {
+            if name.trim().is_empty() || name.trim() != name || name.chars().count() > MAX_DISPLAY_NAME_LENGTH {
+                return Err(format!("Le nom affiché doit faire entre 1 et {} caractères, sans espace au bord", MAX_DISPLAY_NAME_LENGTH));
+            }
+            if name.chars().any(char::is_control) {
+                return Err("Le nom affiché ne peut pas contenir de caractère de contrôle".to_string());
+            }
+        }
+        if let Some(avatar) = &self.avatar {
+            let count = avatar.chars().count();
+            if count == 0 || count > MAX_AVATAR_LENGTH || avatar.chars().any(|c| c.is_whitespace() || c.is_control() || c.is_ascii()) {
+                return Err(format!("L'avatar doit être un emoji (au plus {} caractères)", MAX_AVATAR_LENGTH));
+            }
+        }
+        if let Some(room_id) = &self.default_room {
+            validate_room_id(room_id)?;
+        }
+        Ok(())
+    }
+
+    /// Ce que `username` envoie est-il refusé ?
+    pub fn blocks(&self, username: &str) -> bool {
+        self.blocked.contains(username)
+    }
+
+    /// Ce que les autres utilisateurs voient : nom affiché et avatar seulement
+    pub fn public(&self) -> Self {
+        Self {
+            display_name: self.display_name.clone(),
+            avatar: self.avatar.clone(),
+            ..Self::default()
+        }
+    }
+
+    /// Changer un champ désigné par son nom (`name`, `avatar`, `room`, `mentions`, `bell`) ;
+    /// une valeur vide efface les champs facultatifs
+    pub fn set(&mut self, field: &str, value: &str) -> Result<(), String> {
+        let value = value.trim();
+        let optional = |value: &str| (!value.is_empty()).then(|| value.to_string());
+        let switch = |value: &str| match value {
+            "on" => Ok(true),
+            "off" => Ok(false),
+            _ => Err(format!("{} attend on ou off", field)),
+        };
+        match field {
+            "name" => self.display_name = optional(value),
+            "avatar" => self.avatar = optional(value),
+            "room" => self.default_room = optional(value),
+            "mentions" => self.notifications.mentions = switch(value)?,
+            "bell" => self.notifications.bell = switch(value)?,
+            _ => return Err(format!("Champ de profil inconnu: {} (name, avatar, room, mentions ou bell)", field)),
+        }
+        Ok(())
+    }
+
+    /// Nom à afficher pour `username` : « avatar nom affiché (username) » si le profil en a un
+    pub fn label(&self, username: &str) -> String {
+        let name = match &self.display_name {
+            Some(display_name) if display_name != username => format!("{} ({})", display_name, username),
+            _ => username.to_string(),
+        };
+        match &self.avatar {
+            Some(avatar) => format!("{} {}", avatar, name),
+            None => name,
+        }
+    }
+}
+
+/// Profils de tous les utilisateurs ; sans chemin, ils ne vivent qu'en mémoire.
+/// Un utilisateur sans profil enregistré a le profil par défaut
+#[derive(Debug, Default)]
+pub struct ProfileStore {
+    path: Option<PathBuf>,
+    profiles: RwLock<HashMap<String, UserProfile>>,
+}
+
+impl ProfileStore {
+    /// Charger les profils depuis un fichier JSON (absent : aucun profil)
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let profiles = match fs::read_to_string(&path) {
+            Ok(contenu) => serde_json::from_str(&contenu).map_err(io::Error::other)?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => return Err(e),
+        };
+        Ok(Self { path: Some(path), profiles: RwLock::new(profiles) })
+    }
+
+    pub fn get(&self, username: &str) -> UserProfile {
+        let profiles = self.profiles.read().unwrap_or_else(PoisonError::into_inner);
+        profiles.get(username).cloned().unwrap_or_default()
+    }
+
+    /// Remplacer le profil d'un utilisateur et l'enregistrer sur disque ; l'ancien est rétabli si l'écriture échoue.
+    /// La liste des utilisateurs bloqués n'est pas remplacée (voir `set_blocked`) ; renvoie le profil enregistré
+    pub fn update(&self, username: &str, mut profile: UserProfile) -> Result<UserProfile, (ErrorCode, String)> {
+        profile.validate().map_err(|e| (ErrorCode::InvalidFormat, e))?;
+        let mut profiles = self.profiles.write().unwrap_or_else(PoisonError::into_inner);
+        profile.blocked = profiles.get(username).map(|current| current.blocked.clone()).unwrap_or_default();
+        self.replace(&mut profiles, username, profile)
+    }
+
+    /// Bloquer (`blocked`) ou débloquer `target` pour `username`, et l'enregistrer ; renvoie le profil enregistré
+    pub fn set_blocked(&self, username: &str, target: &str, blocked: bool) -> Result<UserProfile, (ErrorCode, String)> {
+        let mut profiles = self.profiles.write().unwrap_or_else(PoisonError::into_inner);
+        let mut profile = profiles.get(username).cloned().unwrap_or_default();
+        let changed = if blocked { profile.blocked.insert(target.to_string()) } else { profile.blocked.remove(target) };
+        if !changed {
+            let message = if blocked { format!("{} est déjà bloqué", target) } else { format!("{} n'est pas bloqué", target) };
+            return Err((ErrorCode::InvalidState, message));
+        }
+        self.replace(&mut profiles, username, profile)
+    }
+
+    fn replace(&self, profiles: &mut HashMap<String, UserProfile>, username: &str, profile: UserProfile) -> Result<UserProfile, (ErrorCode, String)> {
+        let previous = profiles.insert(username.to_string(), profile.clone());
+        if let Err(e) = self.save(profiles) {
+            match previous {
+                Some(previous) => profiles.insert(username.to_string(), previous),
+                None => profiles.remove(username),
+            };
+            return Err((ErrorCode::InternalError, format!("Impossible d'enregistrer le profil: {}", e)));
+        }
+        Ok(profile)
+    }
+
+    fn save(&self, profiles: &HashMap<String, UserProfile>) -> io::Result<()> {
+        match &self.path {
+            Some(path) => write_json_atomically(path, profiles),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_update_persisted() {
+        let path = std::env::temp_dir().join(format!("tp8-profils-{}.json", std::process::id()));
+        let store = ProfileStore::open(&path).unwrap();
+        assert_eq!(store.get("alice"), UserProfile::default());
+
+        let mut profile = UserProfile::default();
+        profile.set("name", "Alice L.").unwrap();
+        profile.set("avatar", "🦊").unwrap();
+        profile.set("room", "tech").unwrap();
+        profile.set("mentions", "off").unwrap();
+        store.update("alice", profile.clone()).unwrap();
+
+        let store = ProfileStore::open(&path).unwrap();
+        assert_eq!(store.get("alice"), profile);
+        assert_eq!(store.get("alice").label("alice"), "🦊 Alice L. (alice)");
+        assert_eq!(store.get("alice").public().default_room, None);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_blocked() {
+        let store = ProfileStore::default();
+        assert!(store.set_blocked("alice", "bob", true).unwrap().blocks("bob"));
+        assert_eq!(store.set_blocked("alice", "bob", true).unwrap_err().0, ErrorCode::InvalidState);
+
+        // Un profil mis à jour par le client garde la liste, que seuls BlockUser et UnblockUser changent
+        let mut profile = UserProfile::default();
+        profile.set("name", "Alice").unwrap();
+        assert!(store.update("alice", profile).unwrap().blocks("bob"));
+        assert!(store.get("alice").public().blocked.is_empty());
+
+        assert!(!store.set_blocked("alice", "bob", false).unwrap().blocks("bob"));
+        assert_eq!(store.set_blocked("alice", "bob", false).unwrap_err().0, ErrorCode::InvalidState);
+    }
+
+    #[test]
+    fn test_validate() {
+        let store = ProfileStore::default();
+        let long_name = "x".repeat(MAX_DISPLAY_NAME_LENGTH + 1);
+        for (field, value) in [("name", "Al\u{7}ice"), ("name", long_name.as_str()), ("avatar", "ab"), ("avatar", "🦊🦊🦊🦊🦊🦊🦊🦊🦊"), ("room", "salle à manger")] {
+            let mut profile = UserProfile::default();
+            profile.set(field, value).unwrap();
+            assert_eq!(store.update("alice", profile).unwrap_err().0, ErrorCode::InvalidFormat, "{} {}", field, value);
+        }
+        assert_eq!(store.get("alice"), UserProfile::default());
+        assert!(UserProfile::default().set("bell", "peut-être").is_err());
+        assert!(UserProfile::default().set("couleur", "bleu").is_err());
+    }
+}
@@ -3,10 +3,19 @@
 
 use serde::{Deserialize, Serialize};
 use chrono::{DateTime, Utc};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::Arc;
+use tokio::sync::broadcast;
+
+use crate::historique::RoomHistory;
+use crate::metriques::ServerStats;
+use crate::profils::UserProfile;
 
 /// Version du protocole
-pub const PROTOCOL_VERSION: u8 = 1;
+pub const PROTOCOL_VERSION: u8 = 2;
+
+/// Plus ancienne version encore comprise : les trames v1 sont décodées telles quelles
+pub const MIN_PROTOCOL_VERSION: u8 = 1;
 
 /// Taille maximale d'un message (64KB)
 pub const MAX_MESSAGE_SIZE: usize = 65536;
@@ -17,6 +26,9 @@ pub type ClientId = String;
 /// Identifiant unique pour chaque salon
 pub type RoomId = String;
 
+/// Identifiant unique d'un message de salon (UUID), pour le modifier ou le supprimer
+pub type MessageId = String;
+
 /// États possibles d'une session client
 #[derive(Debug, Clone, PartialEq)]
 pub enum SessionState {
@@ -36,17 +48,39 @@ pub enum SessionState {
 pub enum Message {
     // --- Messages client vers serveur ---
 
-    /// Connexion initiale avec nom d'utilisateur
-    Connect { username: String },
+    /// Première trame d'un client (v2) : versions qu'il sait parler ; un client v1 n'en envoie pas
+    Hello { min_version: u8, max_version: u8 },
+
+    /// Création d'un compte, suivie de la connexion
+    Register { username: String, password: String },
 
-    /// Rejoindre un salon
-    JoinRoom { room_id: String },
+    /// Connexion avec un compte existant
+    Login { username: String, password: String },
+
+    /// Rejoindre un salon (mot de passe requis pour les salons privés) ; s'il est plein, `wait`
+    /// place le client en file d'attente au lieu de le refuser. Un spectateur (`spectator`) reçoit
+    /// les messages du salon mais ne peut pas y écrire
+    JoinRoom {
+        room_id: String,
+        #[serde(default)]
+        password: Option<String>,
+        #[serde(default)]
+        wait: bool,
+        #[serde(default)]
+        spectator: bool,
+    },
 
     /// Quitter le salon actuel
     LeaveRoom,
 
-    /// Envoyer un message dans le salon
-    SendMessage { content: String },
+    /// Envoyer un message dans le salon, éventuellement en réponse à un message de son historique
+    SendMessage {
+        content: String,
+        #[serde(default)]
+        kind: MessageKind,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        parent_message_id: Option<MessageId>,
+    },
 
     /// Message privé à un utilisateur
     PrivateMessage { target_user: String, content: String },
@@ -57,25 +91,220 @@ pub enum Message {
     /// Lister les utilisateurs dans le salon actuel
     ListUsers,
 
+    /// Demander les derniers messages du salon actuel
+    GetHistory { count: usize },
+
+    /// Créer un salon (le créateur en devient l'administrateur) ;
+    /// avec un mot de passe il est privé, avec `invite_only` seuls les invités peuvent entrer,
+    /// avec `max_users` il n'accueille pas plus de membres à la fois
+    CreateRoom {
+        room_id: String,
+        name: String,
+        #[serde(default)]
+        password: Option<String>,
+        #[serde(default)]
+        invite_only: bool,
+        #[serde(default)]
+        max_users: Option<usize>,
+    },
+
+    /// Supprimer un salon dont on est l'administrateur
+    DeleteRoom { room_id: String },
+
+    /// Autoriser un utilisateur à entrer dans un salon sur invitation
+    InviteUser { room_id: String, username: String },
+
+    /// Accuser réception des messages d'un salon jusqu'au numéro `sequence` inclus
+    MessageAck { room_id: String, sequence: u64 },
+
+    /// Signaler la lecture des messages d'un salon jusqu'au numéro `sequence` inclus
+    MarkRead { room_id: String, sequence: u64 },
+
+    /// Signaler qu'on est en train d'écrire dans le salon
+    Typing { room_id: String },
+
+    /// Changer son statut de présence
+    PresenceUpdate { status: PresenceStatus },
+
+    /// Expulser un utilisateur d'un salon (administrateurs uniquement)
+    KickUser { room_id: String, username: String },
+
+    /// Bannir un utilisateur d'un salon, pour `duration` secondes ou définitivement
+    BanUser { room_id: String, username: String, duration: Option<u64> },
+
+    /// Empêcher un utilisateur de parler dans un salon, pour `duration` secondes ou définitivement
+    MuteUser { room_id: String, username: String, duration: Option<u64> },
+
+    /// Permettre à un spectateur du salon d'y écrire (administrateurs uniquement)
+    PromoteUser { room_id: String, username: String },
+
+    /// Changer le sujet d'un salon (administrateurs uniquement) ; un sujet vide l'efface
+    SetTopic { room_id: String, topic: String },
+
+    /// Épingler un message du salon désigné par son numéro de séquence (administrateurs uniquement)
+    PinMessage { room_id: String, sequence: u64 },
+
+    /// Retirer un message épinglé (administrateurs uniquement)
+    UnpinMessage { room_id: String, sequence: u64 },
+
+    /// Demander les messages épinglés d'un salon
+    GetPins { room_id: String },
+
+    /// Chercher dans l'historique enregistré d'un salon (`/motif/` : expression régulière, sinon texte)
+    SearchHistory { room_id: String, query: String, limit: usize },
+
+    /// Demander le fil de discussion d'un message du salon courant
+    GetThread { message_id: MessageId },
+
+    /// Modifier un de ses messages du salon courant (les administrateurs du salon peuvent modifier tous les messages)
+    EditMessage { message_id: MessageId, new_content: String },
+
+    /// Supprimer un de ses messages du salon courant (les administrateurs du salon peuvent supprimer tous les messages)
+    DeleteMessage { message_id: MessageId },
+
+    /// Demander le profil d'un utilisateur (`None` : le sien, complet ; celui d'un autre ne montre que son nom et son avatar)
+    GetProfile {
+        #[serde(default)]
+        username: Option<String>,
+    },
+
+    /// Remplacer son profil
+    UpdateProfile { profile: UserProfile },
+
+    /// Ne plus recevoir les messages privés ni les mentions d'un utilisateur ; le profil mis à jour est renvoyé
+    BlockUser { username: String },
+
+    /// Recevoir de nouveau les messages privés et les mentions d'un utilisateur bloqué
+    UnblockUser { username: String },
+
+    /// Demander les statistiques d'activité du serveur (administrateurs du serveur uniquement)
+    GetStats,
+
+    // --- Transfert de fichiers (relayé par le serveur dans les deux sens) ---
+
+    /// Proposer un fichier à un utilisateur ou aux membres d'un salon ; `from` est renseigné par le serveur
+    FileOffer {
+        transfer_id: String,
+        target: FileTarget,
+        filename: String,
+        size: u64,
+        #[serde(default)]
+        from: Option<String>,
+    },
+
+    /// Accepter un fichier proposé ; `from` est renseigné par le serveur en le relayant à l'expéditeur
+    FileAccept {
+        transfer_id: String,
+        #[serde(default)]
+        from: Option<String>,
+    },
+
+    /// Morceau de fichier numéro `seq` (à partir de 0), `data` encodé en base64
+    FileChunk { transfer_id: String, seq: u64, data: String },
+
+    /// Fin du transfert, avec l'empreinte SHA-256 (hexadécimal) du fichier complet
+    FileComplete { transfer_id: String, sha256: String },
+
     /// Déconnexion propre
     Disconnect,
 
+    // --- Fédération (entre serveurs, sur le port de fédération) ---
+
+    /// Ouvrir un lien : nom du serveur qui se connecte et secret partagé par les serveurs fédérés
+    FederationHello { server: String, secret: String },
+
+    /// Lien accepté, avec le nom du serveur qui l'accepte ; les RoomMessage des salons partagés circulent ensuite
+    FederationAck { server: String },
+
     // --- Messages serveur vers client ---
 
+    /// Version retenue pour la suite de la connexion
+    HelloAck { version: u8 },
+
+    /// Réponse à Disconnect : le client a quitté son salon et le serveur ferme la connexion
+    DisconnectAck,
+
+    /// Aucune version commune (ou trame d'une version inconnue) : le serveur ferme la connexion
+    VersionMismatch { min_version: u8, max_version: u8, message: String },
+
     /// Confirmation de connexion
     ConnectAck { client_id: String, message: String },
 
-    /// Erreur lors de la connexion
-    ConnectError { reason: String },
+    /// Erreur lors de l'inscription ou de la connexion
+    ConnectError { code: ErrorCode, reason: String },
 
-    /// Confirmation d'entrée dans un salon
-    JoinRoomAck { room_id: String, users: Vec<String> },
+    /// Confirmation d'entrée dans un salon, avec son sujet s'il en a un
+    JoinRoomAck {
+        room_id: String,
+        users: Vec<String>,
+        #[serde(default)]
+        topic: Option<String>,
+    },
 
     /// Erreur lors de l'entrée dans un salon
     JoinRoomError { reason: String },
 
-    /// Notification qu'un utilisateur a rejoint le salon
-    UserJoined { username: String, room_id: String },
+    /// En attente d'une place dans un salon plein, à la place `position` (1 : le prochain à entrer) ;
+    /// renvoyé à chaque fois que la file avance. Une fois entré, le client reçoit JoinRoomAck
+    RoomQueued { room_id: String, position: usize },
+
+    /// Confirmation de création d'un salon
+    CreateRoomAck { room_id: String, name: String },
+
+    /// Erreur lors de la création d'un salon
+    CreateRoomError { reason: String },
+
+    /// Confirmation de suppression d'un salon
+    DeleteRoomAck { room_id: String },
+
+    /// Erreur lors de la suppression d'un salon
+    DeleteRoomError { reason: String },
+
+    /// Notification aux membres : le salon a été supprimé
+    RoomDeleted { room_id: String },
+
+    /// Confirmation d'invitation
+    InviteUserAck { room_id: String, username: String },
+
+    /// Notification à l'invité
+    RoomInvitation { room_id: String, from: String },
+
+    /// Notification : un utilisateur a été expulsé du salon
+    UserKicked { room_id: String, username: String, by: String },
+
+    /// Notification : un utilisateur a été banni du salon (`until` absent : définitivement)
+    UserBanned { room_id: String, username: String, by: String, until: Option<DateTime<Utc>> },
+
+    /// Notification : un utilisateur a été rendu muet dans le salon
+    UserMuted { room_id: String, username: String, by: String, until: Option<DateTime<Utc>> },
+
+    /// Notification : un spectateur du salon peut désormais y écrire
+    UserPromoted { room_id: String, username: String, by: String },
+
+    /// Notification : le sujet du salon a changé (`topic` absent : sujet effacé)
+    TopicChanged { room_id: String, topic: Option<String>, by: String },
+
+    /// Notification : un message a été épinglé dans le salon
+    MessagePinned { room_id: String, entry: HistoryEntry, by: String },
+
+    /// Notification : un message n'est plus épinglé
+    MessageUnpinned { room_id: String, sequence: u64, by: String },
+
+    /// Messages épinglés d'un salon, dans l'ordre où ils ont été épinglés
+    Pins { room_id: String, messages: Vec<HistoryEntry> },
+
+    /// Résultats d'une recherche, du plus ancien au plus récent
+    SearchResults { room_id: String, query: String, messages: Vec<HistoryEntry> },
+
+    /// Notification qu'un utilisateur a rejoint le salon, avec son nom affiché et son avatar s'il en a
+    UserJoined {
+        username: String,
+        room_id: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        display_name: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        avatar: Option<String>,
+    },
 
     /// Notification qu'un utilisateur a quitté le salon
     UserLeft { username: String, room_id: String },
@@ -85,9 +314,48 @@ pub enum Message {
         from: String,
         content: String,
         timestamp: DateTime<Utc>,
-        room_id: String
+        room_id: String,
+        /// Numéro croissant attribué par le serveur, propre à chaque salon
+        #[serde(default)]
+        sequence: u64,
+        #[serde(default)]
+        message_id: MessageId,
+        #[serde(default)]
+        kind: MessageKind,
+        /// Heure de réception par le serveur, qui fait foi pour l'ordre ; `timestamp` est l'heure d'envoi
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        received_at: Option<DateTime<Utc>>,
+        /// Message auquel celui-ci répond
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        parent_message_id: Option<MessageId>,
     },
 
+    /// Notification : un message du salon a été modifié
+    MessageEdited {
+        room_id: String,
+        message_id: MessageId,
+        sequence: u64,
+        new_content: String,
+        by: String,
+        edited_at: DateTime<Utc>,
+    },
+
+    /// Notification : un message du salon a été supprimé
+    MessageDeleted { room_id: String, message_id: MessageId, sequence: u64, by: String },
+
+    /// Notification : un message d'un salon mentionne le destinataire (@nom), où qu'il se trouve
+    Mention {
+        room_id: String,
+        from: String,
+        content: String,
+        timestamp: DateTime<Utc>,
+        #[serde(default)]
+        sequence: u64,
+    },
+
+    /// Profil d'un utilisateur, en réponse à GetProfile ou UpdateProfile, et le sien à la connexion
+    Profile { username: String, profile: UserProfile },
+
     /// Message privé reçu
     PrivateMessageReceived {
         from: String,
@@ -95,11 +363,61 @@ pub enum Message {
         timestamp: DateTime<Utc>
     },
 
+    /// Le destinataire d'un message privé est hors ligne : le message l'attendra
+    PrivateMessageQueued { target_user: String },
+
+    /// Résumé des messages privés et mentions reçus hors ligne, envoyé à la connexion avant leur livraison
+    PendingMessages {
+        count: usize,
+        senders: HashMap<String, usize>, // expéditeur -> nombre de messages
+    },
+
     /// Liste des salons disponibles
-    RoomList { rooms: HashMap<String, usize> }, // room_id -> nombre d'utilisateurs
+    RoomList {
+        rooms: HashMap<String, usize>, // room_id -> nombre d'utilisateurs
+        /// room_id -> messages non lus, pour les salons où l'utilisateur a déjà lu quelque chose
+        #[serde(default)]
+        unread: HashMap<String, usize>,
+    },
+
+    /// Liste des utilisateurs dans le salon, avec leur statut de présence et les spectateurs
+    UserList {
+        users: Vec<String>,
+        room_id: String,
+        #[serde(default)]
+        statuses: HashMap<String, PresenceStatus>,
+        #[serde(default)]
+        spectators: Vec<String>,
+    },
+
+    /// Notification : un membre du salon est en train d'écrire
+    UserTyping { room_id: String, username: String },
+
+    /// Transfert de fichier interrompu (expéditeur parti, limite dépassée...)
+    FileAborted { transfer_id: String, reason: String },
+
+    /// Le serveur s'arrête ; les connexions restantes seront fermées après `grace_seconds`
+    ServerShutdown { reason: String, grace_seconds: u64 },
+
+    /// Annonce de l'opérateur du serveur à tous les clients
+    ServerAnnouncement { message: String },
+
+    /// Notification : un membre du salon a changé de statut
+    UserPresence { username: String, status: PresenceStatus },
+
+    /// L'horloge du client s'écarte de celle du serveur de `offset_secs` secondes (positif : en avance) ;
+    /// l'heure de ses trames est remplacée par celle du serveur. Envoyé au premier écart constaté, puis
+    /// seulement si l'horloge du client se dérègle à nouveau après avoir été remise à l'heure
+    ClockSkew { offset_secs: i64 },
 
-    /// Liste des utilisateurs dans le salon
-    UserList { users: Vec<String>, room_id: String },
+    /// Derniers messages d'un salon, du plus ancien au plus récent
+    History { room_id: String, messages: Vec<HistoryEntry> },
+
+    /// Fil de discussion de `message_id` : le message qui l'a lancé puis les réponses, dans l'ordre
+    Thread { room_id: String, message_id: MessageId, messages: Vec<HistoryEntry> },
+
+    /// Statistiques d'activité du serveur, en réponse à GetStats
+    Stats { stats: ServerStats },
 
     /// Erreur générale
     Error { code: ErrorCode, message: String },
@@ -111,11 +429,116 @@ pub enum Message {
     Pong,
 }
 
+/// Destinataire d'un transfert de fichier
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum FileTarget {
+    User(String),
+    Room(RoomId),
+}
+
+/// Statut de présence d'un utilisateur
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum PresenceStatus {
+    #[default]
+    Online,
+    Away,
+    Busy,
+}
+
+impl std::str::FromStr for PresenceStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "online" => Ok(PresenceStatus::Online),
+            "away" => Ok(PresenceStatus::Away),
+            "busy" => Ok(PresenceStatus::Busy),
+            other => Err(format!("Statut inconnu: {} (online, away ou busy)", other)),
+        }
+    }
+}
+
+impl std::fmt::Display for PresenceStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let label = match self {
+            PresenceStatus::Online => "online",
+            PresenceStatus::Away => "away",
+            PresenceStatus::Busy => "busy",
+        };
+        write!(f, "{}", label)
+    }
+}
+
+/// Longueur maximale du langage annoncé pour un bloc de code
+pub const MAX_LANGUAGE_LENGTH: usize = 20;
+
+/// Nature d'un message de salon, qui décide de son affichage
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum MessageKind {
+    #[default]
+    Text,
+    /// Action à la troisième personne : « * alice salue »
+    Action,
+    /// Bloc de code ; le langage sert à la coloration
+    Code { language: Option<String> },
+    /// Annonce mise en avant, réservée aux administrateurs du salon (vérifié par le serveur)
+    Announcement,
+}
+
+impl MessageKind {
+    pub fn is_text(&self) -> bool {
+        *self == MessageKind::Text
+    }
+
+    /// Vérifier le langage d'un bloc de code (lettres, chiffres, '+', '#', '-' et '_')
+    pub fn validate(&self) -> Result<(), String> {
+        if let MessageKind::Code { language: Some(language) } = self {
+            let allowed = |c: char| c.is_ascii_alphanumeric() || "+#-_".contains(c);
+            if language.is_empty() || language.len() > MAX_LANGUAGE_LENGTH || !language.chars().all(allowed) {
+                return Err(format!("Langage de code invalide: {}", language));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Message conservé dans l'historique d'un salon
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct HistoryEntry {
+    pub from: String,
+    pub content: String,
+    pub timestamp: DateTime<Utc>,
+    #[serde(default)]
+    pub sequence: u64,
+    /// Vide pour les messages enregistrés avant les identifiants
+    #[serde(default)]
+    pub id: MessageId,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub edited_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "MessageKind::is_text")]
+    pub kind: MessageKind,
+    /// Heure de réception par ce serveur, croissante avec `sequence` ; absente des messages enregistrés avant
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub received_at: Option<DateTime<Utc>>,
+    /// Message auquel celui-ci répond
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent_id: Option<MessageId>,
+}
+
+impl HistoryEntry {
+    /// Heure du serveur, qui ordonne les messages ; l'heure d'envoi pour les plus anciens
+    pub fn server_time(&self) -> DateTime<Utc> {
+        self.received_at.unwrap_or(self.timestamp)
+    }
+}
+
 /// Codes d'erreur du protocole
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)] // Added PartialEq
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)] // Hash : clé des compteurs d'erreurs (metriques)
 pub enum ErrorCode {
     /// Nom d'utilisateur déjà pris
     UsernameAlreadyTaken,
+    /// Identifiants invalides
+    AuthFailed,
     /// Salon inexistant
     RoomNotFound,
     /// Utilisateur non trouvé
@@ -126,6 +549,22 @@ pub enum ErrorCode {
     InvalidFormat,
     /// Message trop volumineux
     MessageTooLarge,
+    /// Mot de passe du salon manquant ou incorrect
+    InvalidRoomPassword,
+    /// Salon sur invitation et utilisateur non invité
+    NotInvited,
+    /// Salon plein
+    RoomFull,
+    /// Action réservée à l'administrateur du salon
+    PermissionDenied,
+    /// Utilisateur banni du salon
+    Banned,
+    /// Utilisateur réduit au silence dans le salon
+    Muted,
+    /// Spectateur du salon : lecture seule
+    ReadOnly,
+    /// Le destinataire a bloqué l'expéditeur
+    Blocked,
     /// Limite de débit dépassée (non implémenté ici, mais bonne pratique)
     RateLimitExceeded,
     /// Erreur serveur interne
@@ -145,6 +584,9 @@ pub struct ProtocolFrame {
     pub message: Message,
     /// Timestamp d'envoi
     pub timestamp: DateTime<Utc>,
+    /// Serveur d'origine d'un message relayé par la fédération ; absent pour un message local
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub origin: Option<String>,
 }
 
 impl ProtocolFrame {
@@ -156,6 +598,7 @@ impl ProtocolFrame {
             sequence,
             message,
             timestamp: Utc::now(),
+            origin: None,
         }
     }
 
@@ -173,9 +616,12 @@ impl ProtocolFrame {
 
     /// Valider la trame (côté serveur principalement)
     pub fn validate(&self) -> Result<(), String> {
-        if self.version != PROTOCOL_VERSION {
+        if !is_supported_version(self.version) {
             return Err(format!("Version de protocole non supportée: {}", self.version));
         }
+        if self.message.since_version() > self.version {
+            return Err(format!("Message inconnu en version {} du protocole", self.version));
+        }
 
         // Vérifier la taille du message sérialisé (utile avant l'envoi aussi)
         // Note: Cette validation est pour la taille du message *après* sérialisation JSON.
@@ -191,10 +637,73 @@ impl ProtocolFrame {
 
         Ok(())
     }
+
+    /// Adapter une trame à un pair qui parle `version` ; `None` si le message n'existe pas dans cette version
+    pub fn for_version(mut self, version: u8) -> Option<Self> {
+        if self.message.since_version() > version {
+            self.message = match self.message {
+                Message::VersionMismatch { message, .. } => Message::Error { code: ErrorCode::InvalidFormat, message },
+                _ => return None,
+            };
+        }
+        self.version = version;
+        Some(self)
+    }
+}
+
+/// Version annoncée par une trame, même si son contenu n'est pas lisible (version plus récente)
+pub fn peek_version(data: &[u8]) -> Option<u8> {
+    #[derive(Deserialize)]
+    struct Envelope {
+        version: u8,
+    }
+    serde_json::from_slice::<Envelope>(data).ok().map(|envelope| envelope.version)
+}
+
+pub fn is_supported_version(version: u8) -> bool {
+    (MIN_PROTOCOL_VERSION..=PROTOCOL_VERSION).contains(&version)
+}
+
+/// Version retenue pour un client qui parle de `min_version` à `max_version` : la plus récente commune
+pub fn negotiate_version(min_version: u8, max_version: u8) -> Option<u8> {
+    let version = max_version.min(PROTOCOL_VERSION);
+    (version >= min_version.max(MIN_PROTOCOL_VERSION)).then_some(version)
 }
 
 /// Utilitaires pour le protocole
 impl Message {
+    /// Première version du protocole qui connaît ce message
+    pub fn since_version(&self) -> u8 {
+        match self {
+            Message::Hello { .. } |
+            Message::HelloAck { .. } |
+            Message::VersionMismatch { .. } |
+            Message::ServerAnnouncement { .. } |
+            Message::MarkRead { .. } |
+            Message::EditMessage { .. } |
+            Message::DeleteMessage { .. } |
+            Message::MessageEdited { .. } |
+            Message::MessageDeleted { .. } |
+            Message::GetProfile { .. } |
+            Message::UpdateProfile { .. } |
+            Message::Profile { .. } |
+            Message::FederationHello { .. } |
+            Message::FederationAck { .. } |
+            Message::ClockSkew { .. } |
+            Message::RoomQueued { .. } |
+            Message::PromoteUser { .. } |
+            Message::UserPromoted { .. } |
+            Message::GetThread { .. } |
+            Message::Thread { .. } |
+            Message::BlockUser { .. } |
+            Message::UnblockUser { .. } |
+            Message::GetStats |
+            Message::Stats { .. } |
+            Message::DisconnectAck => 2,
+            _ => 1,
+        }
+    }
+
     /// Vérifier si un message nécessite une authentification
     pub fn requires_auth(&self) -> bool {
         matches!(self,
@@ -204,7 +713,35 @@ impl Message {
             Message::PrivateMessage { .. } |
             Message::ListRooms |
             Message::ListUsers |
-            Message::Disconnect // Disconnect should be from an authenticated client
+            Message::GetHistory { .. } |
+            Message::CreateRoom { .. } |
+            Message::DeleteRoom { .. } |
+            Message::InviteUser { .. } |
+            Message::MessageAck { .. } |
+            Message::MarkRead { .. } |
+            Message::Typing { .. } |
+            Message::PresenceUpdate { .. } |
+            Message::KickUser { .. } |
+            Message::BanUser { .. } |
+            Message::MuteUser { .. } |
+            Message::PromoteUser { .. } |
+            Message::SetTopic { .. } |
+            Message::PinMessage { .. } |
+            Message::UnpinMessage { .. } |
+            Message::GetPins { .. } |
+            Message::SearchHistory { .. } |
+            Message::GetThread { .. } |
+            Message::EditMessage { .. } |
+            Message::DeleteMessage { .. } |
+            Message::GetProfile { .. } |
+            Message::UpdateProfile { .. } |
+            Message::BlockUser { .. } |
+            Message::UnblockUser { .. } |
+            Message::GetStats |
+            Message::FileOffer { .. } |
+            Message::FileAccept { .. } |
+            Message::FileChunk { .. } |
+            Message::FileComplete { .. }
         )
     }
 
@@ -212,18 +749,90 @@ impl Message {
     pub fn requires_room(&self) -> bool {
         matches!(self,
             Message::SendMessage { .. } |
-            Message::ListUsers
+            Message::ListUsers |
+            Message::GetHistory { .. } |
+            Message::Typing { .. } |
+            Message::GetThread { .. } |
+            Message::EditMessage { .. } |
+            Message::DeleteMessage { .. }
         )
     }
 }
 
+/// Longueur maximale d'un identifiant de salon
+pub const MAX_ROOM_ID_LENGTH: usize = 32;
+
+/// Vérifier qu'un identifiant de salon est utilisable (lettres, chiffres, '-' et '_')
+pub fn validate_room_id(room_id: &str) -> Result<(), String> {
+    if room_id.is_empty() || room_id.len() > MAX_ROOM_ID_LENGTH {
+        return Err(format!("L'identifiant de salon doit faire entre 1 et {} caractères", MAX_ROOM_ID_LENGTH));
+    }
+    if !room_id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        return Err("L'identifiant de salon ne peut contenir que des lettres, chiffres, '-' et '_'".to_string());
+    }
+    Ok(())
+}
+
+/// Longueur maximale du sujet d'un salon
+pub const MAX_TOPIC_LENGTH: usize = 200;
+
+/// Nombre maximal de messages épinglés par salon
+pub const MAX_PINNED_MESSAGES: usize = 10;
+
+/// Noms mentionnés dans un message (`@nom`), sans doublon et dans l'ordre d'apparition ;
+/// la ponctuation collée à la fin d'une mention est ignorée (« merci @alice, ... »)
+pub fn extract_mentions(content: &str) -> Vec<String> {
+    let mut mentions: Vec<String> = Vec::new();
+    for word in content.split_whitespace() {
+        let Some(name) = word.strip_prefix('@') else {
+            continue;
+        };
+        let name = name.trim_end_matches(|c: char| c.is_ascii_punctuation() && c != '_' && c != '-');
+        if !name.is_empty() && !mentions.iter().any(|m| m == name) {
+            mentions.push(name.to_string());
+        }
+    }
+    mentions
+}
+
+/// Conditions d'entrée dans un salon
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum RoomVisibility {
+    /// Ouvert à tous
+    Public,
+    /// Protégé par un mot de passe (seul le hash est conservé)
+    Private { password_hash: String },
+    /// Réservé aux utilisateurs invités
+    InviteOnly { invited: HashSet<String> },
+}
+
+/// Nombre de diffusions qu'un membre peut avoir en retard avant de perdre les plus anciennes
+pub const ROOM_CHANNEL_CAPACITY: usize = 256;
+
+/// Trame diffusée dans un salon, numérotée dans l'ordre d'envoi
+#[derive(Debug, Clone)]
+pub struct RoomEvent {
+    pub position: u64,
+    pub frame: Arc<ProtocolFrame>, // Partagée par tous les membres, copiée seulement à l'écriture
+    pub exclude: Option<ClientId>,
+}
+
 /// Structure pour représenter l'état d'un salon
-#[derive(Debug, Clone, PartialEq)] // Added PartialEq
+#[derive(Debug, Clone)]
 pub struct Room {
     pub id: RoomId,
     pub name: String,
     pub users: HashMap<ClientId, String>, // client_id -> username
     pub created_at: DateTime<Utc>,
+    pub history: RoomHistory,
+    pub visibility: RoomVisibility,
+    pub topic: Option<String>,
+    pub pinned: Vec<HistoryEntry>, // Copies des messages épinglés, qui survivent à l'historique
+    pub max_users: Option<usize>, // Sans limite si absent
+    pub waiting: VecDeque<ClientId>, // File d'attente quand le salon est plein, le premier entre au prochain départ
+    pub spectators: HashSet<ClientId>, // Membres en lecture seule
+    channel: broadcast::Sender<RoomEvent>, // Chaque membre y est abonné
+    position: u64, // Numéro de la dernière diffusion
 }
 
 impl Room {
@@ -233,17 +842,111 @@ impl Room {
             name,
             users: HashMap::new(),
             created_at: Utc::now(),
+            history: RoomHistory::default(),
+            visibility: RoomVisibility::Public,
+            topic: None,
+            pinned: Vec::new(),
+            max_users: None,
+            waiting: VecDeque::new(),
+            spectators: HashSet::new(),
+            channel: broadcast::channel(ROOM_CHANNEL_CAPACITY).0,
+            position: 0,
         }
     }
 
-    pub fn add_user(&mut self, client_id: ClientId, username: String) {
+    /// Changer le sujet (espaces retirés) ; un sujet vide l'efface
+    pub fn set_topic(&mut self, topic: &str) -> Result<Option<String>, String> {
+        let topic = topic.trim();
+        if topic.chars().count() > MAX_TOPIC_LENGTH {
+            return Err(format!("Le sujet ne peut pas dépasser {} caractères", MAX_TOPIC_LENGTH));
+        }
+        self.topic = (!topic.is_empty()).then(|| topic.to_string());
+        Ok(self.topic.clone())
+    }
+
+    /// Épingler un message encore présent dans l'historique en mémoire
+    pub fn pin(&mut self, sequence: u64) -> Result<HistoryEntry, String> {
+        if self.pinned.iter().any(|e| e.sequence == sequence) {
+            return Err(format!("Le message #{} est déjà épinglé", sequence));
+        }
+        if self.pinned.len() >= MAX_PINNED_MESSAGES {
+            return Err(format!("Pas plus de {} messages épinglés par salon", MAX_PINNED_MESSAGES));
+        }
+        let entry = self.history.get(sequence)
+            .cloned()
+            .ok_or_else(|| format!("Message #{} introuvable dans l'historique du salon", sequence))?;
+        self.pinned.push(entry.clone());
+        Ok(entry)
+    }
+
+    /// Retirer un message épinglé
+    pub fn unpin(&mut self, sequence: u64) -> Result<(), String> {
+        let position = self.pinned.iter().position(|e| e.sequence == sequence)
+            .ok_or_else(|| format!("Le message #{} n'est pas épinglé", sequence))?;
+        self.pinned.remove(position);
+        Ok(())
+    }
+
+    /// Remplacer le contenu d'un message encore en mémoire, copie épinglée comprise ; renvoie le message modifié
+    pub fn edit_message(&mut self, message_id: &str, content: &str, at: DateTime<Utc>) -> Result<HistoryEntry, String> {
+        let entry = self.history.edit(message_id, content, at)
+            .ok_or_else(|| "Message introuvable dans l'historique du salon".to_string())?;
+        if let Some(pinned) = self.pinned.iter_mut().find(|e| e.id == entry.id) {
+            *pinned = entry.clone();
+        }
+        Ok(entry)
+    }
+
+    /// Retirer un message de l'historique en mémoire et des messages épinglés ; renvoie le message retiré
+    pub fn delete_message(&mut self, message_id: &str) -> Result<HistoryEntry, String> {
+        let entry = self.history.remove(message_id)
+            .ok_or_else(|| "Message introuvable dans l'historique du salon".to_string())?;
+        self.pinned.retain(|e| e.id != entry.id);
+        Ok(entry)
+    }
+
+    /// Ajouter un membre ; il reçoit les diffusions à partir de maintenant par le récepteur renvoyé
+    pub fn add_user(&mut self, client_id: ClientId, username: String) -> broadcast::Receiver<RoomEvent> {
         self.users.insert(client_id, username);
+        self.channel.subscribe()
+    }
+
+    /// Diffuser une trame à tous les membres abonnés, sauf `exclude`
+    pub fn broadcast(&mut self, frame: ProtocolFrame, exclude: Option<&ClientId>) {
+        self.position += 1;
+        let event = RoomEvent { position: self.position, frame: Arc::new(frame), exclude: exclude.cloned() };
+        let _ = self.channel.send(event); // Personne n'écoute : rien à faire
+    }
+
+    /// Numéro de la dernière diffusion : un membre qui part doit recevoir jusqu'à celle-ci
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Ajouter un membre en lecture seule
+    pub fn add_spectator(&mut self, client_id: ClientId, username: String) -> broadcast::Receiver<RoomEvent> {
+        self.spectators.insert(client_id.clone());
+        self.add_user(client_id, username)
     }
 
     pub fn remove_user(&mut self, client_id: &ClientId) -> Option<String> {
+        self.spectators.remove(client_id);
         self.users.remove(client_id)
     }
 
+    pub fn is_spectator(&self, client_id: &ClientId) -> bool {
+        self.spectators.contains(client_id)
+    }
+
+    /// Faire d'un spectateur un participant ; faux s'il n'était pas spectateur
+    pub fn promote(&mut self, client_id: &ClientId) -> bool {
+        self.spectators.remove(client_id)
+    }
+
+    pub fn get_spectator_names(&self) -> Vec<String> {
+        self.spectators.iter().filter_map(|id| self.users.get(id).cloned()).collect()
+    }
+
     pub fn get_usernames(&self) -> Vec<String> {
         self.users.values().cloned().collect()
     }
@@ -251,6 +954,33 @@ impl Room {
     pub fn user_count(&self) -> usize {
         self.users.len()
     }
+
+    pub fn is_full(&self) -> bool {
+        self.max_users.is_some_and(|max| self.users.len() >= max)
+    }
+
+    /// Plus de place pour `client_id` (qui garde la sienne s'il est déjà membre)
+    pub fn is_full_for(&self, client_id: &ClientId) -> bool {
+        self.is_full() && !self.users.contains_key(client_id)
+    }
+
+    /// Mettre un client en file d'attente (s'il n'y est pas déjà) ; renvoie sa place, à partir de 1
+    pub fn queue(&mut self, client_id: &ClientId) -> usize {
+        match self.waiting.iter().position(|id| id == client_id) {
+            Some(index) => index + 1,
+            None => {
+                self.waiting.push_back(client_id.clone());
+                self.waiting.len()
+            }
+        }
+    }
+
+    /// Retirer un client de la file d'attente ; faux s'il n'y était pas
+    pub fn unqueue(&mut self, client_id: &ClientId) -> bool {
+        let before = self.waiting.len();
+        self.waiting.retain(|id| id != client_id);
+        self.waiting.len() != before
+    }
 }
 
 #[cfg(test)]
@@ -259,7 +989,7 @@ mod tests {
 
     #[test]
     fn test_protocol_frame_serialization() {
-        let message = Message::Connect { username: "test_user".to_string() };
+        let message = Message::Login { username: "test_user".to_string(), password: "secret".to_string() };
         let frame = ProtocolFrame::new(message, Some("session123".to_string()), 1);
 
         let serialized = frame.serialize().unwrap();
@@ -271,20 +1001,201 @@ mod tests {
 
     #[test]
     fn test_message_validation() {
-        let message = Message::Connect { username: "test".to_string() };
+        let message = Message::Login { username: "test".to_string(), password: "secret".to_string() };
         assert!(!message.requires_auth());
         assert!(!message.requires_room());
 
-        let message = Message::SendMessage { content: "hello".to_string() };
+        let message = Message::SendMessage { content: "hello".to_string(), kind: MessageKind::Text, parent_message_id: None };
         assert!(message.requires_auth());
         assert!(message.requires_room());
     }
 
+    #[test]
+    fn test_join_room_password_is_optional() {
+        let json = r#"{"type":"JoinRoom","data":{"room_id":"general"}}"#;
+        let message: Message = serde_json::from_str(json).unwrap();
+        assert_eq!(message, Message::JoinRoom { room_id: "general".to_string(), password: None, wait: false, spectator: false });
+    }
+
+    #[test]
+    fn test_validate_room_id() {
+        assert!(validate_room_id("rust-fr_2").is_ok());
+        assert!(validate_room_id("").is_err());
+        assert!(validate_room_id("../etc").is_err());
+        assert!(validate_room_id(&"a".repeat(MAX_ROOM_ID_LENGTH + 1)).is_err());
+    }
+
+    #[test]
+    fn test_extract_mentions() {
+        assert_eq!(extract_mentions("merci @alice, et @bob_2 ! @alice?"), vec!["alice", "bob_2"]);
+        assert!(extract_mentions("alice@example.com @ rien").is_empty());
+    }
+
+    #[test]
+    fn test_room_capacity() {
+        let mut room = Room::new("petit".to_string(), "Petit".to_string());
+        let (alice, bob, carol) = ("c1".to_string(), "c2".to_string(), "c3".to_string());
+        assert!(!room.is_full_for(&alice));
+        room.max_users = Some(1);
+        let _receiver = room.add_user(alice.clone(), "alice".to_string());
+        assert!(room.is_full_for(&bob));
+        assert!(!room.is_full_for(&alice)); // Déjà membre
+
+        assert_eq!(room.queue(&bob), 1);
+        assert_eq!(room.queue(&carol), 2);
+        assert_eq!(room.queue(&bob), 1);
+        assert!(room.unqueue(&bob));
+        assert!(!room.unqueue(&bob));
+        assert_eq!(room.waiting, [carol]);
+    }
+
+    #[test]
+    fn test_room_spectators() {
+        let mut room = Room::new("conf".to_string(), "Conférence".to_string());
+        let (alice, bob) = ("c1".to_string(), "c2".to_string());
+        let _alice = room.add_user(alice.clone(), "alice".to_string());
+        let _bob = room.add_spectator(bob.clone(), "bob".to_string());
+        assert!(!room.is_spectator(&alice) && room.is_spectator(&bob));
+        assert_eq!(room.user_count(), 2);
+        assert_eq!(room.get_spectator_names(), ["bob"]);
+
+        assert!(room.promote(&bob));
+        assert!(!room.promote(&bob));
+        assert!(!room.is_spectator(&bob));
+
+        // Un spectateur qui part ne l'est plus à son retour
+        let _bob = room.add_spectator(bob.clone(), "bob".to_string());
+        room.remove_user(&bob);
+        assert!(room.spectators.is_empty());
+    }
+
+    #[test]
+    fn test_room_topic_and_pins() {
+        let mut room = Room::new("rust".to_string(), "Rust".to_string());
+        assert_eq!(room.set_topic("  Sortie de la 2.0  ").unwrap().as_deref(), Some("Sortie de la 2.0"));
+        assert!(room.set_topic(&"a".repeat(MAX_TOPIC_LENGTH + 1)).is_err());
+        assert_eq!(room.set_topic("").unwrap(), None);
+
+        for sequence in 1..=MAX_PINNED_MESSAGES as u64 + 1 {
+            room.history.push(HistoryEntry {
+                from: "alice".to_string(),
+                content: format!("message {}", sequence),
+                timestamp: Utc::now(),
+                sequence,
+                id: format!("id-{}", sequence),
+                edited_at: None,
+                kind: MessageKind::Text,
+                received_at: None,
+                parent_id: None,
+            });
+        }
+        assert_eq!(room.pin(2).unwrap().content, "message 2");
+        assert!(room.pin(2).is_err());
+        assert!(room.pin(999).is_err());
+        for sequence in 3..=MAX_PINNED_MESSAGES as u64 + 1 {
+            room.pin(sequence).unwrap();
+        }
+        assert!(room.pin(1).is_err());
+        room.unpin(2).unwrap();
+        assert!(room.unpin(2).is_err());
+        assert!(room.pin(1).is_ok());
+    }
+
+    #[test]
+    fn test_room_edit_and_delete() {
+        let mut room = Room::new("rust".to_string(), "Rust".to_string());
+        for sequence in 1..=3 {
+            room.history.push(HistoryEntry {
+                from: "alice".to_string(),
+                content: format!("message {}", sequence),
+                timestamp: Utc::now(),
+                sequence,
+                id: format!("id-{}", sequence),
+                edited_at: None,
+                kind: MessageKind::Text,
+                received_at: None,
+                parent_id: None,
+            });
+        }
+        room.pin(2).unwrap();
+
+        // La copie épinglée suit la modification, puis disparaît avec le message
+        let edited = room.edit_message("id-2", "corrigé", Utc::now()).unwrap();
+        assert_eq!((edited.sequence, edited.content.as_str()), (2, "corrigé"));
+        assert!(edited.edited_at.is_some());
+        assert_eq!(room.pinned[0].content, "corrigé");
+        assert!(room.edit_message("inconnu", "x", Utc::now()).is_err());
+
+        assert_eq!(room.delete_message("id-2").unwrap().sequence, 2);
+        assert!(room.pinned.is_empty());
+        assert!(room.delete_message("id-2").is_err());
+        assert_eq!(room.history.len(), 2);
+        assert_eq!(room.history.next_sequence(), 4); // Les numéros ne sont pas réattribués
+    }
+
+    #[test]
+    fn test_room_broadcast() {
+        let mut room = Room::new("rust".to_string(), "Rust".to_string());
+        room.broadcast(ProtocolFrame::new(Message::Ping, None, 0), None); // Aucun membre
+        let mut alice = room.add_user("a".to_string(), "alice".to_string());
+        let mut bob = room.add_user("b".to_string(), "bob".to_string());
+        room.broadcast(ProtocolFrame::new(Message::Pong, None, 0), Some(&"a".to_string()));
+
+        let event = alice.try_recv().unwrap();
+        assert_eq!((event.position, event.exclude.as_deref()), (2, Some("a")));
+        assert_eq!(bob.try_recv().unwrap().frame.message, Message::Pong);
+        assert_eq!(room.position(), 2);
+        assert!(bob.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_message_kind() {
+        // Sans `kind`, un message est du texte ; l'historique ne l'écrit que s'il en diffère
+        let message: Message = serde_json::from_str(r#"{"type":"SendMessage","data":{"content":"salut"}}"#).unwrap();
+        assert_eq!(message, Message::SendMessage { content: "salut".to_string(), kind: MessageKind::Text, parent_message_id: None });
+        let code = MessageKind::Code { language: Some("rust".to_string()) };
+        assert_eq!(serde_json::to_string(&code).unwrap(), r#"{"Code":{"language":"rust"}}"#);
+
+        assert!(code.validate().is_ok());
+        assert!(MessageKind::Code { language: None }.validate().is_ok());
+        for language in ["", "rust; rm -rf", &"c".repeat(MAX_LANGUAGE_LENGTH + 1)] {
+            assert!(MessageKind::Code { language: Some(language.to_string()) }.validate().is_err(), "{}", language);
+        }
+    }
+
+    #[test]
+    fn test_version_negotiation() {
+        assert_eq!(negotiate_version(1, 2), Some(2));
+        assert_eq!(negotiate_version(1, 9), Some(PROTOCOL_VERSION));
+        assert_eq!(negotiate_version(0, 1), Some(1));
+        assert_eq!(negotiate_version(3, 9), None);
+        assert_eq!(negotiate_version(2, 1), None);
+
+        // Une trame v1 (sans Hello) se lit toujours, une trame d'une version future est reconnue comme telle
+        let v1 = br#"{"version":1,"session_id":null,"sequence":3,"message":{"type":"SendMessage","data":{"content":"salut"}},"timestamp":"2024-01-01T00:00:00Z"}"#;
+        let frame = ProtocolFrame::deserialize(v1).unwrap();
+        assert!(frame.validate().is_ok());
+        assert_eq!(peek_version(v1), Some(1));
+        assert_eq!(peek_version(br#"{"version":7,"message":{"type":"Nouveau"}}"#), Some(7));
+        assert!(ProtocolFrame { version: 7, ..frame.clone() }.validate().is_err());
+
+        let hello = ProtocolFrame::new(Message::Hello { min_version: 1, max_version: 2 }, None, 0);
+        assert!(hello.validate().is_ok());
+        assert!(ProtocolFrame { version: 1, ..hello.clone() }.validate().is_err());
+
+        // Vers un client v1 : trame estampillée v1, messages v2 traduits ou omis
+        assert_eq!(frame.clone().for_version(1).map(|f| f.version), Some(1));
+        assert!(ProtocolFrame::new(Message::HelloAck { version: 2 }, None, 0).for_version(1).is_none());
+        let mismatch = Message::VersionMismatch { min_version: 1, max_version: 2, message: "non".to_string() };
+        let downgraded = ProtocolFrame::new(mismatch, None, 0).for_version(1).unwrap();
+        assert_eq!(downgraded.message, Message::Error { code: ErrorCode::InvalidFormat, message: "non".to_string() });
+    }
+
     #[test]
     fn test_protocol_frame_validation_max_size() {
         // Create a message that is intentionally too large after serialization
         let long_content = "a".repeat(MAX_MESSAGE_SIZE / 2); // Half of max size
-        let message = Message::SendMessage { content: long_content };
+        let message = Message::SendMessage { content: long_content, kind: MessageKind::Text, parent_message_id: None };
         let frame = ProtocolFrame::new(message, Some("session_id".to_string()), 1);
 
         // This message should be fine as it's below MAX_MESSAGE_SIZE even after JSON overhead
@@ -293,7 +1204,7 @@ mod tests {
 
         // Now, let's create a message that will exceed the limit
         let super_long_content = "b".repeat(MAX_MESSAGE_SIZE + 100); // Definitely too large
-        let large_message = Message::SendMessage { content: super_long_content };
+        let large_message = Message::SendMessage { content: super_long_content, kind: MessageKind::Text, parent_message_id: None };
         let large_frame = ProtocolFrame::new(large_message, Some("session_id_2".to_string()), 2);
 
         let result_large = large_frame.validate();
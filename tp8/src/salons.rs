@@ -0,0 +1,93 @@
+// src/salons.rs
+// Salons créés par les clients, sauvegardés dans un fichier JSON à l'arrêt du serveur
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::protocole::{HistoryEntry, RoomId, RoomVisibility};
+use crate::stockage::write_json_atomically;
+
+/// Description d'un salon à recréer au démarrage (l'historique est conservé à part)
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RoomRecord {
+    pub id: RoomId,
+    pub name: String,
+    pub owner: String,
+    pub visibility: RoomVisibility,
+    pub created_at: DateTime<Utc>,
+    #[serde(default)]
+    pub topic: Option<String>,
+    #[serde(default)]
+    pub pinned: Vec<HistoryEntry>,
+    #[serde(default)]
+    pub max_users: Option<usize>,
+}
+
+/// Fichier des salons
+#[derive(Debug, Clone)]
+pub struct RoomStore {
+    path: PathBuf,
+}
+
+impl RoomStore {
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        Self { path: path.as_ref().to_path_buf() }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Lire les salons sauvegardés (fichier absent : aucun salon)
+    pub fn load(&self) -> io::Result<Vec<RoomRecord>> {
+        match fs::read_to_string(&self.path) {
+            Ok(contenu) => serde_json::from_str(&contenu).map_err(io::Error::other),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn save(&self, rooms: &[RoomRecord]) -> io::Result<()> {
+        write_json_atomically(&self.path, rooms)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocole::MessageKind;
+
+    #[test]
+    fn test_save_then_load() {
+        let store = RoomStore::new(std::env::temp_dir().join(format!("tp8-salons-{}.json", std::process::id())));
+        assert!(store.load().unwrap().is_empty());
+
+        let rooms = vec![RoomRecord {
+            id: "rust".to_string(),
+            name: "Rustacés".to_string(),
+            owner: "alice".to_string(),
+            visibility: RoomVisibility::InviteOnly { invited: ["bob".to_string()].into() },
+            created_at: Utc::now(),
+            topic: Some("Tout sur Rust".to_string()),
+            pinned: vec![HistoryEntry {
+                from: "alice".to_string(),
+                content: "Bienvenue !".to_string(),
+                timestamp: Utc::now(),
+                sequence: 1,
+                id: "5f0c6c2e-4e1b-4a8e-9d3a-2f1e0b7c9a11".to_string(),
+                edited_at: None,
+                kind: MessageKind::Text,
+                received_at: None,
+                parent_id: None,
+            }],
+            max_users: Some(12),
+        }];
+        store.save(&rooms).unwrap();
+        assert_eq!(store.load().unwrap(), rooms);
+        fs::remove_file(store.path()).unwrap();
+    }
+}
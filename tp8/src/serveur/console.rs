@@ -0,0 +1,191 @@
+// src/serveur/console.rs
+// Operator console: commands typed on stdin or sent to the local admin socket, run against the shared state
+
+use std::io::{self, BufRead};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
+
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+
+use tokio::task::JoinHandle;
+
+use crate::admin::{AdminCommand, ADMIN_HELP};
+use crate::journal::ChatEvent;
+use crate::protocole::{ErrorCode, Message, SessionState};
+
+use super::ServerState;
+
+impl ServerState {
+    /// Run one operator command; the answer is meant for the operator only
+    fn run_admin(&self, command: AdminCommand) -> String {
+        match command {
+            AdminCommand::ListClients => {
+                let mut lines: Vec<String> = self.clients.iter()
+                    .map(|client| {
+                        let version = client.protocol_version.load(Ordering::Relaxed);
+                        let queue = self.client_senders.get(client.key())
+                            .map(|outbox| format!("{}/{} dropped={}", outbox.queue.len(), outbox.queue.capacity(), outbox.queue.dropped()))
+                            .unwrap_or_else(|| "-".to_string());
+                        format!(
+                            "{} {} room={} {:?} v{} queue={}",
+                            client.key(),
+                            client.username.as_deref().unwrap_or("(anonymous)"),
+                            client.current_room.as_deref().unwrap_or("-"),
+                            client.presence,
+                            version,
+                            queue,
+                        )
+                    })
+                    .collect();
+                lines.sort();
+                lines.push(format!("{} connection(s)", lines.len()));
+                lines.join("\n")
+            }
+            AdminCommand::ListRooms => {
+                let owners: Vec<(String, String)> = self.room_owners.iter()
+                    .map(|owner| (owner.key().clone(), owner.value().clone()))
+                    .collect();
+                let mut lines: Vec<String> = self.rooms.iter()
+                    .map(|room| {
+                        let owner = owners.iter().find(|(id, _)| id == room.key()).map(|(_, owner)| owner.as_str());
+                        format!(
+                            "{} \"{}\" {} member(s), {} message(s) in memory, owner={}",
+                            room.key(),
+                            room.name,
+                            room.user_count(),
+                            room.history.len(),
+                            owner.unwrap_or("(built-in)"),
+                        )
+                    })
+                    .collect();
+                lines.sort();
+                lines.push(format!("{} room(s)", lines.len()));
+                lines.join("\n")
+            }
+            AdminCommand::Kick { username, reason } => {
+                let Some(client_id) = self.client_of(&username) else {
+                    return format!("{} is not connected", username);
+                };
+                let message = match &reason {
+                    Some(reason) => format!("Disconnected by the server operator: {}", reason),
+                    None => "Disconnected by the server operator.".to_string(),
+                };
+                self.send_to_clients([&client_id], Message::Error { code: ErrorCode::PermissionDenied, message });
+                if let Some(client) = self.clients.get(&client_id) {
+                    client.disconnect.notify_one();
+                }
+                self.journal.info(format!("🔨 {} disconnected by the operator{}", username, reason.map(|r| format!(" ({})", r)).unwrap_or_default()));
+                format!("{} disconnected", username)
+            }
+            AdminCommand::Broadcast { text } => {
+                let connected: Vec<_> = self.clients.iter().map(|client| client.key().clone()).collect();
+                self.send_to_clients(&connected, Message::ServerAnnouncement { message: text.clone() });
+                self.journal.info(format!("📢 Announcement to {} client(s): {}", connected.len(), text));
+                format!("announcement sent to {} client(s)", connected.len())
+            }
+            AdminCommand::Stats => {
+                let (mut authenticated, mut in_room) = (0, 0);
+                for client in self.clients.iter() {
+                    match client.session_state {
+                        SessionState::Authenticated(_) => authenticated += 1,
+                        SessionState::InRoom(_, _) => in_room += 1,
+                        _ => {}
+                    }
+                }
+                let (mut queued, mut dropped, mut deepest) = (0, 0, None);
+                for outbox in self.client_senders.iter() {
+                    let depth = outbox.queue.len();
+                    queued += depth;
+                    dropped += outbox.queue.dropped();
+                    if deepest.as_ref().is_none_or(|(_, most)| depth > *most) {
+                        deepest = Some((outbox.key().clone(), depth));
+                    }
+                }
+                let deepest = match deepest {
+                    Some((client_id, depth)) if depth > 0 => format!(", deepest {} for {}", depth, client_id),
+                    _ => String::new(),
+                };
+                [
+                    format!("uptime: {}s", self.started.elapsed().as_secs()),
+                    format!("connections: {} ({} authenticated, {} in a room)", self.clients.len(), authenticated + in_room, in_room),
+                    format!("rooms: {} ({} created by clients)", self.rooms.len(), self.room_owners.len()),
+                    format!("accounts: {}", self.users.len()),
+                    format!("file transfers in progress: {}", self.transfers.len()),
+                    format!(
+                        "send queues: {} frame(s) waiting{} (capacity {} each), {} dropped",
+                        queued, deepest, self.config.send_queue_capacity, dropped,
+                    ),
+                    match self.federation.servers() {
+                        servers if servers.is_empty() => format!("federation: {}, no link", self.config.server_name),
+                        servers => format!("federation: {}, linked with {}", self.config.server_name, servers.join(", ")),
+                    },
+                ].join("\n")
+            }
+            AdminCommand::Help => ADMIN_HELP.to_string(),
+        }
+    }
+
+    /// Answer to a line typed by the operator, if it was not blank
+    fn admin_line(&self, line: &str) -> Option<String> {
+        AdminCommand::parse(line).map(|command| match command {
+            Ok(command) => self.run_admin(command),
+            Err(e) => format!("❌ {}", e),
+        })
+    }
+}
+
+/// Read commands from the server's stdin until it closes. A plain thread does the reading: a
+/// blocking read on the runtime would hold up its shutdown until the operator pressed Enter
+pub(super) fn stdin(state: Arc<ServerState>) {
+    let (tx_lines, mut rx_lines) = mpsc::unbounded_channel();
+    std::thread::spawn(move || {
+        for line in io::stdin().lock().lines().map_while(Result::ok) {
+            if tx_lines.send(line).is_err() {
+                break;
+            }
+        }
+    });
+    tokio::spawn(async move {
+        while let Some(line) = rx_lines.recv().await {
+            if let Some(answer) = state.admin_line(&line) {
+                println!("{}", answer);
+            }
+        }
+    });
+}
+
+/// Accept operator connections on the admin socket, one command per line, until the task is aborted
+pub(super) async fn listen(state: Arc<ServerState>, addr: SocketAddr) -> io::Result<JoinHandle<()>> {
+    let listener = TcpListener::bind(addr).await?;
+    state.journal.info(format!("🛠️ Admin console listening on {} (type help)", addr));
+    Ok(tokio::spawn(async move {
+        loop {
+            match listener.accept().await {
+                Ok((stream, peer)) => {
+                    let state = Arc::clone(&state);
+                    tokio::spawn(async move {
+                        if let Err(e) = session(&state, stream).await {
+                            state.journal.warn(format!("⚠️ Admin session with {} ended: {}", peer, e));
+                        }
+                    });
+                }
+                Err(e) => state.journal.log(ChatEvent::error(None, format!("Error accepting an admin connection: {}", e))),
+            }
+        }
+    }))
+}
+
+async fn session(state: &ServerState, stream: TcpStream) -> io::Result<()> {
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+    while let Some(line) = lines.next_line().await? {
+        if let Some(answer) = state.admin_line(&line) {
+            writer.write_all(answer.as_bytes()).await?;
+            writer.write_all(b"\n").await?;
+        }
+    }
+    Ok(())
+}
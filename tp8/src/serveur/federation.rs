@@ -0,0 +1,420 @@
+// src/serveur/federation.rs
+// Federation: links with other chat servers, over which the messages of shared rooms are relayed
+
+use std::collections::{HashSet, VecDeque};
+use std::io;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+use chrono::Utc;
+use dashmap::DashMap;
+use dashmap::mapref::entry::Entry;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::{JoinHandle, JoinSet};
+
+use crate::envoi::{Push, SendQueue};
+use crate::filtres::{FilterContext, FilterDecision};
+use crate::journal::ChatEvent;
+use crate::protocole::{ErrorCode, HistoryEntry, Message, ProtocolFrame, Room};
+use crate::trame::{read_frame_limited, write_frame};
+
+use super::{clock_offset, room_message, ServerState};
+
+/// Delay before connecting again to a peer whose link failed or closed
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// How long a server that connects has to introduce itself
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Relayed message ids remembered, so that a message coming back over another link is dropped
+const SEEN_CAPACITY: usize = 4096;
+
+/// Frames waiting to be sent to a linked server
+type LinkQueue = Arc<SendQueue<ProtocolFrame>>;
+
+/// Links with the other servers, and the messages already relayed over them
+#[derive(Default)]
+pub(super) struct Federation {
+    links: DashMap<String, LinkQueue>, // server name -> its send queue
+    seen: Mutex<SeenIds>,
+}
+
+/// The last SEEN_CAPACITY message ids, oldest first
+#[derive(Default)]
+struct SeenIds {
+    order: VecDeque<String>,
+    ids: HashSet<String>,
+}
+
+impl Federation {
+    /// Names of the servers currently linked
+    pub(super) fn servers(&self) -> Vec<String> {
+        let mut servers: Vec<String> = self.links.iter().map(|link| link.key().clone()).collect();
+        servers.sort();
+        servers
+    }
+
+    /// Remember `message_id`; false if it was seen already
+    fn first_sight(&self, message_id: &str) -> bool {
+        let mut seen = self.seen.lock().unwrap_or_else(PoisonError::into_inner);
+        if !seen.ids.insert(message_id.to_string()) {
+            return false;
+        }
+        seen.order.push_back(message_id.to_string());
+        if seen.order.len() > SEEN_CAPACITY {
+            if let Some(oldest) = seen.order.pop_front() {
+                seen.ids.remove(&oldest);
+            }
+        }
+        true
+    }
+}
+
+impl ServerState {
+    /// Relay a message of a shared room to the linked servers, except the one it came from and the one it
+    /// was first sent on; a local message leaves with this server as its origin
+    pub(super) fn federate(&self, frame: &ProtocolFrame, from: Option<&str>) {
+        let Message::RoomMessage { room_id, message_id, .. } = &frame.message else {
+            return;
+        };
+        if !self.config.federated_rooms.contains(room_id) {
+            return;
+        }
+        if from.is_none() {
+            self.federation.first_sight(message_id); // Dropped if it ever comes back
+        }
+
+        let mut frame = frame.clone();
+        frame.session_id = None;
+        let origin = frame.origin.get_or_insert_with(|| self.config.server_name.clone()).clone();
+        for link in self.federation.links.iter() {
+            if Some(link.key().as_str()) == from || *link.key() == origin {
+                continue;
+            }
+            if link.push(frame.clone(), true) == Push::Stalled {
+                self.journal.warn(format!("🐢 Federated server {} stopped reading, its oldest messages are dropped.", link.key()));
+            }
+        }
+    }
+
+    /// A frame from the linked server `peer`: a message of a shared room goes on to the other links,
+    /// then to the local members of the room, through the room's filters
+    fn receive_federated(&self, peer: &str, frame: ProtocolFrame) -> Result<(), String> {
+        frame.validate()?;
+        let origin = frame.origin.clone();
+        let Message::RoomMessage { from, content, timestamp, room_id, message_id, kind, parent_message_id, .. } = frame.message.clone() else {
+            return match frame.message {
+                Message::Ping => Ok(()), // Keeps the link alive, nothing to answer
+                other => Err(format!("Unexpected message from a federated server: {:?}", other)),
+            };
+        };
+        let origin = origin.ok_or("Relayed message without an origin")?;
+        if origin == self.config.server_name || !self.federation.first_sight(&message_id) {
+            return Ok(()); // Came back around a loop of links
+        }
+        if !self.config.federated_rooms.contains(&room_id) {
+            return Ok(()); // Shared by the peer, not by us
+        }
+        self.federate(&frame, Some(peer));
+
+        let from = format!("{}@{}", from, origin);
+        // The parent is not checked: it may have reached the origin before this server joined the link
+        let mut message = Message::SendMessage { content, kind, parent_message_id };
+        let context = FilterContext { room_id: &room_id, username: &from, now: Instant::now() };
+        if let FilterDecision::Reject(_, reason) = self.filters.chain(&room_id).apply(&context, &mut message) {
+            self.journal.info(format!("🚫 [{}] Message from {} filtered out: {}", room_id, from, reason));
+            return Ok(());
+        }
+        let Message::SendMessage { content, kind, parent_message_id } = message else {
+            return Err("A filter replaced the message".to_string());
+        };
+
+        // The origin's clock is no more trusted than a client's
+        let now = Utc::now();
+        let timestamp = if clock_offset(&self.config, timestamp, now).is_some() { now } else { timestamp };
+        let entry = self.record_message(&room_id, HistoryEntry {
+            from,
+            content,
+            timestamp,
+            sequence: 0, // Assigned by record_message
+            id: message_id,
+            edited_at: None,
+            kind,
+            received_at: None, // Set by record_message
+            parent_id: parent_message_id,
+        })?;
+        let mut local = ProtocolFrame::new(room_message(&room_id, &entry), None, entry.sequence);
+        local.origin = Some(origin);
+
+        self.journal.log(ChatEvent::RoomMessage {
+            room_id: room_id.clone(),
+            from: entry.from.clone(),
+            content: entry.content.clone(),
+            message_id: entry.id.clone(),
+            origin: local.origin.clone(),
+        });
+        self.broadcast_to_room(&room_id, local, None);
+        self.notify_mentions(&room_id, &entry);
+        Ok(())
+    }
+
+    /// Take the link with `server`; None if that server is already linked
+    fn register_link(&self, server: &str) -> Option<LinkQueue> {
+        match self.federation.links.entry(server.to_string()) {
+            Entry::Occupied(_) => None,
+            Entry::Vacant(vacant) => {
+                let queue = Arc::new(SendQueue::new(self.config.send_queue_capacity, self.config.slow_client_limit()));
+                vacant.insert(Arc::clone(&queue));
+                Some(queue)
+            }
+        }
+    }
+
+    fn unregister_link(&self, server: &str, queue: &LinkQueue) {
+        self.federation.links.remove_if(server, |_, registered| Arc::ptr_eq(registered, queue));
+        queue.close();
+    }
+}
+
+/// Create the shared rooms, listen on `federation_bind` and keep a link with every configured peer.
+/// Returns the address bound for federation, if any, and the tasks to abort at shutdown
+pub(super) async fn start(state: &Arc<ServerState>) -> io::Result<(Option<SocketAddr>, Vec<JoinHandle<()>>)> {
+    let config = Arc::clone(&state.config);
+    if config.federation_bind.is_none() && config.federation_peers.is_empty() {
+        return Ok((None, Vec::new()));
+    }
+    for room_id in &config.federated_rooms {
+        if state.add_room(Room::new(room_id.clone(), room_id.clone())) {
+            state.journal.info(format!("🌐 Shared room {} created", room_id));
+        }
+    }
+
+    let mut tasks = Vec::new();
+    let mut bound = None;
+    if let Some(addr) = config.federation_bind {
+        let listener = TcpListener::bind(addr).await?;
+        bound = Some(listener.local_addr()?);
+        state.journal.info(format!("🌐 Federation listening on {} as {}", listener.local_addr()?, config.server_name));
+        tasks.push(tokio::spawn(listen(Arc::clone(state), listener)));
+    }
+    for peer in &config.federation_peers {
+        tasks.push(tokio::spawn(connect_loop(Arc::clone(state), peer.clone())));
+    }
+    let rooms: Vec<&str> = config.federated_rooms.iter().map(String::as_str).collect();
+    state.journal.info(format!("🌐 Shared rooms: {}", rooms.join(", ")));
+    Ok((bound, tasks))
+}
+
+/// Accept servers that link to this one; the links end with this task
+async fn listen(state: Arc<ServerState>, listener: TcpListener) {
+    let mut links = JoinSet::new();
+    loop {
+        let (stream, addr) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                state.journal.log(ChatEvent::error(None, format!("Error accepting a federation connection: {}", e)));
+                continue;
+            }
+        };
+        while links.try_join_next().is_some() {} // Reap closed links
+
+        let state = Arc::clone(&state);
+        links.spawn(async move {
+            match accept_link(&state, stream).await {
+                Ok((server, stream, queue)) => run_link(&state, &server, stream, &queue).await,
+                Err(e) => state.journal.log(ChatEvent::error(None, format!("Federation handshake with {} failed: {}", addr, e))),
+            }
+        });
+    }
+}
+
+/// Check the introduction of a server that connected, then accept it
+async fn accept_link(state: &ServerState, mut stream: TcpStream) -> Result<(String, TcpStream, LinkQueue), String> {
+    let frame = tokio::time::timeout(HANDSHAKE_TIMEOUT, read_frame_limited(&mut stream, state.config.max_message_size))
+        .await
+        .map_err(|_| "no introduction in time".to_string())?
+        .map_err(|e| e.to_string())?
+        .ok_or("connection closed")?;
+
+    let accepted = match frame.message {
+        Message::FederationHello { server, secret } => {
+            if state.config.federation_secret.as_deref() != Some(secret.as_str()) {
+                Err((ErrorCode::AuthFailed, format!("Wrong federation secret from {}", server)))
+            } else if server == state.config.server_name {
+                Err((ErrorCode::InvalidState, format!("Server name {} is ours", server)))
+            } else {
+                state.register_link(&server)
+                    .map(|queue| (server.clone(), queue))
+                    .ok_or((ErrorCode::InvalidState, format!("Already linked with {}", server)))
+            }
+        }
+        other => Err((ErrorCode::InvalidFormat, format!("Expected FederationHello, got {:?}", other))),
+    };
+    match accepted {
+        Ok((server, queue)) => {
+            let ack = ProtocolFrame::new(Message::FederationAck { server: state.config.server_name.clone() }, None, 0);
+            if let Err(e) = write_frame(&mut stream, &ack).await {
+                state.unregister_link(&server, &queue);
+                return Err(e.to_string());
+            }
+            Ok((server, stream, queue))
+        }
+        Err((code, message)) => {
+            let _ = write_frame(&mut stream, &ProtocolFrame::new(Message::Error { code, message: message.clone() }, None, 0)).await;
+            Err(message)
+        }
+    }
+}
+
+/// Link with the server at `addr`, again and again whenever the link drops, until the task is aborted
+async fn connect_loop(state: Arc<ServerState>, addr: String) {
+    let mut last_error = None;
+    loop {
+        match connect(&state, &addr).await {
+            Ok((server, stream, queue)) => {
+                last_error = None;
+                run_link(&state, &server, stream, &queue).await;
+            }
+            Err(e) => {
+                // A peer that stays down is reported once, not every RECONNECT_DELAY
+                if last_error.as_ref() != Some(&e) {
+                    state.journal.warn(format!("⚠️ Could not link with {}: {} (retrying every {}s)", addr, e, RECONNECT_DELAY.as_secs()));
+                    last_error = Some(e);
+                }
+            }
+        }
+        tokio::time::sleep(RECONNECT_DELAY).await;
+    }
+}
+
+async fn connect(state: &ServerState, addr: &str) -> Result<(String, TcpStream, LinkQueue), String> {
+    let mut stream = TcpStream::connect(addr).await.map_err(|e| e.to_string())?;
+    let hello = Message::FederationHello {
+        server: state.config.server_name.clone(),
+        secret: state.config.federation_secret.clone().unwrap_or_default(),
+    };
+    write_frame(&mut stream, &ProtocolFrame::new(hello, None, 0)).await.map_err(|e| e.to_string())?;
+
+    let frame = tokio::time::timeout(HANDSHAKE_TIMEOUT, read_frame_limited(&mut stream, state.config.max_message_size))
+        .await
+        .map_err(|_| "no answer in time".to_string())?
+        .map_err(|e| e.to_string())?
+        .ok_or("connection closed")?;
+    match frame.message {
+        Message::FederationAck { server } => {
+            let queue = state.register_link(&server).ok_or(format!("already linked with {}", server))?;
+            Ok((server, stream, queue))
+        }
+        Message::Error { message, .. } => Err(message),
+        other => Err(format!("unexpected answer: {:?}", other)),
+    }
+}
+
+/// Relay frames both ways until either side fails or falls silent, then forget the link
+async fn run_link(state: &ServerState, server: &str, stream: TcpStream, queue: &LinkQueue) {
+    state.journal.info(format!("🌐 Linked with {}", server));
+    let (mut reader, mut writer) = stream.into_split();
+    let heartbeat = state.config.heartbeat_interval();
+    // Both sides ping; hearing nothing for several heartbeats means the peer is gone
+    let silence = heartbeat * (state.config.max_missed_pongs + 1);
+
+    let send = async {
+        let mut interval = tokio::time::interval(heartbeat);
+        loop {
+            let frame = tokio::select! {
+                frame = queue.recv() => match frame {
+                    Some(frame) => frame,
+                    None => return Ok(()),
+                },
+                _ = interval.tick() => ProtocolFrame::new(Message::Ping, None, 0),
+            };
+            write_frame(&mut writer, &frame).await.map_err(|e| e.to_string())?;
+        }
+    };
+    let receive = async {
+        loop {
+            let frame = tokio::time::timeout(silence, read_frame_limited(&mut reader, state.config.max_message_size))
+                .await
+                .map_err(|_| format!("nothing heard for {}s", silence.as_secs()))?
+                .map_err(|e| e.to_string())?;
+            let Some(frame) = frame else {
+                return Ok(());
+            };
+            if let Err(e) = state.receive_federated(server, frame) {
+                state.journal.warn(format!("⚠️ Frame from {} ignored: {}", server, e));
+            }
+        }
+    };
+    let ended: Result<(), String> = tokio::select! {
+        ended = send => ended,
+        ended = receive => ended,
+    };
+
+    state.unregister_link(server, queue);
+    match ended {
+        Ok(()) => state.journal.info(format!("🌐 Link with {} closed", server)),
+        Err(e) => state.journal.warn(format!("⚠️ Link with {} lost: {}", server, e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::configuration::ServerConfig;
+    use crate::courrier::MailboxStore;
+    use crate::journal::Journal;
+    use crate::profils::ProfileStore;
+    use crate::protocole::MessageKind;
+    use crate::utilisateurs::UserStore;
+
+    #[tokio::test]
+    async fn test_relay_without_loops() {
+        let config = ServerConfig {
+            server_name: "b".to_string(),
+            federated_rooms: ["general".to_string()].into(),
+            ..ServerConfig::default()
+        };
+        let state = ServerState::new(
+            Arc::new(config),
+            None,
+            UserStore::default(),
+            ProfileStore::default(),
+            MailboxStore::default(),
+            Journal::default(),
+        );
+        let to_a = state.register_link("a").unwrap();
+        let to_c = state.register_link("c").unwrap();
+        assert!(state.register_link("a").is_none());
+
+        let relayed = |room_id: &str, message_id: &str, origin: &str| {
+            let message = Message::RoomMessage {
+                from: "alice".to_string(),
+                content: "Bonjour".to_string(),
+                timestamp: Utc::now(),
+                room_id: room_id.to_string(),
+                sequence: 1,
+                message_id: message_id.to_string(),
+                kind: MessageKind::Text,
+                received_at: None,
+                parent_message_id: None,
+            };
+            ProtocolFrame { origin: Some(origin.to_string()), ..ProtocolFrame::new(message, None, 1) }
+        };
+        let history = |state: &ServerState| state.rooms.get("general").unwrap().history.len();
+
+        // From a: delivered here and passed on to c only
+        state.receive_federated("a", relayed("general", "m1", "a")).unwrap();
+        assert_eq!(history(&state), 1);
+        assert_eq!((to_a.len(), to_c.len()), (0, 1));
+
+        // The same message again over c, one of ours, or a room we do not share: dropped
+        state.receive_federated("c", relayed("general", "m1", "a")).unwrap();
+        state.receive_federated("c", relayed("general", "m2", "b")).unwrap();
+        state.receive_federated("a", relayed("tech", "m3", "a")).unwrap();
+        assert_eq!(history(&state), 1);
+        assert_eq!((to_a.len(), to_c.len()), (0, 1));
+
+        assert_eq!(state.rooms.get("general").unwrap().history.find("m1").unwrap().from, "alice@a");
+    }
+}
@@ -0,0 +1,2948 @@
+// src/serveur/mod.rs
+// Serveur de messagerie utilisant le protocole SCP : état partagé, connexions et démarrage
+
+mod console;
+mod federation;
+
+use tokio::net::TcpListener;
+use tokio::task::JoinHandle;
+use tokio_rustls::TlsAcceptor;
+use std::collections::{HashMap, HashSet};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::time::{Duration, Instant};
+use tokio::io::AsyncWrite;
+use tokio::sync::broadcast::{self, error::{RecvError, TryRecvError}};
+use tokio::sync::Notify;
+use uuid::Uuid;
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use dashmap::mapref::entry::Entry;
+
+use crate::protocole::{
+    PROTOCOL_VERSION, MIN_PROTOCOL_VERSION, negotiate_version, Message, ProtocolFrame, ErrorCode,
+    ClientId, RoomId, MessageId, MessageKind, Room, RoomEvent, RoomVisibility, SessionState, HistoryEntry, PresenceStatus, FileTarget, validate_room_id, extract_mentions
+};
+use crate::motdepasse::{hash_password, verify_password};
+use crate::trame::{read_frame_limited, write_frame, FrameError};
+use crate::chiffrement::{self, Transport};
+use crate::configuration::{RateLimits, ServerConfig};
+use crate::debit::TokenBucket;
+use crate::envoi::{Push, SendQueue};
+use crate::salons::{RoomRecord, RoomStore};
+use crate::utilisateurs::UserStore;
+use crate::profils::{ProfileStore, UserProfile};
+use crate::courrier::{MailboxStore, PendingMessage};
+use crate::historique::{HistoryStore, RoomHistory, SearchQuery, MAX_SEARCH_RESULTS};
+use crate::fichiers::{decode_chunk, sanitize_filename};
+use crate::filtres::{FilterContext, FilterDecision, RoomFilters};
+use crate::journal::{ChatEvent, ConsoleLogger, Journal, JsonFileLogger};
+use crate::metriques::{Metrics, ServerStats};
+
+use federation::Federation;
+
+/// How long a closing connection may take to flush its last frames
+const SEND_DRAIN_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Minimum delay between two typing notifications relayed for the same client
+const TYPING_THROTTLE: Duration = Duration::from_secs(2);
+
+/// Violations are forgotten after this long without a new one
+const RATE_VIOLATION_RESET: Duration = Duration::from_secs(60);
+
+/// How often empty rooms are looked for
+const ROOM_GC_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Server-wide role of a client
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Role {
+    Member,
+    /// Server administrator: may moderate every room
+    Admin,
+}
+
+/// Moderation action requested by an administrator
+#[derive(Debug, Clone, Copy)]
+enum Sanction {
+    Kick,
+    Ban(Option<u64>),  // duration in seconds, None = permanent
+    Mute(Option<u64>), // duration in seconds, None = permanent
+}
+
+/// Room settings an administrator may change
+#[derive(Debug, Clone)]
+enum RoomUpdate {
+    Topic(String), // empty = clear the topic
+    Pin(u64),      // sequence number of the message
+    Unpin(u64),
+}
+
+/// Change to a room message, by its author or a room administrator
+#[derive(Debug, Clone)]
+enum MessageChange {
+    Edit(String), // new content
+    Delete,
+}
+
+/// Budget a client message is charged against
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum RateClass {
+    Chat,
+    Control,
+}
+
+/// Outcome of a rate-limit check
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum RateDecision {
+    Allowed,
+    Limited,
+    Disconnect,
+}
+
+/// Automatic replies and file chunks (bounded by the file size limit) are not charged
+fn rate_class(message: &Message) -> Option<RateClass> {
+    match message {
+        Message::SendMessage { .. } | Message::PrivateMessage { .. } | Message::EditMessage { .. } => Some(RateClass::Chat),
+        Message::Pong | Message::MessageAck { .. } | Message::MarkRead { .. } | Message::FileChunk { .. } | Message::Disconnect => None,
+        _ => Some(RateClass::Control),
+    }
+}
+
+/// What a connection's send task is given
+#[derive(Debug)]
+#[allow(clippy::large_enum_variant)] // Nearly everything sent is a frame: boxing them would cost an allocation each
+enum Outgoing {
+    Frame(ProtocolFrame),
+    /// Forward the broadcasts of the room just joined
+    Subscribe(broadcast::Receiver<RoomEvent>),
+    /// Stop forwarding room broadcasts after the one numbered `after`, the last sent while the client was a member
+    Unsubscribe { after: u64 },
+}
+
+/// A connection's bounded send queue, and the means to close the connection when the client stops reading
+struct Outbox {
+    queue: Arc<SendQueue<Outgoing>>,
+    disconnect: Arc<Notify>,
+}
+
+/// Restrictions per room: username -> end of the restriction (None = permanent)
+type Restrictions = DashMap<RoomId, HashMap<String, Option<DateTime<Utc>>>>;
+
+/// Position of each user in each room: username -> highest sequence reached, never going back
+type Markers = DashMap<RoomId, HashMap<String, u64>>;
+
+/// Structure representing a connected client
+#[derive(Debug, Clone)]
+#[allow(dead_code)] // `id` is kept for diagnostics
+struct Client {
+    id: ClientId,
+    username: Option<String>,
+    current_room: Option<RoomId>,
+    session_state: SessionState,
+    role: Role,
+    missed_pongs: u32, // Server pings left unanswered in a row
+    presence: PresenceStatus,
+    last_typing: Option<Instant>, // Last typing notification relayed, for throttling
+    chat_bucket: TokenBucket,
+    control_bucket: TokenBucket,
+    rate_violations: u32, // Rate-limited messages since the last reset
+    last_violation: Option<Instant>,
+    disconnect: Arc<Notify>, // Wakes the connection task to close it (heartbeat timeout, abuse)
+    protocol_version: Arc<AtomicU8>, // Version spoken on this connection, 0 until its first frame
+    profile: UserProfile, // Loaded at login, kept in step with UpdateProfile
+    clock_skew: Option<i64>, // Seconds its clock is ahead of ours, while beyond max_clock_skew
+    waiting_for: Option<RoomId>, // Full room it is queued for
+    waits_as_spectator: bool, // Whether it enters that room as a spectator
+}
+
+/// File transfer relayed by the server, from its offer to its FileComplete
+#[derive(Debug)]
+struct Transfer {
+    sender: ClientId,
+    offered_to: HashSet<ClientId>,
+    accepted: HashSet<ClientId>, // Recipients the chunks are relayed to
+    size: u64,      // Announced size, enforced while relaying
+    received: u64,  // Bytes relayed so far
+    next_seq: u64,  // Expected sequence of the next chunk
+}
+
+impl Client {
+    fn new(id: ClientId, limits: &RateLimits) -> Self {
+        Self {
+            id,
+            username: None,
+            current_room: None,
+            session_state: SessionState::Connected,
+            role: Role::Member,
+            missed_pongs: 0,
+            presence: PresenceStatus::Online,
+            last_typing: None,
+            chat_bucket: TokenBucket::new(limits.chat_burst, limits.chat_per_sec),
+            control_bucket: TokenBucket::new(limits.control_burst, limits.control_per_sec),
+            rate_violations: 0,
+            last_violation: None,
+            disconnect: Arc::new(Notify::new()),
+            protocol_version: Arc::new(AtomicU8::new(0)),
+            profile: UserProfile::default(),
+            clock_skew: None,
+            waiting_for: None,
+            waits_as_spectator: false,
+        }
+    }
+}
+
+/// Global server state, shared by every connection without a global lock: each map locks its own
+/// shards, so logins, joins and messages in different rooms do not wait for each other.
+/// Lock order, to stay deadlock-free: an entry of `clients` may be held while taking one of `rooms`,
+/// and either while taking one of the other maps, never the reverse; and no entry is held while
+/// another entry of the same map is taken.
+struct ServerState {
+    clients: DashMap<ClientId, Client>,
+    rooms: DashMap<RoomId, Room>,
+    username_to_client: DashMap<String, ClientId>, // To find a client by username
+    client_senders: DashMap<ClientId, Outbox>, // To send messages to specific clients
+    history_store: Option<HistoryStore>, // On-disk room history, if enabled
+    users: UserStore, // Registered accounts (locked internally, never while hashing)
+    profiles: ProfileStore, // Display names and preferences (locked internally)
+    mailboxes: Mutex<MailboxStore>, // Private messages waiting for offline users
+    room_owners: DashMap<RoomId, String>, // room_id -> username of its creator (admin); built-in rooms have none
+    empty_since: DashMap<RoomId, Instant>, // Client-created rooms currently without members
+    bans: Restrictions,
+    mutes: Restrictions,
+    acked: Markers, // Messages received by each user's client
+    read: Markers,  // Messages each user has read, for unread counts
+    transfers: DashMap<String, Transfer>, // transfer_id -> file transfer in progress
+    filters: RoomFilters, // Content filters applied to room messages (locked internally)
+    federation: Federation, // Links with other servers, for the shared rooms
+    journal: Journal, // Where every event of the server is reported
+    metrics: Metrics, // Room activity and error counts, for GetStats and the periodic digest (locked internally)
+    config: Arc<ServerConfig>, // Limits, built-in rooms and administrators
+    started: Instant,
+}
+
+impl ServerState {
+    fn new(
+        config: Arc<ServerConfig>,
+        history_store: Option<HistoryStore>,
+        users: UserStore,
+        profiles: ProfileStore,
+        mailboxes: MailboxStore,
+        journal: Journal,
+    ) -> Self {
+        let state = Self {
+            clients: DashMap::new(),
+            rooms: DashMap::new(),
+            username_to_client: DashMap::new(),
+            client_senders: DashMap::new(),
+            history_store,
+            users,
+            profiles,
+            mailboxes: Mutex::new(mailboxes),
+            room_owners: DashMap::new(),
+            empty_since: DashMap::new(),
+            bans: DashMap::new(),
+            mutes: DashMap::new(),
+            acked: DashMap::new(),
+            read: DashMap::new(),
+            transfers: DashMap::new(),
+            filters: RoomFilters::new(&config.filters),
+            federation: Federation::default(),
+            journal,
+            metrics: Metrics::default(),
+            config: Arc::clone(&config),
+            started: Instant::now(),
+        };
+
+        // Built-in rooms, without an admin
+        for room in &config.rooms {
+            let mut built_in = Room::new(room.id.clone(), room.name.clone());
+            built_in.max_users = room.max_users;
+            state.add_room(built_in);
+        }
+
+        state
+    }
+
+    /// Register a room, restoring its persisted history if any; false if the id is already taken
+    fn add_room(&self, mut room: Room) -> bool {
+        let capacity = self.config.history_capacity;
+        room.history = RoomHistory::with_capacity(capacity);
+        if let Some(store) = &self.history_store {
+            match store.load(&room.id, capacity) {
+                Ok(history) => room.history = history,
+                Err(e) => self.journal.warn(format!("⚠️ Could not load history for room {}: {}", room.id, e)),
+            }
+        }
+        match self.rooms.entry(room.id.clone()) {
+            Entry::Occupied(_) => false,
+            Entry::Vacant(vacant) => {
+                vacant.insert(room);
+                true
+            }
+        }
+    }
+
+    /// Recreate the client-created rooms saved by a previous run
+    fn restore_rooms(&self, records: Vec<RoomRecord>) -> usize {
+        let mut restored = 0;
+        for record in records {
+            if self.rooms.contains_key(&record.id) {
+                continue;
+            }
+            let mut room = Room::new(record.id.clone(), record.name);
+            room.created_at = record.created_at;
+            room.visibility = record.visibility;
+            room.topic = record.topic;
+            room.pinned = record.pinned;
+            room.max_users = record.max_users;
+            self.add_room(room);
+            self.room_owners.insert(record.id.clone(), record.owner);
+            self.empty_since.insert(record.id, Instant::now());
+            restored += 1;
+        }
+        restored
+    }
+
+    /// Client-created rooms, as saved on shutdown (built-in rooms are recreated anyway)
+    fn room_records(&self) -> Vec<RoomRecord> {
+        let owners: Vec<(RoomId, String)> = self.room_owners.iter()
+            .map(|entry| (entry.key().clone(), entry.value().clone()))
+            .collect();
+        owners.into_iter()
+            .filter_map(|(room_id, owner)| {
+                let room = self.rooms.get(&room_id)?;
+                Some(RoomRecord {
+                    id: room.id.clone(),
+                    name: room.name.clone(),
+                    owner,
+                    visibility: room.visibility.clone(),
+                    created_at: room.created_at,
+                    topic: room.topic.clone(),
+                    pinned: room.pinned.clone(),
+                    max_users: room.max_users,
+                })
+            })
+            .collect()
+    }
+
+    /// Number a room message, then keep it in memory and on disk
+    fn record_message(&self, room_id: &str, mut entry: HistoryEntry) -> Result<HistoryEntry, String> {
+        let mut room = self.rooms.get_mut(room_id).ok_or_else(|| format!("Salon {} introuvable", room_id))?;
+        entry.sequence = room.history.next_sequence();
+        // Server time never goes back within a room, so it orders the messages like their sequence numbers
+        let now = Utc::now();
+        entry.received_at = Some(room.history.last_received_at().map_or(now, |last| last.max(now)));
+        room.history.push(entry.clone());
+        self.metrics.message(room_id, &entry.from, entry.content.len(), Instant::now());
+
+        // Still holding the room: its file receives the messages in sequence order
+        if let Some(store) = &self.history_store {
+            if let Err(e) = store.append(room_id, &entry) {
+                self.journal.warn(format!("⚠️ Could not persist message for room {}: {}", room_id, e));
+            }
+        }
+        Ok(entry)
+    }
+
+    /// Check the clock of a client against ours as its frame arrives. Beyond max_clock_skew, the frame
+    /// gets the server time instead; returns the offset in seconds when the client has just drifted that far
+    fn check_clock(&self, client_id: &ClientId, frame: &mut ProtocolFrame) -> Option<i64> {
+        let now = Utc::now();
+        let offset = clock_offset(&self.config, frame.timestamp, now);
+        let mut client = self.clients.get_mut(client_id)?;
+        let newly_skewed = offset.is_some() && client.clock_skew.is_none();
+        client.clock_skew = offset;
+        drop(client);
+        if offset.is_some() {
+            frame.timestamp = now;
+        }
+        if newly_skewed {
+            let offset = offset.unwrap_or_default();
+            self.journal.warn(format!("⏰ Client {} clock is off by {}s, its timestamps are replaced by the server's.", client_id, offset));
+        }
+        offset.filter(|_| newly_skewed)
+    }
+
+    /// Remember the highest sequence a user has received in a room
+    fn acknowledge(&self, client_id: &ClientId, room_id: &str, sequence: u64) -> Result<(), String> {
+        self.advance_marker(&self.acked, client_id, room_id, sequence)
+    }
+
+    /// Remember the highest sequence a user has read in a room
+    fn mark_read(&self, client_id: &ClientId, room_id: &str, sequence: u64) -> Result<(), String> {
+        self.advance_marker(&self.read, client_id, room_id, sequence)
+    }
+
+    fn advance_marker(&self, markers: &Markers, client_id: &ClientId, room_id: &str, sequence: u64) -> Result<(), String> {
+        let username = self.clients.get(client_id).ok_or("Client introuvable")?
+            .username.clone().ok_or("Client non authentifié")?;
+        let last_sequence = self.rooms.get(room_id).ok_or_else(|| format!("Salon {} introuvable", room_id))?
+            .history.last_sequence();
+        if sequence > last_sequence {
+            return Err(format!("Numéro de séquence {} inconnu dans le salon {}", sequence, room_id));
+        }
+
+        let mut room_markers = markers.entry(room_id.to_string()).or_default();
+        let marker = room_markers.entry(username).or_insert(0);
+        *marker = (*marker).max(sequence);
+        Ok(())
+    }
+
+    /// Messages of others a user has not read yet, in the rooms where they have read something;
+    /// only the messages still in memory are counted
+    fn unread_counts(&self, username: &str) -> HashMap<RoomId, usize> {
+        let positions: Vec<(RoomId, u64)> = self.read.iter()
+            .filter_map(|room| room.value().get(username).map(|sequence| (room.key().clone(), *sequence)))
+            .collect();
+        positions.into_iter()
+            .filter_map(|(room_id, sequence)| {
+                let unread = self.rooms.get(&room_id)?.history.unread(sequence, username);
+                Some((room_id, unread))
+            })
+            .collect()
+    }
+
+    /// Messages to replay when a user enters a room: everything after their last acknowledgement
+    /// if they were here before, otherwise the latest messages for context
+    fn replay_for(&self, username: &str, room_id: &str) -> Vec<HistoryEntry> {
+        let acked = self.acked.get(room_id).and_then(|users| users.get(username).copied());
+        let Some(room) = self.rooms.get(room_id) else {
+            return Vec::new();
+        };
+        match acked {
+            Some(sequence) => room.history.since(sequence),
+            None => room.history.last(self.config.history_replay),
+        }
+    }
+
+    /// Register a new connection; returns the queue its send task reads from
+    fn add_client(&self, client_id: ClientId) -> Arc<SendQueue<Outgoing>> {
+        let client = Client::new(client_id.clone(), &self.config.rate_limits);
+        let queue = Arc::new(SendQueue::new(self.config.send_queue_capacity, self.config.slow_client_limit()));
+        let outbox = Outbox { queue: Arc::clone(&queue), disconnect: Arc::clone(&client.disconnect) };
+        self.clients.insert(client_id.clone(), client);
+        self.client_senders.insert(client_id, outbox);
+        queue
+    }
+
+    /// Queue something for a client's send task. Only frames are dropped when the client does not keep up
+    /// (oldest first); one whose queue stays full for `slow_client_secs` is disconnected
+    fn enqueue(&self, client_id: &ClientId, outgoing: Outgoing) -> Push {
+        let Some(outbox) = self.client_senders.get(client_id) else {
+            return Push::Closed;
+        };
+        if let Outgoing::Frame(ProtocolFrame { message: Message::Error { code, .. }, .. }) = &outgoing {
+            self.metrics.error(code);
+        }
+        let droppable = matches!(outgoing, Outgoing::Frame(_));
+        let pushed = outbox.queue.push(outgoing, droppable);
+        if pushed == Push::Stalled {
+            self.journal.warn(format!(
+                "🐢 Client {} stopped reading ({} frame(s) dropped so far). Disconnecting.",
+                client_id,
+                outbox.queue.dropped(),
+            ));
+            outbox.disconnect.notify_one();
+        }
+        pushed
+    }
+
+    fn remove_client(&self, client_id: &ClientId) {
+        if let Some((_, client)) = self.clients.remove(client_id) {
+            self.journal.log(ChatEvent::Disconnected { client_id: client_id.clone(), username: client.username.clone() });
+            if let (Some(username), Some(room_id)) = (&client.username, &client.current_room) {
+                self.journal.log(ChatEvent::Left { username: username.clone(), room_id: room_id.clone() });
+            }
+
+            // Remove from username -> client map if the user was authenticated
+            if let Some(username) = &client.username {
+                self.username_to_client.remove(username);
+            }
+
+            // Remove from the current room if the user was in one
+            if let Some(room_id) = &client.current_room {
+                if let Some(mut room) = self.rooms.get_mut(room_id) {
+                    if room.remove_user(client_id).is_some() {
+                        // Notify other room members that the user left
+                        let notification = Message::UserLeft {
+                            username: client.username.clone().unwrap_or_else(|| "un client anonyme".to_string()),
+                            room_id: room_id.clone(),
+                        };
+                        let frame = ProtocolFrame::new(notification, None, 0); // Sequence 0 for notifications
+                        room.broadcast(frame, Some(client_id));
+                    }
+                }
+                self.admit_waiting(room_id);
+            }
+
+            // Nor does it wait for a room anymore
+            if let Some(room_id) = &client.waiting_for {
+                if self.rooms.get_mut(room_id).is_some_and(|mut room| room.unqueue(client_id)) {
+                    self.notify_queue(room_id);
+                }
+            }
+        }
+
+        // Transfers sent by the client cannot complete anymore
+        let sent: Vec<String> = self.transfers.iter()
+            .filter(|transfer| &transfer.sender == client_id)
+            .map(|transfer| transfer.key().clone())
+            .collect();
+        for transfer_id in sent {
+            self.abort_transfer(&transfer_id, client_id, "L'expéditeur s'est déconnecté");
+        }
+        for mut transfer in self.transfers.iter_mut() {
+            transfer.offered_to.remove(client_id);
+            transfer.accepted.remove(client_id);
+        }
+
+        // Close the queue last: the notices above may still be for this client
+        if let Some((_, outbox)) = self.client_senders.remove(client_id) {
+            outbox.queue.close();
+        }
+    }
+
+    /// Explicit disconnection: acknowledge it, then forget the client right away (room, maps, UserLeft to
+    /// the room) and wake its connection task, which closes the socket once the acknowledgement is flushed.
+    /// Returns the username the client was logged in with
+    fn disconnect(&self, client_id: &ClientId) -> Option<String> {
+        let (username, dead) = {
+            let mut client = self.clients.get_mut(client_id)?;
+            client.session_state = SessionState::Closed;
+            (client.username.clone(), Arc::clone(&client.disconnect))
+        };
+        self.send_to_clients([client_id], Message::DisconnectAck);
+        self.remove_client(client_id);
+        dead.notify_one();
+        username
+    }
+
+    /// Charge a message to the client's budget; repeated violations end in a disconnection
+    fn check_rate(&self, client_id: &ClientId, class: RateClass) -> RateDecision {
+        let max_violations = self.config.rate_limits.max_violations;
+        let Some(mut client) = self.clients.get_mut(client_id) else {
+            return RateDecision::Allowed;
+        };
+        let bucket = match class {
+            RateClass::Chat => &mut client.chat_bucket,
+            RateClass::Control => &mut client.control_bucket,
+        };
+        if bucket.try_take() {
+            return RateDecision::Allowed;
+        }
+
+        let now = Instant::now();
+        if client.last_violation.is_some_and(|last| now.duration_since(last) > RATE_VIOLATION_RESET) {
+            client.rate_violations = 0;
+        }
+        client.last_violation = Some(now);
+        client.rate_violations += 1;
+        if client.rate_violations >= max_violations {
+            RateDecision::Disconnect
+        } else {
+            RateDecision::Limited
+        }
+    }
+
+    /// Register (if `register`) or check credentials, then bind the account to this connection.
+    /// Hashing takes a while: nothing of the state is locked meanwhile
+    fn authenticate_client(
+        &self,
+        client_id: &ClientId,
+        username: String,
+        password: &str,
+        register: bool,
+    ) -> Result<(), (ErrorCode, String)> {
+        let session_state = self.clients.get(client_id)
+            .map(|client| client.session_state.clone())
+            .ok_or((ErrorCode::InternalError, "Client non trouvé".to_string()))?;
+        // Ensure the client is in "Connected" state
+        if !matches!(session_state, SessionState::Connected) {
+            return Err((
+                ErrorCode::InvalidState,
+                format!("Action non autorisée. Client déjà dans l'état: {:?}", session_state),
+            ));
+        }
+
+        if register {
+            self.users.register(&username, password)?;
+        } else {
+            self.users.verify(&username, password)?;
+        }
+
+        // One live session per account: the name is claimed atomically
+        match self.username_to_client.entry(username.clone()) {
+            Entry::Occupied(_) => {
+                return Err((ErrorCode::UsernameAlreadyTaken, "Ce compte est déjà connecté".to_string()));
+            }
+            Entry::Vacant(vacant) => {
+                vacant.insert(client_id.clone());
+            }
+        }
+
+        if let Some(mut client) = self.clients.get_mut(client_id) {
+            client.username = Some(username.clone());
+            client.session_state = SessionState::Authenticated(username.clone());
+            client.role = if self.config.admins.contains(&username) { Role::Admin } else { Role::Member };
+            client.profile = self.profiles.get(&username);
+        }
+        Ok(())
+    }
+
+    /// A user's profile: complete for oneself (`username` None or one's own name), public part for anybody else
+    fn profile_of(&self, client_id: &ClientId, username: Option<String>) -> Result<Message, (ErrorCode, String)> {
+        let (own_name, own_profile) = self.clients.get(client_id)
+            .and_then(|client| Some((client.username.clone()?, client.profile.clone())))
+            .ok_or((ErrorCode::InvalidState, "Client non authentifié".to_string()))?;
+        match username {
+            None => Ok(Message::Profile { username: own_name, profile: own_profile }),
+            Some(username) if username == own_name => Ok(Message::Profile { username, profile: own_profile }),
+            Some(username) if self.users.contains(&username) => {
+                let profile = self.profiles.get(&username).public();
+                Ok(Message::Profile { username, profile })
+            }
+            Some(username) => Err((ErrorCode::UserNotFound, format!("Utilisateur {} inconnu", username))),
+        }
+    }
+
+    /// Replace the client's profile, on disk and for the rest of the session
+    fn update_profile(&self, client_id: &ClientId, profile: UserProfile) -> Result<Message, (ErrorCode, String)> {
+        let username = self.username_of(client_id)
+            .ok_or((ErrorCode::InvalidState, "Client non authentifié".to_string()))?;
+        let profile = self.profiles.update(&username, profile)?;
+        if let Some(mut client) = self.clients.get_mut(client_id) {
+            client.profile = profile.clone();
+        }
+        Ok(Message::Profile { username, profile })
+    }
+
+    /// Block or unblock a user for the client, and return its updated profile
+    fn set_blocked(&self, client_id: &ClientId, target: &str, blocked: bool) -> Result<Message, (ErrorCode, String)> {
+        let username = self.username_of(client_id)
+            .ok_or((ErrorCode::InvalidState, "Client non authentifié".to_string()))?;
+        if target == username {
+            return Err((ErrorCode::InvalidState, "Impossible de se bloquer soi-même".to_string()));
+        }
+        if blocked && !self.users.contains(target) {
+            return Err((ErrorCode::UserNotFound, format!("Utilisateur {} inconnu", target)));
+        }
+        let profile = self.profiles.set_blocked(&username, target, blocked)?;
+        if let Some(mut client) = self.clients.get_mut(client_id) {
+            client.profile = profile.clone();
+        }
+        Ok(Message::Profile { username, profile })
+    }
+
+    /// Activity figures of the server, completed with what only the state knows
+    fn stats(&self) -> ServerStats {
+        let mut stats = self.metrics.snapshot(Instant::now());
+        stats.uptime_secs = self.started.elapsed().as_secs();
+        stats.connected = self.clients.len();
+        for room in self.rooms.iter() {
+            stats.rooms.entry(room.key().clone()).or_default().members = room.users.len();
+        }
+        stats
+    }
+
+    /// The server's activity figures, for server administrators only
+    fn stats_for(&self, client_id: &ClientId) -> Result<Message, (ErrorCode, String)> {
+        let is_admin = self.clients.get(client_id).is_some_and(|client| client.role == Role::Admin);
+        if !is_admin {
+            return Err((ErrorCode::PermissionDenied, "Statistiques réservées aux administrateurs du serveur".to_string()));
+        }
+        Ok(Message::Stats { stats: self.stats() })
+    }
+
+    fn join_room(&self, client_id: &ClientId, room_id: &str, spectator: bool) -> Result<Vec<String>, String> {
+        let (username, old_room) = {
+            let mut client = self.clients.get_mut(client_id).ok_or("Client non trouvé")?;
+            let username = client.username.clone().ok_or("Client non authentifié")?;
+
+            // Check if the room exists
+            if !self.rooms.contains_key(room_id) {
+                return Err("Salon inexistant".to_string());
+            }
+
+            // Join the new room
+            let old_room = client.current_room.replace(room_id.to_string());
+            client.session_state = SessionState::InRoom(username.clone(), room_id.to_string());
+            (username, old_room)
+        };
+
+        // Leave previous room if applicable
+        if let Some(old_room_id) = old_room {
+            if let Some(mut old_room) = self.rooms.get_mut(&old_room_id) {
+                old_room.remove_user(client_id);
+                // Notify old room members
+                let notification = Message::UserLeft {
+                    username: username.clone(),
+                    room_id: old_room_id.clone(),
+                };
+                let frame = ProtocolFrame::new(notification, None, 0);
+                old_room.broadcast(frame, Some(client_id));
+                self.journal.log(ChatEvent::Left { username: username.clone(), room_id: old_room_id.clone() });
+            }
+            self.unsubscribe(client_id, &old_room_id);
+            if old_room_id != room_id {
+                self.admit_waiting(&old_room_id);
+            }
+        }
+
+        let mut room = match self.rooms.get_mut(room_id) {
+            Some(room) if !room.is_full_for(client_id) => room,
+            room => {
+                // Deleted or filled up in the meantime
+                let reason = if room.is_some() { "Salon complet" } else { "Salon inexistant" };
+                drop(room);
+                if let Some(mut client) = self.clients.get_mut(client_id) {
+                    client.current_room = None;
+                    client.session_state = SessionState::Authenticated(username);
+                }
+                return Err(reason.to_string());
+            }
+        };
+        let receiver = if spectator {
+            room.add_spectator(client_id.clone(), username)
+        } else {
+            room.add_user(client_id.clone(), username)
+        };
+        let usernames = room.get_usernames();
+        drop(room);
+        self.enqueue(client_id, Outgoing::Subscribe(receiver));
+
+        Ok(usernames)
+    }
+
+    /// Leave the current room, and the waiting line of a full room if the client is in one
+    fn leave_room(&self, client_id: &ClientId) -> Result<(), String> {
+        let left_queue = self.leave_queue(client_id);
+        let (username, room_id) = {
+            let mut client = self.clients.get_mut(client_id).ok_or("Client non trouvé")?;
+            let username = client.username.clone().ok_or("Client non authentifié")?;
+            let Some(room_id) = client.current_room.take() else {
+                return if left_queue { Ok(()) } else { Err("Vous n'êtes pas dans un salon".to_string()) };
+            };
+            client.session_state = SessionState::Authenticated(username.clone());
+            (username, room_id)
+        };
+
+        // Remove from room
+        if let Some(mut room) = self.rooms.get_mut(&room_id) {
+            room.remove_user(client_id);
+        }
+
+        // Notify other room users
+        let notification = Message::UserLeft {
+            username: username.clone(),
+            room_id: room_id.clone(),
+        };
+        let frame = ProtocolFrame::new(notification, None, 0);
+        self.broadcast_to_room(&room_id, frame, Some(client_id));
+        self.unsubscribe(client_id, &room_id);
+        self.admit_waiting(&room_id);
+
+        self.journal.log(ChatEvent::Left { username, room_id });
+        Ok(())
+    }
+
+    /// Enter a room whose access was checked: JoinRoomAck to the client, UserJoined to the members,
+    /// then what the client missed
+    fn enter_room(&self, client_id: &ClientId, room_id: &str, spectator: bool) -> Result<(), String> {
+        let users = self.join_room(client_id, room_id, spectator)?;
+        self.leave_queue(client_id);
+        let topic = self.rooms.get(room_id).and_then(|room| room.topic.clone());
+        self.send_to_clients([client_id], Message::JoinRoomAck { room_id: room_id.to_string(), users, topic });
+
+        // Notify other users in the room that someone joined
+        let joined = self.clients.get(client_id)
+            .and_then(|client| Some((client.username.clone()?, client.profile.clone())));
+        if let Some((username, profile)) = joined {
+            let notification = Message::UserJoined {
+                username: username.clone(),
+                room_id: room_id.to_string(),
+                display_name: profile.display_name,
+                avatar: profile.avatar,
+            };
+            let frame = ProtocolFrame::new(notification, None, 0); // Sequence 0 for notifications
+            self.broadcast_to_room(room_id, frame, Some(client_id)); // Exclude the client who just joined
+
+            // Replay what the user missed since their last visit, or some context for a newcomer
+            let messages = self.replay_for(&username, room_id);
+            if !messages.is_empty() {
+                self.send_to_clients([client_id], Message::History { room_id: room_id.to_string(), messages });
+            }
+            self.journal.log(ChatEvent::Joined { username, room_id: room_id.to_string() });
+        }
+        Ok(())
+    }
+
+    /// Queue a client for a full room, out of any other line; returns its position,
+    /// or None if the room has space after all (or does not exist)
+    fn queue_for_room(&self, client_id: &ClientId, room_id: &str, spectator: bool) -> Option<usize> {
+        self.leave_queue(client_id);
+        let mut client = self.clients.get_mut(client_id)?;
+        let mut room = self.rooms.get_mut(room_id)?;
+        if !room.is_full_for(client_id) {
+            return None;
+        }
+        client.waiting_for = Some(room_id.to_string()); // Before anyone can admit it
+        client.waits_as_spectator = spectator;
+        Some(room.queue(client_id))
+    }
+
+    /// Take a client out of the line it waits in, if any; the others behind move up.
+    /// Returns whether the client was waiting
+    fn leave_queue(&self, client_id: &ClientId) -> bool {
+        let Some(room_id) = self.clients.get_mut(client_id).and_then(|mut client| client.waiting_for.take()) else {
+            return false;
+        };
+        if self.rooms.get_mut(&room_id).is_some_and(|mut room| room.unqueue(client_id)) {
+            self.notify_queue(&room_id);
+        }
+        true
+    }
+
+    /// Tell everyone waiting for a room their position
+    fn notify_queue(&self, room_id: &str) {
+        let waiting: Vec<ClientId> = self.rooms.get(room_id).map(|room| room.waiting.iter().cloned().collect()).unwrap_or_default();
+        for (index, client_id) in waiting.iter().enumerate() {
+            self.send_to_clients([client_id], Message::RoomQueued { room_id: room_id.to_string(), position: index + 1 });
+        }
+    }
+
+    /// Let waiting clients into a room while it has space, first come first served
+    fn admit_waiting(&self, room_id: &str) {
+        let mut admitted = false;
+        loop {
+            let next = match self.rooms.get_mut(room_id) {
+                Some(mut room) if !room.is_full() => room.waiting.pop_front(),
+                _ => None,
+            };
+            let Some(client_id) = next else {
+                break;
+            };
+            admitted = true;
+            let spectator = match self.clients.get_mut(&client_id) {
+                Some(mut client) => {
+                    client.waiting_for = None;
+                    client.waits_as_spectator
+                }
+                None => false,
+            };
+            // Banned while waiting
+            let banned = self.username_of(&client_id).is_some_and(|username| active_restriction(&self.bans, room_id, &username).is_some());
+            let entered = if banned { Err("Vous êtes banni de ce salon".to_string()) } else { self.enter_room(&client_id, room_id, spectator) };
+            match entered {
+                Ok(()) => self.journal.info(format!("🎟️ Client {} entered {} from the waiting line", client_id, room_id)),
+                Err(reason) => self.send_to_clients([&client_id], Message::JoinRoomError { reason }),
+            }
+        }
+        if admitted {
+            self.notify_queue(room_id);
+        }
+    }
+
+    async fn create_room(
+        &self,
+        client_id: &ClientId,
+        room_id: &str,
+        name: &str,
+        password: Option<&str>,
+        invite_only: bool,
+        max_users: Option<usize>,
+    ) -> Result<(), String> {
+        let username = self.username_of(client_id).ok_or("Client non authentifié")?;
+
+        validate_room_id(room_id)?;
+        if max_users == Some(0) {
+            return Err("Un salon doit pouvoir accueillir au moins un membre".to_string());
+        }
+        if self.rooms.contains_key(room_id) {
+            return Err(format!("Le salon {} existe déjà", room_id));
+        }
+
+        let visibility = match (password, invite_only) {
+            (Some(_), true) => return Err("Un salon ne peut pas être à la fois protégé par mot de passe et sur invitation".to_string()),
+            (Some(password), false) => {
+                // Argon2 is slow on purpose: hash on a blocking thread rather than stall this worker
+                let password = password.to_string();
+                let password_hash = tokio::task::spawn_blocking(move || hash_password(&password))
+                    .await
+                    .map_err(|e| format!("Hashing task failed: {}", e))??;
+                RoomVisibility::Private { password_hash }
+            }
+            (None, true) => RoomVisibility::InviteOnly { invited: Default::default() },
+            (None, false) => RoomVisibility::Public,
+        };
+
+        let name = if name.trim().is_empty() { room_id } else { name.trim() };
+        let mut room = Room::new(room_id.to_string(), name.to_string());
+        room.visibility = visibility;
+        room.max_users = max_users;
+        // Someone may have taken the id while the password was hashed
+        if !self.add_room(room) {
+            return Err(format!("Le salon {} existe déjà", room_id));
+        }
+        self.room_owners.insert(room_id.to_string(), username);
+        self.empty_since.insert(room_id.to_string(), Instant::now());
+        Ok(())
+    }
+
+    /// Check whether a client may enter a room (the room admin always may)
+    async fn check_room_access(&self, client_id: &ClientId, room_id: &str, password: Option<&str>) -> Result<(), (ErrorCode, String)> {
+        let username = self.username_of(client_id);
+        // A copy, so that no room guard is held while the password is checked on a blocking thread
+        let Some(visibility) = self.rooms.get(room_id).map(|room| room.visibility.clone()) else {
+            return Ok(()); // join_room reports missing rooms
+        };
+        if username.is_some() && self.owner_of(room_id) == username {
+            return Ok(());
+        }
+        if let Some(username) = &username {
+            if let Some(until) = active_restriction(&self.bans, room_id, username) {
+                let message = match until {
+                    Some(until) => format!("Vous êtes banni de ce salon jusqu'à {}", until.format("%d/%m %H:%M:%S")),
+                    None => "Vous êtes banni de ce salon".to_string(),
+                };
+                return Err((ErrorCode::Banned, message));
+            }
+        }
+
+        match visibility {
+            RoomVisibility::Public => Ok(()),
+            RoomVisibility::Private { password_hash } => match password {
+                Some(password) => {
+                    let password = password.to_string();
+                    let valid = tokio::task::spawn_blocking(move || verify_password(&password, &password_hash))
+                        .await
+                        .map_err(|e| (ErrorCode::InternalError, format!("Password check failed: {}", e)))?;
+                    if valid {
+                        Ok(())
+                    } else {
+                        Err((ErrorCode::InvalidRoomPassword, "Mot de passe du salon incorrect".to_string()))
+                    }
+                }
+                None => Err((ErrorCode::InvalidRoomPassword, "Ce salon est protégé par un mot de passe".to_string())),
+            },
+            RoomVisibility::InviteOnly { invited } => match &username {
+                Some(username) if invited.contains(username) => Ok(()),
+                _ => Err((ErrorCode::NotInvited, "Ce salon est accessible sur invitation uniquement".to_string())),
+            },
+        }
+    }
+
+    fn invite_user(&self, client_id: &ClientId, room_id: &str, invitee: &str) -> Result<(), (ErrorCode, String)> {
+        let username = self.username_of(client_id)
+            .ok_or((ErrorCode::InvalidState, "Client non authentifié".to_string()))?;
+
+        if self.owner_of(room_id) != Some(username) {
+            return Err((ErrorCode::PermissionDenied, "Seul l'administrateur du salon peut inviter".to_string()));
+        }
+        let mut room = self.rooms.get_mut(room_id)
+            .ok_or((ErrorCode::RoomNotFound, "Salon inexistant".to_string()))?;
+        match &mut room.visibility {
+            RoomVisibility::InviteOnly { invited } => {
+                invited.insert(invitee.to_string());
+                Ok(())
+            }
+            _ => Err((ErrorCode::InvalidState, "Ce salon n'est pas sur invitation".to_string())),
+        }
+    }
+
+    /// Whether a client may moderate a room: server admins everywhere, room owners in their rooms
+    fn can_moderate(&self, client: &Client, room_id: &str) -> bool {
+        client.role == Role::Admin
+            || (client.username.is_some() && self.owner_of(room_id) == client.username)
+    }
+
+    /// The name of a client allowed to moderate an existing room
+    fn require_moderator(&self, client_id: &ClientId, room_id: &str) -> Result<String, (ErrorCode, String)> {
+        let client = self.clients.get(client_id)
+            .ok_or((ErrorCode::InternalError, "Client non trouvé".to_string()))?;
+        let username = client.username.clone()
+            .ok_or((ErrorCode::InvalidState, "Client non authentifié".to_string()))?;
+        if !self.rooms.contains_key(room_id) {
+            return Err((ErrorCode::RoomNotFound, "Salon inexistant".to_string()));
+        }
+        if !self.can_moderate(&client, room_id) {
+            return Err((ErrorCode::PermissionDenied, "Action réservée aux administrateurs du salon".to_string()));
+        }
+        Ok(username)
+    }
+
+    /// Apply a sanction and return the notification describing it
+    fn moderate(&self, client_id: &ClientId, room_id: &str, target: &str, sanction: Sanction) -> Result<Message, (ErrorCode, String)> {
+        let by = self.require_moderator(client_id, room_id)?;
+        if target == by || self.owner_of(room_id).as_deref() == Some(target) {
+            return Err((ErrorCode::PermissionDenied, "Impossible de sanctionner cet utilisateur".to_string()));
+        }
+
+        let target_id = self.client_of(target);
+        let in_room = target_id.as_ref()
+            .is_some_and(|id| self.rooms.get(room_id).is_some_and(|room| room.users.contains_key(id)));
+        let until_of = |duration: Option<u64>| {
+            duration.map(|secs| Utc::now() + chrono::Duration::seconds(secs.min(i64::MAX as u64) as i64))
+        };
+
+        let (notification, remove_from_room) = match sanction {
+            Sanction::Kick => {
+                if !in_room {
+                    return Err((ErrorCode::UserNotFound, format!("{} n'est pas dans ce salon", target)));
+                }
+                (Message::UserKicked { room_id: room_id.to_string(), username: target.to_string(), by }, true)
+            }
+            Sanction::Ban(duration) => {
+                let until = until_of(duration);
+                self.bans.entry(room_id.to_string()).or_default().insert(target.to_string(), until);
+                (Message::UserBanned { room_id: room_id.to_string(), username: target.to_string(), by, until }, in_room)
+            }
+            Sanction::Mute(duration) => {
+                let until = until_of(duration);
+                self.mutes.entry(room_id.to_string()).or_default().insert(target.to_string(), until);
+                (Message::UserMuted { room_id: room_id.to_string(), username: target.to_string(), by, until }, false)
+            }
+        };
+
+        // Everyone in the room sees the sanction, and so does the target wherever they are
+        self.broadcast_to_room(room_id, ProtocolFrame::new(notification.clone(), None, 0), None);
+        if let Some(target_id) = &target_id {
+            if !in_room {
+                self.enqueue(target_id, Outgoing::Frame(ProtocolFrame::new(notification.clone(), Some(target_id.clone()), 0)));
+            }
+            if remove_from_room {
+                if let Some(mut room) = self.rooms.get_mut(room_id) {
+                    room.remove_user(target_id);
+                }
+                self.unsubscribe(target_id, room_id); // After the notification above
+                if let Some(mut client) = self.clients.get_mut(target_id) {
+                    client.current_room = None;
+                    client.session_state = SessionState::Authenticated(target.to_string());
+                }
+                self.admit_waiting(room_id);
+            }
+        }
+
+        Ok(notification)
+    }
+
+    /// Let a spectator of a room write in it, and return the notification sent to its members
+    fn promote(&self, client_id: &ClientId, room_id: &str, target: &str) -> Result<Message, (ErrorCode, String)> {
+        let by = self.require_moderator(client_id, room_id)?;
+
+        let target_id = self.client_of(target)
+            .ok_or((ErrorCode::UserNotFound, format!("{} n'est pas connecté", target)))?;
+        let mut room = self.rooms.get_mut(room_id)
+            .ok_or((ErrorCode::RoomNotFound, "Salon inexistant".to_string()))?;
+        if !room.promote(&target_id) {
+            return Err((ErrorCode::UserNotFound, format!("{} n'est pas spectateur de ce salon", target)));
+        }
+        let notification = Message::UserPromoted { room_id: room_id.to_string(), username: target.to_string(), by };
+        room.broadcast(ProtocolFrame::new(notification.clone(), None, 0), None);
+        Ok(notification)
+    }
+
+    /// Change a room's topic or pinned messages and return the notification sent to its members
+    fn update_room(&self, client_id: &ClientId, room_id: &str, update: RoomUpdate) -> Result<Message, (ErrorCode, String)> {
+        let by = self.require_moderator(client_id, room_id)?;
+
+        let mut room = self.rooms.get_mut(room_id)
+            .ok_or((ErrorCode::RoomNotFound, "Salon inexistant".to_string()))?;
+        let room_id = room_id.to_string();
+        let notification = match update {
+            RoomUpdate::Topic(topic) => {
+                let topic = room.set_topic(&topic).map_err(|e| (ErrorCode::InvalidFormat, e))?;
+                Message::TopicChanged { room_id: room_id.clone(), topic, by }
+            }
+            RoomUpdate::Pin(sequence) => {
+                let entry = room.pin(sequence).map_err(|e| (ErrorCode::InvalidState, e))?;
+                Message::MessagePinned { room_id: room_id.clone(), entry, by }
+            }
+            RoomUpdate::Unpin(sequence) => {
+                room.unpin(sequence).map_err(|e| (ErrorCode::InvalidState, e))?;
+                Message::MessageUnpinned { room_id: room_id.clone(), sequence, by }
+            }
+        };
+
+        room.broadcast(ProtocolFrame::new(notification.clone(), None, 0), None);
+        Ok(notification)
+    }
+
+    /// Edit or delete a message of the client's current room, in memory and on disk, and return the
+    /// notification sent to the room's members
+    fn change_message(&self, client_id: &ClientId, message_id: &str, change: MessageChange) -> Result<Message, (ErrorCode, String)> {
+        let (by, room_id, moderator) = {
+            let client = self.clients.get(client_id)
+                .ok_or((ErrorCode::InternalError, "Client non trouvé".to_string()))?;
+            let by = client.username.clone()
+                .ok_or((ErrorCode::InvalidState, "Client non authentifié".to_string()))?;
+            let room_id = client.current_room.clone()
+                .ok_or((ErrorCode::InvalidState, "Vous n'êtes dans aucun salon".to_string()))?;
+            let moderator = self.can_moderate(&client, &room_id);
+            (by, room_id, moderator)
+        };
+        if let MessageChange::Edit(content) = &change {
+            if content.trim().is_empty() {
+                return Err((ErrorCode::InvalidFormat, "Le nouveau contenu ne peut pas être vide".to_string()));
+            }
+            if active_restriction(&self.mutes, &room_id, &by).is_some() {
+                return Err((ErrorCode::Muted, format!("Vous ne pouvez pas parler dans #{}", room_id)));
+            }
+            if self.rooms.get(&room_id).is_some_and(|room| room.is_spectator(client_id)) {
+                return Err((ErrorCode::ReadOnly, format!("Vous êtes spectateur de #{}", room_id)));
+            }
+        }
+
+        let mut room = self.rooms.get_mut(&room_id)
+            .ok_or((ErrorCode::RoomNotFound, "Salon inexistant".to_string()))?;
+        let author = room.history.find(message_id)
+            .map(|entry| entry.from.clone())
+            .ok_or((ErrorCode::InvalidState, "Message introuvable dans l'historique du salon".to_string()))?;
+        if author != by && !moderator {
+            return Err((ErrorCode::PermissionDenied, "Seuls l'auteur et les administrateurs du salon peuvent changer ce message".to_string()));
+        }
+
+        let (notification, replacement) = match change {
+            MessageChange::Edit(content) => {
+                let entry = room.edit_message(message_id, &content, Utc::now()).map_err(|e| (ErrorCode::InvalidState, e))?;
+                let notification = Message::MessageEdited {
+                    room_id: room_id.clone(),
+                    message_id: entry.id.clone(),
+                    sequence: entry.sequence,
+                    new_content: entry.content.clone(),
+                    by,
+                    edited_at: entry.edited_at.unwrap_or_else(Utc::now),
+                };
+                (notification, Some(entry))
+            }
+            MessageChange::Delete => {
+                let entry = room.delete_message(message_id).map_err(|e| (ErrorCode::InvalidState, e))?;
+                let notification = Message::MessageDeleted { room_id: room_id.clone(), message_id: entry.id, sequence: entry.sequence, by };
+                (notification, None)
+            }
+        };
+
+        // Still holding the room, like record_message: the file never misses a message appended meanwhile
+        if let Some(store) = &self.history_store {
+            if let Err(e) = store.rewrite(&room_id, message_id, replacement.as_ref()) {
+                self.journal.warn(format!("⚠️ Could not update the history of room {}: {}", room_id, e));
+            }
+        }
+        room.broadcast(ProtocolFrame::new(notification.clone(), None, 0), None);
+        Ok(notification)
+    }
+
+    /// Pinned messages of a room, visible to its members and administrators
+    fn pins(&self, client_id: &ClientId, room_id: &str) -> Result<Vec<HistoryEntry>, (ErrorCode, String)> {
+        let client = self.clients.get(client_id)
+            .ok_or((ErrorCode::InternalError, "Client non trouvé".to_string()))?;
+        let room = self.rooms.get(room_id)
+            .ok_or((ErrorCode::RoomNotFound, "Salon inexistant".to_string()))?;
+        if !room.users.contains_key(client_id) && !self.can_moderate(&client, room_id) {
+            return Err((ErrorCode::PermissionDenied, "Vous n'êtes pas membre de ce salon".to_string()));
+        }
+        Ok(room.pinned.clone())
+    }
+
+    /// Search a room's history: the whole persisted history when there is a store, the memory buffer otherwise
+    fn search_history(&self, client_id: &ClientId, room_id: &str, query: &str, limit: usize) -> Result<Vec<HistoryEntry>, (ErrorCode, String)> {
+        let username = self.username_of(client_id)
+            .ok_or((ErrorCode::InvalidState, "Client non authentifié".to_string()))?;
+        if !self.rooms.contains_key(room_id) {
+            return Err((ErrorCode::RoomNotFound, "Salon inexistant".to_string()));
+        }
+        if !self.may_read_room(&username, room_id) {
+            return Err((ErrorCode::PermissionDenied, "Vous n'avez pas accès à ce salon".to_string()));
+        }
+
+        let query = SearchQuery::parse(query).map_err(|e| (ErrorCode::InvalidFormat, e))?;
+        let limit = limit.clamp(1, MAX_SEARCH_RESULTS);
+        match &self.history_store {
+            Some(store) => store.search(room_id, &query, limit)
+                .map_err(|e| (ErrorCode::InternalError, format!("Lecture de l'historique impossible: {}", e))),
+            None => self.rooms.get(room_id)
+                .map(|room| room.history.search(&query, limit))
+                .ok_or((ErrorCode::RoomNotFound, "Salon inexistant".to_string())),
+        }
+    }
+
+    fn delete_room(&self, client_id: &ClientId, room_id: &str) -> Result<(), String> {
+        let username = self.username_of(client_id).ok_or("Client non authentifié")?;
+
+        if !self.rooms.contains_key(room_id) {
+            return Err("Salon inexistant".to_string());
+        }
+        match self.owner_of(room_id) {
+            Some(owner) if owner == username => {}
+            Some(_) => return Err("Seul l'administrateur du salon peut le supprimer".to_string()),
+            None => return Err("Les salons par défaut ne peuvent pas être supprimés".to_string()),
+        }
+
+        self.close_room(room_id);
+        Ok(())
+    }
+
+    /// Remove a room, sending its members back to the lobby
+    fn close_room(&self, room_id: &str) {
+        let notification = ProtocolFrame::new(Message::RoomDeleted { room_id: room_id.to_string() }, None, 0);
+        self.broadcast_to_room(room_id, notification, None);
+
+        self.metrics.forget_room(room_id);
+        if let Some((_, room)) = self.rooms.remove(room_id) {
+            // Those waiting for it stop waiting
+            for waiting_id in &room.waiting {
+                if let Some(mut client) = self.clients.get_mut(waiting_id) {
+                    client.waiting_for = None;
+                }
+            }
+            self.send_to_clients(&room.waiting, Message::RoomDeleted { room_id: room_id.to_string() });
+            for member_id in room.users.keys() {
+                self.enqueue(member_id, Outgoing::Unsubscribe { after: room.position() });
+                if let Some(mut member) = self.clients.get_mut(member_id) {
+                    if member.current_room.as_deref() == Some(room_id) {
+                        member.current_room = None;
+                        if let Some(username) = member.username.clone() {
+                            member.session_state = SessionState::Authenticated(username);
+                        }
+                    }
+                }
+            }
+        }
+        self.room_owners.remove(room_id);
+        self.empty_since.remove(room_id);
+        self.bans.remove(room_id);
+        self.mutes.remove(room_id);
+        self.acked.remove(room_id);
+        self.read.remove(room_id);
+
+        if let Some(store) = &self.history_store {
+            if let Err(e) = store.remove(room_id) {
+                self.journal.warn(format!("⚠️ Could not remove history of room {}: {}", room_id, e));
+            }
+        }
+    }
+
+    /// Remove client-created rooms that stayed empty longer than `grace`
+    fn collect_empty_rooms(&self, grace: Duration) -> Vec<RoomId> {
+        let now = Instant::now();
+        let owned: Vec<RoomId> = self.room_owners.iter().map(|entry| entry.key().clone()).collect();
+        for room_id in owned {
+            if self.rooms.get(&room_id).is_some_and(|room| room.user_count() > 0) {
+                self.empty_since.remove(&room_id);
+            } else {
+                self.empty_since.entry(room_id).or_insert(now);
+            }
+        }
+
+        let expired: Vec<RoomId> = self.empty_since.iter()
+            .filter(|since| now.duration_since(*since.value()) >= grace)
+            .map(|since| since.key().clone())
+            .collect();
+        for room_id in &expired {
+            self.close_room(room_id);
+        }
+        expired
+    }
+
+    /// Account name of a connection, once authenticated
+    fn username_of(&self, client_id: &ClientId) -> Option<String> {
+        self.clients.get(client_id).and_then(|client| client.username.clone())
+    }
+
+    /// Connection of an online user
+    fn client_of(&self, username: &str) -> Option<ClientId> {
+        self.username_to_client.get(username).map(|client_id| client_id.value().clone())
+    }
+
+    /// Creator of a client-created room
+    fn owner_of(&self, room_id: &str) -> Option<String> {
+        self.room_owners.get(room_id).map(|owner| owner.value().clone())
+    }
+
+    // Helper function to send a message to a specific client
+    async fn send_message_to_client(&self, client_id: &ClientId, message: Message) {
+        let frame = ProtocolFrame::new(message, Some(client_id.clone()), 0); // Sequence 0 for server messages
+        if self.enqueue(client_id, Outgoing::Frame(frame)) == Push::Closed {
+            self.journal.warn(format!("Warning: Could not queue a message for client {}. Perhaps disconnected.", client_id));
+        }
+    }
+
+    fn send_to_clients<'a>(&self, client_ids: impl IntoIterator<Item = &'a ClientId>, message: Message) {
+        for client_id in client_ids {
+            self.enqueue(client_id, Outgoing::Frame(ProtocolFrame::new(message.clone(), Some(client_id.clone()), 0)));
+        }
+    }
+
+    /// Register a file offer; returns the clients it must be relayed to
+    fn offer_file(
+        &self,
+        client_id: &ClientId,
+        transfer_id: &str,
+        target: &FileTarget,
+        filename: &str,
+        size: u64,
+    ) -> Result<HashSet<ClientId>, (ErrorCode, String)> {
+        let (username, current_room) = {
+            let client = self.clients.get(client_id)
+                .ok_or((ErrorCode::InternalError, "Client non trouvé".to_string()))?;
+            let username = client.username.clone()
+                .ok_or((ErrorCode::InvalidState, "Client non authentifié".to_string()))?;
+            (username, client.current_room.clone())
+        };
+
+        if size > self.config.max_file_size {
+            return Err((
+                ErrorCode::MessageTooLarge,
+                format!("Fichier trop volumineux: {} octets (max: {})", size, self.config.max_file_size),
+            ));
+        }
+        if sanitize_filename(filename).is_none() {
+            return Err((ErrorCode::InvalidFormat, "Nom de fichier invalide".to_string()));
+        }
+        if self.transfers.contains_key(transfer_id) {
+            return Err((ErrorCode::InvalidState, "Identifiant de transfert déjà utilisé".to_string()));
+        }
+
+        let recipients: HashSet<ClientId> = match target {
+            FileTarget::User(target_user) => {
+                let target_id = self.client_of(target_user)
+                    .ok_or((ErrorCode::UserNotFound, format!("{} n'est pas connecté", target_user)))?;
+                if &target_id == client_id {
+                    return Err((ErrorCode::InvalidState, "Impossible de s'envoyer un fichier".to_string()));
+                }
+                HashSet::from([target_id])
+            }
+            FileTarget::Room(room_id) => {
+                if current_room.as_ref() != Some(room_id) {
+                    return Err((ErrorCode::InvalidState, format!("Vous n'êtes pas dans le salon {}", room_id)));
+                }
+                if active_restriction(&self.mutes, room_id, &username).is_some() {
+                    return Err((ErrorCode::Muted, format!("Vous êtes réduit au silence dans le salon {}", room_id)));
+                }
+                let room = self.rooms.get(room_id)
+                    .ok_or((ErrorCode::RoomNotFound, "Salon inexistant".to_string()))?;
+                if room.is_spectator(client_id) {
+                    return Err((ErrorCode::ReadOnly, format!("Vous êtes spectateur du salon {}", room_id)));
+                }
+                room.users.keys().filter(|id| *id != client_id).cloned().collect()
+            }
+        };
+        if recipients.is_empty() {
+            return Err((ErrorCode::InvalidState, "Personne d'autre dans le salon".to_string()));
+        }
+
+        match self.transfers.entry(transfer_id.to_string()) {
+            Entry::Occupied(_) => Err((ErrorCode::InvalidState, "Identifiant de transfert déjà utilisé".to_string())),
+            Entry::Vacant(vacant) => {
+                vacant.insert(Transfer {
+                    sender: client_id.clone(),
+                    offered_to: recipients.clone(),
+                    accepted: HashSet::new(),
+                    size,
+                    received: 0,
+                    next_seq: 0,
+                });
+                Ok(recipients)
+            }
+        }
+    }
+
+    /// Accept an offered file before its first chunk; returns the sender to notify
+    fn accept_file(&self, client_id: &ClientId, transfer_id: &str) -> Result<ClientId, (ErrorCode, String)> {
+        let mut transfer = self.transfers.get_mut(transfer_id)
+            .ok_or((ErrorCode::InvalidState, "Transfert inconnu ou terminé".to_string()))?;
+        if !transfer.offered_to.contains(client_id) {
+            return Err((ErrorCode::PermissionDenied, "Ce fichier ne vous a pas été proposé".to_string()));
+        }
+        if transfer.next_seq > 0 {
+            return Err((ErrorCode::InvalidState, "Le transfert a déjà commencé".to_string()));
+        }
+        transfer.accepted.insert(client_id.clone());
+        Ok(transfer.sender.clone())
+    }
+
+    /// Check a chunk against the transfer (order, announced size); returns the recipients to relay it to
+    fn relay_chunk(
+        &self,
+        client_id: &ClientId,
+        transfer_id: &str,
+        seq: u64,
+        data: &str,
+    ) -> Result<HashSet<ClientId>, (ErrorCode, String)> {
+        let mut transfer = self.transfers.get_mut(transfer_id)
+            .ok_or((ErrorCode::InvalidState, "Transfert inconnu ou terminé".to_string()))?;
+        if &transfer.sender != client_id {
+            return Err((ErrorCode::PermissionDenied, "Ce transfert ne vous appartient pas".to_string()));
+        }
+        if transfer.accepted.is_empty() {
+            return Err((ErrorCode::InvalidState, "Aucun destinataire n'a accepté le fichier".to_string()));
+        }
+        if seq != transfer.next_seq {
+            return Err((ErrorCode::InvalidFormat, format!("Morceau {} reçu, {} attendu", seq, transfer.next_seq)));
+        }
+        let length = decode_chunk(data).map_err(|e| (ErrorCode::InvalidFormat, e))?.len() as u64;
+        if transfer.received + length > transfer.size {
+            return Err((ErrorCode::MessageTooLarge, format!("Le fichier dépasse les {} octets annoncés", transfer.size)));
+        }
+
+        transfer.received += length;
+        transfer.next_seq += 1;
+        Ok(transfer.accepted.clone())
+    }
+
+    /// Close a fully relayed transfer; returns the recipients to notify
+    fn complete_file(&self, client_id: &ClientId, transfer_id: &str) -> Result<HashSet<ClientId>, (ErrorCode, String)> {
+        {
+            let transfer = self.transfers.get(transfer_id)
+                .ok_or((ErrorCode::InvalidState, "Transfert inconnu ou terminé".to_string()))?;
+            if &transfer.sender != client_id {
+                return Err((ErrorCode::PermissionDenied, "Ce transfert ne vous appartient pas".to_string()));
+            }
+            if transfer.received != transfer.size {
+                return Err((
+                    ErrorCode::InvalidFormat,
+                    format!("Fichier incomplet: {} / {} octets", transfer.received, transfer.size),
+                ));
+            }
+        }
+        Ok(self.transfers.remove(transfer_id).map(|(_, transfer)| transfer.accepted).unwrap_or_default())
+    }
+
+    /// Drop a transfer owned by `sender` and tell its recipients
+    fn abort_transfer(&self, transfer_id: &str, sender: &ClientId, reason: &str) {
+        if let Some((_, transfer)) = self.transfers.remove_if(transfer_id, |_, transfer| &transfer.sender == sender) {
+            let notice = Message::FileAborted { transfer_id: transfer_id.to_string(), reason: reason.to_string() };
+            self.send_to_clients(&transfer.offered_to, notice);
+        }
+    }
+
+    /// Hand the frame to the room's channel; each member's send task copies it out, without holding the room
+    fn broadcast_to_room(&self, room_id: &str, message_frame: ProtocolFrame, exclude_client: Option<&ClientId>) {
+        if let Some(mut room) = self.rooms.get_mut(room_id) {
+            room.broadcast(message_frame, exclude_client);
+        }
+    }
+
+    /// The client's send task stops forwarding the room once it has sent everything broadcast so far
+    fn unsubscribe(&self, client_id: &ClientId, room_id: &str) {
+        let Some(after) = self.rooms.get(room_id).map(|room| room.position()) else {
+            return;
+        };
+        self.enqueue(client_id, Outgoing::Unsubscribe { after });
+    }
+
+    /// Whether a user may read what is said in a room, even without being in it
+    fn may_read_room(&self, username: &str, room_id: &str) -> bool {
+        let Some(room) = self.rooms.get(room_id) else {
+            return false;
+        };
+        if self.owner_of(room_id).as_deref() == Some(username) {
+            return true;
+        }
+        if active_restriction(&self.bans, room_id, username).is_some() {
+            return false;
+        }
+        match &room.visibility {
+            RoomVisibility::Public => true,
+            RoomVisibility::InviteOnly { invited } => invited.contains(username),
+            // Knowing the password is only proven by being in the room
+            RoomVisibility::Private { .. } => room.users.values().any(|member| member == username),
+        }
+    }
+
+    /// Send a Mention to every user named in a room message, or leave it in their mailbox if offline;
+    /// returns the users notified
+    fn notify_mentions(&self, room_id: &str, entry: &HistoryEntry) -> Vec<String> {
+        let mut notified = Vec::new();
+        for username in extract_mentions(&entry.content) {
+            if username == entry.from || !self.users.contains(&username) || !self.may_read_room(&username, room_id) {
+                continue;
+            }
+            let profile = self.profiles.get(&username);
+            if !profile.notifications.mentions || profile.blocks(&entry.from) {
+                continue;
+            }
+            match self.client_of(&username) {
+                Some(target_id) => {
+                    let mention = Message::Mention {
+                        room_id: room_id.to_string(),
+                        from: entry.from.clone(),
+                        content: entry.content.clone(),
+                        timestamp: entry.timestamp,
+                        sequence: entry.sequence,
+                    };
+                    self.send_to_clients([&target_id], mention);
+                }
+                None => {
+                    let pending = PendingMessage {
+                        from: entry.from.clone(),
+                        content: entry.content.clone(),
+                        timestamp: entry.timestamp,
+                        room_id: Some(room_id.to_string()),
+                        sequence: entry.sequence,
+                    };
+                    if let Err(e) = lock(&self.mailboxes).deposit(&username, pending) {
+                        self.journal.warn(format!("⚠️ Could not queue mention for {}: {}", username, e));
+                        continue;
+                    }
+                }
+            }
+            notified.push(username);
+        }
+        notified
+    }
+
+    /// Deliver a private message; returns false when the target is offline and the message was queued
+    fn send_private_message(&self, from_username: &str, to_username: &str, content: &str) -> Result<bool, String> {
+        let Some(to_client_id) = self.client_of(to_username) else {
+            if !self.users.contains(to_username) {
+                return Err("Utilisateur destinataire non trouvé".to_string());
+            }
+            let pending = PendingMessage {
+                from: from_username.to_string(),
+                content: content.to_string(),
+                timestamp: Utc::now(),
+                room_id: None,
+                sequence: 0,
+            };
+            lock(&self.mailboxes).deposit(to_username, pending)?;
+            return Ok(false);
+        };
+
+        let message = Message::PrivateMessageReceived {
+            from: from_username.to_string(),
+            content: content.to_string(),
+            timestamp: Utc::now(),
+        };
+        let frame = ProtocolFrame::new(message, Some(to_client_id.clone()), 0); // Sequence 0 for server messages
+        match self.enqueue(&to_client_id, Outgoing::Frame(frame)) {
+            Push::Closed => Err("Unable to send message: recipient disconnected".to_string()),
+            _ => Ok(true),
+        }
+    }
+}
+
+/// Lock a mutex even if a task panicked while holding it: the stores stay consistent on disk
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Current restriction of a user in a room: `Some(until)` while it applies (`until` None = permanent)
+fn active_restriction(restrictions: &Restrictions, room_id: &str, username: &str) -> Option<Option<DateTime<Utc>>> {
+    let until = *restrictions.get(room_id)?.get(username)?;
+    match until {
+        Some(end) if end <= Utc::now() => None,
+        _ => Some(until),
+    }
+}
+
+/// Write a frame in the protocol version the client speaks (current version until it is known)
+async fn write_for_client<W: AsyncWrite + Unpin>(writer: &mut W, frame: &ProtocolFrame, version: &AtomicU8) -> std::io::Result<()> {
+    match version.load(Ordering::Relaxed) {
+        0 => write_frame(writer, frame).await,
+        version if version == frame.version => write_frame(writer, frame).await,
+        version => match frame.clone().for_version(version) {
+            Some(frame) => write_frame(writer, &frame).await,
+            None => Ok(()), // Nothing this client would understand
+        },
+    }
+}
+
+/// Seconds `sent_at` is ahead of the server clock (behind if negative), when beyond max_clock_skew
+fn clock_offset(config: &ServerConfig, sent_at: DateTime<Utc>, now: DateTime<Utc>) -> Option<i64> {
+    let tolerance = config.max_clock_skew()?;
+    let offset = sent_at - now;
+    (offset.abs().to_std().unwrap_or_default() > tolerance).then(|| offset.num_seconds())
+}
+
+/// How a recorded message of `room_id` reaches the members
+fn room_message(room_id: &str, entry: &HistoryEntry) -> Message {
+    Message::RoomMessage {
+        from: entry.from.clone(),
+        content: entry.content.clone(),
+        timestamp: entry.timestamp,
+        room_id: room_id.to_string(),
+        sequence: entry.sequence,
+        message_id: entry.id.clone(),
+        kind: entry.kind.clone(),
+        received_at: entry.received_at,
+        parent_message_id: entry.parent_id.clone(),
+    }
+}
+
+/// Main server handler
+struct ChatServer {
+    state: Arc<ServerState>,
+    config: Arc<ServerConfig>,
+}
+
+impl ChatServer {
+    fn new(
+        config: ServerConfig,
+        history_store: Option<HistoryStore>,
+        users: UserStore,
+        profiles: ProfileStore,
+        mailboxes: MailboxStore,
+        journal: Journal,
+    ) -> Self {
+        let config = Arc::new(config);
+        let state = ServerState::new(Arc::clone(&config), history_store, users, profiles, mailboxes, journal);
+        Self {
+            state: Arc::new(state),
+            config,
+        }
+    }
+
+    /// Warn every client, give them `grace` to leave (a second Ctrl+C cuts it short), then close the remaining connections
+    async fn shutdown(&self, reason: &str, grace: Duration) {
+        let connected: Vec<ClientId> = self.state.clients.iter().map(|client| client.key().clone()).collect();
+        let notice = Message::ServerShutdown { reason: reason.to_string(), grace_seconds: grace.as_secs() };
+        self.state.send_to_clients(&connected, notice);
+        self.state.journal.info(format!("🛑 Shutting down: {} client(s) warned, {}s grace period", connected.len(), grace.as_secs()));
+
+        let everybody_left = async {
+            while !self.state.clients.is_empty() {
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+        };
+        tokio::select! {
+            _ = tokio::time::sleep(grace) => {}
+            _ = tokio::signal::ctrl_c() => self.state.journal.info("⏩ Grace period skipped"),
+            _ = everybody_left => {}
+        }
+
+        for client in self.state.clients.iter() {
+            client.disconnect.notify_one();
+        }
+    }
+
+    /// Ping the client periodically; signal `dead` once it has missed too many Pongs
+    async fn heartbeat(state: Arc<ServerState>, client_id: ClientId, config: Arc<ServerConfig>, dead: Arc<Notify>) {
+        let mut ticker = tokio::time::interval(config.heartbeat_interval());
+        ticker.tick().await; // The first tick completes immediately
+
+        loop {
+            ticker.tick().await;
+            {
+                let Some(mut client) = state.clients.get_mut(&client_id) else {
+                    break;
+                };
+                if client.missed_pongs >= config.max_missed_pongs {
+                    state.journal.info(format!("💀 Client {} missed {} pings, disconnecting.", client_id, client.missed_pongs));
+                    dead.notify_one();
+                    break;
+                }
+                client.missed_pongs += 1;
+            }
+            state.send_message_to_client(&client_id, Message::Ping).await;
+        }
+    }
+
+    /// Write the frames of one client: direct ones, and the broadcasts of the room it is subscribed to
+    async fn send_loop<W: AsyncWrite + Unpin>(
+        state: Arc<ServerState>,
+        mut writer: W,
+        outgoing: Arc<SendQueue<Outgoing>>,
+        client_id: ClientId,
+        version: Arc<AtomicU8>,
+    ) {
+        #[allow(clippy::large_enum_variant)] // Lives for one turn of the loop, boxing would cost an allocation per frame
+        enum Next {
+            Outgoing(Option<Outgoing>),
+            Room(Result<RoomEvent, RecvError>),
+        }
+
+        let mut room: Option<broadcast::Receiver<RoomEvent>> = None;
+        loop {
+            // Direct frames and subscription changes first: an Unsubscribe is always seen before later broadcasts
+            let next = tokio::select! {
+                biased;
+                outgoing = outgoing.recv() => Next::Outgoing(outgoing),
+                event = async {
+                    match room.as_mut() {
+                        Some(receiver) => receiver.recv().await,
+                        None => std::future::pending().await,
+                    }
+                } => Next::Room(event),
+            };
+
+            let written = match next {
+                Next::Outgoing(None) => break,
+                Next::Outgoing(Some(Outgoing::Frame(frame))) => write_for_client(&mut writer, &frame, &version).await,
+                Next::Outgoing(Some(Outgoing::Subscribe(receiver))) => {
+                    room = Some(receiver);
+                    Ok(())
+                }
+                Next::Outgoing(Some(Outgoing::Unsubscribe { after })) => {
+                    // Everything up to `after` is already in the channel: send it, then stop listening
+                    let mut written = Ok(());
+                    if let Some(mut receiver) = room.take() {
+                        loop {
+                            match receiver.try_recv() {
+                                Ok(event) if event.position > after => break,
+                                Ok(event) if event.exclude.as_ref() == Some(&client_id) => {}
+                                Ok(event) => {
+                                    written = write_for_client(&mut writer, &event.frame, &version).await;
+                                    if written.is_err() {
+                                        break;
+                                    }
+                                }
+                                Err(TryRecvError::Lagged(_)) => {}
+                                Err(_) => break,
+                            }
+                        }
+                    }
+                    written
+                }
+                Next::Room(Ok(event)) if event.exclude.as_ref() == Some(&client_id) => Ok(()),
+                Next::Room(Ok(event)) => write_for_client(&mut writer, &event.frame, &version).await,
+                Next::Room(Err(RecvError::Lagged(skipped))) => {
+                    state.journal.warn(format!("⚠️ Client {} is too slow, {} room message(s) skipped.", client_id, skipped));
+                    Ok(())
+                }
+                Next::Room(Err(RecvError::Closed)) => {
+                    room = None;
+                    Ok(())
+                }
+            };
+            // Check if writing fails (e.g., client disconnected)
+            if let Err(e) = written {
+                state.journal.log(ChatEvent::error(Some(&client_id), format!("Write error: {}. Connection might be closed.", e)));
+                break;
+            }
+        }
+        state.journal.info(format!("⚙️ Send task for client {} finished.", client_id));
+    }
+
+    async fn handle_client<S: Transport + 'static>(&self, stream: S, client_id: ClientId) {
+        // Add the client to the server state
+        let queue = self.state.add_client(client_id.clone());
+        let (dead, protocol_version) = self.state.clients.get(&client_id)
+            .map(|client| (Arc::clone(&client.disconnect), Arc::clone(&client.protocol_version)))
+            .unwrap_or_default();
+
+        // Dedicated halves: the send task owns the writer, this task keeps the reader
+        let (mut read_stream, write_stream) = tokio::io::split(stream);
+
+        // Task to send messages to the client
+        let mut send_task = tokio::spawn(Self::send_loop(Arc::clone(&self.state), write_stream, queue, client_id.clone(), Arc::clone(&protocol_version)));
+
+        // Heartbeat task: wakes the reception loop below if the client stops answering
+        let heartbeat_task = tokio::spawn(Self::heartbeat(
+            Arc::clone(&self.state),
+            client_id.clone(),
+            Arc::clone(&self.config),
+            Arc::clone(&dead),
+        ));
+
+        // Main message reception loop, unless the connection was declared dead (heartbeat, rate limiting)
+        loop {
+            let read = tokio::select! {
+                biased; // A connection declared dead reads nothing more, even if frames are waiting
+                _ = dead.notified() => break,
+                read = read_frame_limited(&mut read_stream, self.config.max_message_size) => read,
+            };
+            match read {
+                Ok(Some(mut frame)) => {
+                    // A client that does not open with Hello speaks the version of its first frame (v1 clients)
+                    if !matches!(frame.message, Message::Hello { .. }) {
+                        let _ = protocol_version.compare_exchange(0, frame.version, Ordering::Relaxed, Ordering::Relaxed);
+                    }
+                    if let Some(offset_secs) = self.state.check_clock(&client_id, &mut frame) {
+                        self.state.send_message_to_client(&client_id, Message::ClockSkew { offset_secs }).await;
+                    }
+                    if let Err(e) = self.process_message(frame, &client_id).await {
+                        self.state.journal.log(ChatEvent::error(Some(&client_id), format!("Error processing message: {}", e)));
+                        // Send an internal error to the client
+                        let error_msg = Message::Error {
+                            code: ErrorCode::InternalError,
+                            message: format!("Processing error: {}", e),
+                        };
+                        self.state.send_message_to_client(&client_id, error_msg).await;
+                    }
+                }
+                Ok(None) => break, // Logged when the client is removed
+                Err(FrameError::TooLarge { length, max }) => {
+                    self.state.journal.log(ChatEvent::error(Some(&client_id), format!("Message too large: {} bytes. Disconnecting.", length)));
+                    // Try to send an error to the client before closing the connection
+                    let error_msg = Message::Error {
+                        code: ErrorCode::MessageTooLarge,
+                        message: format!("Message too large ({} bytes), max is {} bytes.", length, max),
+                    };
+                    self.state.send_message_to_client(&client_id, error_msg).await;
+                    break; // The stream is out of sync: disconnect
+                }
+                Err(FrameError::Invalid(e)) => {
+                    self.state.journal.log(ChatEvent::error(Some(&client_id), format!("Deserialization error: {}. Disconnecting.", e)));
+                    let error_msg = Message::Error {
+                        code: ErrorCode::InvalidFormat,
+                        message: format!("Invalid message format: {}", e),
+                    };
+                    self.state.send_message_to_client(&client_id, error_msg).await;
+                    break;
+                }
+                Err(FrameError::UnsupportedVersion(version)) => {
+                    self.state.journal.log(ChatEvent::error(Some(&client_id), format!("Unsupported protocol version {}. Disconnecting.", version)));
+                    let error_msg = Message::VersionMismatch {
+                        min_version: MIN_PROTOCOL_VERSION,
+                        max_version: PROTOCOL_VERSION,
+                        message: format!("Protocol version {} is not supported.", version),
+                    };
+                    self.state.send_message_to_client(&client_id, error_msg).await;
+                    break;
+                }
+                Err(FrameError::Io(e)) => {
+                    // This error usually means the connection was lost
+                    self.state.journal.log(ChatEvent::error(Some(&client_id), format!("Read error: {}", e)));
+                    break;
+                }
+            }
+        }
+
+        // Cleanup on disconnection
+        heartbeat_task.abort();
+        self.state.remove_client(&client_id);
+        // The "Client disconnected" message is now handled within remove_client for notifications
+        // Removing the client closed its queue: let the send task flush pending frames (e.g. the error above),
+        // but not wait forever on a client that stopped reading
+        if tokio::time::timeout(SEND_DRAIN_TIMEOUT, &mut send_task).await.is_err() {
+            send_task.abort();
+            self.state.journal.warn(format!("⚠️ Pending frames for client {} dropped.", client_id));
+        }
+    }
+
+    async fn process_message(&self, frame: ProtocolFrame, client_id: &ClientId) -> Result<(), String> {
+        // Validate the frame (version, size)
+        frame.validate()?;
+
+        // Rate limiting: the message is dropped, which is not a processing error
+        if let Some(class) = rate_class(&frame.message) {
+            let state = &self.state;
+            let decision = state.check_rate(client_id, class);
+            if decision != RateDecision::Allowed {
+                let message = match class {
+                    RateClass::Chat => "Too many messages, slow down.".to_string(),
+                    RateClass::Control => "Too many requests, slow down.".to_string(),
+                };
+                state.send_message_to_client(client_id, Message::Error { code: ErrorCode::RateLimitExceeded, message }).await;
+                if decision == RateDecision::Disconnect {
+                    self.state.journal.info(format!("🚫 Client {} keeps exceeding rate limits, disconnecting.", client_id));
+                    if let Some(client) = state.clients.get(client_id) {
+                        client.disconnect.notify_one();
+                    }
+                }
+                return Ok(());
+            }
+        }
+
+        // Copy the client's session state for validation
+        let session_state = self.state.clients.get(client_id)
+            .map(|client| client.session_state.clone())
+            .ok_or("Client not found in server state (internal error)")?;
+
+        // Precondition checks for received message state
+        match &frame.message {
+            Message::Register { .. } | Message::Login { .. } => {
+                // Register/Login are allowed only if the client is not already authenticated
+                if !matches!(session_state, SessionState::Connected) {
+                    let error_msg = format!("Already connected or authenticated. Current state: {:?}", session_state);
+                    let response = Message::Error { code: ErrorCode::InvalidState, message: error_msg.clone() };
+                    self.state.send_message_to_client(client_id, response).await;
+                    return Err(error_msg);
+                }
+            },
+            _ => {
+                // All other messages require authentication (except Ping which is handled below)
+                if frame.message.requires_auth() && !matches!(session_state, SessionState::Authenticated(_) | SessionState::InRoom(_, _)) {
+                    let error_msg = format!("Authentication required for this action. Current state: {:?}", session_state);
+                    let response = Message::Error { code: ErrorCode::InvalidState, message: error_msg.clone() };
+                    self.state.send_message_to_client(client_id, response).await;
+                    return Err(error_msg);
+                }
+
+                // Check if the message requires being in a room
+                if frame.message.requires_room() && !matches!(session_state, SessionState::InRoom(_, _)) {
+                    let error_msg = format!("Requires being in a room. Current state: {:?}", session_state);
+                    let response = Message::Error { code: ErrorCode::InvalidState, message: error_msg.clone() };
+                    self.state.send_message_to_client(client_id, response).await;
+                    return Err(error_msg);
+                }
+            }
+        }
+
+        // Message processing
+        match frame.message {
+            Message::Hello { min_version, max_version } => {
+                self.handle_hello(client_id, min_version, max_version).await
+            }
+            Message::Register { username, password } => {
+                self.handle_connect(client_id, username, password, true).await
+            }
+            Message::Login { username, password } => {
+                self.handle_connect(client_id, username, password, false).await
+            }
+            Message::JoinRoom { room_id, password, wait, spectator } => {
+                self.handle_join_room(client_id, room_id, password, wait, spectator).await
+            }
+            Message::LeaveRoom => {
+                self.handle_leave_room(client_id).await
+            }
+            Message::SendMessage { content, kind, parent_message_id } => {
+                self.handle_send_message(client_id, content, kind, parent_message_id, frame.timestamp).await
+            }
+            Message::PrivateMessage { target_user, content } => {
+                self.handle_private_message(client_id, target_user, content).await
+            }
+            Message::ListRooms => {
+                self.handle_list_rooms(client_id).await
+            }
+            Message::ListUsers => {
+                self.handle_list_users(client_id).await
+            }
+            Message::GetHistory { count } => {
+                self.handle_get_history(client_id, count).await
+            }
+            Message::CreateRoom { room_id, name, password, invite_only, max_users } => {
+                self.handle_create_room(client_id, room_id, name, password, invite_only, max_users).await
+            }
+            Message::InviteUser { room_id, username } => {
+                self.handle_invite_user(client_id, room_id, username).await
+            }
+            Message::Typing { room_id } => {
+                self.handle_typing(client_id, room_id).await
+            }
+            Message::PresenceUpdate { status } => {
+                self.handle_presence_update(client_id, status).await
+            }
+            Message::KickUser { room_id, username } => {
+                self.handle_moderation(client_id, room_id, username, Sanction::Kick).await
+            }
+            Message::BanUser { room_id, username, duration } => {
+                self.handle_moderation(client_id, room_id, username, Sanction::Ban(duration)).await
+            }
+            Message::MuteUser { room_id, username, duration } => {
+                self.handle_moderation(client_id, room_id, username, Sanction::Mute(duration)).await
+            }
+            Message::PromoteUser { room_id, username } => {
+                self.handle_promote(client_id, room_id, username).await
+            }
+            Message::SetTopic { room_id, topic } => {
+                self.handle_room_update(client_id, room_id, RoomUpdate::Topic(topic)).await
+            }
+            Message::PinMessage { room_id, sequence } => {
+                self.handle_room_update(client_id, room_id, RoomUpdate::Pin(sequence)).await
+            }
+            Message::UnpinMessage { room_id, sequence } => {
+                self.handle_room_update(client_id, room_id, RoomUpdate::Unpin(sequence)).await
+            }
+            Message::EditMessage { message_id, new_content } => {
+                self.handle_message_change(client_id, message_id, MessageChange::Edit(new_content)).await
+            }
+            Message::DeleteMessage { message_id } => {
+                self.handle_message_change(client_id, message_id, MessageChange::Delete).await
+            }
+            Message::GetPins { room_id } => {
+                self.handle_get_pins(client_id, room_id).await
+            }
+            Message::GetThread { message_id } => {
+                self.handle_get_thread(client_id, message_id).await
+            }
+            Message::GetProfile { username } => {
+                let profile = self.state.profile_of(client_id, username);
+                self.handle_profile(client_id, profile).await
+            }
+            Message::UpdateProfile { profile } => {
+                let profile = self.state.update_profile(client_id, profile);
+                self.handle_profile(client_id, profile).await
+            }
+            Message::BlockUser { username } => {
+                let profile = self.state.set_blocked(client_id, &username, true);
+                self.handle_profile(client_id, profile).await
+            }
+            Message::UnblockUser { username } => {
+                let profile = self.state.set_blocked(client_id, &username, false);
+                self.handle_profile(client_id, profile).await
+            }
+            Message::GetStats => {
+                self.handle_get_stats(client_id).await
+            }
+            Message::SearchHistory { room_id, query, limit } => {
+                self.handle_search_history(client_id, room_id, query, limit).await
+            }
+            Message::DeleteRoom { room_id } => {
+                self.handle_delete_room(client_id, room_id).await
+            }
+            Message::FileOffer { transfer_id, target, filename, size, .. } => {
+                self.handle_file_offer(client_id, transfer_id, target, filename, size).await
+            }
+            Message::FileAccept { transfer_id, .. } => {
+                self.handle_file_accept(client_id, transfer_id).await
+            }
+            Message::FileChunk { transfer_id, seq, data } => {
+                self.handle_file_chunk(client_id, transfer_id, seq, data).await
+            }
+            Message::FileComplete { transfer_id, sha256 } => {
+                self.handle_file_complete(client_id, transfer_id, sha256).await
+            }
+            Message::Disconnect => {
+                let username = self.state.disconnect(client_id);
+                self.state.journal.info(format!("👋 Client {} ({}) sent DISCONNECT.", client_id, username.as_deref().unwrap_or("anonymous")));
+                Ok(())
+            }
+            Message::Ping => {
+                self.handle_ping(client_id).await
+            }
+            Message::MessageAck { room_id, sequence } => {
+                self.state.acknowledge(client_id, &room_id, sequence)
+            }
+            Message::MarkRead { room_id, sequence } => {
+                self.state.mark_read(client_id, &room_id, sequence)
+            }
+            Message::Pong => {
+                // Answer to a server heartbeat
+                if let Some(mut client) = self.state.clients.get_mut(client_id) {
+                    client.missed_pongs = 0;
+                }
+                Ok(())
+            }
+            // Server-to-client messages should never be received here;
+            // if so, it's a client protocol error.
+            _ => {
+                let error_msg = format!("Unexpected message type received from client: {:?}", frame.message);
+                let response = Message::Error { code: ErrorCode::InvalidFormat, message: error_msg.clone() };
+                self.state.send_message_to_client(client_id, response).await;
+                Err(error_msg)
+            }
+        }
+    }
+
+    async fn handle_connect(&self, client_id: &ClientId, username: String, password: String, register: bool) -> Result<(), String> {
+        let state = &self.state;
+
+        // Argon2 is slow on purpose: hash on a blocking thread rather than stall this worker
+        let authenticated = {
+            let (state, client_id, username) = (Arc::clone(state), client_id.clone(), username.clone());
+            tokio::task::spawn_blocking(move || state.authenticate_client(&client_id, username, &password, register))
+                .await
+                .map_err(|e| format!("Authentication task failed: {}", e))?
+        };
+        match authenticated {
+            Ok(()) => {
+                let response = Message::ConnectAck {
+                    client_id: client_id.clone(),
+                    message: format!("Bienvenue, {} !", username),
+                };
+                state.send_message_to_client(client_id, response).await;
+                // The profile loaded at login: display settings, and the room the client joins by default
+                if let Ok(profile) = state.profile_of(client_id, None) {
+                    state.send_message_to_client(client_id, profile).await;
+                }
+                state.journal.log(ChatEvent::Authenticated { client_id: client_id.clone(), username: username.clone(), registered: register });
+
+                // Hand over the private messages and mentions received while offline
+                let taken = lock(&state.mailboxes).take(&username);
+                let blocked = state.profiles.get(&username).blocked;
+                let pending = match taken {
+                    // Queued before their sender was blocked
+                    Ok(pending) => pending.into_iter().filter(|message| !blocked.contains(&message.from)).collect::<Vec<_>>(),
+                    Err(e) => {
+                        self.state.journal.warn(format!("⚠️ Could not empty the mailbox of {}: {}", username, e));
+                        Vec::new()
+                    }
+                };
+                if !pending.is_empty() {
+                    let mut senders = HashMap::new();
+                    for message in &pending {
+                        *senders.entry(message.from.clone()).or_insert(0) += 1;
+                    }
+                    state.send_message_to_client(client_id, Message::PendingMessages { count: pending.len(), senders }).await;
+                    for message in pending {
+                        let delivered = match message.room_id {
+                            Some(room_id) => Message::Mention {
+                                room_id,
+                                from: message.from,
+                                content: message.content,
+                                timestamp: message.timestamp,
+                                sequence: message.sequence,
+                            },
+                            None => Message::PrivateMessageReceived {
+                                from: message.from,
+                                content: message.content,
+                                timestamp: message.timestamp,
+                            },
+                        };
+                        state.send_message_to_client(client_id, delivered).await;
+                    }
+                }
+                Ok(())
+            }
+            Err((code, reason)) => {
+                let response = Message::ConnectError { code, reason: reason.clone() };
+                state.send_message_to_client(client_id, response).await;
+                Err(reason)
+            }
+        }
+    }
+
+    async fn handle_join_room(
+        &self,
+        client_id: &ClientId,
+        room_id: String,
+        password: Option<String>,
+        wait: bool,
+        spectator: bool,
+    ) -> Result<(), String> {
+        let state = &self.state;
+
+        if let Err((code, message)) = state.check_room_access(client_id, &room_id, password.as_deref()).await {
+            state.send_message_to_client(client_id, Message::Error { code, message: message.clone() }).await;
+            return Err(message);
+        }
+
+        // A full room turns the client away, or lets it wait for a place if it asked to
+        if state.rooms.get(&room_id).is_some_and(|room| room.is_full_for(client_id)) {
+            if wait {
+                if let Some(position) = state.queue_for_room(client_id, &room_id, spectator) {
+                    self.state.journal.info(format!("⏳ Client {} waits for a place in {} (position {})", client_id, room_id, position));
+                    state.send_message_to_client(client_id, Message::RoomQueued { room_id, position }).await;
+                    return Ok(());
+                }
+            } else {
+                let message = format!("Le salon {} est complet", room_id);
+                state.send_message_to_client(client_id, Message::Error { code: ErrorCode::RoomFull, message: message.clone() }).await;
+                return Err(message);
+            }
+        }
+
+        state.enter_room(client_id, &room_id, spectator).inspect_err(|e| {
+            state.send_to_clients([client_id], Message::JoinRoomError { reason: e.clone() });
+        })
+    }
+
+    async fn handle_leave_room(&self, client_id: &ClientId) -> Result<(), String> {
+        let state = &self.state;
+
+        match state.leave_room(client_id) {
+            Ok(_) => {
+                // No specific success message for LeaveRoom, the client knows it left
+                // A generic message could be sent if desired
+                Ok(())
+            },
+            Err(e) => {
+                let response = Message::Error {
+                    code: ErrorCode::InvalidState,
+                    message: e.clone(),
+                };
+                state.send_message_to_client(client_id, response).await;
+                Err(e)
+            }
+        }
+    }
+
+    /// `sent_at`: when the client sent it, by its clock (or ours if its clock cannot be trusted)
+    async fn handle_send_message(
+        &self,
+        client_id: &ClientId,
+        content: String,
+        kind: MessageKind,
+        parent_message_id: Option<MessageId>,
+        sent_at: DateTime<Utc>,
+    ) -> Result<(), String> {
+        let state = &self.state;
+
+        let (username, room_id, moderator) = {
+            let client = state.clients.get(client_id).ok_or("Client not found")?;
+            let moderator = client.current_room.as_deref().is_some_and(|room_id| state.can_moderate(&client, room_id));
+            (client.username.clone(), client.current_room.clone(), moderator)
+        };
+        let username = username.ok_or("Client not authenticated")?;
+        let room_id = room_id.ok_or("Client not in a room")?;
+
+        if state.rooms.get(&room_id).is_some_and(|room| room.is_spectator(client_id)) {
+            let message = format!("You are a spectator of #{}, a room administrator may let you write", room_id);
+            state.send_message_to_client(client_id, Message::Error { code: ErrorCode::ReadOnly, message: message.clone() }).await;
+            return Err(message);
+        }
+
+        if let Some(until) = active_restriction(&state.mutes, &room_id, &username) {
+            let message = match until {
+                Some(until) => format!("You are muted in #{} until {}", room_id, until.format("%d/%m %H:%M:%S")),
+                None => format!("You are muted in #{}", room_id),
+            };
+            state.send_message_to_client(client_id, Message::Error { code: ErrorCode::Muted, message: message.clone() }).await;
+            return Err(message);
+        }
+
+        // A reply must answer a message still in the room's history
+        let parent_missing = parent_message_id.as_deref()
+            .is_some_and(|parent| state.rooms.get(&room_id).is_none_or(|room| room.history.find(parent).is_none()));
+        if parent_missing {
+            let message = format!("The message being replied to is no longer in the history of #{}", room_id);
+            state.send_message_to_client(client_id, Message::Error { code: ErrorCode::InvalidState, message: message.clone() }).await;
+            return Err(message);
+        }
+
+        // Anyone may act or share code; announcements are for the room's administrators
+        let refusal = match kind.validate() {
+            Err(e) => Some((ErrorCode::InvalidFormat, e)),
+            Ok(()) if kind == MessageKind::Announcement && !moderator => {
+                Some((ErrorCode::PermissionDenied, format!("Only administrators of #{} may post announcements", room_id)))
+            }
+            Ok(()) => None,
+        };
+        if let Some((code, message)) = refusal {
+            state.send_message_to_client(client_id, Message::Error { code, message: message.clone() }).await;
+            return Err(message);
+        }
+
+        // The room's filter chain may rewrite the content or refuse it altogether
+        let mut message = Message::SendMessage { content, kind, parent_message_id };
+        let context = FilterContext { room_id: &room_id, username: &username, now: Instant::now() };
+        if let FilterDecision::Reject(code, reason) = state.filters.chain(&room_id).apply(&context, &mut message) {
+            self.state.journal.info(format!("🚫 [{}] Message from {} filtered out: {}", room_id, username, reason));
+            state.send_message_to_client(client_id, Message::Error { code, message: reason.clone() }).await;
+            return Err(reason);
+        }
+        let Message::SendMessage { content, kind, parent_message_id } = message else {
+            return Err("A filter replaced the message".to_string());
+        };
+
+        let entry = state.record_message(&room_id, HistoryEntry {
+            from: username.clone(),
+            content: content.clone(),
+            timestamp: sent_at,
+            sequence: 0, // Assigned by record_message
+            id: Uuid::new_v4().to_string(),
+            edited_at: None,
+            kind,
+            received_at: None, // Set by record_message
+            parent_id: parent_message_id,
+        })?;
+        let frame = ProtocolFrame::new(room_message(&room_id, &entry), None, entry.sequence);
+        state.federate(&frame, None); // Shared rooms also go to the linked servers
+        state.broadcast_to_room(&room_id, frame, None); // Broadcast to all members of the room
+        let _ = state.mark_read(client_id, &room_id, entry.sequence); // Whoever writes has read what came before
+
+        state.journal.log(ChatEvent::RoomMessage {
+            room_id: room_id.clone(),
+            from: username.clone(),
+            content,
+            message_id: entry.id.clone(),
+            origin: None,
+        });
+        let notified = state.notify_mentions(&room_id, &entry);
+        if !notified.is_empty() {
+            self.state.journal.info(format!("🔔 [{}] {} mentioned {}", room_id, username, notified.join(", ")));
+        }
+        Ok(())
+    }
+
+    async fn handle_private_message(&self, client_id: &ClientId, target_user: String, content: String) -> Result<(), String> {
+        let state = &self.state;
+
+        let username = state.clients.get(client_id).ok_or("Client not found")?
+            .username.clone().ok_or("Client not authenticated")?;
+
+        // Check that the target user is not the sender
+        if username == target_user {
+            let error_msg = "You cannot send a private message to yourself.".to_string();
+            let response = Message::Error { code: ErrorCode::InvalidState, message: error_msg.clone() };
+            state.send_message_to_client(client_id, response).await;
+            return Err(error_msg);
+        }
+
+        // The sender learns it is blocked rather than believing the message delivered
+        if state.profiles.get(&target_user).blocks(&username) {
+            let error_msg = format!("{} does not accept your messages.", target_user);
+            state.send_message_to_client(client_id, Message::Error { code: ErrorCode::Blocked, message: error_msg.clone() }).await;
+            return Err(error_msg);
+        }
+
+        match state.send_private_message(&username, &target_user, &content) {
+            Ok(true) => {
+                state.journal.log(ChatEvent::PrivateMessage { from: username, to: target_user, content, offline: false });
+                Ok(())
+            },
+            Ok(false) => {
+                state.send_message_to_client(client_id, Message::PrivateMessageQueued { target_user: target_user.clone() }).await;
+                state.journal.log(ChatEvent::PrivateMessage { from: username, to: target_user, content, offline: true });
+                Ok(())
+            },
+            Err(e) => {
+                let response = Message::Error {
+                    code: ErrorCode::UserNotFound, // Or other appropriate code
+                    message: e.clone(),
+                };
+                state.send_message_to_client(client_id, response).await;
+                Err(e)
+            }
+        }
+    }
+
+    async fn handle_list_rooms(&self, client_id: &ClientId) -> Result<(), String> {
+        let state = &self.state;
+
+        let rooms: HashMap<String, usize> = state.rooms.iter()
+            .map(|room| (room.key().clone(), room.user_count()))
+            .collect();
+
+        let unread = state.username_of(client_id).map(|username| state.unread_counts(&username)).unwrap_or_default();
+
+        let response = Message::RoomList { rooms, unread };
+        state.send_message_to_client(client_id, response).await;
+
+        Ok(())
+    }
+
+    async fn handle_list_users(&self, client_id: &ClientId) -> Result<(), String> {
+        let state = &self.state;
+
+        let room_id = state.clients.get(client_id).ok_or("Client not found")?
+            .current_room.clone().ok_or("Client not in a room")?;
+
+        // Members are copied out first: clients are never looked up while a room is held
+        let members = state.rooms.get(&room_id).map(|room| (room.users.clone(), room.get_usernames(), room.get_spectator_names()));
+        if let Some((members, users, spectators)) = members {
+            let statuses = members.iter()
+                .filter_map(|(id, username)| state.clients.get(id).map(|member| (username.clone(), member.presence)))
+                .collect();
+            let response = Message::UserList {
+                users,
+                room_id: room_id.clone(),
+                statuses,
+                spectators,
+            };
+            state.send_message_to_client(client_id, response).await;
+        } else {
+            // Should not happen if client.current_room is Some
+            let response = Message::Error {
+                code: ErrorCode::InternalError,
+                message: "Room not found for user list.".to_string(),
+            };
+            state.send_message_to_client(client_id, response).await;
+        }
+
+        Ok(())
+    }
+
+    async fn handle_get_history(&self, client_id: &ClientId, count: usize) -> Result<(), String> {
+        let state = &self.state;
+
+        let room_id = state.clients.get(client_id).ok_or("Client not found")?
+            .current_room.clone().ok_or("Client not in a room")?;
+        let messages = state.rooms.get(&room_id).ok_or("Room not found for history")?
+            .history.last(count.min(state.config.history_capacity));
+
+        let response = Message::History { room_id, messages };
+        state.send_message_to_client(client_id, response).await;
+
+        Ok(())
+    }
+
+    async fn handle_create_room(
+        &self,
+        client_id: &ClientId,
+        room_id: String,
+        name: String,
+        password: Option<String>,
+        invite_only: bool,
+        max_users: Option<usize>,
+    ) -> Result<(), String> {
+        let state = &self.state;
+
+        match state.create_room(client_id, &room_id, &name, password.as_deref(), invite_only, max_users).await {
+            Ok(()) => {
+                let name = state.rooms.get(&room_id).map(|room| room.name.clone()).unwrap_or_default();
+                self.state.journal.info(format!("🏠 Salon {} ({}) créé par {}", room_id, name, client_id));
+                state.send_message_to_client(client_id, Message::CreateRoomAck { room_id, name }).await;
+                Ok(())
+            }
+            Err(e) => {
+                state.send_message_to_client(client_id, Message::CreateRoomError { reason: e.clone() }).await;
+                Err(e)
+            }
+        }
+    }
+
+    async fn handle_delete_room(&self, client_id: &ClientId, room_id: String) -> Result<(), String> {
+        let state = &self.state;
+
+        match state.delete_room(client_id, &room_id) {
+            Ok(()) => {
+                self.state.journal.info(format!("🗑️ Salon {} supprimé par {}", room_id, client_id));
+                state.send_message_to_client(client_id, Message::DeleteRoomAck { room_id }).await;
+                Ok(())
+            }
+            Err(e) => {
+                state.send_message_to_client(client_id, Message::DeleteRoomError { reason: e.clone() }).await;
+                Err(e)
+            }
+        }
+    }
+
+    async fn handle_invite_user(&self, client_id: &ClientId, room_id: String, username: String) -> Result<(), String> {
+        let state = &self.state;
+
+        match state.invite_user(client_id, &room_id, &username) {
+            Ok(()) => {
+                let from = state.username_of(client_id).unwrap_or_default();
+                if let Some(invitee_id) = state.client_of(&username) {
+                    let invitation = Message::RoomInvitation { room_id: room_id.clone(), from };
+                    state.send_message_to_client(&invitee_id, invitation).await;
+                }
+                state.send_message_to_client(client_id, Message::InviteUserAck { room_id, username }).await;
+                Ok(())
+            }
+            Err((code, message)) => {
+                state.send_message_to_client(client_id, Message::Error { code, message: message.clone() }).await;
+                Err(message)
+            }
+        }
+    }
+
+    async fn handle_file_offer(
+        &self,
+        client_id: &ClientId,
+        transfer_id: String,
+        target: FileTarget,
+        filename: String,
+        size: u64,
+    ) -> Result<(), String> {
+        let state = &self.state;
+
+        match state.offer_file(client_id, &transfer_id, &target, &filename, size) {
+            Ok(recipients) => {
+                let from = state.username_of(client_id);
+                self.state.journal.info(format!("📎 {} propose {} ({} octets) à {:?}", from.as_deref().unwrap_or("?"), filename, size, target));
+                state.send_to_clients(&recipients, Message::FileOffer { transfer_id, target, filename, size, from });
+                Ok(())
+            }
+            Err((code, message)) => {
+                state.send_message_to_client(client_id, Message::Error { code, message: message.clone() }).await;
+                Err(message)
+            }
+        }
+    }
+
+    async fn handle_file_accept(&self, client_id: &ClientId, transfer_id: String) -> Result<(), String> {
+        let state = &self.state;
+
+        match state.accept_file(client_id, &transfer_id) {
+            Ok(sender) => {
+                let from = state.username_of(client_id);
+                state.send_to_clients([&sender], Message::FileAccept { transfer_id, from });
+                Ok(())
+            }
+            Err((code, message)) => {
+                state.send_message_to_client(client_id, Message::Error { code, message: message.clone() }).await;
+                Err(message)
+            }
+        }
+    }
+
+    async fn handle_file_chunk(&self, client_id: &ClientId, transfer_id: String, seq: u64, data: String) -> Result<(), String> {
+        let state = &self.state;
+
+        match state.relay_chunk(client_id, &transfer_id, seq, &data) {
+            Ok(recipients) => {
+                state.send_to_clients(&recipients, Message::FileChunk { transfer_id, seq, data });
+                Ok(())
+            }
+            Err((code, message)) => {
+                // A broken stream cannot be resumed: the whole transfer is dropped
+                state.abort_transfer(&transfer_id, client_id, &message);
+                state.send_message_to_client(client_id, Message::Error { code, message: message.clone() }).await;
+                Err(message)
+            }
+        }
+    }
+
+    async fn handle_file_complete(&self, client_id: &ClientId, transfer_id: String, sha256: String) -> Result<(), String> {
+        let state = &self.state;
+
+        match state.complete_file(client_id, &transfer_id) {
+            Ok(recipients) => {
+                self.state.journal.info(format!("📎 Transfert {} terminé ({} destinataire(s))", transfer_id, recipients.len()));
+                state.send_to_clients(&recipients, Message::FileComplete { transfer_id, sha256 });
+                Ok(())
+            }
+            Err((code, message)) => {
+                state.abort_transfer(&transfer_id, client_id, &message);
+                state.send_message_to_client(client_id, Message::Error { code, message: message.clone() }).await;
+                Err(message)
+            }
+        }
+    }
+
+    async fn handle_typing(&self, client_id: &ClientId, room_id: String) -> Result<(), String> {
+        let state = &self.state;
+
+        let username = {
+            let mut client = state.clients.get_mut(client_id).ok_or("Client not found")?;
+            if client.current_room.as_ref() != Some(&room_id) {
+                return Err(format!("Typing notification for #{} while not in that room", room_id));
+            }
+            // Throttled: extra notifications are silently dropped
+            let now = Instant::now();
+            if client.last_typing.is_some_and(|last| now.duration_since(last) < TYPING_THROTTLE) {
+                return Ok(());
+            }
+            client.last_typing = Some(now);
+            client.username.clone().ok_or("Client not authenticated")?
+        };
+        // Spectators cannot write: nothing to announce
+        if state.rooms.get(&room_id).is_some_and(|room| room.is_spectator(client_id)) {
+            return Ok(());
+        }
+
+        let frame = ProtocolFrame::new(Message::UserTyping { room_id: room_id.clone(), username }, None, 0);
+        state.broadcast_to_room(&room_id, frame, Some(client_id));
+        Ok(())
+    }
+
+    async fn handle_presence_update(&self, client_id: &ClientId, status: PresenceStatus) -> Result<(), String> {
+        let state = &self.state;
+
+        let (username, current_room) = {
+            let mut client = state.clients.get_mut(client_id).ok_or("Client not found")?;
+            client.presence = status;
+            (client.username.clone().ok_or("Client not authenticated")?, client.current_room.clone())
+        };
+
+        // The sender gets the notification too, as a confirmation
+        if let Some(room_id) = current_room {
+            let frame = ProtocolFrame::new(Message::UserPresence { username: username.clone(), status }, None, 0);
+            state.broadcast_to_room(&room_id, frame, None);
+        } else {
+            state.send_message_to_client(client_id, Message::UserPresence { username: username.clone(), status }).await;
+        }
+        self.state.journal.info(format!("🟢 {} est maintenant {}", username, status));
+        Ok(())
+    }
+
+    async fn handle_moderation(&self, client_id: &ClientId, room_id: String, target: String, sanction: Sanction) -> Result<(), String> {
+        let state = &self.state;
+
+        match state.moderate(client_id, &room_id, &target, sanction) {
+            Ok(notification) => {
+                self.state.journal.info(format!("🛡️ [{}] {:?} {} par {}", room_id, sanction, target, client_id));
+                // The moderator may not be in the room: make sure they get a confirmation
+                let in_room = state.rooms.get(&room_id).is_some_and(|room| room.users.contains_key(client_id));
+                if !in_room {
+                    state.send_message_to_client(client_id, notification).await;
+                }
+                Ok(())
+            }
+            Err((code, message)) => {
+                state.send_message_to_client(client_id, Message::Error { code, message: message.clone() }).await;
+                Err(message)
+            }
+        }
+    }
+
+    async fn handle_promote(&self, client_id: &ClientId, room_id: String, target: String) -> Result<(), String> {
+        let state = &self.state;
+
+        match state.promote(client_id, &room_id, &target) {
+            Ok(notification) => {
+                self.state.journal.info(format!("🎤 [{}] {} promu par {}", room_id, target, client_id));
+                let in_room = state.rooms.get(&room_id).is_some_and(|room| room.users.contains_key(client_id));
+                if !in_room {
+                    state.send_message_to_client(client_id, notification).await;
+                }
+                Ok(())
+            }
+            Err((code, message)) => {
+                state.send_message_to_client(client_id, Message::Error { code, message: message.clone() }).await;
+                Err(message)
+            }
+        }
+    }
+
+    async fn handle_room_update(&self, client_id: &ClientId, room_id: String, update: RoomUpdate) -> Result<(), String> {
+        let state = &self.state;
+
+        match state.update_room(client_id, &room_id, update.clone()) {
+            Ok(notification) => {
+                self.state.journal.info(format!("📌 [{}] {:?} par {}", room_id, update, client_id));
+                // Same as moderation: an administrator outside the room still gets a confirmation
+                let in_room = state.rooms.get(&room_id).is_some_and(|room| room.users.contains_key(client_id));
+                if !in_room {
+                    state.send_message_to_client(client_id, notification).await;
+                }
+                Ok(())
+            }
+            Err((code, message)) => {
+                state.send_message_to_client(client_id, Message::Error { code, message: message.clone() }).await;
+                Err(message)
+            }
+        }
+    }
+
+    async fn handle_message_change(&self, client_id: &ClientId, message_id: MessageId, change: MessageChange) -> Result<(), String> {
+        let state = &self.state;
+
+        match state.change_message(client_id, &message_id, change) {
+            Ok(notification) => {
+                match &notification {
+                    Message::MessageEdited { room_id, sequence, by, new_content, .. } => self.state.journal.info(format!("✏️ [{}] #{} modifié par {}: {}", room_id, sequence, by, new_content)),
+                    Message::MessageDeleted { room_id, sequence, by, .. } => self.state.journal.info(format!("🗑️ [{}] #{} supprimé par {}", room_id, sequence, by)),
+                    _ => {}
+                }
+                Ok(())
+            }
+            Err((code, message)) => {
+                state.send_message_to_client(client_id, Message::Error { code, message: message.clone() }).await;
+                Err(message)
+            }
+        }
+    }
+
+    /// Answer GetProfile and UpdateProfile with the profile, or the reason it could not be read or changed
+    async fn handle_profile(&self, client_id: &ClientId, profile: Result<Message, (ErrorCode, String)>) -> Result<(), String> {
+        let state = &self.state;
+
+        match profile {
+            Ok(profile) => {
+                state.send_message_to_client(client_id, profile).await;
+                Ok(())
+            }
+            Err((code, message)) => {
+                state.send_message_to_client(client_id, Message::Error { code, message: message.clone() }).await;
+                Err(message)
+            }
+        }
+    }
+
+    async fn handle_get_stats(&self, client_id: &ClientId) -> Result<(), String> {
+        let state = &self.state;
+
+        match state.stats_for(client_id) {
+            Ok(stats) => {
+                state.send_message_to_client(client_id, stats).await;
+                Ok(())
+            }
+            Err((code, message)) => {
+                state.send_message_to_client(client_id, Message::Error { code, message: message.clone() }).await;
+                Err(message)
+            }
+        }
+    }
+
+    async fn handle_get_pins(&self, client_id: &ClientId, room_id: String) -> Result<(), String> {
+        let state = &self.state;
+
+        match state.pins(client_id, &room_id) {
+            Ok(messages) => {
+                state.send_message_to_client(client_id, Message::Pins { room_id, messages }).await;
+                Ok(())
+            }
+            Err((code, message)) => {
+                state.send_message_to_client(client_id, Message::Error { code, message: message.clone() }).await;
+                Err(message)
+            }
+        }
+    }
+
+    async fn handle_get_thread(&self, client_id: &ClientId, message_id: MessageId) -> Result<(), String> {
+        let state = &self.state;
+
+        let room_id = state.clients.get(client_id).ok_or("Client not found")?
+            .current_room.clone().ok_or("Client not in a room")?;
+        let messages = state.rooms.get(&room_id).map(|room| room.history.thread(&message_id)).unwrap_or_default();
+        if messages.is_empty() {
+            let message = format!("Message {} is not in the history of #{}", message_id, room_id);
+            state.send_message_to_client(client_id, Message::Error { code: ErrorCode::InvalidState, message: message.clone() }).await;
+            return Err(message);
+        }
+
+        state.send_message_to_client(client_id, Message::Thread { room_id, message_id, messages }).await;
+        Ok(())
+    }
+
+    async fn handle_search_history(&self, client_id: &ClientId, room_id: String, query: String, limit: usize) -> Result<(), String> {
+        let state = &self.state;
+
+        match state.search_history(client_id, &room_id, &query, limit) {
+            Ok(messages) => {
+                self.state.journal.info(format!("🔍 [{}] \"{}\": {} result(s) for {}", room_id, query, messages.len(), client_id));
+                state.send_message_to_client(client_id, Message::SearchResults { room_id, query, messages }).await;
+                Ok(())
+            }
+            Err((code, message)) => {
+                state.send_message_to_client(client_id, Message::Error { code, message: message.clone() }).await;
+                Err(message)
+            }
+        }
+    }
+
+    /// Pick the protocol version of the connection; without a common version the client is told so and disconnected
+    async fn handle_hello(&self, client_id: &ClientId, min_version: u8, max_version: u8) -> Result<(), String> {
+        let state = &self.state;
+        let (protocol_version, disconnect) = state.clients.get(client_id)
+            .map(|client| (Arc::clone(&client.protocol_version), Arc::clone(&client.disconnect)))
+            .ok_or("Client not found in server state (internal error)")?;
+        if protocol_version.load(Ordering::Relaxed) != 0 {
+            let error_msg = "Protocol version already negotiated".to_string();
+            state.send_message_to_client(client_id, Message::Error { code: ErrorCode::InvalidState, message: error_msg.clone() }).await;
+            return Err(error_msg);
+        }
+
+        match negotiate_version(min_version, max_version) {
+            Some(version) => {
+                protocol_version.store(version, Ordering::Relaxed);
+                self.state.journal.info(format!("🤝 Client {} speaks SCP v{}", client_id, version));
+                state.send_message_to_client(client_id, Message::HelloAck { version }).await;
+            }
+            None => {
+                self.state.journal.info(format!("🚫 Client {} speaks SCP v{} to v{}, disconnecting.", client_id, min_version, max_version));
+                let message = format!(
+                    "No common protocol version: client speaks v{} to v{}, server speaks v{} to v{}.",
+                    min_version, max_version, MIN_PROTOCOL_VERSION, PROTOCOL_VERSION,
+                );
+                let response = Message::VersionMismatch { min_version: MIN_PROTOCOL_VERSION, max_version: PROTOCOL_VERSION, message };
+                state.send_message_to_client(client_id, response).await;
+                disconnect.notify_one();
+            }
+        }
+        Ok(())
+    }
+
+    async fn handle_ping(&self, client_id: &ClientId) -> Result<(), String> {
+        let state = &self.state;
+        let response = Message::Pong;
+        state.send_message_to_client(client_id, response).await;
+        Ok(())
+    }
+}
+
+/// Errors that prevent the server from starting or from saving its state when it stops
+pub type ServerError = Box<dyn std::error::Error + Send + Sync>;
+
+/// A running server: where it listens, and the means to stop it
+pub struct ServerHandle {
+    local_addr: SocketAddr,
+    federation_addr: Option<SocketAddr>,
+    state: Arc<ServerState>,
+    stop: Arc<Notify>,
+    task: JoinHandle<Result<(), ServerError>>,
+}
+
+impl ServerHandle {
+    /// Address actually bound, useful when the configuration asked for port 0
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Address bound for the other servers of the federation, if `federation_bind` is set
+    pub fn federation_addr(&self) -> Option<SocketAddr> {
+        self.federation_addr
+    }
+
+    /// Names of the servers currently linked with this one
+    pub fn federated_servers(&self) -> Vec<String> {
+        self.state.federation.servers()
+    }
+
+    /// Accept operator commands on stdin for as long as the server runs
+    pub fn spawn_console(&self) {
+        console::stdin(Arc::clone(&self.state));
+    }
+
+    /// Warn the clients, give them the configured grace period, close the connections and save the rooms
+    pub async fn shutdown(self) -> Result<(), ServerError> {
+        self.stop.notify_one();
+        self.task.await?
+    }
+}
+
+/// Open the stores, bind `config.bind` (port 0 picks a free one) and serve connections in the background
+pub async fn start_server(config: ServerConfig) -> Result<ServerHandle, ServerError> {
+    // Events go through tracing (console, and the logging server set up by the binary), and to the log file if configured
+    let mut journal = Journal::new(&config.server_name).with(ConsoleLogger);
+    if let Some(path) = &config.log_file {
+        journal = journal.with(JsonFileLogger::open(path)?);
+    }
+
+    let acceptor = match (&config.tls_cert, &config.tls_key) {
+        (Some(cert), Some(key)) => Some(chiffrement::acceptor(cert, key)?),
+        _ => None,
+    };
+    let history_store = match &config.history_dir {
+        Some(dir) => {
+            journal.info(format!("💾 Room history persisted in {}", dir.display()));
+            Some(HistoryStore::open(dir)?)
+        }
+        None => None,
+    };
+
+    let users = UserStore::open(&config.users_file)?;
+    journal.info(format!("👤 {} account(s) loaded from {}", users.len(), config.users_file.display()));
+
+    let profiles = ProfileStore::open(&config.profiles_file)?;
+    let mailboxes = MailboxStore::open(&config.mailbox_file)?;
+
+    let server = ChatServer::new(config, history_store, users, profiles, mailboxes, journal);
+    let config = Arc::clone(&server.config);
+
+    let room_store = RoomStore::new(&config.rooms_file);
+    let restored = server.state.restore_rooms(room_store.load()?);
+    if restored > 0 {
+        server.state.journal.info(format!("🏠 {} room(s) restored from {}", restored, config.rooms_file.display()));
+    }
+
+    let listener = TcpListener::bind(config.bind).await?;
+    let local_addr = listener.local_addr()?;
+    let mut background = Vec::new();
+    if let Some(addr) = config.admin_bind {
+        background.push(console::listen(Arc::clone(&server.state), addr).await?);
+    }
+    let (federation_addr, links) = federation::start(&server.state).await?;
+    background.extend(links);
+
+    // Garbage-collect client-created rooms left empty for too long
+    let gc_state = Arc::clone(&server.state);
+    let room_grace = config.room_grace();
+    background.push(tokio::spawn(async move {
+        let mut interval = tokio::time::interval(ROOM_GC_INTERVAL);
+        loop {
+            interval.tick().await;
+            for room_id in gc_state.collect_empty_rooms(room_grace) {
+                gc_state.journal.info(format!("🧹 Salon vide {} supprimé", room_id));
+            }
+        }
+    }));
+
+    // Periodic digest of the activity, to size rooms and tune the rate limits
+    if let Some(period) = config.metrics_digest_interval() {
+        let digest_state = Arc::clone(&server.state);
+        background.push(tokio::spawn(async move {
+            let mut interval = tokio::time::interval(period);
+            interval.tick().await; // The first tick is immediate: nothing to report yet
+            loop {
+                interval.tick().await;
+                digest_state.journal.info(digest_state.stats().digest());
+            }
+        }));
+    }
+
+    server.state.journal.info(format!("📡 Server listening on {}{}", local_addr, if acceptor.is_some() { " (TLS)" } else { "" }));
+    let rooms: Vec<&str> = config.rooms.iter().map(|room| room.id.as_str()).collect();
+    server.state.journal.info(format!("💡 Available rooms: {}", rooms.join(", ")));
+
+    let state = Arc::clone(&server.state);
+    let stop = Arc::new(Notify::new());
+    let task = tokio::spawn(serve(server, listener, acceptor, room_store, background, Arc::clone(&stop)));
+    Ok(ServerHandle { local_addr, federation_addr, state, stop, task })
+}
+
+/// Accept connections until `stop` is notified, then shut down and save the rooms
+async fn serve(
+    server: ChatServer,
+    listener: TcpListener,
+    acceptor: Option<TlsAcceptor>,
+    room_store: RoomStore,
+    background: Vec<JoinHandle<()>>,
+    stop: Arc<Notify>,
+) -> Result<(), ServerError> {
+    let config = Arc::clone(&server.config);
+    let mut connections = tokio::task::JoinSet::new();
+    loop {
+        let (stream, addr) = tokio::select! {
+            _ = stop.notified() => break,
+            accepted = listener.accept() => match accepted {
+                Ok(accepted) => accepted,
+                Err(e) => {
+                    server.state.journal.log(ChatEvent::error(None, format!("Error accepting a connection: {}", e)));
+                    continue;
+                }
+            },
+        };
+        while connections.try_join_next().is_some() {} // Reap finished connections
+
+        let client_id = Uuid::new_v4().to_string();
+        server.state.journal.log(ChatEvent::Connected { client_id: client_id.clone(), addr: addr.to_string() });
+
+        let server_clone = ChatServer { // Clone the Arc reference to the server state
+            state: Arc::clone(&server.state),
+            config: Arc::clone(&server.config),
+        };
+
+        let acceptor = acceptor.clone();
+        connections.spawn(async move {
+            match acceptor {
+                Some(acceptor) => match acceptor.accept(stream).await {
+                    Ok(stream) => server_clone.handle_client(stream, client_id).await,
+                    Err(e) => server_clone.state.journal.log(ChatEvent::error(Some(&client_id), format!("TLS handshake failed with {}: {}", addr, e))),
+                },
+                None => server_clone.handle_client(stream, client_id).await,
+            }
+        });
+    }
+
+    // No new connections; each remaining one flushes its send queue before closing
+    drop(listener);
+    for task in background {
+        task.abort();
+    }
+    server.shutdown("Server is shutting down", config.shutdown_grace()).await;
+    let closed = async {
+        while connections.join_next().await.is_some() {}
+    };
+    if tokio::time::timeout(SEND_DRAIN_TIMEOUT + Duration::from_secs(1), closed).await.is_err() {
+        server.state.journal.warn("⚠️ Some connections did not close in time.");
+        connections.abort_all();
+    }
+
+    // History and accounts are written as they change; rooms are saved now
+    room_store.save(&server.state.room_records())?;
+    server.state.journal.info(format!("💾 {} room(s) saved to {}", server.state.room_owners.len(), config.rooms_file.display()));
+    server.state.journal.info("👋 Server stopped.");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_disconnect_cleans_up() {
+        let state = ServerState::new(
+            Arc::new(ServerConfig::default()),
+            None,
+            UserStore::default(),
+            ProfileStore::default(),
+            MailboxStore::default(),
+            Journal::default(),
+        );
+        let mut queues = Vec::new();
+        for (client_id, username) in [("c1", "alice"), ("c2", "bob")] {
+            queues.push(state.add_client(client_id.to_string()));
+            // Logged in without a password check, which needs a registered account
+            state.username_to_client.insert(username.to_string(), client_id.to_string());
+            if let Some(mut client) = state.clients.get_mut(client_id) {
+                client.username = Some(username.to_string());
+                client.session_state = SessionState::Authenticated(username.to_string());
+            }
+            state.join_room(&client_id.to_string(), "general", false).unwrap();
+        }
+
+        assert_eq!(state.disconnect(&"c2".to_string()).as_deref(), Some("bob"));
+        assert!(state.clients.get("c2").is_none() && state.client_senders.get("c2").is_none());
+        assert!(state.client_of("bob").is_none());
+        assert_eq!(state.rooms.get("general").unwrap().get_usernames(), vec!["alice".to_string()]);
+
+        // bob's queue ends with the acknowledgement, then closes
+        let mut frames = Vec::new();
+        while let Some(outgoing) = queues[1].recv().await {
+            if let Outgoing::Frame(frame) = outgoing {
+                frames.push(frame.message);
+            }
+        }
+        assert_eq!(frames.last(), Some(&Message::DisconnectAck));
+
+        // A second Disconnect, once the client is gone, changes nothing
+        assert_eq!(state.disconnect(&"c2".to_string()), None);
+        assert!(state.client_of("alice").is_some());
+    }
+}
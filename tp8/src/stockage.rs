@@ -0,0 +1,16 @@
+// src/stockage.rs
+// Écriture des fichiers JSON des comptes, profils, boîtes aux lettres et salons
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde::Serialize;
+
+/// Réécrire le fichier `path` (via un fichier temporaire pour ne jamais le laisser à moitié écrit)
+pub fn write_json_atomically<T: Serialize + ?Sized>(path: &Path, value: &T) -> io::Result<()> {
+    let json = serde_json::to_string_pretty(value).map_err(io::Error::other)?;
+    let temporaire = path.with_extension("tmp");
+    fs::write(&temporaire, json)?;
+    fs::rename(temporaire, path)
+}
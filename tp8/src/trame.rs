@@ -0,0 +1,140 @@
+// src/trame.rs
+// Découpage du flux TCP en trames : longueur sur 4 octets (big-endian) puis JSON de la ProtocolFrame.
+// Le découpage lui-même est celui de net-commun ; ce module y ajoute la lecture de la ProtocolFrame et sa version
+
+use std::fmt;
+use std::io;
+
+use net_commun::{read_length_prefixed, write_length_prefixed};
+use tokio::io::{AsyncRead, AsyncWrite};
+
+use crate::protocole::{is_supported_version, peek_version, ProtocolFrame, MAX_MESSAGE_SIZE};
+
+/// Erreur de lecture d'une trame
+#[derive(Debug)]
+pub enum FrameError {
+    /// Erreur de la connexion (y compris une fermeture au milieu d'une trame)
+    Io(io::Error),
+    /// Longueur annoncée supérieure au maximum accepté ; le flux n'est plus synchronisé
+    TooLarge { length: usize, max: usize },
+    /// Contenu qui n'est pas une ProtocolFrame valide ; la trame suivante reste lisible
+    Invalid(serde_json::Error),
+    /// Trame d'une version du protocole que l'on ne sait pas lire
+    UnsupportedVersion(u8),
+}
+
+impl fmt::Display for FrameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FrameError::Io(e) => write!(f, "erreur de connexion: {}", e),
+            FrameError::TooLarge { length, max } => {
+                write!(f, "trame trop volumineuse: {} octets (max: {})", length, max)
+            }
+            FrameError::Invalid(e) => write!(f, "trame invalide: {}", e),
+            FrameError::UnsupportedVersion(version) => write!(f, "version de protocole non supportée: {}", version),
+        }
+    }
+}
+
+impl std::error::Error for FrameError {}
+
+impl From<io::Error> for FrameError {
+    fn from(e: io::Error) -> Self {
+        FrameError::Io(e)
+    }
+}
+
+impl From<net_commun::FrameError> for FrameError {
+    fn from(e: net_commun::FrameError) -> Self {
+        match e {
+            net_commun::FrameError::Io(e) => FrameError::Io(e),
+            net_commun::FrameError::TooLarge { length, max } => FrameError::TooLarge { length, max },
+            // Les blocs bruts ne sont pas décodés par net-commun : ce cas ne vient pas de read_length_prefixed
+            net_commun::FrameError::Invalid(reason) => FrameError::Io(io::Error::new(io::ErrorKind::InvalidData, reason)),
+        }
+    }
+}
+
+/// Lire la trame suivante ; `Ok(None)` si le pair a fermé proprement la connexion entre deux trames
+pub async fn read_frame<R: AsyncRead + Unpin>(reader: &mut R) -> Result<Option<ProtocolFrame>, FrameError> {
+    read_frame_limited(reader, MAX_MESSAGE_SIZE).await
+}
+
+/// Comme `read_frame`, en refusant les trames de plus de `max` octets
+pub async fn read_frame_limited<R: AsyncRead + Unpin>(reader: &mut R, max: usize) -> Result<Option<ProtocolFrame>, FrameError> {
+    let Some(buffer) = read_length_prefixed(reader, max).await? else {
+        return Ok(None);
+    };
+    match ProtocolFrame::deserialize(&buffer) {
+        Ok(frame) if is_supported_version(frame.version) => Ok(Some(frame)),
+        Ok(frame) => Err(FrameError::UnsupportedVersion(frame.version)),
+        Err(e) => match peek_version(&buffer) {
+            Some(version) if !is_supported_version(version) => Err(FrameError::UnsupportedVersion(version)),
+            _ => Err(FrameError::Invalid(e)),
+        },
+    }
+}
+
+/// Écrire une trame (préfixe de longueur et contenu en une seule écriture)
+pub async fn write_frame<W: AsyncWrite + Unpin>(writer: &mut W, frame: &ProtocolFrame) -> io::Result<()> {
+    let data = frame.serialize().map_err(io::Error::other)?;
+    write_length_prefixed(writer, &data).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocole::Message;
+    use tokio::io::AsyncWriteExt;
+
+    #[tokio::test]
+    async fn test_round_trip_then_eof() {
+        let (mut client, mut server) = tokio::io::duplex(1024);
+        let frame = ProtocolFrame::new(Message::Ping, None, 7);
+        write_frame(&mut client, &frame).await.unwrap();
+        drop(client);
+
+        assert_eq!(read_frame(&mut server).await.unwrap(), Some(frame));
+        assert!(read_frame(&mut server).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_partial_reads() {
+        // Tampon de 5 octets : chaque trame arrive en plusieurs morceaux
+        let (mut client, mut server) = tokio::io::duplex(5);
+        let frames = [ProtocolFrame::new(Message::Ping, None, 1), ProtocolFrame::new(Message::Pong, None, 2)];
+        let sent = frames.clone();
+        let writer = tokio::spawn(async move {
+            for frame in &sent {
+                write_frame(&mut client, frame).await.unwrap();
+            }
+        });
+        for frame in frames {
+            assert_eq!(read_frame(&mut server).await.unwrap(), Some(frame));
+        }
+        writer.await.unwrap();
+        assert!(read_frame(&mut server).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_too_large_and_invalid() {
+        let (mut client, mut server) = tokio::io::duplex(1024);
+        client.write_all(&(MAX_MESSAGE_SIZE as u32 + 1).to_be_bytes()).await.unwrap();
+        assert!(matches!(read_frame(&mut server).await, Err(FrameError::TooLarge { .. })));
+
+        let (mut client, mut server) = tokio::io::duplex(1024);
+        client.write_all(&2048u32.to_be_bytes()).await.unwrap();
+        assert!(matches!(read_frame_limited(&mut server, 1024).await, Err(FrameError::TooLarge { length: 2048, max: 1024 })));
+
+        let (mut client, mut server) = tokio::io::duplex(1024);
+        client.write_all(&3u32.to_be_bytes()).await.unwrap();
+        client.write_all(b"{}}").await.unwrap();
+        assert!(matches!(read_frame(&mut server).await, Err(FrameError::Invalid(_))));
+
+        let (mut client, mut server) = tokio::io::duplex(1024);
+        let future = br#"{"version":9,"message":{"type":"Nouveau"}}"#;
+        client.write_all(&(future.len() as u32).to_be_bytes()).await.unwrap();
+        client.write_all(future).await.unwrap();
+        assert!(matches!(read_frame(&mut server).await, Err(FrameError::UnsupportedVersion(9))));
+    }
+}
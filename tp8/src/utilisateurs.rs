@@ -0,0 +1,155 @@
+// src/utilisateurs.rs
+// Comptes utilisateurs : mots de passe hachés (Argon2), persistés dans un fichier JSON
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{PoisonError, RwLock, RwLockReadGuard};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::motdepasse::{hash_password, verify_password};
+use crate::protocole::ErrorCode;
+use crate::stockage::write_json_atomically;
+
+/// Longueur maximale d'un nom d'utilisateur
+pub const MAX_USERNAME_LENGTH: usize = 32;
+
+/// Longueur minimale d'un mot de passe
+pub const MIN_PASSWORD_LENGTH: usize = 4;
+
+/// Compte enregistré
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct UserRecord {
+    pub password_hash: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Ensemble des comptes ; sans chemin, les comptes ne vivent qu'en mémoire.
+/// Partageable entre connexions : le verrou ne couvre que la table, jamais le calcul Argon2
+#[derive(Debug, Default)]
+pub struct UserStore {
+    path: Option<PathBuf>,
+    users: RwLock<HashMap<String, UserRecord>>,
+}
+
+impl UserStore {
+    /// Charger les comptes depuis un fichier JSON (absent : aucun compte)
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let users = match fs::read_to_string(&path) {
+            Ok(contenu) => serde_json::from_str(&contenu).map_err(io::Error::other)?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => return Err(e),
+        };
+        Ok(Self { path: Some(path), users: RwLock::new(users) })
+    }
+
+    /// Créer un compte et l'enregistrer sur disque
+    pub fn register(&self, username: &str, password: &str) -> Result<(), (ErrorCode, String)> {
+        validate_username(username).map_err(|e| (ErrorCode::InvalidFormat, e))?;
+        if password.chars().count() < MIN_PASSWORD_LENGTH {
+            return Err((
+                ErrorCode::InvalidFormat,
+                format!("Le mot de passe doit faire au moins {} caractères", MIN_PASSWORD_LENGTH),
+            ));
+        }
+        if self.contains(username) {
+            return Err(taken());
+        }
+
+        // Le hachage est long : le nom est revérifié ensuite, au cas où un autre l'aurait pris entre-temps
+        let password_hash = hash_password(password).map_err(|e| (ErrorCode::InternalError, e))?;
+        let mut users = self.users.write().unwrap_or_else(PoisonError::into_inner);
+        if users.contains_key(username) {
+            return Err(taken());
+        }
+        users.insert(username.to_string(), UserRecord { password_hash, created_at: Utc::now() });
+        if let Err(e) = self.save(&users) {
+            users.remove(username);
+            return Err((ErrorCode::InternalError, format!("Impossible d'enregistrer le compte: {}", e)));
+        }
+        Ok(())
+    }
+
+    /// Vérifier les identifiants ; même réponse pour un compte inconnu ou un mauvais mot de passe
+    pub fn verify(&self, username: &str, password: &str) -> Result<(), (ErrorCode, String)> {
+        let password_hash = self.read().get(username).map(|record| record.password_hash.clone());
+        match password_hash {
+            Some(password_hash) if verify_password(password, &password_hash) => Ok(()),
+            _ => Err((ErrorCode::AuthFailed, "Identifiants invalides".to_string())),
+        }
+    }
+
+    pub fn contains(&self, username: &str) -> bool {
+        self.read().contains_key(username)
+    }
+
+    pub fn len(&self) -> usize {
+        self.read().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.read().is_empty()
+    }
+
+    fn read(&self) -> RwLockReadGuard<'_, HashMap<String, UserRecord>> {
+        self.users.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn save(&self, users: &HashMap<String, UserRecord>) -> io::Result<()> {
+        match &self.path {
+            Some(path) => write_json_atomically(path, users),
+            None => Ok(()),
+        }
+    }
+}
+
+fn taken() -> (ErrorCode, String) {
+    (ErrorCode::UsernameAlreadyTaken, "Nom d'utilisateur déjà enregistré".to_string())
+}
+
+/// Vérifier qu'un nom d'utilisateur est utilisable (non vide, sans espace, longueur bornée)
+pub fn validate_username(username: &str) -> Result<(), String> {
+    if username.is_empty() || username.chars().count() > MAX_USERNAME_LENGTH {
+        return Err(format!("Le nom d'utilisateur doit faire entre 1 et {} caractères", MAX_USERNAME_LENGTH));
+    }
+    if username.chars().any(char::is_whitespace) {
+        return Err("Le nom d'utilisateur ne peut pas contenir d'espace".to_string());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_register_then_verify_persisted() {
+        let path = std::env::temp_dir().join(format!("tp8-utilisateurs-{}.json", std::process::id()));
+        let store = UserStore::open(&path).unwrap();
+        store.register("alice", "secret").unwrap();
+        assert_eq!(store.register("alice", "autre").unwrap_err().0, ErrorCode::UsernameAlreadyTaken);
+        assert_eq!(store.register("bob", "abc").unwrap_err().0, ErrorCode::InvalidFormat);
+
+        let store = UserStore::open(&path).unwrap();
+        assert!(store.verify("alice", "secret").is_ok());
+        assert_eq!(store.verify("alice", "mauvais").unwrap_err().0, ErrorCode::AuthFailed);
+        assert_eq!(store.verify("inconnu", "secret").unwrap_err().0, ErrorCode::AuthFailed);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_concurrent_registration() {
+        // Deux inscriptions simultanées sous le même nom : une seule aboutit
+        let store = UserStore::default();
+        let results: Vec<_> = std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..2).map(|_| scope.spawn(|| store.register("carol", "secret"))).collect();
+            handles.into_iter().map(|handle| handle.join().unwrap()).collect()
+        });
+        assert_eq!(results.iter().filter(|result| result.is_ok()).count(), 1);
+        assert!(store.verify("carol", "secret").is_ok());
+    }
+}
@@ -0,0 +1,55 @@
+// tests/bot.rs
+// Un bot écrit avec tp8::client : il répond dans le salon et en privé, entre dans un salon et le quitte
+
+mod common;
+
+use common::{TestServer, PASSWORD};
+use tp8::client::{BotHandler, ChatBot, ClientError, PrivateMessage, RoomMessage};
+use tp8::protocole::{Message, MessageKind};
+
+/// Répète les messages du salon ; « !bye » en privé le fait quitter le salon puis se déconnecter
+struct Echo;
+
+impl BotHandler for Echo {
+    async fn on_room_message(&mut self, bot: &mut ChatBot, message: RoomMessage) -> Result<(), ClientError> {
+        bot.send(format!("écho: {}", message.content)).await
+    }
+
+    async fn on_private_message(&mut self, bot: &mut ChatBot, message: PrivateMessage) -> Result<(), ClientError> {
+        if message.content == "!bye" {
+            bot.leave().await?;
+            return bot.quit().await;
+        }
+        bot.private_message(&message.from, format!("écho: {}", message.content)).await
+    }
+}
+
+#[tokio::test]
+async fn test_bot_echo() {
+    let server = TestServer::start("bot").await;
+    let addr = server.handle.local_addr().to_string();
+    let mut bot = ChatBot::connect(&addr, "echo", PASSWORD).await.unwrap();
+    assert_eq!(bot.join("general").await.unwrap(), vec!["echo".to_string()]);
+    let running = tokio::spawn(async move {
+        bot.run(&mut Echo).await.map(|()| bot.room().map(String::from))
+    });
+
+    let mut alice = server.register("alice").await;
+    alice.join("general").await;
+    alice.send(Message::SendMessage { content: "bonjour".to_string(), kind: MessageKind::Text, parent_message_id: None }).await;
+    alice.expect(|m| matches!(m, Message::RoomMessage { from, content, .. } if from == "echo" && content == "écho: bonjour")).await;
+
+    alice.send(Message::PrivateMessage { target_user: "echo".to_string(), content: "psst".to_string() }).await;
+    alice.expect(|m| matches!(m, Message::PrivateMessageReceived { from, content, .. } if from == "echo" && content == "écho: psst")).await;
+
+    // Le bot s'en va sur demande ; la boucle se termine avec la connexion
+    alice.send(Message::PrivateMessage { target_user: "echo".to_string(), content: "!bye".to_string() }).await;
+    alice.expect(|m| matches!(m, Message::UserLeft { username, .. } if username == "echo")).await;
+    assert_eq!(running.await.unwrap().unwrap(), None);
+
+    // Le compte existe désormais : le bot s'y reconnecte
+    let bot = ChatBot::connect(&addr, "echo", PASSWORD).await.unwrap();
+    assert_eq!(bot.username(), "echo");
+    assert!(matches!(ChatBot::connect(&addr, "echo", "mauvais").await, Err(ClientError::Refused(_))));
+    server.stop().await;
+}
@@ -0,0 +1,139 @@
+// tests/common/mod.rs
+// Outils communs aux tests d'intégration : un serveur sur un port éphémère et des clients pilotés trame par trame
+
+#![allow(dead_code)] // Chaque fichier de tests n'utilise qu'une partie des outils
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::TcpStream;
+
+use tp8::configuration::ServerConfig;
+use tp8::protocole::{Message, ProtocolFrame, PROTOCOL_VERSION};
+use tp8::serveur::{start_server, ServerHandle};
+use tp8::trame::{read_frame, write_frame};
+
+/// Attente maximale d'une trame attendue
+pub const RECV_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Mot de passe de tous les comptes de test
+pub const PASSWORD: &str = "secret-de-test";
+
+/// Serveur de test : fichiers dans un répertoire temporaire propre au test, port choisi par le système
+pub struct TestServer {
+    pub handle: ServerHandle,
+    pub dir: PathBuf,
+}
+
+impl TestServer {
+    pub async fn start(name: &str) -> Self {
+        Self::start_with(name, |_| {}).await
+    }
+
+    /// Comme `start`, avec des réglages retouchés par `configure`
+    pub async fn start_with(name: &str, configure: impl FnOnce(&mut ServerConfig)) -> Self {
+        let dir = std::env::temp_dir().join(format!("tp8-integration-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).expect("répertoire temporaire");
+        let mut config = ServerConfig {
+            bind: "127.0.0.1:0".parse().unwrap(),
+            users_file: dir.join("utilisateurs.json"),
+            mailbox_file: dir.join("courrier.json"),
+            rooms_file: dir.join("salons.json"),
+            profiles_file: dir.join("profils.json"),
+            shutdown_grace_secs: 0,
+            ..ServerConfig::default()
+        };
+        configure(&mut config);
+        let handle = start_server(config).await.expect("démarrage du serveur");
+        Self { handle, dir }
+    }
+
+    /// Nouveau client, version négociée
+    pub async fn client(&self) -> ClientHandle {
+        ClientHandle::connect(&self.handle.local_addr().to_string()).await
+    }
+
+    /// Nouveau client inscrit sous `username`
+    pub async fn register(&self, username: &str) -> ClientHandle {
+        let mut client = self.client().await;
+        client.send(Message::Register { username: username.to_string(), password: PASSWORD.to_string() }).await;
+        client.expect(|m| matches!(m, Message::ConnectAck { .. })).await;
+        client
+    }
+
+    pub async fn stop(self) {
+        self.handle.shutdown().await.expect("arrêt du serveur");
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// Client de test : envoie des messages et lit les trames reçues, en répondant lui-même aux Ping
+pub struct ClientHandle {
+    reader: OwnedReadHalf,
+    writer: OwnedWriteHalf,
+}
+
+impl ClientHandle {
+    /// Se connecter et négocier la version courante du protocole
+    pub async fn connect(addr: &str) -> Self {
+        let stream = TcpStream::connect(addr).await.expect("connexion au serveur");
+        let (reader, writer) = stream.into_split();
+        let mut client = Self { reader, writer };
+        client.send(Message::Hello { min_version: PROTOCOL_VERSION, max_version: PROTOCOL_VERSION }).await;
+        client.expect(|m| matches!(m, Message::HelloAck { .. })).await;
+        client
+    }
+
+    pub async fn send(&mut self, message: Message) {
+        self.send_frame(ProtocolFrame::new(message, None, 0)).await;
+    }
+
+    /// Envoyer une trame préparée par le test (horodatage, version...)
+    pub async fn send_frame(&mut self, frame: ProtocolFrame) {
+        write_frame(&mut self.writer, &frame).await.expect("envoi d'une trame");
+    }
+
+    /// Prochain message du serveur ; `None` si la connexion est fermée
+    pub async fn recv(&mut self) -> Option<Message> {
+        loop {
+            let frame = tokio::time::timeout(RECV_TIMEOUT, read_frame(&mut self.reader))
+                .await
+                .expect("aucune trame reçue à temps")
+                .expect("trame illisible")?;
+            match frame.message {
+                Message::Ping => self.send(Message::Pong).await,
+                message => return Some(message),
+            }
+        }
+    }
+
+    /// Lire jusqu'au premier message qui satisfait `wanted`, en ignorant les autres
+    pub async fn expect(&mut self, wanted: impl Fn(&Message) -> bool) -> Message {
+        let mut skipped = Vec::new();
+        loop {
+            match self.recv().await {
+                Some(message) if wanted(&message) => return message,
+                Some(message) => skipped.push(message),
+                None => panic!("connexion fermée avant le message attendu ; reçus : {:?}", skipped),
+            }
+        }
+    }
+
+    /// Se déconnecter proprement : le serveur confirme, oublie le client puis ferme la connexion
+    pub async fn disconnect(mut self) {
+        self.send(Message::Disconnect).await;
+        self.expect(|m| *m == Message::DisconnectAck).await;
+        assert_eq!(self.recv().await, None, "connexion restée ouverte après DisconnectAck");
+    }
+
+    /// Entrer dans un salon ; renvoie la liste des membres annoncée par le serveur
+    pub async fn join(&mut self, room_id: &str) -> Vec<String> {
+        self.send(Message::JoinRoom { room_id: room_id.to_string(), password: None, wait: false, spectator: false }).await;
+        match self.expect(|m| matches!(m, Message::JoinRoomAck { .. } | Message::JoinRoomError { .. })).await {
+            Message::JoinRoomAck { users, .. } => users,
+            other => panic!("entrée refusée dans {} : {:?}", room_id, other),
+        }
+    }
+}
@@ -0,0 +1,63 @@
+// tests/federation.rs
+// Deux serveurs fédérés : les messages des salons partagés passent de l'un à l'autre
+
+mod common;
+
+use std::time::Duration;
+
+use common::TestServer;
+use tp8::protocole::{Message, MessageKind};
+
+const SECRET: &str = "secret-de-federation";
+
+#[tokio::test]
+async fn test_salon_partage() {
+    let a = TestServer::start_with("federation-a", |config| {
+        config.server_name = "a".to_string();
+        config.federation_bind = Some("127.0.0.1:0".parse().unwrap());
+        config.federated_rooms = ["general".to_string()].into();
+        config.federation_secret = Some(SECRET.to_string());
+    }).await;
+    let peer = a.handle.federation_addr().unwrap().to_string();
+    let b = TestServer::start_with("federation-b", |config| {
+        config.server_name = "b".to_string();
+        config.federation_peers = vec![peer];
+        config.federated_rooms = ["general".to_string()].into();
+        config.federation_secret = Some(SECRET.to_string());
+    }).await;
+    for _ in 0..100 {
+        if !a.handle.federated_servers().is_empty() && !b.handle.federated_servers().is_empty() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    assert_eq!((a.handle.federated_servers(), b.handle.federated_servers()), (vec!["b".to_string()], vec!["a".to_string()]));
+
+    let mut alice = a.register("alice").await;
+    let mut bob = b.register("bob").await;
+    alice.join("general").await;
+    bob.join("general").await;
+
+    // Dans les deux sens, avec le serveur d'origine à côté du nom ; chacun ne reçoit le sien qu'une fois
+    let say = |content: &str| Message::SendMessage { content: content.to_string(), kind: MessageKind::Text, parent_message_id: None };
+    alice.send(say("Bonjour de a")).await;
+    alice.expect(|m| matches!(m, Message::RoomMessage { from, .. } if from == "alice")).await;
+    bob.expect(|m| matches!(m, Message::RoomMessage { from, content, .. } if from == "alice@a" && content == "Bonjour de a")).await;
+
+    bob.send(say("Bonjour de b")).await;
+    bob.expect(|m| matches!(m, Message::RoomMessage { from, .. } if from == "bob")).await;
+    let received = alice.expect(|m| matches!(m, Message::RoomMessage { .. })).await;
+    assert!(matches!(received, Message::RoomMessage { from, content, .. } if from == "bob@b" && content == "Bonjour de b"));
+
+    // Les autres salons restent locaux
+    alice.join("tech").await;
+    bob.join("tech").await;
+    alice.send(say("Rien que pour a")).await;
+    alice.expect(|m| matches!(m, Message::RoomMessage { .. })).await;
+    bob.send(say("Rien que pour b")).await;
+    let received = bob.expect(|m| matches!(m, Message::RoomMessage { .. })).await;
+    assert!(matches!(received, Message::RoomMessage { from, .. } if from == "bob"));
+
+    b.stop().await;
+    a.stop().await;
+}
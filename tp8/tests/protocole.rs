@@ -0,0 +1,441 @@
+// tests/protocole.rs
+// Scénarios de bout en bout : plusieurs clients contre un vrai serveur
+
+mod common;
+
+use common::{ClientHandle, TestServer, PASSWORD};
+use tp8::filtres::FilterRules;
+use tp8::profils::UserProfile;
+use tp8::protocole::{ErrorCode, Message, MessageKind, ProtocolFrame};
+
+#[tokio::test]
+async fn test_salon_et_message_prive() {
+    let server = TestServer::start("salon").await;
+    let mut alice = server.register("alice").await;
+    let mut bob = server.register("bob").await;
+
+    assert_eq!(alice.join("general").await, vec!["alice".to_string()]);
+    let mut members = bob.join("general").await;
+    members.sort();
+    assert_eq!(members, vec!["alice".to_string(), "bob".to_string()]);
+    alice.expect(|m| matches!(m, Message::UserJoined { username, .. } if username == "bob")).await;
+
+    // Un message de salon revient à tous les membres, expéditeur compris
+    alice.send(Message::SendMessage { content: "Bonjour".to_string(), kind: MessageKind::Text, parent_message_id: None }).await;
+    for client in [&mut alice, &mut bob] {
+        match client.expect(|m| matches!(m, Message::RoomMessage { .. })).await {
+            Message::RoomMessage { from, content, room_id, .. } => {
+                assert_eq!((from.as_str(), content.as_str(), room_id.as_str()), ("alice", "Bonjour", "general"));
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    bob.send(Message::PrivateMessage { target_user: "alice".to_string(), content: "Psst".to_string() }).await;
+    match alice.expect(|m| matches!(m, Message::PrivateMessageReceived { .. })).await {
+        Message::PrivateMessageReceived { from, content, .. } => assert_eq!((from.as_str(), content.as_str()), ("bob", "Psst")),
+        _ => unreachable!(),
+    }
+
+    bob.send(Message::LeaveRoom).await;
+    alice.expect(|m| matches!(m, Message::UserLeft { username, room_id } if username == "bob" && room_id == "general")).await;
+
+    // Hors d'un salon, envoyer un message de salon est refusé
+    bob.send(Message::SendMessage { content: "Encore là ?".to_string(), kind: MessageKind::Text, parent_message_id: None }).await;
+    bob.expect(|m| matches!(m, Message::Error { code: ErrorCode::InvalidState, .. })).await;
+
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn test_message_prive_hors_ligne() {
+    let server = TestServer::start("hors-ligne").await;
+    let mut alice = server.register("alice").await;
+    let bob = server.register("bob").await;
+    bob.disconnect().await;
+
+    alice.send(Message::PrivateMessage { target_user: "bob".to_string(), content: "À plus tard".to_string() }).await;
+    alice.expect(|m| matches!(m, Message::PrivateMessageQueued { target_user } if target_user == "bob")).await;
+
+    // Le message attend bob jusqu'à sa prochaine connexion
+    let mut bob = server.client().await;
+    bob.send(Message::Login { username: "bob".to_string(), password: PASSWORD.to_string() }).await;
+    bob.expect(|m| matches!(m, Message::ConnectAck { .. })).await;
+    bob.expect(|m| matches!(m, Message::PendingMessages { count: 1, .. })).await;
+    match bob.expect(|m| matches!(m, Message::PrivateMessageReceived { .. })).await {
+        Message::PrivateMessageReceived { from, content, .. } => assert_eq!((from.as_str(), content.as_str()), ("alice", "À plus tard")),
+        _ => unreachable!(),
+    }
+
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn test_authentification_requise() {
+    let server = TestServer::start("authentification").await;
+    let mut client = server.client().await;
+
+    client.send(Message::JoinRoom { room_id: "general".to_string(), password: None, wait: false, spectator: false }).await;
+    client.expect(|m| matches!(m, Message::Error { code: ErrorCode::InvalidState, .. })).await;
+
+    client.send(Message::Login { username: "personne".to_string(), password: PASSWORD.to_string() }).await;
+    client.expect(|m| matches!(m, Message::ConnectError { .. })).await;
+
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn test_profil() {
+    let server = TestServer::start("profil").await;
+    let mut alice = server.register("alice").await;
+    alice.expect(|m| matches!(m, Message::Profile { .. })).await;
+
+    let mut profile = UserProfile::default();
+    profile.set("name", "Alice L.").unwrap();
+    profile.set("avatar", "🦊").unwrap();
+    profile.set("room", "tech").unwrap();
+    alice.send(Message::UpdateProfile { profile: profile.clone() }).await;
+    alice.expect(|m| matches!(m, Message::Profile { profile: received, .. } if *received == profile)).await;
+
+    // Les autres voient le nom affiché à l'arrivée dans un salon, mais pas les préférences
+    let mut bob = server.register("bob").await;
+    bob.join("general").await;
+    alice.join("general").await;
+    bob.expect(|m| matches!(m, Message::UserJoined { username, display_name: Some(name), avatar: Some(_), .. }
+        if username == "alice" && name == "Alice L.")).await;
+    bob.send(Message::GetProfile { username: Some("alice".to_string()) }).await;
+    match bob.expect(|m| matches!(m, Message::Profile { .. })).await {
+        Message::Profile { username, profile: public } => {
+            assert_eq!(username, "alice");
+            assert_eq!(public, profile.public());
+        }
+        _ => unreachable!(),
+    }
+
+    let mut invalid = profile.clone();
+    invalid.avatar = Some("xx".to_string());
+    alice.send(Message::UpdateProfile { profile: invalid }).await;
+    alice.expect(|m| matches!(m, Message::Error { code: ErrorCode::InvalidFormat, .. })).await;
+
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn test_deconnexion() {
+    let server = TestServer::start("deconnexion").await;
+    let mut alice = server.register("alice").await;
+    let mut bob = server.register("bob").await;
+    alice.join("general").await;
+    bob.join("general").await;
+    alice.expect(|m| matches!(m, Message::UserJoined { username, .. } if username == "bob")).await;
+
+    // Les autres membres l'apprennent tout de suite, et le compte est aussitôt libre
+    bob.disconnect().await;
+    alice.expect(|m| matches!(m, Message::UserLeft { username, room_id } if username == "bob" && room_id == "general")).await;
+    let mut bob = server.client().await;
+    bob.send(Message::Login { username: "bob".to_string(), password: PASSWORD.to_string() }).await;
+    bob.expect(|m| matches!(m, Message::ConnectAck { .. })).await;
+
+    // Sans être authentifié aussi
+    server.client().await.disconnect().await;
+
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn test_filtres() {
+    let server = TestServer::start_with("filtres", |config| {
+        config.rate_limits.chat_burst = 10;
+        config.filters.banned_words = vec!["zut".to_string()];
+        config.filters.rooms.insert("tech".to_string(), FilterRules { strip_links: true, ..FilterRules::default() });
+    }).await;
+    let mut alice = server.register("alice").await;
+    alice.join("general").await;
+
+    let say = |content: &str| Message::SendMessage { content: content.to_string(), kind: MessageKind::Text, parent_message_id: None };
+    alice.send(say("Zut, voir https://exemple.fr")).await;
+    alice.expect(|m| matches!(m, Message::RoomMessage { content, .. } if content == "***, voir https://exemple.fr")).await;
+
+    // La même chose trop souvent est refusée
+    for _ in 0..3 {
+        alice.send(say("encore")).await;
+        alice.expect(|m| matches!(m, Message::RoomMessage { content, .. } if content == "encore")).await;
+    }
+    alice.send(say("Encore")).await;
+    alice.expect(|m| matches!(m, Message::Error { code: ErrorCode::RateLimitExceeded, .. })).await;
+
+    // tech a ses propres réglages : liens retirés, mots permis
+    alice.join("tech").await;
+    alice.send(say("Zut, voir https://exemple.fr")).await;
+    alice.expect(|m| matches!(m, Message::RoomMessage { content, .. } if content == "Zut, voir [lien retiré]")).await;
+
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn test_horloge_decalee() {
+    let server = TestServer::start("horloge").await;
+    let mut alice = server.register("alice").await;
+    alice.join("general").await;
+    let say = |content: &str, offset: chrono::TimeDelta| {
+        let mut frame = ProtocolFrame::new(Message::SendMessage { content: content.to_string(), kind: MessageKind::Text, parent_message_id: None }, None, 0);
+        frame.timestamp += offset;
+        frame
+    };
+
+    // Un léger décalage est toléré : l'heure d'envoi est celle du client, l'heure du serveur est à côté
+    alice.send_frame(say("à l'heure", chrono::TimeDelta::seconds(-30))).await;
+    let Message::RoomMessage { timestamp, received_at: Some(received_at), .. } = alice.expect(|m| matches!(m, Message::RoomMessage { .. })).await else {
+        panic!("heure de réception absente");
+    };
+    assert!(received_at - timestamp >= chrono::TimeDelta::seconds(29));
+
+    // Une heure d'avance : signalé une fois, et l'heure du serveur remplace celle du client
+    alice.send_frame(say("en avance", chrono::TimeDelta::hours(1))).await;
+    alice.expect(|m| matches!(m, Message::ClockSkew { offset_secs } if (3590..=3600).contains(offset_secs))).await;
+    let Message::RoomMessage { timestamp, received_at: Some(received_at), .. } = alice.expect(|m| matches!(m, Message::RoomMessage { .. })).await else {
+        panic!("heure de réception absente");
+    };
+    assert!((received_at - timestamp).abs() < chrono::TimeDelta::seconds(1));
+    alice.send_frame(say("toujours en avance", chrono::TimeDelta::hours(1))).await;
+    assert!(matches!(alice.recv().await, Some(Message::RoomMessage { .. })));
+
+    // Les messages rejoués restent dans l'ordre du serveur
+    let mut bob = server.register("bob").await;
+    bob.join("general").await;
+    let Message::History { messages, .. } = bob.expect(|m| matches!(m, Message::History { .. })).await else {
+        unreachable!();
+    };
+    let contents: Vec<&str> = messages.iter().map(|e| e.content.as_str()).collect();
+    assert_eq!(contents, vec!["à l'heure", "en avance", "toujours en avance"]);
+
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn test_salon_complet() {
+    let server = TestServer::start_with("complet", |config| config.rooms[0].max_users = Some(1)).await;
+    let (mut alice, mut bob, mut carol) = (server.register("alice").await, server.register("bob").await, server.register("carol").await);
+    assert_eq!(alice.join("general").await, vec!["alice".to_string()]);
+
+    // Plein : refusé, ou en file d'attente sur demande
+    let join = |wait: bool| Message::JoinRoom { room_id: "general".to_string(), password: None, wait, spectator: false };
+    bob.send(join(false)).await;
+    bob.expect(|m| matches!(m, Message::Error { code: ErrorCode::RoomFull, .. })).await;
+    bob.send(join(true)).await;
+    bob.expect(|m| matches!(m, Message::RoomQueued { position: 1, .. })).await;
+    carol.send(join(true)).await;
+    carol.expect(|m| matches!(m, Message::RoomQueued { position: 2, .. })).await;
+
+    // Chaque départ fait entrer le suivant et avancer la file
+    alice.send(Message::LeaveRoom).await;
+    assert_eq!(bob.expect(|m| matches!(m, Message::JoinRoomAck { .. })).await, Message::JoinRoomAck {
+        room_id: "general".to_string(),
+        users: vec!["bob".to_string()],
+        topic: None,
+    });
+    carol.expect(|m| matches!(m, Message::RoomQueued { position: 1, .. })).await;
+    bob.disconnect().await;
+    carol.expect(|m| matches!(m, Message::JoinRoomAck { .. })).await;
+
+    // Quitter la file avant d'entrer
+    alice.send(join(true)).await;
+    alice.expect(|m| matches!(m, Message::RoomQueued { position: 1, .. })).await;
+    alice.send(Message::LeaveRoom).await;
+    carol.send(Message::LeaveRoom).await;
+    alice.send(Message::ListRooms).await;
+    let Message::RoomList { rooms, .. } = alice.expect(|m| matches!(m, Message::RoomList { .. })).await else {
+        unreachable!();
+    };
+    assert_eq!(rooms["general"], 0);
+
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn test_moderation() {
+    let server = TestServer::start_with("moderation", |config| {
+        config.admins.insert("alice".to_string());
+    }).await;
+    let (mut alice, mut bob, mut carol) = (server.register("alice").await, server.register("bob").await, server.register("carol").await);
+    carol.join("general").await;
+    bob.send(Message::CreateRoom {
+        room_id: "atelier".to_string(),
+        name: "Atelier".to_string(),
+        password: None,
+        invite_only: false,
+        max_users: None,
+    }).await;
+    bob.expect(|m| matches!(m, Message::CreateRoomAck { .. })).await;
+
+    // Réservé aux administrateurs ; le propriétaire d'un salon n'y est jamais sanctionné
+    carol.send(Message::KickUser { room_id: "general".to_string(), username: "bob".to_string() }).await;
+    carol.expect(|m| matches!(m, Message::Error { code: ErrorCode::PermissionDenied, .. })).await;
+    alice.send(Message::BanUser { room_id: "atelier".to_string(), username: "bob".to_string(), duration: None }).await;
+    alice.expect(|m| matches!(m, Message::Error { code: ErrorCode::PermissionDenied, .. })).await;
+
+    // Banni pour une seconde : sorti du salon, refusé à l'entrée, puis de nouveau admis
+    alice.send(Message::BanUser { room_id: "general".to_string(), username: "carol".to_string(), duration: Some(1) }).await;
+    carol.expect(|m| matches!(m, Message::UserBanned { username, .. } if username == "carol")).await;
+    carol.send(Message::JoinRoom { room_id: "general".to_string(), password: None, wait: false, spectator: false }).await;
+    carol.expect(|m| matches!(m, Message::Error { code: ErrorCode::Banned, .. })).await;
+    tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+    carol.join("general").await;
+
+    // Rendue muette : elle reste dans le salon mais ne peut plus y écrire
+    alice.send(Message::MuteUser { room_id: "general".to_string(), username: "carol".to_string(), duration: None }).await;
+    carol.expect(|m| matches!(m, Message::UserMuted { username, .. } if username == "carol")).await;
+    carol.send(Message::SendMessage { content: "Et moi ?".to_string(), kind: MessageKind::Text, parent_message_id: None }).await;
+    carol.expect(|m| matches!(m, Message::Error { code: ErrorCode::Muted, .. })).await;
+
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn test_spectateur() {
+    let server = TestServer::start_with("spectateur", |config| {
+        config.admins.insert("alice".to_string());
+    }).await;
+    let (mut alice, mut bob) = (server.register("alice").await, server.register("bob").await);
+    alice.join("general").await;
+    bob.send(Message::JoinRoom { room_id: "general".to_string(), password: None, wait: false, spectator: true }).await;
+    bob.expect(|m| matches!(m, Message::JoinRoomAck { .. })).await;
+
+    // Le spectateur lit le salon mais ne peut pas y écrire
+    alice.send(Message::SendMessage { content: "Bienvenue".to_string(), kind: MessageKind::Text, parent_message_id: None }).await;
+    bob.expect(|m| matches!(m, Message::RoomMessage { from, .. } if from == "alice")).await;
+    bob.send(Message::SendMessage { content: "Je peux parler ?".to_string(), kind: MessageKind::Text, parent_message_id: None }).await;
+    bob.expect(|m| matches!(m, Message::Error { code: ErrorCode::ReadOnly, .. })).await;
+    alice.send(Message::ListUsers).await;
+    let Message::UserList { spectators, .. } = alice.expect(|m| matches!(m, Message::UserList { .. })).await else {
+        unreachable!();
+    };
+    assert_eq!(spectators, vec!["bob".to_string()]);
+
+    // Seul un administrateur peut le laisser participer
+    bob.send(Message::PromoteUser { room_id: "general".to_string(), username: "bob".to_string() }).await;
+    bob.expect(|m| matches!(m, Message::Error { code: ErrorCode::PermissionDenied, .. })).await;
+    alice.send(Message::PromoteUser { room_id: "general".to_string(), username: "bob".to_string() }).await;
+    bob.expect(|m| matches!(m, Message::UserPromoted { username, by, .. } if username == "bob" && by == "alice")).await;
+    bob.send(Message::SendMessage { content: "Merci !".to_string(), kind: MessageKind::Text, parent_message_id: None }).await;
+    alice.expect(|m| matches!(m, Message::RoomMessage { from, .. } if from == "bob")).await;
+    alice.send(Message::PromoteUser { room_id: "general".to_string(), username: "bob".to_string() }).await;
+    alice.expect(|m| matches!(m, Message::Error { code: ErrorCode::UserNotFound, .. })).await;
+
+    server.stop().await;
+}
+
+/// Envoyer un message au salon, en réponse à `parent` s'il est donné ; renvoie son identifiant et son parent
+async fn say(client: &mut ClientHandle, content: &str, parent: Option<String>) -> (String, Option<String>) {
+    client.send(Message::SendMessage { content: content.to_string(), kind: MessageKind::Text, parent_message_id: parent }).await;
+    match client.expect(|m| matches!(m, Message::RoomMessage { content: c, .. } if c == content)).await {
+        Message::RoomMessage { message_id, parent_message_id, .. } => (message_id, parent_message_id),
+        _ => unreachable!(),
+    }
+}
+
+#[tokio::test]
+async fn test_messages_non_lus() {
+    let server = TestServer::start("non_lus").await;
+    let (mut alice, mut bob) = (server.register("alice").await, server.register("bob").await);
+    alice.join("general").await;
+    bob.join("general").await;
+    for content in ["un", "deux", "trois"] {
+        say(&mut alice, content, None).await;
+    }
+    let Message::RoomMessage { sequence: first, .. } = bob.expect(|m| matches!(m, Message::RoomMessage { content, .. } if content == "un")).await else { unreachable!() };
+    bob.expect(|m| matches!(m, Message::RoomMessage { content, .. } if content == "trois")).await;
+
+    // Lu jusqu'au premier : deux messages d'alice restent à lire
+    bob.send(Message::MarkRead { room_id: "general".to_string(), sequence: first }).await;
+    bob.send(Message::ListRooms).await;
+    let Message::RoomList { unread, .. } = bob.expect(|m| matches!(m, Message::RoomList { .. })).await else { unreachable!() };
+    assert_eq!(unread.get("general"), Some(&2));
+
+    // Pas au-delà du dernier message du salon
+    bob.send(Message::MarkRead { room_id: "general".to_string(), sequence: first + 3 }).await;
+    bob.expect(|m| matches!(m, Message::Error { .. })).await;
+
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn test_fil_de_discussion() {
+    let server = TestServer::start("fil").await;
+    let (mut alice, mut bob) = (server.register("alice").await, server.register("bob").await);
+    alice.join("general").await;
+    bob.join("general").await;
+
+    let (question, _) = say(&mut alice, "Qui vient ce soir ?", None).await;
+    say(&mut alice, "Autre chose", None).await;
+    let (answer, parent) = say(&mut bob, "Moi", Some(question.clone())).await;
+    assert_eq!(parent, Some(question.clone()));
+
+    // Le parent doit être dans l'historique du salon
+    bob.send(Message::SendMessage { content: "Hein ?".to_string(), kind: MessageKind::Text, parent_message_id: Some("inconnu".to_string()) }).await;
+    bob.expect(|m| matches!(m, Message::Error { code: ErrorCode::InvalidState, .. })).await;
+
+    alice.send(Message::GetThread { message_id: answer }).await;
+    let Message::Thread { messages, .. } = alice.expect(|m| matches!(m, Message::Thread { .. })).await else {
+        unreachable!();
+    };
+    let contents: Vec<&str> = messages.iter().map(|e| e.content.as_str()).collect();
+    assert_eq!(contents, vec!["Qui vient ce soir ?", "Moi"]);
+    assert_eq!(messages[1].parent_id, Some(question));
+
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn test_blocage() {
+    let server = TestServer::start("blocage").await;
+    let (mut alice, mut bob) = (server.register("alice").await, server.register("bob").await);
+    alice.join("general").await;
+
+    bob.send(Message::BlockUser { username: "alice".to_string() }).await;
+    // Le profil reçu à la connexion vient d'abord
+    bob.expect(|m| matches!(m, Message::Profile { profile, .. } if profile.blocks("alice"))).await;
+    bob.send(Message::BlockUser { username: "personne".to_string() }).await;
+    bob.expect(|m| matches!(m, Message::Error { code: ErrorCode::UserNotFound, .. })).await;
+
+    // L'expéditrice bloquée le sait ; ses mentions ne parviennent plus
+    alice.send(Message::PrivateMessage { target_user: "bob".to_string(), content: "Tu es là ?".to_string() }).await;
+    alice.expect(|m| matches!(m, Message::Error { code: ErrorCode::Blocked, .. })).await;
+    say(&mut alice, "@bob tu m'entends ?", None).await;
+
+    bob.send(Message::UnblockUser { username: "alice".to_string() }).await;
+    bob.expect(|m| matches!(m, Message::Profile { profile, .. } if profile.blocked.is_empty())).await;
+    say(&mut alice, "@bob et maintenant ?", None).await;
+    match bob.expect(|m| matches!(m, Message::Mention { .. } | Message::PrivateMessageReceived { .. })).await {
+        Message::Mention { content, .. } => assert_eq!(content, "@bob et maintenant ?"),
+        other => panic!("reçu {:?}", other),
+    }
+
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn test_statistiques() {
+    let server = TestServer::start_with("statistiques", |config| {
+        config.admins.insert("alice".to_string());
+    }).await;
+    let (mut alice, mut bob) = (server.register("alice").await, server.register("bob").await);
+    alice.join("general").await;
+    say(&mut alice, "abcd", None).await;
+    say(&mut alice, "abcdefgh", None).await;
+
+    // Réservé aux administrateurs du serveur, et le refus est compté
+    bob.send(Message::GetStats).await;
+    bob.expect(|m| matches!(m, Message::Error { code: ErrorCode::PermissionDenied, .. })).await;
+
+    alice.send(Message::GetStats).await;
+    let Message::Stats { stats } = alice.expect(|m| matches!(m, Message::Stats { .. })).await else { unreachable!() };
+    let general = &stats.rooms["general"];
+    assert_eq!((general.messages, general.messages_per_minute, general.active_users, general.members), (2, 2, 1, 1));
+    assert_eq!(general.average_size, 6.0);
+    assert_eq!((stats.connected, stats.active_users), (2, 1));
+    assert_eq!(stats.errors[&ErrorCode::PermissionDenied], 1);
+    assert_eq!(stats.rooms["tech"].messages, 0);
+
+    server.stop().await;
+}
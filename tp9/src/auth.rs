@@ -0,0 +1,76 @@
+// src/auth.rs
+// Jetons d'authentification : chaque jeton accepté désigne l'identité de son porteur
+
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Jetons acceptés par le serveur, avec l'identité de leur porteur
+#[derive(Debug, Default)]
+pub struct Tokens {
+    identities: HashMap<String, String>,
+}
+
+impl Tokens {
+    /// Une ligne par jeton, `<jeton> <identité>` ; les lignes vides et celles qui commencent par `#` sont ignorées
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut identities = HashMap::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let Some((token, identity)) = line.split_once(char::is_whitespace) else {
+                return Err(format!("ligne {} : identité manquante après le jeton", number + 1));
+            };
+            if identities.insert(token.to_string(), identity.trim().to_string()).is_some() {
+                return Err(format!("ligne {} : jeton déjà défini", number + 1));
+            }
+        }
+        Ok(Self { identities })
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        Self::parse(&text).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    /// Aucun jeton configuré : l'authentification est désactivée
+    pub fn is_empty(&self) -> bool {
+        self.identities.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.identities.len()
+    }
+
+    /// Identité du porteur de `token`, s'il est accepté
+    pub fn identity(&self, token: &str) -> Option<&str> {
+        self.identities.get(token).map(String::as_str)
+    }
+}
+
+/// Jeton passé en paramètre `token` de l'URL de connexion (`/ws?token=...`)
+pub fn token_from_query(query: &str) -> Option<String> {
+    url::form_urlencoded::parse(query.as_bytes())
+        .find(|(name, _)| name == "token")
+        .map(|(_, value)| value.into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokens() {
+        let tokens = Tokens::parse("# jetons\nabc123 alice\n\n  xyz  Bob Martin\n").unwrap();
+        assert_eq!(tokens.len(), 2);
+        assert_eq!(tokens.identity("abc123"), Some("alice"));
+        assert_eq!(tokens.identity("xyz"), Some("Bob Martin"));
+        assert_eq!(tokens.identity("alice"), None);
+        assert!(Tokens::parse("seul").unwrap_err().starts_with("ligne 1"));
+        assert!(Tokens::parse("a x\na y").unwrap_err().starts_with("ligne 2"));
+
+        assert_eq!(token_from_query("room=x&token=a%2Bb"), Some("a+b".to_string()));
+        assert_eq!(token_from_query("room=x"), None);
+    }
+}
@@ -1,32 +1,531 @@
-use tokio_tungstenite::connect_async;
+use tokio::fs::File;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader, Lines, Stdin};
+use tokio::net::TcpStream;
+use tokio::sync::mpsc::{self, UnboundedSender};
+use tokio::time::{interval_at, sleep, Instant};
+use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream};
+use tungstenite::error::ProtocolError;
+use tungstenite::{Error, Message};
 use url::Url;
 use futures_util::{SinkExt, StreamExt};
+use std::collections::{HashMap, VecDeque};
 use std::io::{self, Write};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
-#[tokio::main]
-async fn main() {
-    let url = Url::parse("ws://127.0.0.1:9001").unwrap();
-    let (mut ws_stream, _) = connect_async(url).await.expect("Connexion échouée");
+use tp7_dns::client::{ClientDns, SERVEUR_PAR_DEFAUT};
+use tp9::latency::Latency;
+use tp9::protocol::{BlobHeader, WsMessage, BLOB_CHUNK_SIZE, CLOSE_UNAUTHORIZED};
 
-    println!("Connecté au serveur WebSocket. Tape un message :");
+/// Adresse du serveur sans --discover
+const DEFAULT_ADDR: &str = "127.0.0.1:9001";
 
-    loop {
-        print!("> ");
-        io::stdout().flush().unwrap();
+/// Nom SRV sous lequel tp7 annonce le serveur WebSocket
+const WS_SERVICE: &str = "_ws._tcp.local";
+
+/// Dossier où sont écrits les fichiers reçus
+const DOWNLOAD_DIR: &str = "downloads";
+
+/// Attente avant la première tentative de reconnexion, doublée à chaque échec jusqu'à `MAX_RECONNECT_DELAY`
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(30);
+
+/// Intervalle entre deux mesures de latence en arrière-plan
+const LATENCY_SAMPLE_INTERVAL: Duration = Duration::from_secs(10);
 
-        let mut input = String::new();
-        io::stdin().read_line(&mut input).unwrap();
-        let input = input.trim();
+/// Mesures de latence gardées pour /stats
+const LATENCY_SAMPLES: usize = 100;
+
+/// Messages gardés pendant une déconnexion ; au-delà, les plus anciens sont abandonnés
+const MAX_PENDING: usize = 100;
+
+/// Affichage d'un message du serveur
+fn render(message: WsMessage) -> String {
+    match message {
+        WsMessage::Chat { from, text } => format!("{}: {}", from, text),
+        WsMessage::Join { name, room: Some(room) } => format!("{} a rejoint {}", name, room),
+        WsMessage::Join { name, room: None } => format!("Connecté en tant que {}", name),
+        WsMessage::Leave { name, room: Some(room) } => format!("{} a quitté {}", name, room),
+        WsMessage::Leave { name, room: None } => format!("{} est parti", name),
+        WsMessage::History { room, messages } => {
+            let mut lines = vec![format!("--- Derniers messages de {} ---", room)];
+            lines.extend(messages.into_iter().map(render));
+            lines.push("---".to_string());
+            lines.join("\n")
+        }
+        WsMessage::Subscribe { topic } => format!("Abonné à {}", topic),
+        WsMessage::Unsubscribe { topic } => format!("Désabonné de {}", topic),
+        WsMessage::Publish { topic, from, payload } => format!("[{}] {}: {}", topic, from, payload),
+        WsMessage::Announcement { text } => format!("*** Annonce du serveur : {} ***", text),
+        WsMessage::Auth { .. } => "Message d'authentification inattendu".to_string(),
+        WsMessage::Ping { .. } => "Pong".to_string(),
+        WsMessage::Error { message } => format!("Erreur : {}", message),
+    }
+}
 
-        if input == "exit" {
+/// Changement d'état de la connexion
+fn state(line: &str) {
+    println!("\r[état] {}", line);
+}
+
+fn prompt() {
+    print!("> ");
+    io::stdout().flush().unwrap();
+}
+
+/// Affiche une ligne reçue pendant que l'utilisateur tape, puis réaffiche l'invite
+fn show(line: String) {
+    println!("\r{}", line);
+    prompt();
+}
+
+/// Type de contenu annoncé pour un fichier, d'après son extension
+fn content_type_of(path: &Path) -> &'static str {
+    match path.extension().and_then(|extension| extension.to_str()).map(|extension| extension.to_lowercase()).as_deref() {
+        Some("txt" | "md") => "text/plain",
+        Some("html") => "text/html",
+        Some("json") => "application/json",
+        Some("pdf") => "application/pdf",
+        Some("png") => "image/png",
+        Some("jpg" | "jpeg") => "image/jpeg",
+        Some("gif") => "image/gif",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Envoie un fichier au salon en trames binaires, en affichant l'avancement
+async fn send_file(path: PathBuf, frames: UnboundedSender<Message>) {
+    let name = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+    let (mut file, total) = match File::open(&path).await {
+        Ok(file) => match file.metadata().await {
+            Ok(metadata) if metadata.is_file() => (file, metadata.len()),
+            Ok(_) => return show(format!("{} n'est pas un fichier", path.display())),
+            Err(e) => return show(format!("Impossible de lire {} : {}", path.display(), e)),
+        },
+        Err(e) => return show(format!("Impossible d'ouvrir {} : {}", path.display(), e)),
+    };
+    let content_type = content_type_of(&path).to_string();
+    let mut buffer = vec![0u8; BLOB_CHUNK_SIZE];
+    let mut offset = 0;
+    let mut shown = None;
+    loop {
+        let read = match file.read(&mut buffer).await {
+            Ok(read) => read,
+            Err(e) => return show(format!("Erreur de lecture de {} : {}", path.display(), e)),
+        };
+        let header = BlobHeader { from: String::new(), content_type: content_type.clone(), name: Some(name.clone()), offset, total };
+        // Un fichier vide part quand même, en une trame sans données
+        if (read > 0 || offset == 0) && frames.send(header.to_frame(&buffer[..read])).is_err() {
+            return show(format!("Envoi de {} interrompu", name));
+        }
+        offset += read as u64;
+        // Avancement par dizaines de pour cent
+        let percent = (offset * 100).checked_div(total).unwrap_or(100);
+        if shown != Some(percent / 10) {
+            shown = Some(percent / 10);
+            show(format!("Envoi de {} : {} % ({}/{} octets)", name, percent, offset, total));
+        }
+        if read == 0 || offset >= total {
             break;
         }
+    }
+}
+
+/// Range une trame binaire reçue ; renvoie le message à afficher quand un fichier est complet.
+/// Les fichiers en cours sont indexés par (expéditeur, nom)
+async fn receive_blob(files: &mut HashMap<(String, String), File>, frame: &[u8]) -> Result<Option<String>, String> {
+    let (header, data) = BlobHeader::parse(frame)?;
+    let Some(name) = &header.name else {
+        return Ok(Some(format!("{} a envoyé {} octets ({})", header.from, data.len(), header.content_type)));
+    };
+    // Seul le nom compte : pas de chemin choisi par l'expéditeur
+    let name = Path::new(name).file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_else(|| "fichier".to_string());
+    let key = (header.from.clone(), name.clone());
+    let path = Path::new(DOWNLOAD_DIR).join(&name);
+    if header.offset == 0 {
+        tokio::fs::create_dir_all(DOWNLOAD_DIR).await.map_err(|e| e.to_string())?;
+        let file = File::create(&path).await.map_err(|e| format!("impossible de créer {} : {}", path.display(), e))?;
+        files.insert(key.clone(), file);
+    }
+    let Some(file) = files.get_mut(&key) else {
+        return Err(format!("morceau de {} reçu sans son début", name));
+    };
+    file.write_all(data).await.map_err(|e| format!("erreur d'écriture de {} : {}", path.display(), e))?;
+    if !header.is_last(data) {
+        return Ok(None);
+    }
+    if let Some(mut file) = files.remove(&key) {
+        file.flush().await.map_err(|e| e.to_string())?;
+    }
+    Ok(Some(format!("Fichier reçu de {} : {} ({} octets, {})", header.from, path.display(), header.total, header.content_type)))
+}
+
+/// Lignes tapées par l'utilisateur, lues sur l'entrée standard
+type Input = Lines<BufReader<Stdin>>;
+
+/// Fin d'une session
+enum SessionEnd {
+    /// L'utilisateur a quitté
+    Quit,
+    /// Le serveur a fermé la connexion, ou elle a été perdue
+    Lost,
+    /// Le serveur n'accepte pas notre jeton
+    Refused,
+}
+
+/// Ce que l'utilisateur a tapé
+enum Command {
+    Quit,
+    /// Fichier à envoyer, morceau par morceau
+    SendFile(PathBuf),
+    /// Ping horodaté, dont l'aller-retour est affiché
+    Ping,
+    /// Statistiques de latence
+    Stats,
+    Message(WsMessage),
+}
+
+/// Interprète une ligne non vide ; un changement de salon met à jour `room`
+fn parse_input(input: &str, name: &str, room: &mut String) -> Command {
+    if let Some(path) = input.strip_prefix("/send ") {
+        return Command::SendFile(PathBuf::from(path.trim()));
+    }
+    let message = match input {
+        "exit" => return Command::Quit,
+        "/ping" => return Command::Ping,
+        "/stats" => return Command::Stats,
+        text => WsMessage::from_command(text).unwrap_or_else(|| WsMessage::Chat { from: name.to_string(), text: text.to_string() }),
+    };
+    if let WsMessage::Join { room: Some(joined), .. } = &message {
+        *room = joined.clone();
+    }
+    Command::Message(message)
+}
+
+/// Résumé des mesures de latence, pour /stats
+fn latency_stats(latency: &Mutex<Latency>) -> String {
+    match latency.lock().unwrap().stats() {
+        Some(stats) => format!(
+            "Latence sur {} mesure(s) : dernière {} ms, min {} ms, moyenne {} ms, max {} ms",
+            stats.count,
+            stats.last.as_millis(),
+            stats.min.as_millis(),
+            stats.average.as_millis(),
+            stats.max.as_millis()
+        ),
+        None => "Aucune mesure de latence pour l'instant".to_string(),
+    }
+}
+
+/// Garde un message à envoyer à la prochaine connexion
+fn queue(pending: &mut VecDeque<Message>, frame: Message) {
+    if pending.len() == MAX_PENDING {
+        pending.pop_front();
+        show(format!("File d'attente pleine : le plus ancien des {} messages en attente est abandonné", MAX_PENDING));
+    }
+    pending.push_back(frame);
+}
+
+/// Une connexion au serveur, jusqu'à sa fermeture ; les Ping du serveur reçoivent leur Pong de tungstenite.
+/// Les messages de `pending` partent juste après l'entrée dans le salon ; ceux qui n'ont pas pu partir y reviennent
+async fn session(
+    ws_stream: WebSocketStream<MaybeTlsStream<TcpStream>>,
+    name: &str,
+    room: &mut String,
+    lines: &mut Input,
+    pending: &mut VecDeque<Message>,
+    latency: &Arc<Mutex<Latency>>,
+) -> SessionEnd {
+    let (mut write, mut read) = ws_stream.split();
+
+    // --- Tâche d'envoi ---
+    // Les trames à envoyer arrivent par `tx_frames` ; une trame Close termine l'envoi. La tâche
+    // renvoie les messages texte qu'elle n'a pas pu envoyer
+    let (tx_frames, mut rx_frames) = mpsc::unbounded_channel::<Message>();
+    let send_task = tokio::spawn(async move {
+        let mut unsent = Vec::new();
+        while let Some(frame) = rx_frames.recv().await {
+            let closing = frame.is_close();
+            match write.send(frame.clone()).await {
+                Ok(()) => {}
+                // Le serveur a fermé tout de suite (refus, serveur plein...) : la réception l'affiche
+                Err(Error::Protocol(ProtocolError::SendAfterClosing)) => {
+                    unsent.push(frame);
+                    break;
+                }
+                Err(e) => {
+                    show(format!("Erreur d'envoi : {}", e));
+                    unsent.push(frame);
+                    break;
+                }
+            }
+            if closing {
+                break;
+            }
+        }
+        rx_frames.close();
+        while let Ok(frame) = rx_frames.try_recv() {
+            unsent.push(frame);
+        }
+        // Les morceaux de fichier isolés seraient inutilisables : seuls les messages texte sont repris
+        unsent.retain(|frame| frame.is_text());
+        unsent
+    });
 
-        ws_stream.send(input.into()).await.unwrap();
+    // --- Tâche de réception ---
+    // Les messages du serveur s'affichent dès leur arrivée, même pendant la saisie ; la tâche
+    // renvoie le code de la trame Close du serveur
+    let receive_latency = latency.clone();
+    let mut receive_task = tokio::spawn(async move {
+        let mut files = HashMap::new();
+        let mut close_code = None;
+        while let Some(msg) = read.next().await {
+            match msg {
+                Ok(Message::Binary(frame)) => match receive_blob(&mut files, &frame).await {
+                    Ok(Some(line)) => show(line),
+                    Ok(None) => {}
+                    Err(e) => show(format!("Erreur de réception : {}", e)),
+                },
+                Ok(Message::Text(text)) => match WsMessage::parse(&text) {
+                    Ok(WsMessage::Ping { stamp: Some(stamp) }) => {
+                        // Les mesures en arrière-plan ne s'affichent pas
+                        let answer = receive_latency.lock().unwrap().answer(stamp);
+                        if let Some((rtt, true)) = answer {
+                            show(format!("Pong : aller-retour {} ms", rtt.as_millis()));
+                        }
+                    }
+                    Ok(message) => show(render(message)),
+                    Err(_) => show(format!("Réponse du serveur : {}", text)),
+                },
+                // La lecture suivante envoie notre réponse et termine le flux
+                Ok(Message::Close(Some(frame))) => {
+                    show(format!("Connexion fermée par le serveur ({}: {})", frame.code, frame.reason));
+                    close_code = Some(u16::from(frame.code));
+                }
+                Ok(Message::Close(None)) => show("Connexion fermée par le serveur".to_string()),
+                Ok(_) => {}
+                Err(e) => {
+                    show(format!("Erreur de lecture : {}", e));
+                    break;
+                }
+            }
+        }
+        close_code
+    });
 
-        if let Some(msg) = ws_stream.next().await {
-            let msg = msg.unwrap();
-            println!("Réponse du serveur : {}", msg);
+    // Un nom et un salon à chaque connexion : après une reconnexion, le dernier salon rejoint
+    let join = WsMessage::Join { name: name.to_string(), room: Some(room.clone()) }.to_frame();
+    let _ = tx_frames.send(join.clone());
+    if !pending.is_empty() {
+        show(format!("Envoi de {} message(s) en attente", pending.len()));
+        for frame in pending.drain(..) {
+            let _ = tx_frames.send(frame);
+        }
+    } else {
+        prompt();
+    }
+
+    // --- Mesure de la latence en arrière-plan ---
+    let sampler_frames = tx_frames.clone();
+    let sampler_latency = latency.clone();
+    let sampler = tokio::spawn(async move {
+        let mut ticks = interval_at(Instant::now() + LATENCY_SAMPLE_INTERVAL, LATENCY_SAMPLE_INTERVAL);
+        loop {
+            ticks.tick().await;
+            let stamp = sampler_latency.lock().unwrap().stamp(false);
+            if sampler_frames.send(WsMessage::Ping { stamp: Some(stamp) }.to_frame()).is_err() {
+                break;
+            }
+        }
+    });
+
+    // --- Saisie ---
+    let end = loop {
+        let line = tokio::select! {
+            line = lines.next_line() => line,
+            close_code = &mut receive_task => {
+                sampler.abort();
+                drop(tx_frames);
+                // Les messages non envoyés repartiront en tête, avant ceux tapés pendant la déconnexion
+                if let Ok(unsent) = send_task.await {
+                    for frame in unsent.into_iter().rev().filter(|frame| *frame != join) {
+                        pending.push_front(frame);
+                    }
+                }
+                // Se reconnecter avec le même jeton serait refusé de nouveau
+                if let Ok(Some(CLOSE_UNAUTHORIZED)) = close_code {
+                    return SessionEnd::Refused;
+                }
+                return SessionEnd::Lost;
+            }
+        };
+        let Ok(Some(line)) = line else {
+            break SessionEnd::Quit;
+        };
+        let input = line.trim();
+        if input.is_empty() {
+            prompt();
+            continue;
+        }
+        match parse_input(input, name, room) {
+            Command::Quit => break SessionEnd::Quit,
+            Command::SendFile(path) => {
+                tokio::spawn(send_file(path, tx_frames.clone()));
+            }
+            Command::Ping => {
+                let stamp = latency.lock().unwrap().stamp(true);
+                let _ = tx_frames.send(WsMessage::Ping { stamp: Some(stamp) }.to_frame());
+            }
+            Command::Stats => show(latency_stats(latency)),
+            Command::Message(message) => {
+                if let Err(unsent) = tx_frames.send(message.to_frame()) {
+                    queue(pending, unsent.0);
+                }
+            }
+        }
+        prompt();
+    };
+
+    // Fermeture propre : le serveur répond à Leave par une trame Close, ce qui termine la réception
+    sampler.abort();
+    let _ = tx_frames.send(WsMessage::Leave { name: name.to_string(), room: None }.to_frame());
+    let _ = receive_task.await;
+    drop(tx_frames);
+    let _ = send_task.await;
+    end
+}
+
+/// Attend `delay` avant la prochaine tentative de connexion ; les messages tapés entre-temps sont
+/// mis en attente. Renvoie false si l'utilisateur quitte
+async fn wait_offline(
+    delay: Duration,
+    name: &str,
+    room: &mut String,
+    lines: &mut Input,
+    pending: &mut VecDeque<Message>,
+    latency: &Mutex<Latency>,
+) -> bool {
+    let reconnect = sleep(delay);
+    tokio::pin!(reconnect);
+    loop {
+        let line = tokio::select! {
+            _ = &mut reconnect => return true,
+            line = lines.next_line() => line,
+        };
+        let Ok(Some(line)) = line else {
+            return false;
+        };
+        let input = line.trim();
+        if input.is_empty() {
+            prompt();
+            continue;
+        }
+        match parse_input(input, name, room) {
+            Command::Quit => return false,
+            Command::SendFile(_) => show("Non connecté : fichier non envoyé, réessayer une fois reconnecté".to_string()),
+            Command::Ping => show("Non connecté : pas de mesure possible".to_string()),
+            Command::Stats => show(latency_stats(latency)),
+            Command::Message(message) => {
+                queue(pending, message.to_frame());
+                show(format!("Non connecté : message mis en attente ({} en attente)", pending.len()));
+            }
         }
     }
 }
+
+/// Adresse annoncée par l'enregistrement SRV de tp7 ; le client DNS est bloquant, il tourne hors des tâches async
+async fn discover_server(dns: SocketAddr) -> Result<SocketAddr, String> {
+    let client = ClientDns::new(dns);
+    match tokio::task::spawn_blocking(move || client.decouvrir(WS_SERVICE)).await {
+        Ok(resultat) => resultat.map_err(|e| e.to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    // Arguments : `client [nom] [salon] [--token <jeton>] [--discover [--dns <ip:port>]]` ; avec un jeton,
+    // le serveur impose son identité ; avec --discover, son adresse est demandée à tp7
+    let mut positional = Vec::new();
+    let mut token = None;
+    let mut discover = false;
+    let mut dns: SocketAddr = SERVEUR_PAR_DEFAUT.parse().unwrap();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--token" => token = Some(args.next().expect("--token demande un jeton")),
+            "--discover" => discover = true,
+            "--dns" => dns = args.next().and_then(|addr| addr.parse().ok()).expect("--dns demande une adresse ip:port"),
+            _ => positional.push(arg),
+        }
+    }
+    let mut positional = positional.into_iter();
+    let name = positional.next().unwrap_or_else(|| "anonyme".to_string());
+    let mut room = positional.next().unwrap_or_else(|| "general".to_string());
+
+    let addr = if discover {
+        match discover_server(dns).await {
+            Ok(addr) => {
+                println!("Serveur découvert via {} ({}) : {}", dns, WS_SERVICE, addr);
+                addr
+            }
+            Err(e) => {
+                eprintln!("Découverte de {} via {} impossible : {}", WS_SERVICE, dns, e);
+                std::process::exit(1);
+            }
+        }
+    } else {
+        DEFAULT_ADDR.parse().unwrap()
+    };
+    let mut url = Url::parse(&format!("ws://{}/ws", addr)).unwrap();
+    if let Some(token) = &token {
+        url.query_pairs_mut().append_pair("token", token);
+    }
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    let mut pending = VecDeque::new();
+    // Les mesures survivent aux reconnexions
+    let latency = Arc::new(Mutex::new(Latency::new(LATENCY_SAMPLES)));
+    let mut delay = RECONNECT_DELAY;
+    let mut attempt = 1;
+
+    println!("Tape un message (join:<salon> pour changer de salon, /send <fichier> pour envoyer un fichier, /ping pour mesurer la latence, /stats pour ses statistiques, exit pour quitter)");
+    println!("Sujets : subscribe:<sujet>, unsubscribe:<sujet>, publish:<sujet>:<contenu>");
+    loop {
+        state(&format!("Connexion à {} (tentative {})...", url.as_str().split('?').next().unwrap_or_default(), attempt));
+        match connect_async(url.clone()).await {
+            Ok((ws_stream, _)) => {
+                state("Connecté.");
+                // Une connexion établie remet l'attente à zéro
+                (delay, attempt) = (RECONNECT_DELAY, 1);
+                let end = session(ws_stream, &name, &mut room, &mut lines, &mut pending, &latency).await;
+                state("Déconnecté.");
+                match end {
+                    SessionEnd::Quit => break,
+                    SessionEnd::Refused => {
+                        println!("Authentification refusée : vérifier le jeton (--token)");
+                        break;
+                    }
+                    SessionEnd::Lost => {}
+                }
+            }
+            Err(e) => {
+                state(&format!("Connexion échouée : {}", e));
+                attempt += 1;
+            }
+        }
+
+        // Attente avant de se reconnecter ; l'utilisateur peut continuer à écrire, ou quitter
+        state(&format!("Nouvelle tentative dans {} s...", delay.as_secs()));
+        prompt();
+        if !wait_offline(delay, &name, &mut room, &mut lines, &mut pending, &latency).await {
+            break;
+        }
+        delay = (delay * 2).min(MAX_RECONNECT_DELAY);
+    }
+    if !pending.is_empty() {
+        println!("\r{} message(s) en attente non envoyé(s)", pending.len());
+    }
+    println!();
+}
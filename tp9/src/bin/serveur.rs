@@ -1,40 +1,623 @@
-use tokio::net::TcpListener;
-use tokio_tungstenite::accept_async;
-//use futures_util::{StreamExt, SinkExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc::{self, UnboundedSender};
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::sync::{broadcast, watch};
+use tokio::task::JoinSet;
+use tokio::time::{interval_at, timeout, Instant};
+use tokio_rustls::TlsAcceptor;
+use tracing::{debug, error, info, warn};
+use tokio_tungstenite::{accept_async_with_config, accept_hdr_async_with_config, WebSocketStream};
+use tungstenite::handshake::server::{ErrorResponse, Request, Response};
+use tungstenite::http::StatusCode;
+use tungstenite::protocol::frame::coding::CloseCode;
+use tungstenite::protocol::{CloseFrame, WebSocketConfig};
+use tungstenite::{Error, Message};
+use clap::Parser;
+use std::io;
 use std::net::SocketAddr;
-use futures_util::stream::StreamExt; // pour `.next()` et `.split()`
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use futures_util::stream::{SplitStream, StreamExt}; // pour `.next()` et `.split()`
 use futures_util::sink::SinkExt;     // pour `.send()`
 
+use config_commun::ConfigError;
+use tp8::chiffrement::{self, Transport};
+use tp9::auth::{token_from_query, Tokens};
+use tp9::bridge;
+use tp9::config::Config;
+use tp9::http;
+use tp9::limits::RateLimiter;
+use tp9::metrics::Metrics;
+use tp9::protocol::{BlobHeader, WsMessage, CLOSE_UNAUTHORIZED};
+use tp9::rooms::Rooms;
+use tp9::topics::Topics;
 
+/// Connexion d'un client, chiffrée ou non
+type Stream = Box<dyn Transport>;
 
-#[tokio::main]
-async fn main() {
-    let addr = "127.0.0.1:9001".parse::<SocketAddr>().unwrap();
-    let listener = TcpListener::bind(&addr).await.expect("Erreur bind serveur");
+/// État du serveur partagé par les connexions
+struct Shared {
+    config: Config,
+    tokens: Tokens,
+    /// Partagé aussi avec le pont SCP
+    rooms: Arc<Rooms>,
+    topics: Topics,
+    /// Annonces tapées par l'opérateur sur l'entrée standard du serveur
+    announcements: broadcast::Sender<String>,
+    metrics: Metrics,
+    /// Présent quand la configuration demande TLS
+    tls: Option<TlsAcceptor>,
+}
 
-    println!("Serveur WebSocket en écoute sur {}", addr);
+impl Shared {
+    /// Limites de taille des messages reçus
+    fn ws_config(&self) -> WebSocketConfig {
+        WebSocketConfig {
+            max_message_size: Some(self.config.max_message_size),
+            max_frame_size: Some(self.config.max_message_size),
+            ..WebSocketConfig::default()
+        }
+    }
+}
 
-    while let Ok((stream, addr)) = listener.accept().await {
-        tokio::spawn(async move {
-            let ws_stream = accept_async(stream)
-                .await
-                .expect("Erreur handshake WebSocket");
-            println!("Nouvelle connexion de : {}", addr);
+/// État d'une connexion pour le traitement de ses messages
+struct Connection {
+    addr: SocketAddr,
+    /// Identité donnée par le jeton, sinon nom annoncé par le client (son adresse tant qu'il ne s'est pas présenté)
+    name: String,
+    authenticated: bool,
+    sender: UnboundedSender<Message>,
+    shared: Arc<Shared>,
+}
+
+impl Connection {
+    fn reply(&self, message: WsMessage) {
+        let _ = self.sender.send(message.to_frame());
+    }
+
+    /// Sortir du salon actuel en prévenant ceux qui y restent
+    fn leave_room(&self) -> Option<String> {
+        let room = self.shared.rooms.leave(self.addr)?;
+        self.shared.rooms.broadcast(&room, &WsMessage::Leave { name: self.name.clone(), room: Some(room.clone()) });
+        Some(room)
+    }
+
+    /// Traite un message du client ; renvoie false quand le client s'en va
+    fn handle(&mut self, message: WsMessage) -> bool {
+        match message {
+            WsMessage::Chat { text, .. } => match self.shared.rooms.room_of(self.addr) {
+                Some(room) => {
+                    self.shared.rooms.broadcast(&room, &WsMessage::Chat { from: self.name.clone(), text });
+                    self.shared.metrics.message_relayed();
+                }
+                None => self.reply(WsMessage::Error {
+                    message: format!("aucun salon : join:<salon> d'abord (par exemple join:{})", self.shared.config.default_room()),
+                }),
+            },
+            WsMessage::Join { name, room } => {
+                // Un client authentifié garde l'identité de son jeton
+                if !name.is_empty() && !self.authenticated {
+                    self.name = name;
+                }
+                match room {
+                    Some(room) if !self.shared.config.room_allowed(&room) => self.reply(WsMessage::Error {
+                        message: format!("salon {} fermé ; salons ouverts : {}", room, self.shared.config.rooms.join(", ")),
+                    }),
+                    Some(room) => {
+                        if let Some(previous) = self.shared.rooms.join(&room, self.addr, self.sender.clone()) {
+                            self.shared.rooms.broadcast(&previous, &WsMessage::Leave { name: self.name.clone(), room: Some(previous.clone()) });
+                        }
+                        // Le nouvel arrivant reçoit aussi l'annonce, qui lui sert de confirmation
+                        self.shared.rooms.broadcast(&room, &WsMessage::Join { name: self.name.clone(), room: Some(room.clone()) });
+                    }
+                    None => self.reply(WsMessage::Join { name: self.name.clone(), room: None }),
+                }
+            }
+            WsMessage::Leave { room: Some(_), .. } => match self.leave_room() {
+                Some(room) => self.reply(WsMessage::Leave { name: self.name.clone(), room: Some(room) }),
+                None => self.reply(WsMessage::Error { message: "aucun salon à quitter".to_string() }),
+            },
+            WsMessage::Leave { room: None, .. } => {
+                self.reply(WsMessage::Leave { name: self.name.clone(), room: None });
+                return false;
+            }
+            WsMessage::Subscribe { topic } | WsMessage::Unsubscribe { topic } if topic.trim().is_empty() => {
+                self.reply(WsMessage::Error { message: "sujet vide".to_string() })
+            }
+            WsMessage::Subscribe { topic } => {
+                match self.shared.topics.subscribe(&topic, self.addr, self.sender.clone()) {
+                    true => self.reply(WsMessage::Subscribe { topic }),
+                    false => self.reply(WsMessage::Error { message: format!("déjà abonné à {}", topic) }),
+                }
+            }
+            WsMessage::Unsubscribe { topic } => match self.shared.topics.unsubscribe(&topic, self.addr) {
+                true => self.reply(WsMessage::Unsubscribe { topic }),
+                false => self.reply(WsMessage::Error { message: format!("pas abonné à {}", topic) }),
+            },
+            WsMessage::Publish { topic, payload, .. } => {
+                self.shared.topics.publish(&topic, &WsMessage::Publish { topic: topic.clone(), from: self.name.clone(), payload });
+                self.shared.metrics.message_relayed();
+            }
+            WsMessage::Auth { .. } => self.reply(WsMessage::Error { message: "jeton déjà présenté".to_string() }),
+            WsMessage::Ping { stamp } => self.reply(WsMessage::Ping { stamp }),
+            WsMessage::History { .. } | WsMessage::Announcement { .. } | WsMessage::Error { .. } => {
+                self.reply(WsMessage::Error { message: "message réservé au serveur".to_string() })
+            }
+        }
+        true
+    }
 
-            let (mut write, mut read) = ws_stream.split();
+    /// Relaie une trame binaire au salon, avec le nom de l'expéditeur dans son en-tête
+    fn relay_blob(&self, frame: &[u8]) {
+        let Some(room) = self.shared.rooms.room_of(self.addr) else {
+            return self.reply(WsMessage::Error { message: "aucun salon où envoyer les données".to_string() });
+        };
+        match BlobHeader::parse(frame) {
+            Ok((mut header, data)) => {
+                header.from = self.name.clone();
+                self.shared.rooms.relay(&room, header.to_frame(data), self.addr);
+                self.shared.metrics.message_relayed();
+            }
+            Err(e) => self.reply(WsMessage::Error { message: e }),
+        }
+    }
+}
+
+/// Fenêtre de la limite de nouvelles connexions par adresse IP (`ip_rate`)
+const RATE_WINDOW: Duration = Duration::from_secs(60);
+
+/// Annonces en attente d'envoi par connexion
+const ANNOUNCEMENT_BACKLOG: usize = 16;
+
+/// Attente de la réponse du client à notre trame Close
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Attente de la fin de la négociation TLS
+const TLS_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Attente du message `auth` d'un client qui n'a pas mis son jeton dans l'URL
+const AUTH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Identité du client : d'après le jeton de l'URL, sinon d'après son premier message, qui doit être `auth`.
+/// `None` quand aucun jeton n'est configuré
+async fn authenticate(
+    tokens: &Tokens,
+    query_token: Option<String>,
+    read: &mut SplitStream<WebSocketStream<Stream>>,
+) -> Result<Option<String>, &'static str> {
+    if tokens.is_empty() {
+        return Ok(None);
+    }
+    let token = match query_token {
+        Some(token) => token,
+        None => match timeout(AUTH_TIMEOUT, read.next()).await {
+            Ok(Some(Ok(Message::Text(text)))) => match WsMessage::parse(&text) {
+                Ok(WsMessage::Auth { token }) => token,
+                _ => return Err("jeton attendu : ?token=<jeton> ou message auth"),
+            },
+            Ok(_) => return Err("jeton attendu : ?token=<jeton> ou message auth"),
+            Err(_) => return Err("pas de jeton présenté à temps"),
+        },
+    };
+    tokens.identity(&token).map(|identity| Some(identity.to_string())).ok_or("jeton invalide")
+}
 
-            while let Some(msg) = read.next().await {
-                let msg = msg.unwrap();
-                println!("Reçu de {}: {}", addr, msg);
+/// Trame Close envoyée au client avec son code et sa raison
+fn close_frame(code: CloseCode, reason: &str) -> Message {
+    Message::Close(Some(CloseFrame { code, reason: reason.to_string().into() }))
+}
+
+/// Négociation TLS si elle est configurée ; la connexion reste en clair sinon
+async fn secure(stream: TcpStream, tls: Option<&TlsAcceptor>) -> io::Result<Stream> {
+    let Some(acceptor) = tls else {
+        return Ok(Box::new(stream));
+    };
+    match timeout(TLS_TIMEOUT, acceptor.accept(stream)).await {
+        Ok(stream) => Ok(Box::new(stream?)),
+        Err(_) => Err(io::ErrorKind::TimedOut.into()),
+    }
+}
+
+/// Connexion au-delà des limites : le client reçoit une trame Close 1013 (réessayer plus tard)
+async fn refuse(stream: TcpStream, addr: SocketAddr, reason: &str, shared: Arc<Shared>) {
+    let Ok(stream) = secure(stream, shared.tls.as_ref()).await else {
+        return;
+    };
+    let mut ws_stream = match timeout(CLOSE_TIMEOUT, accept_async_with_config(stream, Some(shared.ws_config()))).await {
+        Ok(Ok(ws_stream)) => ws_stream,
+        // Pas un client WebSocket (ou trop lent) : la connexion est simplement fermée
+        _ => return,
+    };
+    let _ = ws_stream.send(close_frame(CloseCode::Again, reason)).await;
+    let _ = timeout(CLOSE_TIMEOUT, async { while let Some(Ok(_)) = ws_stream.next().await {} }).await;
+    info!("Connexion fermée avec {} (refusée)", addr);
+}
 
-                // Répond avec un écho
-                if write.send(msg).await.is_err() {
-                    println!("Erreur en envoyant la réponse.");
-                    break;
+async fn handle_connection(stream: TcpStream, addr: SocketAddr, shared: Arc<Shared>, mut shutdown: watch::Receiver<bool>) {
+    // Une requête HTTP ordinaire reçoit la page de test ; seul le chemin WebSocket mène au handshake.
+    // En TLS, l'en-tête n'est lisible qu'après la négociation : pas de page de test
+    if shared.tls.is_none() {
+        match http::peek_request(&stream).await {
+            Ok(http::Request::Upgrade(_)) => {}
+            Ok(http::Request::Page { method, path }) => {
+                info!("Requête HTTP de {}: {} {}", addr, method, path);
+                if let Err(e) = http::answer(stream, &method, &path, &shared.config.ws_path).await {
+                    warn!("Erreur en répondant à {}: {}", addr, e);
                 }
+                return;
             }
+            Err(e) => {
+                warn!("Requête invalide de {}: {}", addr, e);
+                return;
+            }
+        }
+    }
+    let stream = match secure(stream, shared.tls.as_ref()).await {
+        Ok(stream) => stream,
+        Err(e) => {
+            warn!("Erreur TLS avec {}: {}", addr, e);
+            return;
+        }
+    };
+    // Le type de l'erreur est imposé par tungstenite
+    let mut query_token = None;
+    #[allow(clippy::result_large_err)]
+    let check_path = |request: &Request, response: Response| {
+        if request.uri().path() == shared.config.ws_path {
+            query_token = request.uri().query().and_then(token_from_query);
+            return Ok(response);
+        }
+        let mut refusal = ErrorResponse::new(Some(format!("pas de WebSocket sur {}\n", request.uri().path())));
+        *refusal.status_mut() = StatusCode::NOT_FOUND;
+        Err(refusal)
+    };
+    let ws_stream = match accept_hdr_async_with_config(stream, check_path, Some(shared.ws_config())).await {
+        Ok(ws_stream) => ws_stream,
+        Err(e) => {
+            warn!("Erreur handshake WebSocket avec {}: {}", addr, e);
+            return;
+        }
+    };
+    info!("Nouvelle connexion de : {}", addr);
+
+    let (mut write, mut read) = ws_stream.split();
+
+    let identity = match authenticate(&shared.tokens, query_token, &mut read).await {
+        Ok(identity) => identity,
+        Err(reason) => {
+            warn!("Connexion refusée pour {}: {}", addr, reason);
+            let _ = write.send(close_frame(CloseCode::from(CLOSE_UNAUTHORIZED), reason)).await;
+            let _ = timeout(CLOSE_TIMEOUT, async { while let Some(Ok(_)) = read.next().await {} }).await;
+            return;
+        }
+    };
+    if let Some(identity) = &identity {
+        info!("{} authentifié : {}", addr, identity);
+    }
+    shared.metrics.client_connected();
 
-            println!("Connexion fermée avec {}", addr);
+    // Les réponses et les messages des salons passent par ce canal ; une trame Close l'arrête
+    let (sender, mut outgoing) = mpsc::unbounded_channel::<Message>();
+    let writer_shared = shared.clone();
+    let writer = tokio::spawn(async move {
+        while let Some(frame) = outgoing.recv().await {
+            let closing = frame.is_close();
+            let size = frame.len();
+            if write.send(frame).await.is_err() {
+                // Un client déjà parti ne reçoit pas notre trame Close : rien d'anormal
+                if !closing {
+                    warn!("Erreur en envoyant la réponse.");
+                }
+                break;
+            }
+            writer_shared.metrics.sent(size);
+            if closing {
+                break;
+            }
+        }
+        write
+    });
+
+    let authenticated = identity.is_some();
+    let name = identity.unwrap_or_else(|| addr.to_string());
+    if authenticated {
+        let _ = sender.send(WsMessage::Join { name: name.clone(), room: None }.to_frame());
+    }
+    let (ping_interval, max_missed_pongs) = (shared.config.ping_interval(), shared.config.max_missed_pongs);
+    let mut connection = Connection { addr, name, authenticated, sender, shared };
+    let mut announcements = connection.shared.announcements.subscribe();
+    let mut heartbeat = interval_at(Instant::now() + ping_interval, ping_interval);
+    let mut missed_pongs = 0;
+    // Trame Close à envoyer en partant, si c'est nous qui fermons
+    let close = loop {
+        let msg = tokio::select! {
+            msg = read.next() => msg,
+            _ = shutdown.changed() => break Some(close_frame(CloseCode::Away, "arrêt du serveur")),
+            announcement = announcements.recv() => {
+                // Une connexion trop lente pour suivre perd les plus anciennes annonces
+                if let Ok(text) = announcement {
+                    connection.reply(WsMessage::Announcement { text });
+                }
+                continue;
+            }
+            _ = heartbeat.tick() => {
+                if missed_pongs >= max_missed_pongs {
+                    warn!("{} ne répond plus aux Ping", addr);
+                    break Some(close_frame(CloseCode::Away, "pas de réponse aux Ping"));
+                }
+                missed_pongs += 1;
+                let _ = connection.sender.send(Message::Ping(Vec::new()));
+                continue;
+            }
+        };
+        match msg {
+            Some(Ok(Message::Text(text))) => {
+                debug!("Reçu de {}: {}", addr, text);
+                connection.shared.metrics.received(text.len());
+                let message = WsMessage::parse(&text)
+                    .map_err(|e| e.to_string())
+                    .or_else(|e| WsMessage::from_command(&text).ok_or(e));
+                match message {
+                    Ok(message) => {
+                        if !connection.handle(message) {
+                            break Some(close_frame(CloseCode::Normal, "au revoir"));
+                        }
+                    }
+                    Err(e) => connection.reply(WsMessage::Error { message: format!("message invalide: {}", e) }),
+                }
+            }
+            Some(Ok(Message::Binary(frame))) => {
+                connection.shared.metrics.received(frame.len());
+                connection.relay_blob(&frame);
+            }
+            // Tungstenite prépare lui-même la réponse, envoyée à la fermeture de la connexion
+            Some(Ok(Message::Close(frame))) => {
+                match frame {
+                    Some(frame) => info!("{} ferme la connexion ({}: {})", addr, frame.code, frame.reason),
+                    None => info!("{} ferme la connexion", addr),
+                }
+                break None;
+            }
+            Some(Ok(Message::Pong(_))) => missed_pongs = 0,
+            // Les Ping reçoivent leur Pong de tungstenite
+            Some(Ok(Message::Ping(_) | Message::Frame(_))) => {}
+            Some(Err(e)) => {
+                warn!("Erreur de lecture de {}: {}", addr, e);
+                break match e {
+                    Error::Protocol(_) => Some(close_frame(CloseCode::Protocol, "trame invalide")),
+                    Error::Utf8 => Some(close_frame(CloseCode::Invalid, "texte non UTF-8")),
+                    Error::Capacity(_) => Some(close_frame(CloseCode::Size, "message trop grand")),
+                    _ => None,
+                };
+            }
+            None => break None,
+        }
+    };
+
+    // Quelle que soit la raison du départ, la connexion quitte le registre et ses sujets
+    connection.leave_room();
+    connection.shared.topics.unsubscribe_all(addr);
+    let initiated = close.is_some();
+    if let Some(frame) = close {
+        let _ = connection.sender.send(frame);
+    }
+    connection.shared.metrics.client_disconnected();
+    // Le rédacteur s'arrête quand plus personne ne peut lui écrire
+    drop(connection);
+    if let Ok(mut write) = writer.await {
+        // Envoie la réponse à la trame Close du client, s'il en a envoyé une
+        let _ = write.close().await;
+    }
+    if initiated {
+        // Le client répond à notre Close par le sien ; la lecture se termine alors
+        let _ = timeout(CLOSE_TIMEOUT, async { while let Some(Ok(_)) = read.next().await {} }).await;
+    }
+    info!("Connexion fermée avec {}", addr);
+}
+
+/// Port de supervision : chaque requête reçoit l'état du serveur sur /health, une erreur ailleurs
+async fn serve_health(listener: TcpListener, shared: Arc<Shared>) {
+    loop {
+        let (stream, addr) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                error!("Erreur accept (supervision): {}", e);
+                continue;
+            }
+        };
+        let shared = shared.clone();
+        tokio::spawn(async move {
+            let answered = match http::peek_request(&stream).await {
+                Ok(http::Request::Page { method, path }) => http::answer_health(stream, &method, &path, &shared.metrics).await,
+                Ok(http::Request::Upgrade(path)) => http::answer_health(stream, "GET", &path, &shared.metrics).await,
+                Err(e) => Err(e),
+            };
+            if let Err(e) = answered {
+                warn!("Erreur de supervision avec {}: {}", addr, e);
+            }
         });
     }
 }
+
+/// Serveur WebSocket de discussion : les options l'emportent sur les variables TP9_*, qui l'emportent sur le fichier
+#[derive(Parser)]
+#[command(about)]
+struct Args {
+    /// Fichier de configuration TOML
+    #[arg(long)]
+    config: Option<PathBuf>,
+    /// Adresse d'écoute
+    #[arg(long)]
+    addr: Option<SocketAddr>,
+    /// Chemin des connexions WebSocket
+    #[arg(long)]
+    path: Option<String>,
+    /// Fichier des jetons acceptés, une ligne `<jeton> <identité>` chacun
+    #[arg(long)]
+    tokens: Option<PathBuf>,
+    /// Messages gardés par salon
+    #[arg(long)]
+    history: Option<usize>,
+    /// Taille maximale d'un message reçu, en octets
+    #[arg(long)]
+    max_message_size: Option<usize>,
+    /// Secondes entre deux Ping
+    #[arg(long)]
+    ping_interval: Option<u64>,
+    /// Salon ouvert aux clients, à répéter ; les salons du fichier sont alors remplacés
+    #[arg(long = "room")]
+    rooms: Vec<String>,
+    /// Connexions simultanées
+    #[arg(long)]
+    max_connections: Option<usize>,
+    /// Nouvelles connexions par adresse IP et par minute
+    #[arg(long)]
+    ip_rate: Option<usize>,
+    /// Port de /health
+    #[arg(long)]
+    metrics_port: Option<u16>,
+    /// Certificat TLS (PEM), avec --tls-key
+    #[arg(long, requires = "tls_key")]
+    tls_cert: Option<PathBuf>,
+    /// Clé privée TLS (PEM), avec --tls-cert
+    #[arg(long, requires = "tls_cert")]
+    tls_key: Option<PathBuf>,
+    /// Adresse d'un serveur SCP (tp8) dont un salon est relié à un salon WebSocket
+    #[arg(long)]
+    bridge: Option<String>,
+    /// Salon relié : `<salon>`, ou `<salon WebSocket>:<salon SCP>`
+    #[arg(long)]
+    bridge_room: Option<String>,
+    /// Compte du pont sur le serveur SCP
+    #[arg(long)]
+    bridge_name: Option<String>,
+    /// Mot de passe de ce compte, obligatoire avec --bridge
+    #[arg(long)]
+    bridge_password: Option<String>,
+}
+
+impl Args {
+    /// Configuration finale : défauts, puis fichier (`--config` ou TP9_CONFIG), puis variables TP9_*, puis options ; validée
+    fn into_config(self) -> Result<Config, ConfigError> {
+        let mut options: Vec<(&str, String)> = Vec::new();
+        options.extend(self.addr.map(|addr| ("addr", addr.to_string())));
+        options.extend(self.path.map(|path| ("path", path)));
+        options.extend(self.tokens.map(|path| ("tokens", path.display().to_string())));
+        options.extend(self.history.map(|size| ("history", size.to_string())));
+        options.extend(self.max_message_size.map(|size| ("max-message-size", size.to_string())));
+        options.extend(self.ping_interval.map(|secs| ("ping-interval", secs.to_string())));
+        if !self.rooms.is_empty() {
+            options.push(("rooms", self.rooms.join(",")));
+        }
+        options.extend(self.max_connections.map(|count| ("max-connections", count.to_string())));
+        options.extend(self.ip_rate.map(|rate| ("ip-rate", rate.to_string())));
+        options.extend(self.metrics_port.map(|port| ("metrics-port", port.to_string())));
+        options.extend(self.tls_cert.map(|path| ("tls-cert", path.display().to_string())));
+        options.extend(self.tls_key.map(|path| ("tls-key", path.display().to_string())));
+        options.extend(self.bridge.map(|addr| ("bridge", addr)));
+        options.extend(self.bridge_room.map(|room| ("bridge-room", room)));
+        options.extend(self.bridge_name.map(|name| ("bridge-name", name)));
+        options.extend(self.bridge_password.map(|password| ("bridge-password", password)));
+        let options = options.into_iter().map(|(key, value)| (key.to_string(), value));
+        config_commun::merge(self.config.as_deref(), std::env::vars(), options)
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let _journal = trace_commun::init_from_env("tp9-serveur");
+    let config = Args::parse().into_config().map_err(|e| format!("Configuration invalide : {}", e))?;
+    let tokens = match &config.tokens {
+        Some(path) => Tokens::load(path).map_err(|e| format!("Jetons illisibles : {}", e))?,
+        None => Tokens::default(),
+    };
+    match tokens.len() {
+        0 => warn!("Aucun jeton configuré (--tokens) : connexions sans authentification"),
+        count => info!("{} jeton(s) accepté(s)", count),
+    }
+    let tls = match &config.tls {
+        Some(tls) => Some(chiffrement::acceptor(&tls.cert, &tls.key).map_err(|e| format!("TLS impossible : {}", e))?),
+        None => None,
+    };
+
+    let addr = config.addr;
+    let listener = TcpListener::bind(&addr).await.map_err(|e| format!("Écoute impossible sur {} : {}", addr, e))?;
+    let metrics_addr = SocketAddr::new(addr.ip(), config.metrics_port);
+    let metrics_listener = TcpListener::bind(&metrics_addr).await
+        .map_err(|e| format!("Écoute de la supervision impossible sur {} : {}", metrics_addr, e))?;
+    let rooms = Arc::new(Rooms::new(config.history_size));
+    let mut rate_limiter = RateLimiter::new(config.ip_rate, RATE_WINDOW);
+    let shared = Arc::new(Shared {
+        config,
+        tokens,
+        rooms,
+        topics: Topics::default(),
+        announcements: broadcast::channel(ANNOUNCEMENT_BACKLOG).0,
+        metrics: Metrics::new(),
+        tls,
+    });
+    let health = tokio::spawn(serve_health(metrics_listener, shared.clone()));
+    let bridge = shared.config.bridge.clone().map(|config| tokio::spawn(bridge::run(config, shared.rooms.clone())));
+    let (shutdown, shutdown_signal) = watch::channel(false);
+    let mut connections = JoinSet::new();
+
+    let scheme = if shared.tls.is_some() { "wss" } else { "ws" };
+    info!("Serveur WebSocket en écoute sur {}://{}{} (Ctrl-C pour arrêter)", scheme, addr, shared.config.ws_path);
+    if shared.tls.is_none() {
+        info!("Page de test : http://{}/", addr);
+    }
+    info!("Supervision : http://{}/health", metrics_addr);
+    info!(
+        "Limites : {} connexion(s) simultanée(s), {} nouvelle(s) par minute et par adresse, messages de {} octets au plus",
+        shared.config.max_connections, shared.config.ip_rate, shared.config.max_message_size
+    );
+    if !shared.config.rooms.is_empty() {
+        info!("Salons ouverts : {}", shared.config.rooms.join(", "));
+    }
+    info!("Chaque ligne tapée ici est annoncée à tous les clients connectés");
+    let mut operator = BufReader::new(tokio::io::stdin()).lines();
+    let mut operator_open = true;
+
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok((stream, addr)) => {
+                    // Les connexions terminées ne restent pas dans l'ensemble
+                    while connections.try_join_next().is_some() {}
+                    // Les refus ne comptent pas parmi les connexions ; leur fermeture est bornée par CLOSE_TIMEOUT
+                    if connections.len() >= shared.config.max_connections {
+                        warn!("Connexion de {} refusée : {} connexion(s) déjà ouverte(s)", addr, connections.len());
+                        tokio::spawn(refuse(stream, addr, "serveur plein", shared.clone()));
+                    } else if !rate_limiter.allow(addr.ip(), std::time::Instant::now()) {
+                        warn!("Connexion de {} refusée : trop de connexions depuis {}", addr, addr.ip());
+                        tokio::spawn(refuse(stream, addr, "trop de connexions, réessayer plus tard", shared.clone()));
+                    } else {
+                        connections.spawn(handle_connection(stream, addr, shared.clone(), shutdown_signal.clone()));
+                    }
+                }
+                Err(e) => error!("Erreur accept: {}", e),
+            },
+            line = operator.next_line(), if operator_open => match line {
+                Ok(Some(line)) if !line.trim().is_empty() => {
+                    let count = shared.announcements.send(line.trim().to_string()).unwrap_or(0);
+                    info!("Annonce envoyée à {} client(s)", count);
+                }
+                Ok(Some(_)) => {}
+                // Entrée standard fermée (serveur lancé en arrière-plan) : plus d'annonces
+                _ => operator_open = false,
+            },
+            _ = tokio::signal::ctrl_c() => break,
+        }
+    }
+
+    while connections.try_join_next().is_some() {}
+    info!("Arrêt du serveur : fermeture de {} connexion(s)", connections.len());
+    let _ = shutdown.send(true);
+    health.abort();
+    if let Some(bridge) = bridge {
+        bridge.abort();
+    }
+    while connections.join_next().await.is_some() {}
+    Ok(())
+}
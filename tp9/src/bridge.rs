@@ -0,0 +1,307 @@
+// src/bridge.rs
+// Pont vers un serveur de discussion tp8 (protocole SCP) : le pont s'y connecte comme un client, et un
+// salon WebSocket et un salon SCP reçoivent chacun les messages de l'autre
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use tokio::net::tcp::OwnedWriteHalf;
+use tokio::net::TcpStream;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio::time::sleep;
+use tracing::{info, warn};
+use tungstenite::Message as WsFrame;
+
+use tp8::protocole::{ErrorCode, Message, MessageKind, ProtocolFrame, MIN_PROTOCOL_VERSION, PROTOCOL_VERSION};
+use tp8::trame::{read_frame, write_frame};
+
+use crate::config::DEFAULT_ROOM;
+use crate::protocol::WsMessage;
+use crate::rooms::Rooms;
+
+/// Ajouté au nom des auteurs SCP dans le salon WebSocket
+pub const SCP_SUFFIX: &str = "@scp";
+
+/// Attente avant de se reconnecter au serveur SCP
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// Trames du serveur SCP lues d'avance, en attendant que la session les traite
+const FRAME_BACKLOG: usize = 64;
+
+/// Compte du pont sur le serveur SCP par défaut ; son mot de passe doit être configuré
+pub const DEFAULT_USERNAME: &str = "pont-ws";
+
+/// Serveur SCP à relier, salons reliés et compte utilisé par le pont
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BridgeConfig {
+    pub addr: String,
+    pub ws_room: String,
+    pub scp_room: String,
+    pub username: String,
+    pub password: String,
+}
+
+impl Default for BridgeConfig {
+    fn default() -> Self {
+        Self {
+            addr: String::new(),
+            ws_room: DEFAULT_ROOM.to_string(),
+            scp_room: DEFAULT_ROOM.to_string(),
+            username: DEFAULT_USERNAME.to_string(),
+            password: String::new(),
+        }
+    }
+}
+
+/// Relie les deux salons tant que le serveur tourne, en se reconnectant au serveur SCP si besoin
+pub async fn run(config: BridgeConfig, rooms: Arc<Rooms>) {
+    loop {
+        if let Err(e) = session(&config, &rooms).await {
+            warn!("Pont SCP : {}", e);
+        }
+        info!("Pont SCP : nouvelle tentative dans {} s", RECONNECT_DELAY.as_secs());
+        sleep(RECONNECT_DELAY).await;
+    }
+}
+
+/// Message de discussion SCP vu dans le salon WebSocket
+pub fn to_ws(from: &str, content: &str) -> WsMessage {
+    WsMessage::Chat { from: format!("{}{}", from, SCP_SUFFIX), text: content.to_string() }
+}
+
+/// Message du salon WebSocket envoyé dans le salon SCP : le pont parle au nom de l'auteur
+pub fn to_scp(message: WsMessage) -> Option<Message> {
+    match message {
+        WsMessage::Chat { from, text } => Some(Message::SendMessage { content: format!("<{}> {}", from, text), kind: MessageKind::Text, parent_message_id: None }),
+        _ => None,
+    }
+}
+
+/// Côté SCP d'une connexion du pont. Les trames sont lues par une tâche à part : `read_frame` n'est pas
+/// annulable sans perte (une trame à moitié lue serait abandonnée par le `select!` de la session), la
+/// réception d'un canal l'est
+struct Scp {
+    frames: mpsc::Receiver<Result<Message, String>>,
+    reader: JoinHandle<()>,
+    writer: OwnedWriteHalf,
+}
+
+impl Drop for Scp {
+    fn drop(&mut self) {
+        self.reader.abort();
+    }
+}
+
+impl Scp {
+    fn new(stream: TcpStream) -> Self {
+        let (mut reader, writer) = stream.into_split();
+        let (sender, frames) = mpsc::channel(FRAME_BACKLOG);
+        let reader = tokio::spawn(async move {
+            loop {
+                let frame = match read_frame(&mut reader).await {
+                    Ok(Some(frame)) => Ok(frame.message),
+                    Ok(None) => Err("connexion fermée par le serveur SCP".to_string()),
+                    Err(e) => Err(format!("lecture du serveur SCP : {}", e)),
+                };
+                let failed = frame.is_err();
+                if sender.send(frame).await.is_err() || failed {
+                    break;
+                }
+            }
+        });
+        Self { frames, reader, writer }
+    }
+
+    async fn send(&mut self, message: Message) -> Result<(), String> {
+        write_frame(&mut self.writer, &ProtocolFrame::new(message, None, 0))
+            .await
+            .map_err(|e| format!("envoi au serveur SCP : {}", e))
+    }
+
+    /// Prochain message du serveur, Ping compris ; peut être abandonné dans un `select!` sans rien perdre
+    async fn next(&mut self) -> Result<Message, String> {
+        self.frames.recv().await.unwrap_or_else(|| Err("connexion fermée par le serveur SCP".to_string()))
+    }
+
+    /// Prochain message du serveur ; les Ping sont répondus au passage
+    async fn receive(&mut self) -> Result<Message, String> {
+        loop {
+            match self.next().await? {
+                Message::Ping => self.send(Message::Pong).await?,
+                message => return Ok(message),
+            }
+        }
+    }
+
+    /// Lit jusqu'au premier message qui satisfait `wanted`
+    async fn wait_for(&mut self, wanted: impl Fn(&Message) -> bool) -> Result<Message, String> {
+        loop {
+            let message = self.receive().await?;
+            if wanted(&message) {
+                return Ok(message);
+            }
+        }
+    }
+
+    /// Versions, compte du pont (créé à la première connexion) et entrée dans le salon
+    async fn open(&mut self, config: &BridgeConfig) -> Result<(), String> {
+        self.send(Message::Hello { min_version: MIN_PROTOCOL_VERSION, max_version: PROTOCOL_VERSION }).await?;
+        if let Message::VersionMismatch { message, .. } = self.wait_for(|m| matches!(m, Message::HelloAck { .. } | Message::VersionMismatch { .. })).await? {
+            return Err(message);
+        }
+
+        let answered = |m: &Message| matches!(m, Message::ConnectAck { .. } | Message::ConnectError { .. });
+        self.send(Message::Register { username: config.username.clone(), password: config.password.clone() }).await?;
+        match self.wait_for(answered).await? {
+            Message::ConnectError { code: ErrorCode::UsernameAlreadyTaken, .. } => {
+                self.send(Message::Login { username: config.username.clone(), password: config.password.clone() }).await?;
+                if let Message::ConnectError { reason, .. } = self.wait_for(answered).await? {
+                    return Err(format!("connexion refusée : {}", reason));
+                }
+            }
+            Message::ConnectError { reason, .. } => return Err(format!("inscription refusée : {}", reason)),
+            _ => {}
+        }
+
+        self.send(Message::JoinRoom { room_id: config.scp_room.clone(), password: None, wait: false, spectator: false }).await?;
+        let joined = |m: &Message| matches!(m, Message::JoinRoomAck { .. } | Message::JoinRoomError { .. });
+        if let Message::JoinRoomError { reason } = self.wait_for(joined).await? {
+            return Err(format!("salon {} refusé : {}", config.scp_room, reason));
+        }
+        Ok(())
+    }
+}
+
+/// Une connexion au serveur SCP, jusqu'à sa perte
+async fn session(config: &BridgeConfig, rooms: &Rooms) -> Result<(), String> {
+    let stream = TcpStream::connect(&config.addr).await.map_err(|e| format!("connexion à {} : {}", config.addr, e))?;
+    let _ = stream.set_nodelay(true);
+    // L'adresse locale de la connexion identifie le pont parmi les membres du salon WebSocket
+    let id = stream.local_addr().map_err(|e| e.to_string())?;
+    let mut scp = Scp::new(stream);
+    scp.open(config).await?;
+    info!("Pont SCP : salon {} relié à {} sur {}", config.ws_room, config.scp_room, config.addr);
+
+    let (sender, mut from_ws) = mpsc::unbounded_channel::<WsFrame>();
+    rooms.join(&config.ws_room, id, sender);
+    let result = loop {
+        tokio::select! {
+            // Seule la réception du canal peut être abandonnée : les réponses partent dans le corps des branches
+            message = scp.next() => match message {
+                Ok(Message::Ping) => {
+                    if let Err(e) = scp.send(Message::Pong).await {
+                        break Err(e);
+                    }
+                }
+                Ok(Message::RoomMessage { room_id, from, content, sequence, .. }) if room_id == config.scp_room => {
+                    // Numéro 0 : le serveur ne numérote pas ses messages
+                    if sequence > 0
+                        && let Err(e) = scp.send(Message::MessageAck { room_id, sequence }).await
+                    {
+                        break Err(e);
+                    }
+                    // Nos propres messages reviennent par le salon SCP : ils viennent du salon WebSocket
+                    if from != config.username {
+                        rooms.broadcast_except(&config.ws_room, &to_ws(&from, &content), id);
+                    }
+                }
+                Ok(Message::RoomDeleted { room_id }) if room_id == config.scp_room => break Err(format!("salon {} supprimé", room_id)),
+                Ok(Message::UserKicked { username, .. } | Message::UserBanned { username, .. }) if username == config.username => {
+                    break Err(format!("pont exclu du salon {}", config.scp_room));
+                }
+                Ok(Message::Error { message, .. }) => warn!("Pont SCP : erreur du serveur : {}", message),
+                Ok(_) => {}
+                Err(e) => break Err(e),
+            },
+            Some(frame) = from_ws.recv() => {
+                let WsFrame::Text(text) = frame else { continue };
+                if let Some(message) = WsMessage::parse(&text).ok().and_then(to_scp)
+                    && let Err(e) = scp.send(message).await
+                {
+                    break Err(e);
+                }
+            }
+        }
+    };
+    rooms.leave(id);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_translation() {
+        assert_eq!(to_ws("bob", "salut"), WsMessage::Chat { from: "bob@scp".to_string(), text: "salut".to_string() });
+        let chat = WsMessage::Chat { from: "alice".to_string(), text: "bonjour".to_string() };
+        assert_eq!(to_scp(chat), Some(Message::SendMessage { content: "<alice> bonjour".to_string(), kind: MessageKind::Text, parent_message_id: None }));
+        assert_eq!(to_scp(WsMessage::Ping { stamp: None }), None);
+    }
+
+    #[tokio::test]
+    async fn test_slow_scp_frame_during_ws_input() {
+        use tokio::io::AsyncWriteExt;
+        use tokio::net::TcpListener;
+
+        // Un serveur SCP réduit au strict nécessaire : versions, compte et salon acceptés
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let config = BridgeConfig { addr: listener.local_addr().unwrap().to_string(), password: "s3cret".to_string(), ..BridgeConfig::default() };
+        let rooms = Arc::new(Rooms::new(0));
+        let bridge = tokio::spawn({
+            let (config, rooms) = (config.clone(), rooms.clone());
+            async move { session(&config, &rooms).await }
+        });
+        let (mut stream, _) = listener.accept().await.unwrap();
+        for reply in [
+            Message::HelloAck { version: PROTOCOL_VERSION },
+            Message::ConnectAck { client_id: "pont".to_string(), message: String::new() },
+            Message::JoinRoomAck { room_id: DEFAULT_ROOM.to_string(), topic: None, users: Vec::new() },
+        ] {
+            read_frame(&mut stream).await.unwrap().unwrap();
+            write_frame(&mut stream, &ProtocolFrame::new(reply, None, 0)).await.unwrap();
+        }
+        while rooms.room_of(stream.peer_addr().unwrap()).is_none() {
+            tokio::task::yield_now().await;
+        }
+
+        // Une trame SCP écrite octet par octet, pendant que des messages arrivent du salon WebSocket
+        let member = "127.0.0.1:2".parse().unwrap();
+        let (sender, mut to_member) = mpsc::unbounded_channel();
+        rooms.join(DEFAULT_ROOM, member, sender);
+        let mut frame = ProtocolFrame::new(Message::Ping, None, 0);
+        frame.message = Message::RoomMessage {
+            from: "carol".to_string(),
+            content: "lentement".to_string(),
+            timestamp: frame.timestamp,
+            room_id: DEFAULT_ROOM.to_string(),
+            sequence: 0,
+            message_id: String::new(),
+            kind: MessageKind::Text,
+            received_at: None,
+            parent_message_id: None,
+        };
+        let data = frame.serialize().unwrap();
+        let bytes = [(data.len() as u32).to_be_bytes().to_vec(), data].concat();
+        let chunks: Vec<&[u8]> = bytes.chunks(bytes.len().div_ceil(8)).collect();
+        for (i, chunk) in chunks.iter().enumerate() {
+            stream.write_all(chunk).await.unwrap();
+            let chat = WsMessage::Chat { from: "alice".to_string(), text: i.to_string() };
+            rooms.broadcast_except(DEFAULT_ROOM, &chat, member);
+            sleep(Duration::from_millis(20)).await;
+        }
+
+        let received = tokio::time::timeout(Duration::from_secs(5), to_member.recv()).await.unwrap().unwrap();
+        assert_eq!(received, to_ws("carol", "lentement").to_frame());
+        for i in 0..chunks.len() {
+            let frame = read_frame(&mut stream).await.unwrap().unwrap();
+            assert!(matches!(frame.message, Message::SendMessage { content, .. } if content == format!("<alice> {}", i)));
+        }
+        assert!(!bridge.is_finished());
+        bridge.abort();
+    }
+}
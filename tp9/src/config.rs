@@ -0,0 +1,237 @@
+// src/config.rs
+// Configuration du serveur : valeurs par défaut, puis fichier TOML facultatif, variables TP9_* et options de la ligne de commande
+
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use config_commun::{list, parse, Settings};
+use serde::Deserialize;
+
+use crate::bridge::BridgeConfig;
+use crate::protocol::BLOB_CHUNK_SIZE;
+
+/// Préfixe des variables d'environnement : `TP9_IP_RATE` pour `--ip-rate`, `TP9_CONFIG` pour `--config`
+pub const ENV_PREFIX: &str = "TP9_";
+
+/// Salon proposé quand la configuration n'en liste aucun
+pub const DEFAULT_ROOM: &str = "general";
+
+/// Plus petite taille de message acceptable : un morceau de fichier et son en-tête doivent passer
+pub const MIN_MESSAGE_SIZE: usize = BLOB_CHUNK_SIZE + 4 * 1024;
+
+/// Certificat et clé privée (PEM) des connexions wss://
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TlsConfig {
+    pub cert: PathBuf,
+    pub key: PathBuf,
+}
+
+/// Configuration complète du serveur ; les clés absentes du fichier gardent leur valeur par défaut
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub addr: SocketAddr,
+    /// Chemin des connexions WebSocket
+    pub ws_path: String,
+    /// Taille maximale d'un message reçu, en octets
+    pub max_message_size: usize,
+    /// Intervalle entre deux Ping envoyés à chaque client
+    pub ping_interval_secs: u64,
+    /// Ping sans Pong tolérés avant de fermer la connexion
+    pub max_missed_pongs: u32,
+    /// Salons ouverts aux clients ; vide, tous les noms sont acceptés. Le premier sert d'exemple aux clients perdus
+    pub rooms: Vec<String>,
+    /// Messages gardés par salon pour ceux qui arrivent ensuite
+    pub history_size: usize,
+    /// Fichier des jetons acceptés ; sans lui, les connexions ne sont pas authentifiées
+    pub tokens: Option<PathBuf>,
+    /// Connexions simultanées
+    pub max_connections: usize,
+    /// Nouvelles connexions par adresse IP et par minute
+    pub ip_rate: usize,
+    /// Port de /health, sur l'adresse du serveur
+    pub metrics_port: u16,
+    pub tls: Option<TlsConfig>,
+    /// Salon relié à un serveur SCP
+    pub bridge: Option<BridgeConfig>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            addr: "127.0.0.1:9001".parse().expect("adresse par défaut valide"),
+            ws_path: "/ws".to_string(),
+            max_message_size: 1024 * 1024,
+            ping_interval_secs: 30,
+            max_missed_pongs: 2,
+            rooms: Vec::new(),
+            history_size: 50,
+            tokens: None,
+            max_connections: 100,
+            ip_rate: 20,
+            metrics_port: 9002,
+            tls: None,
+            bridge: None,
+        }
+    }
+}
+
+impl Config {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        toml::from_str(text).map_err(|e| e.message().to_string())
+    }
+
+    pub fn ping_interval(&self) -> Duration {
+        Duration::from_secs(self.ping_interval_secs)
+    }
+
+    /// Salon donné en exemple aux clients qui n'en ont pas rejoint
+    pub fn default_room(&self) -> &str {
+        self.rooms.first().map_or(DEFAULT_ROOM, String::as_str)
+    }
+
+    pub fn room_allowed(&self, room: &str) -> bool {
+        self.rooms.is_empty() || self.rooms.iter().any(|allowed| allowed == room)
+    }
+
+    fn tls_mut(&mut self) -> &mut TlsConfig {
+        self.tls.get_or_insert_with(|| TlsConfig { cert: PathBuf::new(), key: PathBuf::new() })
+    }
+
+    fn bridge_mut(&mut self) -> &mut BridgeConfig {
+        self.bridge.get_or_insert_with(BridgeConfig::default)
+    }
+}
+
+impl Settings for Config {
+    const ENV_PREFIX: &'static str = ENV_PREFIX;
+
+    /// Surcharger un réglage ; `key` est le nom de l'option sans `--`. `rooms` remplace la liste des salons
+    /// (séparés par des virgules) ; les options du pont le créent au besoin
+    fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "addr" => self.addr = parse(key, value)?,
+            "path" => self.ws_path = value.trim().to_string(),
+            "tokens" => self.tokens = Some(PathBuf::from(value)),
+            "history" => self.history_size = parse(key, value)?,
+            "max-message-size" => self.max_message_size = parse(key, value)?,
+            "ping-interval" => self.ping_interval_secs = parse(key, value)?,
+            "max-missed-pongs" => self.max_missed_pongs = parse(key, value)?,
+            "rooms" => self.rooms = list(value).collect(),
+            "max-connections" => self.max_connections = parse(key, value)?,
+            "ip-rate" => self.ip_rate = parse(key, value)?,
+            "metrics-port" => self.metrics_port = parse(key, value)?,
+            "tls-cert" => self.tls_mut().cert = PathBuf::from(value),
+            "tls-key" => self.tls_mut().key = PathBuf::from(value),
+            "bridge" => self.bridge_mut().addr = value.trim().to_string(),
+            "bridge-room" => {
+                // Un seul nom de salon : le même des deux côtés
+                let (ws_room, scp_room) = value.split_once(':').unwrap_or((value, value));
+                let bridge = self.bridge_mut();
+                (bridge.ws_room, bridge.scp_room) = (ws_room.to_string(), scp_room.to_string());
+            }
+            "bridge-name" => self.bridge_mut().username = value.to_string(),
+            "bridge-password" => self.bridge_mut().password = value.to_string(),
+            _ => return Err(format!("Option inconnue: {}", key)),
+        }
+        Ok(())
+    }
+
+    /// Vérifie les valeurs une fois toutes les sources appliquées ; complète le chemin WebSocket d'un `/` au besoin
+    fn validate(&mut self) -> Result<(), String> {
+        if !self.ws_path.starts_with('/') {
+            self.ws_path.insert(0, '/');
+        }
+        if self.max_message_size < MIN_MESSAGE_SIZE {
+            return Err(format!("max_message_size : au moins {} octets", MIN_MESSAGE_SIZE));
+        }
+        if self.ping_interval_secs == 0 {
+            return Err("ping_interval_secs : au moins 1 seconde".to_string());
+        }
+        if let Some(tls) = &self.tls
+            && (tls.cert.as_os_str().is_empty() || tls.key.as_os_str().is_empty())
+        {
+            return Err("tls : le certificat et la clé vont ensemble".to_string());
+        }
+        if let Some(room) = self.rooms.iter().find(|room| room.trim().is_empty()) {
+            return Err(format!("rooms : nom de salon vide ({:?})", room));
+        }
+        if let Some(bridge) = &self.bridge {
+            if bridge.addr.is_empty() {
+                return Err("bridge : adresse du serveur SCP manquante".to_string());
+            }
+            // Le pont crée son compte à la première connexion : sans secret, n'importe qui pourrait parler en son nom
+            if bridge.password.is_empty() {
+                return Err("bridge : mot de passe du compte du pont manquant (password, --bridge-password)".to_string());
+            }
+            if !self.room_allowed(&bridge.ws_room) {
+                return Err(format!("bridge : salon {} absent de rooms", bridge.ws_room));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let mut config = Config::parse(
+            r#"
+            addr = "0.0.0.0:8080"
+            ws_path = "chat"
+            ping_interval_secs = 10
+            rooms = ["accueil", "jeux"]
+
+            [tls]
+            cert = "cert.pem"
+            key = "key.pem"
+
+            [bridge]
+            addr = "127.0.0.1:9999"
+            ws_room = "jeux"
+            password = "s3cret"
+            "#,
+        )
+        .unwrap();
+        config.validate().unwrap();
+        assert_eq!(config.addr, "0.0.0.0:8080".parse().unwrap());
+        assert_eq!(config.ws_path, "/chat");
+        assert_eq!(config.ping_interval(), Duration::from_secs(10));
+        assert_eq!(config.default_room(), "accueil");
+        assert!(config.room_allowed("jeux") && !config.room_allowed("general"));
+        assert_eq!(config.tls.as_ref().unwrap().key, PathBuf::from("key.pem"));
+        // Les clés absentes gardent leur valeur par défaut
+        assert_eq!(config.history_size, Config::default().history_size);
+        assert_eq!(config.bridge.as_ref().unwrap().scp_room, DEFAULT_ROOM);
+
+        assert!(Config::parse("port = 80").is_err());
+        let mut small = Config::parse("max_message_size = 1024").unwrap();
+        assert!(small.validate().is_err());
+        let mut outside = Config::parse("rooms = [\"accueil\"]\n[bridge]\naddr = \"127.0.0.1:9999\"\npassword = \"s3cret\"").unwrap();
+        assert!(outside.validate().is_err());
+        let mut no_password = Config::parse("[bridge]\naddr = \"127.0.0.1:9999\"").unwrap();
+        assert!(no_password.validate().unwrap_err().contains("mot de passe"));
+    }
+
+    #[test]
+    fn test_env_and_options() {
+        let env = [("TP9_IP_RATE", "5"), ("TP9_ROOMS", "accueil, jeux"), ("TP9_BRIDGE", "127.0.0.1:9999"), ("TP9_BRIDGE_PASSWORD", "s3cret"), ("PATH", "/bin")];
+        let env = env.map(|(name, value)| (name.to_string(), value.to_string()));
+        let options = [("bridge-room", "jeux:general"), ("ip-rate", "8")].map(|(key, value)| (key.to_string(), value.to_string()));
+        let config: Config = config_commun::merge(None, env, options).unwrap();
+        assert_eq!(config.ip_rate, 8); // L'option l'emporte sur la variable
+        assert_eq!(config.rooms, ["accueil", "jeux"]);
+        let bridge = config.bridge.unwrap();
+        assert_eq!((bridge.addr.as_str(), bridge.ws_room.as_str(), bridge.scp_room.as_str()), ("127.0.0.1:9999", "jeux", "general"));
+
+        let half_tls = [("tls-cert".to_string(), "cert.pem".to_string())];
+        assert!(config_commun::merge::<Config>(None, Vec::new(), half_tls).is_err());
+        let error = config_commun::merge::<Config>(None, [("TP9_HISTORY".to_string(), "beaucoup".to_string())], Vec::new()).unwrap_err();
+        assert!(error.to_string().contains("TP9_HISTORY"));
+    }
+}
@@ -0,0 +1,134 @@
+// src/http.rs
+// Requêtes HTTP arrivant sur le port WebSocket : les demandes de connexion WebSocket passent
+// au handshake, les autres reçoivent la page de test sur / et une erreur 404 ailleurs.
+// Le port de supervision répond de même à /health
+
+use std::io;
+use std::time::Duration;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::time::{sleep, timeout};
+
+use crate::metrics::Metrics;
+
+/// Page de test intégrée ; `{{WS_PATH}}` y est remplacé par le chemin WebSocket
+const PAGE: &str = include_str!("page.html");
+
+/// Taille maximale de l'en-tête d'une requête
+const MAX_HEAD_SIZE: usize = 8 * 1024;
+
+/// Attente de l'en-tête complet de la requête
+const HEAD_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Ce que demande une requête HTTP
+#[derive(Debug, PartialEq)]
+pub enum Request {
+    /// Demande de connexion WebSocket, sur ce chemin
+    Upgrade(String),
+    /// Requête ordinaire : méthode et chemin
+    Page { method: String, path: String },
+}
+
+/// Méthode, chemin et demande de connexion WebSocket d'un en-tête de requête complet
+fn parse_head(head: &str) -> Option<Request> {
+    let mut lines = head.lines();
+    let mut request_line = lines.next()?.split_whitespace();
+    let (method, path) = (request_line.next()?, request_line.next()?);
+    let upgrade = lines.filter_map(|line| line.split_once(':')).any(|(name, value)| {
+        name.trim().eq_ignore_ascii_case("upgrade") && value.trim().eq_ignore_ascii_case("websocket")
+    });
+    Some(match upgrade {
+        true => Request::Upgrade(path.to_string()),
+        false => Request::Page { method: method.to_string(), path: path.to_string() },
+    })
+}
+
+/// Lit l'en-tête de la requête sans le consommer, pour laisser le handshake WebSocket le relire
+pub async fn peek_request(stream: &TcpStream) -> io::Result<Request> {
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+    let mut buffer = vec![0u8; MAX_HEAD_SIZE];
+    let peek = async {
+        loop {
+            let read = stream.peek(&mut buffer).await?;
+            if read == 0 {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            if let Some(end) = buffer[..read].windows(4).position(|window| window == b"\r\n\r\n") {
+                let head = std::str::from_utf8(&buffer[..end]).map_err(|_| invalid("en-tête HTTP non UTF-8"))?;
+                return parse_head(head).ok_or_else(|| invalid("requête HTTP invalide"));
+            }
+            if read == buffer.len() {
+                return Err(invalid("en-tête HTTP trop grand"));
+            }
+            // `peek` rend aussitôt les données déjà là : on attend la suite
+            sleep(Duration::from_millis(10)).await;
+        }
+    };
+    timeout(HEAD_TIMEOUT, peek).await.map_err(|_| io::Error::from(io::ErrorKind::TimedOut))?
+}
+
+/// Répond à une requête ordinaire puis ferme la connexion
+pub async fn answer(stream: TcpStream, method: &str, path: &str, ws_path: &str) -> io::Result<()> {
+    let (status, content_type, body) = match (method, path) {
+        ("GET", "/" | "/index.html") => ("200 OK", "text/html; charset=utf-8", PAGE.replace("{{WS_PATH}}", ws_path)),
+        _ => not_found(method, path),
+    };
+    respond(stream, status, content_type, body).await
+}
+
+/// Répond à une requête du port de supervision : l'état du serveur en JSON sur /health
+pub async fn answer_health(stream: TcpStream, method: &str, path: &str, metrics: &Metrics) -> io::Result<()> {
+    let (status, content_type, body) = match (method, path) {
+        ("GET", "/health") => {
+            let body = serde_json::to_string(&metrics.health()).map_err(io::Error::other)?;
+            ("200 OK", "application/json", body + "\n")
+        }
+        _ => not_found(method, path),
+    };
+    respond(stream, status, content_type, body).await
+}
+
+/// Réponse à un chemin inconnu ou à une méthode autre que GET
+fn not_found(method: &str, path: &str) -> (&'static str, &'static str, String) {
+    match method {
+        "GET" => ("404 Not Found", "text/plain; charset=utf-8", format!("{} introuvable\n", path)),
+        _ => ("405 Method Not Allowed", "text/plain; charset=utf-8", format!("méthode {} non prise en charge\n", method)),
+    }
+}
+
+async fn respond(mut stream: TcpStream, status: &str, content_type: &str, body: String) -> io::Result<()> {
+    // L'en-tête a seulement été lu par `peek_request` : on le consomme avant de répondre
+    let mut head = Vec::new();
+    while !head.ends_with(b"\r\n\r\n") && head.len() < MAX_HEAD_SIZE {
+        let mut byte = [0u8; 1];
+        if stream.read(&mut byte).await? == 0 {
+            break;
+        }
+        head.push(byte[0]);
+    }
+
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_head() {
+        let upgrade = "GET /ws HTTP/1.1\r\nHost: localhost\r\nConnection: Upgrade\r\nUpgrade: WebSocket";
+        assert_eq!(parse_head(upgrade), Some(Request::Upgrade("/ws".to_string())));
+        let page = "GET / HTTP/1.1\r\nHost: localhost";
+        assert_eq!(parse_head(page), Some(Request::Page { method: "GET".to_string(), path: "/".to_string() }));
+        assert_eq!(parse_head("GET"), None);
+    }
+}
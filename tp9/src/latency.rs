@@ -0,0 +1,90 @@
+// src/latency.rs
+// Mesure de la latence côté client : chaque ping porte un horodatage que le serveur renvoie tel quel
+
+use std::collections::{HashSet, VecDeque};
+use std::time::{Duration, Instant};
+
+/// Aller-retours mesurés, les plus récents seulement
+pub struct Latency {
+    /// Origine des horodatages, en millisecondes
+    start: Instant,
+    samples: VecDeque<Duration>,
+    max_samples: usize,
+    /// Pings demandés par l'utilisateur, dont la réponse est affichée
+    announced: HashSet<u64>,
+}
+
+/// Résumé des mesures gardées
+#[derive(Debug, PartialEq)]
+pub struct Stats {
+    pub count: usize,
+    pub last: Duration,
+    pub min: Duration,
+    pub average: Duration,
+    pub max: Duration,
+}
+
+impl Latency {
+    pub fn new(max_samples: usize) -> Self {
+        Self { start: Instant::now(), samples: VecDeque::new(), max_samples, announced: HashSet::new() }
+    }
+
+    /// Horodatage d'un nouveau ping ; `announce` : la réponse sera affichée
+    pub fn stamp(&mut self, announce: bool) -> u64 {
+        let stamp = self.start.elapsed().as_millis() as u64;
+        if announce {
+            self.announced.insert(stamp);
+        }
+        stamp
+    }
+
+    /// Réponse à un ping : son aller-retour, et s'il faut l'afficher ; `None` pour un horodatage venu du futur
+    pub fn answer(&mut self, stamp: u64) -> Option<(Duration, bool)> {
+        let now = self.start.elapsed().as_millis() as u64;
+        let rtt = Duration::from_millis(now.checked_sub(stamp)?);
+        self.record(rtt);
+        Some((rtt, self.announced.remove(&stamp)))
+    }
+
+    pub fn record(&mut self, rtt: Duration) {
+        if self.samples.len() == self.max_samples {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(rtt);
+    }
+
+    pub fn stats(&self) -> Option<Stats> {
+        let last = *self.samples.back()?;
+        let total: Duration = self.samples.iter().sum();
+        Some(Stats {
+            count: self.samples.len(),
+            last,
+            min: *self.samples.iter().min()?,
+            average: total / self.samples.len() as u32,
+            max: *self.samples.iter().max()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stats() {
+        let mut latency = Latency::new(3);
+        assert_eq!(latency.stats(), None);
+        for ms in [40, 10, 20, 30] {
+            latency.record(Duration::from_millis(ms));
+        }
+        // La plus ancienne mesure (40 ms) est sortie
+        let ms = Duration::from_millis;
+        assert_eq!(latency.stats(), Some(Stats { count: 3, last: ms(30), min: ms(10), average: ms(20), max: ms(30) }));
+
+        let stamp = latency.stamp(true);
+        let (_, announce) = latency.answer(stamp).unwrap();
+        assert!(announce);
+        assert_eq!(latency.answer(stamp).map(|(_, announce)| announce), Some(false));
+        assert_eq!(latency.answer(u64::MAX), None);
+    }
+}
@@ -0,0 +1,13 @@
+// src/lib.rs
+// Code partagé par le serveur et le client WebSocket
+
+pub mod auth;
+pub mod bridge;
+pub mod config;
+pub mod http;
+pub mod latency;
+pub mod limits;
+pub mod metrics;
+pub mod protocol;
+pub mod rooms;
+pub mod topics;
@@ -0,0 +1,60 @@
+// src/limits.rs
+// Limite des nouvelles connexions par adresse IP, sur une fenêtre glissante
+
+use std::collections::{HashMap, VecDeque};
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+
+/// Au plus `max` nouvelles connexions par adresse IP pendant `window`
+#[derive(Debug)]
+pub struct RateLimiter {
+    max: usize,
+    window: Duration,
+    /// Heures des connexions acceptées récemment, par adresse
+    recent: HashMap<IpAddr, VecDeque<Instant>>,
+}
+
+impl RateLimiter {
+    pub fn new(max: usize, window: Duration) -> Self {
+        Self { max, window, recent: HashMap::new() }
+    }
+
+    /// Enregistre une connexion de `ip` à l'heure `now` ; false si l'adresse a dépassé sa limite.
+    /// Une connexion refusée ne compte pas
+    pub fn allow(&mut self, ip: IpAddr, now: Instant) -> bool {
+        let window = self.window;
+        // Oublie les adresses silencieuses depuis une fenêtre entière
+        self.recent.retain(|_, times| times.back().is_some_and(|last| now.duration_since(*last) < window));
+        let times = self.recent.entry(ip).or_default();
+        while times.front().is_some_and(|first| now.duration_since(*first) >= window) {
+            times.pop_front();
+        }
+        if times.len() >= self.max {
+            return false;
+        }
+        times.push_back(now);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rate_limiter() {
+        let mut limiter = RateLimiter::new(2, Duration::from_secs(10));
+        let (alice, bob) = ("127.0.0.1".parse().unwrap(), "127.0.0.2".parse().unwrap());
+        let start = Instant::now();
+        assert!(limiter.allow(alice, start));
+        assert!(limiter.allow(alice, start + Duration::from_secs(1)));
+        assert!(!limiter.allow(alice, start + Duration::from_secs(2)));
+        // Chaque adresse a sa propre limite
+        assert!(limiter.allow(bob, start + Duration::from_secs(2)));
+
+        // La première connexion sort de la fenêtre : une place se libère
+        assert!(limiter.allow(alice, start + Duration::from_secs(10)));
+        assert!(!limiter.allow(alice, start + Duration::from_secs(10)));
+        assert!(limiter.allow(alice, start + Duration::from_secs(30)));
+    }
+}
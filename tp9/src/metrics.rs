@@ -0,0 +1,103 @@
+// src/metrics.rs
+// Compteurs du serveur, mis à jour par les connexions et lus par l'adresse de supervision
+
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::Instant;
+
+use serde::Serialize;
+
+/// Compteurs partagés par toutes les connexions
+#[derive(Debug)]
+pub struct Metrics {
+    started: Instant,
+    clients: AtomicUsize,
+    messages_relayed: AtomicU64,
+    bytes_in: AtomicU64,
+    bytes_out: AtomicU64,
+}
+
+/// État du serveur à un instant, tel que le renvoie `/health`
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Health {
+    pub status: &'static str,
+    pub uptime_secs: u64,
+    /// Clients WebSocket connectés (après le handshake et l'authentification)
+    pub clients: usize,
+    /// Messages de discussion et trames binaires relayés aux salons
+    pub messages_relayed: u64,
+    pub bytes_in: u64,
+    pub bytes_out: u64,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            clients: AtomicUsize::new(0),
+            messages_relayed: AtomicU64::new(0),
+            bytes_in: AtomicU64::new(0),
+            bytes_out: AtomicU64::new(0),
+        }
+    }
+
+    pub fn client_connected(&self) {
+        self.clients.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn client_disconnected(&self) {
+        self.clients.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    pub fn message_relayed(&self) {
+        self.messages_relayed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Octets reçus des clients (contenu des trames)
+    pub fn received(&self, bytes: usize) {
+        self.bytes_in.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    /// Octets envoyés aux clients (contenu des trames)
+    pub fn sent(&self, bytes: usize) {
+        self.bytes_out.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub fn health(&self) -> Health {
+        Health {
+            status: "ok",
+            uptime_secs: self.started.elapsed().as_secs(),
+            clients: self.clients.load(Ordering::Relaxed),
+            messages_relayed: self.messages_relayed.load(Ordering::Relaxed),
+            bytes_in: self.bytes_in.load(Ordering::Relaxed),
+            bytes_out: self.bytes_out.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_health() {
+        let metrics = Metrics::new();
+        metrics.client_connected();
+        metrics.client_connected();
+        metrics.client_disconnected();
+        metrics.message_relayed();
+        metrics.received(12);
+        metrics.sent(30);
+        metrics.sent(4);
+        let health = metrics.health();
+        assert_eq!(health, Health { status: "ok", uptime_secs: 0, clients: 1, messages_relayed: 1, bytes_in: 12, bytes_out: 34 });
+        let json: serde_json::Value = serde_json::to_value(&health).unwrap();
+        assert_eq!(json["clients"], 1);
+        assert_eq!(json["status"], "ok");
+    }
+}
@@ -0,0 +1,189 @@
+// src/protocol.rs
+// Messages échangés entre le serveur et le client : JSON dans des trames texte WebSocket, fichiers
+// et autres données dans des trames binaires précédées d'un en-tête
+
+use serde::{Deserialize, Serialize};
+use tungstenite::Message;
+
+/// Code de la trame Close envoyée par le serveur pour un jeton absent ou refusé
+pub const CLOSE_UNAUTHORIZED: u16 = 4401;
+
+/// Message applicatif ; le champ `type` du JSON indique la variante
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum WsMessage {
+    /// Message de discussion ; `from` est fixé par le serveur
+    Chat { from: String, text: String },
+    /// Le client annonce son nom (s'il n'est pas vide) et entre dans `room` ; le serveur l'annonce
+    /// aux membres du salon, ou confirme seulement le nom sans salon
+    Join {
+        name: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        room: Option<String>,
+    },
+    /// Départ d'un salon, ou du serveur sans salon
+    Leave {
+        name: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        room: Option<String>,
+    },
+    /// Derniers messages d'un salon, envoyés à qui y entre
+    History { room: String, messages: Vec<WsMessage> },
+    /// Premier message d'un client qui n'a pas mis son jeton dans l'URL de connexion
+    Auth { token: String },
+    /// Abonnement aux publications d'un sujet ; le serveur confirme en renvoyant le message
+    Subscribe { topic: String },
+    /// Fin de l'abonnement ; le serveur confirme en renvoyant le message
+    Unsubscribe { topic: String },
+    /// Publication transmise à tous les abonnés du sujet, l'auteur compris s'il est abonné ; `from` est fixé par le serveur
+    Publish {
+        topic: String,
+        #[serde(default)]
+        from: String,
+        payload: String,
+    },
+    /// Annonce de l'opérateur du serveur, envoyée à tous les clients connectés
+    Announcement { text: String },
+    /// Renvoyé tel quel par le serveur ; l'horodatage du client permet d'en mesurer l'aller-retour
+    Ping {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        stamp: Option<u64>,
+    },
+    /// Requête refusée par le serveur
+    Error { message: String },
+}
+
+impl WsMessage {
+    /// Lire un message reçu dans une trame texte
+    pub fn parse(text: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(text)
+    }
+
+    /// Commandes en texte brut : `join:<salon>`, `msg:<texte>`, `subscribe:<sujet>`, `unsubscribe:<sujet>`
+    /// et `publish:<sujet>:<contenu>`
+    pub fn from_command(text: &str) -> Option<Self> {
+        if let Some(room) = text.strip_prefix("join:") {
+            let room = room.trim();
+            return (!room.is_empty()).then(|| WsMessage::Join { name: String::new(), room: Some(room.to_string()) });
+        }
+        let topic = |topic: &str| Some(topic.trim().to_string()).filter(|topic| !topic.is_empty());
+        if let Some(rest) = text.strip_prefix("subscribe:") {
+            return topic(rest).map(|topic| WsMessage::Subscribe { topic });
+        }
+        if let Some(rest) = text.strip_prefix("unsubscribe:") {
+            return topic(rest).map(|topic| WsMessage::Unsubscribe { topic });
+        }
+        if let Some(rest) = text.strip_prefix("publish:") {
+            let (name, payload) = rest.split_once(':')?;
+            return topic(name).map(|topic| WsMessage::Publish { topic, from: String::new(), payload: payload.to_string() });
+        }
+        let text = text.strip_prefix("msg:")?;
+        Some(WsMessage::Chat { from: String::new(), text: text.to_string() })
+    }
+
+    /// Trame texte WebSocket contenant le message
+    pub fn to_frame(&self) -> Message {
+        // La sérialisation d'un enum sans clé de map ne peut pas échouer
+        Message::Text(serde_json::to_string(self).expect("WsMessage sérialisable"))
+    }
+}
+
+/// Taille des morceaux d'un fichier, un par trame binaire
+pub const BLOB_CHUNK_SIZE: usize = 64 * 1024;
+
+/// En-tête d'une trame binaire : 4 octets de longueur (big-endian), cet en-tête en JSON, puis les données
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BlobHeader {
+    /// Expéditeur, fixé par le serveur
+    #[serde(default)]
+    pub from: String,
+    pub content_type: String,
+    /// Nom du fichier, s'il s'agit d'un fichier
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Position des données de la trame dans le fichier
+    #[serde(default)]
+    pub offset: u64,
+    /// Taille totale du fichier
+    pub total: u64,
+}
+
+impl BlobHeader {
+    /// Trame binaire WebSocket contenant l'en-tête puis `data`
+    pub fn to_frame(&self, data: &[u8]) -> Message {
+        let header = serde_json::to_vec(self).expect("BlobHeader sérialisable");
+        let mut frame = Vec::with_capacity(4 + header.len() + data.len());
+        frame.extend_from_slice(&(header.len() as u32).to_be_bytes());
+        frame.extend_from_slice(&header);
+        frame.extend_from_slice(data);
+        Message::Binary(frame)
+    }
+
+    /// Séparer l'en-tête et les données d'une trame binaire reçue
+    pub fn parse(frame: &[u8]) -> Result<(Self, &[u8]), String> {
+        let (length, rest) = frame.split_first_chunk::<4>().ok_or("trame binaire trop courte")?;
+        let length = u32::from_be_bytes(*length) as usize;
+        if rest.len() < length {
+            return Err("en-tête de trame binaire tronqué".to_string());
+        }
+        let (header, data) = rest.split_at(length);
+        let header = serde_json::from_slice(header).map_err(|e| format!("en-tête de trame binaire invalide: {}", e))?;
+        Ok((header, data))
+    }
+
+    /// Dernier morceau du fichier
+    pub fn is_last(&self, data: &[u8]) -> bool {
+        self.offset + data.len() as u64 >= self.total
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json() {
+        let message = WsMessage::Chat { from: "alice".to_string(), text: "bonjour".to_string() };
+        let Message::Text(json) = message.to_frame() else { panic!("trame texte attendue") };
+        assert_eq!(json, r#"{"type":"chat","from":"alice","text":"bonjour"}"#);
+        assert_eq!(WsMessage::parse(&json).unwrap(), message);
+        assert_eq!(WsMessage::parse(r#"{"type":"ping"}"#).unwrap(), WsMessage::Ping { stamp: None });
+        let ping = WsMessage::Ping { stamp: Some(1234) };
+        assert_eq!(ping.to_frame(), Message::Text(r#"{"type":"ping","stamp":1234}"#.to_string()));
+        assert_eq!(WsMessage::parse(r#"{"type":"auth","token":"abc"}"#).unwrap(), WsMessage::Auth { token: "abc".to_string() });
+        assert!(WsMessage::parse("bonjour").is_err());
+
+        let join = WsMessage::Join { name: "bob".to_string(), room: None };
+        assert_eq!(WsMessage::parse(r#"{"type":"join","name":"bob"}"#).unwrap(), join);
+        assert_eq!(
+            WsMessage::from_command("join: salon"),
+            Some(WsMessage::Join { name: String::new(), room: Some("salon".to_string()) })
+        );
+        assert_eq!(WsMessage::from_command("msg:a:b"), Some(WsMessage::Chat { from: String::new(), text: "a:b".to_string() }));
+        assert_eq!(WsMessage::from_command("join:"), None);
+        assert_eq!(WsMessage::from_command("subscribe: meteo"), Some(WsMessage::Subscribe { topic: "meteo".to_string() }));
+        assert_eq!(
+            WsMessage::from_command("publish:meteo:{\"temp\":12}"),
+            Some(WsMessage::Publish { topic: "meteo".to_string(), from: String::new(), payload: "{\"temp\":12}".to_string() })
+        );
+        assert_eq!(WsMessage::from_command("publish:meteo"), None);
+        assert_eq!(WsMessage::from_command("unsubscribe:"), None);
+    }
+
+    #[test]
+    fn test_blob_frame() {
+        let header = BlobHeader {
+            from: String::new(),
+            content_type: "text/plain".to_string(),
+            name: Some("notes.txt".to_string()),
+            offset: 3,
+            total: 8,
+        };
+        let Message::Binary(frame) = header.to_frame(b"fin!!") else { panic!("trame binaire attendue") };
+        let (parsed, data) = BlobHeader::parse(&frame).unwrap();
+        assert_eq!((&parsed, data), (&header, &b"fin!!"[..]));
+        assert!(parsed.is_last(data));
+        assert!(BlobHeader::parse(&frame[..6]).is_err());
+        assert!(BlobHeader::parse(&[0, 0, 0, 2, b'{', b'}']).is_err());
+    }
+}
@@ -0,0 +1,203 @@
+// src/rooms.rs
+// Salons du serveur : chaque connexion est dans au plus un salon et ne reçoit que ses messages.
+// Les derniers messages de chaque salon sont gardés pour ceux qui arrivent ensuite
+
+use std::collections::{HashMap, VecDeque};
+use std::net::SocketAddr;
+use std::sync::Mutex;
+
+use tokio::sync::mpsc::UnboundedSender;
+use tungstenite::Message;
+
+use crate::protocol::WsMessage;
+
+/// Une connexion est identifiée par l'adresse du client
+pub type ConnectionId = SocketAddr;
+
+#[derive(Default)]
+struct Registry {
+    /// salon -> connexions présentes, avec de quoi leur écrire
+    members: HashMap<String, HashMap<ConnectionId, UnboundedSender<Message>>>,
+    /// connexion -> son salon
+    room_of: HashMap<ConnectionId, String>,
+    /// salon -> derniers messages de discussion, gardés même quand le salon se vide
+    history: HashMap<String, VecDeque<WsMessage>>,
+}
+
+impl Registry {
+    fn remove(&mut self, id: ConnectionId) -> Option<String> {
+        let room = self.room_of.remove(&id)?;
+        if let Some(members) = self.members.get_mut(&room) {
+            members.remove(&id);
+            // Un salon vide disparaît
+            if members.is_empty() {
+                self.members.remove(&room);
+            }
+        }
+        Some(room)
+    }
+
+    /// Envoyer une trame aux connexions de `room` sauf `except` ; renvoie le nombre de destinataires
+    fn send(&self, room: &str, frame: Message, except: Option<ConnectionId>) -> usize {
+        let Some(members) = self.members.get(room) else {
+            return 0;
+        };
+        // Une connexion qui se ferme est retirée par sa propre tâche
+        members.iter()
+            .filter(|(id, _)| Some(**id) != except)
+            .filter(|(_, sender)| sender.send(frame.clone()).is_ok())
+            .count()
+    }
+}
+
+/// Registre des salons, partagé par toutes les connexions
+#[derive(Default)]
+pub struct Rooms {
+    registry: Mutex<Registry>,
+    /// Messages gardés par salon ; 0 : pas d'historique
+    history_size: usize,
+}
+
+impl Rooms {
+    pub fn new(history_size: usize) -> Self {
+        Self { registry: Mutex::default(), history_size }
+    }
+
+    /// Faire entrer la connexion dans `room`, en lui envoyant d'abord l'historique du salon ;
+    /// renvoie le salon quitté au passage
+    pub fn join(&self, room: &str, id: ConnectionId, sender: UnboundedSender<Message>) -> Option<String> {
+        let mut registry = self.registry.lock().unwrap();
+        let previous = registry.remove(id);
+        // Sous le verrou : aucun message du salon ne peut passer entre l'historique et l'entrée
+        if let Some(history) = registry.history.get(room).filter(|history| !history.is_empty()) {
+            let messages = history.iter().cloned().collect();
+            let _ = sender.send(WsMessage::History { room: room.to_string(), messages }.to_frame());
+        }
+        registry.members.entry(room.to_string()).or_default().insert(id, sender);
+        registry.room_of.insert(id, room.to_string());
+        previous
+    }
+
+    /// Retirer la connexion de son salon ; renvoie ce salon
+    pub fn leave(&self, id: ConnectionId) -> Option<String> {
+        self.registry.lock().unwrap().remove(id)
+    }
+
+    pub fn room_of(&self, id: ConnectionId) -> Option<String> {
+        self.registry.lock().unwrap().room_of.get(&id).cloned()
+    }
+
+    /// Envoyer `message` à toutes les connexions de `room` ; renvoie le nombre de destinataires.
+    /// Les messages de discussion entrent dans l'historique du salon
+    pub fn broadcast(&self, room: &str, message: &WsMessage) -> usize {
+        self.publish(room, message, None)
+    }
+
+    /// Comme `broadcast`, sans renvoyer le message à `except`, qui l'apporte d'ailleurs
+    pub fn broadcast_except(&self, room: &str, message: &WsMessage, except: ConnectionId) -> usize {
+        self.publish(room, message, Some(except))
+    }
+
+    fn publish(&self, room: &str, message: &WsMessage, except: Option<ConnectionId>) -> usize {
+        // Historique et envoi sous le même verrou, comme dans `join` : une connexion qui entre reçoit
+        // le message soit dans l'historique, soit en direct, jamais les deux
+        let mut registry = self.registry.lock().unwrap();
+        if self.history_size > 0 && matches!(message, WsMessage::Chat { .. }) {
+            let history = registry.history.entry(room.to_string()).or_default();
+            if history.len() == self.history_size {
+                history.pop_front();
+            }
+            history.push_back(message.clone());
+        }
+        registry.send(room, message.to_frame(), except)
+    }
+
+    /// Envoyer une trame aux connexions de `room` sauf `except`, l'expéditeur ; renvoie le nombre de destinataires
+    pub fn relay(&self, room: &str, frame: Message, except: ConnectionId) -> usize {
+        self.registry.lock().unwrap().send(room, frame, Some(except))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::mpsc;
+
+    #[test]
+    fn test_broadcast_in_room() {
+        let rooms = Rooms::new(0);
+        let (alice, bob) = ("127.0.0.1:1".parse().unwrap(), "127.0.0.1:2".parse().unwrap());
+        let (tx_alice, mut rx_alice) = mpsc::unbounded_channel();
+        let (tx_bob, mut rx_bob) = mpsc::unbounded_channel();
+        assert_eq!(rooms.join("general", alice, tx_alice.clone()), None);
+        rooms.join("jeux", bob, tx_bob);
+
+        let ping = WsMessage::Ping { stamp: None };
+        assert_eq!(rooms.broadcast("general", &ping), 1);
+        assert_eq!(rx_alice.try_recv().unwrap(), ping.to_frame());
+        assert!(rx_bob.try_recv().is_err());
+
+        // Changer de salon quitte le précédent, qui disparaît une fois vide
+        assert_eq!(rooms.join("jeux", alice, tx_alice), Some("general".to_string()));
+        assert_eq!(rooms.broadcast("general", &ping), 0);
+        assert_eq!(rooms.broadcast("jeux", &ping), 2);
+        assert_eq!(rooms.relay("jeux", ping.to_frame(), alice), 1);
+        assert_eq!(rooms.leave(bob), Some("jeux".to_string()));
+        assert_eq!(rooms.room_of(bob), None);
+        assert_eq!(rooms.room_of(alice), Some("jeux".to_string()));
+    }
+
+    #[test]
+    fn test_history() {
+        let rooms = Rooms::new(2);
+        let (alice, bob) = ("127.0.0.1:1".parse().unwrap(), "127.0.0.1:2".parse().unwrap());
+        let (tx_alice, _rx_alice) = mpsc::unbounded_channel();
+        let (tx_bob, mut rx_bob) = mpsc::unbounded_channel();
+        rooms.join("general", alice, tx_alice);
+        let chat = |text: &str| WsMessage::Chat { from: "alice".to_string(), text: text.to_string() };
+        for text in ["un", "deux", "trois"] {
+            rooms.broadcast("general", &chat(text));
+        }
+        rooms.broadcast("general", &WsMessage::Ping { stamp: None });
+
+        // Seuls les deux derniers messages de discussion restent, envoyés avant tout le reste
+        rooms.join("general", bob, tx_bob.clone());
+        let history = WsMessage::History { room: "general".to_string(), messages: vec![chat("deux"), chat("trois")] };
+        assert_eq!(rx_bob.try_recv().unwrap(), history.to_frame());
+        assert!(rx_bob.try_recv().is_err());
+
+        // Pas d'historique pour un salon sans message
+        rooms.join("jeux", bob, tx_bob);
+        assert!(rx_bob.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_join_during_publishes() {
+        let rooms = std::sync::Arc::new(Rooms::new(1000));
+        let chat = |i: usize| WsMessage::Chat { from: "alice".to_string(), text: i.to_string() };
+        let publisher = {
+            let rooms = rooms.clone();
+            std::thread::spawn(move || {
+                for i in 0..500 {
+                    rooms.broadcast("general", &chat(i));
+                }
+            })
+        };
+        // Bob entre pendant que les messages arrivent : chacun lui parvient une fois, dans l'ordre
+        while rooms.registry.lock().unwrap().history.get("general").is_none_or(|history| history.len() < 100) {
+            std::thread::yield_now();
+        }
+        let (tx_bob, mut rx_bob) = mpsc::unbounded_channel();
+        rooms.join("general", "127.0.0.1:2".parse().unwrap(), tx_bob);
+        publisher.join().unwrap();
+
+        let mut received = Vec::new();
+        while let Ok(frame) = rx_bob.try_recv() {
+            match WsMessage::parse(frame.to_text().unwrap()).unwrap() {
+                WsMessage::History { messages, .. } => received.extend(messages),
+                message => received.push(message),
+            }
+        }
+        assert_eq!(received, (0..500).map(chat).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,92 @@
+// src/topics.rs
+// Sujets de publication : indépendamment des salons, une connexion s'abonne à autant de sujets qu'elle
+// veut et reçoit tout ce qui y est publié. Le serveur sert ainsi de petit bus de messages
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use tokio::sync::mpsc::UnboundedSender;
+use tungstenite::Message;
+
+use crate::protocol::WsMessage;
+use crate::rooms::ConnectionId;
+
+/// Registre des abonnements, partagé par toutes les connexions
+#[derive(Default)]
+pub struct Topics {
+    /// sujet -> abonnés, avec de quoi leur écrire ; un sujet sans abonné disparaît
+    subscribers: Mutex<HashMap<String, HashMap<ConnectionId, UnboundedSender<Message>>>>,
+}
+
+impl Topics {
+    /// Abonner la connexion à `topic` ; false si elle l'était déjà
+    pub fn subscribe(&self, topic: &str, id: ConnectionId, sender: UnboundedSender<Message>) -> bool {
+        let mut subscribers = self.subscribers.lock().unwrap();
+        subscribers.entry(topic.to_string()).or_default().insert(id, sender).is_none()
+    }
+
+    /// Désabonner la connexion de `topic` ; false si elle n'y était pas abonnée
+    pub fn unsubscribe(&self, topic: &str, id: ConnectionId) -> bool {
+        let mut subscribers = self.subscribers.lock().unwrap();
+        let Some(topic_subscribers) = subscribers.get_mut(topic) else {
+            return false;
+        };
+        let removed = topic_subscribers.remove(&id).is_some();
+        if topic_subscribers.is_empty() {
+            subscribers.remove(topic);
+        }
+        removed
+    }
+
+    /// Désabonner la connexion de tous ses sujets, à son départ
+    pub fn unsubscribe_all(&self, id: ConnectionId) {
+        let mut subscribers = self.subscribers.lock().unwrap();
+        subscribers.retain(|_, topic_subscribers| {
+            topic_subscribers.remove(&id);
+            !topic_subscribers.is_empty()
+        });
+    }
+
+    /// Envoyer `message` aux abonnés de `topic` ; renvoie le nombre de destinataires
+    pub fn publish(&self, topic: &str, message: &WsMessage) -> usize {
+        let subscribers = self.subscribers.lock().unwrap();
+        let Some(topic_subscribers) = subscribers.get(topic) else {
+            return 0;
+        };
+        let frame = message.to_frame();
+        // Une connexion qui se ferme est désabonnée par sa propre tâche
+        topic_subscribers.values().filter(|sender| sender.send(frame.clone()).is_ok()).count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::mpsc;
+
+    #[test]
+    fn test_publish() {
+        let topics = Topics::default();
+        let (alice, bob) = ("127.0.0.1:1".parse().unwrap(), "127.0.0.1:2".parse().unwrap());
+        let (tx_alice, mut rx_alice) = mpsc::unbounded_channel();
+        let (tx_bob, mut rx_bob) = mpsc::unbounded_channel();
+        assert!(topics.subscribe("meteo", alice, tx_alice.clone()));
+        assert!(!topics.subscribe("meteo", alice, tx_alice.clone()));
+        topics.subscribe("meteo", bob, tx_bob.clone());
+        topics.subscribe("bourse", bob, tx_bob);
+
+        let message = WsMessage::Publish { topic: "meteo".to_string(), from: "carol".to_string(), payload: "soleil".to_string() };
+        assert_eq!(topics.publish("meteo", &message), 2);
+        assert_eq!(rx_alice.try_recv().unwrap(), message.to_frame());
+        assert_eq!(rx_bob.try_recv().unwrap(), message.to_frame());
+
+        assert!(topics.unsubscribe("meteo", alice));
+        assert!(!topics.unsubscribe("meteo", alice));
+        assert_eq!(topics.publish("meteo", &message), 1);
+        // Au départ de bob, plus personne n'écoute
+        topics.unsubscribe_all(bob);
+        assert_eq!(topics.publish("meteo", &message), 0);
+        assert_eq!(topics.publish("bourse", &message), 0);
+        assert!(rx_alice.try_recv().is_err());
+    }
+}
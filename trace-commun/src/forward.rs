@@ -0,0 +1,185 @@
+// src/forward.rs
+// Envoi des événements au serveur de journalisation (tp3) par un thread dédié, qui s'y reconnecte au besoin ;
+// si le serveur ne suit pas, les événements en trop sont perdus plutôt que de ralentir le service
+
+use std::io::{self, Write};
+use std::net::TcpStream;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, SyncSender};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread;
+use std::time::Duration;
+
+use tracing_subscriber::fmt::MakeWriter;
+
+/// Événements en attente d'envoi ; au-delà, les nouveaux sont perdus
+pub const FORWARD_BUFFER: usize = 1024;
+
+/// Délai entre deux tentatives de connexion au serveur de journalisation
+const RETRY: Duration = Duration::from_secs(5);
+
+/// Temps laissé à l'envoi des derniers événements à la fermeture
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Destination des événements JSON, pour la couche de `tracing_subscriber` qui les met en forme
+#[derive(Debug, Clone)]
+pub struct Forwarder {
+    service: String,
+    lines: Arc<Mutex<Option<SyncSender<String>>>>,
+    finished: Arc<Mutex<Option<Receiver<()>>>>,
+}
+
+impl Forwarder {
+    /// Démarrer l'envoi vers `addr` ; chaque ligne reçoit un champ `service`
+    pub fn connect(addr: String, service: String) -> Self {
+        let (tx, rx) = mpsc::sync_channel(FORWARD_BUFFER);
+        let (done_tx, done_rx) = mpsc::channel();
+        thread::spawn(move || {
+            forward(&addr, rx);
+            let _ = done_tx.send(());
+        });
+        Self { service, lines: Arc::new(Mutex::new(Some(tx))), finished: Arc::new(Mutex::new(Some(done_rx))) }
+    }
+
+    /// Ajouter le nom du service à une ligne JSON et la confier au thread d'envoi
+    fn send(&self, line: &str) {
+        let line = line.trim_end();
+        let Some(fields) = line.strip_prefix('{') else {
+            return;
+        };
+        let service = serde_json::to_string(&self.service).unwrap_or_default();
+        let line = format!("{{\"service\":{},{}", service, fields);
+        if let Some(lines) = self.lines.lock().unwrap_or_else(PoisonError::into_inner).as_ref() {
+            let _ = lines.try_send(line); // Canal plein : le serveur de journalisation ne suit pas, l'événement est perdu
+        }
+    }
+
+    /// Cesser d'accepter des événements et laisser un peu de temps à l'envoi de ceux en attente
+    pub fn close(&self) {
+        self.lines.lock().unwrap_or_else(PoisonError::into_inner).take();
+        if let Some(finished) = self.finished.lock().unwrap_or_else(PoisonError::into_inner).take() {
+            if let Err(RecvTimeoutError::Timeout) = finished.recv_timeout(CLOSE_TIMEOUT) {
+                eprintln!("Journal : événements non envoyés au serveur de journalisation");
+            }
+        }
+    }
+}
+
+/// Écriture d'un événement : la couche l'écrit en une ou plusieurs fois, il part à la fin
+pub struct ForwardLine<'a> {
+    forwarder: &'a Forwarder,
+    buffer: Vec<u8>,
+}
+
+impl Write for ForwardLine<'_> {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(bytes);
+        Ok(bytes.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for ForwardLine<'_> {
+    fn drop(&mut self) {
+        if let Ok(line) = std::str::from_utf8(&self.buffer) {
+            self.forwarder.send(line);
+        }
+    }
+}
+
+impl<'a> MakeWriter<'a> for Forwarder {
+    type Writer = ForwardLine<'a>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        ForwardLine { forwarder: self, buffer: Vec::new() }
+    }
+}
+
+/// Transmettre les lignes à `addr` jusqu'à la fermeture du canal, puis dire au revoir (`quit`)
+fn forward(addr: &str, lines: Receiver<String>) {
+    let mut pending: Option<String> = None; // Ligne dont l'envoi a échoué, renvoyée après reconnexion
+    let mut reported = false;
+    loop {
+        let mut stream = match TcpStream::connect(addr) {
+            Ok(stream) => {
+                reported = false;
+                stream
+            }
+            Err(e) => {
+                if !reported {
+                    eprintln!("Serveur de journalisation {} injoignable : {} (nouvel essai toutes les {} s)", addr, e, RETRY.as_secs());
+                    reported = true;
+                }
+                // Fermé pendant l'attente : inutile d'insister
+                if let Err(RecvTimeoutError::Disconnected) = wait(&lines, &mut pending) {
+                    return;
+                }
+                continue;
+            }
+        };
+        loop {
+            let line = match pending.take() {
+                Some(line) => line,
+                None => match lines.recv() {
+                    Ok(line) => line,
+                    Err(_) => {
+                        let _ = stream.write_all(b"quit\n");
+                        return;
+                    }
+                },
+            };
+            if let Err(e) = stream.write_all(format!("{}\n", line).as_bytes()) {
+                eprintln!("Connexion au serveur de journalisation {} perdue : {}", addr, e);
+                pending = Some(line);
+                break;
+            }
+        }
+    }
+}
+
+/// Attendre avant une nouvelle connexion, en gardant la première ligne arrivée entre-temps
+fn wait(lines: &Receiver<String>, pending: &mut Option<String>) -> Result<(), RecvTimeoutError> {
+    if pending.is_some() {
+        thread::sleep(RETRY);
+        return Ok(());
+    }
+    match lines.recv_timeout(RETRY) {
+        Ok(line) => {
+            *pending = Some(line);
+            Ok(())
+        }
+        Err(RecvTimeoutError::Timeout) => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader};
+    use std::net::TcpListener;
+
+    #[test]
+    fn test_forward_lines() {
+        // Un serveur de journalisation comme tp3 : des lignes, puis quit
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let received = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            BufReader::new(stream).lines().map(Result::unwrap).collect::<Vec<_>>()
+        });
+
+        let forwarder = Forwarder::connect(addr, "tp9".to_string());
+        let mut line = forwarder.make_writer();
+        line.write_all(b"{\"level\":\"INFO\",").unwrap();
+        line.write_all(b"\"fields\":{\"message\":\"bonjour\"}}\n").unwrap();
+        drop(line);
+        forwarder.send("pas du JSON");
+        forwarder.close();
+
+        let received = received.join().unwrap();
+        assert_eq!(received, ["{\"service\":\"tp9\",\"level\":\"INFO\",\"fields\":{\"message\":\"bonjour\"}}", "quit"]);
+    }
+}
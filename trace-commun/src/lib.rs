@@ -0,0 +1,137 @@
+// src/lib.rs
+// Journalisation commune aux services des TP : filtrage par niveaux (RUST_LOG), sortie texte ou JSON,
+// et envoi facultatif des événements au serveur de journalisation (tp3), une ligne JSON chacun
+
+mod forward;
+
+use std::env;
+use std::io::{self, IsTerminal};
+use std::str::FromStr;
+
+use tracing_subscriber::fmt::writer::MakeWriterExt;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{EnvFilter, Layer};
+
+pub use forward::Forwarder;
+pub use tracing;
+
+/// Filtre quand RUST_LOG n'est pas défini
+pub const DEFAULT_FILTER: &str = "info";
+
+/// Présentation des événements sur la console
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Format {
+    /// Une ligne lisible par événement
+    #[default]
+    Pretty,
+    /// Une ligne JSON par événement
+    Json,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "pretty" | "texte" => Ok(Format::Pretty),
+            "json" => Ok(Format::Json),
+            other => Err(format!("format de journal inconnu : {} (pretty ou json)", other)),
+        }
+    }
+}
+
+/// Réglages de la journalisation d'un service
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogOptions {
+    /// Nom du service, ajouté aux événements envoyés au serveur de journalisation
+    pub service: String,
+    /// Directives de filtrage, comme RUST_LOG (`info`, `tp9=debug,tungstenite=warn`...)
+    pub filter: String,
+    pub format: Format,
+    /// Serveur de journalisation (tp3) qui reçoit aussi les événements
+    pub forward: Option<String>,
+}
+
+impl LogOptions {
+    /// Réglages par défaut : niveau info, texte sur la console, pas d'envoi
+    pub fn new(service: impl Into<String>) -> Self {
+        Self { service: service.into(), filter: DEFAULT_FILTER.to_string(), format: Format::Pretty, forward: None }
+    }
+
+    /// Réglages lus dans l'environnement : RUST_LOG, LOG_FORMAT (pretty ou json) et LOG_SERVER (adresse du tp3)
+    pub fn from_env(service: impl Into<String>) -> Result<Self, String> {
+        let mut options = Self::new(service);
+        if let Ok(filter) = env::var("RUST_LOG") {
+            options.filter = filter;
+        }
+        if let Ok(format) = env::var("LOG_FORMAT") {
+            options.format = format.parse().map_err(|e| format!("LOG_FORMAT : {}", e))?;
+        }
+        options.forward = env::var("LOG_SERVER").ok().filter(|addr| !addr.trim().is_empty());
+        Ok(options)
+    }
+}
+
+/// Journalisation active ; à garder jusqu'à la fin du programme pour que les derniers événements partent
+#[must_use = "les événements en attente sont perdus si la garde est abandonnée tout de suite"]
+pub struct LogGuard {
+    forwarder: Option<Forwarder>,
+}
+
+impl Drop for LogGuard {
+    fn drop(&mut self) {
+        if let Some(forwarder) = self.forwarder.take() {
+            forwarder.close();
+        }
+    }
+}
+
+/// Installer la journalisation du programme ; les avertissements et erreurs vont sur la sortie d'erreur
+pub fn init(options: &LogOptions) -> Result<LogGuard, String> {
+    let filter = EnvFilter::try_new(&options.filter).map_err(|e| format!("filtre de journal invalide ({}) : {}", options.filter, e))?;
+    let console = io::stderr.with_max_level(tracing::Level::WARN).or_else(io::stdout);
+    let console = match options.format {
+        // Couleurs seulement sur un terminal, pas dans un fichier ou un tube
+        Format::Pretty => tracing_subscriber::fmt::layer().with_ansi(io::stdout().is_terminal()).with_writer(console).boxed(),
+        Format::Json => tracing_subscriber::fmt::layer().json().with_writer(console).boxed(),
+    };
+    let forwarder = options.forward.as_ref().map(|addr| Forwarder::connect(addr.clone(), options.service.clone()));
+    let remote = forwarder.clone().map(|forwarder| tracing_subscriber::fmt::layer().json().with_ansi(false).with_writer(forwarder));
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(console)
+        .with(remote)
+        .try_init()
+        .map_err(|e| format!("journalisation déjà installée : {}", e))?;
+    Ok(LogGuard { forwarder })
+}
+
+/// `init` avec les réglages de l'environnement ; en cas d'erreur, le message est affiché et la journalisation
+/// par défaut est installée
+pub fn init_from_env(service: &str) -> LogGuard {
+    let options = LogOptions::from_env(service).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        LogOptions::new(service)
+    });
+    init(&options).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        LogGuard { forwarder: None }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_options() {
+        assert_eq!("JSON".parse::<Format>(), Ok(Format::Json));
+        assert_eq!("pretty".parse::<Format>(), Ok(Format::Pretty));
+        assert!("xml".parse::<Format>().is_err());
+
+        let options = LogOptions::new("tp9");
+        assert_eq!((options.filter.as_str(), options.format, options.forward), (DEFAULT_FILTER, Format::Pretty, None));
+        assert!(init(&LogOptions { filter: "tp9=plein".to_string(), ..LogOptions::new("tp9") }).is_err());
+    }
+}